@@ -0,0 +1,15 @@
+//! The C API of `serial` as a static and a shared library, `libserial_c`
+//!
+//! The `serial` crate itself keeps the default crate type, this crate only links its `ffi`
+//! module into libraries that C programs can use, with the declarations of
+//! `include/serial.h`:
+//!
+//! ```text
+//! $ cd capi && cargo build
+//! $ cc main.c -I../include -Ltarget -lserial_c
+//! ```
+
+extern crate serial;
+
+pub use serial::ffi::{serial_close, serial_configure, serial_open, serial_read};
+pub use serial::ffi::{serial_set_timeout, serial_strerror, serial_write};
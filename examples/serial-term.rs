@@ -0,0 +1,184 @@
+//! A minimal serial terminal
+//!
+//! ```text
+//! serial-term [--hex] [--echo] [--log FILE] DEVICE [BAUD,FRAME]
+//! ```
+//!
+//! `BAUD,FRAME` looks like `115200,8N1` (the default is `9600,8N1`). Type Ctrl-] to exit; the
+//! input is line buffered, so the line has to be entered.
+
+extern crate serial;
+
+use serial::interact::{Options, interact};
+use serial::trace::{DumpTracer, TracedPort};
+use serial::{BaudRate, SerialPort, Settings};
+use serial::{B1K2, B2K4, B4K8, B9K6, B19K2, B38K4, B57K6, B115K2, B230K4};
+use serial::{Data5, Data6, Data7, Data8, EvenParity, NoParity, OddParity, Stop1, Stop2};
+use std::default::Default;
+use std::io::{File, IoResult, ReadWrite, stdio};
+use std::os;
+
+/// Prints the output as hexadecimal bytes
+struct HexWriter<W> {
+    inner: W,
+}
+
+impl<W: Writer> Writer for HexWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        for &byte in buf.iter() {
+            try!(write!(self.inner, "{:02X}{}", byte, if byte == b'\n' { "\n" } else { " " }));
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+fn main() {
+    let args = os::args();
+    let mut hex = false;
+    let mut options: Options = Default::default();
+    let mut log = None;
+    let mut positional = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_slice() {
+            "--hex" => hex = true,
+            "--echo" => options.local_echo = true,
+            "--log" if i + 1 < args.len() => {
+                i += 1;
+                log = Some(Path::new(args[i].as_slice()));
+            },
+            arg => positional.push(arg.to_string()),
+        }
+        i += 1;
+    }
+
+    if positional.len() < 1 || positional.len() > 2 {
+        return usage(args[0].as_slice())
+    }
+
+    let settings = match positional.get(1) {
+        None => Default::default(),
+        Some(spec) => match parse_settings(spec.as_slice()) {
+            None => return usage(args[0].as_slice()),
+            Some(settings) => settings,
+        },
+    };
+
+    let device = Path::new(positional[0].as_slice());
+    let mut port = match SerialPort::open(&device, ReadWrite) {
+        Err(e) => return fail(format!("Couldn't open {} ({})", device.display(), e)),
+        Ok(port) => port,
+    };
+
+    match port.configure(&settings) {
+        Err(e) => return fail(format!("Couldn't configure {} ({})", device.display(), e)),
+        Ok(_) => {},
+    }
+
+    println!("Connected to {}, type Ctrl-] and Enter to exit", device.display());
+
+    let input = stdio::stdin_raw();
+    let result = match (log, hex) {
+        (None, false) => interact(&mut port, input, &mut stdio::stdout(), &options),
+        (None, true) => {
+            let mut output = HexWriter { inner: stdio::stdout() };
+            interact(&mut port, input, &mut output, &options)
+        },
+        (Some(path), hex) => {
+            let file = match File::create(&path) {
+                Err(e) => return fail(format!("Couldn't create {} ({})", path.display(), e)),
+                Ok(file) => file,
+            };
+            let mut port = TracedPort::new(port, DumpTracer::new(file));
+
+            if hex {
+                let mut output = HexWriter { inner: stdio::stdout() };
+                interact(&mut port, input, &mut output, &options)
+            } else {
+                interact(&mut port, input, &mut stdio::stdout(), &options)
+            }
+        },
+    };
+
+    match result {
+        Err(e) => fail(format!("Connection lost ({})", e)),
+        Ok(_) => println!(""),
+    }
+}
+
+/// Parses settings like `115200,8N1`
+fn parse_settings(spec: &str) -> Option<Settings> {
+    let mut parts = spec.split(',');
+    let baud_rate = match parts.next().and_then(|rate| from_str::<uint>(rate)) {
+        Some(rate) => match baud_rate(rate) {
+            None => return None,
+            Some(rate) => rate,
+        },
+        None => return None,
+    };
+
+    let mut settings = Settings { baud_rate: baud_rate, ..Default::default() };
+
+    match parts.next() {
+        None => {},
+        Some(frame) => {
+            let frame: Vec<char> = frame.chars().map(|c| c.to_uppercase()).collect();
+            if frame.len() != 3 {
+                return None
+            }
+
+            settings.data_bits = match frame[0] {
+                '5' => Data5,
+                '6' => Data6,
+                '7' => Data7,
+                '8' => Data8,
+                _ => return None,
+            };
+            settings.parity = match frame[1] {
+                'E' => EvenParity,
+                'N' => NoParity,
+                'O' => OddParity,
+                _ => return None,
+            };
+            settings.stop_bits = match frame[2] {
+                '1' => Stop1,
+                '2' => Stop2,
+                _ => return None,
+            };
+        },
+    }
+
+    if parts.next().is_some() { None } else { Some(settings) }
+}
+
+/// The usual baud rates, which exist on every platform
+fn baud_rate(rate: uint) -> Option<BaudRate> {
+    Some(match rate {
+        1200 => B1K2,
+        2400 => B2K4,
+        4800 => B4K8,
+        9600 => B9K6,
+        19200 => B19K2,
+        38400 => B38K4,
+        57600 => B57K6,
+        115200 => B115K2,
+        230400 => B230K4,
+        _ => return None,
+    })
+}
+
+fn fail(message: String) {
+    let _ = stdio::stderr().write_line(message.as_slice());
+    os::set_exit_status(1);
+}
+
+fn usage(program: &str) {
+    fail(format!("Usage: {} [--hex] [--echo] [--log FILE] DEVICE [BAUD,FRAME]\n\
+                  e.g. {} /dev/ttyUSB0 115200,8N1", program, program))
+}
@@ -0,0 +1,53 @@
+/* C API of serial.rs, `cargo build` in the `capi` directory builds libserial_c */
+
+#ifndef SERIAL_H
+#define SERIAL_H
+
+#include <stddef.h>
+#include <sys/types.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Return codes, the functions returning a count use the same negative codes */
+#define SERIAL_OK 0
+#define SERIAL_EINVAL -1     /* An argument is null or out of range */
+#define SERIAL_EIO -2        /* Any other input/output error */
+#define SERIAL_ETIMEDOUT -3  /* The read timeout elapsed without receiving data */
+#define SERIAL_EEOF -4       /* The device was closed or hung up */
+#define SERIAL_ENOENT -5     /* The device doesn't exist */
+#define SERIAL_EACCES -6     /* The device can't be opened with the current permissions */
+#define SERIAL_EPANIC -7     /* The library panicked, the port can still be closed */
+
+typedef struct serial_port serial_port;
+
+/* Opens `path` for reading and writing, and stores the port in `*port` */
+int serial_open(const char *path, serial_port **port);
+
+/* Applies e.g. 115200 8N1: `parity` is 'N', 'E', 'O', 'M' or 'S', `flow_control` is 'N' (none),
+ * 'H' (RTS/CTS) or 'S' (XON/XOFF). Non standard baud rates, and the mark and space parities, are
+ * only supported on Linux. */
+int serial_configure(serial_port *port, unsigned int baud_rate, unsigned int data_bits,
+                     char parity, unsigned int stop_bits, char flow_control);
+
+/* Changes the read timeout, a negative `timeout_ms` blocks indefinitely */
+int serial_set_timeout(serial_port *port, long timeout_ms);
+
+/* Reads up to `len` bytes, returns the number of bytes read */
+ssize_t serial_read(serial_port *port, void *buf, size_t len);
+
+/* Writes all the `len` bytes, returns `len` */
+ssize_t serial_write(serial_port *port, const void *buf, size_t len);
+
+/* Closes the port, null is ignored */
+void serial_close(serial_port *port);
+
+/* Returns a static description of a return code */
+const char *serial_strerror(int code);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,271 @@
+//! Protocol analysis of captured or live traffic
+//!
+//! An `Analyzer` runs the traffic through the registered dissectors, which turn the bytes into
+//! timestamped messages of their protocol. It answers "what did the device actually say"
+//! without writing a decoder for each investigation:
+//!
+//! ```ignore
+//! let mut analyzer = Analyzer::new();
+//! analyzer.register(box NmeaDissector::new());
+//! let records = try!(read_capture(&mut BufferedReader::new(try!(File::open(&path)))));
+//! for event in analyzer.analyze_capture(records.as_slice()).iter() {
+//!     println!("{}", event);
+//! }
+//! ```
+//!
+//! ```text
+//! 1418220000.123456 RX nmea: GgaSentence(Gga { ... })
+//! ```
+//!
+//! Live traffic goes through an `AnalyzerTracer` plugged into a `TracedPort`.
+
+use std::fmt;
+
+use at::FinalResult;
+use capture::Record;
+use modbus::{Request, RtuDecoder};
+use nmea::SentenceDecoder;
+use trace::{Direction, Received, Sent, Tracer};
+use Timestamp;
+
+/// Decodes the traffic of a protocol
+pub trait Dissector {
+    /// Name of the protocol, which tags its events
+    fn name(&self) -> &str;
+
+    /// Feeds a chunk of traffic going in `direction`, returns the descriptions of the messages
+    /// it completed
+    ///
+    /// The chunks of both directions are fed as they come, so a dissector keeps a decoding
+    /// state for each direction.
+    fn feed(&mut self, direction: Direction, data: &[u8]) -> Vec<String>;
+}
+
+/// A message found in the traffic
+#[deriving(Clone, PartialEq)]
+pub struct Event {
+    /// Time of the chunk that completed the message
+    pub timestamp: Timestamp,
+    pub direction: Direction,
+    /// Name of the dissector that found the message
+    pub protocol: String,
+    /// Description of the message
+    pub summary: String,
+}
+
+impl fmt::Show for Event {
+    /// Formats the event like the lines of a `DumpTracer`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self.direction {
+            Received => "RX",
+            Sent => "TX",
+        };
+
+        write!(f, "{}.{:06} {} {}: {}", self.timestamp.time.sec, self.timestamp.time.nsec / 1000,
+               direction, self.protocol, self.summary)
+    }
+}
+
+/// Runs traffic through a set of dissectors
+pub struct Analyzer {
+    dissectors: Vec<Box<Dissector + 'static>>,
+}
+
+impl Analyzer {
+    /// An analyzer without dissectors
+    pub fn new() -> Analyzer {
+        Analyzer { dissectors: Vec::new() }
+    }
+
+    /// Adds `dissector`, the events of a chunk are returned in the order of registration
+    pub fn register(&mut self, dissector: Box<Dissector + 'static>) {
+        self.dissectors.push(dissector);
+    }
+
+    /// Analyzes a chunk of traffic going in `direction`, returns the messages it completed
+    pub fn analyze(&mut self, direction: Direction, timestamp: &Timestamp, data: &[u8])
+                   -> Vec<Event> {
+        let mut events = Vec::new();
+
+        for dissector in self.dissectors.iter_mut() {
+            for summary in dissector.feed(direction, data).into_iter() {
+                events.push(Event {
+                    timestamp: timestamp.clone(),
+                    direction: direction,
+                    protocol: dissector.name().to_string(),
+                    summary: summary,
+                });
+            }
+        }
+
+        events
+    }
+
+    /// Analyzes the records of a capture, returns the messages in time order
+    pub fn analyze_capture(&mut self, records: &[Record]) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        for record in records.iter() {
+            events.push_all(self.analyze(record.direction, &record.timestamp,
+                                         record.data.as_slice()).as_slice());
+        }
+
+        events
+    }
+}
+
+/// Analyzes the traffic of a `TracedPort`, and writes the events as lines to a writer
+pub struct AnalyzerTracer<W> {
+    analyzer: Analyzer,
+    inner: W,
+}
+
+impl<W: Writer> AnalyzerTracer<W> {
+    /// Writes the events found by `analyzer` to `inner`
+    pub fn new(analyzer: Analyzer, inner: W) -> AnalyzerTracer<W> {
+        AnalyzerTracer {
+            analyzer: analyzer,
+            inner: inner,
+        }
+    }
+
+    /// Unwraps the analyzer and the writer
+    pub fn into_inner(self) -> (Analyzer, W) {
+        (self.analyzer, self.inner)
+    }
+}
+
+impl<W: Writer> Tracer for AnalyzerTracer<W> {
+    fn trace(&mut self, direction: Direction, timestamp: &Timestamp, data: &[u8]) {
+        for event in self.analyzer.analyze(direction, timestamp, data).iter() {
+            let _ = self.inner.write_line(event.to_string().as_slice());
+        }
+    }
+}
+
+/// AT commands sent, and the lines of the responses
+///
+/// The commands end with CR, the response lines with CR LF. Echoed commands, information
+/// lines and final result codes are told apart.
+pub struct AtDissector {
+    rx: Vec<u8>,
+    tx: Vec<u8>,
+}
+
+impl AtDissector {
+    pub fn new() -> AtDissector {
+        AtDissector {
+            rx: Vec::new(),
+            tx: Vec::new(),
+        }
+    }
+}
+
+impl Dissector for AtDissector {
+    fn name(&self) -> &str {
+        "at"
+    }
+
+    fn feed(&mut self, direction: Direction, data: &[u8]) -> Vec<String> {
+        let mut summaries = Vec::new();
+        let line = match direction {
+            Received => &mut self.rx,
+            Sent => &mut self.tx,
+        };
+
+        for &byte in data.iter() {
+            if byte != b'\r' && byte != b'\n' {
+                line.push(byte);
+                continue
+            }
+
+            let text = String::from_utf8_lossy(line.as_slice()).into_string();
+            let text = text.as_slice().trim();
+            if !text.is_empty() {
+                summaries.push(match direction {
+                    Sent => format!("command {}", text),
+                    Received if FinalResult::parse(text).is_some() => format!("result {}", text),
+                    Received if text.starts_with("AT") => format!("echo {}", text),
+                    Received => format!("info {}", text),
+                });
+            }
+
+            line.clear();
+        }
+
+        summaries
+    }
+}
+
+/// Modbus RTU requests
+///
+/// Like the `RtuDecoder` it uses, only the requests of the functions this crate serves are
+/// recognized, in both directions; the responses are skipped.
+pub struct ModbusRtuDissector {
+    rx: RtuDecoder,
+    tx: RtuDecoder,
+}
+
+impl ModbusRtuDissector {
+    pub fn new() -> ModbusRtuDissector {
+        ModbusRtuDissector {
+            rx: RtuDecoder::new(),
+            tx: RtuDecoder::new(),
+        }
+    }
+}
+
+impl Dissector for ModbusRtuDissector {
+    fn name(&self) -> &str {
+        "modbus"
+    }
+
+    fn feed(&mut self, direction: Direction, data: &[u8]) -> Vec<String> {
+        let decoder = match direction {
+            Received => &mut self.rx,
+            Sent => &mut self.tx,
+        };
+
+        decoder.feed(data).into_iter().map(|adu| {
+            match Request::parse(adu.slice_from(1)) {
+                Ok(request) => format!("unit {} {}", adu[0], request),
+                Err(e) => format!("unit {} invalid request ({})", adu[0], e),
+            }
+        }).collect()
+    }
+}
+
+/// NMEA 0183 sentences
+pub struct NmeaDissector {
+    rx: SentenceDecoder,
+    tx: SentenceDecoder,
+}
+
+impl NmeaDissector {
+    pub fn new() -> NmeaDissector {
+        NmeaDissector {
+            rx: SentenceDecoder::new(),
+            tx: SentenceDecoder::new(),
+        }
+    }
+}
+
+impl Dissector for NmeaDissector {
+    fn name(&self) -> &str {
+        "nmea"
+    }
+
+    fn feed(&mut self, direction: Direction, data: &[u8]) -> Vec<String> {
+        let decoder = match direction {
+            Received => &mut self.rx,
+            Sent => &mut self.tx,
+        };
+
+        decoder.feed(data).into_iter().map(|sentence| {
+            match sentence {
+                Ok(sentence) => sentence.to_string(),
+                Err(e) => format!("invalid sentence ({})", e),
+            }
+        }).collect()
+    }
+}
@@ -0,0 +1,58 @@
+//! `quickcheck::Arbitrary` for the configuration types, enabled by the `arbitrary` feature
+
+use quickcheck::{Arbitrary, Gen};
+
+use {BaudRate, DataBits, FlowControl, Parity, Settings, StopBits};
+use {Data5, Data6, Data7, Data8, EvenParity, NoParity, OddParity, Stop1, Stop2};
+use {HardwareControl, NoFlowControl, SoftwareControl};
+
+static DATA_BITS: &'static [DataBits] = &[Data5, Data6, Data7, Data8];
+static FLOW_CONTROLS: &'static [FlowControl] = &[HardwareControl, NoFlowControl, SoftwareControl];
+static PARITIES: &'static [Parity] = &[EvenParity, NoParity, OddParity];
+static STOP_BITS: &'static [StopBits] = &[Stop1, Stop2];
+
+fn pick<G: Gen, T: Clone>(g: &mut G, values: &[T]) -> T {
+    g.choose(values).unwrap().clone()
+}
+
+impl Arbitrary for BaudRate {
+    fn arbitrary<G: Gen>(g: &mut G) -> BaudRate {
+        pick(g, BaudRate::standard_rates())
+    }
+}
+
+impl Arbitrary for DataBits {
+    fn arbitrary<G: Gen>(g: &mut G) -> DataBits {
+        pick(g, DATA_BITS)
+    }
+}
+
+impl Arbitrary for FlowControl {
+    fn arbitrary<G: Gen>(g: &mut G) -> FlowControl {
+        pick(g, FLOW_CONTROLS)
+    }
+}
+
+impl Arbitrary for Parity {
+    fn arbitrary<G: Gen>(g: &mut G) -> Parity {
+        pick(g, PARITIES)
+    }
+}
+
+impl Arbitrary for StopBits {
+    fn arbitrary<G: Gen>(g: &mut G) -> StopBits {
+        pick(g, STOP_BITS)
+    }
+}
+
+impl Arbitrary for Settings {
+    fn arbitrary<G: Gen>(g: &mut G) -> Settings {
+        Settings {
+            baud_rate: Arbitrary::arbitrary(g),
+            data_bits: Arbitrary::arbitrary(g),
+            flow_control: Arbitrary::arbitrary(g),
+            parity: Arbitrary::arbitrary(g),
+            stop_bits: Arbitrary::arbitrary(g),
+        }
+    }
+}
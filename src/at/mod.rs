@@ -0,0 +1,315 @@
+//! AT command sessions with modems and radio modules
+
+use std::i64;
+use std::io::{IoError, IoResult, OtherIoError, TimedOut};
+use std::time::Duration;
+use time;
+
+use SerialIo;
+
+pub use self::sms::{PduMode, Sms, SmsMode, TextMode, decode_gsm7, decode_ucs2, encode_gsm7};
+pub use self::sms::{encode_ucs2, pack_septets, parse_deliver_pdu, submit_pdu, unpack_septets};
+
+mod sms;
+
+/// Ctrl-Z, ends the data sent after a `>` prompt
+const CTRL_Z: u8 = 0x1A;
+
+/// Final result code, which ends the response to a command
+#[deriving(Clone, PartialEq, Show)]
+pub enum FinalResult {
+    Busy,
+    /// `+CME ERROR: <error>`, the error can be either numeric or verbose
+    CmeError(String),
+    /// `+CMS ERROR: <error>`, the error can be either numeric or verbose
+    CmsError(String),
+    /// `CONNECT [<text>]`, the modem switched to data mode
+    Connect(String),
+    ErrorResult,
+    NoAnswer,
+    NoCarrier,
+    NoDialtone,
+    OkResult,
+}
+
+impl FinalResult {
+    /// Parses `line` as a final result code
+    pub fn parse(line: &str) -> Option<FinalResult> {
+        let after = |prefix: &str| line.slice_from(prefix.len()).trim().to_string();
+
+        match line {
+            "BUSY" => Some(Busy),
+            "ERROR" => Some(ErrorResult),
+            "NO ANSWER" => Some(NoAnswer),
+            "NO CARRIER" => Some(NoCarrier),
+            "NO DIALTONE" => Some(NoDialtone),
+            "OK" => Some(OkResult),
+            _ if line.starts_with("+CME ERROR:") => Some(CmeError(after("+CME ERROR:"))),
+            _ if line.starts_with("+CMS ERROR:") => Some(CmsError(after("+CMS ERROR:"))),
+            _ if line.starts_with("CONNECT") => Some(Connect(after("CONNECT"))),
+            _ => None,
+        }
+    }
+}
+
+/// Response to a command
+#[deriving(Clone, PartialEq, Show)]
+pub struct Response {
+    /// Information lines, without the echo, the URCs and the final result
+    pub lines: Vec<String>,
+    pub result: FinalResult,
+}
+
+impl Response {
+    /// Whether the command succeeded
+    pub fn is_ok(&self) -> bool {
+        self.result == OkResult
+    }
+
+    /// Turns a failed response into an `OtherIoError` that carries the final result
+    pub fn expect_ok(self) -> IoResult<Response> {
+        if self.is_ok() {
+            Ok(self)
+        } else {
+            Err(IoError {
+                kind: OtherIoError,
+                desc: "AT command failed",
+                detail: Some(self.result.to_string()),
+            })
+        }
+    }
+}
+
+/// Receives unsolicited result codes (URCs)
+pub trait UrcHandler {
+    /// Called with every URC line matching the prefix the handler was registered with
+    fn urc(&mut self, line: &str);
+}
+
+/// Forwards the URCs through the channel, they are dropped if the receiver hung up
+impl UrcHandler for Sender<String> {
+    fn urc(&mut self, line: &str) {
+        let _ = self.send_opt(line.to_string());
+    }
+}
+
+/// An AT command session over a port
+///
+/// The session takes over the timeout of the port. Lines equal to the command being executed
+/// are considered its echo and skipped, so the session works whether echo (`ATE1`) is enabled
+/// or not.
+pub struct Session<S> {
+    port: S,
+    buf: Vec<u8>,
+    handlers: Vec<(String, Box<UrcHandler + 'static>)>,
+}
+
+impl<S: SerialIo> Session<S> {
+    /// Starts a session through `port`
+    pub fn new(port: S) -> Session<S> {
+        Session {
+            port: port,
+            buf: Vec::new(),
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.port
+    }
+
+    /// Unwraps the port, discarding any buffered input
+    pub fn into_inner(self) -> S {
+        self.port
+    }
+
+    /// Registers a `handler` for the URCs that start with `prefix`, like `+CMTI:` or `RING`
+    ///
+    /// While a command runs, lines that also start with the response prefix of the command
+    /// (e.g. `+CREG` for `AT+CREG?`) are considered part of its response instead.
+    pub fn on_urc(&mut self, prefix: &str, handler: Box<UrcHandler + 'static>) {
+        self.handlers.push((prefix.to_string(), handler));
+    }
+
+    /// Executes `command` (without the trailing CR), and waits up to `timeout` for its final
+    /// result
+    ///
+    /// Error results (`ERROR`, `+CME ERROR`, ...) are returned as part of the `Response`, a
+    /// missing final result is reported as a `TimedOut` error.
+    pub fn command(&mut self, command: &str, timeout: Duration) -> IoResult<Response> {
+        let deadline = deadline(timeout);
+
+        try!(self.port.write_str(command));
+        try!(self.port.write(b"\r"));
+
+        self.response(command, deadline)
+    }
+
+    /// Executes `command`, waits for the `>` prompt, and then sends `data` followed by Ctrl-Z
+    ///
+    /// This is the sequence used by commands like `AT+CMGS`. If the device answers with a
+    /// final result instead of the prompt, the data is not sent and that result is returned.
+    pub fn command_with_data(&mut self, command: &str, data: &[u8], timeout: Duration)
+                             -> IoResult<Response> {
+        let deadline = deadline(timeout);
+
+        try!(self.port.write_str(command));
+        try!(self.port.write(b"\r"));
+
+        loop {
+            let prompt = self.buf.iter().position(|&byte| byte == b'>');
+            let end = self.buf.iter().position(|&byte| byte == b'\n');
+
+            match (prompt, end) {
+                (Some(prompt), end) if end.map_or(true, |end| prompt < end) => {
+                    self.buf = self.buf.slice_from(prompt + 1).to_vec();
+                    break
+                },
+                (_, Some(_)) => {
+                    let line = try!(self.read_line(deadline));
+
+                    match FinalResult::parse(line.as_slice()) {
+                        None => {},
+                        Some(result) => return Ok(Response { lines: vec![], result: result }),
+                    }
+                },
+                (_, None) => try!(self.fill(deadline)),
+            }
+        }
+
+        try!(self.port.write(data));
+        try!(self.port.write(&[CTRL_Z]));
+
+        self.response(command, deadline)
+    }
+
+    /// Executes `command`, and collects the lines printed before the `>` prompt
+    ///
+    /// This is how devices like the ELM327 end their responses, instead of a final result.
+    /// Lines may be terminated by a lone CR; the echo and the empty lines are skipped.
+    pub fn command_to_prompt(&mut self, command: &str, timeout: Duration)
+                             -> IoResult<Vec<String>> {
+        let deadline = deadline(timeout);
+
+        try!(self.port.write_str(command));
+        try!(self.port.write(b"\r"));
+
+        loop {
+            match self.buf.iter().position(|&byte| byte == b'>') {
+                None => try!(self.fill(deadline)),
+                Some(prompt) => {
+                    let text = String::from_utf8_lossy(self.buf.slice_to(prompt)).into_string();
+                    self.buf = self.buf.slice_from(prompt + 1).to_vec();
+
+                    return Ok(text.as_slice().split(['\r', '\n'].as_slice()).map(|line| {
+                        line.trim()
+                    }).filter(|&line| !line.is_empty() && line != command.trim()).map(|line| {
+                        line.to_string()
+                    }).collect())
+                },
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for URCs, and dispatches them to their handlers
+    ///
+    /// Lines that don't match any handler are discarded
+    pub fn poll_urcs(&mut self, timeout: Duration) -> IoResult<()> {
+        let deadline = deadline(timeout);
+
+        loop {
+            match self.read_line(deadline) {
+                Err(ref e) if e.kind == TimedOut => return Ok(()),
+                Err(e) => return Err(e),
+                Ok(line) => {
+                    self.dispatch(line.as_slice());
+                },
+            }
+        }
+    }
+
+    /// Passes `line` to the handler of its prefix, returns `false` if there's no such handler
+    fn dispatch(&mut self, line: &str) -> bool {
+        for handler in self.handlers.iter_mut() {
+            if line.starts_with(handler.0.as_slice()) {
+                handler.1.urc(line);
+                return true
+            }
+        }
+
+        false
+    }
+
+    /// Reads more data into the buffer, the data must arrive before the `deadline`
+    fn fill(&mut self, deadline: u64) -> IoResult<()> {
+        let now = time::precise_time_ns();
+        if now >= deadline {
+            return Err(IoError {
+                kind: TimedOut,
+                desc: "No response from the device",
+                detail: None,
+            })
+        }
+
+        self.port.set_timeout(Some(Duration::nanoseconds((deadline - now) as i64)));
+
+        let mut chunk = [0u8, ..256];
+        let n = try!(self.port.read(&mut chunk));
+        self.buf.push_all(chunk.slice_to(n));
+
+        Ok(())
+    }
+
+    /// Reads the next non empty line, which must arrive before the `deadline`
+    fn read_line(&mut self, deadline: u64) -> IoResult<String> {
+        loop {
+            let end = self.buf.iter().position(|&byte| byte == b'\n');
+            match end {
+                None => try!(self.fill(deadline)),
+                Some(end) => {
+                    let line = String::from_utf8_lossy(self.buf.slice_to(end));
+                    let line = line.as_slice().trim().to_string();
+                    self.buf = self.buf.slice_from(end + 1).to_vec();
+
+                    if !line.is_empty() {
+                        return Ok(line)
+                    }
+                },
+            }
+        }
+    }
+
+    /// Collects the response to `command`, up to its final result
+    fn response(&mut self, command: &str, deadline: u64) -> IoResult<Response> {
+        // "AT+CSQ" -> "+CSQ", "AT+CREG?" -> "+CREG"
+        let own_prefix = command.slice_from(if command.len() >= 2 { 2 } else { 0 });
+        let own_prefix = own_prefix.split(['=', '?'].as_slice()).next().unwrap_or("");
+
+        let mut lines = Vec::new();
+        loop {
+            let line = try!(self.read_line(deadline));
+
+            if line.as_slice() == command.trim() {
+                continue
+            }
+
+            match FinalResult::parse(line.as_slice()) {
+                None => {},
+                Some(result) => return Ok(Response { lines: lines, result: result }),
+            }
+
+            let own = !own_prefix.is_empty() && line.as_slice().starts_with(own_prefix);
+            if own || !self.dispatch(line.as_slice()) {
+                lines.push(line);
+            }
+        }
+    }
+}
+
+/// Converts a relative `timeout` into an absolute deadline, in `precise_time_ns` units
+fn deadline(timeout: Duration) -> u64 {
+    let timeout = timeout.num_nanoseconds().unwrap_or(i64::MAX);
+
+    time::precise_time_ns() + if timeout < 0 { 0 } else { timeout as u64 }
+}
@@ -0,0 +1,567 @@
+//! SMS on GSM modems, in both text and PDU mode
+//!
+//! Only single part messages are supported: up to 160 GSM 7-bit characters, or 70 UCS-2 units.
+//! New message indications (`+CMTI:`) can be received by registering a URC handler.
+
+use std::io::{IoError, IoResult, InvalidInput, OtherIoError};
+use std::time::Duration;
+
+use hex;
+use SerialIo;
+use super::{Response, Session};
+
+/// Escape to the extension table of the GSM 7-bit alphabet
+const ESCAPE: u8 = 0x1B;
+
+/// GSM 7-bit default alphabet, indexed by septet
+static BASIC: [char, ..128] = [
+    '@', '£', '$', '¥', 'è', 'é', 'ù', 'ì',
+    'ò', 'Ç', '\n', 'Ø', 'ø', '\r', 'Å', 'å',
+    'Δ', '_', 'Φ', 'Γ', 'Λ', 'Ω', 'Π', 'Ψ',
+    'Σ', 'Θ', 'Ξ', '\x1b', 'Æ', 'æ', 'ß', 'É',
+    ' ', '!', '"', '#', '¤', '%', '&', '\'',
+    '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7',
+    '8', '9', ':', ';', '<', '=', '>', '?',
+    '¡', 'A', 'B', 'C', 'D', 'E', 'F', 'G',
+    'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W',
+    'X', 'Y', 'Z', 'Ä', 'Ö', 'Ñ', 'Ü', '§',
+    '¿', 'a', 'b', 'c', 'd', 'e', 'f', 'g',
+    'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w',
+    'x', 'y', 'z', 'ä', 'ö', 'ñ', 'ü', 'à',
+];
+
+/// Extension table of the GSM 7-bit alphabet, as (septet after the escape, character)
+static EXTENSION: [(u8, char), ..10] = [
+    (0x0A, '\x0c'), (0x14, '^'), (0x28, '{'), (0x29, '}'), (0x2F, '\\'),
+    (0x3C, '['), (0x3D, '~'), (0x3E, ']'), (0x40, '|'), (0x65, '€'),
+];
+
+const HEX_DIGITS: &'static [u8] = b"0123456789ABCDEF";
+
+/// Message format used by the modem, selected with `AT+CMGF`
+#[deriving(Clone, PartialEq, Show)]
+pub enum SmsMode {
+    /// Binary PDUs in hexadecimal, independent of the character set of the modem
+    PduMode,
+    /// Human readable, the text uses the character set selected with `AT+CSCS`
+    TextMode,
+}
+
+/// A received message
+#[deriving(Clone, PartialEq, Show)]
+pub struct Sms {
+    /// Originating address, either a phone number or an alphanumeric name
+    pub sender: String,
+    /// Service centre timestamp, formatted as `yy/MM/dd,hh:mm:ss±zz` (zone in quarter hours)
+    pub timestamp: String,
+    pub text: String,
+}
+
+impl<S: SerialIo> Session<S> {
+    /// Sends `text` to `number`, returns the message reference assigned by the network
+    pub fn send_sms(&mut self, number: &str, text: &str, mode: SmsMode, timeout: Duration)
+                    -> IoResult<u8> {
+        let (command, data) = match mode {
+            PduMode => {
+                let pdu = try!(submit_pdu(number, text));
+                // The length excludes the SMSC information, which is a single zero octet
+                (format!("AT+CMGS={}", pdu.len() - 1), to_hex(pdu.as_slice()).into_bytes())
+            },
+            TextMode => {
+                if text.bytes().any(|byte| byte == super::CTRL_Z || byte == ESCAPE) {
+                    return Err(invalid_input("The text contains Ctrl-Z or ESC"))
+                }
+
+                try!(check_number(number));
+                (format!("AT+CMGS=\"{}\"", number), text.as_bytes().to_vec())
+            },
+        };
+
+        try!(self.set_sms_mode(mode, timeout));
+
+        let response = try!(self.command_with_data(command.as_slice(), data.as_slice(), timeout));
+        let response = try!(response.expect_ok());
+        let reference = response.lines.iter().filter_map(|line| {
+            let line = line.as_slice();
+            if line.starts_with("+CMGS:") {
+                from_str::<u8>(line.slice_from("+CMGS:".len()).trim())
+            } else {
+                None
+            }
+        }).next();
+
+        match reference {
+            None => Err(IoError {
+                kind: OtherIoError,
+                desc: "Missing message reference",
+                detail: None,
+            }),
+            Some(reference) => Ok(reference),
+        }
+    }
+
+    /// Lists all the messages stored in the preferred storage, along with their indices
+    pub fn list_sms(&mut self, mode: SmsMode, timeout: Duration) -> IoResult<Vec<(uint, Sms)>> {
+        try!(self.set_sms_mode(mode, timeout));
+
+        let command = match mode {
+            PduMode => "AT+CMGL=4",
+            TextMode => "AT+CMGL=\"ALL\"",
+        };
+
+        let response = try!(self.command(command, timeout).and_then(|r| r.expect_ok()));
+
+        Ok(match mode {
+            PduMode => parse_pdu_list(response.lines.as_slice()),
+            TextMode => parse_text_list(response.lines.as_slice()),
+        })
+    }
+
+    /// Deletes the message stored at `index`
+    pub fn delete_sms(&mut self, index: uint, timeout: Duration) -> IoResult<()> {
+        let command = format!("AT+CMGD={}", index);
+
+        self.command(command.as_slice(), timeout).and_then(|r| r.expect_ok()).map(|_| ())
+    }
+
+    /// Selects the message format
+    fn set_sms_mode(&mut self, mode: SmsMode, timeout: Duration) -> IoResult<Response> {
+        let command = match mode {
+            PduMode => "AT+CMGF=0",
+            TextMode => "AT+CMGF=1",
+        };
+
+        self.command(command, timeout).and_then(|r| r.expect_ok())
+    }
+}
+
+/// Encodes `text` as GSM 7-bit septets, returns `None` if a character isn't in the alphabet
+///
+/// Characters of the extension table take two septets.
+pub fn encode_gsm7(text: &str) -> Option<Vec<u8>> {
+    let mut septets = Vec::with_capacity(text.len());
+
+    for c in text.chars() {
+        match BASIC.iter().position(|&basic| basic == c && c != '\x1b') {
+            Some(septet) => septets.push(septet as u8),
+            None => match EXTENSION.iter().find(|&&(_, extended)| extended == c) {
+                None => return None,
+                Some(&(septet, _)) => {
+                    septets.push(ESCAPE);
+                    septets.push(septet);
+                },
+            },
+        }
+    }
+
+    Some(septets)
+}
+
+/// Decodes GSM 7-bit `septets`
+///
+/// Unknown extension characters are decoded as their basic counterpart.
+pub fn decode_gsm7(septets: &[u8]) -> String {
+    let mut text = String::with_capacity(septets.len());
+    let mut escaped = false;
+
+    for &septet in septets.iter() {
+        let septet = septet & 0x7F;
+
+        if escaped {
+            escaped = false;
+
+            match EXTENSION.iter().find(|&&(extended, _)| extended == septet) {
+                None => text.push(BASIC[septet as uint]),
+                Some(&(_, c)) => text.push(c),
+            }
+        } else if septet == ESCAPE {
+            escaped = true;
+        } else {
+            text.push(BASIC[septet as uint]);
+        }
+    }
+
+    text
+}
+
+/// Packs `septets` into octets, least significant bits first
+pub fn pack_septets(septets: &[u8]) -> Vec<u8> {
+    let mut octets = Vec::with_capacity((septets.len() * 7 + 7) / 8);
+    let mut acc = 0u32;
+    let mut bits = 0u;
+
+    for &septet in septets.iter() {
+        acc |= (septet as u32 & 0x7F) << bits;
+        bits += 7;
+
+        while bits >= 8 {
+            octets.push(acc as u8);
+            acc >>= 8;
+            bits -= 8;
+        }
+    }
+
+    if bits > 0 {
+        octets.push(acc as u8);
+    }
+
+    octets
+}
+
+/// Unpacks up to `count` septets from `octets`
+pub fn unpack_septets(octets: &[u8], count: uint) -> Vec<u8> {
+    let mut septets = Vec::with_capacity(count);
+    let mut acc = 0u32;
+    let mut bits = 0u;
+
+    for &octet in octets.iter() {
+        acc |= (octet as u32) << bits;
+        bits += 8;
+
+        while bits >= 7 && septets.len() < count {
+            septets.push((acc & 0x7F) as u8);
+            acc >>= 7;
+            bits -= 7;
+        }
+    }
+
+    septets
+}
+
+/// Encodes `text` as UCS-2, big endian
+///
+/// Characters outside of the Basic Multilingual Plane are encoded as surrogate pairs.
+pub fn encode_ucs2(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len() * 2);
+
+    for unit in text.utf16_units() {
+        bytes.push((unit >> 8) as u8);
+        bytes.push(unit as u8);
+    }
+
+    bytes
+}
+
+/// Decodes big endian UCS-2 `bytes`, returns `None` for an odd length or unpaired surrogates
+pub fn decode_ucs2(bytes: &[u8]) -> Option<String> {
+    if bytes.len() % 2 != 0 {
+        return None
+    }
+
+    let units: Vec<u16> = bytes.chunks(2).map(|pair| {
+        (pair[0] as u16) << 8 | pair[1] as u16
+    }).collect();
+
+    String::from_utf16(units.as_slice())
+}
+
+/// Builds an SMS-SUBMIT PDU that sends `text` to `number`
+///
+/// The PDU starts with an empty SMSC information, so the modem uses its default service
+/// centre. `text` is encoded as GSM 7-bit when possible, as UCS-2 otherwise.
+pub fn submit_pdu(number: &str, text: &str) -> IoResult<Vec<u8>> {
+    try!(check_number(number));
+
+    let (dcs, length, user_data) = match encode_gsm7(text) {
+        Some(septets) => {
+            if septets.len() > 160 {
+                return Err(invalid_input("The text doesn't fit in a single message"))
+            }
+
+            (0x00, septets.len(), pack_septets(septets.as_slice()))
+        },
+        None => {
+            let bytes = encode_ucs2(text);
+            if bytes.len() > 140 {
+                return Err(invalid_input("The text doesn't fit in a single message"))
+            }
+
+            (0x08, bytes.len(), bytes)
+        },
+    };
+
+    let international = number.starts_with("+");
+    let digits = if international { number.slice_from(1) } else { number };
+
+    // SMSC information length, SMS-SUBMIT with a relative validity period, message reference
+    let mut pdu = vec![0x00, 0x11, 0x00];
+
+    pdu.push(digits.len() as u8);
+    pdu.push(if international { 0x91 } else { 0x81 });
+    for pair in digits.as_bytes().chunks(2) {
+        let low = pair[0] - b'0';
+        let high = if pair.len() == 2 { pair[1] - b'0' } else { 0x0F };
+        pdu.push(high << 4 | low);
+    }
+
+    // Protocol identifier, data coding scheme, validity period (4 days)
+    pdu.push_all(&[0x00, dcs, 0xAA]);
+    pdu.push(length as u8);
+    pdu.push_all(user_data.as_slice());
+
+    Ok(pdu)
+}
+
+/// Parses an SMS-DELIVER PDU, including its leading SMSC information
+///
+/// Returns `None` if the PDU is truncated, isn't an SMS-DELIVER, or uses an unknown alphabet.
+/// The user data header, if any, is skipped.
+pub fn parse_deliver_pdu(pdu: &[u8]) -> Option<Sms> {
+    let mut pos = 0;
+
+    let smsc_len = match take(pdu, &mut pos, 1) {
+        None => return None,
+        Some(len) => len[0] as uint,
+    };
+    if take(pdu, &mut pos, smsc_len).is_none() {
+        return None
+    }
+
+    let first = match take(pdu, &mut pos, 1) {
+        None => return None,
+        Some(first) => first[0],
+    };
+    if first & 0x03 != 0x00 {
+        return None
+    }
+
+    let sender = match take(pdu, &mut pos, 2) {
+        None => return None,
+        Some(header) => {
+            let (digits, kind) = (header[0] as uint, header[1]);
+            let address = match take(pdu, &mut pos, (digits + 1) / 2) {
+                None => return None,
+                Some(address) => address,
+            };
+
+            if kind & 0x70 == 0x50 {
+                decode_gsm7(unpack_septets(address, digits * 4 / 7).as_slice())
+            } else {
+                let number = semi_octets(address, digits);
+                if kind & 0x70 == 0x10 { format!("+{}", number) } else { number }
+            }
+        },
+    };
+
+    let (dcs, timestamp, length) = match take(pdu, &mut pos, 10) {
+        None => return None,
+        Some(fields) => (fields[1], fields.slice(2, 9), fields[9] as uint),
+    };
+
+    let zone = timestamp[6] << 4 | timestamp[6] >> 4;
+    let quarters = (zone >> 4 & 0x07) * 10 + (zone & 0x0F);
+    let timestamp = format!("{}/{}/{},{}:{}:{}{}{:02}",
+                            semi_octets(timestamp.slice(0, 1), 2),
+                            semi_octets(timestamp.slice(1, 2), 2),
+                            semi_octets(timestamp.slice(2, 3), 2),
+                            semi_octets(timestamp.slice(3, 4), 2),
+                            semi_octets(timestamp.slice(4, 5), 2),
+                            semi_octets(timestamp.slice(5, 6), 2),
+                            if zone & 0x80 == 0 { '+' } else { '-' },
+                            quarters);
+
+    let user_data = pdu.slice_from(pos);
+    let header = if first & 0x40 == 0 || user_data.is_empty() {
+        0
+    } else {
+        user_data[0] as uint + 1
+    };
+
+    let alphabet = if dcs & 0xC0 == 0x00 {
+        dcs & 0x0C
+    } else if dcs & 0xF0 == 0xF0 {
+        dcs & 0x04
+    } else {
+        return None
+    };
+
+    let text = match alphabet {
+        0x00 => {
+            let septets = unpack_septets(user_data, length);
+            if septets.len() < length {
+                return None
+            }
+
+            // The header is padded to a septet boundary
+            let skip = (header * 8 + 6) / 7;
+            decode_gsm7(septets.slice_from(if skip > length { length } else { skip }))
+        },
+        0x08 => {
+            if user_data.len() < length || header > length {
+                return None
+            }
+
+            match decode_ucs2(user_data.slice(header, length)) {
+                None => return None,
+                Some(text) => text,
+            }
+        },
+        _ => {
+            if user_data.len() < length || header > length {
+                return None
+            }
+
+            String::from_utf8_lossy(user_data.slice(header, length)).into_string()
+        },
+    };
+
+    Some(Sms { sender: sender, timestamp: timestamp, text: text })
+}
+
+/// Parses the `+CMGL` listing of PDU mode, messages that can't be decoded are skipped
+fn parse_pdu_list(lines: &[String]) -> Vec<(uint, Sms)> {
+    let mut messages = Vec::new();
+
+    for pair in lines.windows(2) {
+        let header = pair[0].as_slice();
+        if !header.starts_with("+CMGL:") {
+            continue
+        }
+
+        let index = header.slice_from("+CMGL:".len()).split(',').next();
+        let index = index.and_then(|index| from_str::<uint>(index.trim()));
+        let pdu = from_hex(pair[1].as_slice());
+
+        match (index, pdu.as_ref().and_then(|pdu| parse_deliver_pdu(pdu.as_slice()))) {
+            (Some(index), Some(sms)) => messages.push((index, sms)),
+            _ => {},
+        }
+    }
+
+    messages
+}
+
+/// Parses the `+CMGL` listing of text mode, the text of a message can span several lines
+fn parse_text_list(lines: &[String]) -> Vec<(uint, Sms)> {
+    let mut messages: Vec<(uint, Sms)> = Vec::new();
+    let mut current = None;
+
+    for line in lines.iter() {
+        let line = line.as_slice();
+
+        if line.starts_with("+CMGL:") {
+            let fields = split_fields(line.slice_from("+CMGL:".len()));
+            let field = |i: uint| fields.as_slice().get(i).map_or(String::new(), |f| f.clone());
+            let index = from_str::<uint>(field(0).as_slice());
+
+            current = match index {
+                None => None,
+                Some(index) => {
+                    messages.push((index, Sms {
+                        sender: field(2),
+                        timestamp: field(4),
+                        text: String::new(),
+                    }));
+
+                    Some(messages.len() - 1)
+                },
+            };
+        } else {
+            match current {
+                None => {},
+                Some(i) => {
+                    let text = &mut messages.as_mut_slice()[i].1.text;
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    text.push_str(line);
+                },
+            }
+        }
+    }
+
+    messages
+}
+
+/// Splits comma separated fields, commas inside double quotes don't separate fields
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+
+    for c in line.trim().chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+
+    fields
+}
+
+/// Checks that `number` is made of decimal digits, optionally preceded by '+'
+fn check_number(number: &str) -> IoResult<()> {
+    let digits = if number.starts_with("+") { number.slice_from(1) } else { number };
+
+    if digits.is_empty() || digits.len() > 20 || !digits.bytes().all(|b| b >= b'0' && b <= b'9') {
+        Err(invalid_input("Invalid phone number"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Decodes up to `count` swapped BCD digits, the filler nibble (0xF) ends the digits
+fn semi_octets(octets: &[u8], count: uint) -> String {
+    let mut digits = String::with_capacity(count);
+
+    for &octet in octets.iter() {
+        for &nibble in [octet & 0x0F, octet >> 4].iter() {
+            if nibble > 9 || digits.len() == count {
+                return digits
+            }
+
+            digits.push((b'0' + nibble) as char);
+        }
+    }
+
+    digits
+}
+
+/// Returns the next `n` bytes of `pdu` and advances `pos`, or `None` if there aren't enough
+fn take<'a>(pdu: &'a [u8], pos: &mut uint, n: uint) -> Option<&'a [u8]> {
+    if pdu.len() - *pos < n {
+        None
+    } else {
+        *pos += n;
+        Some(pdu.slice(*pos - n, *pos))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+
+    for &byte in bytes.iter() {
+        hex.push(HEX_DIGITS[(byte >> 4) as uint] as char);
+        hex.push(HEX_DIGITS[(byte & 0x0F) as uint] as char);
+    }
+
+    hex
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim().as_bytes();
+    if hex.len() % 2 != 0 {
+        return None
+    }
+
+    let mut bytes = Vec::with_capacity(hex.len() / 2);
+    for pair in hex.chunks(2) {
+        match (hex::value(pair[0]), hex::value(pair[1])) {
+            (Some(high), Some(low)) => bytes.push(high << 4 | low),
+            _ => return None,
+        }
+    }
+
+    Some(bytes)
+}
+
+fn invalid_input(desc: &'static str) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: desc,
+        detail: None,
+    }
+}
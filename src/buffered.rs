@@ -0,0 +1,119 @@
+use std::cmp;
+use std::io::{Buffer, IoResult};
+use std::slice::bytes;
+use std::time::Duration;
+
+use {SerialIo, Settings};
+
+/// Default capacity of the read buffer
+const DEFAULT_CAPACITY: uint = 4096;
+
+/// A serial transport with buffered input
+///
+/// Reads are served from an internal buffer, which gets refilled with a single `read()` on the
+/// underlying transport, so `read_line`, `read_until` and byte-sized reads don't cost a system
+/// call each. Writes are passed through untouched.
+pub struct BufferedSerialPort<S> {
+    inner: S,
+    buf: Vec<u8>,
+    pos: uint,
+    cap: uint,
+}
+
+impl<S> BufferedSerialPort<S> {
+    /// Wraps `inner` using a buffer of the default capacity
+    pub fn new(inner: S) -> BufferedSerialPort<S> {
+        BufferedSerialPort::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Wraps `inner` using a buffer of `capacity` bytes
+    pub fn with_capacity(capacity: uint, inner: S) -> BufferedSerialPort<S> {
+        BufferedSerialPort {
+            inner: inner,
+            buf: Vec::from_elem(capacity, 0u8),
+            pos: 0,
+            cap: 0,
+        }
+    }
+
+    /// Returns a reference to the underlying transport
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying transport
+    ///
+    /// Reading directly from the transport bypasses (and may reorder data with respect to) the
+    /// buffered input.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying transport, discarding any buffered input
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Reader> Buffer for BufferedSerialPort<S> {
+    fn fill_buf<'a>(&'a mut self) -> IoResult<&'a [u8]> {
+        if self.pos == self.cap {
+            self.cap = try!(self.inner.read(self.buf.as_mut_slice()));
+            self.pos = 0;
+        }
+
+        Ok(self.buf.slice(self.pos, self.cap))
+    }
+
+    fn consume(&mut self, amt: uint) {
+        self.pos = cmp::min(self.pos + amt, self.cap);
+    }
+}
+
+impl<S: Reader> Reader for BufferedSerialPort<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        // Nothing buffered and a big enough destination, skip the extra copy
+        if self.pos == self.cap && buf.len() >= self.buf.len() {
+            return self.inner.read(buf)
+        }
+
+        let nread = {
+            let available = try!(self.fill_buf());
+            let nread = cmp::min(available.len(), buf.len());
+            bytes::copy_memory(buf, available.slice_to(nread));
+            nread
+        };
+
+        self.consume(nread);
+
+        Ok(nread)
+    }
+}
+
+impl<S: SerialIo> SerialIo for BufferedSerialPort<S> {
+    fn settings(&self) -> IoResult<Settings> {
+        self.inner.settings()
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.inner.configure(settings)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_timeout(timeout)
+    }
+}
+
+impl<S: Writer> Writer for BufferedSerialPort<S> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
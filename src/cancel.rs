@@ -0,0 +1,92 @@
+use libc::{c_int, c_void, size_t};
+use libc::funcs::posix88::fcntl::fcntl;
+use libc::funcs::posix88::unistd::{close, pipe as open_pipe, read, write};
+use std::io::{IoError, IoResult, OtherIoError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use poll;
+use termios::FAILURE;
+
+const F_SETFD: c_int = 2;
+const FD_CLOEXEC: c_int = 1;
+
+/// Description of the errors of the cancelled reads
+const CANCELLED: &'static str = "Read operation cancelled";
+
+/// A file descriptor, closed when dropped
+pub struct Fd(pub c_int);
+
+impl Drop for Fd {
+    fn drop(&mut self) {
+        unsafe { close(self.0) };
+    }
+}
+
+/// Aborts the blocking reads of a port from another task, see `SerialPort::read_canceller`
+///
+/// The handles of a port are clones, any of them can be used.
+#[deriving(Clone)]
+pub struct ReadCanceller {
+    pipe: Arc<Fd>,
+}
+
+impl ReadCanceller {
+    /// Makes the read in progress fail, it returns an error for which `is_cancellation` holds
+    ///
+    /// Without a read in progress, the next one is cancelled.
+    pub fn cancel(&self) -> IoResult<()> {
+        let byte = 0u8;
+
+        match unsafe { write(self.pipe.0, &byte as *const u8 as *const c_void, 1) } {
+            -1 => Err(IoError::last_error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Tells whether `error` comes from a cancelled read
+    pub fn is_cancellation(error: &IoError) -> bool {
+        error.kind == OtherIoError && error.desc == CANCELLED
+    }
+}
+
+/// Creates the pipe behind a `ReadCanceller`, returns its read end
+pub fn pipe() -> IoResult<(Fd, ReadCanceller)> {
+    let mut fds = [0 as c_int, ..2];
+
+    match unsafe { open_pipe(fds.as_mut_ptr()) } {
+        FAILURE => return Err(IoError::last_error()),
+        _ => {},
+    }
+    let (reader, writer) = (Fd(fds[0]), Fd(fds[1]));
+
+    // Like the ports, the pipe isn't inherited by child processes
+    for &fd in fds.iter() {
+        match unsafe { fcntl(fd, F_SETFD, FD_CLOEXEC) } {
+            FAILURE => return Err(IoError::last_error()),
+            _ => {},
+        }
+    }
+
+    Ok((reader, ReadCanceller { pipe: Arc::new(writer) }))
+}
+
+/// Consumes the pending cancellations of the read end `fd`, returns the error of the read
+pub fn consume(fd: &Fd) -> IoError {
+    let mut buf = [0u8, ..64];
+
+    // Without waiting once the pipe is empty
+    while poll::wait(fd.0, poll::POLLIN, Some(Duration::zero())).unwrap_or(false) {
+        let n = unsafe { read(fd.0, buf.as_mut_ptr() as *mut c_void, buf.len() as size_t) };
+
+        if n <= 0 {
+            break
+        }
+    }
+
+    IoError {
+        kind: OtherIoError,
+        desc: CANCELLED,
+        detail: None,
+    }
+}
@@ -0,0 +1,148 @@
+//! Captures of the traffic of a port, as JSON lines
+//!
+//! Every chunk of traffic is a JSON object on its own line:
+//!
+//! ```text
+//! {"sec":1418220000,"nsec":123456789,"mono_ns":5020331977846,"dir":"tx","data":"41540d"}
+//! ```
+//!
+//! - `sec` and `nsec`: wall clock time, since the Unix epoch
+//! - `mono_ns`: monotonic time in nanoseconds, from an arbitrary origin; use it to compute the
+//!   delays between the records
+//! - `dir`: `rx` for received data, `tx` for sent data
+//! - `data`: the bytes, in hexadecimal
+//!
+//! Captures are made by a `CaptureTracer` plugged into a `TracedPort`, and can be processed by
+//! any tool that understands JSON.
+
+use serialize::hex::{FromHex, ToHex};
+use serialize::json;
+use std::io::{InvalidInput, IoError, IoResult};
+use time::Timespec;
+
+use trace::{Direction, Received, Sent, Tracer};
+use Timestamp;
+
+/// A chunk of captured traffic
+#[deriving(Clone, PartialEq, Show)]
+pub struct Record {
+    pub direction: Direction,
+    pub timestamp: Timestamp,
+    pub data: Vec<u8>,
+}
+
+/// A record, as it's serialized
+#[deriving(Decodable, Encodable)]
+struct JsonRecord {
+    sec: i64,
+    nsec: i32,
+    mono_ns: u64,
+    dir: String,
+    data: String,
+}
+
+impl Record {
+    /// Parses a line of a capture, `None` if it isn't a valid record
+    pub fn parse(line: &str) -> Option<Record> {
+        let record: JsonRecord = match json::decode(line) {
+            Err(_) => return None,
+            Ok(record) => record,
+        };
+
+        let direction = match record.dir.as_slice() {
+            "rx" => Received,
+            "tx" => Sent,
+            _ => return None,
+        };
+
+        match record.data.as_slice().from_hex() {
+            Err(_) => None,
+            Ok(data) => Some(Record {
+                direction: direction,
+                timestamp: Timestamp {
+                    time: Timespec::new(record.sec, record.nsec),
+                    precise_ns: record.mono_ns,
+                },
+                data: data,
+            }),
+        }
+    }
+
+    /// Serializes the record as a line of a capture, without the line terminator
+    pub fn to_json(&self) -> String {
+        json::encode(&JsonRecord {
+            sec: self.timestamp.time.sec,
+            nsec: self.timestamp.time.nsec,
+            mono_ns: self.timestamp.precise_ns,
+            dir: match self.direction {
+                Received => "rx",
+                Sent => "tx",
+            }.to_string(),
+            data: self.data.as_slice().to_hex(),
+        })
+    }
+}
+
+/// Writes the traffic of a `TracedPort` as a capture
+pub struct CaptureTracer<W> {
+    inner: W,
+}
+
+impl<W: Writer> CaptureTracer<W> {
+    /// Writes the capture to `inner`
+    pub fn new(inner: W) -> CaptureTracer<W> {
+        CaptureTracer {
+            inner: inner,
+        }
+    }
+
+    /// Returns a reference to the writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Unwraps the writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Writer> Tracer for CaptureTracer<W> {
+    fn trace(&mut self, direction: Direction, timestamp: &Timestamp, data: &[u8]) {
+        let record = Record {
+            direction: direction,
+            timestamp: timestamp.clone(),
+            data: data.to_vec(),
+        };
+
+        let _ = self.inner.write_line(record.to_json().as_slice());
+    }
+}
+
+/// Reads all the records of a capture
+///
+/// Empty lines are skipped, any other line that isn't a record is reported as an
+/// `InvalidInput` error.
+pub fn read_capture<B: Buffer>(input: &mut B) -> IoResult<Vec<Record>> {
+    let mut records = Vec::new();
+
+    for (i, line) in input.lines().enumerate() {
+        let line = try!(line);
+        let line = line.as_slice().trim();
+
+        if line.is_empty() {
+            continue
+        }
+
+        match Record::parse(line) {
+            None => return Err(IoError {
+                kind: InvalidInput,
+                desc: "Invalid capture record",
+                detail: Some(format!("line {}: {}", i + 1, line)),
+            }),
+            Some(record) => records.push(record),
+        }
+    }
+
+    Ok(records)
+}
@@ -0,0 +1,410 @@
+//! Checksums and CRCs used by serial protocols
+//!
+//! All the checksums are computed incrementally, the data can be fed in several chunks.
+
+/// A checksum computed incrementally over a byte stream
+pub trait Checksum {
+    /// Feeds `data` to the checksum
+    fn update(&mut self, data: &[u8]);
+
+    /// Returns the checksum of the data fed so far, serialized in transmission order
+    fn bytes(&self) -> Vec<u8>;
+
+    /// Returns the number of bytes of the serialized checksum
+    ///
+    /// The checksums of this module answer without allocating.
+    fn size(&self) -> uint {
+        self.bytes().len()
+    }
+
+    /// Tells whether `expected` is the checksum of the data fed so far, serialized in
+    /// transmission order
+    ///
+    /// The checksums of this module compare without allocating.
+    fn matches(&self, expected: &[u8]) -> bool {
+        self.bytes().as_slice() == expected
+    }
+
+    /// Restarts the computation, as if no data had been fed
+    fn reset(&mut self);
+}
+
+/// Bitwise CRC computation, shared by all the widths
+///
+/// Reflected CRCs are computed with a reflected polynomial, and are transmitted least
+/// significant byte first. The other CRCs are transmitted most significant byte first.
+#[deriving(Clone)]
+struct Engine {
+    width: uint,
+    poly: u32,
+    init: u32,
+    xorout: u32,
+    reflected: bool,
+    crc: u32,
+}
+
+impl Engine {
+    fn new(width: uint, poly: u32, init: u32, xorout: u32, reflected: bool) -> Engine {
+        let poly = if reflected { reflect(poly, width) } else { poly };
+
+        Engine {
+            width: width,
+            poly: poly,
+            init: init,
+            xorout: xorout,
+            reflected: reflected,
+            crc: init,
+        }
+    }
+
+    /// Returns the `i`th byte of the serialized CRC
+    fn byte(&self, i: uint) -> u8 {
+        let n = self.size();
+        let shift = if self.reflected { 8 * i } else { 8 * (n - 1 - i) };
+
+        (self.value() >> shift) as u8
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        range(0, self.size()).map(|i| self.byte(i)).collect()
+    }
+
+    fn mask(&self) -> u32 {
+        if self.width == 32 { 0xFFFF_FFFF } else { (1 << self.width) - 1 }
+    }
+
+    fn matches(&self, expected: &[u8]) -> bool {
+        expected.len() == self.size() &&
+            expected.iter().enumerate().all(|(i, &byte)| byte == self.byte(i))
+    }
+
+    fn reset(&mut self) {
+        self.crc = self.init;
+    }
+
+    fn size(&self) -> uint {
+        self.width / 8
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let top = 1u32 << (self.width - 1);
+        let mask = self.mask();
+
+        for &byte in data.iter() {
+            if self.reflected {
+                self.crc ^= byte as u32;
+
+                for _ in range(0u, 8) {
+                    self.crc = if self.crc & 1 != 0 {
+                        (self.crc >> 1) ^ self.poly
+                    } else {
+                        self.crc >> 1
+                    };
+                }
+            } else {
+                self.crc ^= (byte as u32) << (self.width - 8);
+
+                for _ in range(0u, 8) {
+                    self.crc = if self.crc & top != 0 {
+                        (self.crc << 1) ^ self.poly
+                    } else {
+                        self.crc << 1
+                    } & mask;
+                }
+            }
+        }
+    }
+
+    fn value(&self) -> u32 {
+        (self.crc ^ self.xorout) & self.mask()
+    }
+}
+
+/// Reverses the order of the lower `width` bits of `value`
+fn reflect(value: u32, width: uint) -> u32 {
+    range(0, width).fold(0, |reflected, i| reflected | ((value >> i) & 1) << (width - 1 - i))
+}
+
+/// 8-bit CRC
+#[deriving(Clone)]
+pub struct Crc8(Engine);
+
+impl Crc8 {
+    /// CRC with the given parameters, `poly` is given in its normal (non reflected) form
+    pub fn new(poly: u8, init: u8, xorout: u8, reflected: bool) -> Crc8 {
+        Crc8(Engine::new(8, poly as u32, init as u32, xorout as u32, reflected))
+    }
+
+    /// CRC-8 (SMBus)
+    pub fn smbus() -> Crc8 {
+        Crc8::new(0x07, 0x00, 0x00, false)
+    }
+
+    /// CRC-8/MAXIM (Dallas 1-Wire)
+    pub fn maxim() -> Crc8 {
+        Crc8::new(0x31, 0x00, 0x00, true)
+    }
+
+    /// Returns the CRC of the data fed so far
+    pub fn value(&self) -> u8 {
+        self.0.value() as u8
+    }
+}
+
+impl Checksum for Crc8 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        self.0.bytes()
+    }
+
+    fn size(&self) -> uint {
+        self.0.size()
+    }
+
+    fn matches(&self, expected: &[u8]) -> bool {
+        self.0.matches(expected)
+    }
+
+    fn reset(&mut self) {
+        self.0.reset()
+    }
+}
+
+/// 16-bit CRC
+#[deriving(Clone)]
+pub struct Crc16(Engine);
+
+impl Crc16 {
+    /// CRC with the given parameters, `poly` is given in its normal (non reflected) form
+    pub fn new(poly: u16, init: u16, xorout: u16, reflected: bool) -> Crc16 {
+        Crc16(Engine::new(16, poly as u32, init as u32, xorout as u32, reflected))
+    }
+
+    /// CRC-16/CCITT-FALSE
+    pub fn ccitt() -> Crc16 {
+        Crc16::new(0x1021, 0xFFFF, 0x0000, false)
+    }
+
+    /// CRC-16/KERMIT
+    pub fn kermit() -> Crc16 {
+        Crc16::new(0x1021, 0x0000, 0x0000, true)
+    }
+
+    /// CRC-16/MODBUS
+    pub fn modbus() -> Crc16 {
+        Crc16::new(0x8005, 0xFFFF, 0x0000, true)
+    }
+
+    /// CRC-16/XMODEM
+    pub fn xmodem() -> Crc16 {
+        Crc16::new(0x1021, 0x0000, 0x0000, false)
+    }
+
+    /// Returns the CRC of the data fed so far
+    pub fn value(&self) -> u16 {
+        self.0.value() as u16
+    }
+}
+
+impl Checksum for Crc16 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        self.0.bytes()
+    }
+
+    fn size(&self) -> uint {
+        self.0.size()
+    }
+
+    fn matches(&self, expected: &[u8]) -> bool {
+        self.0.matches(expected)
+    }
+
+    fn reset(&mut self) {
+        self.0.reset()
+    }
+}
+
+/// 32-bit CRC
+#[deriving(Clone)]
+pub struct Crc32(Engine);
+
+impl Crc32 {
+    /// CRC with the given parameters, `poly` is given in its normal (non reflected) form
+    pub fn new(poly: u32, init: u32, xorout: u32, reflected: bool) -> Crc32 {
+        Crc32(Engine::new(32, poly, init, xorout, reflected))
+    }
+
+    /// CRC-32 (IEEE 802.3), as used by Ethernet, zlib and ZMODEM
+    pub fn ieee() -> Crc32 {
+        Crc32::new(0x04C1_1DB7, 0xFFFF_FFFF, 0xFFFF_FFFF, true)
+    }
+
+    /// Returns the CRC of the data fed so far
+    pub fn value(&self) -> u32 {
+        self.0.value()
+    }
+}
+
+impl Checksum for Crc32 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        self.0.bytes()
+    }
+
+    fn size(&self) -> uint {
+        self.0.size()
+    }
+
+    fn matches(&self, expected: &[u8]) -> bool {
+        self.0.matches(expected)
+    }
+
+    fn reset(&mut self) {
+        self.0.reset()
+    }
+}
+
+/// 8-bit Fletcher checksum, transmitted as `CK_A` followed by `CK_B`
+///
+/// Used by the u-blox UBX protocol
+#[deriving(Clone)]
+pub struct Fletcher8 {
+    a: u8,
+    b: u8,
+}
+
+impl Fletcher8 {
+    /// Creates a checksum over no data
+    pub fn new() -> Fletcher8 {
+        Fletcher8 { a: 0, b: 0 }
+    }
+
+    /// Returns the `(CK_A, CK_B)` pair of the data fed so far
+    pub fn value(&self) -> (u8, u8) {
+        (self.a, self.b)
+    }
+}
+
+impl Checksum for Fletcher8 {
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data.iter() {
+            self.a += byte;
+            self.b += self.a;
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        vec![self.a, self.b]
+    }
+
+    fn size(&self) -> uint {
+        2
+    }
+
+    fn matches(&self, expected: &[u8]) -> bool {
+        expected == [self.a, self.b].as_slice()
+    }
+
+    fn reset(&mut self) {
+        self.a = 0;
+        self.b = 0;
+    }
+}
+
+/// Longitudinal redundancy check, the two's complement of the sum of all the bytes
+///
+/// Used by Modbus ASCII
+#[deriving(Clone)]
+pub struct Lrc {
+    sum: u8,
+}
+
+impl Lrc {
+    /// Creates an LRC over no data
+    pub fn new() -> Lrc {
+        Lrc { sum: 0 }
+    }
+
+    /// Returns the LRC of the data fed so far
+    pub fn value(&self) -> u8 {
+        !self.sum + 1
+    }
+}
+
+impl Checksum for Lrc {
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data.iter() {
+            self.sum += byte;
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        vec![self.value()]
+    }
+
+    fn size(&self) -> uint {
+        1
+    }
+
+    fn matches(&self, expected: &[u8]) -> bool {
+        expected == [self.value()].as_slice()
+    }
+
+    fn reset(&mut self) {
+        self.sum = 0;
+    }
+}
+
+/// Exclusive or of all the bytes
+///
+/// Used by NMEA 0183
+#[deriving(Clone)]
+pub struct Xor {
+    value: u8,
+}
+
+impl Xor {
+    /// Creates a XOR over no data
+    pub fn new() -> Xor {
+        Xor { value: 0 }
+    }
+
+    /// Returns the XOR of the data fed so far
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+}
+
+impl Checksum for Xor {
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data.iter() {
+            self.value ^= byte;
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        vec![self.value]
+    }
+
+    fn size(&self) -> uint {
+        1
+    }
+
+    fn matches(&self, expected: &[u8]) -> bool {
+        expected == [self.value].as_slice()
+    }
+
+    fn reset(&mut self) {
+        self.value = 0;
+    }
+}
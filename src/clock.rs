@@ -0,0 +1,88 @@
+use std::cmp;
+use std::io::timer;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time;
+
+/// A clock that only moves when told to, for the simulated devices of the `mock`, `replay` and
+/// `sim` modules
+///
+/// With a virtual clock, the delays of a simulated device don't take real time: a read that
+/// has to wait for data moves the clock forward to the arrival of the data, or by the timeout
+/// of the port if it elapses first, and returns at once. Tests of timeout and retry logic run
+/// instantly, and give the same results on a loaded machine. `advance` stands for the time the
+/// application spends between the operations. The handles of a clock are clones, they all move
+/// together.
+///
+/// ```ignore
+/// let clock = VirtualClock::new();
+/// let mut port = MockPort::new(Strict).clock(clock.clone())
+///     .rule(Rule::on(b"PING").reply(b"PONG").after(Duration::seconds(2)));
+/// port.set_timeout(Some(Duration::seconds(1)));
+/// try!(port.write(b"PING"));
+/// assert!(port.read_exact(4).is_err());        // 1 s later
+/// assert_eq!(port.read_exact(4), Ok(b"PONG".to_vec()));  // 2 s later
+/// assert_eq!(clock.now(), Duration::seconds(2));
+/// ```
+#[deriving(Clone)]
+pub struct VirtualClock {
+    /// Nanoseconds elapsed since the creation of the clock, 64 bits wide on every target
+    ns: Arc<Mutex<u64>>,
+}
+
+impl VirtualClock {
+    /// A clock at zero
+    pub fn new() -> VirtualClock {
+        VirtualClock { ns: Arc::new(Mutex::new(0)) }
+    }
+
+    /// Returns the time elapsed since the creation of the clock
+    pub fn now(&self) -> Duration {
+        Duration::nanoseconds(self.now_ns() as i64)
+    }
+
+    /// Moves the clock forward by `duration`, negative durations are ignored
+    pub fn advance(&self, duration: Duration) {
+        self.advance_ns(nanoseconds(duration));
+    }
+
+    fn now_ns(&self) -> u64 {
+        *self.ns.lock()
+    }
+
+    fn advance_ns(&self, ns: u64) {
+        *self.ns.lock() += ns;
+    }
+}
+
+/// Where a simulated device takes the time from
+#[deriving(Clone)]
+pub enum TimeSource {
+    /// The `time::precise_time_ns` clock, waits sleep
+    RealTime,
+    /// A virtual clock, waits move it forward
+    Virtual(VirtualClock),
+}
+
+impl TimeSource {
+    /// Returns the current time in nanoseconds, from an arbitrary origin
+    pub fn now(&self) -> u64 {
+        match *self {
+            RealTime => time::precise_time_ns(),
+            Virtual(ref clock) => clock.now_ns(),
+        }
+    }
+
+    /// Waits for `ns` nanoseconds
+    pub fn sleep(&self, ns: u64) {
+        match *self {
+            RealTime => timer::sleep(Duration::nanoseconds(ns as i64)),
+            Virtual(ref clock) => clock.advance_ns(ns),
+        }
+    }
+}
+
+/// Converts `duration` to nanoseconds, negative durations to 0
+pub fn nanoseconds(duration: Duration) -> u64 {
+    cmp::max(duration.num_nanoseconds().unwrap_or(0), 0) as u64
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use ioctl::SerialStruct;
+#[cfg(target_os = "linux")]
+use libc::c_int;
+#[cfg(target_os = "linux")]
+use std::io::{InvalidInput, IoError, IoResult};
+
+/// The bits of `flags` that select the speed
+#[cfg(target_os = "linux")]
+pub const ASYNC_SPD_MASK: c_int = 0x1030;
+/// Speed bits meaning that the 38400 baud rate uses `custom_divisor`
+#[cfg(target_os = "linux")]
+pub const ASYNC_SPD_CUST: c_int = 0x0030;
+
+/// `closing_wait` meaning that `close` waits until the output is drained
+#[cfg(target_os = "linux")]
+const CLOSING_WAIT_INF: u16 = 0;
+/// `closing_wait` meaning that `close` doesn't wait at all
+#[cfg(target_os = "linux")]
+const CLOSING_WAIT_NONE: u16 = 65535;
+
+/// What the serial driver reports about the hardware behind a port, see
+/// `SerialPort::driver_info`
+#[deriving(Clone, PartialEq, Show)]
+pub struct DriverInfo {
+    /// UART model, one of the `PORT_*` constants of `linux/serial.h`, see `uart_name`
+    pub uart_type: uint,
+    /// Index of the port in its driver
+    pub line: uint,
+    /// I/O port address, 0 for memory mapped and USB devices
+    pub port: uint,
+    pub irq: uint,
+    /// The `ASYNC_*` flags of `linux/serial.h`
+    pub flags: uint,
+    /// Size of the transmit FIFO in bytes
+    pub xmit_fifo_size: uint,
+    /// Clock of the baud rate generator, divided by 16
+    pub baud_base: uint,
+    /// Divisor of `baud_base` used when the `ASYNC_SPD_CUST` flag is set
+    pub custom_divisor: uint,
+    /// How long DTR stays low after the port is closed
+    pub close_delay: Duration,
+    /// How long `close` waits for the output to drain, `None` for as long as it takes
+    pub closing_wait: Option<Duration>,
+}
+
+impl DriverInfo {
+    /// Names the UART model, `None` if it's unknown
+    pub fn uart_name(&self) -> Option<&'static str> {
+        Some(match self.uart_type {
+            1 => "8250",
+            2 => "16450",
+            3 => "16550",
+            4 => "16550A",
+            5 => "Cirrus",
+            6 => "16650",
+            7 => "16650V2",
+            8 => "16750",
+            9 => "Startech",
+            10 => "16C950",
+            11 => "16654",
+            12 => "16850",
+            13 => "RSA",
+            _ => return None,
+        })
+    }
+}
+
+/// Converts the structure filled by `TIOCGSERIAL`
+#[cfg(target_os = "linux")]
+pub fn from_serial_struct(serial: &SerialStruct) -> DriverInfo {
+    DriverInfo {
+        uart_type: serial.type_ as uint,
+        line: serial.line as uint,
+        port: serial.port as uint,
+        irq: serial.irq as uint,
+        flags: serial.flags as uint,
+        xmit_fifo_size: serial.xmit_fifo_size as uint,
+        baud_base: serial.baud_base as uint,
+        custom_divisor: serial.custom_divisor as uint,
+        close_delay: Duration::milliseconds(serial.close_delay as i64 * 10),
+        closing_wait: closing_wait(serial.closing_wait),
+    }
+}
+
+/// Decodes a `closing_wait`, in hundredths of seconds
+#[cfg(target_os = "linux")]
+fn closing_wait(raw: u16) -> Option<Duration> {
+    match raw {
+        CLOSING_WAIT_INF => None,
+        CLOSING_WAIT_NONE => Some(Duration::zero()),
+        centiseconds => Some(Duration::milliseconds(centiseconds as i64 * 10)),
+    }
+}
+
+/// Encodes a `closing_wait`, see `SerialPort::set_closing_wait`
+#[cfg(target_os = "linux")]
+pub fn raw_closing_wait(wait: Option<Duration>) -> IoResult<u16> {
+    let wait = match wait {
+        None => return Ok(CLOSING_WAIT_INF),
+        Some(wait) => wait,
+    };
+
+    match (wait.num_milliseconds() + 9) / 10 {
+        centiseconds if centiseconds <= 0 => Ok(CLOSING_WAIT_NONE),
+        centiseconds if centiseconds < CLOSING_WAIT_NONE as i64 => Ok(centiseconds as u16),
+        _ => Err(IoError {
+            kind: InvalidInput,
+            desc: "The closing wait can't exceed 655.34 seconds",
+            detail: Some(format!("got {}", wait)),
+        }),
+    }
+}
@@ -0,0 +1,50 @@
+use libc::c_int;
+
+#[cfg(target_os = "linux")]
+use ioctl::SerialIcounter;
+use ioctl::{TIOCM_CAR, TIOCM_CTS, TIOCM_DSR, TIOCM_RNG};
+use Timestamp;
+
+/// A modem status input, whose transitions `SerialPort::wait_for_edge` reports
+#[deriving(Clone, PartialEq, Show)]
+pub enum StatusLine {
+    /// Data Carrier Detect, the input that GPS receivers usually pulse once per second
+    CarrierDetect,
+    ClearToSend,
+    DataSetReady,
+    RingIndicator,
+}
+
+/// A transition of a status line, see `SerialPort::wait_for_edge`
+#[deriving(Clone, PartialEq, Show)]
+pub struct Edge {
+    pub line: StatusLine,
+    /// Level read right after the wakeup; after a pulse shorter than the wakeup latency, it's
+    /// the level the line went back to
+    pub level: bool,
+    /// Transitions counted by the driver during the wait, more than 1 when the line bounced
+    pub transitions: uint,
+    /// When the wakeup happened, `precise_ns` is the most accurate
+    pub timestamp: Timestamp,
+}
+
+/// The `TIOCM_*` bit of `line`
+pub fn bit(line: StatusLine) -> c_int {
+    match line {
+        CarrierDetect => TIOCM_CAR,
+        ClearToSend => TIOCM_CTS,
+        DataSetReady => TIOCM_DSR,
+        RingIndicator => TIOCM_RNG,
+    }
+}
+
+/// The counter of `TIOCGICOUNT` for `line`
+#[cfg(target_os = "linux")]
+pub fn transitions(line: StatusLine, counters: &SerialIcounter) -> c_int {
+    match line {
+        CarrierDetect => counters.dcd,
+        ClearToSend => counters.cts,
+        DataSetReady => counters.dsr,
+        RingIndicator => counters.rng,
+    }
+}
@@ -0,0 +1,240 @@
+//! ESC/POS, the command language of receipt printers
+//!
+//! Commands are escape sequences mixed with the text to print. `Receipt` builds a sequence of
+//! commands, `Printer` sends it and queries the real-time status of the printer.
+
+use std::cmp;
+use std::io::{InvalidInput, IoError, IoResult};
+
+const LF: u8 = 0x0A;
+const DLE: u8 = 0x10;
+const EOT: u8 = 0x04;
+const ESC: u8 = 0x1B;
+const GS: u8 = 0x1D;
+
+/// Horizontal alignment of the printed lines
+#[deriving(Clone, PartialEq, Show)]
+pub enum Alignment {
+    AlignLeft = 0,
+    AlignCenter = 1,
+    AlignRight = 2,
+}
+
+/// Barcode symbologies, the values select them in the `GS k` command
+#[deriving(Clone, PartialEq, Show)]
+pub enum Symbology {
+    UpcA = 65,
+    UpcE = 66,
+    Ean13 = 67,
+    Ean8 = 68,
+    Code39 = 69,
+    Itf = 70,
+    Codabar = 71,
+    Code93 = 72,
+    /// The data must start with the code set, e.g. `{B` for code set B
+    Code128 = 73,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum Cut {
+    FullCut = 65,
+    /// Leaves one point uncut
+    PartialCut = 66,
+}
+
+/// Builds a sequence of ESC/POS commands
+///
+/// ```ignore
+/// let receipt = Receipt::new().init().align(AlignCenter).bold(true).line("RECEIPT")
+///     .bold(false).line("1 x Coffee  2.50").cut(PartialCut);
+/// ```
+#[deriving(Clone, PartialEq, Show)]
+pub struct Receipt {
+    bytes: Vec<u8>,
+}
+
+impl Receipt {
+    /// Creates an empty sequence
+    pub fn new() -> Receipt {
+        Receipt {
+            bytes: Vec::new(),
+        }
+    }
+
+    /// Returns the encoded commands
+    pub fn as_slice(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+
+    /// Unwraps the encoded commands
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Aligns the following lines, the alignment applies from the start of a line
+    pub fn align(self, alignment: Alignment) -> Receipt {
+        self.raw(&[ESC, b'a', alignment as u8])
+    }
+
+    /// Prints a barcode of `data`, with its human readable text below
+    ///
+    /// The data is limited to 255 bytes, the printer ignores data it can't encode.
+    pub fn barcode(self, symbology: Symbology, data: &[u8]) -> Receipt {
+        let data = data.slice_to(cmp::min(data.len(), 255));
+
+        self.raw(&[GS, b'H', 2, GS, b'k', symbology as u8, data.len() as u8]).raw(data)
+    }
+
+    /// Changes the height of the barcodes, in dots
+    pub fn barcode_height(self, dots: u8) -> Receipt {
+        self.raw(&[GS, b'h', dots])
+    }
+
+    /// Turns emphasized (bold) printing on or off
+    pub fn bold(self, enable: bool) -> Receipt {
+        self.raw(&[ESC, b'E', enable as u8])
+    }
+
+    /// Feeds the paper up to the cutting position, and cuts it
+    pub fn cut(self, cut: Cut) -> Receipt {
+        self.raw(&[GS, b'V', cut as u8, 0])
+    }
+
+    /// Prints the buffer, and feeds the paper by `lines`
+    pub fn feed(self, lines: u8) -> Receipt {
+        self.raw(&[ESC, b'd', lines])
+    }
+
+    /// Resets the printer settings to their defaults, and clears the print buffer
+    pub fn init(self) -> Receipt {
+        self.raw(&[ESC, b'@'])
+    }
+
+    /// Adds `text` followed by a line feed, see `text`
+    pub fn line(self, text: &str) -> Receipt {
+        self.text(text).raw(&[LF])
+    }
+
+    /// Adds bytes as is, for commands that this builder doesn't cover
+    pub fn raw(mut self, bytes: &[u8]) -> Receipt {
+        self.bytes.push_all(bytes);
+        self
+    }
+
+    /// Scales the characters by `width` and `height`, each from 1 to 8
+    pub fn size(self, width: u8, height: u8) -> Receipt {
+        let scale = |factor: u8| cmp::min(cmp::max(factor, 1), 8) - 1;
+
+        self.raw(&[GS, b'!', scale(width) << 4 | scale(height)])
+    }
+
+    /// Adds `text` to print
+    ///
+    /// Code pages vary between printers, so characters outside of printable ASCII (and `\n`)
+    /// are replaced by `?`. Use `raw` to print text in the code page of the printer.
+    pub fn text(self, text: &str) -> Receipt {
+        let bytes: Vec<u8> = text.chars().map(|c| {
+            if c == '\n' || c >= ' ' && c <= '~' { c as u8 } else { b'?' }
+        }).collect();
+
+        self.raw(bytes.as_slice())
+    }
+
+    /// Turns underlining on or off
+    pub fn underline(self, enable: bool) -> Receipt {
+        self.raw(&[ESC, b'-', enable as u8])
+    }
+}
+
+/// Real-time status of a printer
+#[deriving(Clone, PartialEq, Show)]
+pub struct Status {
+    /// Pin 3 of the drawer kick-out connector is high, usually the drawer is open
+    pub drawer_open: bool,
+    pub offline: bool,
+    pub cover_open: bool,
+    /// The paper feed button is pressed
+    pub feed_pressed: bool,
+    pub paper_near_end: bool,
+    pub paper_end: bool,
+    pub cutter_error: bool,
+    /// Error that requires turning the printer off and on
+    pub unrecoverable_error: bool,
+    /// Error that clears by itself, like the head getting too hot
+    pub recoverable_error: bool,
+}
+
+impl Status {
+    /// Parses the answers to the `DLE EOT 1` to `DLE EOT 4` status requests, `None` if one of
+    /// them isn't a status byte
+    pub fn parse(answers: &[u8, ..4]) -> Option<Status> {
+        if answers.iter().any(|&byte| byte & 0x93 != 0x12) {
+            return None
+        }
+
+        let (printer, offline, error, paper) = (answers[0], answers[1], answers[2], answers[3]);
+        let bit = |byte: u8, n: uint| byte & (1 << n) != 0;
+
+        Some(Status {
+            drawer_open: bit(printer, 2),
+            offline: bit(printer, 3),
+            cover_open: bit(offline, 2),
+            feed_pressed: bit(offline, 3),
+            paper_near_end: bit(paper, 2) || bit(paper, 3),
+            paper_end: bit(paper, 5) || bit(paper, 6) || bit(offline, 5),
+            cutter_error: bit(error, 3),
+            unrecoverable_error: bit(error, 5),
+            recoverable_error: bit(error, 6),
+        })
+    }
+}
+
+/// A receipt printer connected to a port
+pub struct Printer<S> {
+    port: S,
+}
+
+impl<S: Reader + Writer> Printer<S> {
+    pub fn new(port: S) -> Printer<S> {
+        Printer {
+            port: port,
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.port
+    }
+
+    /// Unwraps the port
+    pub fn into_inner(self) -> S {
+        self.port
+    }
+
+    /// Sends the commands of `receipt`
+    pub fn print(&mut self, receipt: &Receipt) -> IoResult<()> {
+        self.port.write(receipt.as_slice())
+    }
+
+    /// Queries the real-time status of the printer
+    ///
+    /// The printer answers even while it's busy or offline. Use a timeout on the port to bound
+    /// the wait.
+    pub fn status(&mut self) -> IoResult<Status> {
+        let mut answers = [0u8, ..4];
+
+        for (i, answer) in answers.iter_mut().enumerate() {
+            try!(self.port.write(&[DLE, EOT, i as u8 + 1]));
+            *answer = try!(self.port.read_byte());
+        }
+
+        match Status::parse(&answers) {
+            None => Err(IoError {
+                kind: InvalidInput,
+                desc: "Invalid status byte",
+                detail: Some(format!("{}", answers.as_slice())),
+            }),
+            Some(status) => Ok(status),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use libc::c_int;
+use std::cmp;
+use std::io::{IoError, IoResult, TimedOut};
+use std::io::timer;
+use std::time::Duration;
+use time;
+
+use ioctl::{TIOCM_CAR, TIOCM_CTS, TIOCM_DSR, TIOCM_RNG};
+use poll;
+use {ReadCanceller, SerialPort};
+
+/// How often the status inputs are sampled, when no data arrives
+const TICK_MS: i64 = 20;
+
+/// Position of the break count in the `counters`
+const BREAKS: uint = 4;
+
+bitflags! {
+    #[doc = "Events of `SerialPort::wait_for_events`"]
+    #[doc = ""]
+    #[doc = "The values are those of the `EV_*` masks of `WaitCommEvent` on Windows."]
+    flags EventSet: u32 {
+        #[doc = "Data can be read"]
+        const RX_AVAILABLE = 0x0001,
+        #[doc = "The output queue is empty"]
+        const TX_EMPTY = 0x0004,
+        #[doc = "The Clear To Send input changed"]
+        const CTS_CHANGED = 0x0008,
+        #[doc = "The Data Set Ready input changed"]
+        const DSR_CHANGED = 0x0010,
+        #[doc = "The Data Carrier Detect input changed"]
+        const CD_CHANGED = 0x0020,
+        #[doc = "A break was received"]
+        const BREAK_RECEIVED = 0x0040,
+        #[doc = "A framing, parity or overrun error occurred"]
+        const LINE_ERROR = 0x0080,
+        #[doc = "The Ring Indicator input changed"]
+        const RI_CHANGED = 0x0100,
+    }
+}
+
+/// Levels of the modem status inputs
+#[deriving(Clone, PartialEq, Show)]
+pub struct ModemStatus {
+    pub cts: bool,
+    pub dsr: bool,
+    /// Data Carrier Detect
+    pub dcd: bool,
+    pub ri: bool,
+}
+
+/// Callbacks of `SerialPort::run_events`
+///
+/// Only `on_data` is required, the other events are ignored by default.
+pub trait EventHandler {
+    /// Called with each chunk of received data
+    fn on_data(&mut self, data: &[u8]);
+
+    /// Called when a break is received, after the data that preceded it
+    ///
+    /// The drivers only count the breaks on Linux, elsewhere this isn't called.
+    fn on_break(&mut self) {}
+
+    /// Called when a status input changes, with the levels of all of them
+    fn on_modem_status(&mut self, _status: &ModemStatus) {}
+
+    /// Called when a read fails, returns whether to carry on
+    ///
+    /// By default the loop stops, and returns the error.
+    fn on_error(&mut self, _error: &IoError) -> bool {
+        false
+    }
+}
+
+/// Runs the loop of `SerialPort::run_events`
+pub fn run<H: EventHandler>(port: &mut SerialPort, handler: &mut H) -> IoResult<()> {
+    let timeout = port.timeout();
+    port.set_timeout(Some(Duration::milliseconds(TICK_MS)));
+
+    let result = event_loop(port, handler);
+    port.set_timeout(timeout);
+
+    result
+}
+
+fn event_loop<H: EventHandler>(port: &mut SerialPort, handler: &mut H) -> IoResult<()> {
+    let mut buf = [0u8, ..256];
+    // Ptys have no status inputs nor counters, their events are skipped
+    let mut status = modem_status(port).ok();
+    let mut breaks = counters(port).map(|counters| counters[BREAKS]);
+
+    loop {
+        match port.read(&mut buf) {
+            Err(ref e) if e.kind == TimedOut => {},
+            Err(ref e) if ReadCanceller::is_cancellation(e) => return Ok(()),
+            Err(e) => if !handler.on_error(&e) {
+                return Err(e)
+            },
+            Ok(n) => handler.on_data(buf.slice_to(n)),
+        }
+
+        let count = counters(port).map(|counters| counters[BREAKS]);
+        match (breaks, count) {
+            (Some(before), Some(after)) if after != before => handler.on_break(),
+            _ => {},
+        }
+        breaks = count;
+
+        if status.is_some() {
+            let now = modem_status(port).ok();
+            if now.is_some() && now != status {
+                handler.on_modem_status(now.as_ref().unwrap());
+                status = now;
+            }
+        }
+    }
+}
+
+/// Runs `SerialPort::wait_for_events`
+pub fn wait(port: &mut SerialPort, events: EventSet, timeout: Option<Duration>)
+            -> IoResult<EventSet> {
+    let deadline = timeout.map(|timeout| {
+        time::precise_time_ns() + cmp::max(timeout.num_nanoseconds().unwrap_or(0), 0) as u64
+    });
+    let before = snapshot(port);
+
+    loop {
+        let mut ready = changes(&before, &snapshot(port));
+        if events.contains(RX_AVAILABLE) && try!(port.readable()) {
+            ready.insert(RX_AVAILABLE);
+        }
+        if events.contains(TX_EMPTY) && try!(port.output_queue()) == 0 {
+            ready.insert(TX_EMPTY);
+        }
+
+        let ready = ready & events;
+        if !ready.is_empty() {
+            return Ok(ready)
+        }
+
+        let now = time::precise_time_ns();
+        let tick = match deadline {
+            Some(deadline) if deadline <= now => return Err(IoError {
+                kind: TimedOut,
+                desc: "None of the events occurred in time",
+                detail: None,
+            }),
+            Some(deadline) => cmp::min(deadline - now, TICK_MS as u64 * 1_000_000),
+            None => TICK_MS as u64 * 1_000_000,
+        };
+        let tick = Duration::nanoseconds(tick as i64);
+
+        // Data wakes the wait up early, the other events are sampled
+        if events.contains(RX_AVAILABLE) {
+            try!(poll::wait(port.fd, poll::POLLIN, Some(tick)));
+        } else {
+            timer::sleep(tick);
+        }
+    }
+}
+
+/// The state the events are detected against
+struct Snapshot {
+    /// `TIOCM_*` levels, `None` without status inputs, e.g. on ptys
+    lines: Option<c_int>,
+    /// Transitions of CTS, DSR, CD and RI, breaks and line errors, only counted on Linux
+    counters: Option<[c_int, ..6]>,
+}
+
+fn snapshot(port: &SerialPort) -> Snapshot {
+    Snapshot {
+        lines: port.modem_lines().ok(),
+        counters: counters(port),
+    }
+}
+
+/// The events between `before` and `after`, except the data and output events
+fn changes(before: &Snapshot, after: &Snapshot) -> EventSet {
+    let mut events = EventSet::empty();
+
+    match (before.counters, after.counters) {
+        (Some(before), Some(after)) => {
+            let counted = [CTS_CHANGED, DSR_CHANGED, CD_CHANGED, RI_CHANGED, BREAK_RECEIVED,
+                           LINE_ERROR];
+
+            for (i, &event) in counted.iter().enumerate() {
+                if after[i] != before[i] {
+                    events.insert(event);
+                }
+            }
+        },
+        _ => {},
+    }
+
+    // Catches the changes where the drivers don't count them
+    match (before.lines, after.lines) {
+        (Some(before), Some(after)) => {
+            let lines = [(TIOCM_CTS, CTS_CHANGED), (TIOCM_DSR, DSR_CHANGED),
+                         (TIOCM_CAR, CD_CHANGED), (TIOCM_RNG, RI_CHANGED)];
+
+            for &(bit, event) in lines.iter() {
+                if (before ^ after) & bit != 0 {
+                    events.insert(event);
+                }
+            }
+        },
+        _ => {},
+    }
+
+    events
+}
+
+fn modem_status(port: &SerialPort) -> IoResult<ModemStatus> {
+    let lines = try!(port.modem_lines());
+
+    Ok(ModemStatus {
+        cts: lines & TIOCM_CTS != 0,
+        dsr: lines & TIOCM_DSR != 0,
+        dcd: lines & TIOCM_CAR != 0,
+        ri: lines & TIOCM_RNG != 0,
+    })
+}
+
+/// Returns the transitions of CTS, DSR, CD and RI, the breaks and the line errors since the
+/// port was opened, if the driver counts them
+#[cfg(target_os = "linux")]
+fn counters(port: &SerialPort) -> Option<[c_int, ..6]> {
+    port.icount().ok().map(|c| {
+        [c.cts, c.dsr, c.dcd, c.rng, c.brk, c.frame + c.overrun + c.parity + c.buf_overrun]
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn counters(_: &SerialPort) -> Option<[c_int, ..6]> {
+    None
+}
@@ -0,0 +1,166 @@
+//! Expect-style scripting of interactive devices
+//!
+//! Router consoles, modems and boot loaders are driven by waiting for a prompt and answering
+//! it. `Expect` waits for literal or regular expression patterns in the output of the device,
+//! and keeps what came before them.
+//!
+//! ```ignore
+//! let mut session = Expect::new(port);
+//! let output = try!(session.run(&[("login:", "admin\r"), ("Password:", "hunter2\r")],
+//!                               Duration::seconds(5)));
+//! ```
+
+use regex::Regex;
+use std::cmp;
+use std::i64;
+use std::io::{IoError, IoResult, TimedOut};
+use std::time::Duration;
+use time;
+
+use SerialIo;
+
+/// Largest amount of unmatched output kept in the error of a failed `expect`
+const MAX_DETAIL_LEN: uint = 80;
+
+/// What to wait for in the output
+#[deriving(Clone)]
+pub enum Pattern {
+    LiteralPattern(String),
+    RegexPattern(Regex),
+}
+
+impl Pattern {
+    /// Finds the first match in `text`, returns its start and end
+    fn find(&self, text: &str) -> Option<(uint, uint)> {
+        match *self {
+            LiteralPattern(ref literal) => {
+                text.find_str(literal.as_slice()).map(|start| (start, start + literal.len()))
+            },
+            RegexPattern(ref regex) => regex.find(text),
+        }
+    }
+
+    /// Describes the pattern, for error messages
+    fn describe(&self) -> String {
+        match *self {
+            LiteralPattern(ref literal) => literal.clone(),
+            RegexPattern(ref regex) => format!("/{}/", regex),
+        }
+    }
+}
+
+/// Output of the device that matched a pattern
+#[deriving(Clone, PartialEq, Show)]
+pub struct Match {
+    /// Output received before the match
+    pub before: String,
+    /// The matching output
+    pub matched: String,
+}
+
+/// Scripts the interaction with a device
+///
+/// The session takes over the timeout of the port. The output is decoded as UTF-8, invalid
+/// sequences are replaced.
+pub struct Expect<S> {
+    port: S,
+    /// Output not consumed by a match yet
+    buf: String,
+}
+
+impl<S: SerialIo> Expect<S> {
+    pub fn new(port: S) -> Expect<S> {
+        Expect {
+            port: port,
+            buf: String::new(),
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.port
+    }
+
+    /// Unwraps the port, discarding the unmatched output
+    pub fn into_inner(self) -> S {
+        self.port
+    }
+
+    /// Waits up to `timeout` for the `pattern`, consumes the output up to the end of the match
+    ///
+    /// If the pattern doesn't show up in time, the output is kept and a `TimedOut` error is
+    /// returned, with the end of the output as its detail.
+    pub fn expect(&mut self, pattern: &Pattern, timeout: Duration) -> IoResult<Match> {
+        let timeout = timeout.num_nanoseconds().unwrap_or(i64::MAX);
+        let deadline = time::precise_time_ns() + if timeout < 0 { 0 } else { timeout as u64 };
+
+        loop {
+            match pattern.find(self.buf.as_slice()) {
+                None => {},
+                Some((start, end)) => {
+                    let found = Match {
+                        before: self.buf.as_slice().slice_to(start).to_string(),
+                        matched: self.buf.as_slice().slice(start, end).to_string(),
+                    };
+                    self.buf = self.buf.as_slice().slice_from(end).to_string();
+
+                    return Ok(found)
+                },
+            }
+
+            let now = time::precise_time_ns();
+            if now >= deadline {
+                let chars: Vec<char> = self.buf.as_slice().chars().collect();
+                let skip = chars.len() - cmp::min(chars.len(), MAX_DETAIL_LEN);
+                let tail: String = chars.slice_from(skip).iter().map(|&c| c).collect();
+
+                return Err(IoError {
+                    kind: TimedOut,
+                    desc: "Pattern not found in the output",
+                    detail: Some(format!("{}: {}", pattern.describe(),
+                                         tail.as_slice().escape_default())),
+                })
+            }
+
+            self.port.set_timeout(Some(Duration::nanoseconds((deadline - now) as i64)));
+
+            let mut chunk = [0u8, ..256];
+            match self.port.read(&mut chunk) {
+                Err(ref e) if e.kind == TimedOut => {},
+                Err(e) => return Err(e),
+                Ok(n) => {
+                    let text = String::from_utf8_lossy(chunk.slice_to(n));
+                    self.buf.push_str(text.as_slice());
+                },
+            }
+        }
+    }
+
+    /// Waits for a literal `pattern`, see `expect`
+    pub fn expect_literal(&mut self, pattern: &str, timeout: Duration) -> IoResult<Match> {
+        self.expect(&LiteralPattern(pattern.to_string()), timeout)
+    }
+
+    /// Runs a script of literal prompts and their answers, waiting up to `timeout` for each
+    /// prompt
+    ///
+    /// Returns the output received until the last prompt, included.
+    pub fn run(&mut self, script: &[(&str, &str)], timeout: Duration) -> IoResult<String> {
+        let mut output = String::new();
+
+        for &(prompt, answer) in script.iter() {
+            let found = try!(self.expect_literal(prompt, timeout));
+            output.push_str(found.before.as_slice());
+            output.push_str(found.matched.as_slice());
+
+            try!(self.send(answer));
+        }
+
+        Ok(output)
+    }
+
+    /// Sends `text` as is, without adding any line terminator
+    pub fn send(&mut self, text: &str) -> IoResult<()> {
+        self.port.write_str(text)
+    }
+}
@@ -0,0 +1,179 @@
+use libc::{c_int, c_void, size_t, socklen_t, ssize_t};
+use libc::funcs::posix88::fcntl::fcntl;
+use libc::funcs::posix88::unistd::close;
+use std::io::{EndOfFile, InvalidInput, IoError, IoResult};
+use std::mem;
+use std::ptr;
+
+use termios::FAILURE;
+
+const F_SETFD: c_int = 2;
+const FD_CLOEXEC: c_int = 1;
+const SCM_RIGHTS: c_int = 1;
+
+#[cfg(target_os = "linux")]
+mod os {
+    use libc::{c_int, size_t};
+
+    pub type IovLen = size_t;
+    pub type ControlLen = size_t;
+
+    pub const SOL_SOCKET: c_int = 1;
+    pub const MSG_CTRUNC: c_int = 0x08;
+    /// The received descriptor is closed on exec from the start
+    pub const MSG_CMSG_CLOEXEC: c_int = 0x40000000;
+    /// `cmsghdr` are aligned on `size_t`
+    pub const CMSG_ALIGN: uint = 8;
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use libc::{c_int, socklen_t};
+
+    pub type IovLen = c_int;
+    pub type ControlLen = socklen_t;
+
+    pub const SOL_SOCKET: c_int = 0xFFFF;
+    pub const MSG_CTRUNC: c_int = 0x20;
+    /// Not supported, close on exec is set once the descriptor is received
+    pub const MSG_CMSG_CLOEXEC: c_int = 0;
+    /// `cmsghdr` are aligned on 32 bits
+    pub const CMSG_ALIGN: uint = 4;
+}
+
+/// `struct iovec`
+#[repr(C)]
+struct IoVec {
+    base: *mut c_void,
+    len: size_t,
+}
+
+/// `struct msghdr`
+#[repr(C)]
+struct MsgHdr {
+    name: *mut c_void,
+    namelen: socklen_t,
+    iov: *mut IoVec,
+    iovlen: os::IovLen,
+    control: *mut c_void,
+    controllen: os::ControlLen,
+    flags: c_int,
+}
+
+/// `struct cmsghdr`, followed by the data of the message
+#[repr(C)]
+struct CmsgHdr {
+    len: os::ControlLen,
+    level: c_int,
+    type_: c_int,
+}
+
+extern {
+    fn recvmsg(socket: c_int, msg: *mut MsgHdr, flags: c_int) -> ssize_t;
+    fn sendmsg(socket: c_int, msg: *const MsgHdr, flags: c_int) -> ssize_t;
+}
+
+/// Rounds `len` up to the alignment of the control messages
+fn align(len: uint) -> uint {
+    (len + os::CMSG_ALIGN - 1) & !(os::CMSG_ALIGN - 1)
+}
+
+/// Offset of the data of a control message, `CMSG_DATA`
+fn data_offset() -> uint {
+    align(mem::size_of::<CmsgHdr>())
+}
+
+/// Receives a file descriptor sent over the UNIX `socket`, with `SCM_RIGHTS`
+///
+/// The sender has to send at least one byte of data along with the descriptor, like `send_fd`
+/// does; the byte is discarded. The descriptor received is closed on exec, and owned by the
+/// caller, see `SerialPort::from_received_fd`. Fails with an `EndOfFile` error if the socket
+/// was closed, and with an `InvalidInput` error if the message didn't carry a descriptor.
+pub fn receive_fd(socket: c_int) -> IoResult<c_int> {
+    let mut byte = 0u8;
+    let mut iov = IoVec { base: &mut byte as *mut u8 as *mut c_void, len: 1 };
+    // Room for the header and a descriptor, aligned for the header
+    let mut control = [0u64, ..4];
+    let mut msg = MsgHdr {
+        name: ptr::null_mut(),
+        namelen: 0,
+        iov: &mut iov,
+        iovlen: 1,
+        control: control.as_mut_ptr() as *mut c_void,
+        controllen: align(data_offset() + mem::size_of::<c_int>()) as os::ControlLen,
+        flags: 0,
+    };
+
+    match unsafe { recvmsg(socket, &mut msg, os::MSG_CMSG_CLOEXEC) } {
+        -1 => return Err(IoError::last_error()),
+        0 => return Err(IoError {
+            kind: EndOfFile,
+            desc: "The socket was closed",
+            detail: None,
+        }),
+        _ => {},
+    }
+
+    let header = unsafe { &*(control.as_ptr() as *const CmsgHdr) };
+    let fd = if msg.controllen as uint >= data_offset() + mem::size_of::<c_int>() &&
+                header.level == os::SOL_SOCKET && header.type_ == SCM_RIGHTS {
+        unsafe { *((control.as_ptr() as *const u8).offset(data_offset() as int) as *const c_int) }
+    } else {
+        FAILURE
+    };
+
+    if fd == FAILURE || msg.flags & os::MSG_CTRUNC != 0 {
+        if fd != FAILURE {
+            unsafe { close(fd) };
+        }
+
+        return Err(IoError {
+            kind: InvalidInput,
+            desc: "The message didn't carry a single file descriptor",
+            detail: None,
+        })
+    }
+
+    if unsafe { fcntl(fd, F_SETFD, FD_CLOEXEC) } == FAILURE {
+        let error = IoError::last_error();
+        unsafe { close(fd) };
+        return Err(error)
+    }
+
+    Ok(fd)
+}
+
+/// Sends the file descriptor `fd` over the UNIX `socket`, with `SCM_RIGHTS`, for `receive_fd`
+///
+/// This is the side of the process that opens the ports on behalf of others, like a broker
+/// running with the privileges the others lack. The descriptor stays open in the sender, which
+/// can close it once sent.
+pub fn send_fd(socket: c_int, fd: c_int) -> IoResult<()> {
+    let mut byte = 0u8;
+    let mut iov = IoVec { base: &mut byte as *mut u8 as *mut c_void, len: 1 };
+    let mut control = [0u64, ..4];
+    let len = data_offset() + mem::size_of::<c_int>();
+
+    unsafe {
+        let header = control.as_mut_ptr() as *mut CmsgHdr;
+        (*header).len = len as os::ControlLen;
+        (*header).level = os::SOL_SOCKET;
+        (*header).type_ = SCM_RIGHTS;
+        *((control.as_mut_ptr() as *mut u8).offset(data_offset() as int) as *mut c_int) = fd;
+    }
+
+    let msg = MsgHdr {
+        name: ptr::null_mut(),
+        namelen: 0,
+        iov: &mut iov,
+        iovlen: 1,
+        control: control.as_mut_ptr() as *mut c_void,
+        controllen: align(len) as os::ControlLen,
+        flags: 0,
+    };
+
+    match unsafe { sendmsg(socket, &msg, 0) } {
+        -1 => Err(IoError::last_error()),
+        _ => Ok(()),
+    }
+}
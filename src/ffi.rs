@@ -0,0 +1,249 @@
+//! C API, enabled by the `ffi` feature
+//!
+//! The functions return `SERIAL_OK` (zero) or a non negative count on success, and one of the
+//! negative `SERIAL_E*` codes on failure; `serial_strerror` describes the codes. Ports are
+//! opaque pointers, created by `serial_open` and released by `serial_close`. The matching
+//! declarations are in `include/serial.h`, the `capi` directory builds the libraries.
+//!
+//! Each call runs in a task of its own: a panic can't unwind into the C caller, it fails the
+//! call with `SERIAL_EPANIC` instead.
+
+use libc::{c_char, c_int, c_long, c_uint, size_t, ssize_t};
+use std::c_str::CString;
+use std::default::Default;
+use std::io::{EndOfFile, FileNotFound, InvalidInput, IoError, IoResult, PermissionDenied};
+use std::io::{ReadWrite, TimedOut};
+use std::mem;
+use std::slice::raw;
+use std::task;
+use std::time::Duration;
+
+use {BaudRate, SerialPort, Settings};
+use {Data5, Data6, Data7, Data8, EvenParity, MarkParity, NoParity, OddParity, SpaceParity};
+use {Stop1, Stop2};
+use {HardwareControl, NoFlowControl, SoftwareControl};
+
+pub const SERIAL_OK: c_int = 0;
+/// An argument is null or out of range
+pub const SERIAL_EINVAL: c_int = -1;
+/// Any other input/output error
+pub const SERIAL_EIO: c_int = -2;
+/// The read timeout elapsed without receiving data
+pub const SERIAL_ETIMEDOUT: c_int = -3;
+/// The device was closed or hung up
+pub const SERIAL_EEOF: c_int = -4;
+/// The device doesn't exist
+pub const SERIAL_ENOENT: c_int = -5;
+/// The device can't be opened with the current permissions
+pub const SERIAL_EACCES: c_int = -6;
+/// The library panicked, the port may be left in any state but can still be closed
+pub const SERIAL_EPANIC: c_int = -7;
+
+/// Opens the device at `path` for reading and writing, and stores the port in `*port`
+#[no_mangle]
+pub extern "C" fn serial_open(path: *const c_char, port: *mut *mut SerialPort) -> c_int {
+    guard(SERIAL_EPANIC, proc() {
+        if path.is_null() || port.is_null() {
+            return SERIAL_EINVAL
+        }
+
+        let path = unsafe { CString::new(path, false) };
+        match SerialPort::open(&Path::new(path.as_bytes_no_nul()), ReadWrite) {
+            Err(e) => code(&e),
+            Ok(opened) => {
+                let opened: Box<SerialPort> = box opened;
+                unsafe { *port = mem::transmute(opened) };
+                SERIAL_OK
+            },
+        }
+    })
+}
+
+/// Applies a frame format like 115200 8N1 to `port`
+///
+/// `parity` is one of `'N'`, `'E'`, `'O'`, `'M'` and `'S'`, `flow_control` one of `'N'` (none),
+/// `'H'` (RTS/CTS) and `'S'` (XON/XOFF). On Linux any `baud_rate` is accepted, elsewhere only
+/// the standard ones; the mark and space parities are only supported on Linux.
+#[no_mangle]
+pub extern "C" fn serial_configure(port: *mut SerialPort, baud_rate: c_uint, data_bits: c_uint,
+                                   parity: c_char, stop_bits: c_uint, flow_control: c_char)
+                                   -> c_int {
+    guard(SERIAL_EPANIC, proc() {
+        let port = match unsafe { port.as_mut() } {
+            None => return SERIAL_EINVAL,
+            Some(port) => port,
+        };
+
+        let mut settings: Settings = Default::default();
+        settings.data_bits = match data_bits {
+            5 => Data5,
+            6 => Data6,
+            7 => Data7,
+            8 => Data8,
+            _ => return SERIAL_EINVAL,
+        };
+        settings.parity = match parity as u8 {
+            b'E' => EvenParity,
+            b'M' => MarkParity,
+            b'N' => NoParity,
+            b'O' => OddParity,
+            b'S' => SpaceParity,
+            _ => return SERIAL_EINVAL,
+        };
+        settings.stop_bits = match stop_bits {
+            1 => Stop1,
+            2 => Stop2,
+            _ => return SERIAL_EINVAL,
+        };
+        settings.flow_control = match flow_control as u8 {
+            b'H' => HardwareControl,
+            b'N' => NoFlowControl,
+            b'S' => SoftwareControl,
+            _ => return SERIAL_EINVAL,
+        };
+
+        let result = match standard_baud_rate(baud_rate as uint) {
+            Some(rate) => {
+                settings.baud_rate = rate;
+                port.configure(&settings)
+            },
+            None => custom_baud_rate(port, &settings, baud_rate),
+        };
+
+        match result {
+            Err(e) => code(&e),
+            Ok(()) => SERIAL_OK,
+        }
+    })
+}
+
+/// Changes the read timeout of `port`, a negative `timeout_ms` makes the reads block
+/// indefinitely
+#[no_mangle]
+pub extern "C" fn serial_set_timeout(port: *mut SerialPort, timeout_ms: c_long) -> c_int {
+    guard(SERIAL_EPANIC, proc() {
+        let port = match unsafe { port.as_mut() } {
+            None => return SERIAL_EINVAL,
+            Some(port) => port,
+        };
+
+        let timeout = if timeout_ms < 0 {
+            None
+        } else {
+            Some(Duration::milliseconds(timeout_ms as i64))
+        };
+        port.set_timeout(timeout);
+
+        SERIAL_OK
+    })
+}
+
+/// Reads up to `len` bytes into `buf`, returns the number of bytes read
+#[no_mangle]
+pub extern "C" fn serial_read(port: *mut SerialPort, buf: *mut u8, len: size_t) -> ssize_t {
+    guard(SERIAL_EPANIC as ssize_t, proc() {
+        let port = match unsafe { port.as_mut() } {
+            None => return SERIAL_EINVAL as ssize_t,
+            Some(port) => port,
+        };
+
+        if buf.is_null() {
+            return SERIAL_EINVAL as ssize_t
+        }
+
+        let result = unsafe { raw::mut_buf_as_slice(buf, len as uint, |buf| port.read(buf)) };
+        match result {
+            Err(e) => code(&e) as ssize_t,
+            Ok(n) => n as ssize_t,
+        }
+    })
+}
+
+/// Writes the `len` bytes of `buf`, returns `len`
+#[no_mangle]
+pub extern "C" fn serial_write(port: *mut SerialPort, buf: *const u8, len: size_t) -> ssize_t {
+    guard(SERIAL_EPANIC as ssize_t, proc() {
+        let port = match unsafe { port.as_mut() } {
+            None => return SERIAL_EINVAL as ssize_t,
+            Some(port) => port,
+        };
+
+        if buf.is_null() {
+            return SERIAL_EINVAL as ssize_t
+        }
+
+        let result = unsafe { raw::buf_as_slice(buf, len as uint, |buf| port.write(buf)) };
+        match result {
+            Err(e) => code(&e) as ssize_t,
+            Ok(()) => len as ssize_t,
+        }
+    })
+}
+
+/// Closes `port`, which must not be used afterwards; null is ignored
+#[no_mangle]
+pub extern "C" fn serial_close(port: *mut SerialPort) {
+    guard((), proc() {
+        if !port.is_null() {
+            let _port: Box<SerialPort> = unsafe { mem::transmute(port) };
+        }
+    })
+}
+
+/// Returns a static, nul terminated description of the error `code`
+#[no_mangle]
+pub extern "C" fn serial_strerror(code: c_int) -> *const c_char {
+    let message: &'static [u8] = match code {
+        SERIAL_OK => b"Success\0",
+        SERIAL_EINVAL => b"Invalid argument\0",
+        SERIAL_EIO => b"Input/output error\0",
+        SERIAL_ETIMEDOUT => b"Operation timed out\0",
+        SERIAL_EEOF => b"End of file\0",
+        SERIAL_ENOENT => b"No such device\0",
+        SERIAL_EACCES => b"Permission denied\0",
+        SERIAL_EPANIC => b"Internal error\0",
+        _ => b"Unknown error\0",
+    };
+
+    message.as_ptr() as *const c_char
+}
+
+/// Maps an error to its code
+fn code(error: &IoError) -> c_int {
+    match error.kind {
+        EndOfFile => SERIAL_EEOF,
+        FileNotFound => SERIAL_ENOENT,
+        InvalidInput => SERIAL_EINVAL,
+        PermissionDenied => SERIAL_EACCES,
+        TimedOut => SERIAL_ETIMEDOUT,
+        _ => SERIAL_EIO,
+    }
+}
+
+/// Runs `body` in a task of its own, returns `panicked` if it panics rather than unwinding into
+/// the C caller
+fn guard<T: Send>(panicked: T, body: proc(): Send -> T) -> T {
+    task::try(body).unwrap_or(panicked)
+}
+
+/// The standard rate of the platform running at `rate` bits per second
+fn standard_baud_rate(rate: uint) -> Option<BaudRate> {
+    BaudRate::standard_rates().iter().map(|&standard| standard).find(|standard| {
+        standard.bits_per_second() == rate
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn custom_baud_rate(port: &mut SerialPort, settings: &Settings, rate: c_uint) -> IoResult<()> {
+    try!(port.configure(settings));
+    port.set_custom_baud_rate(rate as u32)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn custom_baud_rate(_: &mut SerialPort, _: &Settings, _: c_uint) -> IoResult<()> {
+    Err(IoError {
+        kind: InvalidInput,
+        desc: "Non standard baud rates are only supported on Linux",
+        detail: None,
+    })
+}
@@ -0,0 +1,401 @@
+//! Firmata client, to control boards running StandardFirmata
+//!
+//! Firmata borrows the framing of MIDI: a status byte with the most significant bit set,
+//! followed by 7-bit data bytes. Longer messages are sent as SysEx, between `0xF0` and `0xF7`.
+
+use std::collections::RingBuf;
+use std::io::IoResult;
+
+const ANALOG_MESSAGE: u8 = 0xE0;
+const DIGITAL_MESSAGE: u8 = 0x90;
+const REPORT_ANALOG: u8 = 0xC0;
+const REPORT_DIGITAL: u8 = 0xD0;
+const SET_PIN_MODE: u8 = 0xF4;
+const SET_DIGITAL_PIN_VALUE: u8 = 0xF5;
+const PROTOCOL_VERSION: u8 = 0xF9;
+const SYSTEM_RESET: u8 = 0xFF;
+const START_SYSEX: u8 = 0xF0;
+const END_SYSEX: u8 = 0xF7;
+
+// SysEx commands
+const ANALOG_MAPPING_QUERY: u8 = 0x69;
+const ANALOG_MAPPING_RESPONSE: u8 = 0x6A;
+const CAPABILITY_QUERY: u8 = 0x6B;
+const CAPABILITY_RESPONSE: u8 = 0x6C;
+const PIN_STATE_QUERY: u8 = 0x6D;
+const PIN_STATE_RESPONSE: u8 = 0x6E;
+const EXTENDED_ANALOG: u8 = 0x6F;
+const STRING_DATA: u8 = 0x71;
+const REPORT_FIRMWARE: u8 = 0x79;
+const SAMPLING_INTERVAL: u8 = 0x7A;
+
+/// Ends the modes of a pin in a capability response, and marks non-analog pins
+const NONE: u8 = 0x7F;
+
+/// Largest SysEx message accepted
+const MAX_SYSEX_LEN: uint = 4096;
+
+#[deriving(Clone, FromPrimitive, PartialEq, Show)]
+pub enum PinMode {
+    InputMode = 0x00,
+    OutputMode = 0x01,
+    AnalogMode = 0x02,
+    PwmMode = 0x03,
+    ServoMode = 0x04,
+    ShiftMode = 0x05,
+    I2cMode = 0x06,
+    OneWireMode = 0x07,
+    StepperMode = 0x08,
+    EncoderMode = 0x09,
+    SerialMode = 0x0A,
+    PullupMode = 0x0B,
+}
+
+/// A message sent by the board
+#[deriving(Clone, PartialEq, Show)]
+pub enum Message {
+    /// Value of an analog channel
+    AnalogMessage(u8, u16),
+    /// Modes supported by each pin, with their resolution in bits
+    CapabilityMessage(Vec<Vec<(PinMode, u8)>>),
+    /// Analog channel of each pin, `None` for the digital only pins
+    AnalogMappingMessage(Vec<Option<u8>>),
+    /// State of the 8 pins of a port, pin 0 of the port in the least significant bit
+    DigitalMessage(u8, u8),
+    /// Version and name of the firmware
+    FirmwareMessage(u8, u8, String),
+    /// Pin, mode and state (the output value, or whether the pullup is enabled)
+    PinStateMessage(u8, Option<PinMode>, u32),
+    StringMessage(String),
+    /// Unrecognized SysEx, the command and its 7-bit data
+    SysexMessage(u8, Vec<u8>),
+    /// Protocol version implemented by the firmware
+    VersionMessage(u8, u8),
+}
+
+impl Message {
+    /// Parses a SysEx `command` and its `data`
+    fn parse_sysex(command: u8, data: &[u8]) -> Message {
+        match command {
+            ANALOG_MAPPING_RESPONSE => {
+                AnalogMappingMessage(data.iter().map(|&channel| {
+                    if channel == NONE { None } else { Some(channel) }
+                }).collect())
+            },
+            CAPABILITY_RESPONSE => {
+                let mut pins = vec![];
+                let mut modes = vec![];
+                let mut i = 0;
+
+                while i < data.len() {
+                    if data[i] == NONE {
+                        pins.push(modes);
+                        modes = vec![];
+                        i += 1;
+                    } else if i + 1 < data.len() {
+                        match FromPrimitive::from_u8(data[i]) {
+                            None => {},
+                            Some(mode) => modes.push((mode, data[i + 1])),
+                        }
+                        i += 2;
+                    } else {
+                        break
+                    }
+                }
+
+                CapabilityMessage(pins)
+            },
+            PIN_STATE_RESPONSE if data.len() >= 2 => {
+                let state = data.slice_from(2).iter().take(4).enumerate();
+                let state = state.fold(0, |state, (i, &byte)| state | (byte as u32) << (7 * i));
+
+                PinStateMessage(data[0], FromPrimitive::from_u8(data[1]), state)
+            },
+            REPORT_FIRMWARE if data.len() >= 2 => {
+                FirmwareMessage(data[0], data[1], decode_string(data.slice_from(2)))
+            },
+            STRING_DATA => StringMessage(decode_string(data)),
+            _ => SysexMessage(command, data.to_vec()),
+        }
+    }
+}
+
+/// Splits the incoming byte stream into messages
+pub struct Parser {
+    buf: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            buf: Vec::new(),
+        }
+    }
+
+    /// Feeds `data`, returns the messages it completes
+    ///
+    /// Incomplete messages interrupted by a status byte are dropped, as are the messages that
+    /// only a host sends.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Message> {
+        let mut messages = Vec::new();
+
+        for &byte in data.iter() {
+            if byte & 0x80 != 0 {
+                if byte == END_SYSEX && self.buf.as_slice().starts_with(&[START_SYSEX]) {
+                    if self.buf.len() >= 2 {
+                        let command = self.buf[1];
+                        messages.push(Message::parse_sysex(command, self.buf.slice_from(2)));
+                    }
+
+                    self.buf.clear();
+                    continue
+                }
+
+                self.buf.clear();
+            } else if self.buf.is_empty() {
+                // A data byte without a status byte
+                continue
+            } else if self.buf.len() == MAX_SYSEX_LEN {
+                self.buf.clear();
+                continue
+            }
+
+            self.buf.push(byte);
+
+            let status = self.buf[0];
+            let complete = match status & 0xF0 {
+                ANALOG_MESSAGE | DIGITAL_MESSAGE => self.buf.len() == 3,
+                _ if status == PROTOCOL_VERSION => self.buf.len() == 3,
+                _ => false,
+            };
+
+            if complete {
+                let (low, high) = (self.buf[1] as u16, self.buf[2] as u16);
+                let channel = status & 0x0F;
+
+                messages.push(match status & 0xF0 {
+                    ANALOG_MESSAGE => AnalogMessage(channel, high << 7 | low),
+                    DIGITAL_MESSAGE => DigitalMessage(channel, (high << 7 | low) as u8),
+                    _ => VersionMessage(self.buf[1], self.buf[2]),
+                });
+
+                self.buf.clear();
+            }
+        }
+
+        messages
+    }
+
+    /// Discards any partially received message
+    pub fn reset(&mut self) {
+        self.buf.clear();
+    }
+}
+
+/// A board running Firmata, connected to a port
+///
+/// The latest digital and analog values reported by the board are kept, they are updated
+/// whenever messages are received.
+pub struct Board<S> {
+    port: S,
+    parser: Parser,
+    pending: RingBuf<Message>,
+    /// Output state of the digital ports
+    outputs: [u8, ..16],
+    /// Input state reported for the digital ports
+    inputs: [u8, ..16],
+    analog: [Option<u16>, ..16],
+}
+
+impl<S: Reader + Writer> Board<S> {
+    /// Talks Firmata through `port`, which must use the baud rate of the firmware (57600 for
+    /// StandardFirmata)
+    pub fn new(port: S) -> Board<S> {
+        Board {
+            port: port,
+            parser: Parser::new(),
+            pending: RingBuf::new(),
+            outputs: [0, ..16],
+            inputs: [0, ..16],
+            analog: [None, ..16],
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.port
+    }
+
+    /// Unwraps the port
+    pub fn into_inner(self) -> S {
+        self.port
+    }
+
+    /// Receives the next message
+    ///
+    /// Use a timeout on the port to bound the wait.
+    pub fn receive(&mut self) -> IoResult<Message> {
+        loop {
+            match self.pending.pop_front() {
+                None => {},
+                Some(message) => {
+                    self.update(&message);
+                    return Ok(message)
+                },
+            }
+
+            let mut chunk = [0u8, ..256];
+            let n = try!(self.port.read(&mut chunk));
+            self.pending.extend(self.parser.feed(chunk.slice_to(n)).into_iter());
+        }
+    }
+
+    /// Returns the latest reported value of the analog `channel`
+    pub fn analog_read(&self, channel: u8) -> Option<u16> {
+        self.analog.get(channel as uint).and_then(|value| *value)
+    }
+
+    /// Returns the latest reported state of the digital `pin`
+    ///
+    /// Reports of the port of the pin must be enabled, see `report_digital`.
+    pub fn digital_read(&self, pin: u8) -> bool {
+        let port = (pin / 8) as uint;
+
+        port < 16 && self.inputs[port] & (1 << (pin % 8) as uint) != 0
+    }
+
+    /// Sets the `value` of an analog output (PWM or servo) `pin`
+    pub fn analog_write(&mut self, pin: u8, value: u16) -> IoResult<()> {
+        if pin < 16 {
+            self.port.write(&[ANALOG_MESSAGE | pin, value as u8 & 0x7F, (value >> 7) as u8 & 0x7F])
+        } else {
+            let mut sysex = vec![pin & 0x7F];
+            let mut value = value;
+            while value != 0 || sysex.len() == 1 {
+                sysex.push(value as u8 & 0x7F);
+                value >>= 7;
+            }
+
+            self.send_sysex(EXTENDED_ANALOG, sysex.as_slice())
+        }
+    }
+
+    /// Drives the digital output `pin`
+    pub fn digital_write(&mut self, pin: u8, value: bool) -> IoResult<()> {
+        let port = (pin / 8) as uint;
+        if port >= 16 {
+            return self.port.write(&[SET_DIGITAL_PIN_VALUE, pin & 0x7F, value as u8])
+        }
+
+        let mask = 1 << (pin % 8) as uint;
+        if value {
+            self.outputs[port] |= mask;
+        } else {
+            self.outputs[port] &= !mask;
+        }
+
+        let state = self.outputs[port];
+        self.port.write(&[DIGITAL_MESSAGE | port as u8, state & 0x7F, state >> 7])
+    }
+
+    /// Enables or disables the reports of the analog `channel`
+    pub fn report_analog(&mut self, channel: u8, enable: bool) -> IoResult<()> {
+        self.port.write(&[REPORT_ANALOG | (channel & 0x0F), enable as u8])
+    }
+
+    /// Enables or disables the reports of a digital `port` (pins `8 * port` to `8 * port + 7`)
+    pub fn report_digital(&mut self, port: u8, enable: bool) -> IoResult<()> {
+        self.port.write(&[REPORT_DIGITAL | (port & 0x0F), enable as u8])
+    }
+
+    /// Resets the board to its power-up state
+    pub fn reset(&mut self) -> IoResult<()> {
+        self.outputs = [0, ..16];
+        self.port.write(&[SYSTEM_RESET])
+    }
+
+    /// Sets how often the analog channels are sampled, in milliseconds
+    pub fn set_sampling_interval(&mut self, millis: u16) -> IoResult<()> {
+        self.send_sysex(SAMPLING_INTERVAL, &[millis as u8 & 0x7F, (millis >> 7) as u8 & 0x7F])
+    }
+
+    pub fn set_pin_mode(&mut self, pin: u8, mode: PinMode) -> IoResult<()> {
+        self.port.write(&[SET_PIN_MODE, pin & 0x7F, mode as u8])
+    }
+
+    /// Queries the analog channel of each pin
+    pub fn query_analog_mapping(&mut self) -> IoResult<Vec<Option<u8>>> {
+        try!(self.send_sysex(ANALOG_MAPPING_QUERY, &[]));
+
+        loop {
+            match try!(self.receive()) {
+                AnalogMappingMessage(mapping) => return Ok(mapping),
+                _ => {},
+            }
+        }
+    }
+
+    /// Queries the modes supported by each pin
+    pub fn query_capabilities(&mut self) -> IoResult<Vec<Vec<(PinMode, u8)>>> {
+        try!(self.send_sysex(CAPABILITY_QUERY, &[]));
+
+        loop {
+            match try!(self.receive()) {
+                CapabilityMessage(pins) => return Ok(pins),
+                _ => {},
+            }
+        }
+    }
+
+    /// Queries the version and name of the firmware
+    pub fn query_firmware(&mut self) -> IoResult<(u8, u8, String)> {
+        try!(self.send_sysex(REPORT_FIRMWARE, &[]));
+
+        loop {
+            match try!(self.receive()) {
+                FirmwareMessage(major, minor, name) => return Ok((major, minor, name)),
+                _ => {},
+            }
+        }
+    }
+
+    /// Queries the mode and state of `pin`
+    pub fn query_pin_state(&mut self, pin: u8) -> IoResult<(Option<PinMode>, u32)> {
+        try!(self.send_sysex(PIN_STATE_QUERY, &[pin & 0x7F]));
+
+        loop {
+            match try!(self.receive()) {
+                PinStateMessage(p, mode, state) if p == pin => return Ok((mode, state)),
+                _ => {},
+            }
+        }
+    }
+
+    fn send_sysex(&mut self, command: u8, data: &[u8]) -> IoResult<()> {
+        let mut sysex = vec![START_SYSEX, command];
+        sysex.push_all(data);
+        sysex.push(END_SYSEX);
+
+        self.port.write(sysex.as_slice())
+    }
+
+    /// Records the values reported by `message`
+    fn update(&mut self, message: &Message) {
+        match *message {
+            AnalogMessage(channel, value) if channel < 16 => {
+                self.analog[channel as uint] = Some(value);
+            },
+            DigitalMessage(port, state) if port < 16 => self.inputs[port as uint] = state,
+            _ => {},
+        }
+    }
+}
+
+/// Decodes a string sent as pairs of 7-bit bytes, least significant first
+fn decode_string(data: &[u8]) -> String {
+    let bytes: Vec<u8> = data.chunks(2).map(|pair| {
+        pair[0] | if pair.len() == 2 { pair[1] << 7 } else { 0 }
+    }).collect();
+
+    String::from_utf8_lossy(bytes.as_slice()).into_string()
+}
@@ -0,0 +1,194 @@
+//! Typed termios flag sets, for the combinations the rest of the API doesn't cover
+//!
+//! The sets are read and written with `SerialPort::input_flags`, `SerialPort::control_flags`,
+//! `SerialPort::local_flags` and their setters. The constants have the same names on every
+//! platform, only their values differ.
+//!
+//! ```ignore
+//! let flags = try!(port.local_flags());
+//! try!(port.set_local_flags(flags | ICANON | ECHO));
+//! ```
+
+#[cfg(target_os = "linux")]
+bitflags! {
+    #[doc = "Input modes, `c_iflag`"]
+    flags InputFlags: ::libc::c_uint {
+        #[doc = "Ignore breaks"]
+        const IGNBRK = 0x0001,
+        #[doc = "Flush the queues on break, and signal it"]
+        const BRKINT = 0x0002,
+        #[doc = "Ignore the characters with framing or parity errors"]
+        const IGNPAR = 0x0004,
+        #[doc = "Prefix the characters with parity errors with `\\xFF\\0`"]
+        const PARMRK = 0x0008,
+        #[doc = "Check the parity of the received characters"]
+        const INPCK = 0x0010,
+        #[doc = "Clear the high bit of the received characters"]
+        const ISTRIP = 0x0020,
+        #[doc = "Translate NL to CR"]
+        const INLCR = 0x0040,
+        #[doc = "Ignore CR"]
+        const IGNCR = 0x0080,
+        #[doc = "Translate CR to NL"]
+        const ICRNL = 0x0100,
+        #[doc = "XON/XOFF flow control of the output"]
+        const IXON = 0x0400,
+        #[doc = "Any character restarts the output"]
+        const IXANY = 0x0800,
+        #[doc = "XON/XOFF flow control of the input"]
+        const IXOFF = 0x1000,
+        #[doc = "Ring the bell when the input queue is full"]
+        const IMAXBEL = 0x2000,
+    }
+}
+
+#[cfg(target_os = "macos")]
+bitflags! {
+    #[doc = "Input modes, `c_iflag`"]
+    flags InputFlags: ::libc::c_ulong {
+        #[doc = "Ignore breaks"]
+        const IGNBRK = 0x0001,
+        #[doc = "Flush the queues on break, and signal it"]
+        const BRKINT = 0x0002,
+        #[doc = "Ignore the characters with framing or parity errors"]
+        const IGNPAR = 0x0004,
+        #[doc = "Prefix the characters with parity errors with `\\xFF\\0`"]
+        const PARMRK = 0x0008,
+        #[doc = "Check the parity of the received characters"]
+        const INPCK = 0x0010,
+        #[doc = "Clear the high bit of the received characters"]
+        const ISTRIP = 0x0020,
+        #[doc = "Translate NL to CR"]
+        const INLCR = 0x0040,
+        #[doc = "Ignore CR"]
+        const IGNCR = 0x0080,
+        #[doc = "Translate CR to NL"]
+        const ICRNL = 0x0100,
+        #[doc = "XON/XOFF flow control of the output"]
+        const IXON = 0x0200,
+        #[doc = "XON/XOFF flow control of the input"]
+        const IXOFF = 0x0400,
+        #[doc = "Any character restarts the output"]
+        const IXANY = 0x0800,
+        #[doc = "Ring the bell when the input queue is full"]
+        const IMAXBEL = 0x2000,
+    }
+}
+
+#[cfg(target_os = "linux")]
+bitflags! {
+    #[doc = "Control modes, `c_cflag`, without the baud rate bits"]
+    flags ControlFlags: ::libc::c_uint {
+        #[doc = "5 data bits, the `CSIZE` bits cleared"]
+        const CS5 = 0x0000,
+        const CS6 = 0x0010,
+        const CS7 = 0x0020,
+        const CS8 = 0x0030,
+        #[doc = "Mask of the data bits field"]
+        const CSIZE = 0x0030,
+        #[doc = "2 stop bits"]
+        const CSTOPB = 0x0040,
+        #[doc = "Enable the receiver"]
+        const CREAD = 0x0080,
+        #[doc = "Generate and check parity"]
+        const PARENB = 0x0100,
+        #[doc = "Odd parity, even otherwise"]
+        const PARODD = 0x0200,
+        #[doc = "Drop DTR and RTS on the last close"]
+        const HUPCL = 0x0400,
+        #[doc = "Ignore the modem status lines"]
+        const CLOCAL = 0x0800,
+        #[doc = "RTS/CTS flow control"]
+        const CRTSCTS = 0x80000000,
+    }
+}
+
+#[cfg(target_os = "macos")]
+bitflags! {
+    #[doc = "Control modes, `c_cflag`, without the baud rate bits"]
+    flags ControlFlags: ::libc::c_ulong {
+        #[doc = "5 data bits, the `CSIZE` bits cleared"]
+        const CS5 = 0x0000,
+        const CS6 = 0x0100,
+        const CS7 = 0x0200,
+        const CS8 = 0x0300,
+        #[doc = "Mask of the data bits field"]
+        const CSIZE = 0x0300,
+        #[doc = "2 stop bits"]
+        const CSTOPB = 0x0400,
+        #[doc = "Enable the receiver"]
+        const CREAD = 0x0800,
+        #[doc = "Generate and check parity"]
+        const PARENB = 0x1000,
+        #[doc = "Odd parity, even otherwise"]
+        const PARODD = 0x2000,
+        #[doc = "Drop DTR and RTS on the last close"]
+        const HUPCL = 0x4000,
+        #[doc = "Ignore the modem status lines"]
+        const CLOCAL = 0x8000,
+        #[doc = "RTS/CTS flow control"]
+        const CRTSCTS = 0x00030000,
+    }
+}
+
+#[cfg(target_os = "linux")]
+bitflags! {
+    #[doc = "Local modes, `c_lflag`"]
+    flags LocalFlags: ::libc::c_uint {
+        #[doc = "Generate signals for the INTR, QUIT and SUSP characters"]
+        const ISIG = 0x0001,
+        #[doc = "Canonical mode, the input is available line by line"]
+        const ICANON = 0x0002,
+        #[doc = "Echo the input"]
+        const ECHO = 0x0008,
+        #[doc = "Echo the ERASE character as an erasure"]
+        const ECHOE = 0x0010,
+        #[doc = "Echo a NL after the KILL character"]
+        const ECHOK = 0x0020,
+        #[doc = "Echo NL even without `ECHO`"]
+        const ECHONL = 0x0040,
+        #[doc = "Don't flush the queues on signals"]
+        const NOFLSH = 0x0080,
+        #[doc = "Stop the background jobs that write"]
+        const TOSTOP = 0x0100,
+        #[doc = "Echo the control characters as `^X`"]
+        const ECHOCTL = 0x0200,
+        #[doc = "Echo the erased characters"]
+        const ECHOPRT = 0x0400,
+        #[doc = "Echo the KILL character by erasing the line"]
+        const ECHOKE = 0x0800,
+        #[doc = "Implementation defined input processing"]
+        const IEXTEN = 0x8000,
+    }
+}
+
+#[cfg(target_os = "macos")]
+bitflags! {
+    #[doc = "Local modes, `c_lflag`"]
+    flags LocalFlags: ::libc::c_ulong {
+        #[doc = "Echo the KILL character by erasing the line"]
+        const ECHOKE = 0x0001,
+        #[doc = "Echo the ERASE character as an erasure"]
+        const ECHOE = 0x0002,
+        #[doc = "Echo a NL after the KILL character"]
+        const ECHOK = 0x0004,
+        #[doc = "Echo the input"]
+        const ECHO = 0x0008,
+        #[doc = "Echo NL even without `ECHO`"]
+        const ECHONL = 0x0010,
+        #[doc = "Echo the erased characters"]
+        const ECHOPRT = 0x0020,
+        #[doc = "Echo the control characters as `^X`"]
+        const ECHOCTL = 0x0040,
+        #[doc = "Generate signals for the INTR, QUIT and SUSP characters"]
+        const ISIG = 0x0080,
+        #[doc = "Canonical mode, the input is available line by line"]
+        const ICANON = 0x0100,
+        #[doc = "Implementation defined input processing"]
+        const IEXTEN = 0x0400,
+        #[doc = "Stop the background jobs that write"]
+        const TOSTOP = 0x00400000,
+        #[doc = "Don't flush the queues on signals"]
+        const NOFLSH = 0x80000000,
+    }
+}
@@ -0,0 +1,96 @@
+use checksum::Checksum;
+use framing::{
+    BadChecksum, BorrowedFrameResult, Decoder, Encoder, FrameError, FrameResult, InPlaceDecoder,
+};
+
+/// Adds a checksum to the frames of another codec
+///
+/// The checksum of the payload is appended to every frame before encoding it, and it's verified
+/// and stripped from the decoded frames, mismatches are reported as `BadChecksum`.
+pub struct Checked<C, K> {
+    codec: C,
+    checksum: K,
+}
+
+impl<C, K: Checksum> Checked<C, K> {
+    /// Protects the frames of `codec` with `checksum`
+    pub fn new(codec: C, checksum: K) -> Checked<C, K> {
+        Checked {
+            codec: codec,
+            checksum: checksum,
+        }
+    }
+
+    /// Computes the checksum of `data`
+    fn checksum(&mut self, data: &[u8]) -> Vec<u8> {
+        self.checksum.reset();
+        self.checksum.update(data);
+        self.checksum.bytes()
+    }
+}
+
+impl<C: Decoder, K: Checksum> Decoder for Checked<C, K> {
+    fn feed(&mut self, data: &[u8]) -> Vec<FrameResult> {
+        let n = self.checksum(&[]).len();
+        let mut frames = Vec::new();
+
+        for frame in self.codec.feed(data).into_iter() {
+            let frame = match frame {
+                Err(e) => Err(e),
+                Ok(ref frame) if frame.len() < n => Err(BadChecksum),
+                Ok(frame) => {
+                    let (payload, checksum) = frame.split_at(frame.len() - n);
+
+                    if self.checksum(payload).as_slice() == checksum {
+                        Ok(payload.to_vec())
+                    } else {
+                        Err(BadChecksum)
+                    }
+                },
+            };
+
+            frames.push(frame);
+        }
+
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.codec.reset()
+    }
+}
+
+impl<C: InPlaceDecoder, K: Checksum> InPlaceDecoder for Checked<C, K> {
+    fn decode_in_place<'a>(&mut self, buf: &'a mut [u8])
+                           -> (uint, Option<BorrowedFrameResult<'a>>) {
+        let (consumed, frame) = self.codec.decode_in_place(buf);
+        let n = self.checksum.size();
+
+        let frame = match frame {
+            Some(Ok(frame)) if frame.len() < n => Some(Err(BadChecksum)),
+            Some(Ok(frame)) => {
+                let (payload, checksum) = frame.split_at(frame.len() - n);
+
+                self.checksum.reset();
+                self.checksum.update(payload);
+                if self.checksum.matches(checksum) {
+                    Some(Ok(payload))
+                } else {
+                    Some(Err(BadChecksum))
+                }
+            },
+            frame => frame,
+        };
+
+        (consumed, frame)
+    }
+}
+
+impl<C: Encoder, K: Checksum> Encoder for Checked<C, K> {
+    fn encode(&mut self, frame: &[u8], out: &mut Vec<u8>) -> Result<(), FrameError> {
+        let mut checked = frame.to_vec();
+        checked.push_all(self.checksum(frame).as_slice());
+
+        self.codec.encode(checked.as_slice(), out)
+    }
+}
@@ -0,0 +1,132 @@
+use framing::{
+    BorrowedFrameResult, Decoder, Encoder, FrameError, FrameResult, InPlaceDecoder, Truncated,
+};
+
+/// Consistent Overhead Byte Stuffing
+///
+/// The encoded frames don't contain any zero byte, which is used to terminate each frame. Empty
+/// frames are preserved, consecutive zero bytes are skipped by the decoder.
+pub struct Cobs {
+    buf: Vec<u8>,
+    code: u8,
+    remaining: u8,
+    started: bool,
+}
+
+impl Cobs {
+    /// Creates a codec with an empty decoding state
+    pub fn new() -> Cobs {
+        Cobs {
+            buf: Vec::new(),
+            code: 0,
+            remaining: 0,
+            started: false,
+        }
+    }
+}
+
+impl Decoder for Cobs {
+    fn feed(&mut self, data: &[u8]) -> Vec<FrameResult> {
+        let mut frames = Vec::new();
+
+        for &byte in data.iter() {
+            if byte == 0 {
+                if self.remaining != 0 {
+                    frames.push(Err(Truncated));
+                } else if self.started {
+                    frames.push(Ok(self.buf.clone()));
+                }
+
+                self.reset();
+            } else if self.remaining == 0 {
+                // Every block but the maximal ones encodes a trailing zero, which is implied for
+                // the last block of the frame
+                if self.started && self.code != 0xFF {
+                    self.buf.push(0);
+                }
+
+                self.code = byte;
+                self.remaining = byte - 1;
+                self.started = true;
+            } else {
+                self.buf.push(byte);
+                self.remaining -= 1;
+            }
+        }
+
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.buf.clear();
+        self.code = 0;
+        self.remaining = 0;
+        self.started = false;
+    }
+}
+
+impl InPlaceDecoder for Cobs {
+    fn decode_in_place<'a>(&mut self, buf: &'a mut [u8])
+                           -> (uint, Option<BorrowedFrameResult<'a>>) {
+        let end = match buf.iter().position(|&byte| byte == 0) {
+            None => return (0, None),
+            Some(0) => return (1, None),
+            Some(end) => end,
+        };
+
+        // Every code byte but the first one becomes at most one zero, so the decoded frame never
+        // catches up with the bytes still to decode
+        let mut len = 0;
+        let mut code = 0u8;
+        let mut remaining = 0u8;
+
+        for i in range(0, end) {
+            let byte = buf[i];
+
+            if remaining == 0 {
+                if i != 0 && code != 0xFF {
+                    buf[len] = 0;
+                    len += 1;
+                }
+
+                code = byte;
+                remaining = byte - 1;
+            } else {
+                buf[len] = byte;
+                len += 1;
+                remaining -= 1;
+            }
+        }
+
+        let frame = if remaining != 0 { Err(Truncated) } else { Ok(buf.slice_to(len)) };
+
+        (end + 1, Some(frame))
+    }
+}
+
+impl Encoder for Cobs {
+    fn encode(&mut self, frame: &[u8], out: &mut Vec<u8>) -> Result<(), FrameError> {
+        let mut code_pos = out.len();
+        let mut code = 1u8;
+        out.push(0);
+
+        for &byte in frame.iter() {
+            if byte != 0 {
+                out.push(byte);
+                code += 1;
+            }
+
+            if byte == 0 || code == 0xFF {
+                out.as_mut_slice()[code_pos] = code;
+                code_pos = out.len();
+                code = 1;
+                out.push(0);
+            }
+        }
+
+        out.as_mut_slice()[code_pos] = code;
+        out.push(0);
+
+        Ok(())
+    }
+}
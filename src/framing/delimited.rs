@@ -0,0 +1,147 @@
+use framing::{
+    BorrowedFrameResult, Decoder, Encoder, FrameError, FrameResult, InPlaceDecoder, InvalidEscape,
+};
+
+/// Frames terminated by a delimiter byte
+///
+/// Without escaping, the payload must not contain the delimiter. With escaping, occurrences of
+/// the delimiter and of the escape byte inside the payload are prefixed with the escape byte.
+/// Empty frames are skipped by the decoder.
+pub struct Delimited {
+    delimiter: u8,
+    escape: Option<u8>,
+    buf: Vec<u8>,
+    escaped: bool,
+    invalid: Option<u8>,
+}
+
+impl Delimited {
+    /// Frames terminated by `delimiter`, without escaping
+    pub fn new(delimiter: u8) -> Delimited {
+        Delimited {
+            delimiter: delimiter,
+            escape: None,
+            buf: Vec::new(),
+            escaped: false,
+            invalid: None,
+        }
+    }
+
+    /// Frames terminated by `delimiter`, with payload bytes escaped by `escape`
+    pub fn escaped(delimiter: u8, escape: u8) -> Delimited {
+        Delimited { escape: Some(escape), ..Delimited::new(delimiter) }
+    }
+
+    /// Returns the position of the delimiter ending the first frame of `buf`
+    fn frame_end(&self, buf: &[u8]) -> Option<uint> {
+        let mut escaped = false;
+
+        for (i, &byte) in buf.iter().enumerate() {
+            if escaped {
+                escaped = false;
+            } else if Some(byte) == self.escape {
+                escaped = true;
+            } else if byte == self.delimiter {
+                return Some(i)
+            }
+        }
+
+        None
+    }
+}
+
+impl Decoder for Delimited {
+    fn feed(&mut self, data: &[u8]) -> Vec<FrameResult> {
+        let mut frames = Vec::new();
+
+        for &byte in data.iter() {
+            if self.escaped {
+                self.escaped = false;
+
+                if byte != self.delimiter && Some(byte) != self.escape && self.invalid.is_none() {
+                    self.invalid = Some(byte);
+                }
+
+                self.buf.push(byte);
+            } else if Some(byte) == self.escape {
+                self.escaped = true;
+            } else if byte == self.delimiter {
+                match self.invalid.take() {
+                    None => if !self.buf.is_empty() {
+                        frames.push(Ok(self.buf.clone()));
+                    },
+                    Some(byte) => frames.push(Err(InvalidEscape(byte))),
+                }
+
+                self.buf.clear();
+            } else {
+                self.buf.push(byte);
+            }
+        }
+
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.buf.clear();
+        self.escaped = false;
+        self.invalid = None;
+    }
+}
+
+impl InPlaceDecoder for Delimited {
+    fn decode_in_place<'a>(&mut self, buf: &'a mut [u8])
+                           -> (uint, Option<BorrowedFrameResult<'a>>) {
+        let end = match self.frame_end(buf) {
+            None => return (0, None),
+            Some(end) => end,
+        };
+
+        let mut len = 0;
+        let mut escaped = false;
+        let mut invalid = None;
+
+        for i in range(0, end) {
+            let byte = buf[i];
+
+            if escaped {
+                escaped = false;
+
+                if byte != self.delimiter && Some(byte) != self.escape && invalid.is_none() {
+                    invalid = Some(byte);
+                }
+            } else if Some(byte) == self.escape {
+                escaped = true;
+                continue
+            }
+
+            buf[len] = byte;
+            len += 1;
+        }
+
+        let frame = match invalid {
+            Some(byte) => Some(Err(InvalidEscape(byte))),
+            None if len == 0 => None,
+            None => Some(Ok(buf.slice_to(len))),
+        };
+
+        (end + 1, frame)
+    }
+}
+
+impl Encoder for Delimited {
+    fn encode(&mut self, frame: &[u8], out: &mut Vec<u8>) -> Result<(), FrameError> {
+        for &byte in frame.iter() {
+            match self.escape {
+                Some(escape) if byte == escape || byte == self.delimiter => out.push(escape),
+                _ => {},
+            }
+
+            out.push(byte);
+        }
+
+        out.push(self.delimiter);
+
+        Ok(())
+    }
+}
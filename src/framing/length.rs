@@ -0,0 +1,221 @@
+use std::cmp;
+
+use framing::{
+    BorrowedFrameResult, Decoder, Encoder, FrameError, FrameResult, InPlaceDecoder, Oversized,
+};
+
+/// Width of the length prefix
+#[deriving(Clone, PartialEq, Show)]
+pub enum PrefixWidth {
+    U8Prefix,
+    U16Prefix,
+    U32Prefix,
+}
+
+impl PrefixWidth {
+    /// Number of bytes used by the prefix
+    fn bytes(&self) -> uint {
+        match *self {
+            U8Prefix => 1,
+            U16Prefix => 2,
+            U32Prefix => 4,
+        }
+    }
+
+    /// Largest length that the prefix can represent
+    fn max_len(&self) -> u64 {
+        match *self {
+            U8Prefix => 0xFF,
+            U16Prefix => 0xFFFF,
+            U32Prefix => 0xFFFF_FFFF,
+        }
+    }
+}
+
+/// Byte order of the length prefix
+#[deriving(Clone, PartialEq, Show)]
+pub enum Endianness {
+    BigEndian,
+    LittleEndian,
+}
+
+/// Frames preceded by their length
+///
+/// The prefix holds the length of the payload, and doesn't count itself. If a maximum frame
+/// length is set, longer frames are refused by the encoder, and reported as `Oversized` by the
+/// decoder, which then skips over their payload.
+pub struct LengthPrefixed {
+    width: PrefixWidth,
+    endianness: Endianness,
+    max_len: Option<uint>,
+    header: Vec<u8>,
+    buf: Vec<u8>,
+    in_body: bool,
+    remaining: uint,
+    skip: bool,
+    /// Payload bytes of an oversized frame still to be skipped by `decode_in_place`
+    discard: uint,
+}
+
+impl LengthPrefixed {
+    /// Frames prefixed with a length of the given `width` and `endianness`
+    pub fn new(width: PrefixWidth, endianness: Endianness) -> LengthPrefixed {
+        LengthPrefixed {
+            width: width,
+            endianness: endianness,
+            max_len: None,
+            header: Vec::new(),
+            buf: Vec::new(),
+            in_body: false,
+            remaining: 0,
+            skip: false,
+            discard: 0,
+        }
+    }
+
+    /// Limits the length of the frames' payload to `max_len` bytes
+    pub fn max_frame_len(mut self, max_len: uint) -> LengthPrefixed {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Checks that a frame of `len` bytes is acceptable
+    fn check(&self, len: u64) -> Result<(), FrameError> {
+        let too_long = match self.max_len {
+            None => false,
+            Some(max_len) => len > max_len as u64,
+        };
+
+        if too_long || len > self.width.max_len() {
+            Err(Oversized(len as uint))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads the length held by the prefix `header`
+    fn decode_len(&self, header: &[u8]) -> u64 {
+        match self.endianness {
+            BigEndian => header.iter().fold(0u64, |len, &b| len << 8 | b as u64),
+            LittleEndian => header.iter().rev().fold(0u64, |len, &b| len << 8 | b as u64),
+        }
+    }
+}
+
+impl Decoder for LengthPrefixed {
+    fn feed(&mut self, mut data: &[u8]) -> Vec<FrameResult> {
+        let mut frames = Vec::new();
+        let width = self.width.bytes();
+
+        while !data.is_empty() {
+            if !self.in_body {
+                let n = cmp::min(width - self.header.len(), data.len());
+                self.header.push_all(data.slice_to(n));
+                data = data.slice_from(n);
+
+                if self.header.len() == width {
+                    let len = self.decode_len(self.header.as_slice());
+
+                    self.header.clear();
+                    self.in_body = true;
+                    self.remaining = len as uint;
+
+                    match self.check(len) {
+                        Err(e) => {
+                            self.skip = true;
+                            frames.push(Err(e));
+                        },
+                        Ok(_) => {},
+                    }
+                }
+            }
+
+            // Zero length frames complete as soon as their prefix does
+            if self.in_body {
+                let n = cmp::min(self.remaining, data.len());
+                if !self.skip {
+                    self.buf.push_all(data.slice_to(n));
+                }
+                data = data.slice_from(n);
+                self.remaining -= n;
+
+                if self.remaining == 0 {
+                    if !self.skip {
+                        frames.push(Ok(self.buf.clone()));
+                    }
+
+                    self.buf.clear();
+                    self.in_body = false;
+                    self.skip = false;
+                }
+            }
+        }
+
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.header.clear();
+        self.buf.clear();
+        self.in_body = false;
+        self.remaining = 0;
+        self.skip = false;
+        self.discard = 0;
+    }
+}
+
+impl InPlaceDecoder for LengthPrefixed {
+    fn decode_in_place<'a>(&mut self, buf: &'a mut [u8])
+                           -> (uint, Option<BorrowedFrameResult<'a>>) {
+        // The payload of an oversized frame may not fit in the buffer, so it's consumed as it
+        // comes in
+        if self.discard > 0 {
+            let n = cmp::min(self.discard, buf.len());
+            self.discard -= n;
+
+            return (n, None)
+        }
+
+        let width = self.width.bytes();
+        if buf.len() < width {
+            return (0, None)
+        }
+
+        let len = self.decode_len(buf.slice_to(width));
+        match self.check(len) {
+            Err(e) => {
+                self.discard = len as uint;
+                return (width, Some(Err(e)))
+            },
+            Ok(_) => {},
+        }
+
+        let end = width + len as uint;
+        if buf.len() < end {
+            return (0, None)
+        }
+
+        (end, Some(Ok(buf.slice(width, end))))
+    }
+}
+
+impl Encoder for LengthPrefixed {
+    fn encode(&mut self, frame: &[u8], out: &mut Vec<u8>) -> Result<(), FrameError> {
+        let len = frame.len() as u64;
+        try!(self.check(len));
+
+        let width = self.width.bytes();
+        for i in range(0, width) {
+            let shift = match self.endianness {
+                BigEndian => 8 * (width - 1 - i),
+                LittleEndian => 8 * i,
+            };
+
+            out.push((len >> shift) as u8);
+        }
+
+        out.push_all(frame);
+
+        Ok(())
+    }
+}
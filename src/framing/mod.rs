@@ -0,0 +1,166 @@
+//! Framing codecs, splitting a byte stream into discrete frames
+
+use std::collections::RingBuf;
+use std::io::{IoError, IoResult, InvalidInput};
+
+pub use self::checked::Checked;
+pub use self::cobs::Cobs;
+pub use self::delimited::Delimited;
+pub use self::length::{
+    BigEndian, Endianness, LengthPrefixed, LittleEndian, PrefixWidth, U16Prefix, U32Prefix,
+    U8Prefix,
+};
+pub use self::slip::Slip;
+
+mod checked;
+mod cobs;
+mod delimited;
+mod length;
+mod slip;
+
+/// Size of the chunks requested from the underlying transport
+const CHUNK_SIZE: uint = 256;
+
+/// Errors found while decoding a frame
+#[deriving(Clone, PartialEq, Show)]
+pub enum FrameError {
+    /// The checksum of the frame doesn't match its payload
+    BadChecksum,
+    /// The frame contained an invalid escape sequence, the byte that followed the escape byte is
+    /// included
+    InvalidEscape(u8),
+    /// The frame is longer than allowed, its length is included
+    Oversized(uint),
+    /// The frame ended before the length it announced
+    Truncated,
+}
+
+/// Outcome of decoding a single frame
+pub type FrameResult = Result<Vec<u8>, FrameError>;
+
+/// Outcome of decoding a single frame in place, the frame borrows the caller's buffer
+pub type BorrowedFrameResult<'a> = Result<&'a [u8], FrameError>;
+
+/// Converts frames into their on-the-wire representation
+pub trait Encoder {
+    /// Encodes `frame`, and appends the result to `out`
+    ///
+    /// Nothing is appended if the frame can't be encoded
+    fn encode(&mut self, frame: &[u8], out: &mut Vec<u8>) -> Result<(), FrameError>;
+}
+
+/// Incrementally extracts frames from a byte stream
+///
+/// Partially received frames are kept between calls, so the stream can be fed in chunks split at
+/// arbitrary points.
+pub trait Decoder {
+    /// Feeds `data` to the decoder, returns the frames it completed
+    fn feed(&mut self, data: &[u8]) -> Vec<FrameResult>;
+
+    /// Discards the partially received frame, if any
+    fn reset(&mut self);
+}
+
+/// Extracts frames from a buffer owned by the caller, without allocating
+///
+/// Unlike `Decoder`, the codec doesn't keep the partially received frame: the caller keeps the
+/// bytes that weren't consumed, in a fixed array or a `RingBuffer` for instance, and calls again
+/// once more data came in. The frame is decoded inside the buffer and returned as a slice of it.
+///
+/// ```ignore
+/// let (consumed, frame) = codec.decode_in_place(buf.slice_mut(start, end));
+/// match frame {
+///     Some(Ok(frame)) => handle(frame),
+///     Some(Err(e)) => warn(e),
+///     None => {},
+/// }
+/// start += consumed;
+/// ```
+pub trait InPlaceDecoder {
+    /// Decodes the first frame of `buf`, returns the number of bytes consumed and the frame, if
+    /// one was completed
+    ///
+    /// The consumed bytes are overwritten by the decoded frame and must be dropped before the
+    /// next call, even when no frame is returned, like for the skipped empty frames. Nothing is
+    /// consumed until the buffer holds a whole frame, so it must be large enough for the longest
+    /// encoded frame.
+    fn decode_in_place<'a>(&mut self, buf: &'a mut [u8])
+                           -> (uint, Option<BorrowedFrameResult<'a>>);
+}
+
+/// Sends and receives whole frames over a transport
+pub struct Framed<S, C> {
+    inner: S,
+    codec: C,
+    pending: RingBuf<FrameResult>,
+}
+
+impl<S: Reader + Writer, C: Decoder + Encoder> Framed<S, C> {
+    /// Frames the data sent/received through `inner` using `codec`
+    pub fn new(inner: S, codec: C) -> Framed<S, C> {
+        Framed {
+            inner: inner,
+            codec: codec,
+            pending: RingBuf::new(),
+        }
+    }
+
+    /// Returns a reference to the codec
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Returns a reference to the underlying transport
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying transport
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying transport, discarding any partially received frame
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Receives the next frame
+    ///
+    /// Malformed frames are reported as `InvalidInput` errors, errors of the transport (like
+    /// time outs) are passed through. In both cases the next call resumes with the following
+    /// frame.
+    pub fn read_frame(&mut self) -> IoResult<Vec<u8>> {
+        loop {
+            match self.pending.pop_front() {
+                None => {},
+                Some(Err(e)) => return Err(IoError {
+                    kind: InvalidInput,
+                    desc: "Malformed frame",
+                    detail: Some(e.to_string()),
+                }),
+                Some(Ok(frame)) => return Ok(frame),
+            }
+
+            let mut chunk = [0u8, ..CHUNK_SIZE];
+            let n = try!(self.inner.read(&mut chunk));
+            self.pending.extend(self.codec.feed(chunk.slice_to(n)).into_iter());
+        }
+    }
+
+    /// Encodes and sends `frame`
+    ///
+    /// Frames that the codec can't encode are refused with an `InvalidInput` error
+    pub fn write_frame(&mut self, frame: &[u8]) -> IoResult<()> {
+        let mut buf = Vec::new();
+
+        match self.codec.encode(frame, &mut buf) {
+            Err(e) => Err(IoError {
+                kind: InvalidInput,
+                desc: "Frame can't be encoded",
+                detail: Some(e.to_string()),
+            }),
+            Ok(_) => self.inner.write(buf.as_slice()),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use framing::{
+    BorrowedFrameResult, Decoder, Encoder, FrameError, FrameResult, InPlaceDecoder, InvalidEscape,
+};
+
+const END: u8 = 0xC0;
+const ESC: u8 = 0xDB;
+const ESC_END: u8 = 0xDC;
+const ESC_ESC: u8 = 0xDD;
+
+/// Serial Line Internet Protocol framing, as specified by RFC 1055
+///
+/// Frames are terminated by `END`, and `END`/`ESC` bytes inside the payload are replaced by the
+/// `ESC ESC_END`/`ESC ESC_ESC` sequences. Empty frames are skipped by the decoder, as they
+/// appear when the sender flushes line noise with an extra `END`.
+pub struct Slip {
+    buf: Vec<u8>,
+    escaped: bool,
+    invalid: Option<u8>,
+    leading_end: bool,
+}
+
+impl Slip {
+    /// Creates a codec that only sends `END` after each frame
+    pub fn new() -> Slip {
+        Slip {
+            buf: Vec::new(),
+            escaped: false,
+            invalid: None,
+            leading_end: false,
+        }
+    }
+
+    /// Creates a codec that also sends `END` before each frame, as recommended by the RFC to
+    /// flush any line noise accumulated by the receiver
+    pub fn with_leading_end() -> Slip {
+        Slip { leading_end: true, ..Slip::new() }
+    }
+}
+
+impl Decoder for Slip {
+    fn feed(&mut self, data: &[u8]) -> Vec<FrameResult> {
+        let mut frames = Vec::new();
+
+        for &byte in data.iter() {
+            if byte == END {
+                match self.invalid.take() {
+                    None => if !self.buf.is_empty() {
+                        frames.push(Ok(self.buf.clone()));
+                    },
+                    Some(byte) => frames.push(Err(InvalidEscape(byte))),
+                }
+
+                self.buf.clear();
+                self.escaped = false;
+            } else if self.escaped {
+                self.escaped = false;
+
+                match byte {
+                    ESC_END => self.buf.push(END),
+                    ESC_ESC => self.buf.push(ESC),
+                    _ => if self.invalid.is_none() {
+                        self.invalid = Some(byte);
+                    },
+                }
+            } else if byte == ESC {
+                self.escaped = true;
+            } else {
+                self.buf.push(byte);
+            }
+        }
+
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.buf.clear();
+        self.escaped = false;
+        self.invalid = None;
+    }
+}
+
+impl InPlaceDecoder for Slip {
+    fn decode_in_place<'a>(&mut self, buf: &'a mut [u8])
+                           -> (uint, Option<BorrowedFrameResult<'a>>) {
+        let end = match buf.iter().position(|&byte| byte == END) {
+            None => return (0, None),
+            Some(end) => end,
+        };
+
+        let mut len = 0;
+        let mut escaped = false;
+        let mut invalid = None;
+
+        for i in range(0, end) {
+            let byte = buf[i];
+
+            if escaped {
+                escaped = false;
+
+                match byte {
+                    ESC_END => { buf[len] = END; len += 1; },
+                    ESC_ESC => { buf[len] = ESC; len += 1; },
+                    _ => if invalid.is_none() {
+                        invalid = Some(byte);
+                    },
+                }
+            } else if byte == ESC {
+                escaped = true;
+            } else {
+                buf[len] = byte;
+                len += 1;
+            }
+        }
+
+        let frame = match invalid {
+            Some(byte) => Some(Err(InvalidEscape(byte))),
+            None if len == 0 => None,
+            None => Some(Ok(buf.slice_to(len))),
+        };
+
+        (end + 1, frame)
+    }
+}
+
+impl Encoder for Slip {
+    fn encode(&mut self, frame: &[u8], out: &mut Vec<u8>) -> Result<(), FrameError> {
+        if self.leading_end {
+            out.push(END);
+        }
+
+        for &byte in frame.iter() {
+            match byte {
+                END => out.push_all(&[ESC, ESC_END]),
+                ESC => out.push_all(&[ESC, ESC_ESC]),
+                _ => out.push(byte),
+            }
+        }
+
+        out.push(END);
+
+        Ok(())
+    }
+}
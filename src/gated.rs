@@ -0,0 +1,126 @@
+use std::cmp;
+use std::io::{IoError, IoResult, TimedOut};
+use std::io::timer;
+use std::time::Duration;
+use time;
+
+use {SerialIo, SerialPort, Settings};
+
+/// How often CTS is sampled while the device holds it deasserted
+const POLL_MS: i64 = 1;
+
+/// A port whose writes wait for the device to assert CTS, chunk by chunk
+///
+/// This is hardware flow control done by hand, for the devices that signal readiness on CTS but
+/// whose drivers don't implement `HardwareControl` properly. Each chunk is sent once CTS is
+/// asserted, and drained before CTS is checked again, so the device can stop the output between
+/// two chunks. Reads are passed through untouched.
+pub struct CtsGatedWriter {
+    inner: SerialPort,
+    chunk_size: uint,
+    /// How long to wait for CTS before each chunk
+    timeout: Duration,
+}
+
+impl CtsGatedWriter {
+    /// Gates the writes to `inner` by chunks of 16 bytes, waiting up to a second for CTS
+    pub fn new(inner: SerialPort) -> CtsGatedWriter {
+        CtsGatedWriter {
+            inner: inner,
+            chunk_size: 16,
+            timeout: Duration::seconds(1),
+        }
+    }
+
+    /// Sets the number of bytes sent each time CTS is found asserted
+    ///
+    /// The device can only hold the output back between two chunks, so a chunk has to fit in
+    /// its receive buffer.
+    pub fn chunk_size(mut self, chunk_size: uint) -> CtsGatedWriter {
+        self.chunk_size = cmp::max(chunk_size, 1);
+        self
+    }
+
+    /// Sets how long to wait for CTS before each chunk, the write fails with `TimedOut` past it
+    pub fn cts_timeout(mut self, timeout: Duration) -> CtsGatedWriter {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns a reference to the underlying port
+    pub fn get_ref(&self) -> &SerialPort {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying port
+    ///
+    /// Writing directly to the port bypasses the gate.
+    pub fn get_mut(&mut self) -> &mut SerialPort {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying port
+    pub fn into_inner(self) -> SerialPort {
+        self.inner
+    }
+
+    /// Waits until CTS is asserted, fails with `TimedOut` once the timeout elapses
+    fn wait_for_cts(&self) -> IoResult<()> {
+        let timeout = cmp::max(self.timeout.num_nanoseconds().unwrap_or(0), 0) as u64;
+        let deadline = time::precise_time_ns() + timeout;
+
+        while !try!(self.inner.cts()) {
+            if time::precise_time_ns() >= deadline {
+                return Err(IoError {
+                    kind: TimedOut,
+                    desc: "The device didn't assert CTS",
+                    detail: None,
+                })
+            }
+
+            timer::sleep(Duration::milliseconds(POLL_MS));
+        }
+
+        Ok(())
+    }
+}
+
+impl Reader for CtsGatedWriter {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        self.inner.read(buf)
+    }
+}
+
+impl SerialIo for CtsGatedWriter {
+    fn settings(&self) -> IoResult<Settings> {
+        self.inner.settings()
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.inner.configure(settings)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_timeout(timeout)
+    }
+}
+
+impl Writer for CtsGatedWriter {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        for chunk in buf.chunks(self.chunk_size) {
+            try!(self.wait_for_cts());
+            try!(self.inner.write(chunk));
+            try!(self.inner.drain());
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,232 @@
+//! Hex dumps of binary traffic, in the canonical format of `hexdump -C`
+//!
+//! `HexDumpReader` turns the bytes read from a port into dump text, `HexDumpWriter` turns the
+//! bytes written to it into dump text for the writer it wraps. Binary traffic can then be
+//! watched on a terminal, or compared in tests as text:
+//!
+//! ```ignore
+//! let mut console = HexDumpWriter::new(io::stdout());
+//! try!(io::util::copy(&mut port, &mut console));
+//! ```
+//!
+//! ```text
+//! 00000000  41 54 2b 43 47 4d 49 0d  0a 4f 4b 0d 0a           |AT+CGMI..OK..|
+//! 0000000d
+//! ```
+
+use std::cmp;
+use std::io::{EndOfFile, IoResult, TimedOut};
+use std::io;
+use std::slice::bytes;
+
+/// Bytes per line
+const BYTES_PER_LINE: uint = 16;
+
+const HEX_DIGITS: &'static [u8] = b"0123456789abcdef";
+
+/// Formats the dump lines, keeping the bytes of the line in progress
+struct Dump {
+    /// Offset of the first byte of `line`
+    offset: uint,
+    line: Vec<u8>,
+}
+
+impl Dump {
+    fn new() -> Dump {
+        Dump {
+            offset: 0,
+            line: Vec::with_capacity(BYTES_PER_LINE),
+        }
+    }
+
+    /// Adds `data`, appends the lines it completes to `out`
+    fn push(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        for &byte in data.iter() {
+            self.line.push(byte);
+
+            if self.line.len() == BYTES_PER_LINE {
+                self.end_line(out);
+            }
+        }
+    }
+
+    /// Appends the line in progress to `out`, even if it's short
+    fn end_line(&mut self, out: &mut Vec<u8>) {
+        if self.line.is_empty() {
+            return
+        }
+
+        push_hex(self.offset, 8, out);
+        out.push_all(b"  ");
+        for i in range(0, BYTES_PER_LINE) {
+            match self.line.get(i) {
+                Some(&byte) => {
+                    push_hex(byte as uint, 2, out);
+                    out.push(b' ');
+                },
+                None => out.push_all(b"   "),
+            }
+
+            if i == BYTES_PER_LINE / 2 - 1 {
+                out.push(b' ');
+            }
+        }
+
+        out.push_all(b" |");
+        for &byte in self.line.iter() {
+            out.push(if byte >= b' ' && byte <= b'~' { byte } else { b'.' });
+        }
+        out.push_all(b"|\n");
+
+        self.offset += self.line.len();
+        self.line.clear();
+    }
+
+    /// Appends the line in progress and the final offset to `out`
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        self.end_line(out);
+        push_hex(self.offset, 8, out);
+        out.push(b'\n');
+    }
+}
+
+/// Appends `value` in lower case hex to `out`, on at least `digits` digits
+fn push_hex(value: uint, digits: uint, out: &mut Vec<u8>) {
+    let mut hex = Vec::new();
+    let mut value = value;
+
+    while value != 0 || hex.len() < digits {
+        hex.push(HEX_DIGITS[value & 0x0F]);
+        value >>= 4;
+    }
+    hex.reverse();
+
+    out.push_all(hex.as_slice());
+}
+
+/// A reader that returns the hex dump of the bytes read from `inner`
+///
+/// A line is returned once its 16 bytes are read, so the dump doesn't depend on how the data
+/// was split by the reads. When `inner` times out, the line in progress is returned short and
+/// the next line starts at the following offset, so live traffic shows up without delay. At
+/// the end of `inner`, the final offset is returned before `EndOfFile`.
+pub struct HexDumpReader<R> {
+    inner: R,
+    dump: Dump,
+    /// Text not returned yet
+    pending: Vec<u8>,
+    pos: uint,
+    finished: bool,
+}
+
+impl<R: Reader> HexDumpReader<R> {
+    /// Dumps the bytes read from `inner`
+    pub fn new(inner: R) -> HexDumpReader<R> {
+        HexDumpReader {
+            inner: inner,
+            dump: Dump::new(),
+            pending: Vec::new(),
+            pos: 0,
+            finished: false,
+        }
+    }
+
+    /// Returns a reference to the reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwraps the reader, the text not returned yet is lost
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Reader> Reader for HexDumpReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        while self.pos == self.pending.len() {
+            self.pending.clear();
+            self.pos = 0;
+
+            if self.finished {
+                return Err(io::standard_error(EndOfFile))
+            }
+
+            let mut raw = [0u8, ..256];
+            match self.inner.read(&mut raw) {
+                Ok(n) => self.dump.push(raw.slice_to(n), &mut self.pending),
+                Err(ref e) if e.kind == EndOfFile => {
+                    self.dump.finish(&mut self.pending);
+                    self.finished = true;
+                },
+                Err(e) => {
+                    if e.kind == TimedOut {
+                        self.dump.end_line(&mut self.pending);
+                    }
+
+                    if self.pending.is_empty() {
+                        return Err(e)
+                    }
+                },
+            }
+        }
+
+        let n = cmp::min(buf.len(), self.pending.len() - self.pos);
+        bytes::copy_memory(buf, self.pending.slice(self.pos, self.pos + n));
+        self.pos += n;
+
+        Ok(n)
+    }
+}
+
+/// A writer that writes the hex dump of the bytes written to it to `inner`
+///
+/// The lines are written once their 16 bytes are; `flush` writes the line in progress short,
+/// and the next line starts at the following offset. `finish` ends the dump with the final
+/// offset, dropping the writer loses the line in progress.
+pub struct HexDumpWriter<W> {
+    inner: W,
+    dump: Dump,
+}
+
+impl<W: Writer> HexDumpWriter<W> {
+    /// Dumps the bytes written to `inner`
+    pub fn new(inner: W) -> HexDumpWriter<W> {
+        HexDumpWriter {
+            inner: inner,
+            dump: Dump::new(),
+        }
+    }
+
+    /// Returns a reference to the writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Writes the line in progress and the final offset, returns the writer
+    pub fn finish(mut self) -> IoResult<W> {
+        let mut text = Vec::new();
+        self.dump.finish(&mut text);
+        try!(self.inner.write(text.as_slice()));
+        try!(self.inner.flush());
+
+        Ok(self.inner)
+    }
+}
+
+impl<W: Writer> Writer for HexDumpWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let mut text = Vec::new();
+        self.dump.push(buf, &mut text);
+
+        self.inner.write(text.as_slice())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        let mut text = Vec::new();
+        self.dump.end_line(&mut text);
+        try!(self.inner.write(text.as_slice()));
+
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,172 @@
+//! Hardware in the loop tests, against a real adapter named by environment variables; enabled
+//! by the `testing` feature
+//!
+//! - `SERIAL_HIL_DEVICE`: path of the device, the tests are skipped when it's unset
+//! - `SERIAL_HIL_WIRING`: `loopback` (the default) for a plug that links TX to RX, RTS to CTS
+//!   and DTR to DSR and DCD, or `echo` for a remote device that sends back what it receives
+//! - `SERIAL_HIL_TIMEOUT_MS`: read timeout, 1000 by default
+//!
+//! The port is configured with the default `Settings`. The assertions panic, with the device
+//! path in the message.
+//!
+//! ```ignore
+//! #[test]
+//! fn firmware_upload() {
+//!     let mut hil = match Hil::from_env() {
+//!         None => return,
+//!         Some(hil) => hil,
+//!     };
+//!
+//!     hil.assert_round_trip(b"\x7E\x00\x7E");
+//!     hil.assert_latency(Duration::milliseconds(20));
+//! }
+//! ```
+
+use std::default::Default;
+use std::io::ReadWrite;
+use std::os;
+use std::time::Duration;
+use time;
+
+use {SerialPort, Settings};
+
+/// What's connected to the device under test
+#[deriving(Clone, PartialEq, Show)]
+pub enum Wiring {
+    /// A plug that links TX to RX, RTS to CTS, and DTR to DSR and DCD
+    Loopback,
+    /// A remote device that sends back the bytes it receives
+    EchoDevice,
+}
+
+/// An open device, described by the environment
+pub struct Hil {
+    path: Path,
+    port: SerialPort,
+    wiring: Wiring,
+}
+
+impl Hil {
+    /// Opens the device named by `SERIAL_HIL_DEVICE`, returns `None` if the variable is unset
+    ///
+    /// Panics if the other variables are invalid, or if the device can't be opened.
+    pub fn from_env() -> Option<Hil> {
+        let path = match os::getenv("SERIAL_HIL_DEVICE") {
+            None => return None,
+            Some(path) => Path::new(path),
+        };
+        let path_ = path.display();
+
+        let wiring = match os::getenv("SERIAL_HIL_WIRING") {
+            None => Loopback,
+            Some(wiring) => match wiring.as_slice() {
+                "echo" => EchoDevice,
+                "loopback" => Loopback,
+                wiring => panic!("SERIAL_HIL_WIRING: Expected loopback or echo, got {}", wiring),
+            },
+        };
+
+        let timeout = match os::getenv("SERIAL_HIL_TIMEOUT_MS") {
+            None => 1000,
+            Some(ms) => match from_str::<i64>(ms.as_slice()) {
+                Some(ms) if ms > 0 => ms,
+                _ => panic!("SERIAL_HIL_TIMEOUT_MS: Expected milliseconds, got {}", ms),
+            },
+        };
+
+        let mut port = match SerialPort::open(&path, ReadWrite) {
+            Err(e) => panic!("{}: Couldn't open ({})", path_, e),
+            Ok(port) => port,
+        };
+
+        let settings: Settings = Default::default();
+        match port.configure(&settings) {
+            Err(e) => panic!("{}: Couldn't apply settings {} ({})", path_, settings, e),
+            Ok(_) => {},
+        }
+        port.set_timeout(Some(Duration::milliseconds(timeout)));
+
+        Some(Hil {
+            path: path.clone(),
+            port: port,
+            wiring: wiring,
+        })
+    }
+
+    /// Returns the path of the device
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the open device, for the exchanges the assertions don't cover
+    pub fn port(&mut self) -> &mut SerialPort {
+        &mut self.port
+    }
+
+    pub fn wiring(&self) -> Wiring {
+        self.wiring
+    }
+
+    /// Sends `data`, and checks that it comes back unchanged
+    pub fn assert_round_trip(&mut self, data: &[u8]) {
+        let path_ = self.path.display();
+
+        match self.port.write(data) {
+            Err(e) => panic!("{}: Couldn't write {} bytes ({})", path_, data.len(), e),
+            Ok(_) => {},
+        }
+
+        match self.port.read_exact(data.len()) {
+            Err(e) => panic!("{}: Couldn't read {} bytes back ({})", path_, data.len(), e),
+            Ok(got) => if got.as_slice() != data {
+                panic!("{}: sent {} - got {}", path_, data, got)
+            },
+        }
+    }
+
+    /// Measures the time a byte takes to come back, and checks that it doesn't exceed `max`
+    ///
+    /// Returns the measured latency.
+    pub fn assert_latency(&mut self, max: Duration) -> Duration {
+        let path_ = self.path.display();
+
+        let start = time::precise_time_ns();
+        self.assert_round_trip(&[0x55]);
+        let latency = Duration::nanoseconds((time::precise_time_ns() - start) as i64);
+
+        if latency > max {
+            panic!("{}: latency {} exceeds {}", path_, latency, max)
+        }
+
+        latency
+    }
+
+    /// Toggles RTS and DTR, and checks that CTS, DSR and DCD follow
+    ///
+    /// Panics unless the wiring is `Loopback`, echo devices don't link the modem lines.
+    pub fn assert_modem_lines(&mut self) {
+        let path_ = self.path.display();
+
+        if self.wiring != Loopback {
+            panic!("{}: The modem lines need the loopback wiring, got {}", path_, self.wiring)
+        }
+
+        for &level in [true, false].iter() {
+            match self.port.set_rts(level).and(self.port.set_dtr(level)) {
+                Err(e) => panic!("{}: Couldn't set RTS and DTR ({})", path_, e),
+                Ok(_) => {},
+            }
+
+            let inputs = [("CTS", self.port.cts()), ("DSR", self.port.dsr()),
+                          ("DCD", self.port.dcd())];
+            for &(name, ref got) in inputs.iter() {
+                match *got {
+                    Err(ref e) => panic!("{}: Couldn't read {} ({})", path_, name, e),
+                    Ok(got) => if got != level {
+                        panic!("{}: {}: set {} - got {}", path_, name, level, got)
+                    },
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::io::IoResult;
+#[cfg(target_os = "macos")]
+use std::io::{IoError, IoUnavailable};
+
+/// A process that has a device open, see `holders`
+#[deriving(Clone, PartialEq, Show)]
+pub struct Holder {
+    pub pid: u32,
+    /// Name of the program, as in `/proc/<pid>/comm`
+    pub name: String,
+}
+
+/// Lists the processes that have `device` open, like `lsof` or `fuser` do
+///
+/// The open files of every process are scanned in `/proc/*/fd`, the processes of other users
+/// are only seen with the rights to inspect them. `SerialPort::open` lists the holders in its
+/// error when the device is busy. Only Linux exposes this information, elsewhere this fails with
+/// an `IoUnavailable` error.
+#[cfg(target_os = "linux")]
+pub fn holders(device: &Path) -> IoResult<Vec<Holder>> {
+    use std::io::File;
+    use std::io::fs;
+
+    let device = try!(device_number(device));
+    let mut found = Vec::new();
+
+    for process in try!(fs::readdir(&Path::new("/proc"))).iter() {
+        let pid = match process.filename_str().and_then(|name| from_str::<u32>(name)) {
+            None => continue,
+            Some(pid) => pid,
+        };
+
+        // The process exited, or isn't ours to inspect
+        let fds = match fs::readdir(&process.join("fd")) {
+            Err(_) => continue,
+            Ok(fds) => fds,
+        };
+
+        if fds.iter().any(|fd| device_number(fd).ok() == Some(device)) {
+            let name = File::open(&process.join("comm")).read_to_string().unwrap_or(String::new());
+            found.push(Holder { pid: pid, name: name.as_slice().trim().to_string() });
+        }
+    }
+
+    Ok(found)
+}
+
+/// Lists the processes that have `device` open
+///
+/// Only Linux exposes this information, elsewhere this fails with an `IoUnavailable` error.
+#[cfg(target_os = "macos")]
+pub fn holders(_: &Path) -> IoResult<Vec<Holder>> {
+    Err(IoError {
+        kind: IoUnavailable,
+        desc: "The holders of a device are only listed on Linux",
+        detail: None,
+    })
+}
+
+/// Describes the `holders` for an error detail, e.g. `held by 1234 (minicom)`
+pub fn describe(holders: &[Holder]) -> String {
+    let holders: Vec<String> = holders.iter().map(|holder| {
+        format!("{} ({})", holder.pid, holder.name)
+    }).collect();
+
+    format!("held by {}", holders.connect(", "))
+}
+
+/// Returns the device number of the character device at `path`, following links
+#[cfg(target_os = "linux")]
+fn device_number(path: &Path) -> IoResult<u64> {
+    use libc;
+    use libc::consts::os::posix88::{S_IFCHR, S_IFMT};
+    use libc::funcs::posix88::stat_::stat;
+    use std::io::{InvalidInput, IoError};
+    use std::mem;
+
+    use termios::FAILURE;
+
+    let mut info: libc::stat = unsafe { mem::zeroed() };
+    match path.with_c_str(|path| unsafe { stat(path, &mut info) }) {
+        FAILURE => Err(IoError::last_error()),
+        _ if info.st_mode & S_IFMT != S_IFCHR => Err(IoError {
+            kind: InvalidInput,
+            desc: "Not a character device",
+            detail: None,
+        }),
+        _ => Ok(info.st_rdev as u64),
+    }
+}
@@ -0,0 +1,282 @@
+use std::cmp;
+use std::io::fs::PathExtensions;
+use std::io::{IoError, IoResult, TimedOut};
+use std::io::timer;
+use std::time::Duration;
+use time;
+
+use poll::{POLLIN, wait};
+use ports::available_ports;
+use profile::DeviceMatcher;
+
+/// Longest wait between two lookups
+///
+/// The directory watch only tells when the directories change: udev creates the links of
+/// `/dev/serial` a little after the node, and may create their directory too.
+const CHECK_MS: u64 = 250;
+
+/// A device `wait_for_device` can wait for
+pub trait DeviceLookup {
+    /// Returns the path of the device if it's there
+    fn lookup(&self) -> IoResult<Option<Path>>;
+
+    /// Returns the directory where the device appears
+    fn directory(&self) -> Path;
+}
+
+/// A device at an exact path, a link is followed to check that the device is there
+impl DeviceLookup for Path {
+    fn lookup(&self) -> IoResult<Option<Path>> {
+        Ok(if self.exists() { Some(self.clone()) } else { None })
+    }
+
+    fn directory(&self) -> Path {
+        self.dir_path()
+    }
+}
+
+/// The first device selected, in the order of `available_ports`, or an exact path without a
+/// driver as is, like `DeviceMatcher::open` does
+impl DeviceLookup for DeviceMatcher {
+    fn lookup(&self) -> IoResult<Option<Path>> {
+        match (&self.path, &self.driver) {
+            (&Some(ref path), &None) if !path.as_slice().ends_with("*") => {
+                return Path::new(path.as_slice()).lookup()
+            },
+            _ => {},
+        }
+
+        let ports = try!(available_ports());
+        Ok(ports.into_iter().find(|port| self.matches(port)).map(|port| port.path))
+    }
+
+    fn directory(&self) -> Path {
+        match self.path {
+            None => Path::new("/dev"),
+            Some(ref path) => Path::new(path.as_slice()).dir_path(),
+        }
+    }
+}
+
+/// Waits until `device` appears, and returns its path
+///
+/// This lets a tool start before the adapter is plugged in. The directory of the device and
+/// `/dev` are watched, with inotify on Linux and kqueue on macOS, and the device is looked up
+/// again whenever they change; the lookups are polled when they can't be watched. Fails with a
+/// `TimedOut` error if the device is still missing once the `timeout` elapses, waits forever
+/// without one.
+///
+/// ```ignore
+/// let path = try!(wait_for_device(&Path::new("/dev/ttyUSB0"), None));
+/// let gps = DeviceMatcher { path: None, driver: Some("cdc_acm".to_string()) };
+/// let path = try!(wait_for_device(&gps, Some(Duration::seconds(30))));
+/// ```
+pub fn wait_for_device<D: DeviceLookup>(device: &D, timeout: Option<Duration>)
+                                        -> IoResult<Path> {
+    let deadline = timeout.map(|timeout| {
+        time::precise_time_ns() + cmp::max(timeout.num_nanoseconds().unwrap_or(0), 0) as u64
+    });
+
+    let mut directories = vec![Path::new("/dev")];
+    let directory = device.directory();
+    if directory.is_dir() && !directories.contains(&directory) {
+        directories.push(directory);
+    }
+    // Set up before the first lookup, so that no change is missed
+    let watch = match os::Watch::new(directories.as_slice()) {
+        Err(e) => {
+            debug!("wait_for_device: polling, the directories can't be watched ({})", e);
+            None
+        },
+        Ok(watch) => Some(watch),
+    };
+
+    loop {
+        match try!(device.lookup()) {
+            Some(path) => return Ok(path),
+            None => {},
+        }
+
+        let now = time::precise_time_ns();
+        let check = CHECK_MS * 1_000_000;
+        let wait_ns = match deadline {
+            Some(deadline) if now >= deadline => return Err(IoError {
+                kind: TimedOut,
+                desc: "The device didn't appear",
+                detail: None,
+            }),
+            Some(deadline) => cmp::min(deadline - now, check),
+            None => check,
+        };
+        let duration = Duration::nanoseconds(wait_ns as i64);
+
+        match watch {
+            None => timer::sleep(duration),
+            Some(ref watch) => {
+                if try!(wait(watch.fd(), POLLIN, Some(duration))) {
+                    watch.clear();
+                }
+            },
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use libc::{c_char, c_int, c_void, size_t};
+    use libc;
+    use native::io::file::FileDesc;
+    use std::io::{IoError, IoResult};
+
+    use termios::FAILURE;
+
+    const IN_NONBLOCK: c_int = 0o4000;
+    const IN_CLOEXEC: c_int = 0o2000000;
+    const IN_ATTRIB: u32 = 0x004;
+    const IN_MOVED_TO: u32 = 0x080;
+    const IN_CREATE: u32 = 0x100;
+
+    extern {
+        fn inotify_init1(flags: c_int) -> c_int;
+        fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
+    }
+
+    /// An inotify instance watching directories for new entries
+    pub struct Watch {
+        fd: FileDesc,
+    }
+
+    impl Watch {
+        pub fn new(directories: &[Path]) -> IoResult<Watch> {
+            let fd = match unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) } {
+                FAILURE => return Err(IoError::last_error()),
+                fd => FileDesc::new(fd, true),
+            };
+
+            for directory in directories.iter() {
+                // The attributes change when udev sets the permissions of a new node
+                match directory.with_c_str(|path| unsafe {
+                    inotify_add_watch(fd.fd(), path, IN_CREATE | IN_MOVED_TO | IN_ATTRIB)
+                }) {
+                    FAILURE => return Err(IoError::last_error()),
+                    _ => {},
+                }
+            }
+
+            Ok(Watch { fd: fd })
+        }
+
+        /// Readable when a directory changed
+        pub fn fd(&self) -> c_int {
+            self.fd.fd()
+        }
+
+        /// Discards the pending events
+        pub fn clear(&self) {
+            let mut buf = [0u8, ..4096];
+
+            while unsafe {
+                libc::read(self.fd.fd(), buf.as_mut_ptr() as *mut c_void, buf.len() as size_t)
+            } > 0 {}
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use libc::{c_int, c_long, c_void, intptr_t, time_t, uintptr_t};
+    use libc;
+    use native::io::file::FileDesc;
+    use std::io::{IoError, IoResult};
+    use std::ptr;
+
+    use O_CLOEXEC;
+    use termios::FAILURE;
+
+    /// Opened for the events only, without preventing the unmount of the volume
+    const O_EVTONLY: c_int = 0x8000;
+    const EVFILT_VNODE: i16 = -4;
+    const EV_ADD: u16 = 0x0001;
+    const EV_CLEAR: u16 = 0x0020;
+    const NOTE_WRITE: u32 = 0x0002;
+
+    /// `struct kevent`
+    #[repr(C)]
+    struct KEvent {
+        ident: uintptr_t,
+        filter: i16,
+        flags: u16,
+        fflags: u32,
+        data: intptr_t,
+        udata: *mut c_void,
+    }
+
+    /// `struct timespec`
+    #[repr(C)]
+    struct Timespec {
+        sec: time_t,
+        nsec: c_long,
+    }
+
+    extern {
+        fn kqueue() -> c_int;
+        fn kevent(kq: c_int, changelist: *const KEvent, nchanges: c_int,
+                  eventlist: *mut KEvent, nevents: c_int, timeout: *const Timespec) -> c_int;
+    }
+
+    /// A kqueue watching directories for writes, that is changes of their entries
+    pub struct Watch {
+        kq: FileDesc,
+        /// Watched, kept open as long as the queue
+        _directories: Vec<FileDesc>,
+    }
+
+    impl Watch {
+        pub fn new(directories: &[Path]) -> IoResult<Watch> {
+            let kq = match unsafe { kqueue() } {
+                FAILURE => return Err(IoError::last_error()),
+                fd => FileDesc::new(fd, true),
+            };
+
+            let mut opened = Vec::new();
+            for directory in directories.iter() {
+                let fd = match directory.with_c_str(|path| unsafe {
+                    libc::open(path, O_EVTONLY | O_CLOEXEC, 0)
+                }) {
+                    FAILURE => return Err(IoError::last_error()),
+                    fd => FileDesc::new(fd, true),
+                };
+
+                let change = KEvent {
+                    ident: fd.fd() as uintptr_t,
+                    filter: EVFILT_VNODE,
+                    flags: EV_ADD | EV_CLEAR,
+                    fflags: NOTE_WRITE,
+                    data: 0,
+                    udata: ptr::null_mut(),
+                };
+                match unsafe { kevent(kq.fd(), &change, 1, ptr::null_mut(), 0, ptr::null()) } {
+                    FAILURE => return Err(IoError::last_error()),
+                    _ => {},
+                }
+
+                opened.push(fd);
+            }
+
+            Ok(Watch { kq: kq, _directories: opened })
+        }
+
+        /// Readable when a directory changed
+        pub fn fd(&self) -> c_int {
+            self.kq.fd()
+        }
+
+        /// Discards the pending events
+        pub fn clear(&self) {
+            let mut events: Vec<KEvent> = Vec::with_capacity(8);
+            let now = Timespec { sec: 0, nsec: 0 };
+
+            unsafe { kevent(self.kq.fd(), ptr::null(), 0, events.as_mut_ptr(), 8, &now) };
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Interactive passthrough between a port and a terminal
+//!
+//! This is the core of a terminal program like picocom: what's typed goes to the device, and
+//! what the device prints goes to the screen, until the escape character is typed.
+
+use std::comm::{Disconnected, Empty};
+use std::default::Default;
+use std::io::{EndOfFile, IoResult, TimedOut};
+use std::time::Duration;
+
+use SerialIo;
+
+/// Ctrl-], the escape character of telnet
+pub const DEFAULT_ESCAPE: u8 = 0x1D;
+
+/// How often the port is checked while waiting for input
+const POLL_INTERVAL_MS: i64 = 10;
+
+/// Options of an interactive session
+#[deriving(Clone, PartialEq, Show)]
+pub struct Options {
+    /// Also copy the input to the output, for devices that don't echo
+    pub local_echo: bool,
+    /// Typing this byte ends the session, it isn't sent to the device
+    pub escape: u8,
+}
+
+impl Default for Options {
+    /// No local echo, Ctrl-] to exit
+    fn default() -> Options {
+        Options {
+            local_echo: false,
+            escape: DEFAULT_ESCAPE,
+        }
+    }
+}
+
+/// Bridges `port` to `input` and `output`, until the escape byte is read from `input`, or
+/// `input` ends
+///
+/// `input` is read from a separate task, which only finishes once its next read returns. The
+/// timeout of the port is restored at the end of the session.
+pub fn interact<S: SerialIo, R: Reader + Send, W: Writer>(port: &mut S, input: R,
+                                                          output: &mut W, options: &Options)
+                                                          -> IoResult<()> {
+    let (tx, rx) = channel();
+    spawn(proc() {
+        let mut input = input;
+        let mut buf = [0u8, ..256];
+
+        loop {
+            let chunk = match input.read(&mut buf) {
+                Err(_) => None,
+                Ok(n) => Some(buf.slice_to(n).to_vec()),
+            };
+            let end = chunk.is_none();
+
+            if tx.send_opt(chunk).is_err() || end {
+                break
+            }
+        }
+    });
+
+    let timeout = port.timeout();
+    port.set_timeout(Some(Duration::milliseconds(POLL_INTERVAL_MS)));
+
+    let result = bridge(port, &rx, output, options);
+
+    port.set_timeout(timeout);
+    result
+}
+
+/// Copies data in both directions, until the escape byte or the end of the input
+fn bridge<S: SerialIo, W: Writer>(port: &mut S, input: &Receiver<Option<Vec<u8>>>,
+                                  output: &mut W, options: &Options) -> IoResult<()> {
+    let mut buf = [0u8, ..256];
+
+    loop {
+        match port.read(&mut buf) {
+            Err(ref e) if e.kind == TimedOut => {},
+            Err(ref e) if e.kind == EndOfFile => return Ok(()),
+            Err(e) => return Err(e),
+            Ok(n) => {
+                try!(output.write(buf.slice_to(n)));
+                try!(output.flush());
+            },
+        }
+
+        loop {
+            let chunk = match input.try_recv() {
+                Err(Empty) => break,
+                Err(Disconnected) | Ok(None) => return Ok(()),
+                Ok(Some(chunk)) => chunk,
+            };
+
+            let (chunk, escaped) = match chunk.iter().position(|&byte| byte == options.escape) {
+                None => (chunk.as_slice(), false),
+                Some(i) => (chunk.slice_to(i), true),
+            };
+
+            try!(port.write(chunk));
+            if options.local_echo {
+                try!(output.write(chunk));
+                try!(output.flush());
+            }
+
+            if escaped {
+                return Ok(())
+            }
+        }
+    }
+}
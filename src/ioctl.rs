@@ -0,0 +1,167 @@
+use libc::{c_int, c_ulong};
+
+pub use self::os::{TIOCCBRK, TIOCMBIC, TIOCMBIS, TIOCMGET, TIOCOUTQ, TIOCSBRK};
+
+#[cfg(target_os = "linux")]
+pub use self::os::{SerialIcounter, SerialStruct, TCGETS2, TCSETS2, TIOCGICOUNT, TIOCGSERIAL};
+#[cfg(target_os = "linux")]
+pub use self::os::{TIOCMIWAIT, TIOCSERGETLSR, TIOCSER_TEMT, TIOCSSERIAL, Termios2};
+
+pub const TIOCM_CAR: c_int = 0x040;
+pub const TIOCM_CTS: c_int = 0x020;
+pub const TIOCM_DSR: c_int = 0x100;
+pub const TIOCM_DTR: c_int = 0x002;
+pub const TIOCM_RNG: c_int = 0x080;
+pub const TIOCM_RTS: c_int = 0x004;
+
+#[cfg(target_os = "linux")]
+mod os {
+    use libc::{c_char, c_int, c_uchar, c_uint, c_ulong, c_ushort};
+    use std::ptr;
+
+    pub const TCGETS2: c_ulong = 0x802C542A;
+    pub const TCSETS2: c_ulong = 0x402C542B;
+    pub const TIOCCBRK: c_ulong = 0x5428;
+    pub const TIOCMBIC: c_ulong = 0x5417;
+    pub const TIOCMBIS: c_ulong = 0x5416;
+    pub const TIOCMGET: c_ulong = 0x5415;
+    pub const TIOCGICOUNT: c_ulong = 0x545D;
+    pub const TIOCGSERIAL: c_ulong = 0x541E;
+    pub const TIOCMIWAIT: c_ulong = 0x545C;
+    pub const TIOCOUTQ: c_ulong = 0x5411;
+    pub const TIOCSBRK: c_ulong = 0x5427;
+    pub const TIOCSERGETLSR: c_ulong = 0x5459;
+    pub const TIOCSSERIAL: c_ulong = 0x541F;
+
+    /// Bit of `TIOCSERGETLSR`, the transmit shift register is empty
+    pub const TIOCSER_TEMT: c_uint = 0x01;
+
+    /// `struct serial_struct`, the parameters of the serial drivers
+    #[repr(C)]
+    pub struct SerialStruct {
+        pub type_: c_int,
+        pub line: c_int,
+        pub port: c_uint,
+        pub irq: c_int,
+        pub flags: c_int,
+        pub xmit_fifo_size: c_int,
+        pub custom_divisor: c_int,
+        pub baud_base: c_int,
+        pub close_delay: c_ushort,
+        io_type: c_char,
+        reserved_char: [c_char, ..1],
+        hub6: c_int,
+        pub closing_wait: c_ushort,
+        closing_wait2: c_ushort,
+        iomem_base: *mut c_uchar,
+        iomem_reg_shift: c_ushort,
+        port_high: c_uint,
+        iomap_base: c_ulong,
+    }
+
+    impl SerialStruct {
+        pub fn new() -> SerialStruct {
+            SerialStruct {
+                baud_base: 0,
+                close_delay: 0,
+                closing_wait2: 0,
+                closing_wait: 0,
+                custom_divisor: 0,
+                flags: 0,
+                hub6: 0,
+                io_type: 0,
+                iomap_base: 0,
+                iomem_base: ptr::null_mut(),
+                iomem_reg_shift: 0,
+                irq: 0,
+                line: 0,
+                port: 0,
+                port_high: 0,
+                reserved_char: [0, ..1],
+                type_: 0,
+                xmit_fifo_size: 0,
+            }
+        }
+    }
+
+    /// `struct serial_icounter_struct`, the interrupt counters of the serial drivers
+    #[repr(C)]
+    pub struct SerialIcounter {
+        pub cts: c_int,
+        pub dsr: c_int,
+        pub rng: c_int,
+        pub dcd: c_int,
+        rx: c_int,
+        tx: c_int,
+        pub frame: c_int,
+        pub overrun: c_int,
+        pub parity: c_int,
+        pub brk: c_int,
+        pub buf_overrun: c_int,
+        reserved: [c_int, ..9],
+    }
+
+    impl SerialIcounter {
+        pub fn new() -> SerialIcounter {
+            SerialIcounter {
+                brk: 0,
+                buf_overrun: 0,
+                cts: 0,
+                dcd: 0,
+                dsr: 0,
+                frame: 0,
+                overrun: 0,
+                parity: 0,
+                reserved: [0, ..9],
+                rng: 0,
+                rx: 0,
+                tx: 0,
+            }
+        }
+    }
+
+    /// The kernel termios structure, which carries the baud rates as plain numbers
+    #[repr(C)]
+    pub struct Termios2 {
+        c_iflag: c_uint,
+        c_oflag: c_uint,
+        pub c_cflag: c_uint,
+        c_lflag: c_uint,
+        c_line: u8,
+        c_cc: [u8, ..19],
+        pub c_ispeed: c_uint,
+        pub c_ospeed: c_uint,
+    }
+
+    impl Termios2 {
+        pub fn new() -> Termios2 {
+            Termios2 {
+                c_cc: [0, ..19],
+                c_cflag: 0,
+                c_iflag: 0,
+                c_ispeed: 0,
+                c_lflag: 0,
+                c_line: 0,
+                c_oflag: 0,
+                c_ospeed: 0,
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use libc::c_ulong;
+
+    pub const TIOCCBRK: c_ulong = 0x2000747A;
+    pub const TIOCMBIC: c_ulong = 0x8004746B;
+    pub const TIOCMBIS: c_ulong = 0x8004746C;
+    pub const TIOCMGET: c_ulong = 0x4004746A;
+    pub const TIOCOUTQ: c_ulong = 0x40047473;
+    pub const TIOCSBRK: c_ulong = 0x2000747B;
+}
+
+#[link(name = "c")]
+extern {
+    pub fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
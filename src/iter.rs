@@ -0,0 +1,150 @@
+use std::io::{Buffer, EndOfFile, IoResult, TimedOut};
+
+/// What an iterator over incoming data does when a read times out
+#[deriving(Clone, PartialEq, Show)]
+pub enum TimeoutBehavior {
+    /// Keep waiting for more data
+    IgnoreTimeout,
+    /// End the iteration
+    StopOnTimeout,
+    /// Yield the `TimedOut` error, the iteration can continue afterwards
+    YieldTimeout,
+}
+
+/// Iterator over the bytes received by a port
+///
+/// Created by `SerialPort::incoming_bytes()`. Reads are done in chunks, through the buffer of
+/// `inner`: the bytes read ahead stay there for the next reads when the iterator is dropped.
+pub struct IncomingBytes<'a, R: 'a> {
+    inner: &'a mut R,
+    on_timeout: TimeoutBehavior,
+}
+
+impl<'a, R: Buffer> IncomingBytes<'a, R> {
+    /// Iterates over the bytes read from `inner`, stopping on the first time out
+    pub fn new(inner: &'a mut R) -> IncomingBytes<'a, R> {
+        IncomingBytes {
+            inner: inner,
+            on_timeout: StopOnTimeout,
+        }
+    }
+
+    /// Changes what the iterator does when a read times out
+    pub fn on_timeout(mut self, behavior: TimeoutBehavior) -> IncomingBytes<'a, R> {
+        self.on_timeout = behavior;
+        self
+    }
+}
+
+impl<'a, R: Buffer> Iterator<IoResult<u8>> for IncomingBytes<'a, R> {
+    fn next(&mut self) -> Option<IoResult<u8>> {
+        loop {
+            let byte = match self.inner.fill_buf() {
+                Err(ref e) if e.kind == EndOfFile => return None,
+                Err(ref e) if e.kind == TimedOut => match self.on_timeout {
+                    IgnoreTimeout => continue,
+                    StopOnTimeout => return None,
+                    YieldTimeout => return Some(Err(e.clone())),
+                },
+                Err(e) => return Some(Err(e)),
+                Ok(available) => match available.head() {
+                    None => continue,
+                    Some(&byte) => byte,
+                },
+            };
+            self.inner.consume(1);
+
+            return Some(Ok(byte))
+        }
+    }
+}
+
+/// Iterator over the lines received by a port
+///
+/// Created by `SerialPort::lines()`. Each line includes its terminator, except maybe the last
+/// one if the port reached EOF. Invalid UTF-8 is replaced with U+FFFD. A partially received line
+/// is discarded if the iteration stops because of a time out; the bytes past the last line
+/// returned stay in the buffer of `inner`.
+pub struct Lines<'a, R: 'a> {
+    inner: &'a mut R,
+    buf: Vec<u8>,
+    terminator: u8,
+    on_timeout: TimeoutBehavior,
+    eof: bool,
+}
+
+impl<'a, R: Buffer> Lines<'a, R> {
+    /// Iterates over the `\n` terminated lines read from `inner`, stopping on the first time out
+    pub fn new(inner: &'a mut R) -> Lines<'a, R> {
+        Lines {
+            inner: inner,
+            buf: Vec::new(),
+            terminator: b'\n',
+            on_timeout: StopOnTimeout,
+            eof: false,
+        }
+    }
+
+    /// Changes the byte that terminates each line
+    pub fn terminator(mut self, terminator: u8) -> Lines<'a, R> {
+        self.terminator = terminator;
+        self
+    }
+
+    /// Changes what the iterator does when a read times out
+    pub fn on_timeout(mut self, behavior: TimeoutBehavior) -> Lines<'a, R> {
+        self.on_timeout = behavior;
+        self
+    }
+
+    /// Splits off the first `len` bytes of the buffer as a line
+    fn take_line(&mut self, len: uint) -> String {
+        let line = String::from_utf8_lossy(self.buf.slice_to(len)).into_string();
+        self.buf = self.buf.slice_from(len).to_vec();
+        line
+    }
+}
+
+impl<'a, R: Buffer> Iterator<IoResult<String>> for Lines<'a, R> {
+    fn next(&mut self) -> Option<IoResult<String>> {
+        let terminator = self.terminator;
+
+        loop {
+            let end = self.buf.iter().position(|&byte| byte == terminator);
+            match end {
+                None => {},
+                Some(i) => return Some(Ok(self.take_line(i + 1))),
+            }
+
+            if self.eof {
+                return if self.buf.is_empty() {
+                    None
+                } else {
+                    let len = self.buf.len();
+                    Some(Ok(self.take_line(len)))
+                }
+            }
+
+            // Nothing is taken past the terminator
+            let taken = match self.inner.fill_buf() {
+                Err(ref e) if e.kind == EndOfFile => {
+                    self.eof = true;
+                    0
+                },
+                Err(ref e) if e.kind == TimedOut => match self.on_timeout {
+                    IgnoreTimeout => 0,
+                    StopOnTimeout => return None,
+                    YieldTimeout => return Some(Err(e.clone())),
+                },
+                Err(e) => return Some(Err(e)),
+                Ok(available) => {
+                    let end = available.iter().position(|&byte| byte == terminator);
+                    let taken = end.map_or(available.len(), |i| i + 1);
+                    self.buf.push_all(available.slice_to(taken));
+                    taken
+                },
+            };
+            self.inner.consume(taken);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::cmp;
+use std::comm::{Disconnected, Empty};
+use std::io::IoError;
+use std::io::timer;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time;
+
+use SerialPort;
+
+/// Longest sleep of the keepalive task, so that it notices when it's stopped
+const SLICE_MS: u64 = 50;
+
+/// Handle of the keepalive task of a `SharedSerialPort`, see `SharedSerialPort::keepalive`
+///
+/// Dropping the handle stops the task.
+pub struct Keepalive {
+    /// Disconnected to stop the task
+    _stop: Sender<()>,
+    errors: Receiver<IoError>,
+}
+
+impl Keepalive {
+    /// Returns the error that stopped the task if a keepalive frame couldn't be written, once
+    pub fn error(&self) -> Option<IoError> {
+        self.errors.try_recv().ok()
+    }
+
+    /// Stops the task
+    pub fn stop(self) {}
+}
+
+/// Starts a task that writes `frame` to `port` whenever nothing was written for `interval`
+pub fn start(port: Arc<Mutex<SerialPort>>, frame: Vec<u8>, interval: Duration) -> Keepalive {
+    let (stop_sender, stop) = channel::<()>();
+    let (error_sender, errors) = channel();
+    let interval = cmp::max(interval.num_nanoseconds().unwrap_or(0), 1) as u64;
+
+    spawn(proc() {
+        loop {
+            match stop.try_recv() {
+                Err(Empty) => {},
+                Err(Disconnected) | Ok(()) => return,
+            }
+
+            let next = port.lock().last_write + interval;
+            let now = time::precise_time_ns();
+            if now < next {
+                let nanos = cmp::min(next - now, SLICE_MS * 1_000_000);
+                timer::sleep(Duration::nanoseconds(nanos as i64));
+                continue
+            }
+
+            let mut port = port.lock();
+            // The application may have written meanwhile
+            if time::precise_time_ns() >= port.last_write + interval {
+                match port.write(frame.as_slice()) {
+                    Err(e) => {
+                        let _ = error_sender.send_opt(e);
+                        return
+                    },
+                    Ok(()) => {},
+                }
+            }
+        }
+    });
+
+    Keepalive {
+        _stop: stop_sender,
+        errors: errors,
+    }
+}
@@ -0,0 +1,172 @@
+//! KISS, the protocol spoken by amateur radio TNCs
+//!
+//! Frames use the SLIP framing (`FEND`, `FESC`, `TFEND` and `TFESC` are the SLIP `END`, `ESC`,
+//! `ESC_END` and `ESC_ESC` bytes). The first byte of a frame holds the radio port in its high
+//! nibble and the command in its low nibble, data frames carry an AX.25 frame without its FCS.
+
+use std::cmp;
+use std::io::IoResult;
+use std::time::Duration;
+
+use framing::{Framed, Slip};
+
+const DATA: u8 = 0x00;
+const TX_DELAY: u8 = 0x01;
+const PERSISTENCE: u8 = 0x02;
+const SLOT_TIME: u8 = 0x03;
+const TX_TAIL: u8 = 0x04;
+const FULL_DUPLEX: u8 = 0x05;
+const SET_HARDWARE: u8 = 0x06;
+const RETURN: u8 = 0xFF;
+
+/// A KISS frame, the first field is the radio port (0 to 15)
+///
+/// The times are in units of 10 ms.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Frame {
+    /// An AX.25 frame to transmit, or received by the radio
+    DataFrame(u8, Vec<u8>),
+    /// Time to wait between keying the transmitter and sending data
+    TxDelayFrame(u8, u8),
+    /// Persistence parameter `p` of the CSMA, scaled to `0..255`
+    PersistenceFrame(u8, u8),
+    /// Time slot of the CSMA
+    SlotTimeFrame(u8, u8),
+    /// Time to keep the transmitter keyed after the data (obsolete)
+    TxTailFrame(u8, u8),
+    FullDuplexFrame(u8, bool),
+    /// Hardware specific command
+    SetHardwareFrame(u8, Vec<u8>),
+    /// Makes the TNC leave KISS mode
+    ReturnFrame,
+}
+
+impl Frame {
+    /// Encodes the frame, without the SLIP framing
+    pub fn encode(&self) -> Vec<u8> {
+        let (port, command, payload) = match *self {
+            DataFrame(port, ref data) => (port, DATA, data.clone()),
+            TxDelayFrame(port, delay) => (port, TX_DELAY, vec![delay]),
+            PersistenceFrame(port, p) => (port, PERSISTENCE, vec![p]),
+            SlotTimeFrame(port, time) => (port, SLOT_TIME, vec![time]),
+            TxTailFrame(port, time) => (port, TX_TAIL, vec![time]),
+            FullDuplexFrame(port, enable) => (port, FULL_DUPLEX, vec![enable as u8]),
+            SetHardwareFrame(port, ref data) => (port, SET_HARDWARE, data.clone()),
+            ReturnFrame => return vec![RETURN],
+        };
+
+        let mut bytes = vec![(port & 0x0F) << 4 | command];
+        bytes.push_all(payload.as_slice());
+
+        bytes
+    }
+
+    /// Parses a frame stripped of its SLIP framing, `None` if the command is unknown or its
+    /// parameter is missing
+    pub fn parse(bytes: &[u8]) -> Option<Frame> {
+        if bytes.is_empty() {
+            return None
+        } else if bytes[0] == RETURN {
+            return Some(ReturnFrame)
+        }
+
+        let (port, command) = (bytes[0] >> 4, bytes[0] & 0x0F);
+        let payload = bytes.slice_from(1);
+
+        match command {
+            DATA => Some(DataFrame(port, payload.to_vec())),
+            SET_HARDWARE => Some(SetHardwareFrame(port, payload.to_vec())),
+            _ if payload.is_empty() => None,
+            TX_DELAY => Some(TxDelayFrame(port, payload[0])),
+            PERSISTENCE => Some(PersistenceFrame(port, payload[0])),
+            SLOT_TIME => Some(SlotTimeFrame(port, payload[0])),
+            TX_TAIL => Some(TxTailFrame(port, payload[0])),
+            FULL_DUPLEX => Some(FullDuplexFrame(port, payload[0] != 0)),
+            _ => None,
+        }
+    }
+}
+
+/// Converts `time` to the 10 ms units of KISS
+fn ticks(time: Duration) -> u8 {
+    cmp::min(cmp::max(time.num_milliseconds() / 10, 0), 255) as u8
+}
+
+/// A TNC in KISS mode, connected to a port
+pub struct Tnc<S> {
+    inner: Framed<S, Slip>,
+}
+
+impl<S: Reader + Writer> Tnc<S> {
+    /// Talks KISS through `port`, the TNC must already be in KISS mode
+    pub fn new(port: S) -> Tnc<S> {
+        Tnc {
+            inner: Framed::new(port, Slip::with_leading_end()),
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut S {
+        self.inner.get_mut()
+    }
+
+    /// Unwraps the port
+    pub fn into_inner(self) -> S {
+        self.inner.into_inner()
+    }
+
+    /// Receives the next frame
+    ///
+    /// Frames with an unknown command are skipped. Use a timeout on the port to bound the wait.
+    pub fn receive(&mut self) -> IoResult<Frame> {
+        loop {
+            match Frame::parse(try!(self.inner.read_frame()).as_slice()) {
+                None => {},
+                Some(frame) => return Ok(frame),
+            }
+        }
+    }
+
+    /// Sends a frame
+    pub fn send(&mut self, frame: &Frame) -> IoResult<()> {
+        self.inner.write_frame(frame.encode().as_slice())
+    }
+
+    /// Transmits an AX.25 `frame` (without FCS) on the radio `port`
+    pub fn send_data(&mut self, port: u8, frame: &[u8]) -> IoResult<()> {
+        self.send(&DataFrame(port, frame.to_vec()))
+    }
+
+    /// Makes the TNC leave KISS mode
+    pub fn exit_kiss(&mut self) -> IoResult<()> {
+        self.send(&ReturnFrame)
+    }
+
+    /// Enables or disables full duplex on the radio `port`
+    pub fn set_full_duplex(&mut self, port: u8, enable: bool) -> IoResult<()> {
+        self.send(&FullDuplexFrame(port, enable))
+    }
+
+    /// Changes the persistence `p` of the radio `port`, the chance of transmitting in a free slot
+    /// is `(p + 1) / 256`
+    pub fn set_persistence(&mut self, port: u8, p: u8) -> IoResult<()> {
+        self.send(&PersistenceFrame(port, p))
+    }
+
+    /// Changes the slot time of the radio `port`, rounded down to 10 ms
+    pub fn set_slot_time(&mut self, port: u8, time: Duration) -> IoResult<()> {
+        self.send(&SlotTimeFrame(port, ticks(time)))
+    }
+
+    /// Changes the delay between keying the transmitter of the radio `port` and sending data,
+    /// rounded down to 10 ms
+    pub fn set_tx_delay(&mut self, port: u8, delay: Duration) -> IoResult<()> {
+        self.send(&TxDelayFrame(port, ticks(delay)))
+    }
+
+    /// Changes the time the transmitter of the radio `port` stays keyed after the data, rounded
+    /// down to 10 ms
+    pub fn set_tx_tail(&mut self, port: u8, time: Duration) -> IoResult<()> {
+        self.send(&TxTailFrame(port, ticks(time)))
+    }
+}
@@ -2,26 +2,150 @@
 #![feature(phase, tuple_indexing)]
 
 extern crate libc;
+#[phase(plugin, link)]
+extern crate log;
 extern crate native;
-#[cfg(test)]
+extern crate regex;
+extern crate serialize;
+extern crate time;
+#[cfg(any(test, feature = "arbitrary"))]
 extern crate quickcheck;
 #[cfg(test)]
 #[phase(plugin)]
 extern crate quickcheck_macros;
 
+use libc::funcs::posix88::fcntl::fcntl;
 use native::io::file::FileDesc;
-use std::io::{FileAccess, IoError, IoResult, Read, ReadWrite, Write};
+use std::ascii::AsciiExt;
+use std::cmp;
+use std::default::Default;
+use std::fmt;
+use std::io::{Buffer, EndOfFile, FileAccess, InvalidInput, IoError, IoResult, IoUnavailable};
+use std::io::{Read, ReadWrite, TimedOut, Write};
+use std::ptr;
+use std::slice::bytes;
+use std::str::FromStr;
+use std::time::Duration;
+use time::Timespec;
 
 use termios::{FAILURE, Termios, SUCCESS};
 
+pub use buffered::BufferedSerialPort;
+pub use cancel::ReadCanceller;
+pub use clock::VirtualClock;
+pub use driver::DriverInfo;
+pub use edge::{CarrierDetect, ClearToSend, DataSetReady, Edge, RingIndicator, StatusLine};
+pub use events::{BREAK_RECEIVED, CD_CHANGED, CTS_CHANGED, DSR_CHANGED, LINE_ERROR, RI_CHANGED};
+pub use events::{EventHandler, EventSet, ModemStatus, RX_AVAILABLE, TX_EMPTY};
+pub use fdpass::{receive_fd, send_fd};
+pub use flags::{ControlFlags, InputFlags, LocalFlags};
+pub use gated::CtsGatedWriter;
+pub use holders::{Holder, holders};
+pub use hotplug::{DeviceLookup, wait_for_device};
+pub use iter::{IgnoreTimeout, IncomingBytes, Lines, StopOnTimeout, TimeoutBehavior, YieldTimeout};
+pub use keepalive::Keepalive;
+pub use mismatch::{BaudCheck, BaudDiagnosis};
+pub use pair::{VirtualPort, virtual_pair};
+pub use priority::{PriorityWriter, WriteQueue};
+pub use probe::{Detection, ProbeSpec, probe};
+pub use ring::RingBuffer;
+pub use shared::SharedSerialPort;
+pub use splice::{Descriptor, copy};
+pub use tap::Tap;
+pub use throttled::ThrottledWriter;
+
+pub mod analyzer;
+pub mod at;
+pub mod capture;
+pub mod checksum;
+pub mod escpos;
+pub mod expect;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod firmata;
+pub mod flags;
+pub mod framing;
+pub mod hexdump;
+#[cfg(any(test, feature = "testing"))]
+pub mod hil;
+pub mod interact;
+pub mod kiss;
+pub mod lin;
+pub mod midi;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
+pub mod modbus;
+pub mod mux;
+pub mod nmea;
+pub mod obd;
+pub mod ports;
+pub mod profile;
+pub mod programmer;
+pub mod raw;
+pub mod replay;
+pub mod rfc2217;
+pub mod rs485;
+pub mod sim;
+pub mod slcan;
+pub mod stable;
+pub mod tee;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod trace;
+pub mod ubx;
+pub mod xbee;
+pub mod xfer;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod buffered;
+mod cancel;
+mod clock;
+mod driver;
+mod edge;
+mod events;
+mod fdpass;
+mod gated;
+mod hex;
+mod holders;
+mod hotplug;
+mod ioctl;
+mod iter;
+mod keepalive;
+mod logging;
+mod mismatch;
+mod pair;
+mod poll;
+mod priority;
+mod probe;
+mod pty;
+mod ring;
+mod shared;
+mod splice;
+mod tap;
 mod termios;
-#[cfg(test)]
-mod socat;
+mod throttled;
 #[cfg(test)]
 mod test;
 
 const O_NOCTTY: libc::c_int = 0x0100;
 
+#[cfg(target_os = "linux")]
+const O_CLOEXEC: libc::c_int = 0x80000;
+#[cfg(target_os = "macos")]
+const O_CLOEXEC: libc::c_int = 0x1000000;
+
+#[cfg(target_os = "linux")]
+const O_NONBLOCK: libc::c_int = 0x0800;
+#[cfg(target_os = "macos")]
+const O_NONBLOCK: libc::c_int = 0x0004;
+
+const F_GETFL: libc::c_int = 3;
+const F_SETFL: libc::c_int = 4;
+
+/// Size of the chunks `fill_buf` reads ahead
+const READ_AHEAD: uint = 256;
+
 #[deriving(PartialEq)]
 pub struct BlockingMode {
     /// The device will block until `bytes` are received
@@ -30,63 +154,621 @@ pub struct BlockingMode {
     pub deciseconds: u8,
 }
 
+/// How reads wait for data, a readable form of the VMIN/VTIME regimes of `BlockingMode`
+///
+/// The durations are rounded up to deciseconds, the unit of VTIME, and range from 0.1 to
+/// 25.5 seconds. These timers are enforced by the driver, they're independent from the timeout set
+/// with `set_timeout`.
+#[deriving(Clone, PartialEq, Show)]
+pub enum ReadMode {
+    /// Reads return at once with the bytes already received, or fail with `TimedOut` when
+    /// there are none
+    NonBlocking,
+    /// Reads wait until this many bytes are received (up to the size of the buffer)
+    BlockUntil(u8),
+    /// Reads return as soon as a byte is received, or with nothing once the duration elapses
+    Timeout(Duration),
+    /// Reads wait indefinitely for a first byte, then return once the line is idle for the
+    /// duration (or the buffer is full)
+    TimeoutAfterFirstByte(Duration),
+}
+
+/// Converts `duration` to the deciseconds of VTIME, rounding up
+fn deciseconds(duration: Duration) -> IoResult<u8> {
+    match duration.num_milliseconds() {
+        ms if ms <= 0 || ms > 25500 => Err(IoError {
+            kind: InvalidInput,
+            desc: "Read mode timers range from 0.1 to 25.5 seconds",
+            detail: Some(format!("got {}", duration)),
+        }),
+        ms => Ok(((ms + 99) / 100) as u8),
+    }
+}
+
+/// Lists the settings of `termios` that alter the received bytes
+fn unclean(termios: &Termios) -> Vec<&'static str> {
+    use termios::{CS8, CSIZE, ICRNL, IGNCR, INLCR, ISTRIP, IXON, PARMRK};
+
+    let checks = [
+        (termios.c_cflag & CSIZE != CS8, "fewer than 8 data bits"),
+        (termios.c_iflag & ISTRIP != 0, "ISTRIP"),
+        (termios.c_iflag & ICRNL != 0, "ICRNL"),
+        (termios.c_iflag & IGNCR != 0, "IGNCR"),
+        (termios.c_iflag & INLCR != 0, "INLCR"),
+        (termios.c_iflag & PARMRK != 0, "PARMRK"),
+        (termios.c_iflag & IXON != 0, "software flow control"),
+    ];
+
+    checks.iter().filter(|&&(failed, _)| failed).map(|&(_, problem)| problem).collect()
+}
+
+/// Error of the driver parameters that only Linux exposes
+#[cfg(target_os = "macos")]
+fn driver_unavailable() -> IoError {
+    IoError {
+        kind: IoUnavailable,
+        desc: "The driver parameters are only available on Linux",
+        detail: None,
+    }
+}
+
+/// Error of the changes that would drop a line powering the device, see
+/// `SerialPort::set_line_power`
+fn powered_line_error(detail: &'static str) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: "The change would drop a line that powers the device",
+        detail: Some(detail.to_string()),
+    }
+}
+
+/// Error of the status line edges, that only Linux reports
+#[cfg(target_os = "macos")]
+fn edges_unavailable() -> IoError {
+    IoError {
+        kind: IoUnavailable,
+        desc: "The status line edges are only reported on Linux",
+        detail: None,
+    }
+}
+
+/// How `SerialPort::open_with_options` opens a device
+#[deriving(Clone, PartialEq, Show)]
+pub struct OpenOptions {
+    /// Closes the device in the programs started with `exec`, so child processes don't inherit
+    /// the port
+    pub close_on_exec: bool,
+    /// When the device is busy (`EBUSY`, e.g. opened by another program in exclusive mode),
+    /// lists the processes holding it in the detail of the error, see `holders`
+    pub find_holders: bool,
+    /// Blocks until the carrier (DCD) is detected, like a plain `open(2)` of a modem line
+    ///
+    /// Otherwise the device is opened in non blocking mode, which is turned off as soon as the
+    /// port is configured, so that `open` doesn't hang on devices that never assert DCD.
+    pub wait_for_carrier: bool,
+    /// Holds DTR and RTS asserted, for devices powered by them, see `SerialPort::set_line_power`
+    pub line_power: LinePower,
+}
+
+impl Default for OpenOptions {
+    /// Close on exec, find the holders of busy devices, don't wait for the carrier, leave DTR
+    /// and RTS to the driver
+    fn default() -> OpenOptions {
+        OpenOptions {
+            close_on_exec: true,
+            find_holders: true,
+            wait_for_carrier: false,
+            line_power: Unpowered,
+        }
+    }
+}
+
+/// Whether DTR and RTS power the device, see `SerialPort::set_line_power`
+#[deriving(Clone, PartialEq, Show)]
+pub enum LinePower {
+    /// The lines are left to the driver and to `set_dtr` and `set_rts`
+    Unpowered,
+    /// The lines are held asserted, and dropped when the port is closed, which powers the
+    /// device off
+    PoweredUntilClose,
+    /// The lines are held asserted, and left asserted when the port is closed, so the device
+    /// stays powered
+    PoweredAfterClose,
+}
+
+/// A complete serial port configuration
+///
+/// This is what `SerialIo::configure` applies and `SerialIo::settings` returns, all at once:
+/// the transports without termios, like the RFC 2217 client and the virtual ports, have no
+/// finer grained setters.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Settings {
+    /// Baud rate used in both directions
+    pub baud_rate: BaudRate,
+    /// Number of data bits per character
+    pub data_bits: DataBits,
+    /// Flow control
+    pub flow_control: FlowControl,
+    /// Bit parity
+    pub parity: Parity,
+    /// Number of stop bits per character
+    pub stop_bits: StopBits,
+}
+
+impl Default for Settings {
+    /// 9600 baud, 8 data bits, no parity, 1 stop bit and no flow control
+    fn default() -> Settings {
+        Settings {
+            baud_rate: B9K6,
+            data_bits: Data8,
+            flow_control: NoFlowControl,
+            parity: NoParity,
+            stop_bits: Stop1,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the port profiles of a file, see the `profile` module
+    ///
+    /// ```ignore
+    /// let profiles = try!(Settings::load_profiles(&Path::new("ports.toml")));
+    /// let mut gps = try!(profiles.open_profile("gps"));
+    /// ```
+    pub fn load_profiles(path: &Path) -> IoResult<profile::Profiles> {
+        profile::Profiles::load(path)
+    }
+
+    /// Lists the settings that differ from `other`, in the order of the fields
+    ///
+    /// ```ignore
+    /// for change in old.diff(&new).iter() {
+    ///     debug!("{}", change);  // e.g. "baud rate: 9600 -> 115200"
+    /// }
+    /// ```
+    pub fn diff(&self, other: &Settings) -> Vec<SettingChange> {
+        let mut changes = Vec::new();
+
+        if self.baud_rate != other.baud_rate {
+            changes.push(SettingChange::new("baud rate",
+                                            self.baud_rate.bits_per_second().to_string(),
+                                            other.baud_rate.bits_per_second().to_string()));
+        }
+        if self.data_bits != other.data_bits {
+            changes.push(SettingChange::new("data bits",
+                                            describe_data_bits(self.data_bits),
+                                            describe_data_bits(other.data_bits)));
+        }
+        if self.flow_control != other.flow_control {
+            changes.push(SettingChange::new("flow control",
+                                            describe_flow_control(self.flow_control),
+                                            describe_flow_control(other.flow_control)));
+        }
+        if self.parity != other.parity {
+            changes.push(SettingChange::new("parity",
+                                            describe_parity(self.parity),
+                                            describe_parity(other.parity)));
+        }
+        if self.stop_bits != other.stop_bits {
+            changes.push(SettingChange::new("stop bits",
+                                            describe_stop_bits(self.stop_bits),
+                                            describe_stop_bits(other.stop_bits)));
+        }
+
+        changes
+    }
+}
+
+/// A setting that differs between two configurations, see `Settings::diff`
+///
+/// Formats as `<setting>: <from> -> <to>`.
+#[deriving(Clone, PartialEq)]
+pub struct SettingChange {
+    /// Name of the setting, e.g. `"parity"`
+    pub setting: &'static str,
+    /// Value in the first configuration, e.g. `"none"`
+    pub from: String,
+    /// Value in the second configuration, e.g. `"even"`
+    pub to: String,
+}
+
+impl SettingChange {
+    fn new<T: Str>(setting: &'static str, from: T, to: T) -> SettingChange {
+        SettingChange {
+            setting: setting,
+            from: from.as_slice().to_string(),
+            to: to.as_slice().to_string(),
+        }
+    }
+}
+
+impl fmt::Show for SettingChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.setting, self.from, self.to)
+    }
+}
+
+fn describe_data_bits(bits: DataBits) -> &'static str {
+    match bits {
+        Data5 => "5",
+        Data6 => "6",
+        Data7 => "7",
+        Data8 => "8",
+    }
+}
+
+fn describe_flow_control(flow: FlowControl) -> &'static str {
+    match flow {
+        HardwareControl => "hardware",
+        NoFlowControl => "none",
+        SoftwareControl => "software",
+    }
+}
+
+fn describe_parity(parity: Parity) -> &'static str {
+    match parity {
+        EvenParity => "even",
+        NoParity => "none",
+        OddParity => "odd",
+        MarkParity => "mark",
+        SpaceParity => "space",
+    }
+}
+
+fn describe_stop_bits(bits: StopBits) -> &'static str {
+    match bits {
+        Stop1 => "1",
+        Stop2 => "2",
+    }
+}
+
+/// Traffic and error counters of a port
+#[deriving(Clone, Default, PartialEq, Show)]
+pub struct Stats {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Calls to `read`, including the failed ones
+    pub reads: u64,
+    /// Calls to `write`, including the failed ones
+    pub writes: u64,
+    /// Reads that timed out
+    pub timeouts: u64,
+    /// Reads and writes that failed, other than by timing out
+    pub errors: u64,
+}
+
+impl Stats {
+    /// Reports every counter to `sink` as a gauge, named like the counters of `MetricsSink`
+    ///
+    /// This suits the exporters that poll the values, rather than count events.
+    pub fn report(&self, sink: &mut MetricsSink) {
+        sink.gauge("serial.bytes_read", self.bytes_read);
+        sink.gauge("serial.bytes_written", self.bytes_written);
+        sink.gauge("serial.reads", self.reads);
+        sink.gauge("serial.writes", self.writes);
+        sink.gauge("serial.timeouts", self.timeouts);
+        sink.gauge("serial.errors", self.errors);
+    }
+}
+
+/// Receives the metrics of a port, to export them to a monitoring system
+///
+/// The counters are named after the fields of `Stats`, prefixed with `serial.`:
+/// `serial.bytes_read`, `serial.reads`, `serial.timeouts`, ...
+pub trait MetricsSink {
+    /// Adds `value` to the counter `name`
+    fn counter(&mut self, name: &str, value: u64);
+
+    /// Sets the gauge `name` to `value`
+    fn gauge(&mut self, name: &str, value: u64);
+}
+
+/// Arrival time of received data
+#[deriving(Clone, PartialEq, PartialOrd, Show)]
+pub struct Timestamp {
+    /// Wall clock time, for correlating with other sources
+    pub time: Timespec,
+    /// Monotonic time in nanoseconds, from an arbitrary origin, see `time::precise_time_ns`
+    pub precise_ns: u64,
+}
+
+impl Timestamp {
+    /// Returns the current time
+    pub fn now() -> Timestamp {
+        Timestamp {
+            time: time::get_time(),
+            precise_ns: time::precise_time_ns(),
+        }
+    }
+}
+
+/// Why `SerialPort::read_until` stopped
+#[deriving(Clone, PartialEq, Show)]
+pub enum StopReason {
+    /// The delimiter was received, it ends the data
+    FoundDelimiter,
+    /// The data reached the maximum length without a delimiter
+    ReachedMaxLength,
+    /// The timeout elapsed before the delimiter
+    TimeoutElapsed,
+    /// The port was closed or hung up
+    PortClosed,
+}
+
+/// What holds up the output of a port, see `SerialPort::flow_state`
+#[deriving(Clone, PartialEq, Show)]
+pub struct FlowState {
+    /// Whether the device suspended the output with an XOFF, `None` when it can't be told
+    pub xoff_suspended: Option<bool>,
+    /// Level of the Clear To Send input, `None` without status inputs, e.g. on ptys
+    pub cts: Option<bool>,
+    /// Level of the Data Set Ready input, `None` without status inputs
+    pub dsr: Option<bool>,
+    /// Bytes written but not sent yet
+    pub output_queued: uint,
+}
+
+/// What the idle watchdog does when no data arrives in time, see `SerialPort::set_idle_watchdog`
+#[deriving(Clone, PartialEq, Show)]
+pub enum WatchdogAction {
+    /// Raises the flag of `idle_expired`, the reads go on as usual
+    RaiseFlag,
+    /// Makes the reads fail with `EndOfFile`, as if the device hung up
+    ClosePort,
+}
+
+/// Operations shared by the serial transports of this crate
+///
+/// Protocol code written against this trait works with real devices, pty pairs and the ports
+/// of terminal servers (see the `rfc2217` module) alike.
+pub trait SerialIo: Reader + Writer {
+    /// Returns the current configuration of the transport
+    fn settings(&self) -> IoResult<Settings>;
+
+    /// Applies the `settings` to the transport
+    fn configure(&mut self, settings: &Settings) -> IoResult<()>;
+
+    /// Returns the read timeout, `None` means that reads block indefinitely
+    fn timeout(&self) -> Option<Duration>;
+
+    /// Changes the read timeout, `None` means that reads block indefinitely
+    fn set_timeout(&mut self, timeout: Option<Duration>);
+
+    /// Reads a single byte, waiting up to the timeout
+    ///
+    /// Fails with `TimedOut` when nothing arrives in time, and with `EndOfFile` when the
+    /// transport is closed. Unlike `Reader::read_u8`, a read of zero bytes isn't retried.
+    fn read_one_byte(&mut self) -> IoResult<u8> {
+        let mut buf = [0u8];
+        try!(self.read_exact_into(&mut buf));
+
+        Ok(buf[0])
+    }
+
+    /// Fills `buf`, the timeout bounds the whole operation rather than each read
+    ///
+    /// Fixed size fields read straight into arrays, e.g. `let mut header = [0u8, ..4]`. When
+    /// the timeout elapses first the error is `TimedOut`, its detail tells how many bytes were
+    /// received, and those are at the start of `buf`. A read of zero bytes means that the
+    /// transport was closed, it fails with `EndOfFile`.
+    fn read_exact_into(&mut self, buf: &mut [u8]) -> IoResult<()> {
+        let timeout = self.timeout();
+        let deadline = timeout.map(|timeout| {
+            time::precise_time_ns() + cmp::max(timeout.num_nanoseconds().unwrap_or(0), 0) as u64
+        });
+
+        let mut n = 0;
+        let mut result = Ok(());
+        while n < buf.len() {
+            match deadline {
+                None => {},
+                Some(deadline) => {
+                    let now = time::precise_time_ns();
+                    let remaining = if deadline > now { deadline - now } else { 0 };
+
+                    self.set_timeout(Some(Duration::nanoseconds(remaining as i64)));
+                },
+            }
+
+            match self.read(buf.slice_from_mut(n)) {
+                Err(e) => {
+                    result = Err(IoError {
+                        kind: e.kind,
+                        desc: e.desc,
+                        detail: Some(format!("received {} of {} bytes", n, buf.len())),
+                    });
+                    break
+                },
+                Ok(0) => {
+                    result = Err(IoError {
+                        kind: EndOfFile,
+                        desc: "The transport was closed",
+                        detail: Some(format!("received {} of {} bytes", n, buf.len())),
+                    });
+                    break
+                },
+                Ok(read) => n += read,
+            }
+        }
+
+        self.set_timeout(timeout);
+        result
+    }
+}
+
 pub struct SerialPort {
     fd: libc::c_int,
     file: FileDesc,
     termios: Termios,
+    timeout: Option<Duration>,
+    stats: Stats,
+    metrics: Option<Box<MetricsSink + Send>>,
+    char_delay: Option<Duration>,
+    frame_delay: Option<Duration>,
+    /// Read end of the pipe of the `ReadCanceller`s, and a handle to clone
+    canceller: Option<(cancel::Fd, ReadCanceller)>,
+    /// How the device was opened, for `reopen`
+    origin: Option<(Path, FileAccess, OpenOptions)>,
+    /// Whether the system calls interrupted by a signal are restarted
+    retry_interrupted: bool,
+    /// Bytes received by `peek`, and not read yet
+    peeked: Vec<u8>,
+    /// When the first of the `peeked` bytes arrived, for `read_timestamped`
+    peeked_at: Option<Timestamp>,
+    /// Absolute deadline of `with_deadline`, on the `time::precise_time_ns` clock
+    deadline: Option<u64>,
+    /// Whether the baud rate changes the driver rejects are ignored
+    ignore_baud_rate: bool,
+    /// Period and action of the idle watchdog
+    watchdog: Option<(Duration, WatchdogAction)>,
+    /// When data last arrived, or the watchdog was set, on the `time::precise_time_ns` clock
+    last_data: u64,
+    /// When data was last written, or the port opened, on the `time::precise_time_ns` clock
+    last_write: u64,
+    /// Whether DTR and RTS are held asserted
+    line_power: LinePower,
 }
 
 impl SerialPort {
-    /// Opens a serial `device` in "raw" mode
+    /// Opens a serial `device` in "raw" mode, with the default `OpenOptions`
     pub fn open(device: &Path, access: FileAccess) -> IoResult<SerialPort> {
-        let flags = match access {
+        SerialPort::open_with_options(device, access, &Default::default())
+    }
+
+    /// Opens a serial `device` in "raw" mode
+    pub fn open_with_options(device: &Path, access: FileAccess, options: &OpenOptions)
+                             -> IoResult<SerialPort> {
+        use libc::consts::os::posix88::EBUSY;
+        use std::os;
+
+        let mut flags = match access {
             Read => libc::O_RDONLY,
             ReadWrite => libc::O_RDWR,
             Write => libc::O_WRONLY,
         } | O_NOCTTY;
 
+        if options.close_on_exec {
+            flags |= O_CLOEXEC;
+        }
+
+        if !options.wait_for_carrier {
+            flags |= O_NONBLOCK;
+        }
+
         let fd = match device.with_c_str(|s| unsafe { libc::open(s, flags, 0) }) {
+            FAILURE if options.find_holders && os::errno() as libc::c_int == EBUSY => {
+                let mut error = IoError::last_error();
+
+                match holders::holders(device) {
+                    Ok(ref holders) if !holders.is_empty() => {
+                        error.detail = Some(holders::describe(holders.as_slice()));
+                    },
+                    _ => {},
+                }
+
+                return Err(error)
+            },
             FAILURE => return Err(IoError::last_error()),
             fd => fd,
         };
 
-        let file = FileDesc::new(fd, true);
+        let mut port = try!(SerialPort::from_fd(fd));
+        port.origin = Some((device.clone(), access, options.clone()));
 
-        let mut termios = Termios::new();
+        if options.line_power != Unpowered {
+            try!(port.set_line_power(options.line_power));
+        }
 
-        match unsafe { termios::tcgetattr(fd, &mut termios) } {
+        // Now that the port is configured, reads and writes block as usual
+        if !options.wait_for_carrier {
+            let flags = unsafe { fcntl(fd, F_GETFL) };
+
+            if flags == FAILURE || unsafe { fcntl(fd, F_SETFL, flags & !O_NONBLOCK) } == FAILURE {
+                return Err(logging::last_error(fd, "fcntl"))
+            }
+        }
+
+        Ok(port)
+    }
+
+    /// Opens a pair of connected pseudo-terminals, both in "raw" mode
+    ///
+    /// The first port is the master side and the second one is the slave side. Anything written
+    /// to one end can be read from the other end, which makes the pair a stand-in for real
+    /// hardware in tests.
+    pub fn pty_pair() -> IoResult<(SerialPort, SerialPort)> {
+        let (mut master, mut slave) = (0, 0);
+
+        match unsafe {
+            pty::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null(), ptr::null())
+        } {
             FAILURE => return Err(IoError::last_error()),
             SUCCESS => {},
             _ => unreachable!(),
         }
 
-        unsafe { termios::cfmakeraw(&mut termios) };
+        let master = match SerialPort::from_fd(master) {
+            Err(e) => {
+                unsafe { libc::close(slave) };
+                return Err(e)
+            },
+            Ok(master) => master,
+        };
+        let slave = try!(SerialPort::from_fd(slave));
 
-        let sp = SerialPort { fd: fd, file: file, termios: termios };
+        Ok((master, slave))
+    }
 
-        try!(sp.update());
+    /// Takes ownership of `fd`, a descriptor of a serial device opened by another process, and
+    /// puts the device in "raw" mode
+    ///
+    /// This lets an unprivileged daemon use ports opened by a privileged broker: the broker
+    /// passes the descriptor with `send_fd`, the daemon gets it with `receive_fd`. The
+    /// descriptor is closed if it isn't a terminal. Like the ports of `open`, the port blocks,
+    /// whatever the mode the broker opened it in; it can't be reopened.
+    ///
+    /// ```ignore
+    /// let fd = try!(serial::receive_fd(broker.as_raw_fd()));
+    /// let mut port = try!(SerialPort::from_received_fd(fd));
+    /// ```
+    pub fn from_received_fd(fd: libc::c_int) -> IoResult<SerialPort> {
+        let port = try!(SerialPort::from_fd(fd));
+
+        let flags = unsafe { fcntl(fd, F_GETFL) };
+        if flags == FAILURE || unsafe { fcntl(fd, F_SETFL, flags & !O_NONBLOCK) } == FAILURE {
+            return Err(logging::last_error(fd, "fcntl"))
+        }
 
-        Ok(sp)
+        Ok(port)
+    }
+
+    /// Returns the file descriptor of the device, for the functions of the `raw` module
+    ///
+    /// The descriptor stays owned by the port, and is closed when the port is dropped.
+    pub fn as_raw_fd(&self) -> libc::c_int {
+        self.fd
     }
 
     /// Returns the input and output baud rates
+    ///
+    /// Fails while a rate set with `set_custom_baud_rate` is in use.
     #[cfg(target_os = "linux")]
     pub fn baud_rate(&self) -> IoResult<(BaudRate, BaudRate)> {
-        let termios = try!(self.fetch());
+        use std::io::OtherIoError;
 
-        let input = termios.c_ispeed;
-        let input = match FromPrimitive::from_u32(input) {
-            None => panic!("unrecognized BaudRate value: {}", input),
-            Some(input) => input,
-        };
+        let termios = try!(self.fetch());
 
-        let output = termios.c_ospeed;
-        let output = match FromPrimitive::from_u32(output) {
-            None => panic!("unrecognized BaudRate value: {}", output),
-            Some(output) => output,
-        };
+        let input = FromPrimitive::from_u32(termios.c_ispeed);
+        let output = FromPrimitive::from_u32(termios.c_ospeed);
 
-        Ok((input, output))
+        match (input, output) {
+            (Some(input), Some(output)) => Ok((input, output)),
+            _ => Err(IoError {
+                kind: OtherIoError,
+                desc: "Custom baud rate in use",
+                detail: None,
+            }),
+        }
     }
 
     /// Returns the input and output baud rates
@@ -119,209 +801,1812 @@ impl SerialPort {
         })
     }
 
-    /// Returns the number of data bits used per character
+    /// Applies all the `settings` to the device
+    pub fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        try!(self.set_baud_rate(BothDirections, settings.baud_rate));
+        try!(self.set_data_bits(settings.data_bits));
+        try!(self.set_flow_control(settings.flow_control));
+        try!(self.set_parity(settings.parity));
+        self.set_stop_bits(settings.stop_bits)
+    }
+
+    /// Returns the control flags of the device, see the `flags` module
+    pub fn control_flags(&self) -> IoResult<ControlFlags> {
+        let termios = try!(self.fetch());
+
+        Ok(ControlFlags::from_bits_truncate(termios.c_cflag))
+    }
+
+    /// Returns the state of the Clear To Send input
+    pub fn cts(&self) -> IoResult<bool> {
+        self.modem_line(ioctl::TIOCM_CTS)
+    }
+
+    /// Returns the number of data bits used per character
+    #[cfg(target_os = "linux")]
+    pub fn data_bits(&self) -> IoResult<DataBits> {
+        use termios::CSIZE;
+
+        let bits = try!(self.fetch()).c_cflag & CSIZE;
+
+        match FromPrimitive::from_u32(bits) {
+            None => panic!("unrecognized DataBits value: {}", bits),
+            Some(bits) => Ok(bits),
+        }
+    }
+
+    /// Returns the number of data bits used per character
+    #[cfg(target_os = "macos")]
+    pub fn data_bits(&self) -> IoResult<DataBits> {
+        use termios::CSIZE;
+
+        let bits = try!(self.fetch()).c_cflag & CSIZE;
+
+        match FromPrimitive::from_u64(bits) {
+            None => panic!("unrecognized DataBits value: {}", bits),
+            Some(bits) => Ok(bits),
+        }
+    }
+
+    /// Returns the state of the Data Carrier Detect input
+    pub fn dcd(&self) -> IoResult<bool> {
+        self.modem_line(ioctl::TIOCM_CAR)
+    }
+
+    /// Listens for `listen` and tells whether the data received and the line errors look like
+    /// the sender uses another baud rate, see `BaudCheck`
+    ///
+    /// The timeout of the port is restored afterwards. The data received meanwhile is
+    /// consumed.
+    pub fn diagnose_baud_rate(&mut self, listen: Duration) -> IoResult<BaudDiagnosis> {
+        mismatch::diagnose(self, listen)
+    }
+
+    /// Waits until all the written data has been transmitted
+    pub fn drain(&mut self) -> IoResult<()> {
+        match unsafe { termios::tcdrain(self.fd) } {
+            FAILURE => Err(logging::last_error(self.fd, "tcdrain")),
+            SUCCESS => Ok(()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns what the driver reports about the UART behind the port, from `TIOCGSERIAL`
+    ///
+    /// Drivers without the ioctl, like those of ptys, fail with an error.
+    #[cfg(target_os = "linux")]
+    pub fn driver_info(&self) -> IoResult<DriverInfo> {
+        let mut serial = ioctl::SerialStruct::new();
+
+        match unsafe {
+            ioctl::ioctl(self.fd, ioctl::TIOCGSERIAL, &mut serial as *mut ioctl::SerialStruct)
+        } {
+            FAILURE => Err(logging::last_error(self.fd, "TIOCGSERIAL")),
+            _ => Ok(driver::from_serial_struct(&serial)),
+        }
+    }
+
+    /// Returns what the driver reports about the UART behind the port
+    ///
+    /// Only Linux exposes this information, elsewhere this fails with an `IoUnavailable`
+    /// error.
+    #[cfg(target_os = "macos")]
+    pub fn driver_info(&self) -> IoResult<DriverInfo> {
+        Err(driver_unavailable())
+    }
+
+    /// Returns the state of the Data Set Ready input
+    pub fn dsr(&self) -> IoResult<bool> {
+        self.modem_line(ioctl::TIOCM_DSR)
+    }
+
+    /// Returns whether the received characters are echoed back, see `set_echo`
+    pub fn echo(&self) -> IoResult<bool> {
+        use termios::ECHO;
+
+        let termios = try!(self.fetch());
+
+        Ok(termios.c_lflag & ECHO != 0)
+    }
+
+    /// Returns the flow control used by the device
+    pub fn flow_control(&self) -> IoResult<FlowControl> {
+        use termios::{CRTSCTS, IXANY, IXOFF, IXON};
+
+        let termios = try!(self.fetch());
+
+        if termios.c_cflag & CRTSCTS != 0 {
+            Ok(HardwareControl)
+        } else if termios.c_iflag & (IXANY | IXOFF | IXON) == 0 {
+            Ok(NoFlowControl)
+        } else {
+            Ok(SoftwareControl)
+        }
+    }
+
+    /// Returns whether the device holds up the output, and the levels of its flow control inputs
+    ///
+    /// Tells "the device stopped us" from "the device is silent" in diagnostics. No driver
+    /// reports the XOFF state as such, it's inferred on Linux: with software flow control on and
+    /// data queued, an idle transmitter (see `output_drained`) means that the output is
+    /// suspended, unless a deasserted CTS holds it under hardware flow control. The inference
+    /// needs data queued and a UART that reports its transmitter, `xoff_suspended` is `None`
+    /// otherwise; it's `Some(false)` when software flow control is off.
+    pub fn flow_state(&self) -> IoResult<FlowState> {
+        use termios::{CRTSCTS, IXON};
+
+        let termios = try!(self.fetch());
+        let queued = try!(self.output_queue());
+        let lines = self.modem_lines().ok();
+        let cts = lines.map(|lines| lines & ioctl::TIOCM_CTS != 0);
+
+        let xoff_suspended = if termios.c_iflag & IXON == 0 {
+            Some(false)
+        } else if queued == 0 {
+            None
+        } else if termios.c_cflag & CRTSCTS != 0 && cts == Some(false) {
+            Some(false)
+        } else {
+            self.transmitter_empty()
+        };
+
+        Ok(FlowState {
+            xoff_suspended: xoff_suspended,
+            cts: cts,
+            dsr: lines.map(|lines| lines & ioctl::TIOCM_DSR != 0),
+            output_queued: queued,
+        })
+    }
+
+    /// Returns whether DTR drops when the port is closed, see `set_hangup_on_close`
+    pub fn hangup_on_close(&self) -> IoResult<bool> {
+        use termios::HUPCL;
+
+        let termios = try!(self.fetch());
+
+        Ok(termios.c_cflag & HUPCL != 0)
+    }
+
+    /// Tells whether the idle watchdog expired: no data arrived for its period
+    ///
+    /// The flag drops once data arrives, unless the watchdog closed the port.
+    pub fn idle_expired(&self) -> bool {
+        self.watchdog_expiry().map_or(false, |expiry| time::precise_time_ns() >= expiry)
+    }
+
+    /// Returns the period and the action of the idle watchdog, see `set_idle_watchdog`
+    pub fn idle_watchdog(&self) -> Option<(Duration, WatchdogAction)> {
+        self.watchdog.clone()
+    }
+
+    /// Returns whether the baud rate changes the driver rejects are ignored, see
+    /// `set_ignore_baud_rate`
+    pub fn ignore_baud_rate(&self) -> bool {
+        self.ignore_baud_rate
+    }
+
+    /// Returns an iterator over the incoming bytes
+    ///
+    /// By default the iteration stops when a read times out, see `IncomingBytes::on_timeout`
+    pub fn incoming_bytes(&mut self) -> IncomingBytes<SerialPort> {
+        IncomingBytes::new(self)
+    }
+
+    /// Returns the input flags of the device, see the `flags` module
+    pub fn input_flags(&self) -> IoResult<InputFlags> {
+        let termios = try!(self.fetch());
+
+        Ok(InputFlags::from_bits_truncate(termios.c_iflag))
+    }
+
+    /// Hands the port over to two tasks, returns a channel of the incoming chunks and one for
+    /// the data to send
+    ///
+    /// A task reads the port (time outs are ignored) and sends each chunk; another writes the
+    /// data it gets, in order. The errors of both directions arrive on the receiver, after which
+    /// the task that failed stops. The writing task stops once the sender is dropped, the reading
+    /// task with the first chunk received after the receiver is dropped.
+    pub fn into_channel(self) -> IoResult<(Receiver<IoResult<Vec<u8>>>, Sender<Vec<u8>>)> {
+        let mut writer = try!(self.duplicate());
+        let mut reader = self;
+        let (incoming, received) = channel();
+        let (outgoing, to_send) = channel::<Vec<u8>>();
+
+        let errors = incoming.clone();
+        spawn(proc() {
+            for data in to_send.iter() {
+                match writer.write(data.as_slice()) {
+                    Err(e) => {
+                        let _ = errors.send_opt(Err(e));
+                        break
+                    },
+                    Ok(()) => {},
+                }
+            }
+        });
+
+        spawn(proc() {
+            let mut buf = [0u8, ..256];
+
+            loop {
+                let chunk = match reader.read(&mut buf) {
+                    // Non blocking reads time out at once, the data is awaited here instead
+                    Err(ref e) if e.kind == TimedOut => match reader.wait_readable() {
+                        Err(ref e) if e.kind == TimedOut => continue,
+                        Err(e) => Err(e),
+                        Ok(()) => continue,
+                    },
+                    Err(e) => Err(e),
+                    Ok(n) => Ok(buf.slice_to(n).to_vec()),
+                };
+                let failed = chunk.is_err();
+
+                if incoming.send_opt(chunk).is_err() || failed {
+                    break
+                }
+            }
+        });
+
+        Ok((received, outgoing))
+    }
+
+    /// Issues the `request` ioctl on the device, with `arg` as argument, returns what the ioctl
+    /// returned
+    ///
+    /// This is an escape hatch for the requests the crate doesn't cover, like the ones specific to
+    /// a driver, while the port keeps managing the descriptor.
+    ///
+    /// # Safety
+    ///
+    /// `arg` has to point to what `request` expects, valid for the duration of the call. The
+    /// request mustn't close the descriptor, nor change the settings behind the back of the
+    /// port in a way it relies on, like its blocking mode.
+    pub unsafe fn ioctl(&self, request: libc::c_ulong, arg: *mut libc::c_void)
+                        -> IoResult<libc::c_int> {
+        match ioctl::ioctl(self.fd, request, arg) {
+            FAILURE => Err(logging::last_error(self.fd, "ioctl")),
+            result => Ok(result),
+        }
+    }
+
+    /// Returns whether `path` refers to the device of the port
+    ///
+    /// Symbolic links are followed and the device numbers compared, so `/dev/ttyUSB0` and its
+    /// `/dev/serial/by-id/...` link match, while the same name given to another adapter after a
+    /// replug doesn't. Paths that aren't character devices never match.
+    pub fn is_same_device(&self, path: &Path) -> IoResult<bool> {
+        use libc::consts::os::posix88::{S_IFCHR, S_IFMT};
+        use libc::funcs::posix88::stat_::{fstat, stat};
+        use std::mem;
+
+        let mut own: libc::stat = unsafe { mem::zeroed() };
+        match unsafe { fstat(self.fd, &mut own) } {
+            FAILURE => return Err(logging::last_error(self.fd, "fstat")),
+            _ => {},
+        }
+
+        let mut other: libc::stat = unsafe { mem::zeroed() };
+        match path.with_c_str(|path| unsafe { stat(path, &mut other) }) {
+            FAILURE => return Err(IoError::last_error()),
+            _ => {},
+        }
+
+        Ok(other.st_mode & S_IFMT == S_IFCHR && other.st_rdev == own.st_rdev)
+    }
+
+    /// Returns whether the received bytes reach the application unchanged
+    ///
+    /// That takes 8 data bits, no software flow control (which consumes XON and XOFF), and
+    /// none of the input translations: high bit stripping (`ISTRIP`), carriage return and line
+    /// feed mapping or dropping (`ICRNL`, `IGNCR`, `INLCR`), and parity error marking
+    /// (`PARMRK`).
+    pub fn is_eight_bit_clean(&self) -> IoResult<bool> {
+        let termios = try!(self.fetch());
+
+        Ok(unclean(&termios).is_empty())
+    }
+
+    /// Returns whether DTR and RTS power the device, see `set_line_power`
+    pub fn line_power(&self) -> LinePower {
+        self.line_power
+    }
+
+    /// Returns an iterator over the incoming `\n` terminated lines
+    ///
+    /// By default the iteration stops when a read times out, see `Lines::terminator` and
+    /// `Lines::on_timeout` to change this behavior
+    pub fn lines(&mut self) -> Lines<SerialPort> {
+        Lines::new(self)
+    }
+
+    /// Returns the local flags of the device, see the `flags` module
+    pub fn local_flags(&self) -> IoResult<LocalFlags> {
+        let termios = try!(self.fetch());
+
+        Ok(LocalFlags::from_bits_truncate(termios.c_lflag))
+    }
+
+    /// Returns whether the modem status lines are ignored, see `set_local_mode`
+    pub fn local_mode(&self) -> IoResult<bool> {
+        use termios::CLOCAL;
+
+        let termios = try!(self.fetch());
+
+        Ok(termios.c_cflag & CLOCAL != 0)
+    }
+
+    /// Turns off the input translations and selects 8 data bits, so that binary data is
+    /// received unchanged, see `is_eight_bit_clean`
+    ///
+    /// Fails if the driver doesn't keep the settings, or if software flow control is on, the
+    /// error detail lists what's wrong.
+    pub fn make_eight_bit_clean(&mut self) -> IoResult<()> {
+        use std::io::OtherIoError;
+        use termios::{CS8, CSIZE, ICRNL, IGNCR, INLCR, ISTRIP, PARMRK};
+
+        self.termios.c_cflag = self.termios.c_cflag & !CSIZE | CS8;
+        self.termios.c_iflag &= !(ICRNL | IGNCR | INLCR | ISTRIP | PARMRK);
+        try!(self.update());
+
+        let problems = unclean(&try!(self.fetch()));
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(IoError {
+                kind: OtherIoError,
+                desc: "The port isn't 8-bit clean",
+                detail: Some(problems.connect(", ")),
+            })
+        }
+    }
+
+    /// Tells whether all the written data has left the port, without blocking like `drain`
+    ///
+    /// On Linux the UART reports whether its transmit shift register is empty (`TIOCSERGETLSR`),
+    /// so the last stop bit is out when this holds, which half-duplex drivers wait for before
+    /// turning the line around. Elsewhere, and with the drivers that don't report it (USB
+    /// adapters, ptys), only the queue of the driver is checked: a few bytes can still be in
+    /// the FIFO of the hardware.
+    pub fn output_drained(&self) -> IoResult<bool> {
+        match self.transmitter_empty() {
+            Some(empty) => Ok(empty),
+            None => self.output_queue().map(|queued| queued == 0),
+        }
+    }
+
+    /// Copies the first bytes of the incoming stream into `buf` without consuming them, returns
+    /// how many
+    ///
+    /// The next reads return the same bytes first. When fewer bytes than `buf` holds were peeked
+    /// so far, the port is read once, subject to the timeout; a time out isn't an error if
+    /// some bytes were peeked before.
+    pub fn peek(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if self.peeked.len() < buf.len() {
+            let mut chunk = Vec::from_elem(buf.len() - self.peeked.len(), 0u8);
+
+            match self.read_device(chunk.as_mut_slice()) {
+                Err(ref e) if e.kind == TimedOut && !self.peeked.is_empty() => {},
+                Err(e) => return Err(e),
+                Ok(n) => {
+                    if self.peeked.is_empty() {
+                        self.peeked_at = Some(Timestamp::now());
+                    }
+                    self.peeked.push_all(chunk.slice_to(n));
+                },
+            }
+        }
+
+        let n = cmp::min(buf.len(), self.peeked.len());
+        bytes::copy_memory(buf, self.peeked.slice_to(n));
+
+        Ok(n)
+    }
+
+    /// Returns the bit parity used by the device
+    pub fn parity(&self) -> IoResult<Parity> {
+        use termios::{CMSPAR, PARENB, PARODD};
+
+        let termios = try!(self.fetch());
+        let flag = |bit| termios.c_cflag & bit != 0;
+
+        match (flag(PARENB), flag(PARODD), flag(CMSPAR)) {
+            (true, true, false) => Ok(OddParity),
+            (true, false, false) => Ok(EvenParity),
+            (true, true, true) => Ok(MarkParity),
+            (true, false, true) => Ok(SpaceParity),
+            (false, _, _) => Ok(NoParity),
+        }
+    }
+
+    /// Reads like `read`, and also returns the time at which the data arrived
+    ///
+    /// The time is taken as soon as `poll` reports the data, before reading it. The delays of
+    /// the driver (and of USB adapters, which batch their input) come on top of it. Bytes
+    /// received by `peek` come first, with the time at which they were peeked.
+    pub fn read_timestamped(&mut self, buf: &mut [u8]) -> IoResult<(uint, Timestamp)> {
+        if !self.peeked.is_empty() {
+            let timestamp = self.peeked_at.clone().unwrap_or_else(|| Timestamp::now());
+
+            return Ok((self.read_peeked(buf), timestamp))
+        }
+
+        let result = self.wait_readable().and_then(|_| {
+            let timestamp = Timestamp::now();
+
+            self.read_ready(buf).map(|n| (n, timestamp))
+        });
+        self.count_read(&result.clone().map(|(n, _)| n));
+
+        result
+    }
+
+    /// Reads until the `delimiter`, at most `max_len` bytes, for up to `timeout`
+    ///
+    /// The data received is returned whatever ended the read, with the reason. Nothing is read
+    /// past the delimiter, at the cost of a system call per byte. The timeout of the port is
+    /// restored afterwards.
+    pub fn read_until(&mut self, delimiter: u8, max_len: uint, timeout: Duration)
+                      -> IoResult<(Vec<u8>, StopReason)> {
+        let nanos = cmp::max(timeout.num_nanoseconds().unwrap_or(0), 0) as u64;
+        let deadline = time::precise_time_ns() + nanos;
+        let previous = self.timeout;
+
+        let mut data = Vec::new();
+        let result = self.read_until_deadline(delimiter, max_len, deadline, &mut data);
+
+        self.timeout = previous;
+        result.map(|reason| (data, reason))
+    }
+
+    /// Returns a handle that aborts the blocking reads of this port from other tasks
+    ///
+    /// The cancelled reads fail with an error recognized by `ReadCanceller::is_cancellation`.
+    /// Once a handle exists, every read polls the port, even without a timeout.
+    pub fn read_canceller(&mut self) -> IoResult<ReadCanceller> {
+        match self.canceller {
+            Some((_, ref canceller)) => return Ok(canceller.clone()),
+            None => {},
+        }
+
+        let (pipe, canceller) = try!(cancel::pipe());
+        self.canceller = Some((pipe, canceller.clone()));
+
+        Ok(canceller)
+    }
+
+    /// Returns the read mode, `None` if the `BlockingMode` set doesn't match any `ReadMode`
+    pub fn read_mode(&self) -> IoResult<Option<ReadMode>> {
+        let mode = try!(self.blocking_mode());
+        let duration = Duration::milliseconds(mode.deciseconds as i64 * 100);
+
+        Ok(match (mode.bytes, mode.deciseconds) {
+            (0, 0) => Some(NonBlocking),
+            (bytes, 0) => Some(BlockUntil(bytes)),
+            (0, _) => Some(Timeout(duration)),
+            (1, _) => Some(TimeoutAfterFirstByte(duration)),
+            _ => None,
+        })
+    }
+
+    /// Closes and opens the device again, then applies the current configuration to it
+    ///
+    /// This recovers from errors after which the file descriptor is useless, like the hang up of
+    /// a USB adapter that was unplugged then plugged back. The timeout, counters and delays are
+    /// kept; a rate set with `set_custom_baud_rate` has to be set again. Ports that weren't
+    /// opened from a path, like those of `pty_pair`, can't be reopened.
+    pub fn reopen(&mut self) -> IoResult<()> {
+        let (device, access, options) = match self.origin {
+            None => return Err(IoError {
+                kind: InvalidInput,
+                desc: "The port wasn't opened from a path",
+                detail: None,
+            }),
+            Some(ref origin) => origin.clone(),
+        };
+
+        let SerialPort { fd, file, .. } = try!(SerialPort::open_with_options(&device, access,
+                                                                             &options));
+
+        // Closes the previous file descriptor
+        self.fd = fd;
+        self.file = file;
+        self.peeked.clear();
+
+        self.update()
+    }
+
+    /// Resets the counters returned by `stats`
+    pub fn reset_stats(&mut self) {
+        self.stats = Default::default();
+    }
+
+    /// Returns whether the reads and writes interrupted by a signal are restarted, see
+    /// `set_retry_interrupted`
+    pub fn retry_interrupted(&self) -> bool {
+        self.retry_interrupted
+    }
+
+    /// Returns the state of the Ring Indicator input
+    pub fn ri(&self) -> IoResult<bool> {
+        self.modem_line(ioctl::TIOCM_RNG)
+    }
+
+    /// Reads the port in a loop, and calls back `handler` on the events: received data, breaks,
+    /// changes of the status inputs, errors
+    ///
+    /// The status inputs are sampled between the reads, every 20 ms when no data arrives, so
+    /// pulses shorter than that can be missed; `wait_for_edge` catches them. The loop returns
+    /// once `handler` declines to carry on after an error, or `Ok` when a `ReadCanceller` of the
+    /// port cancels it. The timeout of the port is restored afterwards.
+    ///
+    /// ```ignore
+    /// struct Console;
+    ///
+    /// impl EventHandler for Console {
+    ///     fn on_data(&mut self, data: &[u8]) { print!("{}", String::from_utf8_lossy(data)) }
+    ///     fn on_break(&mut self) { println!("<break>") }
+    /// }
+    ///
+    /// try!(port.run_events(&mut Console));
+    /// ```
+    pub fn run_events<H: EventHandler>(&mut self, handler: &mut H) -> IoResult<()> {
+        events::run(self, handler)
+    }
+
+    /// Transmits a break for `duration`, after the data written so far
+    ///
+    /// The sleep resolution limits the precision of the duration, which is a minimum.
+    pub fn send_break(&mut self, duration: Duration) -> IoResult<()> {
+        use std::io::timer;
+
+        try!(self.drain());
+        try!(self.set_break(true));
+        timer::sleep(duration);
+        self.set_break(false)
+    }
+
+    /// Changes the baud rate of the input/output or both directions
+    pub fn set_baud_rate(&mut self, direction: Direction, rate: BaudRate) -> IoResult<()> {
+        use termios::speed_t;
+
+        let previous = self.termios;
+        let result = match unsafe { match direction {
+            BothDirections => termios::cfsetspeed(&mut self.termios, rate as speed_t),
+            Input => termios::cfsetispeed(&mut self.termios, rate as speed_t),
+            Output => termios::cfsetospeed(&mut self.termios, rate as speed_t),
+        } } {
+            FAILURE => Err(IoError::last_error()),
+            SUCCESS => self.update(),
+            _ => unreachable!(),
+        };
+
+        match result {
+            Err(_) if self.ignore_baud_rate => {
+                self.termios = previous;
+                Ok(())
+            },
+            result => result,
+        }
+    }
+
+    /// Changes the blocking mode used by the device
+    pub fn set_blocking_mode(&mut self, mode: BlockingMode) -> IoResult<()> {
+        use termios::{VMIN, VTIME};
+
+        self.termios.c_cc[VMIN as uint] = mode.bytes;
+        self.termios.c_cc[VTIME as uint] = mode.deciseconds;
+
+        self.update()
+    }
+
+    /// Starts (`true`) or stops transmitting a break, i.e. holding the line at the space level
+    pub fn set_break(&mut self, enable: bool) -> IoResult<()> {
+        let (request, name) = match enable {
+            true => (ioctl::TIOCSBRK, "TIOCSBRK"),
+            false => (ioctl::TIOCCBRK, "TIOCCBRK"),
+        };
+
+        match unsafe { ioctl::ioctl(self.fd, request) } {
+            FAILURE => Err(logging::last_error(self.fd, name)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Inserts a `delay` between the transmitted bytes, `None` (the default) sends them back to
+    /// back
+    ///
+    /// Each byte is written and drained on its own, then the delay elapses before the next one.
+    /// Slow instruments and some half-duplex radio links need this to keep up.
+    pub fn set_char_delay(&mut self, delay: Option<Duration>) {
+        self.char_delay = delay;
+    }
+
+    /// Changes how long closing the port waits for the output to drain, `TIOCSSERIAL`
+    ///
+    /// `None` waits for as long as it takes, zero doesn't wait at all. The driver default is 30
+    /// seconds, which stalls `close` when the other end stopped the flow. The setting is kept by
+    /// the driver, and only exists on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_closing_wait(&mut self, wait: Option<Duration>) -> IoResult<()> {
+        let wait = try!(driver::raw_closing_wait(wait));
+
+        self.update_serial_struct(|serial| serial.closing_wait = wait)
+    }
+
+    /// Changes how long closing the port waits for the output to drain
+    ///
+    /// Only Linux can change it, elsewhere this fails with an `IoUnavailable` error.
+    #[cfg(target_os = "macos")]
+    pub fn set_closing_wait(&mut self, _: Option<Duration>) -> IoResult<()> {
+        Err(driver_unavailable())
+    }
+
+    /// Replaces the control flags of the device, keeps the bits that `ControlFlags` doesn't name
+    pub fn set_control_flags(&mut self, flags: ControlFlags) -> IoResult<()> {
+        self.termios.c_cflag = self.termios.c_cflag & !ControlFlags::all().bits() | flags.bits();
+
+        self.update()
+    }
+
+    /// Changes the baud rate of both directions to a `rate` that `BaudRate` doesn't cover, like
+    /// the 31250 baud of MIDI
+    ///
+    /// The driver picks the closest rate that the hardware can generate. Use `set_baud_rate` to
+    /// go back to a standard rate.
+    #[cfg(target_os = "linux")]
+    pub fn set_custom_baud_rate(&mut self, rate: u32) -> IoResult<()> {
+        use ioctl::Termios2;
+        use termios::{BOTHER, CBAUD, CIBAUD};
+
+        let mut termios = Termios2::new();
+
+        match unsafe { ioctl::ioctl(self.fd, ioctl::TCGETS2, &mut termios as *mut Termios2) } {
+            FAILURE => return Err(logging::last_error(self.fd, "TCGETS2")),
+            _ => {},
+        }
+
+        termios.c_cflag = termios.c_cflag & !(CBAUD | CIBAUD) | BOTHER;
+        termios.c_ispeed = rate;
+        termios.c_ospeed = rate;
+
+        match unsafe { ioctl::ioctl(self.fd, ioctl::TCSETS2, &termios as *const Termios2) } {
+            FAILURE => return Err(logging::last_error(self.fd, "TCSETS2")),
+            _ => {},
+        }
+
+        // The kernel keeps the custom rate as long as `BOTHER` is set
+        self.termios.c_cflag = self.termios.c_cflag & !(CBAUD | CIBAUD) | BOTHER;
+
+        self.hold_power_lines()
+    }
+
+    /// Makes the 38400 baud rate use the clock divisor `divisor`, 0 goes back to the standard
+    /// 38400 baud (`TIOCSSERIAL`)
+    ///
+    /// The resulting rate is `DriverInfo::baud_base` divided by `divisor`. This is the legacy
+    /// way to get non standard rates, for drivers that ignore `set_custom_baud_rate`. It only
+    /// exists on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_custom_divisor(&mut self, divisor: uint) -> IoResult<()> {
+        use driver::{ASYNC_SPD_CUST, ASYNC_SPD_MASK};
+
+        self.update_serial_struct(|serial| {
+            serial.flags &= !ASYNC_SPD_MASK;
+            if divisor != 0 {
+                serial.flags |= ASYNC_SPD_CUST;
+            }
+            serial.custom_divisor = divisor as libc::c_int;
+        })
+    }
+
+    /// Makes the 38400 baud rate use the clock divisor `divisor`
+    ///
+    /// Only Linux can change it, elsewhere this fails with an `IoUnavailable` error.
+    #[cfg(target_os = "macos")]
+    pub fn set_custom_divisor(&mut self, _: uint) -> IoResult<()> {
+        Err(driver_unavailable())
+    }
+
+    /// Changes the number of data bits per character
+    #[cfg(target_os = "linux")]
+    pub fn set_data_bits(&mut self, bits: DataBits) -> IoResult<()> {
+        use termios::CSIZE;
+
+        self.termios.c_cflag &= !CSIZE;
+        self.termios.c_cflag |= bits as u32;
+
+        self.update()
+    }
+
+    /// Changes the number of data bits per character
+    #[cfg(target_os = "macos")]
+    pub fn set_data_bits(&mut self, bits: DataBits) -> IoResult<()> {
+        use termios::CSIZE;
+
+        self.termios.c_cflag &= !CSIZE;
+        self.termios.c_cflag |= bits as u64;
+
+        self.update()
+    }
+
+    /// Drives the Data Terminal Ready output, `true` asserts the line
+    pub fn set_dtr(&mut self, level: bool) -> IoResult<()> {
+        self.set_modem_line(ioctl::TIOCM_DTR, level)
+    }
+
+    /// Echoes (`true`) the received characters back to the sender, or not, like a terminal
+    ///
+    /// Besides `ECHO`, this covers the echo of erase and kill characters and of control
+    /// characters (`ECHOE`, `ECHOK`, `ECHOKE`, `ECHOCTL`), which only take effect in canonical
+    /// mode. Disabling it also clears `ECHONL`. Raw mode, the default, doesn't echo.
+    pub fn set_echo(&mut self, enable: bool) -> IoResult<()> {
+        use termios::{ECHO, ECHOCTL, ECHOE, ECHOK, ECHOKE, ECHONL};
+
+        if enable {
+            self.termios.c_lflag |= ECHO | ECHOCTL | ECHOE | ECHOK | ECHOKE;
+        } else {
+            self.termios.c_lflag &= !(ECHO | ECHOCTL | ECHOE | ECHOK | ECHOKE | ECHONL);
+        }
+
+        self.update()
+    }
+
+    /// Changes the flow control used by the device
+    pub fn set_flow_control(&mut self, flow: FlowControl) -> IoResult<()> {
+        use termios::{CRTSCTS, IXANY, IXOFF, IXON};
+
+        match flow {
+            HardwareControl => {
+                self.termios.c_cflag |= CRTSCTS;
+                self.termios.c_iflag &= !(IXANY | IXOFF | IXON);
+            } NoFlowControl => {
+                self.termios.c_cflag &= !CRTSCTS;
+                self.termios.c_iflag &= !(IXANY | IXOFF | IXON);
+            } SoftwareControl => {
+                self.termios.c_cflag &= !CRTSCTS;
+                self.termios.c_iflag |= IXANY | IXOFF | IXON;
+            }
+        }
+
+        self.update()
+    }
+
+    /// Waits for `delay` after each `write`, once its data has been transmitted
+    ///
+    /// With protocols that write a frame per call, this keeps a gap between the frames.
+    pub fn set_frame_delay(&mut self, delay: Option<Duration>) {
+        self.frame_delay = delay;
+    }
+
+    /// Drops (`true`) or keeps (`false`) DTR and RTS when the last user closes the port,
+    /// `HUPCL` in termios
+    ///
+    /// Dropping DTR hangs up modems, and resets the many boards that wire DTR to their reset
+    /// line, Arduinos among them. The setting is kept by the driver after the port is closed.
+    pub fn set_hangup_on_close(&mut self, enable: bool) -> IoResult<()> {
+        use termios::HUPCL;
+
+        if enable {
+            self.termios.c_cflag |= HUPCL;
+        } else {
+            self.termios.c_cflag &= !HUPCL;
+        }
+
+        self.update()
+    }
+
+    /// Watches for data, `None` stops the watchdog
+    ///
+    /// Gateways use it to notice a dead sensor or a cut cable, instead of forwarding nothing
+    /// forever. Once no data has arrived for `period`, the watchdog either raises the flag of
+    /// `idle_expired`, or makes the reads fail with an `EndOfFile` error until it's set again;
+    /// the reads waiting for data fail then, whatever their timeout. The period starts over
+    /// with each call.
+    ///
+    /// ```ignore
+    /// port.set_idle_watchdog(Some(Duration::minutes(5)), ClosePort);
+    /// for line in port.lines() { ... }
+    /// ```
+    pub fn set_idle_watchdog(&mut self, period: Option<Duration>, action: WatchdogAction) {
+        self.watchdog = period.map(|period| (period, action));
+        self.last_data = time::precise_time_ns();
+    }
+
+    /// Ignores (`true`) the baud rate changes that the driver rejects, or reports them
+    ///
+    /// Some links have no real baud rate, like Bluetooth RFCOMM: the data goes at the speed of
+    /// the radio, and some drivers refuse the rates they don't expect. Ignoring the errors lets
+    /// `configure` apply the rest of the settings; the rejected rate isn't recorded.
+    pub fn set_ignore_baud_rate(&mut self, enable: bool) {
+        self.ignore_baud_rate = enable;
+    }
+
+    /// Replaces the input flags of the device, keeps the bits that `InputFlags` doesn't name
+    pub fn set_input_flags(&mut self, flags: InputFlags) -> IoResult<()> {
+        self.termios.c_iflag = self.termios.c_iflag & !InputFlags::all().bits() | flags.bits();
+
+        self.update()
+    }
+
+    /// Holds DTR and RTS asserted, for the devices that draw their power from them, or leaves
+    /// them to the driver (`Unpowered`)
+    ///
+    /// Both lines are asserted at once, and again after every configuration change, since some
+    /// drivers pulse them when the termios settings change. Whether they drop when the port is
+    /// closed is set through `HUPCL`, like `set_hangup_on_close`. While the device is powered,
+    /// the changes that would drop a line fail with an `InvalidInput` error: deasserting them
+    /// with `set_dtr` or `set_rts`, hardware flow control, which lets the driver drive RTS, and
+    /// the `B0` baud rate, which hangs up the line. Use `OpenOptions::line_power` to power the
+    /// device from the open.
+    ///
+    /// ```ignore
+    /// let options = OpenOptions { line_power: PoweredUntilClose, ..Default::default() };
+    /// let mut sensor = try!(SerialPort::open_with_options(&path, ReadWrite, &options));
+    /// ```
+    pub fn set_line_power(&mut self, power: LinePower) -> IoResult<()> {
+        use termios::HUPCL;
+
+        match power {
+            Unpowered => {},
+            PoweredUntilClose => self.termios.c_cflag |= HUPCL,
+            PoweredAfterClose => self.termios.c_cflag &= !HUPCL,
+        }
+        self.line_power = power;
+
+        self.update()
+    }
+
+    /// Replaces the local flags of the device, keeps the bits that `LocalFlags` doesn't name
+    pub fn set_local_flags(&mut self, flags: LocalFlags) -> IoResult<()> {
+        self.termios.c_lflag = self.termios.c_lflag & !LocalFlags::all().bits() | flags.bits();
+
+        self.update()
+    }
+
+    /// Ignores (`true`) or honors (`false`) the modem status lines, `CLOCAL` in termios
+    ///
+    /// When they're honored, the loss of the carrier (DCD) hangs up the line: reads return
+    /// `EndOfFile` and the device has to be reopened. Devices without a carrier, which includes
+    /// most of those that aren't modems, want the local mode.
+    pub fn set_local_mode(&mut self, enable: bool) -> IoResult<()> {
+        use termios::CLOCAL;
+
+        if enable {
+            self.termios.c_cflag |= CLOCAL;
+        } else {
+            self.termios.c_cflag &= !CLOCAL;
+        }
+
+        self.update()
+    }
+
+    /// Sends the counters of the port to `sink` as they change, see `MetricsSink`
+    pub fn set_metrics_sink(&mut self, sink: Box<MetricsSink + Send>) {
+        self.metrics = Some(sink);
+    }
+
+    /// Changes the bit parity used by the device
+    ///
+    /// The mark and space parities are only supported on Linux, elsewhere they fail with an
+    /// `IoUnavailable` error.
+    pub fn set_parity(&mut self, parity: Parity) -> IoResult<()> {
+        use termios::{CMSPAR, PARENB, PARODD};
+
+        if CMSPAR == 0 && (parity == MarkParity || parity == SpaceParity) {
+            return Err(IoError {
+                kind: IoUnavailable,
+                desc: "The mark and space parities are only supported on Linux",
+                detail: None,
+            })
+        }
+
+        self.termios.c_cflag &= !CMSPAR;
+        match parity {
+            EvenParity => {
+                self.termios.c_cflag |= PARENB;
+                self.termios.c_cflag &= !PARODD;
+            },
+            NoParity => self.termios.c_cflag &= !PARENB,
+            OddParity => self.termios.c_cflag |= PARENB | PARODD,
+            MarkParity => self.termios.c_cflag |= PARENB | PARODD | CMSPAR,
+            SpaceParity => {
+                self.termios.c_cflag |= PARENB | CMSPAR;
+                self.termios.c_cflag &= !PARODD;
+            },
+        }
+
+        self.update()
+    }
+
+    /// Changes how reads wait for data, see `ReadMode`
+    pub fn set_read_mode(&mut self, mode: ReadMode) -> IoResult<()> {
+        let mode = match mode {
+            NonBlocking => BlockingMode { bytes: 0, deciseconds: 0 },
+            BlockUntil(bytes) => BlockingMode { bytes: bytes, deciseconds: 0 },
+            Timeout(duration) => {
+                BlockingMode { bytes: 0, deciseconds: try!(deciseconds(duration)) }
+            },
+            TimeoutAfterFirstByte(duration) => {
+                BlockingMode { bytes: 1, deciseconds: try!(deciseconds(duration)) }
+            },
+        };
+
+        self.set_blocking_mode(mode)
+    }
+
+    /// Restarts (`true`, the default) the reads and writes interrupted by a signal (`EINTR`),
+    /// or lets them fail
+    ///
+    /// Applications with timers or child processes receive signals at any time; failing lets
+    /// them break out of a blocking read from their signal handler.
+    pub fn set_retry_interrupted(&mut self, enable: bool) {
+        self.retry_interrupted = enable;
+    }
+
+    /// Drives the Request To Send output, `true` asserts the line
+    ///
+    /// With hardware flow control, the driver may also change the line.
+    pub fn set_rts(&mut self, level: bool) -> IoResult<()> {
+        self.set_modem_line(ioctl::TIOCM_RTS, level)
+    }
+
+    /// Changes the read timeout, `None` means that reads block indefinitely
+    ///
+    /// A read that doesn't receive any data within the timeout fails with a `TimedOut` error.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the current configuration of the device
+    ///
+    /// The reported baud rate is the output baud rate
+    pub fn settings(&self) -> IoResult<Settings> {
+        let (_, baud_rate) = try!(self.baud_rate());
+
+        Ok(Settings {
+            baud_rate: baud_rate,
+            data_bits: try!(self.data_bits()),
+            flow_control: try!(self.flow_control()),
+            parity: try!(self.parity()),
+            stop_bits: try!(self.stop_bits()),
+        })
+    }
+
+    /// Changes the number of stop bits per character
+    pub fn set_stop_bits(&mut self, bits: StopBits) -> IoResult<()> {
+        use termios::CSTOPB;
+
+        match bits {
+            Stop1 => self.termios.c_cflag &= !CSTOPB,
+            Stop2 => self.termios.c_cflag |= CSTOPB,
+        }
+
+        self.update()
+    }
+
+    /// Clears (`true`) or keeps the high bit of the received bytes, `ISTRIP` in termios
+    pub fn set_strip_high_bit(&mut self, enable: bool) -> IoResult<()> {
+        use termios::ISTRIP;
+
+        if enable {
+            self.termios.c_iflag |= ISTRIP;
+        } else {
+            self.termios.c_iflag &= !ISTRIP;
+        }
+
+        self.update()
+    }
+
+    /// Returns the traffic and error counters, accumulated since the port was opened or the
+    /// last `reset_stats`
+    pub fn stats(&self) -> Stats {
+        self.stats.clone()
+    }
+
+    /// Returns the number of stop bits per character
+    pub fn stop_bits(&self) -> IoResult<StopBits> {
+        use termios::CSTOPB;
+
+        if try!(self.fetch()).c_cflag & CSTOPB == 0 {
+            Ok(Stop1)
+        } else {
+            Ok(Stop2)
+        }
+    }
+
+    /// Returns whether the high bit of the received bytes is cleared
+    pub fn strip_high_bit(&self) -> IoResult<bool> {
+        use termios::ISTRIP;
+
+        let termios = try!(self.fetch());
+
+        Ok(termios.c_iflag & ISTRIP != 0)
+    }
+
+    /// Returns the read timeout, `None` means that reads block indefinitely
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Blocks until one of the status `lines` changes, returns the edges with the time of the
+    /// wakeup
+    ///
+    /// The driver wakes the task up from its interrupt handler (`TIOCMIWAIT`) and the time is
+    /// read right away from the monotonic clock, so the timestamps are as precise as the wakeup
+    /// latency, typically tens of microseconds. That makes a GPS receiver pulsing DCD once per
+    /// second a usable PPS source. The driver counts the transitions (`TIOCGICOUNT`), so even
+    /// pulses shorter than the latency are reported; several lines can change at once.
+    ///
+    /// The read timeout doesn't apply, the call blocks until an edge; run it in its own task.
+    /// Drivers without the ioctls, like those of ptys, fail with an error.
+    #[cfg(target_os = "linux")]
+    pub fn wait_for_edge(&mut self, lines: &[StatusLine]) -> IoResult<Vec<Edge>> {
+        use libc::consts::os::posix88::EINTR;
+        use std::os;
+
+        let mask = lines.iter().fold(0, |mask, &line| mask | edge::bit(line));
+
+        loop {
+            let before = try!(self.icount());
+
+            match unsafe { ioctl::ioctl(self.fd, ioctl::TIOCMIWAIT, mask as libc::c_ulong) } {
+                FAILURE => match os::errno() as libc::c_int {
+                    EINTR if self.retry_interrupted => continue,
+                    errno => return Err(logging::os_error(self.fd, "TIOCMIWAIT", errno as uint)),
+                },
+                _ => {},
+            }
+
+            let timestamp = Timestamp::now();
+            let after = try!(self.icount());
+            let levels = try!(self.modem_lines());
+
+            let edges: Vec<Edge> = lines.iter().filter_map(|&line| {
+                match edge::transitions(line, &after) - edge::transitions(line, &before) {
+                    0 => None,
+                    transitions => Some(Edge {
+                        line: line,
+                        level: levels & edge::bit(line) != 0,
+                        transitions: transitions as uint,
+                        timestamp: timestamp.clone(),
+                    }),
+                }
+            }).collect();
+
+            // The other lines can wake the task up too
+            if !edges.is_empty() {
+                return Ok(edges)
+            }
+        }
+    }
+
+    /// Blocks until one of the status `lines` changes
+    ///
+    /// Only Linux reports the edges, elsewhere this fails with an `IoUnavailable` error.
+    #[cfg(target_os = "macos")]
+    pub fn wait_for_edge(&mut self, _: &[StatusLine]) -> IoResult<Vec<Edge>> {
+        Err(edges_unavailable())
+    }
+
+    /// Blocks until one of the `events` occurs, returns those that did
+    ///
+    /// The changes are those since the call: a status input that changed, a break or a line
+    /// error. The data and output events are states, they are reported at once if they already
+    /// hold. `None` waits without a time out, otherwise this fails with a `TimedOut` error once
+    /// `timeout` elapses. Arriving data wakes the wait up right away, the other events are
+    /// sampled every 20 ms; on Linux the drivers count the transitions, so a short pulse of a
+    /// status input isn't missed, and the breaks and line errors are only detected there.
+    ///
+    /// ```ignore
+    /// let events = try!(port.wait_for_events(RX_AVAILABLE | CD_CHANGED, None));
+    /// if events.contains(CD_CHANGED) && !try!(port.dcd()) {
+    ///     println!("Carrier lost");
+    /// }
+    /// ```
+    pub fn wait_for_events(&mut self, events: EventSet, timeout: Option<Duration>)
+                           -> IoResult<EventSet> {
+        events::wait(self, events, timeout)
+    }
+
+    /// Runs `action` with every read and write of the port bounded by `deadline`, an absolute
+    /// time on the `time::precise_time_ns` clock
+    ///
+    /// Once the deadline passes the reads and writes fail with `TimedOut`, whatever the read
+    /// timeout, so a transaction made of several operations is bounded as a whole. The read
+    /// timeout still applies to each read. Nested calls keep the earliest deadline, the previous
+    /// one is restored when `action` returns.
+    ///
+    /// ```ignore
+    /// let deadline = time::precise_time_ns() + 500_000_000;
+    /// let reply = try!(port.with_deadline(deadline, |port| {
+    ///     try!(port.write(b"AT\r"));
+    ///     port.read_until(b'\n', 64, Duration::seconds(1))
+    /// }));
+    /// ```
+    pub fn with_deadline<T>(&mut self, deadline: u64, action: |&mut SerialPort| -> IoResult<T>)
+                            -> IoResult<T> {
+        let previous = self.deadline;
+        self.deadline = Some(previous.map_or(deadline, |previous| cmp::min(previous, deadline)));
+
+        let result = action(self);
+        self.deadline = previous;
+
+        result
+    }
+
+    /// Lets `change` modify the termios structure cached by the port, then applies it to the
+    /// device
+    ///
+    /// This is the way to reach the driver flags that the rest of the API doesn't cover, see the
+    /// `raw` module for the constants. Unlike calling `raw::tcsetattr` directly, the changes are
+    /// kept by the later setters.
+    pub fn with_raw_termios(&mut self, change: |&mut Termios|) -> IoResult<()> {
+        change(&mut self.termios);
+
+        self.update()
+    }
+
+    /// Writes all of `buf`, waiting up to `timeout` (or indefinitely) for the driver to accept
+    /// the bytes
+    ///
+    /// The kernel can accept part of the data, or none when its buffer is full (`EAGAIN`, e.g.
+    /// while flow control holds the output, or on a non blocking descriptor); the rest is
+    /// written as room frees up. When the timeout elapses first the error is `TimedOut`, its
+    /// detail tells how many bytes were written.
+    pub fn write_all(&mut self, buf: &[u8], timeout: Option<Duration>) -> IoResult<()> {
+        let deadline = timeout.map(|timeout| {
+            time::precise_time_ns() + cmp::max(timeout.num_nanoseconds().unwrap_or(0), 0) as u64
+        });
+
+        self.write_until_deadline(buf, deadline)
+    }
+
+    /// Writes `data` in chunks of `chunk_size` bytes, separated by `gap`
+    ///
+    /// Each chunk is drained, i.e. transmitted, before the gap starts; this lets large payloads
+    /// (like firmware images) through links without flow control. `progress` is called after
+    /// each chunk with the number of bytes transmitted so far and the total.
+    pub fn write_paced(&mut self, data: &[u8], chunk_size: uint, gap: Duration,
+                       progress: |uint, uint|) -> IoResult<()> {
+        use std::io::timer;
+
+        let mut sent = 0;
+
+        for chunk in data.chunks(cmp::max(chunk_size, 1)) {
+            if sent > 0 {
+                timer::sleep(gap);
+            }
+
+            try!(self.write(chunk));
+            try!(self.drain());
+
+            sent += chunk.len();
+            progress(sent, data.len());
+        }
+
+        Ok(())
+    }
+
+    /// Adds `value` to the `counter` of the stats, and forwards it to the metrics sink
+    fn count(&mut self, counter: &'static str, value: u64) {
+        {
+            let field = match counter {
+                "bytes_read" => &mut self.stats.bytes_read,
+                "bytes_written" => &mut self.stats.bytes_written,
+                "errors" => &mut self.stats.errors,
+                "reads" => &mut self.stats.reads,
+                "timeouts" => &mut self.stats.timeouts,
+                "writes" => &mut self.stats.writes,
+                _ => unreachable!(),
+            };
+            *field += value;
+        }
+
+        match self.metrics {
+            None => {},
+            Some(ref mut sink) => sink.counter(format!("serial.{}", counter).as_slice(), value),
+        }
+    }
+
+    /// Accounts for a read in the counters
+    fn count_read(&mut self, result: &IoResult<uint>) {
+        self.count("reads", 1);
+
+        match *result {
+            Err(ref e) if e.kind == TimedOut => self.count("timeouts", 1),
+            Err(_) => self.count("errors", 1),
+            Ok(n) => {
+                self.count("bytes_read", n as u64);
+
+                if n > 0 {
+                    self.last_data = time::precise_time_ns();
+                }
+            },
+        }
+    }
+
+    /// Tells whether the deadline of `with_deadline` has passed
+    fn deadline_passed(&self) -> bool {
+        self.deadline.map_or(false, |deadline| time::precise_time_ns() >= deadline)
+    }
+
+    /// Opens a second handle on the device, with a duplicate of the file descriptor and the same
+    /// cached configuration
+    fn duplicate(&self) -> IoResult<SerialPort> {
+        use libc::funcs::posix88::unistd::dup;
+
+        const F_SETFD: libc::c_int = 2;
+        const FD_CLOEXEC: libc::c_int = 1;
+
+        let fd = match unsafe { dup(self.fd) } {
+            FAILURE => return Err(logging::last_error(self.fd, "dup")),
+            fd => fd,
+        };
+        let file = FileDesc::new(fd, true);
+
+        // Like the original, the duplicate isn't inherited by child processes
+        match unsafe { fcntl(fd, F_SETFD, FD_CLOEXEC) } {
+            FAILURE => return Err(logging::last_error(fd, "fcntl")),
+            _ => {},
+        }
+
+        Ok(SerialPort {
+            fd: fd,
+            file: file,
+            termios: self.termios,
+            timeout: self.timeout,
+            stats: Default::default(),
+            metrics: None,
+            char_delay: self.char_delay,
+            frame_delay: self.frame_delay,
+            canceller: None,
+            origin: None,
+            retry_interrupted: self.retry_interrupted,
+            peeked: Vec::new(),
+            peeked_at: None,
+            deadline: None,
+            ignore_baud_rate: self.ignore_baud_rate,
+            watchdog: None,
+            last_data: 0,
+            last_write: time::precise_time_ns(),
+            line_power: self.line_power,
+        })
+    }
+
+    /// Fetches the current state of the termios structure
+    fn fetch(&self) -> IoResult<Termios> {
+        let mut termios = Termios::new();
+
+        match unsafe { termios::tcgetattr(self.fd, &mut termios) } {
+            FAILURE => Err(logging::last_error(self.fd, "tcgetattr")),
+            SUCCESS => {
+                logging::tcgetattr(self.fd, &termios);
+                Ok(termios)
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Takes ownership of the open file descriptor `fd`, and puts the device in "raw" mode
+    fn from_fd(fd: libc::c_int) -> IoResult<SerialPort> {
+        let file = FileDesc::new(fd, true);
+
+        let mut termios = Termios::new();
+
+        match unsafe { termios::tcgetattr(fd, &mut termios) } {
+            FAILURE => return Err(logging::last_error(fd, "tcgetattr")),
+            SUCCESS => logging::tcgetattr(fd, &termios),
+            _ => unreachable!(),
+        }
+
+        unsafe { termios::cfmakeraw(&mut termios) };
+
+        let mut sp = SerialPort {
+            fd: fd,
+            file: file,
+            termios: termios,
+            timeout: None,
+            stats: Default::default(),
+            metrics: None,
+            char_delay: None,
+            frame_delay: None,
+            canceller: None,
+            origin: None,
+            retry_interrupted: true,
+            peeked: Vec::new(),
+            peeked_at: None,
+            deadline: None,
+            ignore_baud_rate: false,
+            watchdog: None,
+            last_data: 0,
+            last_write: time::precise_time_ns(),
+            line_power: Unpowered,
+        };
+
+        try!(sp.update());
+
+        Ok(sp)
+    }
+
+    /// Asserts DTR and RTS if they power the device, see `set_line_power`
+    ///
+    /// Ptys have no modem lines, there's nothing to hold; the other drivers without them fail,
+    /// they can't power a device.
+    fn hold_power_lines(&self) -> IoResult<()> {
+        use libc::consts::os::posix88::{EINVAL, ENOTTY};
+        use std::os;
+
+        if self.line_power == Unpowered {
+            return Ok(())
+        }
+
+        let lines = ioctl::TIOCM_DTR | ioctl::TIOCM_RTS;
+        match unsafe { ioctl::ioctl(self.fd, ioctl::TIOCMBIS, &lines as *const libc::c_int) } {
+            FAILURE => match os::errno() as libc::c_int {
+                EINVAL | ENOTTY if pty::is_pty(self.fd) => Ok(()),
+                errno => Err(logging::os_error(self.fd, "TIOCMBIS", errno as uint)),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the transition counters of the modem lines
     #[cfg(target_os = "linux")]
-    pub fn data_bits(&self) -> IoResult<DataBits> {
-        use termios::CSIZE;
+    fn icount(&self) -> IoResult<ioctl::SerialIcounter> {
+        let mut counters = ioctl::SerialIcounter::new();
+
+        match unsafe {
+            ioctl::ioctl(self.fd, ioctl::TIOCGICOUNT, &mut counters as *mut ioctl::SerialIcounter)
+        } {
+            FAILURE => Err(logging::last_error(self.fd, "TIOCGICOUNT")),
+            _ => Ok(counters),
+        }
+    }
 
-        let bits = try!(self.fetch()).c_cflag & CSIZE;
+    /// Returns whether the modem `line` is asserted
+    fn modem_line(&self, line: libc::c_int) -> IoResult<bool> {
+        self.modem_lines().map(|lines| lines & line != 0)
+    }
 
-        match FromPrimitive::from_u32(bits) {
-            None => panic!("unrecognized DataBits value: {}", bits),
-            Some(bits) => Ok(bits),
+    /// Returns the state of all the modem lines, as `TIOCM_*` bits
+    fn modem_lines(&self) -> IoResult<libc::c_int> {
+        let mut lines: libc::c_int = 0;
+
+        match unsafe { ioctl::ioctl(self.fd, ioctl::TIOCMGET, &mut lines as *mut libc::c_int) } {
+            FAILURE => Err(logging::last_error(self.fd, "TIOCMGET")),
+            _ => Ok(lines),
         }
     }
 
-    /// Returns the number of data bits used per character
-    #[cfg(target_os = "macos")]
-    pub fn data_bits(&self) -> IoResult<DataBits> {
-        use termios::CSIZE;
-
-        let bits = try!(self.fetch()).c_cflag & CSIZE;
+    /// Returns the number of bytes written but not sent yet
+    fn output_queue(&self) -> IoResult<uint> {
+        let mut queued: libc::c_int = 0;
 
-        match FromPrimitive::from_u64(bits) {
-            None => panic!("unrecognized DataBits value: {}", bits),
-            Some(bits) => Ok(bits),
+        match unsafe { ioctl::ioctl(self.fd, ioctl::TIOCOUTQ, &mut queued as *mut libc::c_int) } {
+            FAILURE => Err(logging::last_error(self.fd, "TIOCOUTQ")),
+            _ => Ok(queued as uint),
         }
     }
 
-    /// Returns the flow control used by the device
-    pub fn flow_control(&self) -> IoResult<FlowControl> {
-        use termios::{CRTSCTS, IXANY, IXOFF, IXON};
+    /// Asserts or deasserts the modem output `line`
+    fn set_modem_line(&mut self, line: libc::c_int, level: bool) -> IoResult<()> {
+        if !level && self.line_power != Unpowered {
+            return Err(powered_line_error("DTR and RTS power the device"))
+        }
 
-        let termios = try!(self.fetch());
+        let (request, name) = match level {
+            true => (ioctl::TIOCMBIS, "TIOCMBIS"),
+            false => (ioctl::TIOCMBIC, "TIOCMBIC"),
+        };
 
-        if termios.c_cflag & CRTSCTS != 0 {
-            Ok(HardwareControl)
-        } else if termios.c_iflag & (IXANY | IXOFF | IXON) == 0 {
-            Ok(NoFlowControl)
-        } else {
-            Ok(SoftwareControl)
+        match unsafe { ioctl::ioctl(self.fd, request, &line as *const libc::c_int) } {
+            FAILURE => Err(logging::last_error(self.fd, name)),
+            _ => Ok(()),
         }
     }
 
-    /// Returns the bit parity used by the device
-    pub fn parity(&self) -> IoResult<Parity> {
-        use termios::{PARENB, PARODD};
+    /// Reads from the device, waiting for data if there's a timeout, a deadline or a canceller
+    fn read_device(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let waits = self.timeout.is_some() || self.deadline.is_some() ||
+                    self.canceller.is_some() || self.watchdog.is_some();
+        let result = if waits {
+            self.wait_readable().and_then(|_| self.read_ready(buf))
+        } else {
+            self.read_ready(buf)
+        };
+        self.count_read(&result);
 
-        let termios = try!(self.fetch());
+        result
+    }
 
-        match (termios.c_cflag & PARENB != 0, termios.c_cflag & PARODD != 0) {
-            (true, true) => Ok(OddParity),
-            (true, false) => Ok(EvenParity),
-            (false, _) => Ok(NoParity),
+    /// Reads the data that is available, or blocks according to the blocking mode
+    ///
+    /// Without a minimum number of bytes (VMIN), the driver returns nothing once VTIME elapses,
+    /// or at once without VTIME: that's a time out, not the end of the file.
+    fn read_ready(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        use libc::{c_void, size_t};
+        use libc::consts::os::posix88::EINTR;
+        use std::io;
+        use std::os;
+        use termios::VMIN;
+
+        loop {
+            let (data, len) = (buf.as_mut_ptr() as *mut c_void, buf.len() as size_t);
+
+            match unsafe { libc::read(self.fd, data, len) } {
+                -1 => match os::errno() as libc::c_int {
+                    EINTR if self.retry_interrupted => {},
+                    errno => return Err(logging::os_error(self.fd, "read", errno as uint)),
+                },
+                0 if self.termios.c_cc[VMIN as uint] == 0 => {
+                    return Err(io::standard_error(TimedOut))
+                },
+                0 => return Err(io::standard_error(EndOfFile)),
+                n => return Ok(n as uint),
+            }
         }
     }
 
-    /// Changes the baud rate of the input/output or both directions
-    pub fn set_baud_rate(&mut self, direction: Direction, rate: BaudRate) -> IoResult<()> {
-        use termios::speed_t;
+    /// Reads one byte at a time into `data`, see `read_until`
+    fn read_until_deadline(&mut self, delimiter: u8, max_len: uint, deadline: u64,
+                           data: &mut Vec<u8>) -> IoResult<StopReason> {
+        let mut byte = [0u8];
 
-        match unsafe { match direction {
-            BothDirections => termios::cfsetspeed(&mut self.termios, rate as speed_t),
-            Input => termios::cfsetispeed(&mut self.termios, rate as speed_t),
-            Output => termios::cfsetospeed(&mut self.termios, rate as speed_t),
-        } } {
-            FAILURE => Err(IoError::last_error()),
-            SUCCESS => self.update(),
-            _ => unreachable!(),
+        loop {
+            if data.len() >= max_len {
+                return Ok(ReachedMaxLength)
+            }
+
+            let now = time::precise_time_ns();
+            if now >= deadline {
+                return Ok(TimeoutElapsed)
+            }
+            self.timeout = Some(Duration::nanoseconds((deadline - now) as i64));
+
+            match self.read(&mut byte) {
+                Err(ref e) if e.kind == TimedOut => return Ok(TimeoutElapsed),
+                Err(ref e) if e.kind == EndOfFile => return Ok(PortClosed),
+                Err(e) => return Err(e),
+                Ok(0) => return Ok(PortClosed),
+                Ok(_) => {
+                    data.push(byte[0]);
+
+                    if byte[0] == delimiter {
+                        return Ok(FoundDelimiter)
+                    }
+                },
+            }
         }
     }
 
-    /// Changes the blocking mode used by the device
-    pub fn set_blocking_mode(&mut self, mode: BlockingMode) -> IoResult<()> {
-        use termios::{VMIN, VTIME};
-
-        self.termios.c_cc[VMIN as uint] = mode.bytes;
-        self.termios.c_cc[VTIME as uint] = mode.deciseconds;
+    /// Moves the first `peeked` bytes into `buf`, returns how many
+    fn read_peeked(&mut self, buf: &mut [u8]) -> uint {
+        let n = cmp::min(buf.len(), self.peeked.len());
+        bytes::copy_memory(buf, self.peeked.slice_to(n));
+        self.peeked = self.peeked.slice_from(n).to_vec();
 
-        self.update()
+        n
     }
 
-    /// Changes the number of data bits per character
-    #[cfg(target_os = "linux")]
-    pub fn set_data_bits(&mut self, bits: DataBits) -> IoResult<()> {
-        use termios::CSIZE;
+    /// Tells whether a read would return data right away
+    fn readable(&self) -> IoResult<bool> {
+        if !self.peeked.is_empty() {
+            return Ok(true)
+        }
 
-        self.termios.c_cflag &= !CSIZE;
-        self.termios.c_cflag |= bits as u32;
+        poll::wait(self.fd, poll::POLLIN, Some(Duration::zero()))
+    }
 
-        self.update()
+    /// Reads the Transmitter Empty bit of the line status register, `None` if the driver doesn't
+    /// report it
+    #[cfg(target_os = "linux")]
+    fn transmitter_empty(&self) -> Option<bool> {
+        let mut status: libc::c_uint = 0;
+
+        match unsafe {
+            ioctl::ioctl(self.fd, ioctl::TIOCSERGETLSR, &mut status as *mut libc::c_uint)
+        } {
+            FAILURE => None,
+            _ => Some(status & ioctl::TIOCSER_TEMT != 0),
+        }
     }
 
-    /// Changes the number of data bits per character
     #[cfg(target_os = "macos")]
-    pub fn set_data_bits(&mut self, bits: DataBits) -> IoResult<()> {
-        use termios::CSIZE;
-
-        self.termios.c_cflag &= !CSIZE;
-        self.termios.c_cflag |= bits as u64;
-
-        self.update()
+    fn transmitter_empty(&self) -> Option<bool> {
+        None
     }
 
-    /// Changes the flow control used by the device
-    pub fn set_flow_control(&mut self, flow: FlowControl) -> IoResult<()> {
-        use termios::{CRTSCTS, IXANY, IXOFF, IXON};
+    /// Applies `change` to the parameters of the driver, `TIOCGSERIAL` then `TIOCSSERIAL`
+    #[cfg(target_os = "linux")]
+    fn update_serial_struct(&mut self, change: |&mut ioctl::SerialStruct|) -> IoResult<()> {
+        use ioctl::{SerialStruct, TIOCGSERIAL, TIOCSSERIAL};
 
-        match flow {
-            HardwareControl => {
-                self.termios.c_cflag |= CRTSCTS;
-                self.termios.c_iflag &= !(IXANY | IXOFF | IXON);
-            } NoFlowControl => {
-                self.termios.c_cflag &= !CRTSCTS;
-                self.termios.c_iflag &= !(IXANY | IXOFF | IXON);
-            } SoftwareControl => {
-                self.termios.c_cflag &= !CRTSCTS;
-                self.termios.c_iflag |= IXANY | IXOFF | IXON;
-            }
+        let mut serial = SerialStruct::new();
+
+        match unsafe { ioctl::ioctl(self.fd, TIOCGSERIAL, &mut serial as *mut SerialStruct) } {
+            FAILURE => return Err(logging::last_error(self.fd, "TIOCGSERIAL")),
+            _ => {},
         }
 
-        self.update()
+        change(&mut serial);
+
+        match unsafe { ioctl::ioctl(self.fd, TIOCSSERIAL, &serial as *const SerialStruct) } {
+            FAILURE => Err(logging::last_error(self.fd, "TIOCSSERIAL")),
+            _ => Ok(()),
+        }
     }
 
-    /// Changes the bit parity used by the device
-    pub fn set_parity(&mut self, parity: Parity) -> IoResult<()> {
-        use termios::{PARENB, PARODD};
+    /// Updates the underlying termios structure
+    ///
+    /// A change that would drop a line powering the device is undone in the cached structure,
+    /// from the device which still has the previous settings, so that the next changes apply.
+    fn update(&mut self) -> IoResult<()> {
+        use termios::{CRTSCTS, TCSANOW};
+
+        let refusal = if self.line_power == Unpowered {
+            None
+        } else if self.termios.c_cflag & CRTSCTS != 0 {
+            Some("Hardware flow control would drive RTS")
+        } else if self.termios.c_ospeed == 0 {
+            Some("The B0 baud rate would drop DTR")
+        } else {
+            None
+        };
 
-        match parity {
-            EvenParity => {
-                self.termios.c_cflag |= PARENB;
-                self.termios.c_cflag &= !PARODD;
+        match refusal {
+            None => {},
+            Some(detail) => {
+                self.termios = try!(self.fetch());
+                return Err(powered_line_error(detail))
             },
-            NoParity => self.termios.c_cflag &= !PARENB,
-            OddParity => self.termios.c_cflag |= PARENB | PARODD,
         }
 
-        self.update()
-    }
-
-    /// Changes the number of stop bits per character
-    pub fn set_stop_bits(&mut self, bits: StopBits) -> IoResult<()> {
-        use termios::CSTOPB;
-
-        match bits {
-            Stop1 => self.termios.c_cflag &= !CSTOPB,
-            Stop2 => self.termios.c_cflag |= CSTOPB,
+        let before = logging::before_change(self.fd);
+        match unsafe { termios::tcsetattr(self.fd, TCSANOW, &self.termios) } {
+            FAILURE => return Err(logging::last_error(self.fd, "tcsetattr")),
+            SUCCESS => logging::tcsetattr(self.fd, before, &self.termios),
+            _ => unreachable!(),
         }
 
-        self.update()
+        self.hold_power_lines()
     }
 
-    /// Returns the number of stop bits per character
-    pub fn stop_bits(&self) -> IoResult<StopBits> {
-        use termios::CSTOPB;
+    /// Waits until there's data to read, fails with a `TimedOut` error once the timeout elapses
+    /// or the deadline passes, or with the cancellation error
+    fn wait_readable(&self) -> IoResult<()> {
+        use libc::consts::os::posix88::EINTR;
+        use std::os;
+
+        let deadline = self.within_deadline(self.timeout.map(|timeout| {
+            time::precise_time_ns() + cmp::max(timeout.num_nanoseconds().unwrap_or(0), 0) as u64
+        }));
+        // The watchdog only bounds the wait when it closes the port
+        let closes = match self.watchdog {
+            Some((_, ClosePort)) => true,
+            _ => false,
+        };
+        let expiry = if closes { self.watchdog_expiry() } else { None };
+        let deadline = match (deadline, expiry) {
+            (Some(deadline), Some(expiry)) => Some(cmp::min(deadline, expiry)),
+            (deadline, expiry) => deadline.or(expiry),
+        };
 
-        if try!(self.fetch()).c_cflag & CSTOPB == 0 {
-            Ok(Stop1)
-        } else {
-            Ok(Stop2)
+        loop {
+            if closes && self.idle_expired() {
+                return Err(IoError {
+                    kind: EndOfFile,
+                    desc: "No data arrived within the period of the idle watchdog",
+                    detail: None,
+                })
+            }
+
+            if self.deadline_passed() {
+                return Err(IoError {
+                    kind: TimedOut,
+                    desc: "Read operation timed out",
+                    detail: Some("the deadline passed".to_string()),
+                })
+            }
+
+            let timeout = deadline.map(|deadline| {
+                let now = time::precise_time_ns();
+                let remaining = if deadline > now { deadline - now } else { 0 };
+
+                Duration::nanoseconds(remaining as i64)
+            });
+
+            let result = match self.canceller {
+                None => poll::wait(self.fd, poll::POLLIN, timeout),
+                Some((ref pipe, _)) => {
+                    match poll::wait_pair(self.fd, pipe.0, poll::POLLIN, timeout) {
+                        Ok((_, true)) => return Err(cancel::consume(pipe)),
+                        result => result.map(|(ready, _)| ready),
+                    }
+                },
+            };
+
+            match result {
+                // poll isn't restarted after a signal handler, even with SA_RESTART
+                Err(_) if self.retry_interrupted && os::errno() as libc::c_int == EINTR => {},
+                Err(e) => return Err(e),
+                Ok(true) => return Ok(()),
+                Ok(false) if closes && self.idle_expired() => {},
+                Ok(false) => return Err(IoError {
+                    kind: TimedOut,
+                    desc: "Read operation timed out",
+                    detail: None,
+                }),
+            }
         }
     }
 
-    /// Fetches the current state of the termios structure
-    fn fetch(&self) -> IoResult<Termios> {
-        let mut termios = Termios::new();
+    /// Returns when the idle watchdog expires, on the `time::precise_time_ns` clock
+    fn watchdog_expiry(&self) -> Option<u64> {
+        self.watchdog.as_ref().map(|&(period, _)| {
+            self.last_data + cmp::max(period.num_nanoseconds().unwrap_or(0), 0) as u64
+        })
+    }
 
-        match unsafe { termios::tcgetattr(self.fd, &mut termios) } {
-            FAILURE => Err(IoError::last_error()),
-            SUCCESS => Ok(termios),
-            _ => unreachable!(),
+    /// Combines `deadline` with the one of `with_deadline`, the earliest wins
+    fn within_deadline(&self, deadline: Option<u64>) -> Option<u64> {
+        match (deadline, self.deadline) {
+            (Some(deadline), Some(outer)) => Some(cmp::min(deadline, outer)),
+            (deadline, outer) => deadline.or(outer),
         }
     }
 
-    /// Updates the underlying termios structure
-    fn update(&self) -> IoResult<()> {
-        use termios::TCSANOW;
+    /// Writes `buf` right away, and accounts for it in the counters
+    fn write_raw(&mut self, buf: &[u8]) -> IoResult<()> {
+        self.write_until_deadline(buf, None)
+    }
 
-        match unsafe { termios::tcsetattr(self.fd, TCSANOW, &self.termios) } {
-            FAILURE => Err(IoError::last_error()),
-            SUCCESS => Ok(()),
-            _ => unreachable!(),
+    /// Writes all of `buf`, see `write_all`
+    fn write_until_deadline(&mut self, buf: &[u8], deadline: Option<u64>) -> IoResult<()> {
+        use libc::{c_void, size_t};
+        use libc::consts::os::posix88::{EAGAIN, EINTR, EWOULDBLOCK};
+        use std::os;
+
+        let deadline = self.within_deadline(deadline);
+
+        let mut written = 0;
+        while written < buf.len() {
+            // A blocking write could outlast the deadline of `with_deadline`
+            if self.deadline.is_some() {
+                let now = time::precise_time_ns();
+                let remaining = match deadline {
+                    Some(deadline) if deadline > now => deadline - now,
+                    _ => 0,
+                };
+                let timeout = Some(Duration::nanoseconds(remaining as i64));
+
+                if remaining == 0 || !try!(poll::wait(self.fd, poll::POLLOUT, timeout)) {
+                    return Err(IoError {
+                        kind: TimedOut,
+                        desc: "Write operation timed out",
+                        detail: Some(format!("wrote {} of {} bytes", written, buf.len())),
+                    })
+                }
+            }
+
+            let rest = buf.slice_from(written);
+            let (data, len) = (rest.as_ptr() as *const c_void, rest.len() as size_t);
+            let n = unsafe { libc::write(self.fd, data, len) };
+
+            if n >= 0 {
+                written += n as uint;
+                self.count("bytes_written", n as u64);
+                self.last_write = time::precise_time_ns();
+                continue
+            }
+
+            match os::errno() as libc::c_int {
+                EINTR if self.retry_interrupted => {},
+                errno if errno == EAGAIN || errno == EWOULDBLOCK => {
+                    let timeout = deadline.map(|deadline| {
+                        let now = time::precise_time_ns();
+                        let remaining = if deadline > now { deadline - now } else { 0 };
+
+                        Duration::nanoseconds(remaining as i64)
+                    });
+
+                    if !try!(poll::wait(self.fd, poll::POLLOUT, timeout)) {
+                        return Err(IoError {
+                            kind: TimedOut,
+                            desc: "Write operation timed out",
+                            detail: Some(format!("wrote {} of {} bytes", written, buf.len())),
+                        })
+                    }
+                },
+                errno => {
+                    self.count("errors", 1);
+                    return Err(logging::os_error(self.fd, "write", errno as uint))
+                },
+            }
         }
+
+        Ok(())
     }
 }
 
 impl Reader for SerialPort {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
-        match self.file.inner_read(buf) {
-            Err(err) => Err(IoError::from_errno(err.code, true)),
-            Ok(ret) => Ok(ret),
+        if self.peeked.is_empty() {
+            return self.read_device(buf)
+        }
+
+        Ok(self.read_peeked(buf))
+    }
+}
+
+/// The buffer is the one of `peek`: the bytes read ahead and not consumed come first in the
+/// next reads
+impl Buffer for SerialPort {
+    fn fill_buf<'a>(&'a mut self) -> IoResult<&'a [u8]> {
+        if self.peeked.is_empty() {
+            let mut chunk = [0u8, ..READ_AHEAD];
+            let n = try!(self.read_device(&mut chunk));
+            self.peeked_at = Some(Timestamp::now());
+            self.peeked.push_all(chunk.slice_to(n));
         }
+
+        Ok(self.peeked.as_slice())
+    }
+
+    fn consume(&mut self, amt: uint) {
+        let amt = cmp::min(amt, self.peeked.len());
+        self.peeked = self.peeked.slice_from(amt).to_vec();
+    }
+}
+
+impl SerialIo for SerialPort {
+    fn settings(&self) -> IoResult<Settings> {
+        self.settings()
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.configure(settings)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.set_timeout(timeout)
     }
 }
 
 impl Writer for SerialPort {
     fn write(&mut self, buf: &[u8]) -> IoResult<()> {
-        match self.file.inner_write(buf) {
-            Err(err) => Err(IoError::from_errno(err.code, true)),
-            Ok(_) => Ok(()),
+        use std::io::timer;
+
+        self.count("writes", 1);
+
+        match self.char_delay {
+            None => try!(self.write_raw(buf)),
+            Some(delay) => for (i, &byte) in buf.iter().enumerate() {
+                try!(self.write_raw(&[byte]));
+                try!(self.drain());
+
+                if i + 1 < buf.len() {
+                    timer::sleep(delay);
+                }
+            },
+        }
+
+        match self.frame_delay {
+            None => Ok(()),
+            Some(delay) => {
+                try!(self.drain());
+                timer::sleep(delay);
+                Ok(())
+            },
         }
     }
 }
 
 #[cfg(target_os = "linux")]
-#[deriving(FromPrimitive, PartialEq, Show)]
+#[deriving(Clone, Eq, FromPrimitive, PartialEq, Show)]
 #[repr(u32)]
 pub enum BaudRate {
     B0 = termios::B0,
@@ -358,7 +2643,7 @@ pub enum BaudRate {
 }
 
 #[cfg(target_os = "macos")]
-#[deriving(FromPrimitive, PartialEq, Show)]
+#[deriving(Clone, Eq, FromPrimitive, PartialEq, Show)]
 #[repr(u64)]
 pub enum BaudRate {
     B0 = termios::B0,
@@ -386,8 +2671,198 @@ pub enum BaudRate {
     B230K4 = termios::B230400,
 }
 
+/// The rates of the platform in ascending order, without `B0` which hangs up the line
+#[cfg(target_os = "linux")]
+static STANDARD_RATES: &'static [BaudRate] = &[
+    B50, B75, B110, B134, B150, B200, B300, B600, B1K2, B1K8, B2K4, B4K8, B9K6, B19K2, B38K4,
+    B57K6, B115K2, B230K4, B460K8, B500K, B576K, B921K6, B1M, B1M152, B1M5, B2M, B2M5, B3M, B3M5,
+    B4M,
+];
+
+/// The rates of the platform in ascending order, without `B0` which hangs up the line
+#[cfg(target_os = "macos")]
+static STANDARD_RATES: &'static [BaudRate] = &[
+    B50, B75, B110, B134, B150, B200, B300, B600, B1K2, B1K8, B2K4, B4K8, B7K2, B9K6, B14K4,
+    B19K2, B28K8, B38K4, B57K6, B76K8, B115K2, B230K4,
+];
+
+impl BaudRate {
+    /// Returns the rate in bits per second, 0 for `B0` (which hangs up the line)
+    pub fn bits_per_second(&self) -> uint {
+        match *self {
+            B0 => 0,
+            B50 => 50,
+            B75 => 75,
+            B110 => 110,
+            B134 => 134,
+            B150 => 150,
+            B200 => 200,
+            B300 => 300,
+            B600 => 600,
+            B1K2 => 1200,
+            B1K8 => 1800,
+            B2K4 => 2400,
+            B4K8 => 4800,
+            B9K6 => 9600,
+            B19K2 => 19200,
+            B38K4 => 38400,
+            B57K6 => 57600,
+            B115K2 => 115200,
+            B230K4 => 230400,
+            ref rate => platform_bits_per_second(rate),
+        }
+    }
+
+    /// Finds how to get `rate` bits per second, for devices specified at odd rates like 76800
+    /// or 31250 baud
+    ///
+    /// The closest rate of `BaudRate` is picked if it's within `tolerance_percent` of `rate`;
+    /// otherwise, on Linux, the rate is set as is with `set_custom_baud_rate`. Returns `None`
+    /// when neither works. Most UARTs cope with an error of 2% between both ends.
+    ///
+    /// ```ignore
+    /// match BaudRate::nearest(31250, 2.0) {
+    ///     None => println!("MIDI isn't supported here"),
+    ///     Some(nearest) => try!(nearest.apply(&mut port)),
+    /// }
+    /// ```
+    pub fn nearest(rate: u32, tolerance_percent: f64) -> Option<NearestRate> {
+        if rate == 0 {
+            return Some(NearestRate { setting: StandardRate(B0), actual: 0, error_percent: 0.0 })
+        }
+
+        let error = |actual: u32| (actual as f64 - rate as f64) * 100.0 / rate as f64;
+        let closest = STANDARD_RATES.iter().min_by(|standard| {
+            (standard.bits_per_second() as i64 - rate as i64).abs()
+        });
+
+        match closest.map(|&closest| (closest, closest.bits_per_second() as u32)) {
+            Some((closest, actual)) if error(actual).abs() <= tolerance_percent => {
+                Some(NearestRate {
+                    setting: StandardRate(closest),
+                    actual: actual,
+                    error_percent: error(actual),
+                })
+            },
+            _ => custom_rate(rate),
+        }
+    }
+
+    /// Returns the rates of the platform in ascending order, without `B0`
+    ///
+    /// ```ignore
+    /// // Try fast, fall back slower
+    /// for &rate in BaudRate::standard_rates().iter().rev().filter(|&&rate| rate <= B115K2) {
+    ///     ...
+    /// }
+    /// ```
+    pub fn standard_rates() -> &'static [BaudRate] {
+        STANDARD_RATES
+    }
+}
+
+/// The rates are ordered by speed
+impl PartialOrd for BaudRate {
+    fn partial_cmp(&self, other: &BaudRate) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BaudRate {
+    fn cmp(&self, other: &BaudRate) -> cmp::Ordering {
+        self.bits_per_second().cmp(&other.bits_per_second())
+    }
+}
+
+/// How to get a baud rate, see `BaudRate::nearest`
+#[deriving(Clone, PartialEq, Show)]
+pub enum RateSetting {
+    /// A rate of `BaudRate`, for `SerialPort::set_baud_rate`
+    StandardRate(BaudRate),
+    /// Any rate, for `SerialPort::set_custom_baud_rate`
+    CustomRate(u32),
+}
+
+/// The baud rate found by `BaudRate::nearest`
+#[deriving(Clone, PartialEq, Show)]
+pub struct NearestRate {
+    pub setting: RateSetting,
+    /// The rate obtained in bits per second; a custom rate is nominal, the driver rounds it to
+    /// what the clock of the UART can generate
+    pub actual: u32,
+    /// Deviation from the requested rate, negative when slower
+    pub error_percent: f64,
+}
+
+impl NearestRate {
+    /// Sets the rate of both directions of `port`
+    pub fn apply(&self, port: &mut SerialPort) -> IoResult<()> {
+        match self.setting {
+            StandardRate(rate) => port.set_baud_rate(BothDirections, rate),
+            CustomRate(rate) => set_custom_rate(port, rate),
+        }
+    }
+}
+
+/// A custom rate for `BaudRate::nearest`, which exists on Linux
+#[cfg(target_os = "linux")]
+fn custom_rate(rate: u32) -> Option<NearestRate> {
+    Some(NearestRate { setting: CustomRate(rate), actual: rate, error_percent: 0.0 })
+}
+
+#[cfg(target_os = "macos")]
+fn custom_rate(_: u32) -> Option<NearestRate> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn set_custom_rate(port: &mut SerialPort, rate: u32) -> IoResult<()> {
+    port.set_custom_baud_rate(rate)
+}
+
+#[cfg(target_os = "macos")]
+fn set_custom_rate(_: &mut SerialPort, _: u32) -> IoResult<()> {
+    Err(IoError {
+        kind: IoUnavailable,
+        desc: "Custom baud rates are only available on Linux",
+        detail: None,
+    })
+}
+
+/// Bits per second of the baud rates specific to Linux
 #[cfg(target_os = "linux")]
-#[deriving(FromPrimitive, PartialEq, Show)]
+fn platform_bits_per_second(rate: &BaudRate) -> uint {
+    match *rate {
+        B460K8 => 460800,
+        B500K => 500000,
+        B576K => 576000,
+        B921K6 => 921600,
+        B1M => 1000000,
+        B1M152 => 1152000,
+        B1M5 => 1500000,
+        B2M => 2000000,
+        B2M5 => 2500000,
+        B3M => 3000000,
+        B3M5 => 3500000,
+        B4M => 4000000,
+        _ => unreachable!(),
+    }
+}
+
+/// Bits per second of the baud rates specific to OS X
+#[cfg(target_os = "macos")]
+fn platform_bits_per_second(rate: &BaudRate) -> uint {
+    match *rate {
+        B7K2 => 7200,
+        B14K4 => 14400,
+        B28K8 => 28800,
+        B76K8 => 76800,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[deriving(Clone, FromPrimitive, PartialEq, Show)]
 #[repr(u32)]
 pub enum DataBits {
     Data5 = termios::CS5,
@@ -397,7 +2872,7 @@ pub enum DataBits {
 }
 
 #[cfg(target_os = "macos")]
-#[deriving(FromPrimitive, PartialEq, Show)]
+#[deriving(Clone, FromPrimitive, PartialEq, Show)]
 #[repr(u64)]
 pub enum DataBits {
     Data5 = termios::CS5,
@@ -406,29 +2881,106 @@ pub enum DataBits {
     Data8 = termios::CS8,
 }
 
+impl DataBits {
+    /// Returns the number of data bits, 5 to 8
+    pub fn to_uint(&self) -> uint {
+        match *self {
+            Data5 => 5,
+            Data6 => 6,
+            Data7 => 7,
+            Data8 => 8,
+        }
+    }
+}
+
+/// Parses the number of data bits, `"5"` to `"8"`
+impl FromStr for DataBits {
+    fn from_str(s: &str) -> Option<DataBits> {
+        match s.trim() {
+            "5" => Some(Data5),
+            "6" => Some(Data6),
+            "7" => Some(Data7),
+            "8" => Some(Data8),
+            _ => None,
+        }
+    }
+}
+
 pub enum Direction {
     BothDirections,
     Input,
     Output,
 }
 
-#[deriving(FromPrimitive, PartialEq, Show)]
+#[deriving(Clone, FromPrimitive, PartialEq, Show)]
 pub enum FlowControl {
     HardwareControl,
     NoFlowControl,
     SoftwareControl,
 }
 
-#[deriving(FromPrimitive, PartialEq, Show)]
+/// Parses `"none"`, `"hardware"` (or `"rtscts"`) and `"software"` (or `"xonxoff"`), whatever
+/// the case
+impl FromStr for FlowControl {
+    fn from_str(s: &str) -> Option<FlowControl> {
+        match s.trim().to_ascii_lower().as_slice() {
+            "none" => Some(NoFlowControl),
+            "hardware" | "rtscts" => Some(HardwareControl),
+            "software" | "xonxoff" => Some(SoftwareControl),
+            _ => None,
+        }
+    }
+}
+
+#[deriving(Clone, FromPrimitive, PartialEq, Show)]
 pub enum Parity {
     EvenParity,
     NoParity,
     OddParity,
+    /// The parity bit is always 1, only supported on Linux
+    MarkParity,
+    /// The parity bit is always 0, only supported on Linux
+    SpaceParity,
+}
+
+/// Parses the parity letter of the `8N1` notation, or the name of the parity, whatever the case
+impl FromStr for Parity {
+    fn from_str(s: &str) -> Option<Parity> {
+        match s.trim().to_ascii_lower().as_slice() {
+            "n" | "none" => Some(NoParity),
+            "e" | "even" => Some(EvenParity),
+            "o" | "odd" => Some(OddParity),
+            "m" | "mark" => Some(MarkParity),
+            "s" | "space" => Some(SpaceParity),
+            _ => None,
+        }
+    }
 }
 
-#[deriving(FromPrimitive, PartialEq, Show)]
+#[deriving(Clone, FromPrimitive, PartialEq, Show)]
 #[repr(u32)]
 pub enum StopBits {
     Stop1,
     Stop2,
 }
+
+impl StopBits {
+    /// Returns the number of stop bits, 1 or 2
+    pub fn to_uint(&self) -> uint {
+        match *self {
+            Stop1 => 1,
+            Stop2 => 2,
+        }
+    }
+}
+
+/// Parses the number of stop bits, `"1"` or `"2"`
+impl FromStr for StopBits {
+    fn from_str(s: &str) -> Option<StopBits> {
+        match s.trim() {
+            "1" => Some(Stop1),
+            "2" => Some(Stop2),
+            _ => None,
+        }
+    }
+}
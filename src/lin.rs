@@ -0,0 +1,266 @@
+//! LIN bus master, over a UART and a LIN transceiver
+//!
+//! Every frame starts with a header sent by the master: a break of at least 13 bit times, the
+//! `0x55` sync byte, and the protected identifier. The response (up to 8 data bytes and a
+//! checksum) is then sent either by the master itself or by a slave. The bus is a single wire,
+//! so the transceiver echoes everything the master sends back to its receiver.
+
+use std::io::{InvalidInput, IoError, IoResult, OtherIoError};
+use std::io::timer;
+use std::time::Duration;
+use time;
+
+use SerialPort;
+
+const SYNC: u8 = 0x55;
+
+/// Largest frame identifier
+pub const MAX_ID: u8 = 0x3F;
+
+/// Identifiers from this one up are diagnostic frames, which always use the classic checksum
+const FIRST_DIAGNOSTIC_ID: u8 = 0x3C;
+
+/// Checksum models
+#[deriving(Clone, PartialEq, Show)]
+pub enum Checksum {
+    /// LIN 1.x, covers the data bytes only
+    ClassicChecksum,
+    /// LIN 2.x, covers the protected identifier as well
+    EnhancedChecksum,
+}
+
+/// Returns the protected identifier of `id`, the 6-bit identifier followed by its two parity
+/// bits
+pub fn protected_id(id: u8) -> u8 {
+    let id = id & MAX_ID;
+    let bit = |n: uint| id >> n & 1;
+
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+
+    id | p0 << 6 | p1 << 7
+}
+
+/// Computes the classic checksum of `data`, the inverted sum with end-around carry
+pub fn classic_checksum(data: &[u8]) -> u8 {
+    !data.iter().fold(0u8, |sum, &byte| {
+        let sum = sum as u16 + byte as u16;
+        (if sum > 0xFF { sum - 0xFF } else { sum }) as u8
+    })
+}
+
+/// Computes the enhanced checksum of a frame, which covers the protected identifier `pid`
+pub fn enhanced_checksum(pid: u8, data: &[u8]) -> u8 {
+    let mut bytes = vec![pid];
+    bytes.push_all(data);
+
+    classic_checksum(bytes.as_slice())
+}
+
+/// Computes the checksum of a frame with the `model`
+///
+/// Diagnostic frames (identifiers `0x3C` to `0x3F`) use the classic checksum regardless of the
+/// model.
+pub fn checksum(model: Checksum, id: u8, data: &[u8]) -> u8 {
+    match model {
+        EnhancedChecksum if id & MAX_ID < FIRST_DIAGNOSTIC_ID => {
+            enhanced_checksum(protected_id(id), data)
+        },
+        _ => classic_checksum(data),
+    }
+}
+
+/// What the master does after a header
+#[deriving(Clone, PartialEq, Show)]
+pub enum Transfer {
+    /// The master sends the response itself
+    Publish(Vec<u8>),
+    /// A slave sends a response of the given length
+    Subscribe(uint),
+}
+
+/// A slot of a schedule table
+#[deriving(Clone, PartialEq, Show)]
+pub struct Slot {
+    /// Identifier of the frame, without the parity bits
+    pub id: u8,
+    pub transfer: Transfer,
+    pub checksum: Checksum,
+    /// Time from the start of this slot to the start of the next one
+    pub duration: Duration,
+}
+
+/// The master of a LIN bus
+///
+/// The port must already be configured for the bus: usually 19200 or 9600 bauds, 8 data bits,
+/// no parity, 1 stop bit, no flow control.
+pub struct Master {
+    port: SerialPort,
+    bit_rate: u32,
+    echo: bool,
+}
+
+impl Master {
+    /// Drives the bus through `port`, whose baud rate is `bit_rate`
+    ///
+    /// The bit rate is only used to time the breaks. The timeout of the port is set to 50 ms,
+    /// see `set_response_timeout`.
+    pub fn new(mut port: SerialPort, bit_rate: u32) -> Master {
+        port.set_timeout(Some(Duration::milliseconds(50)));
+
+        Master {
+            port: port,
+            bit_rate: bit_rate,
+            echo: true,
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut SerialPort {
+        &mut self.port
+    }
+
+    /// Unwraps the port
+    pub fn into_inner(self) -> SerialPort {
+        self.port
+    }
+
+    /// Changes whether the transceiver echoes the transmitted bytes, `true` by default
+    ///
+    /// The echo is checked against the transmitted bytes, a mismatch means that another node
+    /// was transmitting at the same time.
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    /// Changes how long to wait for each byte of a slave response, 50 ms by default
+    ///
+    /// The bus itself is fast, this mostly covers the latency of USB adapters.
+    pub fn set_response_timeout(&mut self, timeout: Duration) {
+        self.port.set_timeout(Some(timeout));
+    }
+
+    /// Sends a header for `id`, then the response `data` followed by its checksum
+    pub fn publish(&mut self, id: u8, data: &[u8], model: Checksum) -> IoResult<()> {
+        try!(check_frame(id, data.len()));
+        try!(self.header(id));
+
+        let mut response = data.to_vec();
+        response.push(checksum(model, id, data));
+
+        try!(self.port.write(response.as_slice()));
+        self.read_echo(response.as_slice())
+    }
+
+    /// Sends a header for `id`, and receives the `len` bytes of the slave response
+    ///
+    /// A missing response is reported as a `TimedOut` error, a wrong checksum as an
+    /// `InvalidInput` error.
+    pub fn request(&mut self, id: u8, len: uint, model: Checksum) -> IoResult<Vec<u8>> {
+        try!(check_frame(id, len));
+        try!(self.header(id));
+
+        let mut data = try!(self.port.read_exact(len + 1));
+        let received = data.pop().unwrap();
+        let expected = checksum(model, id, data.as_slice());
+
+        if received == expected {
+            Ok(data)
+        } else {
+            Err(IoError {
+                kind: InvalidInput,
+                desc: "LIN checksum mismatch",
+                detail: Some(format!("frame {:02X}: expected {:02X}, received {:02X}",
+                                     id, expected, received)),
+            })
+        }
+    }
+
+    /// Runs a `slot` of a schedule table, returns the response of the slave if the slot
+    /// subscribes to one
+    ///
+    /// The call lasts for the duration of the slot, even if the transfer fails. A schedule
+    /// table is run by cycling through its slots:
+    ///
+    /// ```ignore
+    /// for slot in table.iter().cycle() {
+    ///     match master.run_slot(slot) { ... }
+    /// }
+    /// ```
+    pub fn run_slot(&mut self, slot: &Slot) -> IoResult<Option<Vec<u8>>> {
+        let start = time::precise_time_ns();
+
+        let result = match slot.transfer {
+            Publish(ref data) => {
+                self.publish(slot.id, data.as_slice(), slot.checksum).map(|_| None)
+            },
+            Subscribe(len) => self.request(slot.id, len, slot.checksum).map(|data| Some(data)),
+        };
+
+        let elapsed = Duration::nanoseconds((time::precise_time_ns() - start) as i64);
+        if elapsed < slot.duration {
+            timer::sleep(slot.duration - elapsed);
+        }
+
+        result
+    }
+
+    /// Sends the break, sync byte and protected identifier of a frame
+    fn header(&mut self, id: u8) -> IoResult<()> {
+        // 13 bit times, rounded up
+        let micros = (13_000_000 + self.bit_rate as i64 - 1) / self.bit_rate as i64;
+        try!(self.port.send_break(Duration::microseconds(micros)));
+
+        let header = [SYNC, protected_id(id)];
+        try!(self.port.write(&header));
+
+        if !self.echo {
+            return Ok(())
+        }
+
+        // The break is received as a null byte, if at all
+        let mut sync = try!(self.port.read_byte());
+        while sync == 0 {
+            sync = try!(self.port.read_byte());
+        }
+        let pid = try!(self.port.read_byte());
+
+        compare_echo(&header, &[sync, pid])
+    }
+
+    /// Reads back the echo of the transmitted `bytes`
+    fn read_echo(&mut self, bytes: &[u8]) -> IoResult<()> {
+        if !self.echo {
+            return Ok(())
+        }
+
+        let echo = try!(self.port.read_exact(bytes.len()));
+
+        compare_echo(bytes, echo.as_slice())
+    }
+}
+
+/// Refuses identifiers and response lengths that don't fit in a frame
+fn check_frame(id: u8, len: uint) -> IoResult<()> {
+    if id > MAX_ID || len == 0 || len > 8 {
+        Err(IoError {
+            kind: InvalidInput,
+            desc: "Invalid LIN frame",
+            detail: Some(format!("identifier {:02X}, {} data bytes", id, len)),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+fn compare_echo(sent: &[u8], echo: &[u8]) -> IoResult<()> {
+    if sent == echo {
+        Ok(())
+    } else {
+        Err(IoError {
+            kind: OtherIoError,
+            desc: "LIN bus collision",
+            detail: Some(format!("sent {}, read back {}", sent, echo)),
+        })
+    }
+}
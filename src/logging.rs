@@ -0,0 +1,143 @@
+use libc::c_int;
+use log;
+use std::io::IoError;
+use std::os;
+
+use termios::{Termios, tcgetattr as get};
+
+/// Whether the debug records of the termios changes and of the failed system calls are
+/// emitted, which takes the `logging` feature
+fn enabled() -> bool {
+    cfg!(feature = "logging") && log_enabled!(log::DEBUG)
+}
+
+/// Returns the error of the `operation` that just failed on `fd`, and logs it
+pub fn last_error(fd: c_int, operation: &str) -> IoError {
+    os_error(fd, operation, os::errno() as uint)
+}
+
+/// Returns the error `errno` of the `operation` that failed on `fd`, and logs it
+///
+/// The detail of the error names the operation and the file descriptor, before the message of
+/// the system.
+pub fn os_error(fd: c_int, operation: &str, errno: uint) -> IoError {
+    let mut error = IoError::from_errno(errno, true);
+    error.detail = Some(match error.detail {
+        None => format!("{} on fd {}", operation, fd),
+        Some(message) => format!("{} on fd {}: {}", operation, fd, message),
+    });
+
+    if enabled() {
+        debug!("fd {}: {} failed with errno {} ({})", fd, operation, errno, error);
+    }
+
+    error
+}
+
+/// Logs the termios of `fd` read by `tcgetattr`
+pub fn tcgetattr(fd: c_int, termios: &Termios) {
+    if enabled() {
+        debug!("fd {}: tcgetattr {}", fd, summary(termios));
+    }
+}
+
+/// Reads the termios of `fd` before a change, for `tcsetattr`
+///
+/// Returns `None` when the records aren't emitted, sparing the system call.
+pub fn before_change(fd: c_int) -> Option<Termios> {
+    if !enabled() {
+        return None
+    }
+
+    let mut termios = Termios::new();
+    match unsafe { get(fd, &mut termios) } {
+        0 => Some(termios),
+        _ => None,
+    }
+}
+
+/// Logs the change of the termios of `fd` to `after` by `tcsetattr`, from `before` as read by
+/// `before_change`
+pub fn tcsetattr(fd: c_int, before: Option<Termios>, after: &Termios) {
+    if !enabled() {
+        return
+    }
+
+    match before {
+        None => debug!("fd {}: tcsetattr {}", fd, summary(after)),
+        Some(before) => debug!("fd {}: tcsetattr {}", fd, changes(&before, after)),
+    }
+}
+
+/// Formats the flags and the speeds of `termios`
+fn summary(termios: &Termios) -> String {
+    format!("iflag 0x{:x}, oflag 0x{:x}, cflag 0x{:x}, lflag 0x{:x}, ispeed {}, ospeed {}",
+            termios.c_iflag, termios.c_oflag, termios.c_cflag, termios.c_lflag,
+            termios.c_ispeed, termios.c_ospeed)
+}
+
+/// Formats the flags and the speeds that differ between `before` and `after`
+fn changes(before: &Termios, after: &Termios) -> String {
+    let flags = [
+        ("iflag", before.c_iflag, after.c_iflag),
+        ("oflag", before.c_oflag, after.c_oflag),
+        ("cflag", before.c_cflag, after.c_cflag),
+        ("lflag", before.c_lflag, after.c_lflag),
+    ];
+    let speeds = [
+        ("ispeed", before.c_ispeed, after.c_ispeed),
+        ("ospeed", before.c_ospeed, after.c_ospeed),
+    ];
+
+    let mut changes: Vec<String> = flags.iter().filter(|&&(_, from, to)| from != to)
+                                        .map(|&(name, from, to)| {
+        format!("{} 0x{:x} -> 0x{:x}", name, from, to)
+    }).collect();
+    changes.extend(speeds.iter().filter(|&&(_, from, to)| from != to).map(|&(name, from, to)| {
+        format!("{} {} -> {}", name, from, to)
+    }));
+
+    if changes.is_empty() {
+        "unchanged".to_string()
+    } else {
+        changes.connect(", ")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libc::consts::os::posix88::EBADF;
+    use libc::funcs::posix88::unistd::close;
+    use std::io::IoError;
+
+    use termios::Termios;
+
+    use super::{changes, last_error, os_error, summary};
+
+    #[test]
+    fn errors() {
+        let ebadf = IoError::from_errno(EBADF as uint, false).kind;
+
+        let error = os_error(42, "TIOCMBIS", EBADF as uint);
+        assert_eq!(error.kind, ebadf);
+        assert!(error.detail.unwrap().as_slice().starts_with("TIOCMBIS on fd 42: "));
+
+        assert_eq!(unsafe { close(-1) }, -1);
+        let error = last_error(-1, "close");
+        assert_eq!(error.kind, ebadf);
+        assert!(error.detail.unwrap().as_slice().starts_with("close on fd -1: "));
+    }
+
+    #[test]
+    fn termios() {
+        let before = Termios::new();
+        let mut after = Termios::new();
+        after.c_cflag = 0x30;
+        after.c_ospeed = 13;
+
+        assert_eq!(summary(&after).as_slice(),
+                   "iflag 0x0, oflag 0x0, cflag 0x30, lflag 0x0, ispeed 0, ospeed 13");
+        assert_eq!(changes(&before, &after).as_slice(), "cflag 0x0 -> 0x30, ospeed 0 -> 13");
+        assert_eq!(changes(&after, &after).as_slice(), "unchanged");
+    }
+}
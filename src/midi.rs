@@ -0,0 +1,377 @@
+//! MIDI over a serial port (31250 baud, 8 data bits, no parity, 1 stop bit)
+//!
+//! A message starts with a status byte (most significant bit set), followed by 7-bit data bytes.
+//! Senders may omit the status byte of a channel message when it repeats the previous one
+//! ("running status"), the parser keeps track of it.
+
+use std::collections::RingBuf;
+use std::io::IoResult;
+
+#[cfg(target_os = "linux")]
+use std::default::Default;
+#[cfg(target_os = "linux")]
+use SerialPort;
+
+/// Baud rate of the MIDI current loop
+pub const BAUD_RATE: u32 = 31250;
+
+// Channel messages, the low nibble is the channel
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const POLY_PRESSURE: u8 = 0xA0;
+const CONTROL_CHANGE: u8 = 0xB0;
+const PROGRAM_CHANGE: u8 = 0xC0;
+const CHANNEL_PRESSURE: u8 = 0xD0;
+const PITCH_BEND: u8 = 0xE0;
+
+// System common messages
+const SYSEX_START: u8 = 0xF0;
+const TIME_CODE: u8 = 0xF1;
+const SONG_POSITION: u8 = 0xF2;
+const SONG_SELECT: u8 = 0xF3;
+const TUNE_REQUEST: u8 = 0xF6;
+const SYSEX_END: u8 = 0xF7;
+
+// System real-time messages
+const TIMING_CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+const ACTIVE_SENSING: u8 = 0xFE;
+const RESET: u8 = 0xFF;
+
+/// Largest SysEx message accepted
+const MAX_SYSEX_LEN: uint = 4096;
+
+/// A MIDI message
+///
+/// Channels range from 0 to 15, data values are 7-bit, extra bits are dropped when encoding.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Message {
+    /// Channel, note and velocity
+    NoteOffMessage(u8, u8, u8),
+    /// Channel, note and velocity, a zero velocity is commonly used as a note off
+    NoteOnMessage(u8, u8, u8),
+    /// Channel, note and pressure
+    PolyPressureMessage(u8, u8, u8),
+    /// Channel, controller and value
+    ControlChangeMessage(u8, u8, u8),
+    /// Channel and program
+    ProgramChangeMessage(u8, u8),
+    /// Channel and pressure
+    ChannelPressureMessage(u8, u8),
+    /// Channel and 14-bit value, `0x2000` is the center
+    PitchBendMessage(u8, u16),
+    /// System exclusive data, without the `0xF0` and `0xF7` delimiters
+    SysExMessage(Vec<u8>),
+    /// MIDI time code quarter frame
+    TimeCodeMessage(u8),
+    /// Song position, in sixteenth notes
+    SongPositionMessage(u16),
+    SongSelectMessage(u8),
+    TuneRequestMessage,
+    TimingClockMessage,
+    StartMessage,
+    ContinueMessage,
+    StopMessage,
+    ActiveSensingMessage,
+    ResetMessage,
+}
+
+impl Message {
+    /// Encodes the message, status byte included
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.status()];
+        bytes.push_all(self.data().as_slice());
+
+        match *self {
+            SysExMessage(_) => bytes.push(SYSEX_END),
+            _ => {},
+        }
+
+        bytes
+    }
+
+    /// Returns the status byte of the message
+    pub fn status(&self) -> u8 {
+        match *self {
+            NoteOffMessage(channel, _, _) => NOTE_OFF | channel & 0x0F,
+            NoteOnMessage(channel, _, _) => NOTE_ON | channel & 0x0F,
+            PolyPressureMessage(channel, _, _) => POLY_PRESSURE | channel & 0x0F,
+            ControlChangeMessage(channel, _, _) => CONTROL_CHANGE | channel & 0x0F,
+            ProgramChangeMessage(channel, _) => PROGRAM_CHANGE | channel & 0x0F,
+            ChannelPressureMessage(channel, _) => CHANNEL_PRESSURE | channel & 0x0F,
+            PitchBendMessage(channel, _) => PITCH_BEND | channel & 0x0F,
+            SysExMessage(_) => SYSEX_START,
+            TimeCodeMessage(_) => TIME_CODE,
+            SongPositionMessage(_) => SONG_POSITION,
+            SongSelectMessage(_) => SONG_SELECT,
+            TuneRequestMessage => TUNE_REQUEST,
+            TimingClockMessage => TIMING_CLOCK,
+            StartMessage => START,
+            ContinueMessage => CONTINUE,
+            StopMessage => STOP,
+            ActiveSensingMessage => ACTIVE_SENSING,
+            ResetMessage => RESET,
+        }
+    }
+
+    /// Returns the data bytes that follow the status byte
+    fn data(&self) -> Vec<u8> {
+        match *self {
+            NoteOffMessage(_, a, b) | NoteOnMessage(_, a, b) | PolyPressureMessage(_, a, b) |
+            ControlChangeMessage(_, a, b) => vec![a & 0x7F, b & 0x7F],
+            ProgramChangeMessage(_, a) | ChannelPressureMessage(_, a) | TimeCodeMessage(a) |
+            SongSelectMessage(a) => vec![a & 0x7F],
+            PitchBendMessage(_, value) | SongPositionMessage(value) => {
+                vec![value as u8 & 0x7F, (value >> 7) as u8 & 0x7F]
+            },
+            SysExMessage(ref data) => data.iter().map(|&byte| byte & 0x7F).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Builds the message of `status`, from its complete `data`
+    fn parse(status: u8, data: &[u8]) -> Message {
+        let channel = status & 0x0F;
+
+        match status & 0xF0 {
+            NOTE_OFF => NoteOffMessage(channel, data[0], data[1]),
+            NOTE_ON => NoteOnMessage(channel, data[0], data[1]),
+            POLY_PRESSURE => PolyPressureMessage(channel, data[0], data[1]),
+            CONTROL_CHANGE => ControlChangeMessage(channel, data[0], data[1]),
+            PROGRAM_CHANGE => ProgramChangeMessage(channel, data[0]),
+            CHANNEL_PRESSURE => ChannelPressureMessage(channel, data[0]),
+            PITCH_BEND => PitchBendMessage(channel, data[0] as u16 | (data[1] as u16) << 7),
+            _ => match status {
+                TIME_CODE => TimeCodeMessage(data[0]),
+                SONG_POSITION => SongPositionMessage(data[0] as u16 | (data[1] as u16) << 7),
+                _ => SongSelectMessage(data[0]),
+            },
+        }
+    }
+
+    /// Returns the real-time message of `status`, `None` if it's undefined
+    fn real_time(status: u8) -> Option<Message> {
+        match status {
+            TIMING_CLOCK => Some(TimingClockMessage),
+            START => Some(StartMessage),
+            CONTINUE => Some(ContinueMessage),
+            STOP => Some(StopMessage),
+            ACTIVE_SENSING => Some(ActiveSensingMessage),
+            RESET => Some(ResetMessage),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the number of data bytes of the messages that use `status`
+fn data_len(status: u8) -> uint {
+    match status & 0xF0 {
+        PROGRAM_CHANGE | CHANNEL_PRESSURE => 1,
+        0xF0 => match status {
+            TIME_CODE | SONG_SELECT => 1,
+            SONG_POSITION => 2,
+            _ => 0,
+        },
+        _ => 2,
+    }
+}
+
+/// Splits the incoming byte stream into messages
+pub struct Parser {
+    /// Status of the message being received, also the running status between channel messages
+    status: Option<u8>,
+    data: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            status: None,
+            data: Vec::new(),
+        }
+    }
+
+    /// Feeds `data`, returns the messages it completes
+    ///
+    /// Real-time messages are returned as soon as they arrive, even in the middle of another
+    /// message. Data bytes received without a status byte are dropped, as are incomplete
+    /// messages interrupted by a status byte.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Message> {
+        let mut messages = Vec::new();
+
+        for &byte in data.iter() {
+            if byte >= TIMING_CLOCK {
+                match Message::real_time(byte) {
+                    None => {},
+                    Some(message) => messages.push(message),
+                }
+
+                continue
+            }
+
+            if byte & 0x80 != 0 {
+                if byte == SYSEX_END && self.status == Some(SYSEX_START) {
+                    messages.push(SysExMessage(self.data.clone()));
+                }
+
+                self.data.clear();
+                self.status = match byte {
+                    TUNE_REQUEST => {
+                        messages.push(TuneRequestMessage);
+                        None
+                    },
+                    // `0xF4` and `0xF5` are undefined
+                    SYSEX_END | 0xF4 | 0xF5 => None,
+                    _ => Some(byte),
+                };
+
+                continue
+            }
+
+            let status = match self.status {
+                None => continue,
+                Some(status) => status,
+            };
+
+            if status == SYSEX_START {
+                if self.data.len() == MAX_SYSEX_LEN {
+                    self.status = None;
+                    self.data.clear();
+                } else {
+                    self.data.push(byte);
+                }
+
+                continue
+            }
+
+            self.data.push(byte);
+
+            if self.data.len() == data_len(status) {
+                messages.push(Message::parse(status, self.data.as_slice()));
+                self.data.clear();
+
+                // Only channel messages set the running status
+                if status >= SYSEX_START {
+                    self.status = None;
+                }
+            }
+        }
+
+        messages
+    }
+
+    /// Discards any partially received message, and the running status
+    pub fn reset(&mut self) {
+        self.status = None;
+        self.data.clear();
+    }
+}
+
+/// Encodes messages using running status
+pub struct Encoder {
+    status: Option<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Encoder {
+            status: None,
+        }
+    }
+
+    /// Encodes `message`, omits its status byte if it repeats the status of the previous channel
+    /// message
+    pub fn encode(&mut self, message: &Message) -> Vec<u8> {
+        let status = message.status();
+
+        if status < SYSEX_START {
+            if self.status == Some(status) {
+                return message.data()
+            }
+
+            self.status = Some(status);
+        } else if status < TIMING_CLOCK {
+            self.status = None;
+        }
+
+        message.encode()
+    }
+
+    /// Makes the next message carry its status byte
+    ///
+    /// Worth doing from time to time, so that a receiver that missed a status byte resyncs.
+    pub fn reset(&mut self) {
+        self.status = None;
+    }
+}
+
+/// A MIDI interface connected to a port
+pub struct Device<S> {
+    port: S,
+    parser: Parser,
+    pending: RingBuf<Message>,
+    /// Set when the sent messages use running status
+    encoder: Option<Encoder>,
+}
+
+impl<S: Reader + Writer> Device<S> {
+    /// Talks MIDI through `port`, see `configure` to set it up
+    pub fn new(port: S) -> Device<S> {
+        Device {
+            port: port,
+            parser: Parser::new(),
+            pending: RingBuf::new(),
+            encoder: None,
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.port
+    }
+
+    /// Unwraps the port
+    pub fn into_inner(self) -> S {
+        self.port
+    }
+
+    /// Receives the next message
+    ///
+    /// Use a timeout on the port to bound the wait.
+    pub fn receive(&mut self) -> IoResult<Message> {
+        loop {
+            match self.pending.pop_front() {
+                None => {},
+                Some(message) => return Ok(message),
+            }
+
+            let mut chunk = [0u8, ..256];
+            let n = try!(self.port.read(&mut chunk));
+            self.pending.extend(self.parser.feed(chunk.slice_to(n)).into_iter());
+        }
+    }
+
+    /// Sends a message
+    pub fn send(&mut self, message: &Message) -> IoResult<()> {
+        let bytes = match self.encoder {
+            None => message.encode(),
+            Some(ref mut encoder) => encoder.encode(message),
+        };
+
+        self.port.write(bytes.as_slice())
+    }
+
+    /// Enables or disables running status on the sent messages, disabled by default
+    pub fn set_running_status(&mut self, enable: bool) {
+        self.encoder = if enable { Some(Encoder::new()) } else { None };
+    }
+}
+
+/// Configures `port` for MIDI: 31250 baud, 8 data bits, no parity, 1 stop bit
+#[cfg(target_os = "linux")]
+pub fn configure(port: &mut SerialPort) -> IoResult<()> {
+    try!(port.configure(&Default::default()));
+    port.set_custom_baud_rate(BAUD_RATE)
+}
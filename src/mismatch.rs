@@ -0,0 +1,183 @@
+use std::io::{IoResult, TimedOut};
+use std::time::Duration;
+use time;
+
+use {BaudRate, SerialIo, SerialPort};
+
+/// Fewer bytes than this don't tell anything about the rate
+const MIN_BYTES: u64 = 64;
+/// Line errors per byte received past which the rate is probably wrong
+const MAX_ERROR_RATIO: f64 = 0.01;
+/// Share of single run bytes past which the sender is probably slower than the port
+const MAX_RUN_RATIO: f64 = 0.5;
+
+/// Outcome of a baud rate check, see `BaudCheck`
+#[deriving(Clone, PartialEq, Show)]
+pub struct BaudDiagnosis {
+    /// Bytes examined
+    pub bytes: u64,
+    /// Framing and parity errors counted by the driver meanwhile, `None` if it doesn't count
+    /// them
+    pub line_errors: Option<u64>,
+    /// Share of the bytes made of a single run of zeros then ones, like `0x00`, `0xF0` or
+    /// `0xFF`, which is what a port receives from a slower sender
+    pub run_ratio: f64,
+    /// Whether the rates of the port and of the sender probably differ
+    pub probable_mismatch: bool,
+    /// The standard rate the sender seems to use, when the data tells it
+    pub suggested_rate: Option<BaudRate>,
+}
+
+/// Looks for the signs of a baud rate mismatch in the received data
+///
+/// Garbage bytes are the usual symptom of a port and a device set to different rates. A port
+/// faster than the sender samples each bit of the sender several times, so it receives long
+/// runs of the same bit: mostly bytes like `0x00`, `0x80`, `0xF8` or `0xFF`, from which the rate
+/// of the sender can be estimated. A port slower than the sender misses bits, which shows up
+/// as framing and parity errors in the counters of the driver.
+///
+/// `SerialPort::diagnose_baud_rate` does the whole check on received data; a `BaudCheck` can
+/// also be fed the data an application already reads.
+pub struct BaudCheck {
+    /// Occurrences of each byte value
+    histogram: Vec<u64>,
+    bytes: u64,
+    line_errors: Option<u64>,
+}
+
+impl BaudCheck {
+    /// A check that examined nothing yet
+    pub fn new() -> BaudCheck {
+        BaudCheck {
+            histogram: Vec::from_elem(256, 0u64),
+            bytes: 0,
+            line_errors: None,
+        }
+    }
+
+    /// Examines received `data`
+    pub fn feed(&mut self, data: &[u8]) {
+        for &byte in data.iter() {
+            self.histogram[byte as uint] += 1;
+        }
+
+        self.bytes += data.len() as u64;
+    }
+
+    /// Accounts for `errors` framing and parity errors, counted by the driver while the data
+    /// was received
+    pub fn add_line_errors(&mut self, errors: u64) {
+        self.line_errors = Some(self.line_errors.unwrap_or(0) + errors);
+    }
+
+    /// Diagnoses the data examined so far, received at `rate`
+    ///
+    /// No mismatch is reported before 64 bytes were examined.
+    pub fn diagnose(&self, rate: BaudRate) -> BaudDiagnosis {
+        let runs = range(0u, 256).filter(|&byte| is_run(byte as u8))
+                                 .fold(0, |runs, byte| runs + self.histogram[byte]);
+        let ratio = |n: u64| if self.bytes == 0 { 0.0 } else { n as f64 / self.bytes as f64 };
+
+        let enough = self.bytes >= MIN_BYTES;
+        let slower_sender = enough && ratio(runs) > MAX_RUN_RATIO;
+        let errors = enough && self.line_errors.map_or(false, |n| ratio(n) > MAX_ERROR_RATIO);
+
+        BaudDiagnosis {
+            bytes: self.bytes,
+            line_errors: self.line_errors,
+            run_ratio: ratio(runs),
+            probable_mismatch: slower_sender || errors,
+            suggested_rate: if slower_sender { self.sender_rate(rate) } else { None },
+        }
+    }
+
+    /// Estimates the rate of a sender slower than `rate`
+    ///
+    /// The start bit of the sender lasts as many bits of the port as each of its bits, the
+    /// first of them being taken for the start bit, the others as trailing zeros of the byte.
+    /// When the first data bit sent is a one, which is the most common case, the byte has as
+    /// many trailing zeros.
+    fn sender_rate(&self, rate: BaudRate) -> Option<BaudRate> {
+        let mut zeros = [0u64, ..8];
+        for byte in range(1u, 256) {
+            zeros[trailing_zeros(byte as u8)] += self.histogram[byte];
+        }
+
+        let (bits, &count) = match zeros.iter().enumerate().max_by(|&(_, &count)| count) {
+            None => return None,
+            Some(most) => most,
+        };
+        if bits == 0 || count == 0 {
+            return None
+        }
+
+        let estimate = (rate.bits_per_second() / (bits + 1)) as i64;
+
+        BaudRate::standard_rates().iter().filter(|&&standard| standard < rate).min_by(|standard| {
+            (standard.bits_per_second() as i64 - estimate).abs()
+        }).map(|&standard| standard)
+    }
+}
+
+/// Tells whether the bits of `byte` are a single run of zeros then ones, or ones then zeros
+fn is_run(byte: u8) -> bool {
+    let transitions = (byte ^ (byte >> 1)) & 0x7F;
+
+    transitions == 0 || transitions & (transitions - 1) == 0
+}
+
+/// Returns the number of trailing zeros of `byte`, which isn't zero
+fn trailing_zeros(byte: u8) -> uint {
+    range(0u, 8).take_while(|&bit| byte & (1 << bit) == 0).count()
+}
+
+/// Receives for `listen`, and checks the data and the line errors, see
+/// `SerialPort::diagnose_baud_rate`
+pub fn diagnose(port: &mut SerialPort, listen: Duration) -> IoResult<BaudDiagnosis> {
+    let (rate, _) = try!(port.baud_rate());
+    let timeout = port.timeout();
+    let before = line_errors(port);
+
+    let listen = listen.num_nanoseconds().unwrap_or(0);
+    let deadline = time::precise_time_ns() + if listen < 0 { 0 } else { listen as u64 };
+    let mut check = BaudCheck::new();
+    let mut buf = [0u8, ..256];
+
+    let mut result = Ok(());
+    loop {
+        let now = time::precise_time_ns();
+        if now >= deadline {
+            break
+        }
+
+        port.set_timeout(Some(Duration::nanoseconds((deadline - now) as i64)));
+        match port.read(&mut buf) {
+            Ok(n) => check.feed(buf.slice_to(n)),
+            Err(ref e) if e.kind == TimedOut => break,
+            Err(e) => {
+                result = Err(e);
+                break
+            },
+        }
+    }
+    port.set_timeout(timeout);
+    try!(result);
+
+    match (before, line_errors(port)) {
+        (Some(before), Some(after)) => check.add_line_errors(after - before),
+        _ => {},
+    }
+
+    Ok(check.diagnose(rate))
+}
+
+/// Returns the framing and parity errors counted by the driver since the port was opened
+#[cfg(target_os = "linux")]
+fn line_errors(port: &SerialPort) -> Option<u64> {
+    port.icount().ok().map(|counters| (counters.frame + counters.parity) as u64)
+}
+
+#[cfg(target_os = "macos")]
+fn line_errors(_: &SerialPort) -> Option<u64> {
+    None
+}
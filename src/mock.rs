@@ -0,0 +1,347 @@
+//! A scripted device for protocol unit tests, enabled by the `testing` feature
+//!
+//! A `MockPort` answers the requests of the code under test by rules: when these bytes, or
+//! text matching this regular expression, are received, reply with these bytes after this
+//! delay. The rules double as expectations, checked by `verify` at the end of the test:
+//!
+//! ```ignore
+//! let signal = Regex::new(r"AT\+CSQ\r").unwrap();
+//! let mut port = MockPort::new(Strict)
+//!     .rule(Rule::on(b"AT\r").reply(b"OK\r\n"))
+//!     .rule(Rule::on_regex(signal).reply(b"+CSQ: 23,99\r\n").times(2));
+//! try!(modem.run(&mut port));
+//! port.verify();
+//! ```
+
+use regex::Regex;
+use std::collections::RingBuf;
+use std::default::Default;
+use std::fmt;
+use std::io::{IoError, IoResult, OtherIoError, TimedOut};
+use std::str;
+use std::time::Duration;
+
+use clock::{RealTime, TimeSource, Virtual, nanoseconds};
+use {SerialIo, Settings, VirtualClock};
+
+/// What a rule waits for, at the start of the data received and not matched yet
+#[deriving(Clone)]
+pub enum Trigger {
+    BytesTrigger(Vec<u8>),
+    RegexTrigger(Regex),
+}
+
+impl Trigger {
+    /// Returns the length of the match at the start of `data`
+    ///
+    /// Regular expressions are matched against the longest prefix of `data` that is valid UTF-8.
+    fn find(&self, data: &[u8]) -> Option<uint> {
+        match *self {
+            BytesTrigger(ref bytes) => {
+                if data.starts_with(bytes.as_slice()) { Some(bytes.len()) } else { None }
+            },
+            RegexTrigger(ref regex) => {
+                let mut end = data.len();
+                while str::from_utf8(data.slice_to(end)).is_none() {
+                    end -= 1;
+                }
+
+                match regex.find(str::from_utf8(data.slice_to(end)).unwrap()) {
+                    Some((0, end)) if end > 0 => Some(end),
+                    _ => None,
+                }
+            },
+        }
+    }
+
+    /// Describes the trigger, for the report
+    fn describe(&self) -> String {
+        match *self {
+            BytesTrigger(ref bytes) => format!("{}", bytes),
+            RegexTrigger(ref regex) => format!("/{}/", regex),
+        }
+    }
+}
+
+/// A request the mock expects, and its reply
+#[deriving(Clone)]
+pub struct Rule {
+    trigger: Trigger,
+    reply: Vec<u8>,
+    delay: Duration,
+    times: uint,
+}
+
+impl Rule {
+    /// Matches `bytes`, once, without replying
+    pub fn on(bytes: &[u8]) -> Rule {
+        Rule::new(BytesTrigger(bytes.to_vec()))
+    }
+
+    /// Matches text that `regex` matches, once, without replying
+    ///
+    /// The match has to start with the data not matched yet, but the expression doesn't need
+    /// to be anchored with `^`.
+    pub fn on_regex(regex: Regex) -> Rule {
+        Rule::new(RegexTrigger(regex))
+    }
+
+    /// Replies `bytes` to every match
+    pub fn reply(mut self, bytes: &[u8]) -> Rule {
+        self.reply = bytes.to_vec();
+        self
+    }
+
+    /// Delays the reply by `delay` after the end of the request
+    pub fn after(mut self, delay: Duration) -> Rule {
+        self.delay = delay;
+        self
+    }
+
+    /// Expects the request `times` times, it isn't matched past that
+    pub fn times(mut self, times: uint) -> Rule {
+        self.times = times;
+        self
+    }
+
+    fn new(trigger: Trigger) -> Rule {
+        Rule {
+            trigger: trigger,
+            reply: Vec::new(),
+            delay: Duration::zero(),
+            times: 1,
+        }
+    }
+}
+
+/// How the rules are matched
+#[deriving(Clone, PartialEq, Show)]
+pub enum Expectations {
+    /// In the order they were added, each rule only matches once the previous ones are done
+    Strict,
+    /// In any order, the first rule that matches and isn't done applies
+    Unordered,
+}
+
+/// What a `MockPort` expected and didn't get
+#[deriving(Clone, PartialEq)]
+pub struct Report {
+    /// The rules matched fewer times than expected: their trigger, how many times they were
+    /// matched and expected
+    pub unmet: Vec<(String, uint, uint)>,
+    /// Data received that no rule matched
+    pub unmatched: Vec<u8>,
+}
+
+impl Report {
+    /// Tells whether all the rules were matched as many times as expected, and all the data
+    /// received was matched
+    pub fn is_satisfied(&self) -> bool {
+        self.unmet.is_empty() && self.unmatched.is_empty()
+    }
+}
+
+impl fmt::Show for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_satisfied() {
+            return write!(f, "all the expectations were met")
+        }
+
+        for &(ref trigger, matched, times) in self.unmet.iter() {
+            try!(writeln!(f, "{}: matched {} of {} times", trigger, matched, times));
+        }
+
+        if !self.unmatched.is_empty() {
+            try!(writeln!(f, "unmatched data: {}", self.unmatched));
+        }
+
+        Ok(())
+    }
+}
+
+/// A device that answers by rules, see the module documentation
+///
+/// The data written to the port is matched against the rules as it comes in, the data of a
+/// request can be split across writes. Reads return the replies once their delay elapsed, and
+/// fail with a `TimedOut` error if the timeout of the port elapses first; without a timeout,
+/// reading when no reply is on its way fails with an `OtherIoError`. The delays take real time,
+/// unless the port runs on a `VirtualClock`.
+pub struct MockPort {
+    rules: Vec<Rule>,
+    /// How many times each rule was matched
+    matched: Vec<uint>,
+    expectations: Expectations,
+    /// Data written and not matched yet
+    received: Vec<u8>,
+    /// Bytes of the replies, with the time at which they become readable
+    replies: RingBuf<(u64, u8)>,
+    settings: Settings,
+    timeout: Option<Duration>,
+    clock: TimeSource,
+}
+
+impl MockPort {
+    /// A port with no rules yet
+    pub fn new(expectations: Expectations) -> MockPort {
+        MockPort {
+            rules: Vec::new(),
+            matched: Vec::new(),
+            expectations: expectations,
+            received: Vec::new(),
+            replies: RingBuf::new(),
+            settings: Default::default(),
+            timeout: None,
+            clock: RealTime,
+        }
+    }
+
+    /// Runs the port on `clock`, the delays of the replies and the timeouts move it forward
+    /// instead of taking real time
+    pub fn clock(mut self, clock: VirtualClock) -> MockPort {
+        self.clock = Virtual(clock);
+        self
+    }
+
+    /// Adds `rule`, after the rules added before
+    pub fn rule(mut self, rule: Rule) -> MockPort {
+        self.rules.push(rule);
+        self.matched.push(0);
+        self
+    }
+
+    /// Returns what was expected and didn't happen so far
+    pub fn report(&self) -> Report {
+        let unmet = self.rules.iter().zip(self.matched.iter()).filter_map(|(rule, &matched)| {
+            if matched < rule.times {
+                Some((rule.trigger.describe(), matched, rule.times))
+            } else {
+                None
+            }
+        }).collect();
+
+        Report {
+            unmet: unmet,
+            unmatched: self.received.clone(),
+        }
+    }
+
+    /// Panics with the report, unless all the expectations were met
+    pub fn verify(&self) {
+        let report = self.report();
+
+        if !report.is_satisfied() {
+            panic!("The mock port didn't get what it expected:\n{}", report);
+        }
+    }
+
+    /// Applies the rules to the data received, as long as one matches
+    fn apply_rules(&mut self) {
+        loop {
+            let found = {
+                let mut candidates = range(0, self.rules.len()).filter(|&i| {
+                    self.matched[i] < self.rules[i].times
+                });
+
+                match self.expectations {
+                    Strict => candidates.next().and_then(|i| {
+                        self.rules[i].trigger.find(self.received.as_slice()).map(|n| (i, n))
+                    }),
+                    Unordered => candidates.filter_map(|i| {
+                        self.rules[i].trigger.find(self.received.as_slice()).map(|n| (i, n))
+                    }).next(),
+                }
+            };
+
+            let (i, n) = match found {
+                None => return,
+                Some(found) => found,
+            };
+
+            self.matched[i] += 1;
+            self.received = self.received.slice_from(n).to_vec();
+
+            let rule = &self.rules[i];
+            let due = self.clock.now() + nanoseconds(rule.delay);
+            for &byte in rule.reply.iter() {
+                self.replies.push_back((due, byte));
+            }
+        }
+    }
+}
+
+/// Waits for `timeout` nanoseconds, then fails like a read that didn't get any data
+fn time_out(clock: &TimeSource, timeout: u64) -> IoResult<uint> {
+    clock.sleep(timeout);
+
+    Err(IoError {
+        kind: TimedOut,
+        desc: "Read operation timed out",
+        detail: None,
+    })
+}
+
+impl Reader for MockPort {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        let now = self.clock.now();
+        let wait = self.replies.front().map(|&(due, _)| if due > now { due - now } else { 0 });
+        let timeout = self.timeout.map(nanoseconds);
+
+        match (wait, timeout) {
+            (None, None) => return Err(IoError {
+                kind: OtherIoError,
+                desc: "Read while no reply is on its way",
+                detail: None,
+            }),
+            // Only a write can bring a reply
+            (None, Some(timeout)) => return time_out(&self.clock, timeout),
+            (Some(wait), Some(timeout)) if timeout < wait => return time_out(&self.clock, timeout),
+            (Some(wait), _) => self.clock.sleep(wait),
+        }
+
+        let now = self.clock.now();
+        let mut n = 0;
+        while n < buf.len() {
+            match self.replies.front() {
+                Some(&(due, byte)) if due <= now => buf[n] = byte,
+                _ => break,
+            }
+
+            self.replies.pop_front();
+            n += 1;
+        }
+
+        Ok(n)
+    }
+}
+
+impl SerialIo for MockPort {
+    fn settings(&self) -> IoResult<Settings> {
+        Ok(self.settings.clone())
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.settings = settings.clone();
+        Ok(())
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+impl Writer for MockPort {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        self.received.push_all(buf);
+        self.apply_rules();
+
+        Ok(())
+    }
+}
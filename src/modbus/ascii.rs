@@ -0,0 +1,103 @@
+//! ASCII framing: ':', unit address + PDU + LRC in hexadecimal, CR LF
+
+use checksum::{Checksum, Lrc};
+use hex;
+
+/// Largest ASCII frame, including the delimiters
+pub const MAX_FRAME_LEN: uint = 513;
+
+const HEX_DIGITS: &'static [u8] = b"0123456789ABCDEF";
+
+/// Encodes the `adu` (unit address + PDU) as an ASCII frame
+pub fn encode(adu: &[u8]) -> Vec<u8> {
+    let mut lrc = Lrc::new();
+    lrc.update(adu);
+
+    let lrc = [lrc.value()];
+
+    let mut frame = vec![b':'];
+    for &byte in adu.iter().chain(lrc.iter()) {
+        frame.push(HEX_DIGITS[(byte >> 4) as uint]);
+        frame.push(HEX_DIGITS[(byte & 0x0F) as uint]);
+    }
+    frame.push_all(b"\r\n");
+    frame
+}
+
+/// Decodes an ASCII frame terminated by CR LF, returns the ADU (unit address + PDU) it contains
+///
+/// Anything that precedes the last start of frame (':') is ignored. Returns `None` if the frame
+/// is malformed or its LRC doesn't match.
+pub fn decode(line: &[u8]) -> Option<Vec<u8>> {
+    let start = match line.iter().rposition(|&byte| byte == b':') {
+        None => return None,
+        Some(start) => start + 1,
+    };
+
+    if line.len() < start + 2 || !line.ends_with(b"\r\n") {
+        return None
+    }
+
+    let digits = line.slice(start, line.len() - 2);
+    if digits.len() % 2 != 0 || digits.len() < 6 {
+        return None
+    }
+
+    let mut bytes = Vec::with_capacity(digits.len() / 2);
+    for pair in digits.chunks(2) {
+        match (hex::value(pair[0]), hex::value(pair[1])) {
+            (Some(high), Some(low)) => bytes.push(high << 4 | low),
+            _ => return None,
+        }
+    }
+
+    let received = bytes.pop().unwrap();
+
+    let mut lrc = Lrc::new();
+    lrc.update(bytes.as_slice());
+
+    if lrc.value() == received { Some(bytes) } else { None }
+}
+
+/// Incrementally extracts the ADUs (unit address + PDU) of the ASCII frames in a byte stream
+///
+/// The stream can be fed in chunks split at arbitrary points. Any input is accepted: malformed
+/// frames and frames with a bad LRC are dropped, and a line end always resynchronizes the
+/// decoder.
+pub struct AsciiDecoder {
+    line: Vec<u8>,
+}
+
+impl AsciiDecoder {
+    pub fn new() -> AsciiDecoder {
+        AsciiDecoder { line: Vec::new() }
+    }
+
+    /// Feeds `data` to the decoder, returns the ADUs of the frames it completed
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut adus = Vec::new();
+
+        for &byte in data.iter() {
+            self.line.push(byte);
+
+            if byte == b'\n' {
+                match decode(self.line.as_slice()) {
+                    None => {},
+                    Some(adu) => adus.push(adu),
+                }
+
+                self.line.clear();
+            // Garbage without a frame end, don't let it accumulate
+            } else if self.line.len() > MAX_FRAME_LEN {
+                self.line.clear();
+            }
+        }
+
+        adus
+    }
+
+    /// Discards the partially received frame, if any
+    pub fn reset(&mut self) {
+        self.line.clear();
+    }
+}
@@ -0,0 +1,161 @@
+//! Modbus over serial lines
+
+pub use self::ascii::AsciiDecoder;
+pub use self::rtu::RtuDecoder;
+pub use self::slave::{Handler, Slave};
+
+mod ascii;
+mod rtu;
+mod slave;
+
+/// Largest number of coils/discrete inputs that can be read in one request
+const MAX_READ_BITS: u16 = 2000;
+/// Largest number of registers that can be read in one request
+const MAX_READ_REGISTERS: u16 = 125;
+/// Largest number of coils that can be written in one request
+const MAX_WRITE_BITS: u16 = 1968;
+/// Largest number of registers that can be written in one request
+const MAX_WRITE_REGISTERS: u16 = 123;
+
+/// Exception codes, reported to the master when a request can't be served
+#[deriving(Clone, PartialEq, Show)]
+pub enum Exception {
+    IllegalFunction = 0x01,
+    IllegalDataAddress = 0x02,
+    IllegalDataValue = 0x03,
+    SlaveDeviceFailure = 0x04,
+}
+
+/// Transmission mode of the serial line
+#[deriving(Clone, PartialEq, Show)]
+pub enum Mode {
+    /// Hexadecimal characters, delimited by ':' and CR LF, protected by an LRC
+    Ascii,
+    /// Binary frames, delimited by silent periods, protected by a CRC
+    Rtu,
+}
+
+/// Function codes
+#[deriving(Clone, FromPrimitive, PartialEq, Show)]
+pub enum Function {
+    ReadCoilsFunction = 0x01,
+    ReadDiscreteInputsFunction = 0x02,
+    ReadHoldingRegistersFunction = 0x03,
+    ReadInputRegistersFunction = 0x04,
+    WriteSingleCoilFunction = 0x05,
+    WriteSingleRegisterFunction = 0x06,
+    WriteMultipleCoilsFunction = 0x0F,
+    WriteMultipleRegistersFunction = 0x10,
+}
+
+/// A request, as found in the protocol data unit (PDU) sent by the master
+#[deriving(Clone, PartialEq, Show)]
+pub enum Request {
+    /// Starting address and number of coils
+    ReadCoils(u16, u16),
+    /// Starting address and number of inputs
+    ReadDiscreteInputs(u16, u16),
+    /// Starting address and number of registers
+    ReadHoldingRegisters(u16, u16),
+    /// Starting address and number of registers
+    ReadInputRegisters(u16, u16),
+    /// Address and value of the coil
+    WriteSingleCoil(u16, bool),
+    /// Address and value of the register
+    WriteSingleRegister(u16, u16),
+    /// Starting address and values of the coils
+    WriteMultipleCoils(u16, Vec<bool>),
+    /// Starting address and values of the registers
+    WriteMultipleRegisters(u16, Vec<u16>),
+}
+
+impl Request {
+    /// Parses the request contained in `pdu`
+    ///
+    /// Unknown function codes are reported as `IllegalFunction`, malformed requests as
+    /// `IllegalDataValue`
+    pub fn parse(pdu: &[u8]) -> Result<Request, Exception> {
+        if pdu.is_empty() {
+            return Err(IllegalFunction)
+        }
+
+        let function = match FromPrimitive::from_u8(pdu[0]) {
+            None => return Err(IllegalFunction),
+            Some(function) => function,
+        };
+
+        if pdu.len() < 5 {
+            return Err(IllegalDataValue)
+        }
+
+        let address = be_u16(pdu.slice(1, 3));
+        let value = be_u16(pdu.slice(3, 5));
+
+        match function {
+            ReadCoilsFunction | ReadDiscreteInputsFunction |
+            ReadHoldingRegistersFunction | ReadInputRegistersFunction => {
+                let max = match function {
+                    ReadCoilsFunction | ReadDiscreteInputsFunction => MAX_READ_BITS,
+                    _ => MAX_READ_REGISTERS,
+                };
+
+                if pdu.len() != 5 || value == 0 || value > max {
+                    return Err(IllegalDataValue)
+                }
+
+                Ok(match function {
+                    ReadCoilsFunction => ReadCoils(address, value),
+                    ReadDiscreteInputsFunction => ReadDiscreteInputs(address, value),
+                    ReadHoldingRegistersFunction => ReadHoldingRegisters(address, value),
+                    _ => ReadInputRegisters(address, value),
+                })
+            },
+            WriteSingleCoilFunction => match (pdu.len(), value) {
+                (5, 0xFF00) => Ok(WriteSingleCoil(address, true)),
+                (5, 0x0000) => Ok(WriteSingleCoil(address, false)),
+                _ => Err(IllegalDataValue),
+            },
+            WriteSingleRegisterFunction => if pdu.len() == 5 {
+                Ok(WriteSingleRegister(address, value))
+            } else {
+                Err(IllegalDataValue)
+            },
+            WriteMultipleCoilsFunction => {
+                let count = value as uint;
+
+                if value == 0 || value > MAX_WRITE_BITS || pdu.len() < 6 ||
+                        pdu[5] as uint != (count + 7) / 8 || pdu.len() != 6 + pdu[5] as uint {
+                    return Err(IllegalDataValue)
+                }
+
+                let bits = pdu.slice_from(6);
+                let coils = range(0, count).map(|i| bits[i / 8] & (1 << (i % 8)) != 0).collect();
+
+                Ok(WriteMultipleCoils(address, coils))
+            },
+            WriteMultipleRegistersFunction => {
+                let count = value as uint;
+
+                if value == 0 || value > MAX_WRITE_REGISTERS || pdu.len() < 6 ||
+                        pdu[5] as uint != 2 * count || pdu.len() != 6 + 2 * count {
+                    return Err(IllegalDataValue)
+                }
+
+                let registers = pdu.slice_from(6).chunks(2).map(|bytes| be_u16(bytes));
+
+                Ok(WriteMultipleRegisters(address, registers.collect()))
+            },
+        }
+    }
+}
+
+/// Reads a big endian `u16` from the first two bytes of `bytes`
+fn be_u16(bytes: &[u8]) -> u16 {
+    (bytes[0] as u16) << 8 | bytes[1] as u16
+}
+
+/// Appends `value` to `buf` in big endian order
+fn push_be_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.push((value >> 8) as u8);
+    buf.push(value as u8);
+}
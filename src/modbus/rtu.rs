@@ -0,0 +1,89 @@
+//! RTU framing: unit address, PDU and CRC-16/MODBUS (least significant byte first)
+
+use checksum::{Checksum, Crc16};
+
+/// Returns the length of the request frame that starts `frame`, if it can be determined yet
+pub fn request_len(frame: &[u8]) -> Option<uint> {
+    if frame.len() < 2 {
+        return None
+    }
+
+    match frame[1] {
+        0x01...0x06 => Some(8),
+        0x0F | 0x10 if frame.len() >= 7 => Some(9 + frame[6] as uint),
+        _ => None,
+    }
+}
+
+/// Appends the CRC to the `adu` (unit address + PDU)
+pub fn encode(adu: &[u8]) -> Vec<u8> {
+    let mut crc = Crc16::modbus();
+    crc.update(adu);
+
+    let mut frame = adu.to_vec();
+    frame.push_all(crc.bytes().as_slice());
+    frame
+}
+
+/// Validates the CRC of `frame`, returns the ADU (unit address + PDU) that it protects
+pub fn decode(frame: &[u8]) -> Option<&[u8]> {
+    if frame.len() < 4 {
+        return None
+    }
+
+    let (adu, received) = frame.split_at(frame.len() - 2);
+
+    let mut crc = Crc16::modbus();
+    crc.update(adu);
+
+    if crc.bytes().as_slice() == received { Some(adu) } else { None }
+}
+
+/// Incrementally extracts the ADUs (unit address + PDU) of the RTU requests in a byte stream
+///
+/// Without the silent periods of the line, frames are delimited by their length, so only the
+/// requests of the functions this crate serves are recognized. Any input is accepted: the bytes
+/// that don't start a well formed request are skipped one at a time, which resynchronizes the
+/// decoder on the next request.
+pub struct RtuDecoder {
+    buf: Vec<u8>,
+}
+
+impl RtuDecoder {
+    pub fn new() -> RtuDecoder {
+        RtuDecoder { buf: Vec::new() }
+    }
+
+    /// Feeds `data` to the decoder, returns the ADUs of the requests it completed
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut adus = Vec::new();
+        self.buf.push_all(data);
+
+        loop {
+            let skip = match request_len(self.buf.as_slice()) {
+                Some(len) if self.buf.len() >= len => match decode(self.buf.slice_to(len)) {
+                    None => 1,
+                    Some(adu) => {
+                        adus.push(adu.to_vec());
+                        len
+                    },
+                },
+                // Wait for the rest of the request
+                Some(_) => break,
+                None if self.buf.len() < 2 => break,
+                // The length of the multiple writes comes later
+                None if self.buf[1] == 0x0F || self.buf[1] == 0x10 => break,
+                None => 1,
+            };
+
+            self.buf = self.buf.slice_from(skip).to_vec();
+        }
+
+        adus
+    }
+
+    /// Discards the partially received request, if any
+    pub fn reset(&mut self) {
+        self.buf.clear();
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::RingBuf;
+use std::io::{IoResult, TimedOut};
+use std::mem;
+use std::time::Duration;
+
+use SerialIo;
+use modbus::{
+    Ascii, AsciiDecoder, Exception, IllegalFunction, Mode, ReadCoils, ReadDiscreteInputs,
+    ReadHoldingRegisters, ReadInputRegisters, Request, SlaveDeviceFailure, WriteMultipleCoils,
+    WriteMultipleRegisters, Rtu, WriteSingleCoil, WriteSingleRegister, ascii, push_be_u16, rtu,
+};
+
+/// Unit address used to broadcast requests to every slave
+const BROADCAST: u8 = 0;
+
+/// Callbacks that serve the requests received by a `Slave`
+///
+/// Every callback defaults to answering `IllegalFunction`, so a handler only needs to implement
+/// the functions it supports. Returning an exception sends it back to the master.
+#[allow(unused_variables)]
+pub trait Handler {
+    /// Returns the state of `count` coils, starting at `address`
+    fn read_coils(&mut self, address: u16, count: u16) -> Result<Vec<bool>, Exception> {
+        Err(IllegalFunction)
+    }
+
+    /// Returns the state of `count` discrete inputs, starting at `address`
+    fn read_discrete_inputs(&mut self, address: u16, count: u16) -> Result<Vec<bool>, Exception> {
+        Err(IllegalFunction)
+    }
+
+    /// Returns the value of `count` holding registers, starting at `address`
+    fn read_holding_registers(&mut self, address: u16, count: u16)
+                              -> Result<Vec<u16>, Exception> {
+        Err(IllegalFunction)
+    }
+
+    /// Returns the value of `count` input registers, starting at `address`
+    fn read_input_registers(&mut self, address: u16, count: u16) -> Result<Vec<u16>, Exception> {
+        Err(IllegalFunction)
+    }
+
+    /// Changes the state of the coils starting at `address`
+    ///
+    /// Used for both the single and the multiple coil write requests
+    fn write_coils(&mut self, address: u16, values: &[bool]) -> Result<(), Exception> {
+        Err(IllegalFunction)
+    }
+
+    /// Changes the value of the holding registers starting at `address`
+    ///
+    /// Used for both the single and the multiple register write requests
+    fn write_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        Err(IllegalFunction)
+    }
+}
+
+/// A Modbus slave (server)
+///
+/// The slave listens on a port, ignores the requests addressed to other units and the frames
+/// with a bad CRC, and dispatches the rest to its `Handler`. Broadcast requests are served, but
+/// never answered.
+///
+/// The slave takes over the timeout of the port. In RTU mode, frames are delimited by their
+/// length when the function is known, and otherwise by a silent period (the frame gap).
+pub struct Slave<S, H> {
+    port: S,
+    unit: u8,
+    handler: H,
+    mode: Mode,
+    gap: Duration,
+    buf: Vec<u8>,
+    ascii: AsciiDecoder,
+    adus: RingBuf<Vec<u8>>,
+}
+
+impl<S: SerialIo, H: Handler> Slave<S, H> {
+    /// Serves the requests sent to `unit` through `port`, in RTU mode
+    pub fn new(port: S, unit: u8, handler: H) -> Slave<S, H> {
+        Slave {
+            port: port,
+            unit: unit,
+            handler: handler,
+            mode: Rtu,
+            gap: Duration::milliseconds(20),
+            buf: Vec::new(),
+            ascii: AsciiDecoder::new(),
+            adus: RingBuf::new(),
+        }
+    }
+
+    /// Changes the silent period that ends a frame of unknown length, 20 ms by default
+    pub fn set_frame_gap(&mut self, gap: Duration) {
+        self.gap = gap;
+    }
+
+    /// Changes the transmission mode, discarding any partially received frame
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.buf.clear();
+        self.ascii.reset();
+        self.adus.clear();
+    }
+
+    /// Returns a reference to the handler
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Returns a mutable reference to the handler
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// Unwraps the port and the handler
+    pub fn into_inner(self) -> (S, H) {
+        (self.port, self.handler)
+    }
+
+    /// Serves requests until an I/O error occurs
+    pub fn run(&mut self) -> IoResult<()> {
+        loop {
+            try!(self.serve_one())
+        }
+    }
+
+    /// Waits for the next request addressed to this unit (or broadcast), and serves it
+    pub fn serve_one(&mut self) -> IoResult<()> {
+        loop {
+            let adu = try!(match self.mode {
+                Ascii => self.receive_ascii(),
+                Rtu => self.receive_rtu(),
+            });
+            let (unit, pdu) = (adu[0], adu.slice_from(1));
+
+            if unit != self.unit && unit != BROADCAST {
+                continue
+            }
+
+            let response = self.dispatch(pdu);
+
+            if unit == BROADCAST {
+                return Ok(())
+            }
+
+            let mut adu = vec![unit];
+            adu.push_all(response.as_slice());
+
+            let frame = match self.mode {
+                Ascii => ascii::encode(adu.as_slice()),
+                Rtu => rtu::encode(adu.as_slice()),
+            };
+
+            return self.port.write(frame.as_slice())
+        }
+    }
+
+    /// Runs the `pdu` request through the handler, returns the response PDU
+    fn dispatch(&mut self, pdu: &[u8]) -> Vec<u8> {
+        let function = pdu[0];
+
+        let response = Request::parse(pdu).and_then(|request| {
+            let mut response = vec![function];
+
+            match request {
+                ReadCoils(address, count) | ReadDiscreteInputs(address, count) => {
+                    let bits = try!(match request {
+                        ReadCoils(..) => self.handler.read_coils(address, count),
+                        _ => self.handler.read_discrete_inputs(address, count),
+                    });
+
+                    if bits.len() != count as uint {
+                        return Err(SlaveDeviceFailure)
+                    }
+
+                    let mut packed = Vec::from_elem((bits.len() + 7) / 8, 0u8);
+                    for (i, &bit) in bits.iter().enumerate() {
+                        if bit {
+                            packed.as_mut_slice()[i / 8] |= 1 << (i % 8);
+                        }
+                    }
+
+                    response.push(packed.len() as u8);
+                    response.push_all(packed.as_slice());
+                },
+                ReadHoldingRegisters(address, count) | ReadInputRegisters(address, count) => {
+                    let registers = try!(match request {
+                        ReadHoldingRegisters(..) => {
+                            self.handler.read_holding_registers(address, count)
+                        },
+                        _ => self.handler.read_input_registers(address, count),
+                    });
+
+                    if registers.len() != count as uint {
+                        return Err(SlaveDeviceFailure)
+                    }
+
+                    response.push(2 * registers.len() as u8);
+                    for &register in registers.iter() {
+                        push_be_u16(&mut response, register);
+                    }
+                },
+                WriteSingleCoil(address, value) => {
+                    try!(self.handler.write_coils(address, &[value]));
+                    response = pdu.to_vec();
+                },
+                WriteSingleRegister(address, value) => {
+                    try!(self.handler.write_registers(address, &[value]));
+                    response = pdu.to_vec();
+                },
+                WriteMultipleCoils(address, ref values) => {
+                    try!(self.handler.write_coils(address, values.as_slice()));
+                    response.push_all(pdu.slice(1, 5));
+                },
+                WriteMultipleRegisters(address, ref values) => {
+                    try!(self.handler.write_registers(address, values.as_slice()));
+                    response.push_all(pdu.slice(1, 5));
+                },
+            }
+
+            Ok(response)
+        });
+
+        match response {
+            Err(exception) => vec![function | 0x80, exception as u8],
+            Ok(response) => response,
+        }
+    }
+
+    /// Receives the next well formed ASCII frame, returns its ADU (unit address + PDU)
+    fn receive_ascii(&mut self) -> IoResult<Vec<u8>> {
+        self.port.set_timeout(None);
+
+        loop {
+            match self.adus.pop_front() {
+                None => {},
+                Some(adu) => return Ok(adu),
+            }
+
+            let mut chunk = [0u8, ..256];
+            let n = try!(self.port.read(&mut chunk));
+            self.adus.extend(self.ascii.feed(chunk.slice_to(n)).into_iter());
+        }
+    }
+
+    /// Receives the next well formed RTU frame, returns its ADU (unit address + PDU)
+    fn receive_rtu(&mut self) -> IoResult<Vec<u8>> {
+        loop {
+            let len = rtu::request_len(self.buf.as_slice());
+            match len {
+                Some(len) if self.buf.len() >= len => {
+                    let rest = self.buf.slice_from(len).to_vec();
+                    let frame = mem::replace(&mut self.buf, rest);
+
+                    match rtu::decode(frame.slice_to(len)) {
+                        // Corrupted frame, resynchronize on the next silent period
+                        None => self.buf.clear(),
+                        Some(adu) => return Ok(adu.to_vec()),
+                    }
+                },
+                _ => {},
+            }
+
+            let timeout = if self.buf.is_empty() { None } else { Some(self.gap) };
+            self.port.set_timeout(timeout);
+
+            let mut chunk = [0u8, ..256];
+            match self.port.read(&mut chunk) {
+                Err(ref e) if e.kind == TimedOut => {
+                    let frame = mem::replace(&mut self.buf, Vec::new());
+
+                    match rtu::decode(frame.as_slice()) {
+                        None => {},
+                        Some(adu) => return Ok(adu.to_vec()),
+                    }
+                },
+                Err(e) => return Err(e),
+                Ok(n) => self.buf.push_all(chunk.slice_to(n)),
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Logical channels multiplexed over one physical port
+//!
+//! Several independent byte streams (say a console, telemetry and a file transfer) share one
+//! UART by sending tagged frames. Each frame carries the channel number, up to
+//! `MAX_PAYLOAD` bytes of data and a CRC-16/CCITT, and is delimited with COBS, so a corrupted
+//! frame is dropped without desynchronizing the others.
+//!
+//! Both ends of the link run a `Mux`, and open the channels they use:
+//!
+//! ```ignore
+//! let mux = Mux::new(port);
+//! let mut console = mux.channel(0);
+//! let mut telemetry = mux.channel(1);
+//! try!(console.write_str("reboot\n"));
+//! ```
+
+use std::cmp;
+use std::collections::HashMap;
+use std::comm::{Disconnected, Empty};
+use std::io::{BrokenPipe, EndOfFile, IoError, IoResult, TimedOut};
+use std::slice::bytes;
+use std::time::Duration;
+
+use SerialIo;
+use checksum::Crc16;
+use framing::{Checked, Cobs, Decoder, Encoder};
+
+/// Largest amount of data carried by a frame, longer writes are split
+pub const MAX_PAYLOAD: uint = 255;
+
+/// How often the port is checked while waiting for data to send
+const POLL_INTERVAL_MS: i64 = 10;
+
+/// Requests from the handles to the task driving the port
+enum Command {
+    /// Data received for the channel goes to the sender
+    OpenChannel(u8, Sender<Vec<u8>>),
+    /// Data to send on the channel
+    Transmit(u8, Vec<u8>),
+}
+
+/// Shares a port between logical channels
+///
+/// The port is driven by a separate task, which lives until the `Mux` and all its channels are
+/// dropped, or until the port fails. Frames received for channels that aren't open are
+/// discarded.
+pub struct Mux {
+    commands: Sender<Command>,
+}
+
+impl Mux {
+    /// Takes over `port`, and its timeout
+    pub fn new<S: SerialIo + Send>(port: S) -> Mux {
+        let (tx, rx) = channel();
+
+        spawn(proc() {
+            let mut port = port;
+
+            match pump(&mut port, &rx) {
+                Err(e) => debug!("mux: stopped ({})", e),
+                Ok(()) => {},
+            }
+        });
+
+        Mux {
+            commands: tx,
+        }
+    }
+
+    /// Opens the channel `id`
+    ///
+    /// Opening a channel again replaces the previous handle as the receiver of its data.
+    pub fn channel(&self, id: u8) -> Channel {
+        let (tx, rx) = channel();
+
+        // If the task is gone, the handle will report it on its first read or write
+        let _ = self.commands.send_opt(OpenChannel(id, tx));
+
+        Channel {
+            id: id,
+            commands: self.commands.clone(),
+            data: rx,
+            pending: Vec::new(),
+        }
+    }
+}
+
+/// One logical channel of a `Mux`
+///
+/// Reads block until data is received on the channel, and return `EndOfFile` once the port
+/// failed. Writes fail with `BrokenPipe` in that case.
+pub struct Channel {
+    id: u8,
+    commands: Sender<Command>,
+    data: Receiver<Vec<u8>>,
+    /// Received data that didn't fit in the buffer of the previous read
+    pending: Vec<u8>,
+}
+
+impl Channel {
+    /// Returns the number of the channel
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+}
+
+impl Reader for Channel {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        while self.pending.is_empty() {
+            match self.data.recv_opt() {
+                Err(()) => return Err(IoError {
+                    kind: EndOfFile,
+                    desc: "The multiplexed port was closed",
+                    detail: None,
+                }),
+                Ok(data) => self.pending = data,
+            }
+        }
+
+        let n = cmp::min(buf.len(), self.pending.len());
+        bytes::copy_memory(buf, self.pending.slice_to(n));
+        self.pending = self.pending.slice_from(n).to_vec();
+
+        Ok(n)
+    }
+}
+
+impl Writer for Channel {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        for chunk in buf.chunks(MAX_PAYLOAD) {
+            match self.commands.send_opt(Transmit(self.id, chunk.to_vec())) {
+                Err(_) => return Err(IoError {
+                    kind: BrokenPipe,
+                    desc: "The multiplexed port was closed",
+                    detail: None,
+                }),
+                Ok(()) => {},
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Moves the frames between the port and the channels, until all the handles are dropped
+fn pump<S: SerialIo>(port: &mut S, commands: &Receiver<Command>) -> IoResult<()> {
+    let mut codec = Checked::new(Cobs::new(), Crc16::ccitt());
+    let mut channels: HashMap<u8, Sender<Vec<u8>>> = HashMap::new();
+    let mut buf = [0u8, ..256];
+
+    port.set_timeout(Some(Duration::milliseconds(POLL_INTERVAL_MS)));
+
+    loop {
+        loop {
+            match commands.try_recv() {
+                Err(Empty) => break,
+                Err(Disconnected) => return Ok(()),
+                Ok(OpenChannel(id, tx)) => {
+                    channels.insert(id, tx);
+                },
+                Ok(Transmit(id, data)) => {
+                    let mut frame = Vec::with_capacity(data.len() + 1);
+                    frame.push(id);
+                    frame.push_all(data.as_slice());
+
+                    let mut encoded = Vec::new();
+                    // Frames of COBS never fail to encode
+                    codec.encode(frame.as_slice(), &mut encoded).unwrap();
+                    try!(port.write(encoded.as_slice()));
+                },
+            }
+        }
+
+        let n = match port.read(&mut buf) {
+            Err(ref e) if e.kind == TimedOut => continue,
+            Err(e) => return Err(e),
+            Ok(n) => n,
+        };
+
+        for frame in codec.feed(buf.slice_to(n)).into_iter() {
+            let frame = match frame {
+                Err(e) => {
+                    debug!("mux: dropped a frame ({})", e);
+                    continue
+                },
+                Ok(ref frame) if frame.is_empty() => continue,
+                Ok(frame) => frame,
+            };
+
+            let id = frame[0];
+            let closed = match channels.get(&id) {
+                None => {
+                    debug!("mux: dropped a frame for channel {}, which isn't open", id);
+                    false
+                },
+                Some(tx) => tx.send_opt(frame.slice_from(1).to_vec()).is_err(),
+            };
+
+            if closed {
+                channels.remove(&id);
+            }
+        }
+    }
+}
@@ -0,0 +1,420 @@
+//! NMEA 0183 sentences, as sent by GPS receivers
+
+use std::io::{EndOfFile, InvalidInput, IoError, IoResult};
+use std::num;
+use std::str;
+
+use checksum::{Checksum, Xor};
+
+/// Longest line kept by `SentenceDecoder`, the standard allows 82 characters but some receivers
+/// exceed it
+pub const MAX_SENTENCE_LEN: uint = 256;
+
+/// Errors found while parsing a sentence
+#[deriving(Clone, PartialEq, Show)]
+pub enum NmeaError {
+    /// The checksum doesn't match the content of the sentence
+    BadChecksum,
+    /// The field with the given index (starting at 1, after the address) has an invalid value
+    InvalidField(uint),
+    /// The sentence doesn't follow the `$<address>,<fields>*<checksum>` structure
+    Malformed,
+    /// The sentence has less fields than its type requires
+    MissingFields,
+}
+
+/// Time of day, in UTC
+#[deriving(Clone, PartialEq, Show)]
+pub struct Time {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: f32,
+}
+
+/// Calendar date
+///
+/// Two digit years are mapped to 1980-2079
+#[deriving(Clone, PartialEq, Show)]
+pub struct Date {
+    pub day: u8,
+    pub month: u8,
+    pub year: u16,
+}
+
+/// Fix data (GGA)
+#[deriving(Clone, PartialEq, Show)]
+pub struct Gga {
+    pub time: Option<Time>,
+    /// Latitude in degrees, positive to the north
+    pub latitude: Option<f64>,
+    /// Longitude in degrees, positive to the east
+    pub longitude: Option<f64>,
+    /// Fix quality, 0 means no fix
+    pub fix_quality: u8,
+    pub satellites: Option<u8>,
+    /// Horizontal dilution of precision
+    pub hdop: Option<f32>,
+    /// Altitude above the mean sea level, in meters
+    pub altitude: Option<f32>,
+}
+
+/// Recommended minimum data (RMC)
+#[deriving(Clone, PartialEq, Show)]
+pub struct Rmc {
+    pub time: Option<Time>,
+    /// Whether the receiver considers the data valid
+    pub valid: bool,
+    /// Latitude in degrees, positive to the north
+    pub latitude: Option<f64>,
+    /// Longitude in degrees, positive to the east
+    pub longitude: Option<f64>,
+    /// Speed over ground, in knots
+    pub speed: Option<f32>,
+    /// Track angle, in degrees from true north
+    pub course: Option<f32>,
+    pub date: Option<Date>,
+    /// Magnetic variation in degrees, positive to the east
+    pub magnetic_variation: Option<f32>,
+}
+
+/// Satellite reported by a GSV sentence
+#[deriving(Clone, PartialEq, Show)]
+pub struct Satellite {
+    pub prn: u8,
+    /// Elevation in degrees
+    pub elevation: Option<u8>,
+    /// Azimuth in degrees from true north
+    pub azimuth: Option<u16>,
+    /// Signal to noise ratio in dB, `None` when not tracking
+    pub snr: Option<u8>,
+}
+
+/// Satellites in view (GSV), the full list is spread over several sentences
+#[deriving(Clone, PartialEq, Show)]
+pub struct Gsv {
+    pub total_messages: u8,
+    pub message_number: u8,
+    pub satellites_in_view: u8,
+    pub satellites: Vec<Satellite>,
+}
+
+/// A sentence with a valid checksum, split into its fields
+#[deriving(Clone, PartialEq, Show)]
+pub struct Raw {
+    /// Talker identifier, like "GP" or "GN", or "P" for proprietary sentences
+    pub talker: String,
+    /// Sentence type, like "GGA"
+    pub kind: String,
+    pub fields: Vec<String>,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum Sentence {
+    GgaSentence(Gga),
+    GsvSentence(Gsv),
+    RmcSentence(Rmc),
+    /// Sentence of a type that isn't parsed by this module
+    UnknownSentence(Raw),
+}
+
+impl Sentence {
+    /// Parses a `line`, with or without the trailing CR LF
+    pub fn parse(line: &str) -> Result<Sentence, NmeaError> {
+        let raw = try!(Raw::parse(line));
+        let fields: Vec<&str> = raw.fields.iter().map(|field| field.as_slice()).collect();
+        let fields = fields.as_slice();
+
+        match raw.kind.as_slice() {
+            "GGA" => {
+                try!(expect(fields, 9));
+
+                Ok(GgaSentence(Gga {
+                    time: try!(time(fields, 0)),
+                    latitude: try!(coordinate(fields, 1, "N", "S")),
+                    longitude: try!(coordinate(fields, 3, "E", "W")),
+                    fix_quality: try!(number(fields, 5)).unwrap_or(0),
+                    satellites: try!(number(fields, 6)),
+                    hdop: try!(number(fields, 7)),
+                    altitude: try!(number(fields, 8)),
+                }))
+            },
+            "GSV" => {
+                try!(expect(fields, 3));
+
+                let mut satellites = Vec::new();
+                for i in range(0, (fields.len() - 3) / 4) {
+                    let base = 3 + 4 * i;
+
+                    match try!(number(fields, base)) {
+                        None => {},
+                        Some(prn) => satellites.push(Satellite {
+                            prn: prn,
+                            elevation: try!(number(fields, base + 1)),
+                            azimuth: try!(number(fields, base + 2)),
+                            snr: try!(number(fields, base + 3)),
+                        }),
+                    }
+                }
+
+                Ok(GsvSentence(Gsv {
+                    total_messages: try!(required(fields, 0)),
+                    message_number: try!(required(fields, 1)),
+                    satellites_in_view: try!(required(fields, 2)),
+                    satellites: satellites,
+                }))
+            },
+            "RMC" => {
+                try!(expect(fields, 9));
+
+                let variation = try!(number::<f32>(fields, 9)).map(|variation| {
+                    if field(fields, 10) == Some("W") { -variation } else { variation }
+                });
+
+                Ok(RmcSentence(Rmc {
+                    time: try!(time(fields, 0)),
+                    valid: fields[1] == "A",
+                    latitude: try!(coordinate(fields, 2, "N", "S")),
+                    longitude: try!(coordinate(fields, 4, "E", "W")),
+                    speed: try!(number(fields, 6)),
+                    course: try!(number(fields, 7)),
+                    date: try!(date(fields, 8)),
+                    magnetic_variation: variation,
+                }))
+            },
+            _ => Ok(UnknownSentence(raw.clone())),
+        }
+    }
+}
+
+impl Raw {
+    /// Validates the structure and the checksum of `line`, and splits it into fields
+    pub fn parse(line: &str) -> Result<Raw, NmeaError> {
+        let line = line.trim_right_chars(['\r', '\n'].as_slice());
+
+        if !line.starts_with("$") {
+            return Err(Malformed)
+        }
+
+        let (body, checksum) = match line.find('*') {
+            None => return Err(Malformed),
+            Some(star) => (line.slice(1, star), line.slice_from(star + 1)),
+        };
+
+        let expected = match num::from_str_radix::<u8>(checksum, 16) {
+            Some(expected) if checksum.len() == 2 => expected,
+            _ => return Err(Malformed),
+        };
+
+        let mut xor = Xor::new();
+        xor.update(body.as_bytes());
+
+        if xor.value() != expected {
+            return Err(BadChecksum)
+        }
+
+        let mut fields = body.split(',');
+        let address = fields.next().unwrap();
+
+        // Slicing the address and the fields at fixed offsets needs ASCII
+        if !body.bytes().all(|byte| byte < 0x80) {
+            return Err(Malformed)
+        }
+
+        let (talker, kind) = if address.starts_with("P") {
+            (address.slice_to(1), address.slice_from(1))
+        } else if address.len() == 5 {
+            (address.slice_to(2), address.slice_from(2))
+        } else {
+            return Err(Malformed)
+        };
+
+        Ok(Raw {
+            talker: talker.to_string(),
+            kind: kind.to_string(),
+            fields: fields.map(|field| field.to_string()).collect(),
+        })
+    }
+}
+
+/// Incrementally extracts the sentences from a byte stream
+///
+/// The stream can be fed in chunks split at arbitrary points. Any input is accepted: the bytes
+/// between sentences are skipped, and a `$` or a line end always resynchronizes the decoder.
+pub struct SentenceDecoder {
+    line: Vec<u8>,
+}
+
+impl SentenceDecoder {
+    pub fn new() -> SentenceDecoder {
+        SentenceDecoder { line: Vec::new() }
+    }
+
+    /// Feeds `data` to the decoder, returns the sentences it completed
+    ///
+    /// A sentence interrupted by the start of another one, or longer than `MAX_SENTENCE_LEN`, is
+    /// reported as `Malformed`.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Result<Sentence, NmeaError>> {
+        let mut sentences = Vec::new();
+
+        for &byte in data.iter() {
+            match byte {
+                b'$' => {
+                    if !self.line.is_empty() {
+                        sentences.push(Err(Malformed));
+                    }
+
+                    self.line.clear();
+                    self.line.push(byte);
+                },
+                b'\r' | b'\n' => if !self.line.is_empty() {
+                    sentences.push(match str::from_utf8(self.line.as_slice()) {
+                        None => Err(Malformed),
+                        Some(line) => Sentence::parse(line),
+                    });
+
+                    self.line.clear();
+                },
+                // Noise between sentences
+                _ if self.line.is_empty() => {},
+                _ if self.line.len() == MAX_SENTENCE_LEN => {
+                    sentences.push(Err(Malformed));
+                    self.line.clear();
+                },
+                _ => self.line.push(byte),
+            }
+        }
+
+        sentences
+    }
+
+    /// Discards the partially received sentence, if any
+    pub fn reset(&mut self) {
+        self.line.clear();
+    }
+}
+
+/// Iterator over the sentences read from a buffered port
+///
+/// Sentences that can't be parsed are reported as `InvalidInput` errors, the iteration can
+/// continue afterwards. The iteration ends when the port reaches EOF.
+pub struct Sentences<R> {
+    inner: R,
+}
+
+impl<R: Buffer> Sentences<R> {
+    /// Reads the sentences from `inner`, usually a `BufferedSerialPort`
+    pub fn new(inner: R) -> Sentences<R> {
+        Sentences { inner: inner }
+    }
+
+    /// Unwraps the underlying reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Buffer> Iterator<IoResult<Sentence>> for Sentences<R> {
+    fn next(&mut self) -> Option<IoResult<Sentence>> {
+        match self.inner.read_line() {
+            Err(ref e) if e.kind == EndOfFile => None,
+            Err(e) => Some(Err(e)),
+            Ok(line) => Some(Sentence::parse(line.as_slice()).map_err(|e| IoError {
+                kind: InvalidInput,
+                desc: "Invalid NMEA sentence",
+                detail: Some(format!("{}: {}", e, line.trim())),
+            })),
+        }
+    }
+}
+
+/// Checks that there are at least `n` fields
+fn expect(fields: &[&str], n: uint) -> Result<(), NmeaError> {
+    if fields.len() < n { Err(MissingFields) } else { Ok(()) }
+}
+
+/// Returns the `i`-th field, an empty or missing field maps to `None`
+fn field<'a>(fields: &[&'a str], i: uint) -> Option<&'a str> {
+    match fields.get(i) {
+        Some(field) if !field.is_empty() => Some(*field),
+        _ => None,
+    }
+}
+
+/// Parses the `i`-th field as a number, an empty or missing field maps to `None`
+fn number<T: FromStr>(fields: &[&str], i: uint) -> Result<Option<T>, NmeaError> {
+    match field(fields, i) {
+        None => Ok(None),
+        Some(field) => match from_str(field) {
+            None => Err(InvalidField(i + 1)),
+            Some(value) => Ok(Some(value)),
+        },
+    }
+}
+
+/// Parses the `i`-th field as a number, which must be present
+fn required<T: FromStr>(fields: &[&str], i: uint) -> Result<T, NmeaError> {
+    match try!(number(fields, i)) {
+        None => Err(InvalidField(i + 1)),
+        Some(value) => Ok(value),
+    }
+}
+
+/// Parses a `ddmm.mmmm` coordinate followed by its hemisphere, into signed degrees
+fn coordinate(fields: &[&str], i: uint, positive: &str, negative: &str)
+              -> Result<Option<f64>, NmeaError> {
+    let value = match try!(number::<f64>(fields, i)) {
+        None => return Ok(None),
+        Some(value) => value,
+    };
+
+    let degrees = (value / 100.0) as i64 as f64;
+    let degrees = degrees + (value - degrees * 100.0) / 60.0;
+
+    match field(fields, i + 1) {
+        Some(hemisphere) if hemisphere == positive => Ok(Some(degrees)),
+        Some(hemisphere) if hemisphere == negative => Ok(Some(-degrees)),
+        _ => Err(InvalidField(i + 2)),
+    }
+}
+
+/// Parses a `hhmmss.ss` time
+fn time(fields: &[&str], i: uint) -> Result<Option<Time>, NmeaError> {
+    let field = match field(fields, i) {
+        None => return Ok(None),
+        Some(field) if field.len() >= 6 => field,
+        Some(_) => return Err(InvalidField(i + 1)),
+    };
+
+    let hour = from_str(field.slice(0, 2));
+    let minute = from_str(field.slice(2, 4));
+    let second = from_str(field.slice_from(4));
+
+    match (hour, minute, second) {
+        (Some(hour), Some(minute), Some(second)) => {
+            Ok(Some(Time { hour: hour, minute: minute, second: second }))
+        },
+        _ => Err(InvalidField(i + 1)),
+    }
+}
+
+/// Parses a `ddmmyy` date
+fn date(fields: &[&str], i: uint) -> Result<Option<Date>, NmeaError> {
+    let field = match field(fields, i) {
+        None => return Ok(None),
+        Some(field) if field.len() == 6 => field,
+        Some(_) => return Err(InvalidField(i + 1)),
+    };
+
+    let day = from_str(field.slice(0, 2));
+    let month = from_str(field.slice(2, 4));
+    let year = from_str::<u16>(field.slice(4, 6));
+
+    match (day, month, year) {
+        (Some(day), Some(month), Some(year)) => Ok(Some(Date {
+            day: day,
+            month: month,
+            year: if year < 80 { 2000 + year } else { 1900 + year },
+        })),
+        _ => Err(InvalidField(i + 1)),
+    }
+}
@@ -0,0 +1,341 @@
+//! OBD-II through ELM327 adapters
+//!
+//! The ELM327 speaks AT commands, but ends every response with a `>` prompt instead of a final
+//! result. OBD requests are sent as hexadecimal (`010C` is mode 01, PID 0x0C), and the adapter
+//! prints the responses of the ECUs in hexadecimal as well.
+
+use std::io::{InvalidInput, IoError, IoResult, OtherIoError};
+use std::time::Duration;
+
+use at::Session;
+use hex;
+use SerialIo;
+
+/// Mode 01, current powertrain data
+pub const CURRENT_DATA: u8 = 0x01;
+/// Mode 09, vehicle information
+pub const VEHICLE_INFO: u8 = 0x09;
+
+/// PID of the Vehicle Identification Number, in mode 09
+const VIN: u8 = 0x02;
+
+/// Answers of the adapter that mean the request failed
+const FAILURES: &'static [&'static str] = &[
+    "?", "BUFFER FULL", "NO DATA", "STOPPED", "UNABLE TO CONNECT",
+];
+
+/// Decoded value of a mode 01 PID
+#[deriving(Clone, PartialEq, Show)]
+pub enum Value {
+    /// PIDs `0x00`, `0x20`, ...: the PIDs of the next range that are supported
+    SupportedPids(Vec<u8>),
+    /// PID `0x04`, in %
+    EngineLoad(f64),
+    /// PID `0x05`, in °C
+    CoolantTemperature(i16),
+    /// PID `0x0B`, in kPa
+    IntakePressure(u8),
+    /// PID `0x0C`, in rpm
+    EngineSpeed(f64),
+    /// PID `0x0D`, in km/h
+    VehicleSpeed(u8),
+    /// PID `0x0E`, in ° before top dead center
+    TimingAdvance(f64),
+    /// PID `0x0F`, in °C
+    IntakeTemperature(i16),
+    /// PID `0x10`, in g/s
+    MafRate(f64),
+    /// PID `0x11`, in %
+    ThrottlePosition(f64),
+    /// PID `0x1F`, in seconds
+    RunTime(u16),
+    /// PID `0x2F`, in %
+    FuelLevel(f64),
+    /// PID `0x46`, in °C
+    AmbientTemperature(i16),
+}
+
+/// Decodes the `data` of the mode 01 `pid`, `None` if the PID isn't supported or the data is
+/// too short
+pub fn decode(pid: u8, data: &[u8]) -> Option<Value> {
+    let len = match pid {
+        _ if pid % 0x20 == 0 => 4,
+        0x0C | 0x10 | 0x1F => 2,
+        0x04 | 0x05 | 0x0B | 0x0D | 0x0E | 0x0F | 0x11 | 0x2F | 0x46 => 1,
+        _ => return None,
+    };
+
+    if data.len() < len {
+        return None
+    }
+
+    let (a, b) = (data[0], if len >= 2 { data[1] } else { 0 });
+    let word = (a as u16) << 8 | b as u16;
+    let percent = a as f64 * 100. / 255.;
+
+    Some(match pid {
+        0x04 => EngineLoad(percent),
+        0x05 => CoolantTemperature(a as i16 - 40),
+        0x0B => IntakePressure(a),
+        0x0C => EngineSpeed(word as f64 / 4.),
+        0x0D => VehicleSpeed(a),
+        0x0E => TimingAdvance(a as f64 / 2. - 64.),
+        0x0F => IntakeTemperature(a as i16 - 40),
+        0x10 => MafRate(word as f64 / 100.),
+        0x11 => ThrottlePosition(percent),
+        0x1F => RunTime(word),
+        0x2F => FuelLevel(percent),
+        0x46 => AmbientTemperature(a as i16 - 40),
+        _ => {
+            let bits = data.iter().take(4).fold(0u32, |bits, &byte| bits << 8 | byte as u32);
+
+            SupportedPids(range(0, 32u).filter(|&i| bits & (0x80000000 >> i) != 0).map(|i| {
+                pid as uint + i + 1
+            }).filter(|&pid| pid <= 0xFF).map(|pid| pid as u8).collect())
+        },
+    })
+}
+
+/// Parses the lines printed by the adapter in response to an OBD request, returns the response
+/// of each ECU
+///
+/// Multi-frame (ISO-TP) responses, printed as their length followed by lines numbered `0:`,
+/// `1:`, ..., are reassembled. Failures (`NO DATA`, `CAN ERROR`, ...) are reported as
+/// `OtherIoError`s, and garbled lines as `InvalidInput` errors.
+pub fn parse_response(lines: &[String]) -> IoResult<Vec<Vec<u8>>> {
+    let mut responses = Vec::new();
+    // Multi-frame response being reassembled, with its announced length
+    let mut message: Option<(uint, Vec<u8>)> = None;
+
+    for line in lines.iter() {
+        let line = line.as_slice();
+
+        if FAILURES.contains(&line) || line.contains("ERROR") {
+            return Err(IoError {
+                kind: OtherIoError,
+                desc: "OBD request failed",
+                detail: Some(line.to_string()),
+            })
+        } else if line.starts_with("SEARCHING") || line.starts_with("BUS INIT") {
+            continue
+        }
+
+        match line.find(':') {
+            None => {},
+            Some(colon) => match message {
+                None => return Err(garbled(line)),
+                Some((_, ref mut data)) => {
+                    match parse_hex(line.slice_from(colon + 1)) {
+                        None => return Err(garbled(line)),
+                        Some(bytes) => data.push_all(bytes.as_slice()),
+                    }
+
+                    continue
+                },
+            },
+        }
+
+        match message.take() {
+            None => {},
+            Some((len, data)) => responses.push(try!(reassemble(len, data))),
+        }
+
+        // An odd number of digits can't be data, it's the length of a multi-frame response
+        if line.len() == 3 {
+            match parse_hex(format!("0{}", line).as_slice()) {
+                None => return Err(garbled(line)),
+                Some(len) => message = Some(((len[0] as uint) << 8 | len[1] as uint, vec![])),
+            }
+        } else {
+            match parse_hex(line) {
+                None => return Err(garbled(line)),
+                Some(bytes) => responses.push(bytes),
+            }
+        }
+    }
+
+    match message {
+        None => {},
+        Some((len, data)) => responses.push(try!(reassemble(len, data))),
+    }
+
+    Ok(responses)
+}
+
+/// An ELM327 adapter, driven through an AT session
+pub struct Elm327<S> {
+    session: Session<S>,
+    timeout: Duration,
+}
+
+impl<S: SerialIo> Elm327<S> {
+    /// Drives the adapter through `session`, see `init` to set it up
+    pub fn new(session: Session<S>) -> Elm327<S> {
+        Elm327 {
+            session: session,
+            timeout: Duration::seconds(5),
+        }
+    }
+
+    /// Returns a mutable reference to the session
+    pub fn get_mut(&mut self) -> &mut Session<S> {
+        &mut self.session
+    }
+
+    /// Unwraps the session
+    pub fn into_inner(self) -> Session<S> {
+        self.session
+    }
+
+    /// Changes how long to wait for the response to a command, 5 seconds by default
+    ///
+    /// The first request after `init` can take several seconds, while the adapter detects the
+    /// protocol of the vehicle.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Resets the adapter and selects the automatic protocol detection, returns the version of
+    /// the adapter
+    ///
+    /// Echo, line feeds and headers are turned off, the parsing in this module expects it.
+    pub fn init(&mut self) -> IoResult<String> {
+        let version = try!(self.session.command_to_prompt("ATZ", self.timeout));
+
+        for &command in ["ATE0", "ATL0", "ATH0", "ATSP0"].iter() {
+            try!(self.setting(command));
+        }
+
+        Ok(version.connect(" "))
+    }
+
+    /// Returns the description of the protocol in use, like `ISO 15765-4 (CAN 11/500)`
+    ///
+    /// Right after `init` the protocol is still unknown, make a request first to trigger its
+    /// detection.
+    pub fn protocol(&mut self) -> IoResult<String> {
+        let lines = try!(self.session.command_to_prompt("ATDP", self.timeout));
+
+        Ok(lines.connect(" "))
+    }
+
+    /// Reads and decodes the mode 01 `pid`, from the first ECU that answers
+    pub fn read(&mut self, pid: u8) -> IoResult<Value> {
+        let responses = try!(self.request(CURRENT_DATA, pid));
+
+        match decode(pid, responses[0].as_slice()) {
+            None => Err(IoError {
+                kind: InvalidInput,
+                desc: "PID can't be decoded",
+                detail: Some(format!("PID {:02X}: {}", pid, responses[0])),
+            }),
+            Some(value) => Ok(value),
+        }
+    }
+
+    /// Sends a `mode` request for `pid`, returns the data of each ECU that answered
+    ///
+    /// The mode and PID that start every response are stripped.
+    pub fn request(&mut self, mode: u8, pid: u8) -> IoResult<Vec<Vec<u8>>> {
+        let command = format!("{:02X}{:02X}", mode, pid);
+        let lines = try!(self.session.command_to_prompt(command.as_slice(), self.timeout));
+
+        let responses: Vec<Vec<u8>> = try!(parse_response(lines.as_slice())).iter().filter(|r| {
+            r.len() >= 2 && r[0] == mode | 0x40 && r[1] == pid
+        }).map(|response| response.slice_from(2).to_vec()).collect();
+
+        if responses.is_empty() {
+            Err(IoError {
+                kind: OtherIoError,
+                desc: "No ECU answered the OBD request",
+                detail: Some(lines.connect(" ")),
+            })
+        } else {
+            Ok(responses)
+        }
+    }
+
+    /// Returns the mode 01 PIDs supported by the first ECU that answers
+    pub fn supported_pids(&mut self) -> IoResult<Vec<u8>> {
+        let mut pids = Vec::new();
+        let mut base = 0u8;
+
+        loop {
+            match try!(self.read(base)) {
+                SupportedPids(supported) => pids.push_all(supported.as_slice()),
+                _ => unreachable!(),
+            }
+
+            if base == 0xE0 || !pids.contains(&(base + 0x20)) {
+                return Ok(pids)
+            }
+
+            base += 0x20;
+        }
+    }
+
+    /// Reads the Vehicle Identification Number
+    pub fn vin(&mut self) -> IoResult<String> {
+        let responses = try!(self.request(VEHICLE_INFO, VIN));
+
+        // The first byte is the number of data items
+        let vin = responses[0].iter().skip(1).map(|&byte| byte as char).filter(|&c| c != '\0');
+
+        Ok(vin.collect())
+    }
+
+    /// Sends a setting command, which the adapter must answer with `OK`
+    fn setting(&mut self, command: &str) -> IoResult<()> {
+        let lines = try!(self.session.command_to_prompt(command, self.timeout));
+
+        if lines.iter().any(|line| line.as_slice() == "OK") {
+            Ok(())
+        } else {
+            Err(IoError {
+                kind: OtherIoError,
+                desc: "ELM327 command failed",
+                detail: Some(format!("{}: {}", command, lines.connect(" "))),
+            })
+        }
+    }
+}
+
+fn garbled(line: &str) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: "Garbled OBD response",
+        detail: Some(line.to_string()),
+    }
+}
+
+/// Parses hexadecimal bytes, spaces between the bytes are allowed
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text.bytes().filter(|&byte| byte != b' ').collect();
+    if digits.len() % 2 != 0 || digits.is_empty() {
+        return None
+    }
+
+    let mut bytes = Vec::with_capacity(digits.len() / 2);
+    for pair in digits.as_slice().chunks(2) {
+        match (hex::value(pair[0]), hex::value(pair[1])) {
+            (Some(high), Some(low)) => bytes.push(high << 4 | low),
+            _ => return None,
+        }
+    }
+
+    Some(bytes)
+}
+
+/// Truncates the `data` of a multi-frame response to its announced `len`
+fn reassemble(len: uint, mut data: Vec<u8>) -> IoResult<Vec<u8>> {
+    if data.len() < len {
+        return Err(IoError {
+            kind: InvalidInput,
+            desc: "Incomplete multi-frame OBD response",
+            detail: Some(format!("{} of {} bytes", data.len(), len)),
+        })
+    }
+
+    data.truncate(len);
+    Ok(data)
+}
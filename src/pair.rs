@@ -0,0 +1,225 @@
+use std::cmp;
+use std::collections::RingBuf;
+use std::comm::{Disconnected, Empty};
+use std::default::Default;
+use std::io::{BrokenPipe, EndOfFile, IoError, IoResult, TimedOut};
+use std::io::timer;
+use std::time::Duration;
+use time;
+
+use {SerialIo, Settings};
+use {Data5, Data6, Data7, Data8, NoParity, Stop1, Stop2};
+
+/// How often a read with a timeout checks for data
+const POLL_INTERVAL_MS: i64 = 1;
+
+/// Data in flight: the time its transmission starts, the transmission time of each byte, and
+/// the bytes
+type Chunk = (u64, u64, Vec<u8>);
+
+/// One end of an in-memory serial link, see `virtual_pair`
+pub struct VirtualPort {
+    tx: Sender<Chunk>,
+    rx: Receiver<Chunk>,
+    /// Received bytes, with the time at which they become readable
+    pending: RingBuf<(u64, u8)>,
+    /// The peer was dropped
+    closed: bool,
+    settings: Settings,
+    timeout: Option<Duration>,
+    /// End of the transmission of the last written byte, in `precise_time_ns` units
+    busy_until: u64,
+}
+
+/// Returns two ports connected to each other, like a null modem cable
+///
+/// What's written to one end is read from the other, no faster than the baud rate and the
+/// frame format configured on the writing end allow (10 bits per byte at the default 9600
+/// baud 8N1, and no pacing at all with `B0`). Writes don't block, `flush` waits for the end of
+/// the transmission. Once an end is dropped, the other reads `EndOfFile` and its writes fail
+/// with `BrokenPipe`.
+///
+/// This lets a master and a slave be tested in the same process, without ptys.
+pub fn virtual_pair() -> (VirtualPort, VirtualPort) {
+    let (first_tx, second_rx) = channel();
+    let (second_tx, first_rx) = channel();
+
+    (VirtualPort::new(first_tx, first_rx), VirtualPort::new(second_tx, second_rx))
+}
+
+impl VirtualPort {
+    fn new(tx: Sender<Chunk>, rx: Receiver<Chunk>) -> VirtualPort {
+        VirtualPort {
+            tx: tx,
+            rx: rx,
+            pending: RingBuf::new(),
+            closed: false,
+            settings: Default::default(),
+            timeout: None,
+            busy_until: 0,
+        }
+    }
+
+    /// Transmission time of a byte with the current settings, in nanoseconds
+    fn byte_ns(&self) -> u64 {
+        let rate = self.settings.baud_rate.bits_per_second() as u64;
+        if rate == 0 {
+            return 0
+        }
+
+        let data_bits = match self.settings.data_bits {
+            Data5 => 5,
+            Data6 => 6,
+            Data7 => 7,
+            Data8 => 8,
+        };
+        let parity_bits = if self.settings.parity == NoParity { 0 } else { 1 };
+        let stop_bits = match self.settings.stop_bits {
+            Stop1 => 1,
+            Stop2 => 2,
+        };
+
+        // The start bit comes on top of the others
+        (1 + data_bits + parity_bits + stop_bits) * 1_000_000_000 / rate
+    }
+
+    /// Queues the bytes of a received chunk
+    fn enqueue(&mut self, (start, byte_ns, data): Chunk) {
+        for (i, &byte) in data.iter().enumerate() {
+            self.pending.push_back((start + (i as u64 + 1) * byte_ns, byte));
+        }
+    }
+
+    /// Queues everything received so far, without blocking
+    fn receive(&mut self) {
+        loop {
+            let chunk = match self.rx.try_recv() {
+                Err(Empty) => return,
+                Err(Disconnected) => {
+                    self.closed = true;
+                    return
+                },
+                Ok(chunk) => chunk,
+            };
+
+            self.enqueue(chunk);
+        }
+    }
+}
+
+impl Reader for VirtualPort {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        let deadline = self.timeout.map(|timeout| {
+            time::precise_time_ns() + cmp::max(timeout.num_nanoseconds().unwrap_or(0), 0) as u64
+        });
+
+        loop {
+            self.receive();
+
+            let now = time::precise_time_ns();
+            let due = match self.pending.front() {
+                Some(&(due, _)) => Some(due),
+                None if self.closed => {
+                    return Err(IoError {
+                        kind: EndOfFile,
+                        desc: "The other end of the link was dropped",
+                        detail: None,
+                    })
+                },
+                None => None,
+            };
+
+            match (due, deadline) {
+                (Some(due), _) if due <= now => break,
+                (_, Some(deadline)) if deadline <= now => {
+                    return Err(IoError {
+                        kind: TimedOut,
+                        desc: "Read operation timed out",
+                        detail: None,
+                    })
+                },
+                (Some(due), deadline) => {
+                    let until = deadline.map_or(due, |deadline| cmp::min(due, deadline));
+                    timer::sleep(Duration::nanoseconds((until - now) as i64));
+                },
+                (None, Some(_)) => timer::sleep(Duration::milliseconds(POLL_INTERVAL_MS)),
+                (None, None) => match self.rx.recv_opt() {
+                    Err(()) => self.closed = true,
+                    Ok(chunk) => self.enqueue(chunk),
+                },
+            }
+        }
+
+        let now = time::precise_time_ns();
+        let mut n = 0;
+        while n < buf.len() {
+            match self.pending.front() {
+                Some(&(due, byte)) if due <= now => buf[n] = byte,
+                _ => break,
+            }
+
+            self.pending.pop_front();
+            n += 1;
+        }
+
+        Ok(n)
+    }
+}
+
+impl SerialIo for VirtualPort {
+    fn settings(&self) -> IoResult<Settings> {
+        Ok(self.settings.clone())
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.settings = settings.clone();
+
+        Ok(())
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+impl Writer for VirtualPort {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        if buf.is_empty() {
+            return Ok(())
+        }
+
+        let byte_ns = self.byte_ns();
+        let start = cmp::max(time::precise_time_ns(), self.busy_until);
+
+        match self.tx.send_opt((start, byte_ns, buf.to_vec())) {
+            Err(_) => Err(IoError {
+                kind: BrokenPipe,
+                desc: "The other end of the link was dropped",
+                detail: None,
+            }),
+            Ok(()) => {
+                self.busy_until = start + buf.len() as u64 * byte_ns;
+
+                Ok(())
+            },
+        }
+    }
+
+    /// Waits until the written data is transmitted
+    fn flush(&mut self) -> IoResult<()> {
+        let now = time::precise_time_ns();
+        if self.busy_until > now {
+            timer::sleep(Duration::nanoseconds((self.busy_until - now) as i64));
+        }
+
+        Ok(())
+    }
+}
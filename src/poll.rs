@@ -0,0 +1,68 @@
+use libc::{c_int, c_short};
+use std::cmp;
+use std::i32;
+use std::io::{IoError, IoResult};
+use std::time::Duration;
+
+use termios::FAILURE;
+
+#[cfg(target_os = "linux")]
+#[allow(non_camel_case_types)]
+type nfds_t = ::libc::c_ulong;
+
+#[cfg(target_os = "macos")]
+#[allow(non_camel_case_types)]
+type nfds_t = ::libc::c_uint;
+
+pub const POLLIN: c_short = 0x0001;
+pub const POLLOUT: c_short = 0x0004;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct pollfd {
+    pub fd: c_int,
+    pub events: c_short,
+    pub revents: c_short,
+}
+
+#[link(name = "c")]
+extern {
+    pub fn poll(fds: *mut pollfd, nfds: nfds_t, timeout: c_int) -> c_int;
+}
+
+/// Converts a `timeout` into the milliseconds expected by `poll`, `None` maps to "forever"
+pub fn millis(timeout: Option<Duration>) -> c_int {
+    match timeout {
+        None => -1,
+        Some(timeout) => {
+            cmp::max(0, cmp::min(timeout.num_milliseconds(), i32::MAX as i64)) as c_int
+        },
+    }
+}
+
+/// Waits until `fd` is ready for any of the `events`, returns `false` if `timeout` elapsed first
+pub fn wait(fd: c_int, events: c_short, timeout: Option<Duration>) -> IoResult<bool> {
+    let mut fds = pollfd { fd: fd, events: events, revents: 0 };
+
+    match unsafe { poll(&mut fds, 1, millis(timeout)) } {
+        FAILURE => Err(IoError::last_error()),
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// Waits until `first` or `second` is ready for any of the `events`, returns which ones are
+///
+/// Both are `false` if `timeout` elapsed first.
+pub fn wait_pair(first: c_int, second: c_int, events: c_short, timeout: Option<Duration>)
+                 -> IoResult<(bool, bool)> {
+    let mut fds = [
+        pollfd { fd: first, events: events, revents: 0 },
+        pollfd { fd: second, events: events, revents: 0 },
+    ];
+
+    match unsafe { poll(fds.as_mut_ptr(), 2, millis(timeout)) } {
+        FAILURE => Err(IoError::last_error()),
+        _ => Ok((fds[0].revents != 0, fds[1].revents != 0)),
+    }
+}
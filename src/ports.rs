@@ -0,0 +1,312 @@
+//! Enumeration of the serial ports of the system
+//!
+//! `available_ports` lists the devices with what they are: a built-in UART, a USB adapter, a
+//! Bluetooth link or a pty. A `PortFilter` narrows the list down to the plausible candidates, so
+//! tools don't present every `/dev/tty*` node. `PortInfo::open` opens a port with the quirks
+//! its kind needs.
+//!
+//! ```ignore
+//! for port in try!(PortFilter::new().usb().list()).iter() {
+//!     println!("{} ({})", port.path.display(), port.kind);
+//! }
+//! ```
+
+use std::io::{IoError, IoResult, ReadWrite};
+
+use SerialPort;
+
+/// What kind of device is behind a port
+#[deriving(Clone, PartialEq, Show)]
+pub enum PortKind {
+    /// A UART of the machine, e.g. `/dev/ttyS0` or `/dev/ttyAMA0`
+    BuiltInUart,
+    /// A USB device of the CDC-ACM class, like most microcontroller boards, e.g. `/dev/ttyACM0`
+    UsbCdcAcm,
+    /// A USB to serial bridge chip, e.g. `/dev/ttyUSB0`
+    UsbBridge(BridgeChip),
+    /// A Bluetooth serial port profile link, e.g. `/dev/rfcomm0`
+    Bluetooth,
+    /// The slave side of a pseudo terminal
+    PseudoTerminal,
+}
+
+impl PortKind {
+    /// Tells whether the port is a USB device
+    pub fn is_usb(&self) -> bool {
+        match *self {
+            UsbCdcAcm | UsbBridge(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The maker of a USB to serial bridge
+#[deriving(Clone, PartialEq, Show)]
+pub enum BridgeChip {
+    Ftdi,
+    /// Silicon Labs CP210x
+    Cp210x,
+    /// WCH CH340 and CH341
+    Ch340,
+    /// Prolific PL2303
+    Pl2303,
+    OtherBridge,
+}
+
+/// A serial port found by `available_ports`
+#[deriving(Clone, PartialEq, Show)]
+pub struct PortInfo {
+    /// Device to open
+    pub path: Path,
+    pub kind: PortKind,
+    /// Name of the kernel driver, like `ftdi_sio`, if known
+    pub driver: Option<String>,
+}
+
+impl PortInfo {
+    /// Opens the port for reading and writing, with the quirks of its kind
+    ///
+    /// Bluetooth links are connected by the open, which fails if the device is out of range or
+    /// refuses the connection. They have no real baud rate nor carrier: their baud rate errors
+    /// are ignored (see `SerialPort::set_ignore_baud_rate`) and the modem lines aren't
+    /// watched (see `SerialPort::set_local_mode`).
+    pub fn open(&self) -> IoResult<SerialPort> {
+        if self.kind != Bluetooth {
+            return SerialPort::open(&self.path, ReadWrite)
+        }
+
+        let mut port = match SerialPort::open(&self.path, ReadWrite) {
+            Err(e) => return Err(IoError {
+                kind: e.kind,
+                desc: "Couldn't connect the Bluetooth link",
+                detail: Some(format!("{}: {}", self.path.display(), e)),
+            }),
+            Ok(port) => port,
+        };
+        port.set_ignore_baud_rate(true);
+        try!(port.set_local_mode(true));
+
+        Ok(port)
+    }
+}
+
+/// Selects ports by kind and driver
+///
+/// A port matches when its kind is one of the accepted kinds, and its driver one of the
+/// accepted drivers; without any accepted kind (or driver), all kinds (or drivers) match.
+#[deriving(Clone, Show)]
+pub struct PortFilter {
+    kinds: Vec<PortKind>,
+    any_bridge: bool,
+    drivers: Vec<String>,
+}
+
+impl PortFilter {
+    /// Matches all the ports
+    pub fn new() -> PortFilter {
+        PortFilter {
+            kinds: Vec::new(),
+            any_bridge: false,
+            drivers: Vec::new(),
+        }
+    }
+
+    /// Matches everything but ptys, the ports that can have a device at the other end
+    pub fn plausible() -> PortFilter {
+        PortFilter::new().kind(BuiltInUart).kind(UsbCdcAcm).usb_bridges().kind(Bluetooth)
+    }
+
+    /// Accepts the ports of `kind`
+    pub fn kind(mut self, kind: PortKind) -> PortFilter {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// Accepts the ports of the kernel `driver`
+    pub fn driver(mut self, driver: &str) -> PortFilter {
+        self.drivers.push(driver.to_string());
+        self
+    }
+
+    /// Accepts the USB ports, CDC-ACM devices and bridges
+    pub fn usb(self) -> PortFilter {
+        self.kind(UsbCdcAcm).usb_bridges()
+    }
+
+    /// Accepts the USB to serial bridges, whatever their chip
+    pub fn usb_bridges(mut self) -> PortFilter {
+        self.any_bridge = true;
+        self
+    }
+
+    /// Tells whether `port` is selected
+    pub fn matches(&self, port: &PortInfo) -> bool {
+        let kind = (self.kinds.is_empty() && !self.any_bridge) ||
+                   self.kinds.contains(&port.kind) ||
+                   (self.any_bridge && match port.kind { UsbBridge(_) => true, _ => false });
+        let driver = self.drivers.is_empty() || port.driver.as_ref().map_or(false, |driver| {
+            self.drivers.iter().any(|accepted| accepted == driver)
+        });
+
+        kind && driver
+    }
+
+    /// Lists the available ports that match
+    pub fn list(&self) -> IoResult<Vec<PortInfo>> {
+        let ports = try!(available_ports());
+
+        Ok(ports.into_iter().filter(|port| self.matches(port)).collect())
+    }
+}
+
+/// Lists the serial ports of the system, sorted by path
+///
+/// On Linux the devices are found in sysfs, and classified by their driver; the 8250 UARTs that
+/// the kernel reserves without any hardware behind are left out, as are the virtual consoles.
+/// On macOS the classification is based on the names of the callout devices (`/dev/cu.*`).
+/// Ptys are listed in both cases.
+pub fn available_ports() -> IoResult<Vec<PortInfo>> {
+    let mut ports = try!(system_ports());
+    ports.push_all(pseudo_terminals().as_slice());
+    ports.sort_by(|a, b| a.path.as_vec().cmp(b.path.as_vec()));
+
+    Ok(ports)
+}
+
+/// Lists the Bluetooth serial ports: the RFCOMM bound devices on Linux, the `cu.*Bluetooth*`
+/// devices on macOS
+///
+/// On Linux the devices are bound to a remote address beforehand, with `rfcomm bind`.
+pub fn bluetooth_ports() -> IoResult<Vec<PortInfo>> {
+    PortFilter::new().kind(Bluetooth).list()
+}
+
+/// Classifies the tty devices of sysfs
+#[cfg(target_os = "linux")]
+fn system_ports() -> IoResult<Vec<PortInfo>> {
+    use std::io::File;
+    use std::io::fs;
+    use std::io::fs::PathExtensions;
+
+    let mut ports = Vec::new();
+
+    for class in try!(fs::readdir(&Path::new("/sys/class/tty"))).iter() {
+        let name = match class.filename_str() {
+            None => continue,
+            Some(name) => name.to_string(),
+        };
+        let path = Path::new("/dev").join(name.as_slice());
+
+        // Bound by `rfcomm`, with no device in sysfs
+        if name.as_slice().starts_with("rfcomm") {
+            ports.push(PortInfo { path: path, kind: Bluetooth, driver: None });
+            continue
+        }
+
+        // Virtual consoles, ptmx, etc.
+        let device = class.join("device");
+        if !device.exists() {
+            continue
+        }
+
+        let driver = fs::readlink(&device.join("driver")).ok().and_then(|driver| {
+            driver.filename_str().map(|driver| driver.to_string())
+        });
+        let on_usb = fs::readlink(class).ok().map_or(false, |link| {
+            link.as_str().map_or(false, |link| link.contains("/usb"))
+        });
+
+        let kind = match driver.as_ref().map(|driver| driver.as_slice()) {
+            Some("cdc_acm") => UsbCdcAcm,
+            Some("ftdi_sio") => UsbBridge(Ftdi),
+            Some("cp210x") => UsbBridge(Cp210x),
+            Some("ch341") | Some("ch341-uart") => UsbBridge(Ch340),
+            Some("pl2303") => UsbBridge(Pl2303),
+            _ if on_usb => UsbBridge(OtherBridge),
+            Some("serial8250") => {
+                // `type` is 0 (PORT_UNKNOWN) when no UART answered at the address
+                match File::open(&class.join("type")).read_to_string() {
+                    Ok(ref uart) if uart.as_slice().trim() == "0" => continue,
+                    _ => BuiltInUart,
+                }
+            },
+            _ => BuiltInUart,
+        };
+
+        ports.push(PortInfo { path: path, kind: kind, driver: driver });
+    }
+
+    Ok(ports)
+}
+
+/// Classifies the callout devices by their names
+#[cfg(target_os = "macos")]
+fn system_ports() -> IoResult<Vec<PortInfo>> {
+    use std::io::fs;
+
+    let mut ports = Vec::new();
+
+    for path in try!(fs::readdir(&Path::new("/dev"))).into_iter() {
+        let kind = match path.filename_str() {
+            Some(name) if name.starts_with("cu.") => {
+                let name = name.slice_from(3);
+
+                if name.contains("Bluetooth") {
+                    Bluetooth
+                } else if name.starts_with("usbmodem") {
+                    UsbCdcAcm
+                } else if name.starts_with("SLAB_USBtoUART") {
+                    UsbBridge(Cp210x)
+                } else if name.starts_with("wchusbserial") {
+                    UsbBridge(Ch340)
+                } else if name.starts_with("usbserial-") {
+                    // Named after the serial number, which the Prolific driver doesn't use
+                    UsbBridge(Ftdi)
+                } else if name.starts_with("usbserial") {
+                    UsbBridge(OtherBridge)
+                } else {
+                    BuiltInUart
+                }
+            },
+            _ => continue,
+        };
+
+        ports.push(PortInfo { path: path, kind: kind, driver: None });
+    }
+
+    Ok(ports)
+}
+
+/// Lists the pty slaves in use
+#[cfg(target_os = "linux")]
+fn pseudo_terminals() -> Vec<PortInfo> {
+    use std::io::fs;
+
+    let slaves = fs::readdir(&Path::new("/dev/pts")).unwrap_or(Vec::new());
+
+    slaves.into_iter().filter(|path| {
+        path.filename_str().map_or(false, |name| is_number(name))
+    }).map(|path| {
+        PortInfo { path: path, kind: PseudoTerminal, driver: None }
+    }).collect()
+}
+
+/// Lists the pty slaves in use
+#[cfg(target_os = "macos")]
+fn pseudo_terminals() -> Vec<PortInfo> {
+    use std::io::fs;
+
+    let devices = fs::readdir(&Path::new("/dev")).unwrap_or(Vec::new());
+
+    devices.into_iter().filter(|path| {
+        path.filename_str().map_or(false, |name| {
+            name.starts_with("ttys") && is_number(name.slice_from(4))
+        })
+    }).map(|path| {
+        PortInfo { path: path, kind: PseudoTerminal, driver: None }
+    }).collect()
+}
+
+fn is_number(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b >= b'0' && b <= b'9')
+}
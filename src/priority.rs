@@ -0,0 +1,178 @@
+use std::collections::RingBuf;
+use std::io::{BrokenPipe, IoError, IoResult};
+
+/// Requests from the handles to the task writing to the port
+enum Request {
+    /// A frame sent after the frames queued before it
+    Bulk(Vec<u8>),
+    /// A frame sent before the bulk frames still queued
+    Urgent(Vec<u8>),
+    /// Sends the port back once the frames queued before are written
+    Finish,
+}
+
+/// A port written by a task of the crate, with two priorities
+///
+/// Frames are queued, and written whole, one after the other. Urgent frames (an emergency stop,
+/// an XOFF, a protocol NAK) go out at the next frame boundary, before the bulk frames still
+/// queued, which keep their order. A bulk frame already being written is never cut; queue long
+/// transfers by blocks of the latency the urgent frames can afford.
+///
+/// ```ignore
+/// let writer = PriorityWriter::new(port);
+/// let queue = writer.queue();
+/// spawn(proc() {
+///     if emergency.recv_opt().is_ok() {
+///         let _ = queue.send_urgent(b"\x1bSTOP\r");
+///     }
+/// });
+/// for block in firmware.chunks(64) {
+///     try!(writer.send(block));
+/// }
+/// ```
+///
+/// If a write fails, the task stops and the frames still queued are dropped; the sends that
+/// follow fail with `BrokenPipe`. Dropping the writer and its queues lets the task write the
+/// frames queued, then closes the port.
+pub struct PriorityWriter<W> {
+    queue: WriteQueue,
+    errors: Receiver<IoError>,
+    /// The port, once finished
+    port: Receiver<W>,
+}
+
+impl<W: Writer + Send> PriorityWriter<W> {
+    /// Takes over `port`, written from now on by a separate task
+    pub fn new(port: W) -> PriorityWriter<W> {
+        let (tx, rx) = channel();
+        let (error_sender, errors) = channel();
+        let (port_sender, port_receiver) = channel();
+
+        spawn(proc() {
+            let mut port = port;
+
+            match pump(&mut port, &rx) {
+                Err(e) => {
+                    debug!("priority writer: stopped ({})", e);
+                    let _ = error_sender.send_opt(e);
+                },
+                Ok(true) => {
+                    let _ = port_sender.send_opt(port);
+                },
+                Ok(false) => {},
+            }
+        });
+
+        PriorityWriter {
+            queue: WriteQueue { requests: tx },
+            errors: errors,
+            port: port_receiver,
+        }
+    }
+
+    /// Returns the error that stopped the task if a write failed, once
+    pub fn error(&self) -> Option<IoError> {
+        self.errors.try_recv().ok()
+    }
+
+    /// Waits for the frames queued so far to be written, and returns the port
+    ///
+    /// The frames queued afterwards through the other handles are dropped. Fails with the error
+    /// that stopped the task if a write failed, the port is closed then.
+    pub fn finish(self) -> IoResult<W> {
+        let _ = self.queue.requests.send_opt(Finish);
+
+        match self.port.recv_opt() {
+            Err(()) => Err(self.errors.try_recv().unwrap_or_else(|_| stopped())),
+            Ok(port) => Ok(port),
+        }
+    }
+
+    /// Returns a handle that queues frames from another task
+    pub fn queue(&self) -> WriteQueue {
+        self.queue.clone()
+    }
+
+    /// Queues `frame` after the frames already queued
+    pub fn send(&self, frame: &[u8]) -> IoResult<()> {
+        self.queue.send(frame)
+    }
+
+    /// Queues `frame` before the bulk frames not yet written
+    pub fn send_urgent(&self, frame: &[u8]) -> IoResult<()> {
+        self.queue.send_urgent(frame)
+    }
+}
+
+/// A handle queuing frames to a `PriorityWriter`, see `PriorityWriter::queue`
+#[deriving(Clone)]
+pub struct WriteQueue {
+    requests: Sender<Request>,
+}
+
+impl WriteQueue {
+    /// Queues `frame` after the frames already queued
+    pub fn send(&self, frame: &[u8]) -> IoResult<()> {
+        self.request(Bulk(frame.to_vec()))
+    }
+
+    /// Queues `frame` before the bulk frames not yet written
+    ///
+    /// The urgent frames keep their order between themselves.
+    pub fn send_urgent(&self, frame: &[u8]) -> IoResult<()> {
+        self.request(Urgent(frame.to_vec()))
+    }
+
+    fn request(&self, request: Request) -> IoResult<()> {
+        self.requests.send_opt(request).map_err(|_| stopped())
+    }
+}
+
+/// Writes the frames, urgent ones first, until all the handles are dropped or the port is
+/// asked back, which returns `true`
+fn pump<W: Writer>(port: &mut W, requests: &Receiver<Request>) -> IoResult<bool> {
+    let mut urgent = RingBuf::new();
+    let mut bulk = RingBuf::new();
+    let mut finish = false;
+
+    loop {
+        // Every request made while the previous frame was written is seen before picking the
+        // next one
+        while !finish {
+            let request = if urgent.is_empty() && bulk.is_empty() {
+                match requests.recv_opt() {
+                    Err(()) => return Ok(false),
+                    Ok(request) => request,
+                }
+            } else {
+                match requests.try_recv() {
+                    Err(_) => break,
+                    Ok(request) => request,
+                }
+            };
+
+            match request {
+                Bulk(frame) => bulk.push_back(frame),
+                Urgent(frame) => urgent.push_back(frame),
+                Finish => finish = true,
+            }
+        }
+
+        match urgent.pop_front().or_else(|| bulk.pop_front()) {
+            Some(frame) => try!(port.write(frame.as_slice())),
+            // Both queues are only empty here once finished
+            None => {
+                try!(port.flush());
+                return Ok(true)
+            },
+        }
+    }
+}
+
+fn stopped() -> IoError {
+    IoError {
+        kind: BrokenPipe,
+        desc: "The priority writer stopped",
+        detail: None,
+    }
+}
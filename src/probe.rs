@@ -0,0 +1,114 @@
+use std::default::Default;
+use std::io::{IoResult, ReadWrite, TimedOut};
+use std::time::Duration;
+
+use expect::{Expect, Match, Pattern};
+use {B9K6, BaudRate, SerialPort, Settings};
+
+/// How `probe` recognizes a device: what to send, and what it answers
+#[deriving(Clone)]
+pub struct ProbeSpec {
+    query: Vec<u8>,
+    response: Pattern,
+    baud_rates: Vec<BaudRate>,
+    settings: Settings,
+    timeout: Duration,
+}
+
+impl ProbeSpec {
+    /// Sends `query` at 9600 baud 8N1, and waits up to a second for the `response`
+    ///
+    /// An empty query only listens, for the devices that talk unprompted like GPS receivers.
+    pub fn new(query: &[u8], response: Pattern) -> ProbeSpec {
+        ProbeSpec {
+            query: query.to_vec(),
+            response: response,
+            baud_rates: vec![B9K6],
+            settings: Default::default(),
+            timeout: Duration::seconds(1),
+        }
+    }
+
+    /// Sets the baud rates to try, in order
+    pub fn baud_rates(mut self, baud_rates: &[BaudRate]) -> ProbeSpec {
+        self.baud_rates = baud_rates.to_vec();
+        self
+    }
+
+    /// Sets the frame format and the flow control, the baud rate of `settings` is ignored
+    pub fn settings(mut self, settings: Settings) -> ProbeSpec {
+        self.settings = settings;
+        self
+    }
+
+    /// Sets how long to wait for the response, at each baud rate
+    pub fn timeout(mut self, timeout: Duration) -> ProbeSpec {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// A device found by `probe`
+#[deriving(Clone, PartialEq, Show)]
+pub struct Detection {
+    pub path: Path,
+    /// The baud rate at which the device answered
+    pub baud_rate: BaudRate,
+    pub response: Match,
+}
+
+/// Finds which of the `ports` hosts the device described by `spec`
+///
+/// Each port is opened in turn, and the query sent at each baud rate until the response shows
+/// up. The ports that can't be opened (missing, busy) or don't answer are left out; the others
+/// keep the settings of the last baud rate tried. The candidates can come from the `ports`
+/// module:
+///
+/// ```ignore
+/// let candidates = try!(PortFilter::plausible().list());
+/// let paths: Vec<Path> = candidates.into_iter().map(|port| port.path).collect();
+/// let spec = ProbeSpec::new(b"", LiteralPattern("$GP".to_string()))
+///                      .baud_rates(&[B4K8, B9K6, B38K4]);
+/// let gps = probe(paths.as_slice(), &spec);
+/// ```
+pub fn probe(ports: &[Path], spec: &ProbeSpec) -> Vec<Detection> {
+    ports.iter().filter_map(|path| probe_port(path, spec).ok().and_then(|found| found)).collect()
+}
+
+fn probe_port(path: &Path, spec: &ProbeSpec) -> IoResult<Option<Detection>> {
+    let mut port = try!(SerialPort::open(path, ReadWrite));
+
+    for &baud_rate in spec.baud_rates.iter() {
+        let mut settings = spec.settings.clone();
+        settings.baud_rate = baud_rate;
+        try!(port.configure(&settings));
+
+        // What arrived at the previous baud rate is garbage
+        discard_input(&mut port);
+        try!(port.write(spec.query.as_slice()));
+
+        let mut session = Expect::new(port);
+        let result = session.expect(&spec.response, spec.timeout);
+        port = session.into_inner();
+
+        match result {
+            Err(ref e) if e.kind == TimedOut => {},
+            Err(e) => return Err(e),
+            Ok(response) => return Ok(Some(Detection {
+                path: path.clone(),
+                baud_rate: baud_rate,
+                response: response,
+            })),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Reads and drops the bytes already received
+fn discard_input(port: &mut SerialPort) {
+    let mut buf = [0u8, ..256];
+    port.set_timeout(Some(Duration::zero()));
+
+    while port.read(&mut buf).is_ok() {}
+}
@@ -0,0 +1,355 @@
+//! Named port profiles, loaded from a configuration file
+//!
+//! A profile tells which device to open and how to configure it, so tools handling a fleet of
+//! devices keep the configuration of each one out of their code. Profiles are written in JSON,
+//! or in TOML when the file name ends with `.toml`:
+//!
+//! ```text
+//! [gps]
+//! device = "/dev/serial/by-id/usb-u-blox*"
+//! baud_rate = 9600
+//! timeout_ms = 1000
+//!
+//! [modem]
+//! device = "/dev/ttyUSB*"
+//! driver = "option"
+//! baud_rate = 115200
+//! flow_control = "hardware"
+//! ```
+//!
+//! ```text
+//! {"gps": {"device": "/dev/serial/by-id/usb-u-blox*", "baud_rate": 9600, "timeout_ms": 1000}}
+//! ```
+//!
+//! The keys of a profile are all optional:
+//!
+//! - `device`: path of the device, a trailing `*` matches any suffix
+//! - `driver`: kernel driver of the device, as listed by `available_ports`
+//! - `baud_rate`: a standard rate in bits per second
+//! - `data_bits`, `parity`, `stop_bits` and `flow_control`: as parsed by `DataBits`, `Parity`,
+//!   `StopBits` and `FlowControl`
+//! - `timeout_ms`: read and write timeout in milliseconds, none by default
+//!
+//! The settings default to those of `Settings::default()`.
+//!
+//! ```ignore
+//! let profiles = try!(Settings::load_profiles(&Path::new("/etc/fleet/ports.toml")));
+//! let mut gps = try!(profiles.open_profile("gps"));
+//! ```
+//!
+//! The TOML support is limited to what the schema needs: tables of strings and integers.
+
+use serialize::json;
+use std::collections::TreeMap;
+use std::default::Default;
+use std::io::{File, FileNotFound, InvalidInput, IoError, IoResult, ReadWrite};
+use std::str::FromStr;
+use std::time::Duration;
+
+use ports::{PortInfo, available_ports};
+use {BaudRate, SerialPort, Settings};
+
+/// Which device a profile opens
+#[deriving(Clone, PartialEq, Show)]
+pub struct DeviceMatcher {
+    /// Path of the device, a trailing `*` matches any suffix
+    pub path: Option<String>,
+    /// Kernel driver of the device
+    pub driver: Option<String>,
+}
+
+impl DeviceMatcher {
+    /// Tells whether `port` is selected
+    pub fn matches(&self, port: &PortInfo) -> bool {
+        let path = match self.path {
+            None => true,
+            Some(ref pattern) => {
+                let path = port.path.as_str().unwrap_or("");
+
+                if pattern.as_slice().ends_with("*") {
+                    path.starts_with(pattern.as_slice().slice_to(pattern.len() - 1))
+                } else {
+                    path == pattern.as_slice()
+                }
+            },
+        };
+        let driver = self.driver.as_ref().map_or(true, |driver| {
+            port.driver.as_ref() == Some(driver)
+        });
+
+        path && driver
+    }
+
+    /// Opens the selected device, the first one in the order of `available_ports`
+    ///
+    /// An exact path without a driver is opened as is, without looking it up, so links like
+    /// the ones of `/dev/serial/by-id` can be used.
+    pub fn open(&self) -> IoResult<SerialPort> {
+        match (&self.path, &self.driver) {
+            (&Some(ref path), &None) if !path.as_slice().ends_with("*") => {
+                return SerialPort::open(&Path::new(path.as_slice()), ReadWrite)
+            },
+            _ => {},
+        }
+
+        let ports = try!(available_ports());
+        match ports.iter().find(|port| self.matches(*port)) {
+            None => Err(IoError {
+                kind: FileNotFound,
+                desc: "No port matches the profile",
+                detail: Some(format!("{}", self)),
+            }),
+            Some(port) => port.open(),
+        }
+    }
+}
+
+/// A device and its configuration
+#[deriving(Clone, PartialEq, Show)]
+pub struct Profile {
+    pub device: DeviceMatcher,
+    pub settings: Settings,
+    /// Read and write timeout
+    pub timeout: Option<Duration>,
+}
+
+impl Profile {
+    /// Opens the device and configures it
+    pub fn open(&self) -> IoResult<SerialPort> {
+        let mut port = try!(self.device.open());
+        try!(port.configure(&self.settings));
+        port.set_timeout(self.timeout);
+
+        Ok(port)
+    }
+
+    /// Reads the profile `name` from its keys
+    fn from_table(name: &str, table: &TreeMap<String, Value>) -> IoResult<Profile> {
+        let mut profile = Profile {
+            device: DeviceMatcher { path: None, driver: None },
+            settings: Default::default(),
+            timeout: None,
+        };
+
+        for (key, value) in table.iter() {
+            let key = key.as_slice();
+
+            match (key, value) {
+                ("device", &Text(ref path)) => profile.device.path = Some(path.clone()),
+                ("driver", &Text(ref driver)) => profile.device.driver = Some(driver.clone()),
+                ("baud_rate", &Number(rate)) => {
+                    let standard = BaudRate::standard_rates().iter().find(|standard| {
+                        standard.bits_per_second() as u64 == rate
+                    });
+                    profile.settings.baud_rate = match standard {
+                        None => return Err(invalid_key(name, key)),
+                        Some(&standard) => standard,
+                    };
+                },
+                ("data_bits", value) => profile.settings.data_bits = try!(parse(name, key, value)),
+                ("parity", value) => profile.settings.parity = try!(parse(name, key, value)),
+                ("stop_bits", value) => profile.settings.stop_bits = try!(parse(name, key, value)),
+                ("flow_control", value) => {
+                    profile.settings.flow_control = try!(parse(name, key, value));
+                },
+                ("timeout_ms", &Number(ms)) => {
+                    profile.timeout = Some(Duration::milliseconds(ms as i64));
+                },
+                ("device", _) | ("driver", _) | ("baud_rate", _) | ("timeout_ms", _) => {
+                    return Err(invalid_key(name, key))
+                },
+                _ => return Err(invalid(format!("profile {}: unknown key {}", name, key))),
+            }
+        }
+
+        Ok(profile)
+    }
+}
+
+/// A set of named profiles, see the module documentation
+#[deriving(Clone, PartialEq, Show)]
+pub struct Profiles {
+    profiles: TreeMap<String, Profile>,
+}
+
+impl Profiles {
+    /// Reads the profiles of a file, in TOML if its name ends with `.toml`, in JSON otherwise
+    pub fn load(path: &Path) -> IoResult<Profiles> {
+        let text = try!(File::open(path).read_to_string());
+
+        if path.extension_str() == Some("toml") {
+            Profiles::from_toml(text.as_slice())
+        } else {
+            Profiles::from_json(text.as_slice())
+        }
+    }
+
+    /// Parses profiles written in JSON, an object of profiles
+    pub fn from_json(text: &str) -> IoResult<Profiles> {
+        let root = match json::from_str(text) {
+            Err(e) => return Err(invalid(e.to_string())),
+            Ok(root) => root,
+        };
+        let root = try!(root.as_object().ok_or_else(|| invalid("not an object".to_string())));
+
+        let mut tables = TreeMap::new();
+        for (name, profile) in root.iter() {
+            let profile = try!(profile.as_object().ok_or_else(|| {
+                invalid(format!("profile {}: not an object", name))
+            }));
+
+            let mut table = TreeMap::new();
+            for (key, value) in profile.iter() {
+                let value = match (value.as_string(), value.as_u64()) {
+                    (Some(text), _) => Text(text.to_string()),
+                    (_, Some(number)) => Number(number),
+                    _ => return Err(invalid(format!("profile {}: invalid {}", name, key))),
+                };
+                table.insert(key.clone(), value);
+            }
+            tables.insert(name.clone(), table);
+        }
+
+        Profiles::from_tables(tables)
+    }
+
+    /// Parses profiles written in TOML, a table per profile
+    pub fn from_toml(text: &str) -> IoResult<Profiles> {
+        let mut tables = TreeMap::new();
+        let mut current = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: &str| invalid(format!("line {}: {}", i + 1, message));
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with("#") {
+                continue
+            }
+
+            if line.starts_with("[") {
+                let name = match line.find(']') {
+                    Some(end) if is_comment(line.slice_from(end + 1)) => line.slice(1, end).trim(),
+                    _ => return Err(error("invalid table header")),
+                };
+                if tables.contains_key(&name.to_string()) {
+                    return Err(error("table defined twice"))
+                }
+
+                tables.insert(name.to_string(), TreeMap::new());
+                current = Some(name.to_string());
+                continue
+            }
+
+            let (key, value) = match line.find('=') {
+                None => return Err(error("expected a key and a value")),
+                Some(equal) => (line.slice_to(equal).trim(), line.slice_from(equal + 1).trim()),
+            };
+            let value = match parse_toml_value(value) {
+                None => return Err(error("invalid value")),
+                Some(value) => value,
+            };
+            let table = match current {
+                None => return Err(error("key outside of a profile")),
+                Some(ref name) => tables.get_mut(name).unwrap(),
+            };
+
+            table.insert(key.to_string(), value);
+        }
+
+        Profiles::from_tables(tables)
+    }
+
+    /// Returns the profile `name`
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(&name.to_string())
+    }
+
+    /// Returns the names of the profiles, in alphabetical order
+    pub fn names(&self) -> Vec<&str> {
+        self.profiles.keys().map(|name| name.as_slice()).collect()
+    }
+
+    /// Opens the device of the profile `name` and configures it
+    pub fn open_profile(&self, name: &str) -> IoResult<SerialPort> {
+        match self.get(name) {
+            None => Err(IoError {
+                kind: InvalidInput,
+                desc: "Unknown profile",
+                detail: Some(name.to_string()),
+            }),
+            Some(profile) => profile.open(),
+        }
+    }
+
+    fn from_tables(tables: TreeMap<String, TreeMap<String, Value>>) -> IoResult<Profiles> {
+        let mut profiles = TreeMap::new();
+
+        for (name, table) in tables.iter() {
+            profiles.insert(name.clone(), try!(Profile::from_table(name.as_slice(), table)));
+        }
+
+        Ok(Profiles { profiles: profiles })
+    }
+}
+
+/// A value of a profile, whatever the format of the file
+enum Value {
+    Text(String),
+    Number(u64),
+}
+
+/// Parses the value of `key`, in the profile `name`
+fn parse<T: FromStr>(name: &str, key: &str, value: &Value) -> IoResult<T> {
+    let parsed = match *value {
+        Text(ref text) => from_str(text.as_slice()),
+        Number(number) => from_str(number.to_string().as_slice()),
+    };
+
+    parsed.ok_or_else(|| invalid_key(name, key))
+}
+
+/// Parses a TOML string or integer, followed by an optional comment
+fn parse_toml_value(value: &str) -> Option<Value> {
+    if value.starts_with("\"") {
+        let mut text = String::new();
+        let mut chars = value.slice_from(1).char_indices();
+
+        loop {
+            match chars.next() {
+                None => return None,
+                Some((i, '"')) if is_comment(value.slice_from(i + 2)) => return Some(Text(text)),
+                Some((_, '"')) => return None,
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, '"')) => text.push('"'),
+                    Some((_, '\\')) => text.push('\\'),
+                    _ => return None,
+                },
+                Some((_, c)) => text.push(c),
+            }
+        }
+    }
+
+    let end = value.find('#').unwrap_or(value.len());
+    let digits: String = value.slice_to(end).trim().chars().filter(|&c| c != '_').collect();
+
+    from_str(digits.as_slice()).map(Number)
+}
+
+/// Tells whether `rest` of a line is blank or a comment
+fn is_comment(rest: &str) -> bool {
+    let rest = rest.trim();
+
+    rest.is_empty() || rest.starts_with("#")
+}
+
+fn invalid_key(name: &str, key: &str) -> IoError {
+    invalid(format!("profile {}: invalid {}", name, key))
+}
+
+fn invalid(detail: String) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: "Invalid profiles",
+        detail: Some(detail),
+    }
+}
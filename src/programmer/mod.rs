@@ -0,0 +1,3 @@
+//! Clients for the bootloaders of microcontrollers
+
+pub mod stk500v1;
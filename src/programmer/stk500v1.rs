@@ -0,0 +1,212 @@
+//! STK500 version 1, as spoken by the Arduino bootloaders (Optiboot and its predecessors)
+//!
+//! Only the flash memory is programmed. Addresses are byte addresses, the conversion to the
+//! word addresses of the protocol is done internally.
+
+use std::io::{IoError, IoResult, OtherIoError, TimedOut};
+use std::io::timer;
+use std::time::Duration;
+
+use {SerialIo, SerialPort};
+
+const CRC_EOP: u8 = 0x20;
+const STK_INSYNC: u8 = 0x14;
+const STK_OK: u8 = 0x10;
+
+const STK_ENTER_PROGMODE: u8 = 0x50;
+const STK_GET_PARAMETER: u8 = 0x41;
+const STK_GET_SYNC: u8 = 0x30;
+const STK_LEAVE_PROGMODE: u8 = 0x51;
+const STK_LOAD_ADDRESS: u8 = 0x55;
+const STK_PROG_PAGE: u8 = 0x64;
+const STK_READ_PAGE: u8 = 0x74;
+const STK_READ_SIGN: u8 = 0x75;
+
+/// Flash memory type, for the page commands
+const FLASH: u8 = b'F';
+
+/// Resets the board into its bootloader, by pulsing DTR and RTS
+///
+/// The boards use the falling edge of either line to reset the microcontroller, the bootloader
+/// then waits a short time for a programmer before starting the sketch.
+pub fn reset(port: &mut SerialPort) -> IoResult<()> {
+    try!(port.set_dtr(false));
+    try!(port.set_rts(false));
+    timer::sleep(Duration::milliseconds(250));
+
+    try!(port.set_dtr(true));
+    try!(port.set_rts(true));
+    timer::sleep(Duration::milliseconds(50));
+
+    Ok(())
+}
+
+/// A programming session with an STK500v1 bootloader
+pub struct Stk500v1<S> {
+    port: S,
+    timeout: Duration,
+}
+
+impl<S: SerialIo> Stk500v1<S> {
+    /// Talks to the bootloader through `port`, which must already use the baud rate of the
+    /// bootloader (115200 for Optiboot on the Uno)
+    ///
+    /// The session takes over the timeout of the port.
+    pub fn new(port: S) -> Stk500v1<S> {
+        Stk500v1 {
+            port: port,
+            timeout: Duration::milliseconds(500),
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.port
+    }
+
+    /// Unwraps the port
+    pub fn into_inner(self) -> S {
+        self.port
+    }
+
+    /// Sets how long to wait for each response, 500 ms by default
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Synchronizes with the bootloader, making up to `attempts` attempts
+    ///
+    /// Any stale input, like the output of the previous sketch, is discarded.
+    pub fn sync(&mut self, attempts: uint) -> IoResult<()> {
+        for _ in range(0, attempts) {
+            try!(self.discard_input());
+
+            match self.command(&[STK_GET_SYNC], 0) {
+                Err(ref e) if e.kind == TimedOut || e.kind == OtherIoError => {},
+                Err(e) => return Err(e),
+                Ok(_) => return Ok(()),
+            }
+        }
+
+        Err(out_of_sync())
+    }
+
+    /// Returns the value of a bootloader `parameter`, e.g. 0x81 for the major software version
+    pub fn parameter(&mut self, parameter: u8) -> IoResult<u8> {
+        self.command(&[STK_GET_PARAMETER, parameter], 1).map(|value| value[0])
+    }
+
+    /// Returns the signature of the microcontroller, e.g. `[0x1E, 0x95, 0x0F]` for the
+    /// ATmega328P
+    pub fn signature(&mut self) -> IoResult<[u8, ..3]> {
+        self.command(&[STK_READ_SIGN], 3).map(|s| [s[0], s[1], s[2]])
+    }
+
+    /// Enters the programming mode
+    pub fn enter_programming(&mut self) -> IoResult<()> {
+        self.command(&[STK_ENTER_PROGMODE], 0).map(|_| ())
+    }
+
+    /// Leaves the programming mode, the bootloader then starts the sketch
+    pub fn leave_programming(&mut self) -> IoResult<()> {
+        self.command(&[STK_LEAVE_PROGMODE], 0).map(|_| ())
+    }
+
+    /// Writes the flash page that starts at `address`
+    pub fn write_page(&mut self, address: uint, data: &[u8]) -> IoResult<()> {
+        try!(self.load_address(address));
+
+        let len = data.len();
+        let mut command = vec![STK_PROG_PAGE, (len >> 8) as u8, len as u8, FLASH];
+        command.push_all(data);
+
+        self.command(command.as_slice(), 0).map(|_| ())
+    }
+
+    /// Reads `len` bytes of flash, starting at `address`
+    pub fn read_page(&mut self, address: uint, len: uint) -> IoResult<Vec<u8>> {
+        try!(self.load_address(address));
+
+        self.command(&[STK_READ_PAGE, (len >> 8) as u8, len as u8, FLASH], len)
+    }
+
+    /// Programs the `image` at the start of the flash, and verifies it
+    ///
+    /// The image is written in pages of `page_size` bytes (128 for the ATmega328P), the last
+    /// page is padded with 0xFF. The programming mode is left once the image is verified.
+    pub fn flash(&mut self, image: &[u8], page_size: uint) -> IoResult<()> {
+        try!(self.enter_programming());
+
+        for (i, page) in image.chunks(page_size).enumerate() {
+            let mut page = page.to_vec();
+            page.grow(page_size - page.len(), 0xFF);
+
+            try!(self.write_page(i * page_size, page.as_slice()));
+        }
+
+        for (i, page) in image.chunks(page_size).enumerate() {
+            let address = i * page_size;
+            let read = try!(self.read_page(address, page.len()));
+
+            if read.as_slice() != page {
+                return Err(IoError {
+                    kind: OtherIoError,
+                    desc: "Flash verification failed",
+                    detail: Some(format!("page at {:#06x} differs", address)),
+                })
+            }
+        }
+
+        self.leave_programming()
+    }
+
+    /// Sets the address of the next page operation
+    fn load_address(&mut self, address: uint) -> IoResult<()> {
+        // The flash is addressed in 16-bit words
+        let word = address / 2;
+
+        self.command(&[STK_LOAD_ADDRESS, word as u8, (word >> 8) as u8], 0).map(|_| ())
+    }
+
+    /// Sends `command`, and reads the `len` bytes of its response
+    fn command(&mut self, command: &[u8], len: uint) -> IoResult<Vec<u8>> {
+        try!(self.port.write(command));
+        try!(self.port.write(&[CRC_EOP]));
+
+        self.port.set_timeout(Some(self.timeout));
+
+        if try!(self.port.read_byte()) != STK_INSYNC {
+            return Err(out_of_sync())
+        }
+
+        let response = try!(self.port.read_exact(len));
+
+        if try!(self.port.read_byte()) != STK_OK {
+            return Err(out_of_sync())
+        }
+
+        Ok(response)
+    }
+
+    /// Discards the pending input
+    fn discard_input(&mut self) -> IoResult<()> {
+        self.port.set_timeout(Some(Duration::milliseconds(50)));
+
+        let mut buf = [0u8, ..64];
+        loop {
+            match self.port.read(&mut buf) {
+                Err(ref e) if e.kind == TimedOut => return Ok(()),
+                Err(e) => return Err(e),
+                Ok(_) => {},
+            }
+        }
+    }
+}
+
+fn out_of_sync() -> IoError {
+    IoError {
+        kind: OtherIoError,
+        desc: "Bootloader out of sync",
+        detail: None,
+    }
+}
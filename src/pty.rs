@@ -0,0 +1,63 @@
+use libc::{c_char, c_int, c_void, size_t};
+use std::c_str::CString;
+
+use termios::Termios;
+
+#[cfg(target_os = "linux")]
+#[link(name = "util")]
+extern {
+    pub fn openpty(
+        amaster: *mut c_int,
+        aslave: *mut c_int,
+        name: *mut c_char,
+        termp: *const Termios,
+        winp: *const c_void,
+    ) -> c_int;
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "c")]
+extern {
+    pub fn openpty(
+        amaster: *mut c_int,
+        aslave: *mut c_int,
+        name: *mut c_char,
+        termp: *const Termios,
+        winp: *const c_void,
+    ) -> c_int;
+}
+
+#[cfg(any(test, feature = "testing"))]
+#[link(name = "c")]
+extern {
+    pub fn ttyname(fd: c_int) -> *const c_char;
+}
+
+extern {
+    fn ttyname_r(fd: c_int, buf: *mut c_char, len: size_t) -> c_int;
+}
+
+/// Tells whether `fd` is a side of a pseudo-terminal, from the name of its device
+pub fn is_pty(fd: c_int) -> bool {
+    let mut name = [0 as c_char, ..128];
+
+    if unsafe { ttyname_r(fd, name.as_mut_ptr(), name.len() as size_t) } != 0 {
+        return false
+    }
+
+    let name = unsafe { CString::new(name.as_ptr(), false) };
+    match name.as_str() {
+        None => false,
+        Some(name) => name == "/dev/ptmx" || is_slave(name),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_slave(name: &str) -> bool {
+    name.starts_with("/dev/pts/")
+}
+
+#[cfg(target_os = "macos")]
+fn is_slave(name: &str) -> bool {
+    name.starts_with("/dev/ttys")
+}
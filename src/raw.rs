@@ -0,0 +1,37 @@
+//! The termios and ioctl bindings behind `SerialPort`, for the driver settings that the rest of
+//! the API doesn't cover
+//!
+//! The functions take the descriptor returned by `SerialPort::as_raw_fd`, and report failures
+//! like the C library does: they return `FAILURE` and set `errno`, which
+//! `IoError::last_error` picks up. `SerialPort` caches its termios structure, so its next setter
+//! undoes the changes made through `tcsetattr`; `SerialPort::with_raw_termios` changes the
+//! cached structure instead.
+//!
+//! The constants have the same names on every platform, but their values differ, and some of
+//! them only exist on one platform.
+
+pub use termios::{
+    B0, B50, B75, B110, B134, B150, B200, B300, B600, B1200, B1800, B2400, B4800, B9600, B19200,
+    B38400, B57600, B115200, B230400, CLOCAL, CRTSCTS, CS5, CS6, CS7, CS8, CSIZE, CSTOPB, ECHO,
+    ECHOCTL, ECHOE, ECHOK, ECHOKE, ECHONL, FAILURE, HUPCL, ICRNL, IGNCR, INLCR, ISTRIP, IXANY,
+    IXOFF, IXON, NCCS, PARENB, PARMRK, PARODD, SUCCESS, TCSADRAIN, TCSAFLUSH, TCSANOW, Termios,
+    VMIN, VTIME, cc_t, cfmakeraw, cfsetispeed, cfsetospeed, cfsetspeed, speed_t, tcdrain,
+    tcflag_t, tcgetattr, tcsetattr,
+};
+
+pub use ioctl::{
+    TIOCCBRK, TIOCMBIC, TIOCMBIS, TIOCMGET, TIOCM_CAR, TIOCM_CTS, TIOCM_DSR, TIOCM_DTR,
+    TIOCM_RNG, TIOCM_RTS, TIOCSBRK, ioctl,
+};
+
+#[cfg(target_os = "linux")]
+pub use termios::{
+    B460800, B500000, B576000, B921600, B1000000, B1152000, B1500000, B2000000, B2500000, B3000000,
+    B3500000, B4000000, BOTHER, CBAUD, CIBAUD,
+};
+
+#[cfg(target_os = "linux")]
+pub use ioctl::{SerialStruct, TIOCGSERIAL, TIOCSSERIAL};
+
+#[cfg(target_os = "macos")]
+pub use termios::{B7200, B14400, B28800, B76800};
@@ -0,0 +1,223 @@
+//! Record and replay of serial sessions
+//!
+//! A session with a real device is recorded as a capture (see the `capture` module). A
+//! `ReplayPort` then stands in for the device: it checks that the application sends what was
+//! sent during the recording, and answers with what the device answered, with the same timing.
+//! Tests can run against the behavior of real hardware without the hardware.
+
+use std::cmp;
+use std::default::Default;
+use std::io::{BufferedReader, EndOfFile, File, IoError, IoResult, OtherIoError, TimedOut};
+use std::slice::bytes;
+use std::time::Duration;
+
+use capture::{CaptureTracer, Record, read_capture};
+use clock::{RealTime, TimeSource, Virtual, nanoseconds};
+use trace::{Sent, TracedPort};
+use {SerialIo, Settings, VirtualClock};
+
+/// Records the traffic of `port` to a new capture file at `path`
+pub fn record<S>(port: S, path: &Path) -> IoResult<TracedPort<S, CaptureTracer<File>>> {
+    let file = try!(File::create(path));
+
+    Ok(TracedPort::new(port, CaptureTracer::new(file)))
+}
+
+/// Plays back the device side of a recorded session
+///
+/// Writes must match the data sent during the recording, in the same order but not necessarily
+/// in the same chunks; a mismatch fails with an `OtherIoError`. Reads return the received data
+/// once the recorded delay since the previous record has elapsed, and fail with a `TimedOut`
+/// error if the timeout of the port elapses first. Once the session is over, reads fail with an
+/// `EndOfFile` error. The delays take real time, unless the port runs on a `VirtualClock`.
+pub struct ReplayPort {
+    records: Vec<Record>,
+    /// Index of the current record
+    next: uint,
+    /// Bytes of the current record already read or written
+    pos: uint,
+    /// When the previous record was completed, on the clock of the port
+    last_event: u64,
+    /// Recorded monotonic time of the previous record
+    last_mono_ns: u64,
+    timing: bool,
+    settings: Settings,
+    timeout: Option<Duration>,
+    clock: TimeSource,
+}
+
+impl ReplayPort {
+    /// Plays back the session `records`
+    pub fn new(records: Vec<Record>) -> ReplayPort {
+        let last_mono_ns = records.as_slice().head().map_or(0, |r| r.timestamp.precise_ns);
+
+        ReplayPort {
+            records: records,
+            next: 0,
+            pos: 0,
+            last_event: RealTime.now(),
+            last_mono_ns: last_mono_ns,
+            timing: true,
+            settings: Default::default(),
+            timeout: None,
+            clock: RealTime,
+        }
+    }
+
+    /// Plays back the session recorded in the capture file at `path`
+    pub fn open(path: &Path) -> IoResult<ReplayPort> {
+        let mut file = BufferedReader::new(try!(File::open(path)));
+
+        Ok(ReplayPort::new(try!(read_capture(&mut file))))
+    }
+
+    /// Returns whether the whole session has been played back
+    pub fn is_finished(&self) -> bool {
+        self.next == self.records.len()
+    }
+
+    /// Changes whether the recorded delays are reproduced, `true` by default
+    ///
+    /// Without them, the received data is available as soon as the application reads it.
+    pub fn set_timing(&mut self, enable: bool) {
+        self.timing = enable;
+    }
+
+    /// Runs the port on `clock`, the recorded delays and the timeouts move it forward instead
+    /// of taking real time
+    ///
+    /// The delay of the next record starts over from the current time of `clock`.
+    pub fn set_clock(&mut self, clock: VirtualClock) {
+        self.clock = Virtual(clock);
+        self.last_event = self.clock.now();
+    }
+
+    /// Moves past the current record, once all its bytes went through
+    fn advance(&mut self) {
+        if self.pos == self.records[self.next].data.len() {
+            self.last_event = self.clock.now();
+            self.last_mono_ns = self.records[self.next].timestamp.precise_ns;
+            self.next += 1;
+            self.pos = 0;
+        }
+    }
+}
+
+impl Reader for ReplayPort {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if self.is_finished() {
+            return Err(IoError {
+                kind: EndOfFile,
+                desc: "End of the recorded session",
+                detail: None,
+            })
+        }
+
+        let timed_out = IoError {
+            kind: TimedOut,
+            desc: "Read operation timed out",
+            detail: None,
+        };
+
+        let n = {
+            let record = &self.records[self.next];
+
+            if record.direction == Sent {
+                // The device is waiting for the application to send something first
+                return match self.timeout {
+                    None => Err(IoError {
+                        kind: OtherIoError,
+                        desc: "Read while the recorded session expects a write",
+                        detail: None,
+                    }),
+                    Some(timeout) => {
+                        self.clock.sleep(nanoseconds(timeout));
+                        Err(timed_out)
+                    },
+                }
+            }
+
+            if self.pos == 0 && self.timing {
+                let last = self.last_mono_ns;
+                let due = self.last_event + cmp::max(record.timestamp.precise_ns, last) - last;
+                let now = self.clock.now();
+
+                if due > now {
+                    let wait = due - now;
+
+                    match self.timeout.map(nanoseconds) {
+                        Some(timeout) if timeout < wait => {
+                            self.clock.sleep(timeout);
+                            return Err(timed_out)
+                        },
+                        _ => self.clock.sleep(wait),
+                    }
+                }
+            }
+
+            let data = record.data.slice_from(self.pos);
+            let n = cmp::min(buf.len(), data.len());
+            bytes::copy_memory(buf, data.slice_to(n));
+            n
+        };
+
+        self.pos += n;
+        self.advance();
+
+        Ok(n)
+    }
+}
+
+impl SerialIo for ReplayPort {
+    fn settings(&self) -> IoResult<Settings> {
+        Ok(self.settings.clone())
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.settings = settings.clone();
+        Ok(())
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+impl Writer for ReplayPort {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let mut buf = buf;
+
+        while !buf.is_empty() {
+            let n = {
+                let expected = match self.records.get(self.next) {
+                    Some(record) if record.direction == Sent => record.data.slice_from(self.pos),
+                    _ => return Err(IoError {
+                        kind: OtherIoError,
+                        desc: "Write not in the recorded session",
+                        detail: Some(format!("{}", buf)),
+                    }),
+                };
+
+                let n = cmp::min(buf.len(), expected.len());
+                if buf.slice_to(n) != expected.slice_to(n) {
+                    return Err(IoError {
+                        kind: OtherIoError,
+                        desc: "Write doesn't match the recorded session",
+                        detail: Some(format!("expected {}, got {}", expected, buf)),
+                    })
+                }
+                n
+            };
+
+            buf = buf.slice_from(n);
+            self.pos += n;
+            self.advance();
+        }
+
+        Ok(())
+    }
+}
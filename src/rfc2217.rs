@@ -0,0 +1,304 @@
+//! RFC 2217 client, for the serial ports that terminal servers share over the network
+//!
+//! The connection is a telnet session with the COM-PORT-OPTION: the data goes through as is,
+//! with the IAC byte (`0xFF`) doubled, and the settings are sent in subnegotiations. `ser2net`
+//! and most device servers speak it.
+//!
+//! ```ignore
+//! let mut port = try!(Rfc2217Port::connect("10.0.0.7", 2217));
+//! try!(port.configure(&Settings { baud_rate: B115K2, ..Default::default() }));
+//! ```
+
+use std::cmp;
+use std::default::Default;
+use std::io::{InvalidInput, IoError, IoResult};
+use std::io::net::tcp::TcpStream;
+use std::time::Duration;
+use time;
+
+use {B0, BaudRate, FlowControl, Parity, SerialIo, Settings};
+use {Data5, Data6, Data7, Data8, EvenParity, MarkParity, NoParity, OddParity, SpaceParity};
+use {HardwareControl, NoFlowControl, SoftwareControl, Stop1, Stop2};
+
+// Telnet commands
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+// Telnet options
+const BINARY: u8 = 0;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+const COM_PORT_OPTION: u8 = 44;
+
+// Commands of the COM-PORT-OPTION, the server answers with the code plus `SERVER_OFFSET`
+const SET_BAUDRATE: u8 = 1;
+const SET_DATASIZE: u8 = 2;
+const SET_PARITY: u8 = 3;
+const SET_STOPSIZE: u8 = 4;
+const SET_CONTROL: u8 = 5;
+const SERVER_OFFSET: u8 = 100;
+
+/// Where the decoder of the incoming stream is
+enum State {
+    /// Plain data
+    Data,
+    /// After an IAC
+    Command,
+    /// After an IAC and a WILL, WONT, DO or DONT, waiting for the option
+    Negotiation(u8),
+    /// Within a subnegotiation
+    Subnegotiation,
+    /// After an IAC within a subnegotiation
+    SubnegotiationCommand,
+}
+
+/// A serial port of a terminal server, reached over TCP
+///
+/// `settings` returns the settings last applied with `configure`, updated with the values that
+/// the server acknowledges (it may round the baud rate) as they're read; before `configure`,
+/// the defaults rather than the settings of the server.
+pub struct Rfc2217Port {
+    stream: TcpStream,
+    state: State,
+    /// Bytes of the subnegotiation being received
+    subnegotiation: Vec<u8>,
+    settings: Settings,
+    timeout: Option<Duration>,
+}
+
+impl Rfc2217Port {
+    /// Connects to the terminal server at `host` and `port`
+    pub fn connect(host: &str, port: u16) -> IoResult<Rfc2217Port> {
+        Rfc2217Port::new(try!(TcpStream::connect(host, port)))
+    }
+
+    /// Enables the COM-PORT-OPTION and the binary transmission on an established connection
+    pub fn new(mut stream: TcpStream) -> IoResult<Rfc2217Port> {
+        try!(stream.write(&[IAC, WILL, COM_PORT_OPTION, IAC, WILL, BINARY, IAC, DO, BINARY]));
+
+        Ok(Rfc2217Port {
+            stream: stream,
+            state: Data,
+            subnegotiation: Vec::new(),
+            settings: Default::default(),
+            timeout: None,
+        })
+    }
+
+    /// Sends a COM-PORT-OPTION command
+    fn command(&mut self, code: u8, value: &[u8]) -> IoResult<()> {
+        let mut frame = vec![IAC, SB, COM_PORT_OPTION, code];
+        escape(value, &mut frame);
+        frame.push_all(&[IAC, SE]);
+
+        self.stream.write(frame.as_slice())
+    }
+
+    /// Moves the data of `raw` into `buf` and handles the telnet commands, returns the number of
+    /// data bytes
+    fn decode(&mut self, raw: &[u8], buf: &mut [u8]) -> IoResult<uint> {
+        let mut n = 0;
+
+        for &byte in raw.iter() {
+            self.state = match (self.state, byte) {
+                (Data, IAC) => Command,
+                (Data, _) | (Command, IAC) => {
+                    buf[n] = byte;
+                    n += 1;
+                    Data
+                },
+                (Command, WILL) | (Command, WONT) | (Command, DO) | (Command, DONT) => {
+                    Negotiation(byte)
+                },
+                (Command, SB) => {
+                    self.subnegotiation.clear();
+                    Subnegotiation
+                },
+                // NOP, go ahead and the other commands without effect on a serial port
+                (Command, _) => Data,
+                (Negotiation(command), option) => {
+                    try!(self.negotiate(command, option));
+                    Data
+                },
+                (Subnegotiation, IAC) => SubnegotiationCommand,
+                (Subnegotiation, _) | (SubnegotiationCommand, IAC) => {
+                    self.subnegotiation.push(byte);
+                    Subnegotiation
+                },
+                (SubnegotiationCommand, _) => {
+                    self.acknowledged();
+                    Data
+                },
+            };
+        }
+
+        Ok(n)
+    }
+
+    /// Answers the option requests of the server
+    ///
+    /// The options offered on connection aren't answered again, nor are the refusals.
+    fn negotiate(&mut self, command: u8, option: u8) -> IoResult<()> {
+        let answer = match (command, option) {
+            (DO, BINARY) | (DO, COM_PORT_OPTION) | (WILL, BINARY) => return Ok(()),
+            (DO, SUPPRESS_GO_AHEAD) => WILL,
+            (WILL, SUPPRESS_GO_AHEAD) => DO,
+            (DO, _) => WONT,
+            (WILL, _) => DONT,
+            _ => return Ok(()),
+        };
+
+        self.stream.write(&[IAC, answer, option])
+    }
+
+    /// Records the setting that the server acknowledged in the last subnegotiation
+    fn acknowledged(&mut self) {
+        let (code, value) = match self.subnegotiation.as_slice() {
+            [COM_PORT_OPTION, code, ..value] if code > SERVER_OFFSET => {
+                (code - SERVER_OFFSET, value)
+            },
+            _ => return,
+        };
+
+        match (code, value) {
+            (SET_BAUDRATE, [a, b, c, d]) => {
+                let rate = (a as uint << 24) | (b as uint << 16) | (c as uint << 8) | d as uint;
+                let standard = BaudRate::standard_rates().iter().find(|standard| {
+                    standard.bits_per_second() == rate
+                });
+
+                match standard {
+                    None => {},
+                    Some(&standard) => self.settings.baud_rate = standard,
+                }
+            },
+            (SET_DATASIZE, [5]) => self.settings.data_bits = Data5,
+            (SET_DATASIZE, [6]) => self.settings.data_bits = Data6,
+            (SET_DATASIZE, [7]) => self.settings.data_bits = Data7,
+            (SET_DATASIZE, [8]) => self.settings.data_bits = Data8,
+            (SET_PARITY, [1]) => self.settings.parity = NoParity,
+            (SET_PARITY, [2]) => self.settings.parity = OddParity,
+            (SET_PARITY, [3]) => self.settings.parity = EvenParity,
+            (SET_PARITY, [4]) => self.settings.parity = MarkParity,
+            (SET_PARITY, [5]) => self.settings.parity = SpaceParity,
+            (SET_STOPSIZE, [1]) => self.settings.stop_bits = Stop1,
+            (SET_STOPSIZE, [2]) => self.settings.stop_bits = Stop2,
+            (SET_CONTROL, [1]) => self.settings.flow_control = NoFlowControl,
+            (SET_CONTROL, [2]) => self.settings.flow_control = SoftwareControl,
+            (SET_CONTROL, [3]) => self.settings.flow_control = HardwareControl,
+            // The other settings, and the notifications of the line and modem states
+            _ => {},
+        }
+    }
+}
+
+impl Reader for Rfc2217Port {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        let deadline = self.timeout.map(|timeout| {
+            time::precise_time_ns() + cmp::max(timeout.num_nanoseconds().unwrap_or(0), 0) as u64
+        });
+        let mut raw = Vec::from_elem(buf.len(), 0u8);
+
+        // Telnet commands alone don't end the read
+        loop {
+            let timeout_ms = deadline.map(|deadline| {
+                let now = time::precise_time_ns();
+                if deadline > now { (deadline - now) / 1_000_000 } else { 0 }
+            });
+            self.stream.set_read_timeout(timeout_ms);
+
+            let len = try!(self.stream.read(raw.as_mut_slice()));
+            match try!(self.decode(raw.slice_to(len), buf)) {
+                0 => {},
+                n => return Ok(n),
+            }
+        }
+    }
+}
+
+impl Writer for Rfc2217Port {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let mut escaped = Vec::with_capacity(buf.len());
+        escape(buf, &mut escaped);
+
+        self.stream.write(escaped.as_slice())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.stream.flush()
+    }
+}
+
+impl SerialIo for Rfc2217Port {
+    fn settings(&self) -> IoResult<Settings> {
+        Ok(self.settings.clone())
+    }
+
+    /// Sends the `settings` to the server, without waiting for its acknowledgments
+    ///
+    /// `B0` can't be sent, the protocol uses a zero baud rate to ask for the current one.
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        if settings.baud_rate == B0 {
+            return Err(IoError {
+                kind: InvalidInput,
+                desc: "RFC 2217 has no B0 baud rate",
+                detail: None,
+            })
+        }
+
+        let rate = settings.baud_rate.bits_per_second() as u32;
+        let rate = [(rate >> 24) as u8, (rate >> 16) as u8, (rate >> 8) as u8, rate as u8];
+        try!(self.command(SET_BAUDRATE, &rate));
+        try!(self.command(SET_DATASIZE, &[settings.data_bits.to_uint() as u8]));
+        try!(self.command(SET_PARITY, &[parity_code(settings.parity)]));
+        try!(self.command(SET_STOPSIZE, &[settings.stop_bits.to_uint() as u8]));
+        try!(self.command(SET_CONTROL, &[control_code(settings.flow_control)]));
+        self.settings = settings.clone();
+
+        Ok(())
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+/// Appends `data` to `out`, with the IAC bytes doubled
+fn escape(data: &[u8], out: &mut Vec<u8>) {
+    for &byte in data.iter() {
+        if byte == IAC {
+            out.push(IAC);
+        }
+        out.push(byte);
+    }
+}
+
+fn parity_code(parity: Parity) -> u8 {
+    match parity {
+        NoParity => 1,
+        OddParity => 2,
+        EvenParity => 3,
+        MarkParity => 4,
+        SpaceParity => 5,
+    }
+}
+
+fn control_code(flow_control: FlowControl) -> u8 {
+    match flow_control {
+        NoFlowControl => 1,
+        SoftwareControl => 2,
+        HardwareControl => 3,
+    }
+}
@@ -0,0 +1,108 @@
+use std::cmp;
+use std::io::IoResult;
+
+/// A fixed size ring buffer that reads from a port straight into its storage
+///
+/// High-rate capture (1 to 4 Mbaud) spends much of its time copying and allocating chunks.
+/// `fill` reads into the free space of the ring, and the data is handed out as slices of the
+/// ring, so the bytes are only copied by the kernel. The storage is allocated once.
+///
+/// ```ignore
+/// let mut ring = RingBuffer::with_capacity(1 << 16);
+/// loop {
+///     try!(ring.fill(&mut port));
+///     let (first, second) = ring.slices();
+///     let used = decoder.feed(first) + decoder.feed(second);
+///     ring.consume(used);
+/// }
+/// ```
+pub struct RingBuffer {
+    buf: Vec<u8>,
+    /// Position of the oldest byte
+    start: uint,
+    len: uint,
+}
+
+impl RingBuffer {
+    /// An empty ring that holds up to `capacity` bytes
+    pub fn with_capacity(capacity: uint) -> RingBuffer {
+        RingBuffer {
+            buf: Vec::from_elem(cmp::max(capacity, 1), 0u8),
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of bytes the ring holds at most
+    pub fn capacity(&self) -> uint {
+        self.buf.len()
+    }
+
+    /// Returns the number of bytes held
+    pub fn len(&self) -> uint {
+        self.len
+    }
+
+    /// Tells whether the ring holds no data
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Tells whether there's no room left, `fill` doesn't read then
+    pub fn is_full(&self) -> bool {
+        self.len == self.buf.len()
+    }
+
+    /// Reads once from `reader` into the free space, returns how many bytes came in
+    ///
+    /// The read goes into the free space up to the end of the storage, or the wrapped around
+    /// part, so it can return less than the space left. Returns 0 without reading when the ring
+    /// is full; the errors of `reader`, time outs included, are returned as is.
+    pub fn fill<R: Reader>(&mut self, reader: &mut R) -> IoResult<uint> {
+        if self.is_full() {
+            return Ok(0)
+        }
+
+        let capacity = self.buf.len();
+        let end = (self.start + self.len) % capacity;
+        let free = if end >= self.start { capacity - end } else { self.start - end };
+
+        let n = try!(reader.read(self.buf.slice_mut(end, end + free)));
+        self.len += n;
+
+        Ok(n)
+    }
+
+    /// Returns the data held, oldest first, as two slices since it can wrap around the end of
+    /// the storage; the second one is empty otherwise
+    pub fn slices(&self) -> (&[u8], &[u8]) {
+        let capacity = self.buf.len();
+
+        if self.start + self.len <= capacity {
+            (self.buf.slice(self.start, self.start + self.len), self.buf.slice_to(0))
+        } else {
+            let wrapped = self.start + self.len - capacity;
+
+            (self.buf.slice_from(self.start), self.buf.slice_to(wrapped))
+        }
+    }
+
+    /// Drops the `n` oldest bytes, or all of them if there are fewer
+    pub fn consume(&mut self, n: uint) {
+        let n = cmp::min(n, self.len);
+
+        self.start = (self.start + n) % self.buf.len();
+        self.len -= n;
+
+        // The next read gets the largest contiguous space
+        if self.len == 0 {
+            self.start = 0;
+        }
+    }
+
+    /// Drops all the data
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+}
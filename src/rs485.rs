@@ -0,0 +1,418 @@
+//! RS-485 half-duplex transmission, with the driver enable of the transceiver on RTS or on a
+//! GPIO
+//!
+//! An RS-485 transceiver only drives the bus while its driver enable input is asserted, so the
+//! bus has to be turned around by software: the driver is enabled before sending, and released
+//! once the last stop bit is out, for the other nodes to answer. `HalfDuplex` does this around
+//! each write, whatever line the board wires to the driver enable:
+//!
+//! ```ignore
+//! let line = try!(GpioChipLine::open(&Path::new("/dev/gpiochip0"), 17));
+//! let mut bus = try!(HalfDuplex::new(port, line));
+//! try!(bus.write(request.as_slice()));
+//! let n = try!(bus.read(&mut answer));
+//! ```
+
+use std::io::fs::PathExtensions;
+use std::io::timer;
+use std::io::{File, IoError, IoResult, Open, OtherIoError, Seek, SeekSet, TimedOut, Write};
+use std::time::Duration;
+
+use {SerialIo, SerialPort, Settings};
+
+/// How often the transmitter is checked while the last bytes go out
+const POLL_US: i64 = 50;
+
+/// Drives the driver enable input of an RS-485 transceiver
+pub trait DirectionControl {
+    /// Enables the driver to transmit (`true`), or releases the bus to receive (`false`)
+    ///
+    /// `port` is the port the transceiver is attached to, for the controls using its lines.
+    fn set_transmit(&mut self, port: &mut SerialPort, transmit: bool) -> IoResult<()>;
+}
+
+/// Driver enable wired to RTS
+pub struct RtsControl {
+    inverted: bool,
+}
+
+impl RtsControl {
+    /// RTS is asserted to transmit
+    pub fn new() -> RtsControl {
+        RtsControl { inverted: false }
+    }
+
+    /// RTS is deasserted to transmit, for the boards with an inverter on the line
+    pub fn inverted() -> RtsControl {
+        RtsControl { inverted: true }
+    }
+}
+
+impl DirectionControl for RtsControl {
+    fn set_transmit(&mut self, port: &mut SerialPort, transmit: bool) -> IoResult<()> {
+        port.set_rts(transmit != self.inverted)
+    }
+}
+
+/// Driver enable wired to a GPIO, driven through the sysfs interface of Linux
+///
+/// The GPIO is driven high to transmit, unless inverted.
+pub struct SysfsGpio {
+    value: File,
+    inverted: bool,
+}
+
+impl SysfsGpio {
+    /// Exports the GPIO numbered `gpio` unless it is already, and makes it an output
+    ///
+    /// The GPIO is driven low at once, without a glitch. `HalfDuplex` releases the bus when it
+    /// takes the control, whatever the polarity.
+    pub fn open(gpio: uint) -> IoResult<SysfsGpio> {
+        let dir = Path::new(format!("/sys/class/gpio/gpio{}", gpio));
+
+        if !dir.exists() {
+            let mut export = try!(File::open_mode(&Path::new("/sys/class/gpio/export"), Open,
+                                                  Write));
+            try!(export.write_str(gpio.to_string().as_slice()));
+        }
+
+        let mut direction = try!(File::open_mode(&dir.join("direction"), Open, Write));
+        try!(direction.write_str("low"));
+
+        Ok(SysfsGpio {
+            value: try!(File::open_mode(&dir.join("value"), Open, Write)),
+            inverted: false,
+        })
+    }
+
+    /// Drives the GPIO low to transmit
+    pub fn inverted(mut self) -> SysfsGpio {
+        self.inverted = true;
+        self
+    }
+}
+
+impl DirectionControl for SysfsGpio {
+    fn set_transmit(&mut self, _: &mut SerialPort, transmit: bool) -> IoResult<()> {
+        let level = if transmit != self.inverted { b"1" } else { b"0" };
+
+        try!(self.value.seek(0, SeekSet));
+        self.value.write(level)
+    }
+}
+
+/// Driver enable wired to a line of a GPIO chip, driven through the character device of Linux
+/// (`/dev/gpiochipN`)
+///
+/// The line is driven high to transmit, unless inverted. It stays requested, so other programs
+/// can't drive it, until the value is dropped.
+pub struct GpioChipLine {
+    handle: gpio::LineHandle,
+    inverted: bool,
+}
+
+impl GpioChipLine {
+    /// Requests the line `offset` of the GPIO chip at `chip` as an output, driven low
+    ///
+    /// `HalfDuplex` releases the bus when it takes the control, whatever the polarity.
+    pub fn open(chip: &Path, offset: u32) -> IoResult<GpioChipLine> {
+        Ok(GpioChipLine {
+            handle: try!(gpio::LineHandle::request(chip, offset)),
+            inverted: false,
+        })
+    }
+
+    /// Drives the line low to transmit
+    pub fn inverted(mut self) -> GpioChipLine {
+        self.inverted = true;
+        self
+    }
+}
+
+impl DirectionControl for GpioChipLine {
+    fn set_transmit(&mut self, _: &mut SerialPort, transmit: bool) -> IoResult<()> {
+        self.handle.set(transmit != self.inverted)
+    }
+}
+
+/// A port attached to an RS-485 bus, that enables the driver of the transceiver around writes
+///
+/// Each write enables the driver, sends the data, waits until the last stop bit is out, then
+/// releases the bus. Reads are passed through untouched; transceivers that keep their receiver
+/// enabled while transmitting echo the writes back, unless the echo is suppressed.
+pub struct HalfDuplex<D> {
+    inner: SerialPort,
+    control: D,
+    /// Between enabling the driver and sending
+    lead: Duration,
+    /// Between the end of the transmission and releasing the bus
+    lag: Duration,
+    /// How long the echo of a write can take to be read back, if it's suppressed
+    echo_timeout: Option<Duration>,
+}
+
+impl<D: DirectionControl> HalfDuplex<D> {
+    /// Turns the driver enable of the transceiver attached to `inner` with `control`, the bus
+    /// is released at once
+    pub fn new(inner: SerialPort, control: D) -> IoResult<HalfDuplex<D>> {
+        let mut port = HalfDuplex {
+            inner: inner,
+            control: control,
+            lead: Duration::zero(),
+            lag: Duration::zero(),
+            echo_timeout: None,
+        };
+        try!(port.control.set_transmit(&mut port.inner, false));
+
+        Ok(port)
+    }
+
+    /// Sets how long the driver is enabled before sending, for the transceivers slow to wake up
+    pub fn delay_before_send(mut self, delay: Duration) -> HalfDuplex<D> {
+        self.lead = delay;
+        self
+    }
+
+    /// Sets how long the driver stays enabled after the last stop bit, for the nodes that need
+    /// the line held to see the end of the frame
+    pub fn delay_after_send(mut self, delay: Duration) -> HalfDuplex<D> {
+        self.lag = delay;
+        self
+    }
+
+    /// Reads back and discards the echo of each write, for the transceivers, two-wire buses and
+    /// optical links where the transmitted bytes come back on RX
+    ///
+    /// The echo is compared with the data sent: if another node drove the bus at the same time,
+    /// the write fails with an `OtherIoError` telling the first byte that differs, and the
+    /// frame should be sent again. If the echo doesn't arrive within `timeout` after the bus is
+    /// released, the write fails with `TimedOut`. Either way the rest of the echo, if any, is
+    /// left to the reads.
+    pub fn suppress_echo(mut self, timeout: Duration) -> HalfDuplex<D> {
+        self.echo_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns a reference to the direction control
+    pub fn control(&self) -> &D {
+        &self.control
+    }
+
+    /// Returns a reference to the underlying port
+    pub fn get_ref(&self) -> &SerialPort {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying port
+    ///
+    /// Writing directly to the port doesn't enable the driver.
+    pub fn get_mut(&mut self) -> &mut SerialPort {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying port and the direction control
+    pub fn into_inner(self) -> (SerialPort, D) {
+        (self.inner, self.control)
+    }
+
+    /// Sends `buf` and waits until its last stop bit is out
+    fn send(&mut self, buf: &[u8]) -> IoResult<()> {
+        if self.lead > Duration::zero() {
+            timer::sleep(self.lead);
+        }
+
+        try!(self.inner.write(buf));
+        try!(self.inner.drain());
+
+        // `drain` can return while the UART still shifts the last byte out
+        while !try!(self.inner.output_drained()) {
+            timer::sleep(Duration::microseconds(POLL_US));
+        }
+
+        if self.lag > Duration::zero() {
+            timer::sleep(self.lag);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the echo of `sent` and checks that it matches
+    fn discard_echo(&mut self, sent: &[u8], timeout: Duration) -> IoResult<()> {
+        let previous = self.inner.timeout();
+        self.inner.set_timeout(Some(timeout));
+        let echo = self.inner.read_exact(sent.len());
+        self.inner.set_timeout(previous);
+
+        let echo = match echo {
+            Err(ref e) if e.kind == TimedOut => return Err(IoError {
+                kind: TimedOut,
+                desc: "The echo of the data sent is missing",
+                detail: None,
+            }),
+            Err(e) => return Err(e),
+            Ok(echo) => echo,
+        };
+
+        match sent.iter().zip(echo.iter()).position(|(byte, echoed)| byte != echoed) {
+            None => Ok(()),
+            Some(i) => Err(IoError {
+                kind: OtherIoError,
+                desc: "Bus collision, the echo differs from the data sent",
+                detail: Some(format!("byte {}: sent 0x{:02x}, read back 0x{:02x}", i, sent[i],
+                                     echo[i])),
+            }),
+        }
+    }
+}
+
+impl<D: DirectionControl> Reader for HalfDuplex<D> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        self.inner.read(buf)
+    }
+}
+
+impl<D: DirectionControl> SerialIo for HalfDuplex<D> {
+    fn settings(&self) -> IoResult<Settings> {
+        self.inner.settings()
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.inner.configure(settings)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_timeout(timeout)
+    }
+}
+
+impl<D: DirectionControl> Writer for HalfDuplex<D> {
+    /// Enables the driver, sends `buf`, then releases the bus, even if sending failed, and
+    /// discards the echo if it's suppressed
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        try!(self.control.set_transmit(&mut self.inner, true));
+        let sent = self.send(buf);
+        let released = self.control.set_transmit(&mut self.inner, false);
+        try!(sent.and(released));
+
+        match self.echo_timeout {
+            None => Ok(()),
+            Some(timeout) => self.discard_echo(buf, timeout),
+        }
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod gpio {
+    use libc::{c_char, c_int};
+    use libc;
+    use native::io::file::FileDesc;
+    use std::io::{IoError, IoResult};
+
+    use O_CLOEXEC;
+    use ioctl;
+    use termios::FAILURE;
+
+    /// `_IOWR(0xB4, 0x03, struct gpiohandle_request)`
+    const GPIO_GET_LINEHANDLE_IOCTL: libc::c_ulong = 0xC16CB403;
+    /// `_IOWR(0xB4, 0x09, struct gpiohandle_data)`
+    const GPIOHANDLE_SET_LINE_VALUES_IOCTL: libc::c_ulong = 0xC040B409;
+    const GPIOHANDLE_REQUEST_OUTPUT: u32 = 1 << 1;
+    const GPIOHANDLES_MAX: uint = 64;
+
+    /// `struct gpiohandle_request`
+    #[repr(C)]
+    struct HandleRequest {
+        lineoffsets: [u32, ..GPIOHANDLES_MAX],
+        flags: u32,
+        default_values: [u8, ..GPIOHANDLES_MAX],
+        consumer_label: [c_char, ..32],
+        lines: u32,
+        fd: c_int,
+    }
+
+    /// `struct gpiohandle_data`
+    #[repr(C)]
+    struct HandleData {
+        values: [u8, ..GPIOHANDLES_MAX],
+    }
+
+    /// A line requested as an output
+    pub struct LineHandle {
+        fd: FileDesc,
+    }
+
+    impl LineHandle {
+        pub fn request(chip: &Path, offset: u32) -> IoResult<LineHandle> {
+            let chip = match chip.with_c_str(|s| unsafe {
+                libc::open(s, libc::O_RDWR | O_CLOEXEC, 0)
+            }) {
+                FAILURE => return Err(IoError::last_error()),
+                fd => FileDesc::new(fd, true),
+            };
+
+            let mut request = HandleRequest {
+                lineoffsets: [0, ..GPIOHANDLES_MAX],
+                flags: GPIOHANDLE_REQUEST_OUTPUT,
+                default_values: [0, ..GPIOHANDLES_MAX],
+                consumer_label: [0, ..32],
+                lines: 1,
+                fd: -1,
+            };
+            request.lineoffsets[0] = offset;
+            for (label, &byte) in request.consumer_label.iter_mut().zip(b"serial-rs485".iter()) {
+                *label = byte as c_char;
+            }
+
+            match unsafe {
+                ioctl::ioctl(chip.fd(), GPIO_GET_LINEHANDLE_IOCTL,
+                             &mut request as *mut HandleRequest)
+            } {
+                FAILURE => Err(IoError::last_error()),
+                _ => Ok(LineHandle { fd: FileDesc::new(request.fd, true) }),
+            }
+        }
+
+        pub fn set(&mut self, level: bool) -> IoResult<()> {
+            let mut data = HandleData { values: [0, ..GPIOHANDLES_MAX] };
+            data.values[0] = level as u8;
+
+            match unsafe {
+                ioctl::ioctl(self.fd.fd(), GPIOHANDLE_SET_LINE_VALUES_IOCTL,
+                             &mut data as *mut HandleData)
+            } {
+                FAILURE => Err(IoError::last_error()),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod gpio {
+    use std::io::{IoError, IoResult, IoUnavailable};
+
+    /// GPIO chips are specific to Linux
+    pub struct LineHandle;
+
+    impl LineHandle {
+        pub fn request(_: &Path, _: u32) -> IoResult<LineHandle> {
+            Err(IoError {
+                kind: IoUnavailable,
+                desc: "GPIO chips are only supported on Linux",
+                detail: None,
+            })
+        }
+
+        pub fn set(&mut self, _: bool) -> IoResult<()> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::IoResult;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, SeqCst};
+use std::time::Duration;
+
+use keepalive;
+use {Keepalive, ReadCanceller, SerialIo, SerialPort, Settings};
+
+/// A port that can be cloned and used from several tasks
+///
+/// The clones share two halves of the port, each behind its own lock: one for the reads, the
+/// other for the writes and the configuration, on a duplicate of the file descriptor. A task
+/// blocked in a read doesn't hold up the writers, while the writes (whole buffers) and the
+/// configuration changes never interleave. Reads are meant for a single task at a time; a
+/// second reader waits for the read in progress, which a `ReadCanceller` can abort.
+///
+/// ```ignore
+/// let port = try!(SharedSerialPort::new(try!(SerialPort::open(&path, ReadWrite))));
+/// let mut reader = port.clone();
+/// spawn(proc() {
+///     for line in BufferedReader::new(reader).lines() { ... }
+/// });
+/// try!(port.write(b"AT\r"));
+/// ```
+#[deriving(Clone)]
+pub struct SharedSerialPort {
+    reader: Arc<Mutex<SerialPort>>,
+    /// Writes and configuration
+    control: Arc<Mutex<SerialPort>>,
+    /// Whether the configuration changed since the reader last fetched it
+    stale: Arc<AtomicBool>,
+}
+
+impl SharedSerialPort {
+    /// Shares `port`, its timeout applies to the reads
+    pub fn new(port: SerialPort) -> IoResult<SharedSerialPort> {
+        let control = try!(port.duplicate());
+
+        Ok(SharedSerialPort {
+            reader: Arc::new(Mutex::new(port)),
+            control: Arc::new(Mutex::new(control)),
+            stale: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Applies the `settings` to the device
+    pub fn configure(&self, settings: &Settings) -> IoResult<()> {
+        let result = self.control.lock().configure(settings);
+        self.stale.store(true, SeqCst);
+
+        result
+    }
+
+    /// Writes `frame` whenever nothing was written for `interval`, until the returned handle is
+    /// dropped
+    ///
+    /// Many radio modems and PLC links drop the session without regular traffic. A task of the
+    /// crate watches the writes, all of them, and only sends the frame while the application is
+    /// idle, never in the middle of one of its writes.
+    ///
+    /// ```ignore
+    /// let _keepalive = port.keepalive(b"\x05", Duration::seconds(30));
+    /// ```
+    pub fn keepalive(&self, frame: &[u8], interval: Duration) -> Keepalive {
+        keepalive::start(self.control.clone(), frame.to_vec(), interval)
+    }
+
+    /// Reads into `buf`, subject to the timeout
+    pub fn read(&self, buf: &mut [u8]) -> IoResult<uint> {
+        try!(self.lock_reader()).read(buf)
+    }
+
+    /// Returns a handle that aborts the reads from another task, see
+    /// `SerialPort::read_canceller`
+    pub fn read_canceller(&self) -> IoResult<ReadCanceller> {
+        self.reader.lock().read_canceller()
+    }
+
+    /// Changes the read timeout, once the read in progress returns
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        self.reader.lock().set_timeout(timeout)
+    }
+
+    /// Returns the configuration of the device
+    pub fn settings(&self) -> IoResult<Settings> {
+        self.control.lock().settings()
+    }
+
+    /// Returns the read timeout, once the read in progress returns
+    pub fn timeout(&self) -> Option<Duration> {
+        self.reader.lock().timeout()
+    }
+
+    /// Runs `action` with the writing half, for the configuration that `SharedSerialPort`
+    /// doesn't cover
+    ///
+    /// The other tasks can't write or change the configuration meanwhile; the device settings
+    /// changed this way apply to the reads too.
+    pub fn with_port<T>(&self, action: |&mut SerialPort| -> T) -> T {
+        let result = action(&mut *self.control.lock());
+        self.stale.store(true, SeqCst);
+
+        result
+    }
+
+    /// Writes all of `buf`, without interleaving with the writes of the other tasks
+    pub fn write(&self, buf: &[u8]) -> IoResult<()> {
+        self.control.lock().write(buf)
+    }
+
+    /// Locks the reading half, after fetching the settings changed through the other half
+    ///
+    /// The reader tells the time outs from the end of the file with its copy of the settings.
+    fn lock_reader(&self) -> IoResult<MutexGuard<SerialPort>> {
+        let mut reader = self.reader.lock();
+        if self.stale.swap(false, SeqCst) {
+            reader.termios = try!(reader.fetch());
+        }
+
+        Ok(reader)
+    }
+}
+
+impl Reader for SharedSerialPort {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        try!(self.lock_reader()).read(buf)
+    }
+}
+
+impl Writer for SharedSerialPort {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        self.control.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.control.lock().flush()
+    }
+}
+
+impl SerialIo for SharedSerialPort {
+    fn settings(&self) -> IoResult<Settings> {
+        self.control.lock().settings()
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        let result = self.control.lock().configure(settings);
+        self.stale.store(true, SeqCst);
+
+        result
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.reader.lock().timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.reader.lock().set_timeout(timeout)
+    }
+}
@@ -0,0 +1,359 @@
+//! Simulation of imperfect serial links, for testing protocol implementations
+//!
+//! The wrappers of this module sit between a protocol and its transport (a pty, a mock, or a
+//! real port) and degrade the traffic in reproducible ways.
+
+use std::cmp;
+use std::collections::RingBuf;
+use std::default::Default;
+use std::io::{IoError, IoResult, TimedOut};
+use std::io::timer;
+use std::rand::{Rng, SeedableRng, XorShiftRng};
+use std::slice::bytes;
+use std::time::Duration;
+
+use clock::{RealTime, TimeSource, Virtual, nanoseconds};
+use {SerialIo, Settings, VirtualClock};
+
+/// How the bytes going through a `NoisyPort` are degraded
+///
+/// The probabilities apply to each byte independently, and range from 0 to 1.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Noise {
+    /// Probability that one bit of the byte is flipped
+    pub corrupt: f64,
+    /// Probability that the byte is lost
+    pub drop: f64,
+    /// Probability that the byte is received twice
+    pub duplicate: f64,
+    /// Probability that the byte is held back by `delay`
+    pub stall: f64,
+    pub delay: Duration,
+}
+
+impl Default for Noise {
+    /// A clean line
+    fn default() -> Noise {
+        Noise {
+            corrupt: 0.,
+            drop: 0.,
+            duplicate: 0.,
+            stall: 0.,
+            delay: Duration::zero(),
+        }
+    }
+}
+
+/// A transport that corrupts, drops, duplicates and delays bytes
+///
+/// The noise is drawn from a pseudo random generator seeded by the user, so a failing test
+/// fails the same way every time.
+pub struct NoisyPort<S> {
+    inner: S,
+    rng: XorShiftRng,
+    read_noise: Noise,
+    write_noise: Noise,
+    /// Received bytes that didn't fit in the buffer of the previous read
+    pending: Vec<u8>,
+}
+
+impl<S> NoisyPort<S> {
+    /// Wraps `inner`, without any noise yet
+    pub fn new(inner: S, seed: u32) -> NoisyPort<S> {
+        NoisyPort {
+            inner: inner,
+            rng: SeedableRng::from_seed([seed, 0x193A6754, 0xA8A7D469, 0x97830E05]),
+            read_noise: Default::default(),
+            write_noise: Default::default(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns a reference to the underlying transport
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying transport
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying transport
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Changes the noise applied to the received bytes
+    pub fn set_read_noise(&mut self, noise: Noise) {
+        self.read_noise = noise;
+    }
+
+    /// Changes the noise applied to the sent bytes
+    pub fn set_write_noise(&mut self, noise: Noise) {
+        self.write_noise = noise;
+    }
+}
+
+/// Applies `noise` to `data`, sleeping for the stalled bytes
+fn degrade(rng: &mut XorShiftRng, noise: &Noise, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+
+    for &byte in data.iter() {
+        if rng.gen::<f64>() < noise.stall {
+            timer::sleep(noise.delay);
+        }
+
+        if rng.gen::<f64>() < noise.drop {
+            continue
+        }
+
+        let byte = if rng.gen::<f64>() < noise.corrupt {
+            byte ^ 1 << rng.gen_range(0u, 8)
+        } else {
+            byte
+        };
+
+        out.push(byte);
+        if rng.gen::<f64>() < noise.duplicate {
+            out.push(byte);
+        }
+    }
+
+    out
+}
+
+impl<S: Reader> Reader for NoisyPort<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        // Reading again when all the bytes were dropped, an empty read isn't an answer
+        while self.pending.is_empty() {
+            let mut chunk = Vec::from_elem(buf.len(), 0u8);
+            let n = try!(self.inner.read(chunk.as_mut_slice()));
+
+            self.pending = degrade(&mut self.rng, &self.read_noise, chunk.slice_to(n));
+        }
+
+        let n = cmp::min(buf.len(), self.pending.len());
+        bytes::copy_memory(buf, self.pending.slice_to(n));
+        self.pending = self.pending.slice_from(n).to_vec();
+
+        Ok(n)
+    }
+}
+
+impl<S: SerialIo> SerialIo for NoisyPort<S> {
+    fn settings(&self) -> IoResult<Settings> {
+        self.inner.settings()
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.inner.configure(settings)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_timeout(timeout)
+    }
+}
+
+impl<S: Writer> Writer for NoisyPort<S> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let data = degrade(&mut self.rng, &self.write_noise, buf);
+
+        self.inner.write(data.as_slice())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+/// Characteristics of a slow link
+#[deriving(Clone, PartialEq, Show)]
+pub struct Link {
+    /// Delay between the arrival of the data and its delivery
+    pub latency: Duration,
+    /// Largest random delay added to the latency, drawn for each received chunk
+    pub jitter: Duration,
+    /// Bytes per second, `None` for an unlimited bandwidth
+    pub bandwidth: Option<uint>,
+}
+
+impl Link {
+    /// A link limited by its baud rate, with 10 bits per byte (8N1) and no latency
+    pub fn from_baud_rate(rate: uint) -> Link {
+        Link {
+            latency: Duration::zero(),
+            jitter: Duration::zero(),
+            bandwidth: Some(cmp::max(rate / 10, 1)),
+        }
+    }
+
+    /// Transmission time of a byte, in nanoseconds
+    fn byte_ns(&self) -> u64 {
+        self.bandwidth.map_or(0, |bandwidth| 1_000_000_000 / cmp::max(bandwidth, 1) as u64)
+    }
+}
+
+/// A transport that delivers the received data late, and transmits at a limited rate
+///
+/// Received bytes become readable after the latency (plus the jitter) of the link, and no
+/// faster than its bandwidth; a read whose timeout elapses first fails with a `TimedOut`
+/// error. Writes block for the transmission time of their data, like a real UART without
+/// buffer space. Application timeouts can be tuned against e.g. 1200 baud radio modems this
+/// way. The delays take real time, unless the port runs on a `VirtualClock`.
+pub struct SlowPort<S> {
+    inner: S,
+    link: Link,
+    rng: XorShiftRng,
+    /// Received bytes, with the time at which they become readable
+    queue: RingBuf<(u64, u8)>,
+    /// Time at which the last queued byte becomes readable, on the clock of the port
+    last_due: u64,
+    clock: TimeSource,
+}
+
+impl<S> SlowPort<S> {
+    /// Wraps `inner` in the `link`, `seed` drives the jitter
+    pub fn new(inner: S, link: Link, seed: u32) -> SlowPort<S> {
+        SlowPort {
+            inner: inner,
+            link: link,
+            rng: SeedableRng::from_seed([seed, 0x193A6754, 0xA8A7D469, 0x97830E05]),
+            queue: RingBuf::new(),
+            last_due: 0,
+            clock: RealTime,
+        }
+    }
+
+    /// Returns a reference to the underlying transport
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying transport
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying transport, discarding the data still in flight
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Runs the port on `clock`, the latency, the transmission times and the timeouts move it
+    /// forward instead of taking real time
+    ///
+    /// The data in flight is discarded.
+    pub fn set_clock(&mut self, clock: VirtualClock) {
+        self.clock = Virtual(clock);
+        self.queue.clear();
+        self.last_due = 0;
+    }
+
+    /// Changes the characteristics of the link, for the data received from now on
+    pub fn set_link(&mut self, link: Link) {
+        self.link = link;
+    }
+}
+
+impl<S: SerialIo> Reader for SlowPort<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        if self.queue.is_empty() {
+            let mut chunk = Vec::from_elem(buf.len(), 0u8);
+            let n = try!(self.inner.read(chunk.as_mut_slice()));
+
+            let jitter = nanoseconds(self.link.jitter);
+            let jitter = if jitter == 0 { 0 } else { self.rng.gen_range(0, jitter + 1) };
+            let arrival = self.clock.now() + nanoseconds(self.link.latency) + jitter;
+
+            for &byte in chunk.slice_to(n).iter() {
+                self.last_due = cmp::max(arrival, self.last_due + self.link.byte_ns());
+                self.queue.push_back((self.last_due, byte));
+            }
+        }
+
+        let due = match self.queue.front() {
+            None => return Ok(0),
+            Some(&(due, _)) => due,
+        };
+
+        let now = self.clock.now();
+        if due > now {
+            let wait = due - now;
+
+            match self.inner.timeout().map(nanoseconds) {
+                Some(timeout) if timeout < wait => {
+                    self.clock.sleep(timeout);
+
+                    return Err(IoError {
+                        kind: TimedOut,
+                        desc: "Read operation timed out",
+                        detail: None,
+                    })
+                },
+                _ => self.clock.sleep(wait),
+            }
+        }
+
+        let now = self.clock.now();
+        let mut n = 0;
+        while n < buf.len() {
+            match self.queue.front() {
+                Some(&(due, byte)) if due <= now => buf[n] = byte,
+                _ => break,
+            }
+
+            self.queue.pop_front();
+            n += 1;
+        }
+
+        Ok(n)
+    }
+}
+
+impl<S: SerialIo> SerialIo for SlowPort<S> {
+    fn settings(&self) -> IoResult<Settings> {
+        self.inner.settings()
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.inner.configure(settings)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_timeout(timeout)
+    }
+}
+
+impl<S: Writer> Writer for SlowPort<S> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        try!(self.inner.write(buf));
+
+        let nanos = self.link.byte_ns() * buf.len() as u64;
+        if nanos > 0 {
+            self.clock.sleep(nanos);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,301 @@
+//! slcan, the ASCII protocol of the Lawicel CAN adapters
+//!
+//! Commands and frames are carried as lines terminated by CR. The adapter answers a command
+//! with CR when it succeeds and BEL when it fails; received frames are interleaved with the
+//! answers once the channel is open.
+
+use std::cmp;
+use std::collections::RingBuf;
+use std::io::{InvalidInput, IoError, IoResult, OtherIoError};
+
+use hex;
+
+const CR: u8 = b'\r';
+const BEL: u8 = 0x07;
+
+const HEX_DIGITS: &'static [u8] = b"0123456789ABCDEF";
+
+/// Largest CAN identifiers, standard and extended
+const MAX_STANDARD_ID: u32 = 0x7FF;
+const MAX_EXTENDED_ID: u32 = 0x1FFFFFFF;
+
+/// Largest line kept while waiting for its CR
+const MAX_LINE_LEN: uint = 64;
+
+/// Bit rates of the CAN bus, selected with the `S0` to `S8` commands
+#[deriving(Clone, PartialEq, Show)]
+pub enum Bitrate {
+    Rate10K = 0,
+    Rate20K = 1,
+    Rate50K = 2,
+    Rate100K = 3,
+    Rate125K = 4,
+    Rate250K = 5,
+    Rate500K = 6,
+    Rate800K = 7,
+    Rate1M = 8,
+}
+
+/// A CAN frame
+#[deriving(Clone, PartialEq, Show)]
+pub struct Frame {
+    /// 11-bit identifier, or 29-bit identifier for the extended frames
+    pub id: u32,
+    pub extended: bool,
+    /// Remote transmission request, the frame carries no data and `data` only gives its length
+    pub remote: bool,
+    /// Up to 8 bytes
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    /// Creates a standard data frame
+    pub fn new(id: u32, data: &[u8]) -> Frame {
+        Frame {
+            id: id,
+            extended: false,
+            remote: false,
+            data: data.to_vec(),
+        }
+    }
+
+    /// Creates an extended data frame
+    pub fn new_extended(id: u32, data: &[u8]) -> Frame {
+        Frame { extended: true, ..Frame::new(id, data) }
+    }
+
+    /// Encodes the frame as a `t`, `T`, `r` or `R` command, without the CR
+    ///
+    /// Returns `None` if the identifier or the data is too long.
+    pub fn encode(&self) -> Option<Vec<u8>> {
+        let (max_id, digits) = if self.extended {
+            (MAX_EXTENDED_ID, 8)
+        } else {
+            (MAX_STANDARD_ID, 3)
+        };
+
+        if self.id > max_id || self.data.len() > 8 {
+            return None
+        }
+
+        let mut line = vec![match (self.extended, self.remote) {
+            (false, false) => b't',
+            (true, false) => b'T',
+            (false, true) => b'r',
+            (true, true) => b'R',
+        }];
+
+        for i in range(0, digits).rev() {
+            line.push(HEX_DIGITS[(self.id >> (4 * i) & 0x0F) as uint]);
+        }
+        line.push(b'0' + self.data.len() as u8);
+
+        if !self.remote {
+            for &byte in self.data.iter() {
+                line.push(HEX_DIGITS[(byte >> 4) as uint]);
+                line.push(HEX_DIGITS[(byte & 0x0F) as uint]);
+            }
+        }
+
+        Some(line)
+    }
+
+    /// Parses a `t`, `T`, `r` or `R` line, without its CR
+    ///
+    /// A trailing timestamp (4 hexadecimal digits, when enabled on the adapter) is ignored.
+    pub fn parse(line: &[u8]) -> Option<Frame> {
+        let (extended, remote) = match line.head() {
+            Some(&b't') => (false, false),
+            Some(&b'T') => (true, false),
+            Some(&b'r') => (false, true),
+            Some(&b'R') => (true, true),
+            _ => return None,
+        };
+
+        let digits = if extended { 8 } else { 3 };
+        if line.len() < digits + 2 {
+            return None
+        }
+
+        let mut id = 0u32;
+        for &digit in line.slice(1, digits + 1).iter() {
+            match hex::value(digit) {
+                None => return None,
+                Some(value) => id = id << 4 | value as u32,
+            }
+        }
+
+        let len = match line[digits + 1] {
+            digit @ b'0'...b'8' => (digit - b'0') as uint,
+            _ => return None,
+        };
+
+        let hex = line.slice_from(digits + 2);
+        let data_len = if remote { 0 } else { 2 * len };
+        if hex.len() != data_len && hex.len() != data_len + 4 {
+            return None
+        }
+
+        let mut data = Vec::with_capacity(len);
+        if remote {
+            data.grow(len, 0);
+        } else {
+            for pair in hex.slice_to(data_len).chunks(2) {
+                match (hex::value(pair[0]), hex::value(pair[1])) {
+                    (Some(high), Some(low)) => data.push(high << 4 | low),
+                    _ => return None,
+                }
+            }
+        }
+
+        if id > if extended { MAX_EXTENDED_ID } else { MAX_STANDARD_ID } {
+            return None
+        }
+
+        Some(Frame {
+            id: id,
+            extended: extended,
+            remote: remote,
+            data: data,
+        })
+    }
+}
+
+/// An slcan adapter connected to a port
+pub struct Adapter<S> {
+    port: S,
+    /// Received bytes that don't make a complete line yet
+    buf: Vec<u8>,
+    pending: RingBuf<Frame>,
+}
+
+impl<S: Reader + Writer> Adapter<S> {
+    /// Talks slcan through `port`
+    pub fn new(port: S) -> Adapter<S> {
+        Adapter {
+            port: port,
+            buf: Vec::new(),
+            pending: RingBuf::new(),
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.port
+    }
+
+    /// Unwraps the port
+    pub fn into_inner(self) -> S {
+        self.port
+    }
+
+    /// Closes the CAN channel
+    pub fn close(&mut self) -> IoResult<()> {
+        self.command(b"C").map(|_| ())
+    }
+
+    /// Opens the CAN channel, the bit rate must be selected first
+    pub fn open(&mut self) -> IoResult<()> {
+        self.command(b"O").map(|_| ())
+    }
+
+    /// Opens the CAN channel in listen only mode, the adapter doesn't acknowledge the frames
+    pub fn open_listen_only(&mut self) -> IoResult<()> {
+        self.command(b"L").map(|_| ())
+    }
+
+    /// Receives the next frame
+    ///
+    /// Use a timeout on the port to bound the wait.
+    pub fn receive(&mut self) -> IoResult<Frame> {
+        loop {
+            match self.pending.pop_front() {
+                None => {},
+                Some(frame) => return Ok(frame),
+            }
+
+            match try!(self.read_line()) {
+                Some(line) => match Frame::parse(line.as_slice()) {
+                    None => {},
+                    Some(frame) => return Ok(frame),
+                },
+                None => {},
+            }
+        }
+    }
+
+    /// Sends a frame
+    ///
+    /// Frames with an identifier or data that doesn't fit are refused with an `InvalidInput`
+    /// error.
+    pub fn send(&mut self, frame: &Frame) -> IoResult<()> {
+        match frame.encode() {
+            None => Err(IoError {
+                kind: InvalidInput,
+                desc: "CAN frame can't be encoded",
+                detail: Some(frame.to_string()),
+            }),
+            Some(line) => self.command(line.as_slice()).map(|_| ()),
+        }
+    }
+
+    /// Selects the bit rate of the bus, while the channel is closed
+    pub fn set_bitrate(&mut self, bitrate: Bitrate) -> IoResult<()> {
+        self.command(&[b'S', b'0' + bitrate as u8]).map(|_| ())
+    }
+
+    /// Returns the hardware and software versions reported by the adapter
+    pub fn version(&mut self) -> IoResult<String> {
+        let line = try!(self.command(b"V"));
+
+        Ok(String::from_utf8_lossy(line.slice_from(cmp::min(1, line.len()))).into_string())
+    }
+
+    /// Sends `command`, returns the answer without its CR
+    ///
+    /// Frames received while waiting for the answer are kept for `receive`.
+    fn command(&mut self, command: &[u8]) -> IoResult<Vec<u8>> {
+        let mut line = command.to_vec();
+        line.push(CR);
+        try!(self.port.write(line.as_slice()));
+
+        loop {
+            match try!(self.read_line()) {
+                None => return Err(IoError {
+                    kind: OtherIoError,
+                    desc: "Command refused by the adapter",
+                    detail: Some(String::from_utf8_lossy(command).into_string()),
+                }),
+                Some(line) => match Frame::parse(line.as_slice()) {
+                    None => return Ok(line),
+                    Some(frame) => self.pending.push_back(frame),
+                },
+            }
+        }
+    }
+
+    /// Reads the next line, `None` if the adapter answered BEL
+    fn read_line(&mut self) -> IoResult<Option<Vec<u8>>> {
+        loop {
+            match self.buf.iter().position(|&byte| byte == CR || byte == BEL) {
+                None => {},
+                Some(end) => {
+                    let line = self.buf.slice_to(end).to_vec();
+                    let bell = self.buf[end] == BEL;
+                    self.buf = self.buf.slice_from(end + 1).to_vec();
+
+                    return Ok(if bell { None } else { Some(line) })
+                },
+            }
+
+            // Garbage without any terminator
+            if self.buf.len() > MAX_LINE_LEN {
+                self.buf.clear();
+            }
+
+            let mut chunk = [0u8, ..256];
+            let n = try!(self.port.read(&mut chunk));
+            self.buf.push_all(chunk.slice_to(n));
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use libc::c_int;
+use native::io::file::FileDesc;
+use std::io::{EndOfFile, IoResult};
+
+use SerialPort;
+
+/// A writer backed by a file descriptor, that `copy` can move the data to inside the kernel
+pub trait Descriptor {
+    /// Returns the file descriptor, which stays owned by the writer
+    fn descriptor(&self) -> c_int;
+}
+
+impl Descriptor for FileDesc {
+    fn descriptor(&self) -> c_int {
+        self.fd()
+    }
+}
+
+impl Descriptor for SerialPort {
+    fn descriptor(&self) -> c_int {
+        self.fd
+    }
+}
+
+/// Copies the data received by `port` to `out` until the port is closed, returns the number of
+/// bytes copied
+///
+/// On Linux the data is moved inside the kernel with `splice`, through a pipe, sparing the
+/// copies to and from user space that burn CPU when logging a multi-megabaud stream. Elsewhere,
+/// or when the kernel can't splice the files, the data is read and written as usual. The reads
+/// still follow the timeout of the port: a time out ends the copy with a `TimedOut` error, the
+/// bytes copied so far are in the statistics of the port.
+///
+/// ```ignore
+/// let mut log = FileDesc::new(try!(open_log()), true);
+/// try!(serial::copy(&mut port, &mut log));
+/// ```
+pub fn copy<W: Writer + Descriptor>(port: &mut SerialPort, out: &mut W) -> IoResult<u64> {
+    let mut copied = 0u64;
+
+    // The bytes already taken from the device
+    if !port.peeked.is_empty() {
+        try!(out.write(port.peeked.as_slice()));
+        copied += port.peeked.len() as u64;
+        port.peeked.clear();
+    }
+
+    match try!(splice_all(port, out, &mut copied)) {
+        true => Ok(copied),
+        false => copy_buffered(port, out, copied),
+    }
+}
+
+/// Copies with plain reads and writes, from `copied` bytes on
+fn copy_buffered<W: Writer>(port: &mut SerialPort, out: &mut W, copied: u64) -> IoResult<u64> {
+    let mut buf = [0u8, ..4096];
+    let mut copied = copied;
+
+    loop {
+        let n = match port.read(&mut buf) {
+            Err(ref e) if e.kind == EndOfFile => return Ok(copied),
+            Err(e) => return Err(e),
+            Ok(n) => n,
+        };
+
+        try!(out.write(buf.slice_to(n)));
+        copied += n as u64;
+    }
+}
+
+/// Splices the data until the port is closed, returns `false` if the kernel can't splice
+/// these files, once the pipe is empty
+#[cfg(target_os = "linux")]
+fn splice_all<W: Writer + Descriptor>(port: &mut SerialPort, out: &mut W, copied: &mut u64)
+                                      -> IoResult<bool> {
+    use libc::{c_uint, c_void, size_t, ssize_t};
+    use libc::consts::os::posix88::{EINTR, EINVAL, ENOSYS};
+    use libc::funcs::posix88::unistd::{pipe, read};
+    use std::io::IoError;
+    use std::os;
+    use std::ptr;
+
+    use cancel::Fd;
+    use termios::FAILURE;
+
+    const SPLICE_F_MOVE: c_uint = 1;
+    /// Bytes moved by a splice at most, the default capacity of a pipe
+    const CHUNK: size_t = 65536;
+
+    extern {
+        fn splice(fd_in: c_int, off_in: *mut c_void, fd_out: c_int, off_out: *mut c_void,
+                  len: size_t, flags: c_uint) -> ssize_t;
+    }
+
+    let mut fds = [0 as c_int, ..2];
+    match unsafe { pipe(fds.as_mut_ptr()) } {
+        FAILURE => return Err(IoError::last_error()),
+        _ => {},
+    }
+    let (reader, writer) = (Fd(fds[0]), Fd(fds[1]));
+
+    loop {
+        // Honors the timeout, the deadline and the cancellations of the port
+        let ready = port.wait_readable();
+        match ready {
+            Err(ref e) if e.kind == EndOfFile => return Ok(true),
+            _ => {},
+        }
+        try!(ready);
+
+        let received = unsafe {
+            splice(port.fd, ptr::null_mut(), writer.0, ptr::null_mut(), CHUNK, SPLICE_F_MOVE)
+        };
+        let received = match received {
+            -1 => match os::errno() as c_int {
+                EINTR => continue,
+                EINVAL | ENOSYS => return Ok(false),
+                errno => return Err(IoError::from_errno(errno as uint, true)),
+            },
+            0 => return Ok(true),
+            n => n,
+        };
+        port.count_read(&Ok(received as uint));
+
+        let mut pending = received;
+        while pending > 0 {
+            let sent = unsafe {
+                splice(reader.0, ptr::null_mut(), out.descriptor(), ptr::null_mut(),
+                       pending as size_t, SPLICE_F_MOVE)
+            };
+
+            match sent {
+                -1 => match os::errno() as c_int {
+                    EINTR => {},
+                    EINVAL | ENOSYS => {
+                        // `out` can't be spliced to, what's in the pipe is written as usual
+                        let mut buf = Vec::from_elem(pending as uint, 0u8);
+                        let n = unsafe {
+                            read(reader.0, buf.as_mut_ptr() as *mut c_void, pending as size_t)
+                        };
+                        if n < 0 {
+                            return Err(IoError::last_error())
+                        }
+
+                        try!(out.write(buf.slice_to(n as uint)));
+                        *copied += n as u64;
+                        return Ok(false)
+                    },
+                    errno => return Err(IoError::from_errno(errno as uint, true)),
+                },
+                n => {
+                    pending -= n;
+                    *copied += n as u64;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn splice_all<W: Writer + Descriptor>(_: &mut SerialPort, _: &mut W, _: &mut u64)
+                                      -> IoResult<bool> {
+    Ok(false)
+}
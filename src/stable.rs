@@ -0,0 +1,262 @@
+//! Stable names of the devices, which survive reboots and replugs
+//!
+//! Kernel names like `/dev/ttyUSB0` are handed out in detection order, so they change when
+//! adapters are plugged in another order. The stable names identify the adapter instead: on
+//! Linux they're the udev links of `/dev/serial/by-id` (vendor, model and serial number) and
+//! `/dev/serial/by-path` (the USB or PCI port), on macOS the IOKit registry path of the serial
+//! service. Configurations store the stable name, and resolve it with `find` when opening.
+//!
+//! ```ignore
+//! let device = try!(stable::find("usb-FTDI_FT232R_USB_UART_A50285BI-if00-port0"));
+//! let port = try!(SerialPort::open(&device, ReadWrite));
+//! ```
+
+use std::io::{FileNotFound, IoError, IoResult};
+
+/// Directories of the udev links, in order of preference
+#[cfg(target_os = "linux")]
+const DIRECTORIES: [&'static str, ..2] = ["/dev/serial/by-id", "/dev/serial/by-path"];
+
+/// Returns the device node of the stable `name`
+///
+/// On Linux `name` is a link of `/dev/serial/by-id` or `/dev/serial/by-path`, given as a full
+/// path or just its file name (`by-id` is searched first). On macOS it's an IOKit registry path,
+/// and the device is the callout one (`/dev/cu.*`). Fails with `FileNotFound` when no device
+/// has this name, e.g. because the adapter is unplugged.
+#[cfg(target_os = "linux")]
+pub fn find(name: &str) -> IoResult<Path> {
+    use std::io::fs;
+    use std::io::fs::PathExtensions;
+
+    let candidates = if name.starts_with("/") {
+        vec![Path::new(name)]
+    } else {
+        DIRECTORIES.iter().map(|dir| Path::new(*dir).join(name)).collect()
+    };
+
+    match candidates.into_iter().find(|link| link.exists()) {
+        None => Err(not_found(name)),
+        Some(link) => match fs::readlink(&link) {
+            // udev links are relative, e.g. `../../ttyUSB0`
+            Ok(target) => Ok(link.dir_path().join(target)),
+            Err(_) => Ok(link),
+        },
+    }
+}
+
+/// Returns the device node of the stable `name`
+///
+/// On Linux `name` is a link of `/dev/serial/by-id` or `/dev/serial/by-path`, given as a full
+/// path or just its file name (`by-id` is searched first). On macOS it's an IOKit registry path,
+/// and the device is the callout one (`/dev/cu.*`). Fails with `FileNotFound` when no device
+/// has this name, e.g. because the adapter is unplugged.
+#[cfg(target_os = "macos")]
+pub fn find(name: &str) -> IoResult<Path> {
+    let services = try!(iokit::serial_services());
+
+    match services.into_iter().find(|service| service.registry_path.as_slice() == name) {
+        Some(service) => Ok(service.callout),
+        None => Err(not_found(name)),
+    }
+}
+
+/// Returns the stable name of `device`, `None` if it has none (e.g. a pty)
+///
+/// On Linux it's the full path of the udev link, from `/dev/serial/by-id` when there's one
+/// there; links and `/dev/ttyUSBn` names are both accepted as `device`. On macOS it's the IOKit
+/// registry path, for both the callout and the dial-in device.
+#[cfg(target_os = "linux")]
+pub fn stable_name(device: &Path) -> IoResult<Option<String>> {
+    use std::io::fs;
+
+    let device = try!(device_number(device));
+
+    for dir in DIRECTORIES.iter() {
+        let mut links = match fs::readdir(&Path::new(*dir)) {
+            // No adapter of this kind is plugged in
+            Err(_) => continue,
+            Ok(links) => links,
+        };
+        links.sort();
+
+        for link in links.iter() {
+            match device_number(link) {
+                Ok(number) if number == device => {
+                    return Ok(link.as_str().map(|link| link.to_string()))
+                },
+                _ => {},
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the stable name of `device`, `None` if it has none (e.g. a pty)
+///
+/// On Linux it's the full path of the udev link, from `/dev/serial/by-id` when there's one
+/// there; links and `/dev/ttyUSBn` names are both accepted as `device`. On macOS it's the IOKit
+/// registry path, for both the callout and the dial-in device.
+#[cfg(target_os = "macos")]
+pub fn stable_name(device: &Path) -> IoResult<Option<String>> {
+    let services = try!(iokit::serial_services());
+
+    Ok(services.into_iter()
+               .find(|service| service.callout == *device || service.dialin == *device)
+               .map(|service| service.registry_path))
+}
+
+/// Returns the device number of the character device at `path`, following links
+#[cfg(target_os = "linux")]
+fn device_number(path: &Path) -> IoResult<u64> {
+    use libc;
+    use libc::funcs::posix88::stat_::stat;
+    use std::mem;
+
+    use termios::FAILURE;
+
+    let mut info: libc::stat = unsafe { mem::zeroed() };
+    match path.with_c_str(|path| unsafe { stat(path, &mut info) }) {
+        FAILURE => Err(IoError::last_error()),
+        _ => Ok(info.st_rdev as u64),
+    }
+}
+
+fn not_found(name: &str) -> IoError {
+    IoError {
+        kind: FileNotFound,
+        desc: "No device has this stable name",
+        detail: Some(name.to_string()),
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod iokit {
+    use libc::{c_char, c_long, c_void};
+    use std::c_str::CString;
+    use std::io::{IoError, IoResult, OtherIoError};
+    use std::ptr;
+
+    type CFTypeRef = *const c_void;
+    type IoObject = u32;
+
+    const KERN_SUCCESS: i32 = 0;
+    const MASTER_PORT_DEFAULT: u32 = 0;
+    const UTF8: u32 = 0x08000100;
+    /// Size of an `io_string_t`
+    const IO_STRING_LEN: uint = 512;
+
+    #[link(name = "IOKit", kind = "framework")]
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern {
+        fn IOServiceMatching(name: *const c_char) -> CFTypeRef;
+        fn IOServiceGetMatchingServices(master: u32, matching: CFTypeRef,
+                                        iterator: *mut IoObject) -> i32;
+        fn IOIteratorNext(iterator: IoObject) -> IoObject;
+        fn IOObjectRelease(object: IoObject) -> i32;
+        fn IORegistryEntryGetPath(entry: IoObject, plane: *const c_char,
+                                  path: *mut c_char) -> i32;
+        fn IORegistryEntryCreateCFProperty(entry: IoObject, key: CFTypeRef,
+                                           allocator: CFTypeRef, options: u32) -> CFTypeRef;
+        fn CFStringCreateWithCString(allocator: CFTypeRef, string: *const c_char,
+                                     encoding: u32) -> CFTypeRef;
+        fn CFStringGetCString(string: CFTypeRef, buf: *mut c_char, size: c_long,
+                              encoding: u32) -> u8;
+        fn CFRelease(object: CFTypeRef);
+    }
+
+    /// A serial service of the registry, and its devices
+    pub struct Service {
+        pub registry_path: String,
+        /// `/dev/cu.*`
+        pub callout: Path,
+        /// `/dev/tty.*`
+        pub dialin: Path,
+    }
+
+    /// Lists the `IOSerialBSDClient` services
+    pub fn serial_services() -> IoResult<Vec<Service>> {
+        let mut iterator = 0;
+        let matching = "IOSerialBSDClient".with_c_str(|name| unsafe { IOServiceMatching(name) });
+
+        // The matching dictionary is consumed
+        let code = unsafe {
+            IOServiceGetMatchingServices(MASTER_PORT_DEFAULT, matching, &mut iterator)
+        };
+        match code {
+            KERN_SUCCESS => {},
+            code => return Err(IoError {
+                kind: OtherIoError,
+                desc: "Couldn't list the serial services",
+                detail: Some(format!("IOKit error {}", code)),
+            }),
+        }
+
+        let mut services = Vec::new();
+        loop {
+            let entry = unsafe { IOIteratorNext(iterator) };
+            if entry == 0 {
+                break
+            }
+
+            match (registry_path(entry), string_property(entry, "IOCalloutDevice"),
+                   string_property(entry, "IODialinDevice")) {
+                (Some(registry_path), Some(callout), Some(dialin)) => services.push(Service {
+                    registry_path: registry_path,
+                    callout: Path::new(callout),
+                    dialin: Path::new(dialin),
+                }),
+                _ => {},
+            }
+
+            unsafe { IOObjectRelease(entry) };
+        }
+        unsafe { IOObjectRelease(iterator) };
+
+        Ok(services)
+    }
+
+    fn registry_path(entry: IoObject) -> Option<String> {
+        let mut buf = [0 as c_char, ..IO_STRING_LEN];
+
+        let code = "IOService".with_c_str(|plane| unsafe {
+            IORegistryEntryGetPath(entry, plane, buf.as_mut_ptr())
+        });
+
+        match code {
+            KERN_SUCCESS => c_string(&buf),
+            _ => None,
+        }
+    }
+
+    fn string_property(entry: IoObject, key: &str) -> Option<String> {
+        let mut buf = [0 as c_char, ..IO_STRING_LEN];
+
+        let key = key.with_c_str(|key| unsafe {
+            CFStringCreateWithCString(ptr::null(), key, UTF8)
+        });
+        let value = unsafe { IORegistryEntryCreateCFProperty(entry, key, ptr::null(), 0) };
+        unsafe { CFRelease(key) };
+
+        if value.is_null() {
+            return None
+        }
+
+        let ok = unsafe {
+            CFStringGetCString(value, buf.as_mut_ptr(), buf.len() as c_long, UTF8)
+        };
+        unsafe { CFRelease(value) };
+
+        match ok {
+            0 => None,
+            _ => c_string(&buf),
+        }
+    }
+
+    /// Copies the nul terminated UTF-8 string of `buf`
+    fn c_string(buf: &[c_char, ..IO_STRING_LEN]) -> Option<String> {
+        let string = unsafe { CString::new(buf.as_ptr(), false) };
+
+        string.as_str().map(|s| s.to_string())
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::{EndOfFile, IoResult};
+use std::time::Duration;
+
+use capture::Record;
+use poll;
+use trace::{Direction, Received, Sent};
+use SerialPort;
+
+/// Size of the reads of each port
+const CHUNK_SIZE: uint = 256;
+
+/// Listens to a link between two devices through two ports, one per direction
+///
+/// The RX of each port is wired to the TX of one of the devices, the TX of the ports is left
+/// unconnected. The traffic of both directions is merged into a single capture, as seen from the
+/// first device: what it sends is tagged `tx`, what the second device sends `rx`. The records
+/// are timestamped when they're read, which happens as soon as data comes in, so the order of
+/// the exchanges is kept.
+///
+/// ```ignore
+/// let mut tap = Tap::new(try!(SerialPort::open(&first, Read)),
+///                        try!(SerialPort::open(&second, Read)));
+/// try!(tap.run(&mut try!(File::create(&Path::new("link.capture")))));
+/// ```
+pub struct Tap {
+    first: SerialPort,
+    second: SerialPort,
+}
+
+impl Tap {
+    /// Taps the link with `first`, which receives what the first device sends, and `second`,
+    /// which receives what the second device sends
+    ///
+    /// Both ports should be configured like the link beforehand.
+    pub fn new(first: SerialPort, second: SerialPort) -> Tap {
+        Tap {
+            first: first,
+            second: second,
+        }
+    }
+
+    /// Unwraps the two ports
+    pub fn into_inner(self) -> (SerialPort, SerialPort) {
+        (self.first, self.second)
+    }
+
+    /// Waits up to `timeout` for traffic in either direction, returns the records of what came
+    /// in, in time order
+    ///
+    /// No records are returned if the timeout elapsed first. `None` waits indefinitely.
+    pub fn poll(&mut self, timeout: Option<Duration>) -> IoResult<Vec<Record>> {
+        let (first, second) = try!(poll::wait_pair(self.first.fd, self.second.fd, poll::POLLIN,
+                                                    timeout));
+        let mut records = Vec::new();
+
+        if first {
+            records.push(try!(read_record(&mut self.first, Sent)));
+        }
+        if second {
+            records.push(try!(read_record(&mut self.second, Received)));
+        }
+
+        records.sort_by(|a, b| a.timestamp.precise_ns.cmp(&b.timestamp.precise_ns));
+
+        Ok(records)
+    }
+
+    /// Writes the capture of the link to `out`, until either port reaches EOF
+    pub fn run<W: Writer>(&mut self, out: &mut W) -> IoResult<()> {
+        loop {
+            let records = match self.poll(None) {
+                Err(ref e) if e.kind == EndOfFile => return Ok(()),
+                Err(e) => return Err(e),
+                Ok(records) => records,
+            };
+
+            for record in records.iter() {
+                try!(out.write_line(record.to_json().as_slice()));
+            }
+        }
+    }
+}
+
+/// Reads what `port` received, as a record tagged with `direction`
+fn read_record(port: &mut SerialPort, direction: Direction) -> IoResult<Record> {
+    let mut buf = [0u8, ..CHUNK_SIZE];
+    let (n, timestamp) = try!(port.read_timestamped(&mut buf));
+
+    Ok(Record {
+        direction: direction,
+        timestamp: timestamp,
+        data: buf.slice_to(n).to_vec(),
+    })
+}
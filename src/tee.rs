@@ -0,0 +1,115 @@
+//! Fan out of the incoming data to several consumers
+//!
+//! A `Tee` reads a port from its own task and hands every chunk to all its consumers, so a
+//! logger, a protocol parser and a live console can observe the same stream at once.
+//!
+//! ```ignore
+//! let mut tee = Tee::new();
+//! let frames = tee.subscribe();
+//! tee.add_writer(box try!(File::create(&Path::new("session.log"))));
+//! let stopped = tee.spawn(port);
+//! ```
+
+use std::io::{EndOfFile, IoError, TimedOut};
+use std::mem;
+
+/// Where a `Tee` sends the data
+enum Consumer {
+    ChannelConsumer(Sender<Vec<u8>>),
+    WriterConsumer(Box<Writer + Send>),
+}
+
+/// Copies the data read from a port to every registered consumer
+///
+/// The consumers are channels and writers, a consumer that goes away (a dropped receiver, a
+/// failed write) is removed while the others carry on. Time outs of the port are ignored, any
+/// other error stops the reads.
+pub struct Tee {
+    consumers: Vec<Consumer>,
+    /// Size of the read buffer
+    chunk_size: uint,
+}
+
+impl Tee {
+    /// A tee without consumers, reading up to 256 bytes at a time
+    pub fn new() -> Tee {
+        Tee {
+            consumers: Vec::new(),
+            chunk_size: 256,
+        }
+    }
+
+    /// Sets the largest chunk handed to the consumers
+    pub fn chunk_size(mut self, chunk_size: uint) -> Tee {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Adds a consumer, that receives the chunks in order through the returned channel
+    pub fn subscribe(&mut self) -> Receiver<Vec<u8>> {
+        let (sender, receiver) = channel();
+        self.consumers.push(ChannelConsumer(sender));
+
+        receiver
+    }
+
+    /// Adds a consumer, that gets the data written to it
+    pub fn add_writer(&mut self, writer: Box<Writer + Send>) {
+        self.consumers.push(WriterConsumer(writer));
+    }
+
+    /// Reads `port` until an error, and copies the data to the consumers
+    ///
+    /// Returns the error that stopped the reads, `EndOfFile` once the port is closed. Also
+    /// returns, with an `EndOfFile` error, when no consumer is left.
+    pub fn run<R: Reader>(mut self, mut port: R) -> IoError {
+        let mut buf = Vec::from_elem(self.chunk_size, 0u8);
+
+        while !self.consumers.is_empty() {
+            match port.read(buf.as_mut_slice()) {
+                Err(ref e) if e.kind == TimedOut => {},
+                Err(e) => return e,
+                Ok(n) => self.dispatch(buf.slice_to(n)),
+            }
+        }
+
+        IoError {
+            kind: EndOfFile,
+            desc: "No consumer left",
+            detail: None,
+        }
+    }
+
+    /// Runs the tee in a new task, see `run`
+    ///
+    /// The returned channel receives the error that stopped the reads.
+    pub fn spawn<R: Reader + Send>(self, port: R) -> Receiver<IoError> {
+        let (sender, receiver) = channel();
+
+        spawn(proc() {
+            let _ = sender.send_opt(self.run(port));
+        });
+
+        receiver
+    }
+
+    /// Hands `data` to every consumer, drops the consumers that went away
+    fn dispatch(&mut self, data: &[u8]) {
+        if data.is_empty() {
+            return
+        }
+
+        let consumers = mem::replace(&mut self.consumers, Vec::new());
+
+        for mut consumer in consumers.into_iter() {
+            let alive = match consumer {
+                ChannelConsumer(ref sender) => sender.send_opt(data.to_vec()).is_ok(),
+                WriterConsumer(ref mut writer) => writer.write(data).is_ok(),
+            };
+
+            if alive {
+                self.consumers.push(consumer);
+            }
+        }
+    }
+}
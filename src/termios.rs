@@ -2,10 +2,14 @@ use libc::{c_int, c_uchar};
 
 pub use self::os::{
     B0, B50, B75, B110, B134, B150, B200, B300, B600, B1200, B1800, B2400, B4800, B9600, B19200,
-    B38400, B57600, B115200, B230400, CRTSCTS, CS5, CS6, CS7, CS8, CSIZE, CSTOPB, IXOFF, IXON,
-    NCCS, PARODD, VMIN, VTIME, speed_t,
+    B38400, B57600, B115200, B230400, CLOCAL, CMSPAR, CRTSCTS, CS5, CS6, CS7, CS8, CSIZE, CSTOPB,
+    ECHO, ECHOCTL, ECHOE, ECHOK, ECHOKE, ECHONL, HUPCL, IXOFF, IXON, NCCS, PARODD, VMIN, VTIME,
+    speed_t,
 };
 
+#[cfg(target_os = "linux")]
+pub use self::os::{BOTHER, CBAUD, CIBAUD};
+
 #[cfg(target_os = "linux")]
 pub use self::os::{
     B460800, B500000, B576000, B921600, B1000000, B1152000, B1500000, B2000000, B2500000, B3000000,
@@ -15,15 +19,22 @@ pub use self::os::{
 #[cfg(target_os = "macos")]
 pub use self::os::{B7200, B14400, B28800, B76800};
 
-use self::os::tcflag_t;
+pub use self::os::tcflag_t;
 
 #[allow(non_camel_case_types)]
 pub type cc_t = c_uchar;
 
 pub const FAILURE: c_int = -1;
+pub const ICRNL: tcflag_t = 0x0100;
+pub const IGNCR: tcflag_t = 0x0080;
+pub const INLCR: tcflag_t = 0x0040;
+pub const ISTRIP: tcflag_t = 0x0020;
 pub const IXANY: tcflag_t = 0x0800;
 pub const PARENB: tcflag_t = 0x1000;
+pub const PARMRK: tcflag_t = 0x0008;
 pub const SUCCESS: c_int = 0;
+pub const TCSADRAIN: c_int = 1;
+pub const TCSAFLUSH: c_int = 2;
 pub const TCSANOW: c_int = 0;
 
 #[cfg(target_os = "linux")]
@@ -67,6 +78,12 @@ mod os {
     pub const B75: speed_t = 0x02;
     pub const B921600: speed_t = 0x1007;
     pub const B9600: speed_t = 0x0D;
+    pub const BOTHER: tcflag_t = 0x1000;
+    pub const CBAUD: tcflag_t = 0x100F;
+    pub const CIBAUD: tcflag_t = 0x100F0000;
+    pub const CLOCAL: tcflag_t = 0x0800;
+    /// Mark or space parity, the parity bit set after `PARODD`
+    pub const CMSPAR: tcflag_t = 0x40000000;
     pub const CRTSCTS: tcflag_t = 0x80000000;
     pub const CS5: tcflag_t = 0x00;
     pub const CS6: tcflag_t = 0x10;
@@ -74,6 +91,13 @@ mod os {
     pub const CS8: tcflag_t = 0x30;
     pub const CSIZE: tcflag_t = 0x30;
     pub const CSTOPB: tcflag_t = 0x40;
+    pub const ECHO: tcflag_t = 0x0008;
+    pub const ECHOCTL: tcflag_t = 0x0200;
+    pub const ECHOE: tcflag_t = 0x0010;
+    pub const ECHOK: tcflag_t = 0x0020;
+    pub const ECHOKE: tcflag_t = 0x0800;
+    pub const ECHONL: tcflag_t = 0x0040;
+    pub const HUPCL: tcflag_t = 0x0400;
     pub const IXOFF: tcflag_t = 0x1000;
     pub const IXON: tcflag_t = 0x0400;
     pub const NCCS: uint = 32;
@@ -115,6 +139,9 @@ mod os {
     pub const B75: speed_t = 75;
     pub const B76800: speed_t = 76800;
     pub const B9600: speed_t = 9600;
+    pub const CLOCAL: tcflag_t = 0x8000;
+    /// Not supported, no mark nor space parity
+    pub const CMSPAR: tcflag_t = 0;
     pub const CRTSCTS: tcflag_t = 0x020000 | 0x040000;
     pub const CS5: tcflag_t = 0x0000;
     pub const CS6: tcflag_t = 0x0100;
@@ -122,6 +149,13 @@ mod os {
     pub const CS8: tcflag_t = 0x0300;
     pub const CSIZE: tcflag_t = 0x0300;
     pub const CSTOPB: tcflag_t = 0x0400;
+    pub const ECHO: tcflag_t = 0x0008;
+    pub const ECHOCTL: tcflag_t = 0x0040;
+    pub const ECHOE: tcflag_t = 0x0002;
+    pub const ECHOK: tcflag_t = 0x0004;
+    pub const ECHOKE: tcflag_t = 0x0001;
+    pub const ECHONL: tcflag_t = 0x0010;
+    pub const HUPCL: tcflag_t = 0x4000;
     pub const IXOFF: tcflag_t = 0x0400;
     pub const IXON: tcflag_t = 0x0200;
     pub const NCCS: uint = 20;
@@ -133,10 +167,10 @@ mod os {
 #[repr(C)]
 pub struct Termios {
     pub c_iflag: tcflag_t,
-    c_oflag: tcflag_t,
+    pub c_oflag: tcflag_t,
     pub c_cflag: tcflag_t,
-    c_lflag: tcflag_t,
-    #[cfg(target_os = "linux")] c_line: cc_t,
+    pub c_lflag: tcflag_t,
+    #[cfg(target_os = "linux")] pub c_line: cc_t,
     pub c_cc: [cc_t, ..NCCS],
     pub c_ispeed: speed_t,
     pub c_ospeed: speed_t,
@@ -178,6 +212,7 @@ extern {
     pub fn cfsetispeed(termios: *mut Termios, speed: speed_t) -> c_int;
     pub fn cfsetospeed(termios: *mut Termios, speed: speed_t) -> c_int;
     pub fn cfsetspeed(termios: *mut Termios, speed: speed_t) -> c_int;
+    pub fn tcdrain(fd: c_int) -> c_int;
     pub fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
     pub fn tcsetattr(fd: c_int, optional_actions: c_int, termios: *const Termios) -> c_int;
 }
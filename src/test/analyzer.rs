@@ -0,0 +1,49 @@
+use time::Timespec;
+
+use analyzer::{Analyzer, AtDissector, ModbusRtuDissector};
+use capture::Record;
+use checksum::{Checksum, Crc16};
+use trace::{Direction, Received, Sent};
+use Timestamp;
+
+fn record(direction: Direction, data: &[u8]) -> Record {
+    Record {
+        direction: direction,
+        timestamp: Timestamp {
+            time: Timespec::new(1418220000, 123456789),
+            precise_ns: 0,
+        },
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn analyze_capture() {
+    let mut analyzer = Analyzer::new();
+    analyzer.register(box AtDissector::new());
+    analyzer.register(box ModbusRtuDissector::new());
+
+    let mut adu = vec![0x11, 0x03, 0x00, 0x6B, 0x00, 0x03];
+    let mut crc = Crc16::modbus();
+    crc.update(adu.as_slice());
+    adu.push_all(crc.bytes().as_slice());
+
+    let records = [
+        record(Sent, b"AT+CSQ\r"),
+        record(Received, b"AT+CSQ\r\r\n+CSQ: 23,99\r\n\r\nOK"),
+        record(Received, b"\r\n"),
+        record(Sent, adu.as_slice()),
+    ];
+
+    let events: Vec<String> = analyzer.analyze_capture(&records).iter().map(|event| {
+        event.to_string()
+    }).collect();
+
+    assert_eq!(events, vec![
+        "1418220000.123456 TX at: command AT+CSQ".to_string(),
+        "1418220000.123456 RX at: echo AT+CSQ".to_string(),
+        "1418220000.123456 RX at: info +CSQ: 23,99".to_string(),
+        "1418220000.123456 RX at: result OK".to_string(),
+        "1418220000.123456 TX modbus: unit 17 ReadHoldingRegisters(107, 3)".to_string(),
+    ]);
+}
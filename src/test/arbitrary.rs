@@ -0,0 +1,6 @@
+use Settings;
+
+#[quickcheck]
+fn diff_is_empty_for_equal_settings(a: Settings, b: Settings) -> bool {
+    a.diff(&a).is_empty() && a.diff(&b).is_empty() == (a == b)
+}
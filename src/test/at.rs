@@ -0,0 +1,162 @@
+use std::io::{InvalidInput, TimedOut};
+use std::time::Duration;
+
+use at::{CmeError, FinalResult, OkResult, Session, Sms, TextMode, decode_gsm7, decode_ucs2};
+use at::{encode_gsm7, encode_ucs2, pack_septets, parse_deliver_pdu, submit_pdu, unpack_septets};
+use SerialPort;
+
+#[test]
+fn command() {
+    let (mut modem, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    spawn(proc() {
+        match modem.read_exact(b"AT+CSQ\r".len()) {
+            Err(e) => panic!("modem: Couldn't read command ({})", e),
+            Ok(command) => assert_eq!(command.as_slice(), b"AT+CSQ\r"),
+        }
+
+        // Echo, information line, URC, then the final result
+        let response = "AT+CSQ\r\r\n+CSQ: 20,99\r\n\r\n+CMTI: \"SM\",3\r\n\r\nOK\r\n";
+        match modem.write_str(response) {
+            Err(e) => panic!("modem: Couldn't send response ({})", e),
+            Ok(_) => {},
+        }
+    });
+
+    let (tx, rx) = channel();
+    let mut session = Session::new(port);
+    session.on_urc("+CMTI:", box tx);
+
+    let response = match session.command("AT+CSQ", Duration::seconds(1)) {
+        Err(e) => panic!("Couldn't execute command ({})", e),
+        Ok(response) => response,
+    };
+
+    assert_eq!(response.lines, vec!["+CSQ: 20,99".to_string()]);
+    assert_eq!(response.result, OkResult);
+    assert_eq!(rx.recv(), "+CMTI: \"SM\",3".to_string());
+}
+
+#[test]
+fn final_result() {
+    assert_eq!(FinalResult::parse("+CME ERROR: 10"), Some(CmeError("10".to_string())));
+    assert_eq!(FinalResult::parse("+CSQ: 20,99"), None);
+}
+
+#[test]
+fn timeout() {
+    let (_modem, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let mut session = Session::new(port);
+
+    match session.command("AT", Duration::milliseconds(100)) {
+        Err(ref e) if e.kind == TimedOut => {},
+        got => panic!("Expected a time out, got {}", got),
+    }
+}
+
+#[test]
+fn deliver_pdu() {
+    let pdu = [
+        0x07, 0x91, 0x72, 0x83, 0x01, 0x00, 0x10, 0xF5, 0x04, 0x0B, 0xC8, 0x72, 0x38, 0x88, 0x09,
+        0x00, 0xF1, 0x00, 0x00, 0x99, 0x30, 0x92, 0x51, 0x61, 0x95, 0x80, 0x0A, 0xE8, 0x32, 0x9B,
+        0xFD, 0x46, 0x97, 0xD9, 0xEC, 0x37,
+    ];
+
+    assert_eq!(parse_deliver_pdu(pdu.as_slice()), Some(Sms {
+        sender: "27838890001".to_string(),
+        timestamp: "99/03/29,15:16:59+08".to_string(),
+        text: "hellohello".to_string(),
+    }));
+    assert_eq!(parse_deliver_pdu(pdu.slice_to(20)), None);
+}
+
+#[test]
+fn gsm7() {
+    let septets = encode_gsm7("hellohello").unwrap();
+    let packed = pack_septets(septets.as_slice());
+
+    let expected = [0xE8, 0x32, 0x9B, 0xFD, 0x46, 0x97, 0xD9, 0xEC, 0x37];
+
+    assert_eq!(packed.as_slice(), expected.as_slice());
+    assert_eq!(encode_gsm7("[€]").unwrap(), vec![0x1B, 0x3C, 0x1B, 0x65, 0x1B, 0x3E]);
+    assert_eq!(decode_gsm7(encode_gsm7("Å@{x}").unwrap().as_slice()), "Å@{x}".to_string());
+    assert_eq!(encode_gsm7("日本"), None);
+}
+
+#[quickcheck]
+fn septets(septets: Vec<u8>) -> bool {
+    let septets: Vec<u8> = septets.iter().map(|&septet| septet & 0x7F).collect();
+    let packed = pack_septets(septets.as_slice());
+
+    unpack_septets(packed.as_slice(), septets.len()) == septets
+}
+
+#[test]
+fn send_sms() {
+    let (mut modem, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    spawn(proc() {
+        let exchanges: [(&[u8], &str), ..3] = [
+            (b"AT+CMGF=1\r", "\r\nOK\r\n"),
+            (b"AT+CMGS=\"+123\"\r", "\r\n> "),
+            (b"hi\x1a", "\r\n+CMGS: 7\r\n\r\nOK\r\n"),
+        ];
+
+        for &(expected, response) in exchanges.iter() {
+            match modem.read_exact(expected.len()) {
+                Err(e) => panic!("modem: Couldn't read command ({})", e),
+                Ok(command) => assert_eq!(command.as_slice(), expected),
+            }
+
+            match modem.write_str(response) {
+                Err(e) => panic!("modem: Couldn't send response ({})", e),
+                Ok(_) => {},
+            }
+        }
+    });
+
+    let mut session = Session::new(port);
+
+    match session.send_sms("+123", "hi", TextMode, Duration::seconds(1)) {
+        Err(e) => panic!("Couldn't send SMS ({})", e),
+        Ok(reference) => assert_eq!(reference, 7),
+    }
+}
+
+#[test]
+fn submit() {
+    let pdu = match submit_pdu("+46708251358", "hellohello") {
+        Err(e) => panic!("Couldn't encode PDU ({})", e),
+        Ok(pdu) => pdu,
+    };
+
+    let expected = [
+        0x00, 0x11, 0x00, 0x0B, 0x91, 0x64, 0x07, 0x28, 0x15, 0x53, 0xF8, 0x00, 0x00, 0xAA, 0x0A,
+        0xE8, 0x32, 0x9B, 0xFD, 0x46, 0x97, 0xD9, 0xEC, 0x37,
+    ];
+    assert_eq!(pdu.as_slice(), expected.as_slice());
+
+    match submit_pdu("12a", "hi") {
+        Err(ref e) if e.kind == InvalidInput => {},
+        got => panic!("Expected an invalid input error, got {}", got),
+    }
+}
+
+#[test]
+fn ucs2() {
+    let bytes = encode_ucs2("日本");
+
+    assert_eq!(bytes, vec![0x65, 0xE5, 0x67, 0x2C]);
+    assert_eq!(decode_ucs2(bytes.as_slice()), Some("日本".to_string()));
+    assert_eq!(decode_ucs2([0x65].as_slice()), None);
+}
@@ -0,0 +1,56 @@
+use std::io::{MemReader, MemWriter};
+use time::Timespec;
+
+use capture::{CaptureTracer, Record, read_capture};
+use trace::{Received, Sent, Tracer};
+use Timestamp;
+
+fn timestamp(sec: i64, precise_ns: u64) -> Timestamp {
+    Timestamp {
+        time: Timespec::new(sec, 123456789),
+        precise_ns: precise_ns,
+    }
+}
+
+#[test]
+fn read() {
+    let mut tracer = CaptureTracer::new(MemWriter::new());
+    tracer.trace(Sent, &timestamp(1418220000, 1000), b"AT\r");
+    tracer.trace(Received, &timestamp(1418220001, 2000), b"\r\nOK\r\n");
+
+    let mut capture = tracer.into_inner().unwrap();
+    capture.push_all(b"\n");
+
+    let records = match read_capture(&mut MemReader::new(capture.clone())) {
+        Err(e) => panic!("Couldn't read the capture ({})", e),
+        Ok(records) => records,
+    };
+
+    assert_eq!(records, vec![
+        Record { direction: Sent, timestamp: timestamp(1418220000, 1000), data: b"AT\r".to_vec() },
+        Record {
+            direction: Received,
+            timestamp: timestamp(1418220001, 2000),
+            data: b"\r\nOK\r\n".to_vec(),
+        },
+    ]);
+
+    capture.push_all(b"garbage\n");
+    assert!(read_capture(&mut MemReader::new(capture)).is_err());
+}
+
+#[test]
+fn records() {
+    let line = r#"{"sec":1418220000,"nsec":123456789,"mono_ns":1000,"dir":"tx","data":"41540d"}"#;
+    let record = Record::parse(line).unwrap();
+
+    assert_eq!(record, Record {
+        direction: Sent,
+        timestamp: timestamp(1418220000, 1000),
+        data: b"AT\r".to_vec(),
+    });
+    assert_eq!(record.to_json().as_slice(), line);
+
+    assert_eq!(Record::parse(r#"{"sec":0,"nsec":0,"mono_ns":0,"dir":"up","data":""}"#), None);
+    assert_eq!(Record::parse(r#"{"sec":0,"nsec":0,"mono_ns":0,"dir":"rx","data":"4"}"#), None);
+}
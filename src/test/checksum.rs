@@ -0,0 +1,79 @@
+use checksum::{Checksum, Crc16, Crc32, Crc8, Lrc, Xor};
+
+const CHECK: &'static [u8] = b"123456789";
+
+#[test]
+fn crc8() {
+    for &(ref crc, expected) in [(Crc8::smbus(), 0xF4), (Crc8::maxim(), 0xA1)].iter() {
+        let mut crc = crc.clone();
+        crc.update(CHECK);
+
+        assert_eq!(crc.value(), expected);
+    }
+}
+
+#[test]
+fn crc16() {
+    let crcs = [
+        (Crc16::ccitt(), 0x29B1),
+        (Crc16::kermit(), 0x2189),
+        (Crc16::modbus(), 0x4B37),
+        (Crc16::xmodem(), 0x31C3),
+    ];
+
+    for &(ref crc, expected) in crcs.iter() {
+        let mut crc = crc.clone();
+        crc.update(CHECK);
+
+        assert_eq!(crc.value(), expected);
+    }
+}
+
+#[test]
+fn crc16_byte_order() {
+    let mut modbus = Crc16::modbus();
+    let mut xmodem = Crc16::xmodem();
+    modbus.update(CHECK);
+    xmodem.update(CHECK);
+
+    assert_eq!(modbus.bytes(), vec![0x37, 0x4B]);
+    assert_eq!(xmodem.bytes(), vec![0x31, 0xC3]);
+}
+
+#[test]
+fn crc32() {
+    let mut crc = Crc32::ieee();
+    crc.update(CHECK);
+
+    assert_eq!(crc.value(), 0xCBF4_3926);
+}
+
+#[quickcheck]
+fn incremental(data: Vec<u8>, split: uint) -> bool {
+    let split = if data.is_empty() { 0 } else { split % data.len() };
+
+    let mut whole = Crc32::ieee();
+    whole.update(data.as_slice());
+
+    let mut chunked = Crc32::ieee();
+    chunked.update(data.slice_to(split));
+    chunked.update(data.slice_from(split));
+
+    whole.value() == chunked.value()
+}
+
+#[test]
+fn lrc() {
+    let mut lrc = Lrc::new();
+    lrc.update(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03]);
+
+    assert_eq!(lrc.value(), 0x7E);
+}
+
+#[test]
+fn xor() {
+    let mut xor = Xor::new();
+    xor.update(b"GPGLL,5300.97914,N,00259.98174,E,125926,A");
+
+    assert_eq!(xor.value(), 0x28);
+}
@@ -0,0 +1,66 @@
+use escpos::{AlignCenter, Code128, PartialCut, Printer, Receipt, Status};
+use SerialPort;
+
+#[test]
+fn printer() {
+    let (mut device, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    spawn(proc() {
+        assert_eq!(device.read_exact(4).unwrap(), vec![0x1B, 0x40, b'O', b'K']);
+
+        // Online, cover open, no error, paper near its end
+        for &answer in [0x12, 0x16, 0x12, 0x1E].iter() {
+            assert_eq!(device.read_exact(2).unwrap(), vec![0x10, 0x04]);
+            device.read_byte().unwrap();
+            device.write(&[answer]).unwrap();
+        }
+    });
+
+    let mut printer = Printer::new(port);
+
+    match printer.print(&Receipt::new().init().text("OK")) {
+        Err(e) => panic!("Couldn't print ({})", e),
+        Ok(_) => {},
+    }
+
+    match printer.status() {
+        Err(e) => panic!("Couldn't read the status ({})", e),
+        Ok(status) => {
+            assert!(status.cover_open && status.paper_near_end);
+            assert!(!status.offline && !status.paper_end && !status.recoverable_error);
+        },
+    }
+}
+
+#[test]
+fn receipt() {
+    let receipt = Receipt::new()
+        .align(AlignCenter)
+        .bold(true)
+        .size(2, 1)
+        .line("Café")
+        .barcode(Code128, b"{B42")
+        .cut(PartialCut);
+
+    assert_eq!(receipt.into_bytes(), vec![
+        0x1B, b'a', 1,
+        0x1B, b'E', 1,
+        0x1D, b'!', 0x10,
+        b'C', b'a', b'f', b'?', b'\n',
+        0x1D, b'H', 2, 0x1D, b'k', 73, 4, b'{', b'B', b'4', b'2',
+        0x1D, b'V', 66, 0,
+    ]);
+}
+
+#[test]
+fn status() {
+    let status = Status::parse(&[0x1A, 0x12, 0x52, 0x72]).unwrap();
+
+    assert!(status.offline && status.recoverable_error && status.paper_end);
+    assert!(!status.cover_open && !status.cutter_error);
+
+    assert_eq!(Status::parse(&[0x12, 0x12, 0x00, 0x12]), None);
+}
@@ -0,0 +1,47 @@
+use regex::Regex;
+use std::io::TimedOut;
+use std::time::Duration;
+
+use expect::{Expect, Match, RegexPattern};
+use SerialPort;
+
+#[test]
+fn script() {
+    let (mut device, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    spawn(proc() {
+        device.write(b"\r\nUser Access Verification\r\n\r\nlogin: ").unwrap();
+        assert_eq!(device.read_exact(6).unwrap().as_slice(), b"admin\r");
+        device.write(b"Pass").unwrap();
+        device.write(b"word: ").unwrap();
+        assert_eq!(device.read_exact(8).unwrap().as_slice(), b"hunter2\r");
+        device.write(b"\r\nrouter1# ").unwrap();
+    });
+
+    let mut session = Expect::new(port);
+    let timeout = Duration::seconds(1);
+
+    match session.run(&[("login:", "admin\r"), ("Password:", "hunter2\r")], timeout) {
+        Err(e) => panic!("Couldn't run the script ({})", e),
+        Ok(output) => {
+            assert_eq!(output.as_slice(), "\r\nUser Access Verification\r\n\r\nlogin: Password:")
+        },
+    }
+
+    let prompt = RegexPattern(Regex::new(r"\w+# ").unwrap());
+    match session.expect(&prompt, timeout) {
+        Err(e) => panic!("Couldn't find the prompt ({})", e),
+        Ok(found) => assert_eq!(found, Match {
+            before: " \r\n".to_string(),
+            matched: "router1# ".to_string(),
+        }),
+    }
+
+    match session.expect_literal("never", Duration::milliseconds(50)) {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+}
@@ -0,0 +1,45 @@
+use libc::{c_char, size_t, ssize_t};
+use std::c_str::CString;
+use std::ptr;
+
+use ffi::{SERIAL_EINVAL, SERIAL_ETIMEDOUT, SERIAL_OK};
+use ffi::{serial_close, serial_configure, serial_open, serial_read, serial_set_timeout};
+use ffi::{serial_strerror, serial_write};
+
+use super::{MESSAGE, pty};
+
+#[test]
+fn c_api() {
+    let (mut master, path) = pty();
+
+    let mut port = ptr::null_mut();
+    let status = path.with_c_str(|path| serial_open(path, &mut port));
+    assert_eq!(status, SERIAL_OK);
+
+    let none = b'N' as c_char;
+    assert_eq!(serial_configure(port, 115200, 8, none, 1, none), SERIAL_OK);
+    assert_eq!(serial_configure(port, 115200, 9, none, 1, none), SERIAL_EINVAL);
+
+    master.write_str(MESSAGE).unwrap();
+    let mut buf = [0u8, ..64];
+    let mut n = 0;
+    while n < MESSAGE.len() {
+        let read = serial_read(port, buf.slice_from_mut(n).as_mut_ptr(), (64 - n) as size_t);
+        assert!(read > 0);
+        n += read as uint;
+    }
+    assert_eq!(buf.slice_to(n), MESSAGE.as_bytes());
+
+    let written = serial_write(port, MESSAGE.as_ptr(), MESSAGE.len() as size_t);
+    assert_eq!(written, MESSAGE.len() as ssize_t);
+    assert_eq!(master.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+
+    assert_eq!(serial_set_timeout(port, 50), SERIAL_OK);
+    assert_eq!(serial_read(port, buf.as_mut_ptr(), 64), SERIAL_ETIMEDOUT as ssize_t);
+
+    let message = unsafe { CString::new(serial_strerror(SERIAL_ETIMEDOUT), false) };
+    assert_eq!(message.as_str(), Some("Operation timed out"));
+
+    serial_close(port);
+    assert_eq!(serial_read(ptr::null_mut(), buf.as_mut_ptr(), 64), SERIAL_EINVAL as ssize_t);
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use firmata::{AnalogMappingMessage, AnalogMessage, Board, CapabilityMessage, DigitalMessage};
+use firmata::{AnalogMode, FirmwareMessage, InputMode, OutputMode, Parser, PwmMode};
+use SerialPort;
+
+use super::total;
+
+#[quickcheck]
+fn arbitrary_input(data: Vec<u8>, split: uint) -> bool {
+    total(Parser::new(), Parser::new(), data.as_slice(), split, |parser, data| parser.feed(data))
+}
+
+#[test]
+fn board() {
+    let (mut firmware, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    spawn(proc() {
+        // Capability query
+        assert_eq!(firmware.read_exact(3).unwrap(), vec![0xF0, 0x6B, 0xF7]);
+        // An analog report arrives before the response
+        firmware.write(&[0xE1, 0x7F, 0x03, 0xF0, 0x6C, 0x00, 0x01, 0x01, 0x01, 0x7F]).unwrap();
+        firmware.write(&[0x02, 0x0A, 0x7F, 0xF7]).unwrap();
+
+        // Pin mode, then pin 9 high, then pin 10 high
+        assert_eq!(firmware.read_exact(3).unwrap(), vec![0xF4, 0x09, 0x01]);
+        assert_eq!(firmware.read_exact(3).unwrap(), vec![0x91, 0x02, 0x00]);
+        assert_eq!(firmware.read_exact(3).unwrap(), vec![0x91, 0x06, 0x00]);
+    });
+
+    let mut board = Board::new(port);
+    board.get_mut().set_timeout(Some(Duration::seconds(1)));
+
+    match board.query_capabilities() {
+        Err(e) => panic!("Couldn't query capabilities ({})", e),
+        Ok(pins) => {
+            assert_eq!(pins, vec![vec![(InputMode, 1), (OutputMode, 1)], vec![(AnalogMode, 10)]]);
+        },
+    }
+
+    assert_eq!(board.analog_read(1), Some(0x1FF));
+    assert_eq!(board.analog_read(2), None);
+
+    board.set_pin_mode(9, OutputMode).unwrap();
+    board.digital_write(9, true).unwrap();
+    board.digital_write(10, true).unwrap();
+}
+
+#[test]
+fn parser() {
+    let mut parser = Parser::new();
+
+    // The firmware report is split across two feeds, and a stray data byte is ignored
+    assert_eq!(parser.feed(&[0x05, 0x92, 0x7F, 0x01, 0xF0, 0x79, 0x02, 0x05, b'S', 0x00]), vec![
+        DigitalMessage(2, 0xFF),
+    ]);
+    assert_eq!(parser.feed(&[b'F', 0x00, 0xF7, 0xE0, 0x10]), vec![
+        FirmwareMessage(2, 5, "SF".to_string()),
+    ]);
+    assert_eq!(parser.feed(&[0x01, 0xF0, 0x6A, 0x7F, 0x00, 0xF7]), vec![
+        AnalogMessage(0, 0x90),
+        AnalogMappingMessage(vec![None, Some(0)]),
+    ]);
+
+    // A status byte interrupts an incomplete message
+    assert_eq!(parser.feed(&[0xE0, 0x01, 0xF0, 0x6C, 0x03, 0x08, 0x7F, 0xF7]), vec![
+        CapabilityMessage(vec![vec![(PwmMode, 8)]]),
+    ]);
+}
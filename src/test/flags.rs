@@ -0,0 +1,48 @@
+use flags::{CLOCAL, CREAD, CS7, CS8, CSIZE, ECHO, ICANON, ICRNL, IGNBRK, ISTRIP};
+use flags::{InputFlags, LocalFlags};
+use SerialPort;
+
+#[test]
+fn operations() {
+    let mut flags = ICRNL | ISTRIP;
+
+    assert!(flags.contains(ICRNL));
+    assert!(!flags.contains(IGNBRK));
+
+    flags.insert(IGNBRK);
+    flags.remove(ICRNL);
+    assert!(flags == IGNBRK | ISTRIP);
+    assert!(flags - ISTRIP == IGNBRK);
+    assert!((flags & ICRNL).is_empty());
+    assert!(InputFlags::from_bits(!0).is_none());
+}
+
+#[test]
+fn round_trip() {
+    let (_master, mut port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let input = port.input_flags().unwrap() | ICRNL | ISTRIP;
+    port.set_input_flags(input).unwrap();
+    assert!(port.input_flags().unwrap() == input);
+
+    port.set_input_flags(InputFlags::empty()).unwrap();
+    assert!(port.input_flags().unwrap().is_empty());
+
+    let control = port.control_flags().unwrap() - CSIZE | CS7 | CLOCAL | CREAD;
+    port.set_control_flags(control).unwrap();
+    assert!(port.control_flags().unwrap() & CSIZE == CS7);
+
+    // The baud rate isn't a flag, it survives the replacement
+    let (input_rate, output_rate) = port.baud_rate().unwrap();
+    port.set_control_flags(control - CSIZE | CS8).unwrap();
+    assert!(port.control_flags().unwrap() & CSIZE == CS8);
+    assert!(port.baud_rate().unwrap() == (input_rate, output_rate));
+
+    port.set_local_flags(ICANON | ECHO).unwrap();
+    assert!(port.local_flags().unwrap() == ICANON | ECHO);
+    port.set_local_flags(LocalFlags::empty()).unwrap();
+    assert!(!port.echo().unwrap());
+}
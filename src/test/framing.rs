@@ -0,0 +1,216 @@
+use checksum::Crc16;
+use framing::{
+    BadChecksum, BigEndian, Checked, Cobs, Decoder, Delimited, Encoder, FrameResult,
+    InPlaceDecoder, InvalidEscape, LengthPrefixed, LittleEndian, Oversized, Slip, Truncated,
+    U16Prefix, U32Prefix, U8Prefix,
+};
+
+use super::total;
+
+/// Encodes all the `frames`, then feeds the encoded stream to the `codec` split at `split`
+fn roundtrip<C: Decoder + Encoder>(codec: &mut C, frames: &[Vec<u8>], split: uint) -> bool {
+    let mut stream = Vec::new();
+    for frame in frames.iter() {
+        codec.encode(frame.as_slice(), &mut stream).unwrap();
+    }
+
+    let split = if stream.is_empty() { 0 } else { split % stream.len() };
+    let mut decoded = codec.feed(stream.slice_to(split));
+    decoded.push_all(codec.feed(stream.slice_from(split)).as_slice());
+
+    let expected = frames.iter().map(|frame| Ok(frame.clone())).collect::<Vec<FrameResult>>();
+
+    decoded == expected
+}
+
+/// Decodes arbitrary `data` in place with a fresh codec and with `feed` with another one, and
+/// checks that they agree
+fn in_place<C: Decoder + InPlaceDecoder>(mut fed: C, mut codec: C, data: &[u8]) -> bool {
+    let mut buf = data.to_vec();
+    let mut start = 0;
+    let mut decoded = Vec::new();
+
+    loop {
+        let (consumed, frame) = codec.decode_in_place(buf.slice_from_mut(start));
+        match frame {
+            None => {},
+            Some(frame) => decoded.push(frame.map(|frame| frame.to_vec())),
+        }
+
+        if consumed == 0 {
+            break
+        }
+        start += consumed;
+    }
+
+    fed.feed(data) == decoded
+}
+
+#[quickcheck]
+fn arbitrary_input(data: Vec<u8>, split: uint) -> bool {
+    let data = data.as_slice();
+
+    total(Cobs::new(), Cobs::new(), data, split, |codec, data| codec.feed(data)) &&
+        total(Slip::new(), Slip::new(), data, split, |codec, data| codec.feed(data)) &&
+        total(Delimited::escaped(b'\n', b'\\'), Delimited::escaped(b'\n', b'\\'), data, split,
+              |codec, data| codec.feed(data)) &&
+        total(LengthPrefixed::new(U16Prefix, BigEndian).max_frame_len(64),
+              LengthPrefixed::new(U16Prefix, BigEndian).max_frame_len(64), data, split,
+              |codec, data| codec.feed(data)) &&
+        total(Checked::new(Cobs::new(), Crc16::ccitt()), Checked::new(Cobs::new(), Crc16::ccitt()),
+              data, split, |codec, data| codec.feed(data))
+}
+
+#[test]
+fn checked_corruption() {
+    let mut codec = Checked::new(Cobs::new(), Crc16::ccitt());
+
+    let mut encoded = Vec::new();
+    codec.encode(b"payload", &mut encoded).unwrap();
+    encoded.as_mut_slice()[2] ^= 0x01;
+
+    assert_eq!(codec.feed(encoded.as_slice()), vec![Err(BadChecksum)]);
+}
+
+#[quickcheck]
+fn checked_roundtrip(frames: Vec<Vec<u8>>, split: uint) -> bool {
+    roundtrip(&mut Checked::new(Cobs::new(), Crc16::ccitt()), frames.as_slice(), split)
+}
+
+#[test]
+fn cobs_encode() {
+    let mut encoded = Vec::new();
+    Cobs::new().encode(&[0x11, 0x22, 0x00, 0x33], &mut encoded).unwrap();
+
+    assert_eq!(encoded, vec![0x03, 0x11, 0x22, 0x02, 0x33, 0x00]);
+}
+
+#[test]
+fn cobs_long_run() {
+    let frame = Vec::from_elem(600, 0xAAu8);
+
+    assert!(roundtrip(&mut Cobs::new(), &[frame], 0));
+}
+
+#[quickcheck]
+fn cobs_roundtrip(frames: Vec<Vec<u8>>, split: uint) -> bool {
+    roundtrip(&mut Cobs::new(), frames.as_slice(), split)
+}
+
+#[test]
+fn cobs_truncated() {
+    let mut codec = Cobs::new();
+
+    let frames = codec.feed(&[0x05, 0x11, 0x00, 0x02, 0x22, 0x00]);
+
+    assert_eq!(frames, vec![Err(Truncated), Ok(vec![0x22])]);
+}
+
+#[quickcheck]
+fn delimited_roundtrip(frames: Vec<Vec<u8>>, split: uint) -> bool {
+    let frames = frames.into_iter().filter(|frame| !frame.is_empty()).collect::<Vec<_>>();
+
+    roundtrip(&mut Delimited::escaped(b'\n', b'\\'), frames.as_slice(), split)
+}
+
+#[test]
+fn delimited_invalid_escape() {
+    let mut codec = Delimited::escaped(b'\n', b'\\');
+
+    let frames = codec.feed(b"ab\\c\nde\\\n\n");
+
+    assert_eq!(frames, vec![Err(InvalidEscape(b'c')), Ok(b"de\n".to_vec())]);
+}
+
+#[test]
+fn delimited_spanning_reads() {
+    let mut codec = Delimited::new(b'\n');
+
+    assert_eq!(codec.feed(b"\nfir"), vec![]);
+    assert_eq!(codec.feed(b"st\nsec"), vec![Ok(b"first".to_vec())]);
+    assert_eq!(codec.feed(b"ond\n"), vec![Ok(b"second".to_vec())]);
+}
+
+#[quickcheck]
+fn in_place_arbitrary_input(data: Vec<u8>) -> bool {
+    let data = data.as_slice();
+
+    in_place(Cobs::new(), Cobs::new(), data) &&
+        in_place(Slip::new(), Slip::new(), data) &&
+        in_place(Delimited::escaped(b'\n', b'\\'), Delimited::escaped(b'\n', b'\\'), data) &&
+        in_place(LengthPrefixed::new(U8Prefix, BigEndian).max_frame_len(16),
+                 LengthPrefixed::new(U8Prefix, BigEndian).max_frame_len(16), data) &&
+        in_place(Checked::new(Cobs::new(), Crc16::ccitt()),
+                 Checked::new(Cobs::new(), Crc16::ccitt()), data)
+}
+
+#[test]
+fn in_place_borrowed() {
+    let mut codec = Checked::new(Slip::new(), Crc16::ccitt());
+    let mut buf = Vec::new();
+    codec.encode(&[0x01, 0xC0, 0x02], &mut buf).unwrap();
+    let len = buf.len();
+    buf.push_all(&[0x03, 0x04]);
+
+    let consumed = {
+        let (consumed, frame) = codec.decode_in_place(buf.as_mut_slice());
+        assert_eq!(frame, Some(Ok([0x01, 0xC0, 0x02].as_slice())));
+        consumed
+    };
+    assert_eq!(consumed, len);
+
+    // The partial frame is left to be completed by the next read
+    assert_eq!(codec.decode_in_place(buf.slice_from_mut(consumed)), (0, None));
+}
+
+#[test]
+fn length_prefixed_encode() {
+    let mut encoded = Vec::new();
+    LengthPrefixed::new(U16Prefix, BigEndian).encode(&[0xAA], &mut encoded).unwrap();
+    LengthPrefixed::new(U32Prefix, LittleEndian).encode(&[0xBB], &mut encoded).unwrap();
+
+    assert_eq!(encoded, vec![0x00, 0x01, 0xAA, 0x01, 0x00, 0x00, 0x00, 0xBB]);
+}
+
+#[test]
+fn length_prefixed_oversized() {
+    let mut codec = LengthPrefixed::new(U8Prefix, BigEndian).max_frame_len(2);
+
+    assert_eq!(codec.encode(&[1, 2, 3], &mut Vec::new()), Err(Oversized(3)));
+    assert_eq!(LengthPrefixed::new(U8Prefix, BigEndian).encode(&[0, ..256], &mut Vec::new()),
+               Err(Oversized(256)));
+
+    // The payload of the oversized frame is skipped, and decoding resumes after it
+    let frames = codec.feed(&[3, 1, 2, 3, 1, 4]);
+
+    assert_eq!(frames, vec![Err(Oversized(3)), Ok(vec![4])]);
+}
+
+#[quickcheck]
+fn length_prefixed_roundtrip(frames: Vec<Vec<u8>>, split: uint) -> bool {
+    roundtrip(&mut LengthPrefixed::new(U16Prefix, LittleEndian), frames.as_slice(), split)
+}
+
+#[test]
+fn slip_encode() {
+    let mut encoded = Vec::new();
+    Slip::with_leading_end().encode(&[0x01, 0xC0, 0xDB], &mut encoded).unwrap();
+
+    assert_eq!(encoded, vec![0xC0, 0x01, 0xDB, 0xDC, 0xDB, 0xDD, 0xC0]);
+}
+
+#[test]
+fn slip_invalid_escape() {
+    let mut codec = Slip::new();
+
+    let frames = codec.feed(&[0x01, 0xDB, 0x02, 0xC0, 0x03, 0xC0]);
+
+    assert_eq!(frames, vec![Err(InvalidEscape(0x02)), Ok(vec![0x03])]);
+}
+
+#[quickcheck]
+fn slip_roundtrip(frames: Vec<Vec<u8>>, split: uint) -> bool {
+    let frames = frames.into_iter().filter(|frame| !frame.is_empty()).collect::<Vec<_>>();
+
+    roundtrip(&mut Slip::with_leading_end(), frames.as_slice(), split)
+}
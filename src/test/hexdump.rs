@@ -0,0 +1,31 @@
+use std::io::{BufReader, MemWriter};
+use std::str;
+
+use hexdump::{HexDumpReader, HexDumpWriter};
+
+const DUMP: &'static str = "\
+00000000  30 31 32 33 34 35 36 37  38 39 41 42 43 44 45 46  |0123456789ABCDEF|
+00000010  41 54 0d                                          |AT.|
+00000013
+";
+
+#[test]
+fn reader() {
+    let data = b"0123456789ABCDEFAT\r";
+    let mut reader = HexDumpReader::new(BufReader::new(data));
+
+    assert_eq!(str::from_utf8(reader.read_to_end().unwrap().as_slice()), Some(DUMP));
+}
+
+#[test]
+fn writer() {
+    let mut writer = HexDumpWriter::new(MemWriter::new());
+
+    // The lines don't depend on how the data is split
+    writer.write(b"0123").unwrap();
+    writer.write(b"456789ABCDEFA").unwrap();
+    writer.write(b"T\r").unwrap();
+
+    let dump = writer.finish().unwrap().unwrap();
+    assert_eq!(str::from_utf8(dump.as_slice()), Some(DUMP));
+}
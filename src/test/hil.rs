@@ -0,0 +1,20 @@
+use std::time::Duration;
+
+use hil::{Hil, Loopback};
+
+// Skipped unless `SERIAL_HIL_DEVICE` is set, see the `hil` module
+#[test]
+fn adapter() {
+    let mut hil = match Hil::from_env() {
+        None => return,
+        Some(hil) => hil,
+    };
+
+    hil.assert_round_trip(b"Hello World!");
+    hil.assert_round_trip(Vec::from_fn(256, |i| i as u8).as_slice());
+    hil.assert_latency(Duration::milliseconds(100));
+
+    if hil.wiring() == Loopback {
+        hil.assert_modem_lines();
+    }
+}
@@ -0,0 +1,29 @@
+use std::default::Default;
+use std::io::{MemReader, MemWriter};
+
+use interact::{Options, interact};
+use SerialPort;
+
+#[test]
+fn passthrough() {
+    let (mut device, mut port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    device.write(b"login: ").unwrap();
+
+    // Everything after the escape character is ignored
+    let input = MemReader::new(b"root\r\x1Dreboot\r".to_vec());
+    let mut output = MemWriter::new();
+    let options = Options { local_echo: true, ..Default::default() };
+
+    match interact(&mut port, input, &mut output, &options) {
+        Err(e) => panic!("Couldn't interact ({})", e),
+        Ok(_) => {},
+    }
+
+    assert_eq!(output.get_ref(), b"login: root\r");
+    assert_eq!(device.read_exact(5).unwrap().as_slice(), b"root\r");
+    assert_eq!(port.timeout(), None);
+}
@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use kiss::{DataFrame, Frame, FullDuplexFrame, ReturnFrame, SetHardwareFrame, Tnc, TxDelayFrame};
+use SerialPort;
+
+#[test]
+fn frames() {
+    assert_eq!(TxDelayFrame(1, 50).encode(), vec![0x11, 50]);
+    assert_eq!(FullDuplexFrame(0, true).encode(), vec![0x05, 0x01]);
+    assert_eq!(ReturnFrame.encode(), vec![0xFF]);
+
+    assert_eq!(Frame::parse(&[0x20, 0x82, 0xA0]), Some(DataFrame(2, vec![0x82, 0xA0])));
+    assert_eq!(Frame::parse(&[0x06]), Some(SetHardwareFrame(0, vec![])));
+    assert_eq!(Frame::parse(&[0x01]), None);
+    assert_eq!(Frame::parse(&[0x0C, 0x00]), None);
+}
+
+#[test]
+fn tnc() {
+    let (mut radio, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let mut tnc = Tnc::new(port);
+
+    match tnc.set_tx_delay(0, Duration::milliseconds(300)) {
+        Err(e) => panic!("Couldn't set the TX delay ({})", e),
+        Ok(_) => {},
+    }
+    match tnc.send_data(0, &[0x96, 0xC0, 0xDB]) {
+        Err(e) => panic!("Couldn't send a frame ({})", e),
+        Ok(_) => {},
+    }
+
+    assert_eq!(radio.read_exact(11).unwrap(), vec![
+        0xC0, 0x01, 30, 0xC0,
+        0xC0, 0x00, 0x96, 0xDB, 0xDC, 0xDB, 0xDD,
+    ]);
+    assert_eq!(radio.read_byte().unwrap(), 0xC0);
+
+    // An unknown command is skipped
+    radio.write(&[0xC0, 0x0C, 0xC0, 0xC0, 0x10, 0x82, 0xDB, 0xDC, 0xC0]).unwrap();
+    assert_eq!(tnc.receive().unwrap(), DataFrame(1, vec![0x82, 0xC0]));
+}
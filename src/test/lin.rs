@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use lin::{ClassicChecksum, EnhancedChecksum, Master, Publish, Slot, Subscribe};
+use lin::{checksum, classic_checksum, enhanced_checksum, protected_id};
+use SerialPort;
+
+#[test]
+fn checksums() {
+    assert_eq!(classic_checksum(&[0x01]), 0xFE);
+    // End-around carry
+    assert_eq!(classic_checksum(&[0xFF, 0x02]), 0xFD);
+    assert_eq!(enhanced_checksum(0x4A, &[0x55, 0x93, 0xE5]), 0xE6);
+
+    assert_eq!(checksum(EnhancedChecksum, 0x0A, &[0x55, 0x93, 0xE5]), 0x66);
+    // Diagnostic frames
+    assert_eq!(checksum(EnhancedChecksum, 0x3C, &[0x01]), 0xFE);
+}
+
+#[test]
+fn master() {
+    let (mut device, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    spawn(proc() {
+        assert_eq!(device.read_exact(5).unwrap().as_slice(), &[0x55, 0xCA, 0x01, 0x02, 0x32]);
+
+        assert_eq!(device.read_exact(2).unwrap().as_slice(), &[0x55, 0xCA]);
+        device.write(&[0x55, 0x93, 0xE5, 0x66]).unwrap();
+
+        // Corrupted checksum
+        assert_eq!(device.read_exact(2).unwrap().as_slice(), &[0x55, 0xCA]);
+        device.write(&[0x55, 0x93, 0xE5, 0x00]).unwrap();
+    });
+
+    let mut master = Master::new(port, 19200);
+    // A pty doesn't echo
+    master.set_echo(false);
+
+    let slot = Slot {
+        id: 0x0A,
+        transfer: Publish(vec![0x01, 0x02]),
+        checksum: EnhancedChecksum,
+        duration: Duration::milliseconds(10),
+    };
+    match master.run_slot(&slot) {
+        Err(e) => panic!("Couldn't run a publish slot ({})", e),
+        Ok(response) => assert_eq!(response, None),
+    }
+
+    let slot = Slot { transfer: Subscribe(3), ..slot };
+    match master.run_slot(&slot) {
+        Err(e) => panic!("Couldn't run a subscribe slot ({})", e),
+        Ok(response) => assert_eq!(response, Some(vec![0x55, 0x93, 0xE5])),
+    }
+
+    assert!(master.request(0x0A, 3, EnhancedChecksum).is_err());
+    assert!(master.request(0x40, 1, ClassicChecksum).is_err());
+}
+
+#[test]
+fn protected_ids() {
+    assert_eq!(protected_id(0x00), 0x80);
+    assert_eq!(protected_id(0x01), 0xC1);
+    assert_eq!(protected_id(0x0A), 0xCA);
+    assert_eq!(protected_id(0x10), 0x50);
+    assert_eq!(protected_id(0x3C), 0x3C);
+    assert_eq!(protected_id(0x3D), 0x7D);
+}
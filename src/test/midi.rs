@@ -0,0 +1,86 @@
+use midi::{ActiveSensingMessage, ControlChangeMessage, Device, Encoder, NoteOffMessage};
+use midi::{NoteOnMessage, Parser, PitchBendMessage, ProgramChangeMessage, SongPositionMessage};
+use midi::{SysExMessage, TimingClockMessage, TuneRequestMessage};
+use SerialPort;
+
+use super::total;
+
+#[quickcheck]
+fn arbitrary_input(data: Vec<u8>, split: uint) -> bool {
+    total(Parser::new(), Parser::new(), data.as_slice(), split, |parser, data| parser.feed(data))
+}
+
+#[test]
+fn device() {
+    let (mut synth, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let mut device = Device::new(port);
+    device.set_running_status(true);
+
+    for &note in [60, 64, 67].iter() {
+        match device.send(&NoteOnMessage(0, note, 100)) {
+            Err(e) => panic!("Couldn't send a note ({})", e),
+            Ok(_) => {},
+        }
+    }
+
+    assert_eq!(synth.read_exact(7).unwrap(), vec![0x90, 60, 100, 64, 100, 67, 100]);
+
+    synth.write(&[0xB1, 0x07, 0x7F, 0xFE, 0x0A]).unwrap();
+    assert_eq!(device.receive().unwrap(), ControlChangeMessage(1, 0x07, 0x7F));
+    assert_eq!(device.receive().unwrap(), ActiveSensingMessage);
+
+    synth.write(&[0x40]).unwrap();
+    assert_eq!(device.receive().unwrap(), ControlChangeMessage(1, 0x0A, 0x40));
+}
+
+#[test]
+fn encoder() {
+    let mut encoder = Encoder::new();
+    let mut bytes = Vec::new();
+
+    for message in [
+        NoteOnMessage(2, 60, 100),
+        TimingClockMessage,
+        NoteOnMessage(2, 60, 0),
+        NoteOffMessage(2, 61, 0),
+        NoteOffMessage(2, 62, 0),
+        SysExMessage(vec![0x7E, 0x00]),
+        NoteOffMessage(2, 63, 0),
+    ].iter() {
+        bytes.push_all(encoder.encode(message).as_slice());
+    }
+
+    assert_eq!(bytes, vec![
+        0x92, 60, 100, 0xF8, 60, 0, 0x82, 61, 0, 62, 0, 0xF0, 0x7E, 0x00, 0xF7, 0x82, 63, 0,
+    ]);
+
+    assert_eq!(PitchBendMessage(0, 0x2000).encode(), vec![0xE0, 0x00, 0x40]);
+    assert_eq!(SongPositionMessage(0x3FFF).encode(), vec![0xF2, 0x7F, 0x7F]);
+}
+
+#[test]
+fn parser() {
+    let mut parser = Parser::new();
+
+    // Running status spans feeds, a real-time message splits a note
+    assert_eq!(parser.feed(&[0x40, 0x93, 60, 100, 64]), vec![NoteOnMessage(3, 60, 100)]);
+    assert_eq!(parser.feed(&[0xF8, 100, 0xC3, 5, 6]), vec![
+        TimingClockMessage,
+        NoteOnMessage(3, 64, 100),
+        ProgramChangeMessage(3, 5),
+        ProgramChangeMessage(3, 6),
+    ]);
+
+    // System common messages cancel the running status
+    assert_eq!(parser.feed(&[0xF0, 0x43, 0xF8, 0x12, 0xF7, 0x01, 0xF6, 0xE0, 0x00]), vec![
+        TimingClockMessage,
+        SysExMessage(vec![0x43, 0x12]),
+        TuneRequestMessage,
+    ]);
+    assert_eq!(parser.feed(&[0x40, 0xF2, 0x10]), vec![PitchBendMessage(0, 0x2000)]);
+    assert_eq!(parser.feed(&[0x01, 0x02]), vec![SongPositionMessage(0x90)]);
+}
@@ -0,0 +1,73 @@
+use regex::Regex;
+use std::io::TimedOut;
+use std::time::Duration;
+use time;
+
+use mock::{MockPort, Rule, Strict, Unordered};
+use {SerialIo, VirtualClock};
+
+#[test]
+fn strict() {
+    let mut port = MockPort::new(Strict)
+        .rule(Rule::on(b"AT\r").reply(b"OK\r\n"))
+        .rule(Rule::on_regex(Regex::new(r"AT\+CSQ\r").unwrap()).reply(b"+CSQ: 23\r\n").times(2));
+    port.set_timeout(Some(Duration::milliseconds(10)));
+
+    // Out of order, the request isn't matched
+    port.write(b"AT+CSQ\r").unwrap();
+    assert!(port.read_exact(1).is_err());
+    assert!(!port.report().is_satisfied());
+
+    let mut port = MockPort::new(Strict)
+        .rule(Rule::on(b"AT\r").reply(b"OK\r\n").after(Duration::milliseconds(5)))
+        .rule(Rule::on_regex(Regex::new(r"AT\+CSQ\r").unwrap()).reply(b"+CSQ: 23\r\n").times(2));
+    port.set_timeout(Some(Duration::milliseconds(100)));
+
+    // Requests can be split across writes
+    port.write(b"A").unwrap();
+    port.write(b"T\rAT+CSQ\r").unwrap();
+    assert_eq!(port.read_exact(14).unwrap(), b"OK\r\n+CSQ: 23\r\n".to_vec());
+
+    let report = port.report();
+    assert_eq!(report.unmet, vec![("/AT\\+CSQ\\r/".to_string(), 1, 2)]);
+    assert!(report.unmatched.is_empty());
+
+    port.write(b"AT+CSQ\r").unwrap();
+    port.verify();
+}
+
+#[test]
+fn unordered() {
+    let mut port = MockPort::new(Unordered)
+        .rule(Rule::on(b"ping").reply(b"pong"))
+        .rule(Rule::on(b"stat").reply(b"ok"));
+
+    port.write(b"statpingjunk").unwrap();
+    assert_eq!(port.read_exact(6).unwrap(), b"okpong".to_vec());
+
+    let report = port.report();
+    assert!(report.unmet.is_empty());
+    assert_eq!(report.unmatched, b"junk".to_vec());
+}
+
+#[test]
+fn virtual_clock() {
+    let clock = VirtualClock::new();
+    let mut port = MockPort::new(Strict).clock(clock.clone())
+        .rule(Rule::on(b"PING").reply(b"PONG").after(Duration::hours(1)));
+    port.set_timeout(Some(Duration::minutes(40)));
+    let start = time::precise_time_ns();
+
+    port.write(b"PING").unwrap();
+    match port.read_exact(4) {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+    assert_eq!(clock.now(), Duration::minutes(40));
+
+    clock.advance(Duration::minutes(10));
+    assert_eq!(port.read_exact(4).unwrap(), b"PONG".to_vec());
+    assert_eq!(clock.now(), Duration::hours(1));
+
+    assert!(time::precise_time_ns() - start < 1_000_000_000);
+}
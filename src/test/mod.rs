@@ -0,0 +1,1969 @@
+use libc;
+use libc::funcs::posix88::fcntl::fcntl;
+use std::default::Default;
+use std::io::{EndOfFile, InvalidInput, IoResult, MemWriter, Read, ReadWrite, TimedOut, Write};
+use std::io::timer;
+use std::str;
+use std::time::Duration;
+use time;
+
+use {
+    BaudCheck, BlockingMode, BufferedSerialPort, CtsGatedWriter, DriverInfo, EventHandler,
+    MetricsSink, OpenOptions, PriorityWriter, ReadCanceller, ProbeSpec, RingBuffer, SerialIo,
+    SerialPort, Settings, SharedSerialPort, Stats, Tap, ThrottledWriter, probe, virtual_pair,
+    //StopReason,
+        FoundDelimiter, ReachedMaxLength, TimeoutElapsed,
+    //ReadMode,
+        BlockUntil, NonBlocking, Timeout, TimeoutAfterFirstByte,
+    //Direction,
+        BothDirections, Input, Output,
+    BaudRate,
+        B0, B50, B75, B110, B134, B150, B200, B300, B600, B1K2, B2K4, B4K8, B9K6, B19K2, B38K4,
+        B57K6, B115K2, B230K4,
+    DataBits,
+        Data5, Data6, Data7, Data8,
+    FlowControl,
+        HardwareControl, NoFlowControl, SoftwareControl,
+    Parity,
+        EvenParity, MarkParity, NoParity, OddParity, SpaceParity,
+    StopBits,
+        Stop1, Stop2,
+    //RateSetting,
+        CustomRate, StandardRate,
+    //StatusLine,
+        CarrierDetect, ClearToSend,
+    //EventSet,
+        CD_CHANGED, RX_AVAILABLE, TX_EMPTY,
+    //LinePower,
+        PoweredAfterClose, PoweredUntilClose, Unpowered,
+    //WatchdogAction,
+        ClosePort, RaiseFlag,
+};
+
+#[cfg(target_os = "linux")]
+use {B460K8, B500K, B576K, B921K6, B1M, B1M152, B1M5, B2M, B2M5, B3M, B3M5, B4M};
+
+#[cfg(target_os = "macos")]
+use {B7K2, B14K4, B28K8, B76K8};
+
+use testing;
+use trace::{Received, Sent};
+use {F_GETFL, F_SETFL, O_NONBLOCK};
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod analyzer;
+mod at;
+mod capture;
+mod checksum;
+mod escpos;
+mod expect;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod firmata;
+mod flags;
+mod framing;
+mod hexdump;
+mod hil;
+mod interact;
+mod kiss;
+mod lin;
+mod midi;
+mod mock;
+mod modbus;
+mod mux;
+mod nmea;
+mod obd;
+mod ports;
+mod profile;
+mod programmer;
+mod raw;
+mod replay;
+mod rfc2217;
+mod rs485;
+mod sim;
+mod slcan;
+mod stable;
+mod tee;
+mod trace;
+mod ubx;
+mod xbee;
+mod xfer;
+
+#[cfg(target_os = "linux")]
+const BAUD_RATES: &'static [BaudRate] = &[
+    B0, B50, B75, B110, B134, B150, B200, B300, B600, B1K2, B2K4, B4K8, B9K6, B19K2, B38K4, B57K6,
+    B115K2, B230K4, B460K8, B500K, B576K, B921K6, B1M, B1M152, B1M5, B2M, B2M5, B3M, B3M5, B4M];
+
+#[cfg(target_os = "macos")]
+const BAUD_RATES: &'static [BaudRate] = &[
+    B0, B50, B75, B110, B134, B150, B200, B300, B600, B1K2, B2K4, B4K8, B7K2, B9K6, B14K4, B19K2,
+    B28K8, B38K4, B57K6, B115K2, B230K4];
+
+const MESSAGE: &'static str = "Hello World!";
+
+/// Feeds arbitrary `data` to two fresh decoders, whole to one and split at `split` to the other,
+/// and checks that they agree
+fn total<D, T: Clone + PartialEq>(mut whole: D, mut parts: D, data: &[u8], split: uint,
+                                  feed: |&mut D, &[u8]| -> Vec<T>) -> bool {
+    let split = if data.is_empty() { 0 } else { split % data.len() };
+    let mut decoded = feed(&mut parts, data.slice_to(split));
+    decoded.push_all(feed(&mut parts, data.slice_from(split)).as_slice());
+
+    feed(&mut whole, data) == decoded
+}
+
+/// Opens a pty pair, returns the master side and the path to the slave side
+///
+/// The slave device node only exists as long as the master side is kept open
+fn pty() -> (SerialPort, Path) {
+    match testing::pty() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(testing::Pty { master, path }) => (master, path),
+    }
+}
+
+#[test]
+fn baud_check() {
+    // 9600 bps received at 38400 bps, each bit is sampled 4 times
+    let mut check = BaudCheck::new();
+    for _ in range(0u, 32) {
+        check.feed(&[0xF8, 0xF8, 0x80, 0x00]);
+    }
+    let diagnosis = check.diagnose(B38K4);
+    assert!(diagnosis.probable_mismatch);
+    assert_eq!(diagnosis.run_ratio, 1.0);
+    assert_eq!(diagnosis.suggested_rate, Some(B9K6));
+
+    let mut check = BaudCheck::new();
+    for _ in range(0u, 8) {
+        check.feed(b"$GPGGA,123519,4807.038,N*47\r\n");
+    }
+    assert!(!check.diagnose(B9K6).probable_mismatch);
+
+    check.add_line_errors(12);
+    let diagnosis = check.diagnose(B9K6);
+    assert!(diagnosis.probable_mismatch);
+    assert_eq!(diagnosis.suggested_rate, None);
+}
+
+#[test]
+fn bidirectional_baud_rate() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    for &rate in BAUD_RATES.iter() {
+        match port.set_baud_rate(BothDirections, rate) {
+            Err(e) => panic!("{}: Couldn't set both baud rates to {} ({})", port_, rate, e),
+            Ok(_) => {},
+        }
+        let got = match port.baud_rate() {
+            Err(e) => panic!("{}: Couldn't read baud rate ({})", port_, e),
+            Ok(rate) => rate,
+        };
+
+        if (rate, rate) != got {
+            panic!("{}: set {} - got {}", port_, rate, got);
+        }
+    }
+}
+
+#[quickcheck]
+fn blocking_mode(bytes: u8, deciseconds: u8) -> bool {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    let mode = BlockingMode { bytes: bytes, deciseconds: deciseconds };
+    match port.set_blocking_mode(mode) {
+        Err(e) => {
+            panic!("{}: Couldn't set blocking mode to {} ({})", port_, (bytes, deciseconds), e)
+        },
+        Ok(_) => {},
+    }
+
+    match port.blocking_mode() {
+        Err(e) => panic!("{}: Couldn't read blocking mode ({})", port_, e),
+        Ok(got) => got == mode
+    }
+}
+
+#[test]
+fn buffered_read_line() {
+    let (mut tx, rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    let mut rx = BufferedSerialPort::new(rx);
+
+    match tx.write_str("first\nsecond\n") {
+        Err(e) => panic!("master: Couldn't send message ({})", e),
+        Ok(_) => {},
+    }
+
+    for &expected in ["first\n", "second\n"].iter() {
+        match rx.read_line() {
+            Err(e) => panic!("slave: Couldn't read line ({})", e),
+            Ok(line) => assert_eq!(line.as_slice(), expected),
+        }
+    }
+}
+
+#[test]
+fn close_on_exec() {
+    const F_GETFD: libc::c_int = 1;
+    const FD_CLOEXEC: libc::c_int = 1;
+
+    let (_master, port) = pty();
+    let port_ = port.display();
+
+    for &close_on_exec in [true, false].iter() {
+        let options = OpenOptions { close_on_exec: close_on_exec, ..Default::default() };
+        let port = match SerialPort::open_with_options(&port, ReadWrite, &options) {
+            Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+            Ok(port) => port,
+        };
+
+        let flags = unsafe { fcntl(port.fd, F_GETFD) };
+        assert_eq!(flags & FD_CLOEXEC != 0, close_on_exec);
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn closing_wait() {
+    use driver::raw_closing_wait;
+
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    // The driver of ptys has no parameters
+    assert!(port.set_closing_wait(Some(Duration::seconds(1))).is_err());
+    assert!(port.set_custom_divisor(3).is_err());
+
+    assert_eq!(raw_closing_wait(None).unwrap(), 0);
+    assert_eq!(raw_closing_wait(Some(Duration::zero())).unwrap(), 65535);
+    assert_eq!(raw_closing_wait(Some(Duration::milliseconds(1))).unwrap(), 1);
+    assert_eq!(raw_closing_wait(Some(Duration::seconds(30))).unwrap(), 3000);
+    assert!(raw_closing_wait(Some(Duration::seconds(1000))).is_err());
+}
+
+#[test]
+fn copy() {
+    let (mut device, mut port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    let (mut out, mut log) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    port.set_timeout(Some(Duration::milliseconds(100)));
+    device.write_str(MESSAGE).unwrap();
+
+    // The copy runs until the port times out
+    match ::copy(&mut port, &mut out) {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+    assert_eq!(log.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+    assert_eq!(port.stats().bytes_read, MESSAGE.len() as u64);
+}
+
+#[test]
+fn cts_gated_writer() {
+    let (_master, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    // Ptys have no CTS to wait for, the write fails instead of blocking
+    let mut port = CtsGatedWriter::new(port).cts_timeout(Duration::milliseconds(10));
+    assert!(port.write_str(MESSAGE).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn custom_baud_rate() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    match port.set_custom_baud_rate(31250) {
+        Err(e) => panic!("{}: Couldn't set a custom baud rate ({})", port_, e),
+        Ok(_) => {},
+    }
+
+    // The custom rate survives changes to the other settings
+    match port.set_parity(EvenParity) {
+        Err(e) => panic!("{}: Couldn't set parity ({})", port_, e),
+        Ok(_) => {},
+    }
+    assert!(port.baud_rate().is_err());
+
+    match port.set_baud_rate(BothDirections, B9K6) {
+        Err(e) => panic!("{}: Couldn't set both baud rates to {} ({})", port_, B9K6, e),
+        Ok(_) => {},
+    }
+    match port.baud_rate() {
+        Err(e) => panic!("{}: Couldn't read baud rate ({})", port_, e),
+        Ok(rates) => assert_eq!(rates, (B9K6, B9K6)),
+    }
+}
+
+#[test]
+fn char_delay() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    tx.set_char_delay(Some(Duration::milliseconds(10)));
+    tx.set_frame_delay(Some(Duration::milliseconds(20)));
+
+    let start = time::precise_time_ns();
+    match tx.write(b"12345") {
+        Err(e) => panic!("master: Couldn't write ({})", e),
+        Ok(_) => {},
+    }
+
+    // 4 gaps between the bytes, and the gap after the frame
+    assert!(time::precise_time_ns() - start >= 60_000_000);
+    assert_eq!(tx.stats().bytes_written, 5);
+
+    match rx.read_exact(5) {
+        Err(e) => panic!("slave: Couldn't read ({})", e),
+        Ok(buf) => assert_eq!(buf.as_slice(), b"12345"),
+    }
+}
+
+// XXX The PTY only seems to work with 8 data bits
+#[test]
+#[ignore]
+fn data_bits() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    for &bits in [Data5, Data6, Data7, Data8].iter() {
+        match port.set_data_bits(bits) {
+            Err(e) => panic!("{}: Couldn't set data bits to {} ({})", port_, bits, e),
+            Ok(_) => {},
+        }
+        let got = match port.data_bits() {
+            Err(e) => panic!("{}: Couldn't read data bits ({})", port_, e),
+            Ok(bits) => bits,
+        };
+
+        if bits != got {
+            panic!("{}: set {} - got {}", port_, bits, got);
+        }
+    }
+}
+
+// XXX Should opening a port twice be forbidden?
+// - AFAIK, opening a port twice is not possible in Windows, but it's possible on Linux
+// - FWIW, QSerialPort and minicom forbid this operation via lockfiles
+#[test]
+#[ignore]
+fn double_open() {
+    let (_master, port) = pty();
+
+    let first = SerialPort::open(&port, Write);
+    let second = SerialPort::open(&port, Write);
+
+    assert!(first.is_ok() && second.is_err());
+}
+
+#[test]
+fn driver_info() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    // A pty isn't backed by a UART
+    assert!(port.driver_info().is_err());
+
+    let info = DriverInfo {
+        uart_type: 4,
+        line: 0,
+        port: 0x3F8,
+        irq: 4,
+        flags: 0,
+        xmit_fifo_size: 16,
+        baud_base: 115200,
+        custom_divisor: 0,
+        close_delay: Duration::milliseconds(500),
+        closing_wait: Some(Duration::seconds(30)),
+    };
+    assert_eq!(info.uart_name(), Some("16550A"));
+    assert_eq!(DriverInfo { uart_type: 0, ..info }.uart_name(), None);
+}
+
+#[test]
+fn echo() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    assert!(!rx.echo().unwrap());
+
+    match rx.set_echo(true) {
+        Err(e) => panic!("Couldn't enable the echo ({})", e),
+        Ok(_) => {},
+    }
+    assert!(rx.echo().unwrap());
+
+    // The slave side echoes what the master side sends
+    tx.write_str(MESSAGE).unwrap();
+    assert_eq!(rx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+    assert_eq!(tx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+
+    rx.set_echo(false).unwrap();
+    assert!(!rx.echo().unwrap());
+}
+
+#[test]
+fn eight_bit_clean() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    rx.set_strip_high_bit(true).unwrap();
+    assert!(rx.strip_high_bit().unwrap());
+    assert!(!rx.is_eight_bit_clean().unwrap());
+
+    rx.set_flow_control(SoftwareControl).unwrap();
+    match rx.make_eight_bit_clean() {
+        Err(e) => assert_eq!(e.detail, Some("software flow control".to_string())),
+        Ok(_) => panic!("Expected an error with software flow control"),
+    }
+    assert!(!rx.strip_high_bit().unwrap());
+
+    rx.set_flow_control(NoFlowControl).unwrap();
+    match rx.make_eight_bit_clean() {
+        Err(e) => panic!("Couldn't make the port 8-bit clean ({})", e),
+        Ok(_) => {},
+    }
+    assert!(rx.is_eight_bit_clean().unwrap());
+
+    let data = Vec::from_fn(256, |i| i as u8);
+    tx.write(data.as_slice()).unwrap();
+    assert_eq!(rx.read_exact(256).unwrap(), data);
+}
+
+#[test]
+fn fd_passing() {
+    use libc::funcs::posix88::unistd::write;
+    use {receive_fd, send_fd};
+
+    extern {
+        fn socketpair(domain: libc::c_int, type_: libc::c_int, protocol: libc::c_int,
+                      sockets: *mut libc::c_int) -> libc::c_int;
+    }
+    // AF_UNIX, SOCK_STREAM
+    let mut sockets = [0 as libc::c_int, ..2];
+    assert_eq!(unsafe { socketpair(1, 1, 0, sockets.as_mut_ptr()) }, 0);
+
+    let (mut master, path) = pty();
+    {
+        // The broker opens the device and passes it on
+        let broker = SerialPort::open(&path, ReadWrite).unwrap();
+        send_fd(sockets[0], broker.as_raw_fd()).unwrap();
+    }
+    let mut port = SerialPort::from_received_fd(receive_fd(sockets[1]).unwrap()).unwrap();
+    port.write_str("passed").unwrap();
+    assert_eq!(master.read_exact(6).unwrap(), b"passed".to_vec());
+
+    // Data without a descriptor
+    assert_eq!(unsafe { write(sockets[0], b"x".as_ptr() as *const libc::c_void, 1) }, 1);
+    assert_eq!(receive_fd(sockets[1]).unwrap_err().kind, InvalidInput);
+
+    unsafe {
+        libc::close(sockets[0]);
+        libc::close(sockets[1]);
+    }
+}
+
+#[test]
+fn flow_control() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    for &flow in [HardwareControl, NoFlowControl, SoftwareControl].iter() {
+        match port.set_flow_control(flow) {
+            Err(e) => panic!("{}: Couldn't set flow control to {} ({})", port_, flow, e),
+            Ok(_) => {},
+        }
+        let got = match port.flow_control() {
+            Err(e) => panic!("{}: Couldn't read flow control ({})", port_, e),
+            Ok(flow) => flow,
+        };
+
+        if flow != got {
+            panic!("{}: set {} - got {}", port_, flow, got)
+        }
+    }
+}
+
+#[test]
+fn flow_state() {
+    let (_master, mut port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    port.set_flow_control(NoFlowControl).unwrap();
+    let state = port.flow_state().unwrap();
+    assert_eq!(state.xoff_suspended, Some(false));
+    assert_eq!(state.output_queued, 0);
+    // Ptys have no status inputs
+    assert_eq!(state.cts, None);
+
+    // With nothing queued, an XOFF can't be told
+    port.set_flow_control(SoftwareControl).unwrap();
+    assert_eq!(port.flow_state().unwrap().xoff_suspended, None);
+}
+
+#[test]
+fn hangup_on_close() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    for &enable in [true, false].iter() {
+        match port.set_hangup_on_close(enable) {
+            Err(e) => panic!("{}: Couldn't set hang up on close to {} ({})", port_, enable, e),
+            Ok(_) => {},
+        }
+        let got = match port.hangup_on_close() {
+            Err(e) => panic!("{}: Couldn't read hang up on close ({})", port_, e),
+            Ok(enable) => enable,
+        };
+
+        if enable != got {
+            panic!("{}: set {} - got {}", port_, enable, got)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn holders() {
+    use holders;
+    use std::os;
+
+    let (_master, port) = pty();
+    let _opened = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port.display(), e),
+        Ok(opened) => opened,
+    };
+
+    let found = holders(&port).unwrap();
+    assert!(found.iter().any(|holder| holder.pid == os::getpid() as u32));
+}
+
+#[test]
+fn idle_watchdog() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    rx.set_idle_watchdog(Some(Duration::milliseconds(100)), RaiseFlag);
+    assert!(!rx.idle_expired());
+    timer::sleep(Duration::milliseconds(150));
+    assert!(rx.idle_expired());
+
+    // Data lowers the flag
+    tx.write_str(MESSAGE).unwrap();
+    assert_eq!(rx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+    assert!(!rx.idle_expired());
+
+    // The watchdog cuts a read without a timeout short, and keeps the port closed
+    rx.set_idle_watchdog(Some(Duration::milliseconds(100)), ClosePort);
+    match rx.read_byte() {
+        Err(ref e) if e.kind == EndOfFile => {},
+        result => panic!("Expected the end of file, got {}", result),
+    }
+    tx.write_str(MESSAGE).unwrap();
+    assert!(rx.read_byte().is_err());
+
+    rx.set_idle_watchdog(None, ClosePort);
+    assert_eq!(rx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+}
+
+#[test]
+fn ignore_baud_rate() {
+    let (_master, mut port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    assert!(!port.ignore_baud_rate());
+
+    // The accepted rates still apply
+    port.set_ignore_baud_rate(true);
+    port.set_baud_rate(BothDirections, B4K8).unwrap();
+    assert_eq!(port.baud_rate().unwrap(), (B4K8, B4K8));
+}
+
+#[test]
+fn incoming_bytes() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    rx.set_timeout(Some(Duration::milliseconds(100)));
+
+    tx.write_str(MESSAGE).unwrap();
+
+    // The bytes read ahead aren't lost with the iterator
+    let head = rx.incoming_bytes().take(5).map(|byte| byte.unwrap()).collect::<Vec<u8>>();
+    assert_eq!(head.as_slice(), MESSAGE.as_bytes().slice_to(5));
+    let rest = rx.read_exact(MESSAGE.len() - 5).unwrap();
+    assert_eq!(rest.as_slice(), MESSAGE.as_bytes().slice_from(5));
+}
+
+#[test]
+fn input_baud_rate() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    for &rate in BAUD_RATES.iter() {
+        // XXX Can't set input baud rate to `B0`
+        if rate == B0 {
+            continue
+        }
+
+        match port.set_baud_rate(Input, rate) {
+            Err(e) => panic!("{}: Couldn't set input baud rate to {} ({})", port_, rate, e),
+            Ok(_) => {},
+        }
+        let got = match port.baud_rate() {
+            Err(e) => panic!("{}: Couldn't read baud rate ({})", port_, e),
+            Ok(rates) => rates.0,
+        };
+
+        if rate != got {
+            panic!("{}: set {} - got {}", port_, rate, got)
+        }
+    }
+}
+
+#[test]
+fn into_channel() {
+    let (mut master, slave) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    master.set_timeout(Some(Duration::seconds(1)));
+
+    let (received, outgoing) = slave.into_channel().unwrap();
+
+    outgoing.send(b"ping".to_vec());
+    assert_eq!(master.read_exact(4).unwrap().as_slice(), b"ping");
+
+    master.write_str(MESSAGE).unwrap();
+    let mut data = Vec::new();
+    while data.len() < MESSAGE.len() {
+        data.push_all(received.recv().unwrap().as_slice());
+    }
+    assert_eq!(data.as_slice(), MESSAGE.as_bytes());
+
+    // The hang up ends the reads, with an error
+    drop(master);
+    assert!(received.recv().is_err());
+}
+
+#[test]
+fn is_same_device() {
+    use std::io::TempDir;
+    use std::io::fs;
+
+    let (_master, path) = pty();
+    let (_other_master, other_path) = pty();
+    let port = match SerialPort::open(&path, ReadWrite) {
+        Err(e) => panic!("{}: Couldn't open ({})", path.display(), e),
+        Ok(port) => port,
+    };
+
+    let dir = TempDir::new("serial").unwrap();
+    let link = dir.path().join("by-id");
+    fs::symlink(&path, &link).unwrap();
+
+    assert!(port.is_same_device(&path).unwrap());
+    assert!(port.is_same_device(&link).unwrap());
+    assert!(!port.is_same_device(&other_path).unwrap());
+    assert!(!port.is_same_device(dir.path()).unwrap());
+    assert!(port.is_same_device(&dir.path().join("missing")).is_err());
+}
+
+#[test]
+fn keepalive() {
+    let (mut device, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    let port = SharedSerialPort::new(port).unwrap();
+    device.set_timeout(Some(Duration::seconds(1)));
+
+    let keepalive = port.keepalive(b"ping", Duration::milliseconds(50));
+    assert_eq!(device.read_exact(8).unwrap().as_slice(), b"pingping");
+    assert!(keepalive.error().is_none());
+
+    // Nothing is sent once the handle is dropped
+    drop(keepalive);
+    timer::sleep(Duration::milliseconds(100));
+    device.set_timeout(Some(Duration::zero()));
+    while device.read_byte().is_ok() {}
+    device.set_timeout(Some(Duration::milliseconds(200)));
+    match device.read_byte() {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+}
+
+#[test]
+fn line_power() {
+    let (_master, path) = pty();
+    let path_ = path.display();
+    let options = OpenOptions { line_power: PoweredUntilClose, ..Default::default() };
+    let mut port = match SerialPort::open_with_options(&path, ReadWrite, &options) {
+        Err(e) => panic!("{}: Couldn't open ({})", path_, e),
+        Ok(port) => port,
+    };
+    assert_eq!(port.line_power(), PoweredUntilClose);
+    assert!(port.hangup_on_close().unwrap());
+
+    // The changes that would drop a line are refused
+    for result in [port.set_dtr(false), port.set_rts(false)].iter() {
+        match *result {
+            Err(ref e) if e.kind == InvalidInput => {},
+            ref result => panic!("{}: Expected a refusal, got {}", path_, result),
+        }
+    }
+    match port.set_flow_control(HardwareControl) {
+        Err(ref e) if e.kind == InvalidInput => {},
+        result => panic!("{}: Expected a refusal, got {}", path_, result),
+    }
+    match port.set_baud_rate(Output, B0) {
+        Err(ref e) if e.kind == InvalidInput => {},
+        result => panic!("{}: Expected a refusal, got {}", path_, result),
+    }
+
+    // The refused changes are undone, the next ones apply
+    port.set_parity(EvenParity).unwrap();
+    assert_eq!(port.flow_control().unwrap(), NoFlowControl);
+    assert!(port.baud_rate().unwrap().1 != B0);
+
+    port.set_line_power(PoweredAfterClose).unwrap();
+    assert!(!port.hangup_on_close().unwrap());
+    port.set_line_power(PoweredUntilClose).unwrap();
+    assert!(port.hangup_on_close().unwrap());
+
+    port.set_line_power(Unpowered).unwrap();
+    port.set_flow_control(HardwareControl).unwrap();
+}
+
+#[test]
+fn lines() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    match tx.write_str("first\rsecond\rthird") {
+        Err(e) => panic!("master: Couldn't send message ({})", e),
+        Ok(_) => {},
+    }
+
+    rx.set_timeout(Some(Duration::milliseconds(100)));
+
+    let lines = rx.lines().terminator(b'\r').map(|line| match line {
+        Err(e) => panic!("slave: Couldn't read line ({})", e),
+        Ok(line) => line,
+    }).collect::<Vec<String>>();
+
+    assert_eq!(lines, vec!["first\r".to_string(), "second\r".to_string()]);
+
+    // Nothing is lost past the lines taken
+    tx.write_str("fourth\rfifth").unwrap();
+    let fourth = rx.lines().terminator(b'\r').next().unwrap().unwrap();
+    assert_eq!(fourth.as_slice(), "fourth\r");
+    assert_eq!(rx.read_exact(5).unwrap().as_slice(), b"fifth");
+}
+
+#[test]
+fn local_mode() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    for &enable in [true, false].iter() {
+        match port.set_local_mode(enable) {
+            Err(e) => panic!("{}: Couldn't set local mode to {} ({})", port_, enable, e),
+            Ok(_) => {},
+        }
+        let got = match port.local_mode() {
+            Err(e) => panic!("{}: Couldn't read local mode ({})", port_, e),
+            Ok(enable) => enable,
+        };
+
+        if enable != got {
+            panic!("{}: set {} - got {}", port_, enable, got)
+        }
+    }
+}
+
+#[test]
+fn loopback() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    let (tx_, rx_) = ("master", "slave");
+
+    match tx.write_str(MESSAGE) {
+        Err(e) => panic!("{}: Couldn't send message ({})", tx_, e),
+        _ => {},
+    }
+
+    match rx.read_exact(MESSAGE.len()) {
+        Err(e) => panic!("{}: Couldn't read ({})", rx_, e),
+        Ok(buf) => assert_eq!(str::from_utf8(buf[]), Some(MESSAGE)),
+    }
+}
+
+/// Forwards the counters through a channel
+struct ChannelSink(Sender<(String, u64)>);
+
+impl MetricsSink for ChannelSink {
+    fn counter(&mut self, name: &str, value: u64) {
+        self.0.send((name.to_string(), value));
+    }
+
+    fn gauge(&mut self, _: &str, _: u64) {}
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn mark_parity_unavailable() {
+    use std::io::IoUnavailable;
+
+    let (_master, mut port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    match port.set_parity(MarkParity) {
+        Err(ref e) if e.kind == IoUnavailable => {},
+        result => panic!("Expected the parity to be unavailable, got {}", result),
+    }
+}
+
+#[test]
+fn metrics() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let (sender, receiver) = channel();
+    tx.set_metrics_sink(box ChannelSink(sender.clone()));
+    rx.set_metrics_sink(box ChannelSink(sender));
+
+    match tx.write_str(MESSAGE) {
+        Err(e) => panic!("master: Couldn't send message ({})", e),
+        _ => {},
+    }
+    assert_eq!(receiver.recv(), ("serial.writes".to_string(), 1));
+    assert_eq!(receiver.recv(), ("serial.bytes_written".to_string(), MESSAGE.len() as u64));
+
+    rx.set_timeout(Some(Duration::milliseconds(100)));
+    match rx.read(&mut [0u8, ..64]) {
+        Err(e) => panic!("slave: Couldn't read ({})", e),
+        Ok(n) => {
+            assert_eq!(receiver.recv(), ("serial.reads".to_string(), 1));
+            assert_eq!(receiver.recv(), ("serial.bytes_read".to_string(), n as u64));
+        },
+    }
+}
+
+#[test]
+fn nearest_baud_rate() {
+    let exact = BaudRate::nearest(9600, 0.0).unwrap();
+    assert_eq!(exact.setting, StandardRate(B9K6));
+    assert_eq!(exact.actual, 9600);
+    assert_eq!(exact.error_percent, 0.0);
+
+    let close = BaudRate::nearest(9700, 2.0).unwrap();
+    assert_eq!(close.setting, StandardRate(B9K6));
+    assert!(close.error_percent < -1.0 && close.error_percent > -1.1);
+
+    // MIDI, far from any standard rate
+    let midi = BaudRate::nearest(31250, 2.0);
+    if cfg!(target_os = "linux") {
+        assert_eq!(midi.unwrap().setting, CustomRate(31250));
+    } else {
+        assert!(midi.is_none());
+    }
+}
+
+#[test]
+fn open() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+
+    for &access in [Read, ReadWrite, Write].iter() {
+        match SerialPort::open(&port, Read) {
+            Err(e) => {
+                let access = match access {
+                    Read => "read",
+                    ReadWrite => "read/write",
+                    Write => "write",
+                };
+
+                panic!("{}: Couldn't open in {} mode ({})", port_, access, e)
+            },
+            Ok(_) => {},
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn open_busy() {
+    use ioctl;
+    use std::os;
+
+    let (_master, port) = pty();
+    let first = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port.display(), e),
+        Ok(first) => first,
+    };
+
+    // TIOCEXCL, root isn't subject to it
+    assert!(unsafe { ioctl::ioctl(first.as_raw_fd(), 0x540C) } != -1);
+
+    match SerialPort::open(&port, Read) {
+        Err(e) => {
+            let detail = e.detail.unwrap_or(String::new());
+            assert!(detail.as_slice().contains(format!("{} (", os::getpid()).as_slice()));
+        },
+        Ok(_) => assert_eq!(unsafe { libc::funcs::posix88::unistd::getuid() }, 0),
+    }
+}
+
+#[test]
+fn output_baud_rate() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    for &rate in BAUD_RATES.iter() {
+        match port.set_baud_rate(Output, rate) {
+            Err(e) => panic!("{}: Couldn't set output baud rate to {} ({})", port_, rate, e),
+            Ok(_) => {},
+        }
+        let got = match port.baud_rate() {
+            Err(e) => panic!("{}: Couldn't read baud rate ({})", port_, e),
+            Ok(rates) => rates.1,
+        };
+
+        if rate != got {
+            panic!("{}: set {} - got {}", port_, rate, got)
+        }
+    }
+}
+
+#[test]
+fn output_drained() {
+    let (mut rx, mut tx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    assert!(tx.output_drained().unwrap());
+
+    // Ptys hand the data over at once, there's nothing left once it's written
+    tx.write_str(MESSAGE).unwrap();
+    tx.drain().unwrap();
+    assert!(tx.output_drained().unwrap());
+    assert_eq!(rx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+}
+
+// XXX The PTY only seems to work with no parity
+#[test]
+#[ignore]
+fn parity() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    let mut parities = vec![EvenParity, NoParity, OddParity];
+    if cfg!(target_os = "linux") {
+        parities.push_all(&[MarkParity, SpaceParity]);
+    }
+
+    for &parity in parities.iter() {
+        match port.set_parity(parity) {
+            Err(e) => panic!("{}: Couldn't set parity to {} ({})", port_, parity, e),
+            Ok(_) => {},
+        }
+        let got = match port.parity() {
+            Err(e) => panic!("{}: Couldn't read parity ({})", port_, e),
+            Ok(parity) => parity,
+        };
+
+        if parity != got {
+            panic!("{}: set {} - got {}", port_, parity, got)
+        }
+    }
+}
+
+#[test]
+fn parse_framing() {
+    assert_eq!(from_str::<DataBits>("7"), Some(Data7));
+    assert_eq!(from_str::<DataBits>("9"), None);
+    assert_eq!(from_str::<StopBits>("2"), Some(Stop2));
+    assert_eq!(from_str::<Parity>("E"), Some(EvenParity));
+    assert_eq!(from_str::<Parity>("none"), Some(NoParity));
+    assert_eq!(from_str::<Parity>("M"), Some(MarkParity));
+    assert_eq!(from_str::<Parity>("space"), Some(SpaceParity));
+    assert_eq!(from_str::<Parity>("X"), None);
+    assert_eq!(from_str::<FlowControl>("RTSCTS"), Some(HardwareControl));
+    assert_eq!(from_str::<FlowControl>("xonxoff"), Some(SoftwareControl));
+
+    assert_eq!(Data5.to_uint(), 5);
+    assert_eq!(Stop1.to_uint(), 1);
+}
+
+#[test]
+fn peek() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    rx.set_timeout(Some(Duration::milliseconds(100)));
+
+    tx.write_str(MESSAGE).unwrap();
+
+    let mut header = [0u8, ..5];
+    assert_eq!(rx.peek(&mut header).unwrap(), 5);
+    assert_eq!(header.as_slice(), MESSAGE.as_bytes().slice_to(5));
+
+    // Peeking again doesn't consume either, and asks for more
+    let mut buf = [0u8, ..64];
+    assert_eq!(rx.peek(&mut buf).unwrap(), MESSAGE.len());
+    assert_eq!(buf.slice_to(MESSAGE.len()), MESSAGE.as_bytes());
+
+    // The time out only matters when nothing was peeked
+    assert_eq!(rx.peek(&mut buf).unwrap(), MESSAGE.len());
+
+    assert_eq!(rx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+    match rx.peek(&mut buf) {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+}
+
+#[test]
+fn priority_writer() {
+    /// Reports each write, and holds it until told to go on
+    struct Gate {
+        written: Sender<Vec<u8>>,
+        go: Receiver<()>,
+    }
+
+    impl Writer for Gate {
+        fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+            self.written.send(buf.to_vec());
+            self.go.recv();
+            Ok(())
+        }
+    }
+
+    let (written_sender, written) = channel();
+    let (go, go_receiver) = channel();
+    let writer = PriorityWriter::new(Gate { written: written_sender, go: go_receiver });
+
+    for frame in ["bulk 1", "bulk 2", "bulk 3"].iter() {
+        writer.send(frame.as_bytes()).unwrap();
+    }
+    // The first bulk frame is being written, the urgent ones preempt the others
+    assert_eq!(written.recv(), b"bulk 1".to_vec());
+    writer.queue().send_urgent(b"stop").unwrap();
+    writer.send_urgent(b"nak").unwrap();
+
+    for _ in range(0u, 5) {
+        go.send(());
+    }
+    assert!(writer.finish().is_ok());
+    let rest: Vec<Vec<u8>> = written.iter().collect();
+    assert_eq!(rest, vec![b"stop".to_vec(), b"nak".to_vec(), b"bulk 2".to_vec(),
+                          b"bulk 3".to_vec()]);
+}
+
+#[test]
+fn probe_ports() {
+    use expect::LiteralPattern;
+
+    let (mut silent, silent_path) = pty();
+    let (mut gps, gps_path) = pty();
+    silent.set_timeout(Some(Duration::seconds(5)));
+    gps.set_timeout(Some(Duration::seconds(5)));
+
+    spawn(proc() {
+        // Answers the second query only, as if the first baud rate were wrong
+        for answer in ["#~@!", "GPS 1.2\r\n"].iter() {
+            gps.read_until(b'\r', 16, Duration::seconds(5)).unwrap();
+            gps.write_str(*answer).unwrap();
+        }
+        gps.read_byte().ok();
+    });
+
+    let spec = ProbeSpec::new(b"ID?\r", LiteralPattern("GPS".to_string()))
+                         .baud_rates(&[B4K8, B9K6])
+                         .timeout(Duration::milliseconds(200));
+    let paths = [Path::new("/dev/missing"), silent_path, gps_path.clone()];
+    let found = probe(&paths, &spec);
+
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].path, gps_path);
+    assert_eq!(found[0].baud_rate, B9K6);
+    assert_eq!(found[0].response.matched.as_slice(), "GPS");
+
+    // The silent device got the query at both rates
+    assert_eq!(silent.read_exact(8).unwrap().as_slice(), b"ID?\rID?\r");
+}
+
+#[test]
+fn raw_ioctl() {
+    use ioctl::TIOCOUTQ;
+    use std::ptr;
+
+    let (mut master, mut slave) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let mut queued: libc::c_int = -1;
+    let result = unsafe {
+        master.ioctl(TIOCOUTQ, &mut queued as *mut libc::c_int as *mut libc::c_void)
+    };
+    assert_eq!(result.ok(), Some(0));
+    assert_eq!(queued, 0);
+
+    // Failures are reported with errno
+    let result = unsafe { slave.ioctl(0xDEAD, ptr::null_mut()) };
+    assert!(result.is_err());
+    slave.write_str("still usable").unwrap();
+    assert_eq!(master.read_exact(12).unwrap(), b"still usable".to_vec());
+}
+
+#[test]
+fn read_in_write_only_mode() {
+    let (_master, port) = pty();
+    let mut port = SerialPort::open(&port, Write);
+
+    assert!(port.read_to_string().is_err())
+}
+
+#[test]
+fn read_canceller() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let canceller = rx.read_canceller().unwrap();
+    spawn(proc() {
+        timer::sleep(Duration::milliseconds(100));
+        canceller.cancel().unwrap();
+    });
+
+    let start = time::precise_time_ns();
+    match rx.read_byte() {
+        Err(ref e) if ReadCanceller::is_cancellation(e) => {},
+        result => panic!("Expected a cancellation, got {}", result),
+    }
+    assert!(time::precise_time_ns() - start >= 100_000_000);
+
+    // The reads work as usual afterwards
+    tx.write_str(MESSAGE).unwrap();
+    assert_eq!(rx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+}
+
+#[test]
+fn read_mode() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    let modes = [
+        NonBlocking,
+        BlockUntil(4),
+        Timeout(Duration::milliseconds(500)),
+        TimeoutAfterFirstByte(Duration::milliseconds(100)),
+    ];
+    for mode in modes.iter() {
+        match port.set_read_mode(mode.clone()) {
+            Err(e) => panic!("{}: Couldn't set read mode to {} ({})", port_, mode, e),
+            Ok(_) => {},
+        }
+        let got = match port.read_mode() {
+            Err(e) => panic!("{}: Couldn't read read mode ({})", port_, e),
+            Ok(mode) => mode,
+        };
+
+        if Some(mode.clone()) != got {
+            panic!("{}: set {} - got {}", port_, mode, got)
+        }
+    }
+
+    // Rounded up to deciseconds
+    port.set_read_mode(Timeout(Duration::milliseconds(1))).unwrap();
+    assert!(port.blocking_mode().unwrap() == BlockingMode { bytes: 0, deciseconds: 1 });
+
+    assert!(port.set_read_mode(Timeout(Duration::seconds(30))).is_err());
+    assert!(port.set_read_mode(TimeoutAfterFirstByte(Duration::zero())).is_err());
+
+    port.set_blocking_mode(BlockingMode { bytes: 4, deciseconds: 1 }).unwrap();
+    assert_eq!(port.read_mode().unwrap(), None);
+}
+
+#[test]
+fn read_mode_without_data() {
+    let (_tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    let mut buf = [0u8, ..16];
+
+    // Nothing was received, which isn't the end of the file
+    for mode in [NonBlocking, Timeout(Duration::milliseconds(100))].iter() {
+        rx.set_read_mode(mode.clone()).unwrap();
+        match rx.read(&mut buf) {
+            Err(ref e) if e.kind == TimedOut => {},
+            result => panic!("{}: Expected a time out, got {}", mode, result),
+        }
+    }
+
+    // The other modes wait for a first byte, the timeout of the port applies
+    rx.set_timeout(Some(Duration::milliseconds(100)));
+    for mode in [BlockUntil(4), TimeoutAfterFirstByte(Duration::milliseconds(100))].iter() {
+        rx.set_read_mode(mode.clone()).unwrap();
+        match rx.read(&mut buf) {
+            Err(ref e) if e.kind == TimedOut => {},
+            result => panic!("{}: Expected a time out, got {}", mode, result),
+        }
+    }
+}
+
+#[test]
+fn read_one_byte() {
+    let (mut first, mut second) = virtual_pair();
+
+    first.write(b"\x01\x02\x03\x04").unwrap();
+    assert_eq!(second.read_one_byte().unwrap(), 0x01);
+
+    let mut buf = [0u8, ..3];
+    second.read_exact_into(&mut buf).unwrap();
+    assert_eq!(buf.as_slice(), b"\x02\x03\x04");
+
+    // The bytes received before the time out are kept
+    first.write(b"\x05").unwrap();
+    second.set_timeout(Some(Duration::milliseconds(100)));
+    match second.read_exact_into(&mut buf) {
+        Err(ref e) if e.kind == TimedOut => {
+            assert_eq!(e.detail, Some("received 1 of 3 bytes".to_string()));
+        },
+        result => panic!("Expected a time out, got {}", result),
+    }
+    assert_eq!(buf[0], 0x05);
+    assert_eq!(second.timeout(), Some(Duration::milliseconds(100)));
+
+    drop(first);
+    assert_eq!(second.read_one_byte().map_err(|e| e.kind), Err(EndOfFile));
+}
+
+#[test]
+fn read_timestamped() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let before = time::precise_time_ns();
+    match tx.write_str(MESSAGE) {
+        Err(e) => panic!("master: Couldn't send message ({})", e),
+        _ => {},
+    }
+
+    let mut buf = [0u8, ..64];
+    match rx.read_timestamped(&mut buf) {
+        Err(e) => panic!("slave: Couldn't read ({})", e),
+        Ok((n, timestamp)) => {
+            assert!(n > 0);
+            assert!(timestamp.precise_ns >= before);
+            assert!(timestamp.precise_ns <= time::precise_time_ns());
+        },
+    }
+
+    // Peeked bytes come first, stamped when they were peeked
+    rx.set_timeout(Some(Duration::milliseconds(100)));
+    tx.write_str(MESSAGE).unwrap();
+    let mut header = [0u8, ..5];
+    assert_eq!(rx.peek(&mut header).unwrap(), 5);
+    let peeked = time::precise_time_ns();
+    timer::sleep(Duration::milliseconds(10));
+
+    let (n, timestamp) = rx.read_timestamped(&mut header).unwrap();
+    assert_eq!(n, 5);
+    assert_eq!(header.as_slice(), MESSAGE.as_bytes().slice_to(5));
+    assert!(timestamp.precise_ns <= peeked);
+}
+
+#[test]
+fn read_until() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    tx.write_str("login: root\r\n").unwrap();
+
+    let timeout = Duration::milliseconds(100);
+    assert_eq!(rx.read_until(b':', 64, timeout).unwrap(),
+               (b"login:".to_vec(), FoundDelimiter));
+    assert_eq!(rx.read_until(b'\n', 4, timeout).unwrap(), (b" roo".to_vec(), ReachedMaxLength));
+    assert_eq!(rx.read_until(b'\n', 64, timeout).unwrap(), (b"t\r\n".to_vec(), FoundDelimiter));
+
+    let start = time::precise_time_ns();
+    tx.write_str("$ ").unwrap();
+    assert_eq!(rx.read_until(b'#', 64, timeout).unwrap(), (b"$ ".to_vec(), TimeoutElapsed));
+    assert!(time::precise_time_ns() - start >= 100_000_000);
+    assert_eq!(rx.timeout(), None);
+}
+
+#[test]
+fn reopen() {
+    let (mut master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, ReadWrite) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    port.set_parity(OddParity).unwrap();
+    port.set_timeout(Some(Duration::seconds(1)));
+
+    match port.reopen() {
+        Err(e) => panic!("{}: Couldn't reopen ({})", port_, e),
+        Ok(_) => {},
+    }
+
+    assert_eq!(port.parity().unwrap(), OddParity);
+    assert_eq!(port.timeout(), Some(Duration::seconds(1)));
+
+    master.write_str(MESSAGE).unwrap();
+    assert_eq!(port.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+
+    let (_, mut slave) = SerialPort::pty_pair().unwrap();
+    assert!(slave.reopen().is_err());
+}
+
+#[test]
+fn retry_interrupted() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    assert!(rx.retry_interrupted());
+
+    rx.set_retry_interrupted(false);
+    assert!(!rx.retry_interrupted());
+
+    // Without signals, the reads and writes behave the same either way
+    rx.set_timeout(Some(Duration::milliseconds(100)));
+    tx.write_str(MESSAGE).unwrap();
+    assert_eq!(rx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+    match rx.read_byte() {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+}
+
+/// Collects the data until `len` bytes, then stops the loop
+struct Collector {
+    data: Vec<u8>,
+    len: uint,
+    canceller: ReadCanceller,
+}
+
+impl EventHandler for Collector {
+    fn on_data(&mut self, data: &[u8]) {
+        self.data.push_all(data);
+
+        if self.data.len() >= self.len {
+            self.canceller.cancel().unwrap();
+        }
+    }
+}
+
+#[test]
+fn ring_buffer() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    rx.set_timeout(Some(Duration::seconds(1)));
+
+    let mut ring = RingBuffer::with_capacity(14);
+    tx.write_str(MESSAGE).unwrap();
+    let mut received = 0;
+    while received < MESSAGE.len() {
+        received += ring.fill(&mut rx).unwrap();
+    }
+    assert_eq!(ring.slices(), (MESSAGE.as_bytes(), b""));
+
+    // The data wraps around the end of the storage
+    ring.consume(10);
+    tx.write_str(MESSAGE).unwrap();
+    while received < 2 * MESSAGE.len() {
+        received += ring.fill(&mut rx).unwrap();
+    }
+    assert!(ring.is_full());
+    assert_eq!(ring.fill(&mut rx).unwrap(), 0);
+
+    let (first, second) = ring.slices();
+    assert_eq!(first, b"d!He");
+    assert_eq!(second, b"llo World!");
+}
+
+#[test]
+fn run_events() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    rx.set_timeout(Some(Duration::seconds(1)));
+    let mut collector = Collector {
+        data: Vec::new(),
+        len: MESSAGE.len(),
+        canceller: rx.read_canceller().unwrap(),
+    };
+
+    tx.write_str(MESSAGE).unwrap();
+    match rx.run_events(&mut collector) {
+        Err(e) => panic!("Event loop failed ({})", e),
+        Ok(()) => assert_eq!(collector.data.as_slice(), MESSAGE.as_bytes()),
+    }
+    assert_eq!(rx.timeout(), Some(Duration::seconds(1)));
+
+    // The errors stop the loop by default
+    drop(tx);
+    assert!(rx.run_events(&mut collector).is_err());
+}
+
+#[test]
+fn settings() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    let settings = Settings { baud_rate: B115K2, ..Default::default() };
+
+    // Exercise the trait, to make sure `SerialPort` is usable as a generic transport
+    fn roundtrip<S: SerialIo>(port: &mut S, settings: &Settings) -> Settings {
+        match port.configure(settings) {
+            Err(e) => panic!("Couldn't apply settings {} ({})", settings, e),
+            Ok(_) => {},
+        }
+
+        match port.settings() {
+            Err(e) => panic!("Couldn't read settings ({})", e),
+            Ok(settings) => settings,
+        }
+    }
+
+    let got = roundtrip(&mut port, &settings);
+
+    if settings != got {
+        panic!("{}: set {} - got {}", port_, settings, got)
+    }
+}
+
+#[test]
+fn settings_diff() {
+    let old: Settings = Default::default();
+    let new = Settings { baud_rate: B115K2, parity: EvenParity, ..old };
+
+    assert!(old.diff(&old).is_empty());
+
+    let changes: Vec<String> = old.diff(&new).iter().map(|change| change.to_string()).collect();
+    assert_eq!(changes, vec!["baud rate: 9600 -> 115200".to_string(),
+                             "parity: none -> even".to_string()]);
+
+    let back = new.diff(&old);
+    assert_eq!((back[1].setting, back[1].from.as_slice()), ("parity", "even"));
+}
+
+#[test]
+fn shared_port() {
+    let (mut master, slave) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    master.set_timeout(Some(Duration::seconds(1)));
+
+    let port = SharedSerialPort::new(slave).unwrap();
+    port.set_timeout(Some(Duration::seconds(5)));
+
+    let (sender, receiver) = channel();
+    let mut reader = port.clone();
+    spawn(proc() {
+        sender.send(reader.read_exact(MESSAGE.len()));
+    });
+
+    // The blocked reader doesn't hold up the writes nor the configuration
+    timer::sleep(Duration::milliseconds(50));
+    port.write(b"ping").unwrap();
+    assert_eq!(master.read_exact(4).unwrap().as_slice(), b"ping");
+
+    let settings = Settings { baud_rate: B19K2, parity: EvenParity, ..Default::default() };
+    port.configure(&settings).unwrap();
+    assert_eq!(port.clone().settings().unwrap(), settings);
+
+    master.write_str(MESSAGE).unwrap();
+    assert_eq!(receiver.recv().unwrap().as_slice(), MESSAGE.as_bytes());
+}
+
+#[test]
+fn shared_port_settings() {
+    let (_master, slave) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let port = SharedSerialPort::new(slave).unwrap();
+    port.set_timeout(None);
+
+    // The reader sees the read mode changed through the writing half
+    port.with_port(|port| port.set_read_mode(NonBlocking)).unwrap();
+    let mut buf = [0u8, ..16];
+    assert_eq!(port.read(&mut buf).map_err(|e| e.kind), Err(TimedOut));
+}
+
+// Needs the `socat` program
+#[test]
+#[ignore]
+fn socat() {
+    let socat = match testing::socat() {
+        Err(e) => panic!("Couldn't spawn socat ({})", e),
+        Ok(socat) => socat,
+    };
+    let (first, second) = socat.paths();
+
+    let mut tx = SerialPort::open(first, Write).unwrap();
+    let mut rx = SerialPort::open(second, Read).unwrap();
+    rx.set_timeout(Some(Duration::seconds(1)));
+
+    tx.write_str(MESSAGE).unwrap();
+    assert_eq!(rx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+}
+
+#[test]
+fn standard_rates() {
+    let rates = BaudRate::standard_rates();
+
+    assert!(rates.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(!rates.contains(&B0));
+    assert!(rates.contains(&B9K6));
+
+    assert!(B9K6 > B4K8);
+    assert!(B50 < B230K4);
+    assert_eq!(rates.iter().max(), rates.last());
+}
+
+#[test]
+fn stats() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    match tx.write_str(MESSAGE) {
+        Err(e) => panic!("master: Couldn't send message ({})", e),
+        _ => {},
+    }
+    match rx.read_exact(MESSAGE.len()) {
+        Err(e) => panic!("slave: Couldn't read ({})", e),
+        Ok(_) => {},
+    }
+
+    rx.set_timeout(Some(Duration::milliseconds(10)));
+    assert!(rx.read_byte().is_err());
+
+    let stats = tx.stats();
+    assert_eq!((stats.writes, stats.bytes_written), (1, MESSAGE.len() as u64));
+
+    let stats = rx.stats();
+    assert_eq!(stats.bytes_read, MESSAGE.len() as u64);
+    assert!(stats.reads >= 2);
+    assert_eq!((stats.timeouts, stats.errors), (1, 0));
+
+    rx.reset_stats();
+    let zero: Stats = Default::default();
+    assert_eq!(rx.stats(), zero);
+}
+
+#[test]
+fn stop_bits() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    for &bits in [Stop1, Stop2].iter() {
+        match port.set_stop_bits(bits) {
+            Err(e) => panic!("{}: Couldn't set stop bits to {} ({})", port_, bits, e),
+            Ok(_) => {},
+        }
+        let got = match port.stop_bits() {
+            Err(e) => panic!("{}: Couldn't read parity ({})", port_, e),
+            Ok(bits) => bits,
+        };
+
+        if bits != got {
+            panic!("{}: set {} - got {}", port_, bits, got)
+        }
+    }
+}
+
+#[test]
+fn tap() {
+    let (mut first, first_tap) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    let (mut second, second_tap) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    let mut tap = Tap::new(first_tap, second_tap);
+
+    assert!(tap.poll(Some(Duration::milliseconds(10))).unwrap().is_empty());
+
+    first.write(b"ping").unwrap();
+    let records = tap.poll(Some(Duration::milliseconds(100))).unwrap();
+    second.write(b"pong").unwrap();
+    let later = tap.poll(Some(Duration::milliseconds(100))).unwrap();
+
+    let records: Vec<_> = records.iter().chain(later.iter()).map(|record| {
+        (record.direction, record.data.clone())
+    }).collect();
+    assert_eq!(records, vec![(Sent, b"ping".to_vec()), (Received, b"pong".to_vec())]);
+}
+
+#[test]
+fn throttled_writer() {
+    let mut writer = ThrottledWriter::with_burst(MemWriter::new(), 1000, 100);
+
+    let start = time::precise_time_ns();
+    match writer.write(&[0u8, ..300]) {
+        Err(e) => panic!("Couldn't write ({})", e),
+        Ok(_) => {},
+    }
+
+    // The first 100 bytes are a burst, the next 200 take 200 ms
+    assert!(time::precise_time_ns() - start >= 190_000_000);
+    assert_eq!(writer.get_ref().get_ref().len(), 300);
+}
+
+#[test]
+fn timeout() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+    let mut port = match SerialPort::open(&port, Read) {
+        Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+        Ok(port) => port,
+    };
+
+    port.set_timeout(Some(Duration::milliseconds(100)));
+
+    match port.read_byte() {
+        Err(ref e) if e.kind == TimedOut => {},
+        Err(e) => panic!("{}: Expected a time out, got ({})", port_, e),
+        Ok(byte) => panic!("{}: Expected a time out, got {}", port_, byte),
+    }
+}
+
+#[test]
+fn virtual_pair_link() {
+    let (mut first, mut second) = virtual_pair();
+
+    match first.write_str(MESSAGE) {
+        Err(e) => panic!("Couldn't write ({})", e),
+        Ok(_) => {},
+    }
+
+    let mut buf = [0u8, ..100];
+    let mut n = 0;
+    while n < MESSAGE.len() {
+        match second.read(buf.slice_from_mut(n)) {
+            Err(e) => panic!("Couldn't read ({})", e),
+            Ok(read) => n += read,
+        }
+    }
+    assert_eq!(buf.slice_to(n), MESSAGE.as_bytes());
+
+    second.set_timeout(Some(Duration::milliseconds(50)));
+    match second.read_byte() {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+
+    // 100 bytes at 1200 baud 8N1 take about 833 ms
+    let settings = Settings { baud_rate: B1K2, ..Default::default() };
+    match second.configure(&settings) {
+        Err(e) => panic!("Couldn't configure ({})", e),
+        Ok(_) => {},
+    }
+    second.set_timeout(None);
+
+    let start = time::precise_time_ns();
+    match second.write(&[0u8, ..100]).and_then(|_| first.read_exact(100)) {
+        Err(e) => panic!("Couldn't transfer ({})", e),
+        Ok(data) => assert_eq!(data, Vec::from_elem(100, 0u8)),
+    }
+    assert!(time::precise_time_ns() - start >= 800_000_000);
+
+    drop(second);
+    assert_eq!(first.read_byte().map_err(|e| e.kind), Err(EndOfFile));
+    assert!(first.write_str(MESSAGE).is_err());
+}
+
+#[test]
+fn wait_for_carrier() {
+    let (_master, port) = pty();
+    let port_ = port.display();
+
+    // Without a carrier to wait for, both modes open right away and end up blocking
+    for &wait_for_carrier in [true, false].iter() {
+        let options = OpenOptions { wait_for_carrier: wait_for_carrier, ..Default::default() };
+        let port = match SerialPort::open_with_options(&port, ReadWrite, &options) {
+            Err(e) => panic!("{}: Couldn't open ({})", port_, e),
+            Ok(port) => port,
+        };
+
+        let flags = unsafe { fcntl(port.fd, F_GETFL) };
+        assert_eq!(flags & O_NONBLOCK, 0);
+    }
+}
+
+#[test]
+fn wait_for_device() {
+    use std::io::{File, TempDir};
+
+    let dir = TempDir::new("serial").unwrap();
+    let path = dir.path().join("ttyUSB0");
+
+    match ::wait_for_device(&path, Some(Duration::milliseconds(50))) {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+
+    let node = path.clone();
+    spawn(proc() {
+        timer::sleep(Duration::milliseconds(50));
+        File::create(&node).unwrap();
+    });
+    assert_eq!(::wait_for_device(&path, Some(Duration::seconds(5))).unwrap(), path);
+}
+
+#[test]
+fn wait_for_edge() {
+    let (_master, mut port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    // Ptys have no status lines to wait on, the call mustn't block
+    assert!(port.wait_for_edge(&[CarrierDetect, ClearToSend]).is_err());
+}
+
+#[test]
+fn wait_for_events() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    match rx.wait_for_events(RX_AVAILABLE | CD_CHANGED, Some(Duration::milliseconds(100))) {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+
+    tx.write_str(MESSAGE).unwrap();
+    match rx.wait_for_events(RX_AVAILABLE | CD_CHANGED, Some(Duration::seconds(1))) {
+        Err(e) => panic!("Couldn't wait for the data ({})", e),
+        Ok(events) => assert_eq!(events, RX_AVAILABLE),
+    }
+    assert_eq!(rx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+
+    // Nothing was written on this side, the output queue is empty
+    match rx.wait_for_events(TX_EMPTY, Some(Duration::seconds(1))) {
+        Err(e) => panic!("Couldn't wait for the output ({})", e),
+        Ok(events) => assert_eq!(events, TX_EMPTY),
+    }
+}
+
+#[test]
+fn with_deadline() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    rx.set_timeout(Some(Duration::seconds(5)));
+
+    // The deadline cuts the read timeout short
+    let start = time::precise_time_ns();
+    match rx.with_deadline(start + 100_000_000, |rx| rx.read_exact(MESSAGE.len())) {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+    assert!(time::precise_time_ns() - start < 1_000_000_000);
+
+    // Once it has passed, even the available data isn't read
+    tx.write_str(MESSAGE).unwrap();
+    timer::sleep(Duration::milliseconds(50));
+    match rx.with_deadline(start, |rx| rx.read_exact(MESSAGE.len())) {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+    match tx.with_deadline(start, |tx| tx.write_str(MESSAGE)) {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+
+    // The earliest deadline wins, and none is left afterwards
+    let late = time::precise_time_ns() + 5_000_000_000;
+    let result = rx.with_deadline(late, |rx| rx.with_deadline(start, |rx| rx.read_byte()));
+    assert_eq!(result.map_err(|e| e.kind), Err(TimedOut));
+    assert_eq!(rx.read_exact(MESSAGE.len()).unwrap().as_slice(), MESSAGE.as_bytes());
+}
+
+#[test]
+fn write_all() {
+    let (mut master, mut slave) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    // The writes fail with EAGAIN instead of blocking once the buffer of the pty is full
+    let flags = unsafe { fcntl(slave.fd, F_GETFL) };
+    assert!(unsafe { fcntl(slave.fd, F_SETFL, flags | O_NONBLOCK) } != -1);
+
+    // Nobody reads the master side yet
+    let data = Vec::from_elem(1 << 20, 0x55u8);
+    let written = match slave.write_all(data.as_slice(), Some(Duration::milliseconds(100))) {
+        Err(ref e) if e.kind == TimedOut => slave.stats().bytes_written as uint,
+        result => panic!("Expected a time out, got {}", result),
+    };
+    assert!(written > 0 && written < data.len());
+
+    let (sender, receiver) = channel();
+    let len = data.len();
+    spawn(proc() {
+        master.set_timeout(Some(Duration::seconds(5)));
+        sender.send(master.read_exact(len).map(|data| data.len()));
+    });
+
+    // The rest goes through as the other side reads
+    slave.write_all(data.slice_from(written), None).unwrap();
+    assert_eq!(receiver.recv().unwrap(), data.len());
+}
+
+#[test]
+fn write_in_read_only_mode() {
+    let (_master, port) = pty();
+    let mut port = SerialPort::open(&port, Read);
+
+    assert!(port.write_str(MESSAGE).is_err())
+}
+
+#[test]
+fn write_paced() {
+    let (mut tx, mut rx) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let mut progress = Vec::new();
+    match tx.write_paced(b"0123456789", 4, Duration::milliseconds(5), |sent, total| {
+        progress.push((sent, total));
+    }) {
+        Err(e) => panic!("master: Couldn't write ({})", e),
+        Ok(_) => {},
+    }
+    assert_eq!(progress, vec![(4, 10), (8, 10), (10, 10)]);
+
+    match rx.read_exact(10) {
+        Err(e) => panic!("slave: Couldn't read ({})", e),
+        Ok(buf) => assert_eq!(buf.as_slice(), b"0123456789"),
+    }
+}
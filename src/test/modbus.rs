@@ -0,0 +1,154 @@
+use checksum::{Checksum, Crc16};
+use modbus::{
+    Ascii, AsciiDecoder, Exception, Handler, IllegalDataAddress, IllegalDataValue,
+    IllegalFunction, Mode, ReadCoils, Request, Rtu, RtuDecoder, Slave, WriteMultipleCoils,
+    WriteMultipleRegisters, WriteSingleCoil,
+};
+use SerialPort;
+
+use super::total;
+
+/// Holding registers backed by a vector
+struct Registers(Vec<u16>);
+
+impl Handler for Registers {
+    fn read_holding_registers(&mut self, address: u16, count: u16)
+                              -> Result<Vec<u16>, Exception> {
+        let (start, end) = (address as uint, address as uint + count as uint);
+
+        if end > self.0.len() {
+            Err(IllegalDataAddress)
+        } else {
+            Ok(self.0.slice(start, end).to_vec())
+        }
+    }
+}
+
+/// Appends the CRC to `adu`
+fn rtu(adu: &[u8]) -> Vec<u8> {
+    let mut crc = Crc16::modbus();
+    crc.update(adu);
+
+    let mut frame = adu.to_vec();
+    frame.push_all(crc.bytes().as_slice());
+    frame
+}
+
+#[quickcheck]
+fn arbitrary_input(data: Vec<u8>, split: uint) -> bool {
+    let data = data.as_slice();
+
+    total(AsciiDecoder::new(), AsciiDecoder::new(), data, split, |ascii, data| ascii.feed(data)) &&
+        total(RtuDecoder::new(), RtuDecoder::new(), data, split, |rtu, data| rtu.feed(data))
+}
+
+#[quickcheck]
+fn ascii_decoder_resync(garbage: Vec<u8>) -> bool {
+    let mut decoder = AsciiDecoder::new();
+    decoder.feed(garbage.as_slice());
+
+    let mut stream = b"\r\n".to_vec();
+    stream.push_all(b":010300000002FA\r\n");
+
+    decoder.feed(stream.as_slice()).last() == Some(&vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x02])
+}
+
+#[test]
+fn parse() {
+    assert_eq!(Request::parse(&[0x01, 0x00, 0x13, 0x00, 0x25]), Ok(ReadCoils(0x13, 0x25)));
+    assert_eq!(Request::parse(&[0x05, 0x00, 0xAC, 0xFF, 0x00]), Ok(WriteSingleCoil(0xAC, true)));
+    assert_eq!(Request::parse(&[0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01]),
+               Ok(WriteMultipleCoils(0x13, vec![
+                   true, false, true, true, false, false, true, true, true, false,
+               ])));
+    assert_eq!(Request::parse(&[0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02]),
+               Ok(WriteMultipleRegisters(0x01, vec![0x000A, 0x0102])));
+}
+
+#[test]
+fn parse_invalid() {
+    assert_eq!(Request::parse(&[0x2B, 0x0E, 0x01, 0x00]), Err(IllegalFunction));
+    assert_eq!(Request::parse(&[0x03, 0x00, 0x00, 0x00, 0x00]), Err(IllegalDataValue));
+    assert_eq!(Request::parse(&[0x05, 0x00, 0xAC, 0x12, 0x34]), Err(IllegalDataValue));
+    assert_eq!(Request::parse(&[0x10, 0x00, 0x01, 0x00, 0x02, 0x02, 0x00, 0x0A]),
+               Err(IllegalDataValue));
+}
+
+/// Runs a slave in `mode`, sends it the requests and checks its responses
+///
+/// A `None` response means that the slave must ignore the request
+fn serve(mode: Mode, exchanges: &[(Vec<u8>, Option<Vec<u8>>)]) {
+    let (mut master, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let requests = exchanges.iter().filter(|&&(_, ref response)| response.is_some()).count();
+    spawn(proc() {
+        let mut slave = Slave::new(port, 0x01, Registers(vec![0x000A, 0x0102]));
+        slave.set_mode(mode);
+
+        for _ in range(0, requests) {
+            match slave.serve_one() {
+                Err(e) => panic!("slave: Couldn't serve request ({})", e),
+                Ok(_) => {},
+            }
+        }
+    });
+
+    for &(ref request, ref response) in exchanges.iter() {
+        match master.write(request.as_slice()) {
+            Err(e) => panic!("master: Couldn't send request ({})", e),
+            Ok(_) => {},
+        }
+
+        match *response {
+            None => {},
+            Some(ref response) => match master.read_exact(response.len()) {
+                Err(e) => panic!("master: Couldn't read response ({})", e),
+                Ok(got) => assert_eq!(&got, response),
+            },
+        }
+    }
+}
+
+#[test]
+fn rtu_decoder() {
+    let request = rtu(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x02]);
+    let mut corrupted = request.clone();
+    corrupted.as_mut_slice()[3] ^= 0x01;
+
+    // Noise, a corrupted request, then an unknown function
+    let mut stream = vec![0xFF, 0x00];
+    stream.push_all(corrupted.as_slice());
+    stream.push_all(&[0x01, 0x2B]);
+    stream.push_all(request.as_slice());
+
+    let mut decoder = RtuDecoder::new();
+    let mut adus = Vec::new();
+    for chunk in stream.as_slice().chunks(3) {
+        adus.push_all(decoder.feed(chunk).as_slice());
+    }
+
+    assert_eq!(adus, vec![vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x02]]);
+}
+
+#[test]
+fn slave_ascii() {
+    serve(Ascii, &[
+        // Line noise, then a frame with a bad LRC, both must be ignored
+        (b"\x00\xFF:010300000002FB\r\n".to_vec(), None),
+        (b":010300000002FA\r\n".to_vec(), Some(b":010304000A0102EB\r\n".to_vec())),
+    ]);
+}
+
+#[test]
+fn slave_rtu() {
+    serve(Rtu, &[
+        // Addressed to another unit, must be ignored
+        (rtu(&[0x02, 0x03, 0x00, 0x00, 0x00, 0x02]), None),
+        (rtu(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x02]),
+         Some(rtu(&[0x01, 0x03, 0x04, 0x00, 0x0A, 0x01, 0x02]))),
+        (rtu(&[0x01, 0x03, 0x00, 0x01, 0x00, 0x02]), Some(rtu(&[0x01, 0x83, 0x02]))),
+    ]);
+}
@@ -0,0 +1,30 @@
+use std::io::EndOfFile;
+
+use mux::{MAX_PAYLOAD, Mux};
+use virtual_pair;
+
+#[test]
+fn channels() {
+    let (first, second) = virtual_pair();
+    let (host, device) = (Mux::new(first), Mux::new(second));
+
+    let (mut host_console, mut host_telemetry) = (host.channel(0), host.channel(1));
+    let (mut device_console, mut device_telemetry) = (device.channel(0), device.channel(1));
+
+    // Frames for channels that aren't open are discarded
+    host.channel(7).write(b"lost").unwrap();
+
+    let block = Vec::from_elem(MAX_PAYLOAD * 2 + 10, 0xA5u8);
+    host_telemetry.write(block.as_slice()).unwrap();
+    host_console.write_str("reboot\n").unwrap();
+    device_console.write_str("ok\n").unwrap();
+
+    assert_eq!(device_console.read_exact(7).unwrap().as_slice(), b"reboot\n");
+    assert_eq!(device_telemetry.read_exact(block.len()).unwrap(), block);
+    assert_eq!(host_console.read_exact(3).unwrap().as_slice(), b"ok\n");
+
+    // Closing one end stops the other
+    drop((host, host_console, host_telemetry));
+    assert_eq!(device_console.read_byte().map_err(|e| e.kind), Err(EndOfFile));
+    assert_eq!(device_telemetry.read_byte().map_err(|e| e.kind), Err(EndOfFile));
+}
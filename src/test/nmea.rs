@@ -0,0 +1,130 @@
+use std::io::{BufReader, InvalidInput};
+
+use nmea::{
+    BadChecksum, Date, Gga, GgaSentence, GsvSentence, Malformed, Raw, RmcSentence, Satellite,
+    Sentence, SentenceDecoder, Sentences, Time, UnknownSentence,
+};
+
+use super::total;
+
+const GGA: &'static str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+const GSV: &'static str = "$GPGSV,2,1,08,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45*75";
+const RMC: &'static str = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+
+#[quickcheck]
+fn arbitrary_input(data: Vec<u8>, split: uint) -> bool {
+    total(SentenceDecoder::new(), SentenceDecoder::new(), data.as_slice(), split,
+          |decoder, data| decoder.feed(data))
+}
+
+#[test]
+fn decoder() {
+    let mut stream = b"\xFF\xFE garbage $GPGGA,123519,4807".to_vec();
+    stream.push_all(GGA.as_bytes());
+    stream.push_all(b"\r\n");
+    stream.push_all(RMC.as_bytes());
+    stream.push_all(b"\r\n$GP\xC3\xA9GA*7B\r\n");
+
+    let mut decoder = SentenceDecoder::new();
+    let mut sentences = Vec::new();
+    for chunk in stream.as_slice().chunks(5) {
+        sentences.push_all(decoder.feed(chunk).as_slice());
+    }
+
+    assert_eq!(sentences, vec![
+        Err(Malformed),
+        Sentence::parse(GGA),
+        Sentence::parse(RMC),
+        Err(Malformed),
+    ]);
+}
+
+#[quickcheck]
+fn decoder_resync(garbage: Vec<u8>) -> bool {
+    let mut decoder = SentenceDecoder::new();
+    decoder.feed(garbage.as_slice());
+
+    let mut line = b"\r\n".to_vec();
+    line.push_all(GGA.as_bytes());
+    line.push_all(b"\r\n");
+
+    decoder.feed(line.as_slice()).last() == Some(&Sentence::parse(GGA))
+}
+
+#[test]
+fn gga() {
+    match Sentence::parse(GGA) {
+        Ok(GgaSentence(gga)) => {
+            let (latitude, longitude) = (gga.latitude.unwrap(), gga.longitude.unwrap());
+            assert!((latitude - (48.0 + 7.038 / 60.0)).abs() < 1e-9);
+            assert!((longitude - (11.0 + 31.0 / 60.0)).abs() < 1e-9);
+
+            assert_eq!(gga, Gga {
+                time: Some(Time { hour: 12, minute: 35, second: 19.0 }),
+                latitude: gga.latitude,
+                longitude: gga.longitude,
+                fix_quality: 1,
+                satellites: Some(8),
+                hdop: Some(0.9),
+                altitude: Some(545.4),
+            });
+        },
+        got => panic!("Expected a GGA sentence, got {}", got),
+    }
+}
+
+#[test]
+fn gsv() {
+    match Sentence::parse(GSV) {
+        Ok(GsvSentence(gsv)) => {
+            assert_eq!(gsv.total_messages, 2);
+            assert_eq!(gsv.message_number, 1);
+            assert_eq!(gsv.satellites_in_view, 8);
+            assert_eq!(gsv.satellites.len(), 4);
+            assert_eq!(gsv.satellites[3], Satellite {
+                prn: 14,
+                elevation: Some(22),
+                azimuth: Some(228),
+                snr: Some(45),
+            });
+        },
+        got => panic!("Expected a GSV sentence, got {}", got),
+    }
+}
+
+#[test]
+fn invalid() {
+    assert_eq!(Sentence::parse("$GPGGA,123519*48"), Err(BadChecksum));
+    assert_eq!(Sentence::parse("GPGGA,123519*47"), Err(Malformed));
+    assert_eq!(Sentence::parse("$GPGGA,123519"), Err(Malformed));
+}
+
+#[test]
+fn rmc() {
+    match Sentence::parse(format!("{}\r\n", RMC).as_slice()) {
+        Ok(RmcSentence(rmc)) => {
+            assert!(rmc.valid);
+            assert_eq!(rmc.date, Some(Date { day: 23, month: 3, year: 1994 }));
+            assert_eq!(rmc.speed, Some(22.4));
+            assert_eq!(rmc.magnetic_variation, Some(-3.1));
+        },
+        got => panic!("Expected a RMC sentence, got {}", got),
+    }
+}
+
+#[test]
+fn sentences() {
+    let input = format!("{}\r\n$GPGGA,garbage*00\r\n$PGRME,15.0,M,45.0,M,25.0,M*1C\r\n", GGA);
+    let mut sentences = Sentences::new(BufReader::new(input.as_bytes()));
+
+    assert!(match sentences.next() { Some(Ok(GgaSentence(_))) => true, _ => false });
+    assert!(match sentences.next() { Some(Err(ref e)) => e.kind == InvalidInput, _ => false });
+    assert_eq!(sentences.next().map(|sentence| sentence.unwrap()), Some(UnknownSentence(Raw {
+        talker: "P".to_string(),
+        kind: "GRME".to_string(),
+        fields: vec!["15.0", "M", "45.0", "M", "25.0", "M"].iter().map(|field| {
+            field.to_string()
+        }).collect(),
+    })));
+    assert!(sentences.next().is_none());
+}
@@ -0,0 +1,120 @@
+use std::io::{InvalidInput, OtherIoError};
+use std::time::Duration;
+
+use at::Session;
+use obd::{CoolantTemperature, Elm327, EngineSpeed, SupportedPids, VehicleSpeed, decode};
+use obd::parse_response;
+use SerialPort;
+
+/// Emulates an ELM327 connected to a car, with echo and line feeds on until told otherwise
+fn elm327(mut port: SerialPort) {
+    let mut echo = true;
+
+    loop {
+        let mut command = String::new();
+        loop {
+            match port.read_byte().unwrap() {
+                b'\r' => break,
+                byte => command.push(byte as char),
+            }
+        }
+
+        let response = match command.as_slice() {
+            "ATZ" => "\r\rELM327 v1.5",
+            "ATE0" => {
+                echo = false;
+                "OK"
+            },
+            "ATL0" | "ATH0" | "ATSP0" => "OK",
+            "0100" => "SEARCHING...\r41 00 BE 3F A8 13",
+            "010C" => "41 0C 1A F8\r41 0C 1A FC",
+            "0902" => concat!("014\r0: 49 02 01 31 47 31\r",
+                              "1: 4A 43 35 34 34 34 52\r2: 37 32 35 32 33 36 37"),
+            "0120" => "NO DATA",
+            "STOP" => return,
+            _ => "?",
+        };
+
+        if echo {
+            port.write_str(command.as_slice()).unwrap();
+            port.write_str("\r").unwrap();
+        }
+        port.write_str(response).unwrap();
+        port.write_str("\r\r>").unwrap();
+    }
+}
+
+#[test]
+fn adapter() {
+    let (device, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    spawn(proc() elm327(device));
+
+    let mut elm = Elm327::new(Session::new(port));
+    elm.set_timeout(Duration::seconds(1));
+
+    match elm.init() {
+        Err(e) => panic!("Couldn't initialize the adapter ({})", e),
+        Ok(version) => assert_eq!(version, "ELM327 v1.5".to_string()),
+    }
+
+    match elm.read(0x00) {
+        Err(e) => panic!("Couldn't read the supported PIDs ({})", e),
+        Ok(pids) => assert_eq!(pids, SupportedPids(vec![
+            0x01, 0x03, 0x04, 0x05, 0x06, 0x07, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F, 0x10, 0x11, 0x13,
+            0x15, 0x1C, 0x1F, 0x20,
+        ])),
+    }
+
+    // Two ECUs answer
+    match elm.request(0x01, 0x0C) {
+        Err(e) => panic!("Couldn't read the engine speed ({})", e),
+        Ok(responses) => assert_eq!(responses, vec![vec![0x1A, 0xF8], vec![0x1A, 0xFC]]),
+    }
+
+    match elm.vin() {
+        Err(e) => panic!("Couldn't read the VIN ({})", e),
+        Ok(vin) => assert_eq!(vin, "1G1JC5444R7252367".to_string()),
+    }
+
+    match elm.supported_pids() {
+        Err(ref e) if e.kind == OtherIoError => {},
+        got => panic!("Expected NO DATA for the PIDs 0x21-0x40, got {}", got),
+    }
+
+    elm.get_mut().get_mut().write_str("STOP\r").unwrap();
+}
+
+#[test]
+fn pids() {
+    assert_eq!(decode(0x0C, &[0x1A, 0xF8]), Some(EngineSpeed(1726.)));
+    assert_eq!(decode(0x05, &[0x7B]), Some(CoolantTemperature(83)));
+    assert_eq!(decode(0x0D, &[]), None);
+    assert_eq!(decode(0x0D, &[0x32]), Some(VehicleSpeed(50)));
+    assert_eq!(decode(0xE0, &[0x00, 0x00, 0x00, 0x03]), Some(SupportedPids(vec![0xFF])));
+    assert_eq!(decode(0x03, &[0x02, 0x00]), None);
+}
+
+#[test]
+fn response() {
+    let lines = |text: &str| text.split('\r').map(|line| line.to_string()).collect::<Vec<_>>();
+
+    match parse_response(lines("00A\r0: 49 02 01 31 44\r1: 34 47 50 30 30 52 35").as_slice()) {
+        Err(e) => panic!("Couldn't parse a multi-frame response ({})", e),
+        Ok(responses) => assert_eq!(responses, vec![vec![
+            0x49, 0x02, 0x01, 0x31, 0x44, 0x34, 0x47, 0x50, 0x30, 0x30,
+        ]]),
+    }
+
+    match parse_response(lines("BUS INIT: ...\rCAN ERROR").as_slice()) {
+        Err(ref e) if e.kind == OtherIoError => {},
+        got => panic!("Expected a failure, got {}", got),
+    }
+    match parse_response(lines("014\r0: 49 02 01").as_slice()) {
+        Err(ref e) if e.kind == InvalidInput => {},
+        got => panic!("Expected an incomplete response, got {}", got),
+    }
+}
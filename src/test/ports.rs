@@ -0,0 +1,73 @@
+use ports::{Bluetooth, BuiltInUart, Ch340, Ftdi, PortFilter, PortInfo, PortKind};
+use ports::{PseudoTerminal, UsbBridge, UsbCdcAcm, available_ports};
+use testing;
+
+fn port(path: &str, kind: PortKind, driver: Option<&str>) -> PortInfo {
+    PortInfo { path: Path::new(path), kind: kind, driver: driver.map(|d| d.to_string()) }
+}
+
+#[test]
+fn filters() {
+    let uart = port("/dev/ttyS0", BuiltInUart, Some("serial8250"));
+    let acm = port("/dev/ttyACM0", UsbCdcAcm, Some("cdc_acm"));
+    let ftdi = port("/dev/ttyUSB0", UsbBridge(Ftdi), Some("ftdi_sio"));
+    let ch340 = port("/dev/ttyUSB1", UsbBridge(Ch340), Some("ch341-uart"));
+    let rfcomm = port("/dev/rfcomm0", Bluetooth, None);
+    let pty = port("/dev/pts/3", PseudoTerminal, None);
+    let all = [&uart, &acm, &ftdi, &ch340, &rfcomm, &pty];
+
+    let selected = |filter: PortFilter| -> Vec<String> {
+        all.iter().filter(|port| filter.matches(**port)).map(|port| {
+            port.path.display().to_string()
+        }).collect()
+    };
+
+    assert_eq!(selected(PortFilter::new()).len(), all.len());
+    assert_eq!(selected(PortFilter::plausible()).len(), all.len() - 1);
+    assert_eq!(selected(PortFilter::new().usb()),
+               vec!["/dev/ttyACM0".to_string(), "/dev/ttyUSB0".to_string(),
+                    "/dev/ttyUSB1".to_string()]);
+    assert_eq!(selected(PortFilter::new().usb_bridges().driver("ftdi_sio")),
+               vec!["/dev/ttyUSB0".to_string()]);
+    assert_eq!(selected(PortFilter::new().kind(UsbBridge(Ch340)).kind(Bluetooth)),
+               vec!["/dev/ttyUSB1".to_string(), "/dev/rfcomm0".to_string()]);
+
+    assert!(ftdi.kind.is_usb() && !rfcomm.kind.is_usb());
+}
+
+#[test]
+fn open_bluetooth() {
+    let pty = match testing::pty() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pty) => pty,
+    };
+
+    // A pty stands in for the RFCOMM device
+    let info = PortInfo { path: pty.path.clone(), kind: Bluetooth, driver: None };
+    let port = info.open().unwrap();
+    assert!(port.ignore_baud_rate());
+    assert!(port.local_mode().unwrap());
+
+    let info = PortInfo { path: pty.path.clone(), kind: PseudoTerminal, driver: None };
+    assert!(!info.open().unwrap().ignore_baud_rate());
+
+    let info = PortInfo { path: Path::new("/dev/rfcomm99"), kind: Bluetooth, driver: None };
+    match info.open() {
+        Err(ref e) => assert_eq!(e.desc, "Couldn't connect the Bluetooth link"),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn ptys() {
+    let pty = match testing::pty() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pty) => pty,
+    };
+
+    let listed = available_ports().unwrap();
+    assert!(listed.iter().any(|port| port.path == pty.path && port.kind == PseudoTerminal));
+
+    let plausible = PortFilter::plausible().list().unwrap();
+    assert!(plausible.iter().all(|port| port.kind != PseudoTerminal));
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use profile::Profiles;
+use testing;
+use {B115K2, B9K6, EvenParity, HardwareControl, SerialIo};
+
+#[test]
+fn formats() {
+    let toml = Profiles::from_toml(r#"
+        # Fleet ports
+        [gps]
+        device = "/dev/serial/by-id/usb-u-blox*"  # any u-blox receiver
+        baud_rate = 9600
+        timeout_ms = 1_000
+
+        [modem]
+        driver = "option"
+        baud_rate = 115200
+        parity = "even"
+        flow_control = "hardware"
+    "#).unwrap();
+    let json = Profiles::from_json(r#"{
+        "gps": {"device": "/dev/serial/by-id/usb-u-blox*", "baud_rate": 9600, "timeout_ms": 1000},
+        "modem": {"driver": "option", "baud_rate": 115200, "parity": "even",
+                  "flow_control": "hardware"}
+    }"#).unwrap();
+    assert_eq!(toml, json);
+    assert_eq!(toml.names(), vec!["gps", "modem"]);
+
+    let gps = toml.get("gps").unwrap();
+    assert_eq!(gps.device.path, Some("/dev/serial/by-id/usb-u-blox*".to_string()));
+    assert_eq!(gps.settings.baud_rate, B9K6);
+    assert_eq!(gps.timeout, Some(Duration::seconds(1)));
+
+    let modem = toml.get("modem").unwrap();
+    assert_eq!(modem.device.driver, Some("option".to_string()));
+    assert_eq!(modem.settings.baud_rate, B115K2);
+    assert_eq!(modem.settings.parity, EvenParity);
+    assert_eq!(modem.settings.flow_control, HardwareControl);
+
+    assert!(Profiles::from_toml("[gps]\nbaud_rate = 9601").is_err());
+    assert!(Profiles::from_toml("[gps]\nbaudrate = 9600").is_err());
+    assert!(Profiles::from_toml("baud_rate = 9600").is_err());
+    assert!(Profiles::from_json(r#"{"gps": {"parity": "x"}}"#).is_err());
+}
+
+#[test]
+fn open_profile() {
+    let pty = match testing::pty() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pty) => pty,
+    };
+    let profiles = Profiles::from_json(format!(r#"{{"pty": {{"device": "{}", "baud_rate": 115200,
+                                                  "timeout_ms": 50}}}}"#,
+                                               pty.path.display()).as_slice()).unwrap();
+
+    let port = profiles.open_profile("pty").unwrap();
+    assert_eq!(port.settings().unwrap().baud_rate, B115K2);
+    assert_eq!(port.timeout(), Some(Duration::milliseconds(50)));
+
+    assert!(profiles.open_profile("gps").is_err());
+}
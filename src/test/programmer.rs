@@ -0,0 +1,81 @@
+use programmer::stk500v1::Stk500v1;
+use SerialPort;
+
+/// Emulates Optiboot on an ATmega328P, returns the flash once the programming mode is left
+fn optiboot(mut port: SerialPort) -> Vec<u8> {
+    let mut flash = Vec::from_elem(32 * 1024, 0xFFu8);
+    let mut address = 0u;
+
+    // Leftover output of the previous sketch
+    port.write_str("Hello from the sketch\r\n").unwrap();
+
+    loop {
+        let command = port.read_byte().unwrap();
+        let args = match command {
+            0x30 | 0x50 | 0x51 | 0x75 => 0,
+            0x41 => 1,
+            0x55 => 2,
+            0x64 | 0x74 => 3,
+            _ => panic!("optiboot: Unexpected command {:#04x}", command),
+        };
+
+        let args = port.read_exact(args).unwrap();
+        let len = if args.len() == 3 { (args[0] as uint) << 8 | args[1] as uint } else { 0 };
+        let data = port.read_exact(if command == 0x64 { len } else { 0 }).unwrap();
+        assert_eq!(port.read_byte().unwrap(), 0x20);
+
+        let mut response = vec![0x14];
+        match command {
+            0x41 => response.push(0x08),
+            0x55 => address = (args[0] as uint | (args[1] as uint) << 8) * 2,
+            0x64 => for (i, &byte) in data.iter().enumerate() {
+                flash.as_mut_slice()[address + i] = byte;
+            },
+            0x74 => response.push_all(flash.slice(address, address + len)),
+            0x75 => response.push_all(&[0x1E, 0x95, 0x0F]),
+            _ => {},
+        }
+        response.push(0x10);
+
+        port.write(response.as_slice()).unwrap();
+
+        if command == 0x51 {
+            return flash
+        }
+    }
+}
+
+#[test]
+fn stk500v1() {
+    let (device, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let (tx, rx) = channel();
+    spawn(proc() {
+        tx.send(optiboot(device));
+    });
+
+    let image: Vec<u8> = range(0, 300u).map(|i| (i * 13) as u8).collect();
+    let mut programmer = Stk500v1::new(port);
+
+    match programmer.sync(5) {
+        Err(e) => panic!("Couldn't sync with the bootloader ({})", e),
+        Ok(_) => {},
+    }
+
+    match programmer.signature() {
+        Err(e) => panic!("Couldn't read the signature ({})", e),
+        Ok(signature) => assert_eq!(signature.as_slice(), [0x1E, 0x95, 0x0F].as_slice()),
+    }
+
+    match programmer.flash(image.as_slice(), 128) {
+        Err(e) => panic!("Couldn't flash the image ({})", e),
+        Ok(_) => {},
+    }
+
+    let flash = rx.recv();
+    assert_eq!(flash.slice_to(300), image.as_slice());
+    assert!(flash.slice(300, 384).iter().all(|&byte| byte == 0xFF));
+}
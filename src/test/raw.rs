@@ -0,0 +1,44 @@
+use std::io::IoError;
+
+use raw::{CLOCAL, FAILURE, IXON, TCSANOW, Termios, tcgetattr, tcsetattr};
+use SerialPort;
+
+#[test]
+fn termios() {
+    let (_master, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    let fd = port.as_raw_fd();
+
+    let mut termios = Termios::new();
+    if unsafe { tcgetattr(fd, &mut termios) } == FAILURE {
+        panic!("tcgetattr failed ({})", IoError::last_error());
+    }
+
+    termios.c_cflag ^= CLOCAL;
+    if unsafe { tcsetattr(fd, TCSANOW, &termios) } == FAILURE {
+        panic!("tcsetattr failed ({})", IoError::last_error());
+    }
+    assert_eq!(port.local_mode().unwrap(), termios.c_cflag & CLOCAL != 0);
+
+    // Invalid descriptors are reported like in C
+    assert_eq!(unsafe { tcgetattr(-1, &mut termios) }, FAILURE);
+}
+
+#[test]
+fn with_raw_termios() {
+    let (_master, mut port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    port.with_raw_termios(|termios| termios.c_iflag |= IXON).unwrap();
+    assert!(!port.is_eight_bit_clean().unwrap());
+
+    // The change outlives the next setter
+    port.set_local_mode(true).unwrap();
+    let mut termios = Termios::new();
+    assert!(unsafe { tcgetattr(port.as_raw_fd(), &mut termios) } != FAILURE);
+    assert!(termios.c_iflag & IXON != 0);
+}
@@ -0,0 +1,74 @@
+use std::io::{EndOfFile, TimedOut};
+use std::time::Duration;
+use time;
+
+use capture::Record;
+use replay::ReplayPort;
+use trace::{Direction, Received, Sent};
+use {SerialIo, Timestamp, VirtualClock};
+
+fn record(direction: Direction, ms: u64, data: &[u8]) -> Record {
+    Record {
+        direction: direction,
+        timestamp: Timestamp { time: time::get_time(), precise_ns: ms * 1_000_000 },
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn replay() {
+    let mut port = ReplayPort::new(vec![
+        record(Sent, 0, b"AT\r"),
+        record(Received, 100, b"\r\nOK\r\n"),
+        record(Sent, 150, b"ATI\r"),
+    ]);
+
+    // Not the recorded command
+    assert!(port.write(b"ATZ\r").is_err());
+
+    port.write(b"A").unwrap();
+    port.write(b"T\r").unwrap();
+
+    port.set_timeout(Some(Duration::milliseconds(10)));
+    match port.read_byte() {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+
+    port.set_timeout(None);
+    let start = time::precise_time_ns();
+    assert_eq!(port.read_exact(6).unwrap().as_slice(), b"\r\nOK\r\n");
+    assert!(time::precise_time_ns() - start >= 50_000_000);
+
+    // The device expects a command
+    assert!(port.read_byte().is_err());
+
+    port.write(b"ATI\r").unwrap();
+    assert!(port.is_finished());
+
+    match port.read_byte() {
+        Err(ref e) if e.kind == EndOfFile => {},
+        result => panic!("Expected the end of the session, got {}", result),
+    }
+}
+
+#[test]
+fn virtual_clock() {
+    let clock = VirtualClock::new();
+    let mut port = ReplayPort::new(vec![record(Received, 0, b"+"),
+                                        record(Received, 3_600_000, b"-")]);
+    port.set_clock(clock.clone());
+
+    assert_eq!(port.read_exact(2).unwrap().as_slice(), b"+-");
+    assert_eq!(clock.now(), Duration::hours(1));
+}
+
+#[test]
+fn without_timing() {
+    let mut port = ReplayPort::new(vec![record(Received, 0, b"+"), record(Received, 10000, b"-")]);
+    port.set_timing(false);
+
+    let start = time::precise_time_ns();
+    assert_eq!(port.read_exact(2).unwrap().as_slice(), b"+-");
+    assert!(time::precise_time_ns() - start < 1_000_000_000);
+}
@@ -0,0 +1,57 @@
+use std::default::Default;
+use std::io::{Acceptor, Listener};
+use std::io::net::tcp::TcpListener;
+use std::time::Duration;
+
+use rfc2217::Rfc2217Port;
+use {B57K6, B115K2, EvenParity, SerialIo, Settings};
+
+const IAC: u8 = 255;
+const DO: u8 = 253;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+#[test]
+fn client() {
+    let mut listener = TcpListener::bind("127.0.0.1", 0).unwrap();
+    let port = listener.socket_name().unwrap().port;
+    let mut acceptor = listener.listen().unwrap();
+
+    spawn(proc() {
+        let mut server = acceptor.accept().unwrap();
+        server.set_read_timeout(Some(1000));
+
+        // The COM-PORT-OPTION and the binary transmission are offered on connection
+        assert_eq!(server.read_exact(9).unwrap(), vec![IAC, WILL, 44, IAC, WILL, 0, IAC, DO, 0]);
+
+        // 115200 baud, 8 data bits, even parity, 1 stop bit, no flow control
+        let rate = vec![IAC, SB, 44, 1, 0x00, 0x01, 0xC2, 0x00, IAC, SE];
+        assert_eq!(server.read_exact(10).unwrap(), rate);
+        for &(command, value) in [(2u8, 8u8), (3, 3), (4, 1), (5, 1)].iter() {
+            assert_eq!(server.read_exact(7).unwrap(), vec![IAC, SB, 44, command, value, IAC, SE]);
+        }
+
+        // The rate is rounded, the go ahead suppression is asked for, and the data comes with the
+        // IAC bytes doubled
+        server.write(&[IAC, SB, 44, 101, 0x00, 0x00, 0xE1, 0x00, IAC, SE]).unwrap();
+        server.write(&[IAC, DO, 3, b'a', IAC, IAC, b'b']).unwrap();
+
+        assert_eq!(server.read_exact(3).unwrap(), vec![IAC, WILL, 3]);
+        assert_eq!(server.read_exact(3).unwrap(), vec![0x01, IAC, IAC]);
+        server.write(b"ok").unwrap();
+    });
+
+    let mut port = Rfc2217Port::connect("127.0.0.1", port).unwrap();
+    port.set_timeout(Some(Duration::seconds(1)));
+
+    let settings = Settings { baud_rate: B115K2, parity: EvenParity, ..Default::default() };
+    port.configure(&settings).unwrap();
+    assert_eq!(port.settings().unwrap(), settings);
+
+    assert_eq!(port.read_exact(3).unwrap(), vec![b'a', IAC, b'b']);
+    assert_eq!(port.settings().unwrap().baud_rate, B57K6);
+
+    port.write(&[0x01, IAC]).unwrap();
+    assert_eq!(port.read_exact(2).unwrap().as_slice(), b"ok");
+}
@@ -0,0 +1,59 @@
+use std::io::{IoResult, OtherIoError, TimedOut};
+use std::time::Duration;
+
+use rs485::{DirectionControl, HalfDuplex};
+use SerialPort;
+
+/// Records the direction changes
+struct Recorder(Vec<bool>);
+
+impl DirectionControl for Recorder {
+    fn set_transmit(&mut self, _: &mut SerialPort, transmit: bool) -> IoResult<()> {
+        self.0.push(transmit);
+        Ok(())
+    }
+}
+
+#[test]
+fn echo_suppression() {
+    let (mut master, slave) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+    let mut bus = HalfDuplex::new(slave, Recorder(Vec::new())).unwrap()
+                             .suppress_echo(Duration::milliseconds(100));
+
+    // The echo of the request, then the answer
+    master.write_str("requestanswer").unwrap();
+    bus.write_str("request").unwrap();
+    assert_eq!(master.read_exact(7).unwrap(), b"request".to_vec());
+    assert_eq!(bus.read_exact(6).unwrap(), b"answer".to_vec());
+
+    // Another node talked over the request
+    master.write_str("reqXest").unwrap();
+    match bus.write_str("request") {
+        Err(ref e) if e.kind == OtherIoError => {},
+        result => panic!("Expected a collision, got {}", result),
+    }
+
+    match bus.write_str("request") {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+}
+
+#[test]
+fn half_duplex() {
+    let (mut master, slave) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let mut bus = HalfDuplex::new(slave, Recorder(Vec::new())).unwrap();
+    bus.write_str("request").unwrap();
+    assert_eq!(master.read_exact(7).unwrap(), b"request".to_vec());
+
+    // The bus is released first, then the driver is enabled for the write only
+    let (_, Recorder(changes)) = bus.into_inner();
+    assert_eq!(changes, vec![false, true, false]);
+}
@@ -0,0 +1,111 @@
+use std::default::Default;
+use std::io::{MemReader, MemWriter, TimedOut};
+use std::time::Duration;
+use time;
+
+use sim::{Link, Noise, NoisyPort, SlowPort};
+use {SerialIo, SerialPort, VirtualClock};
+
+const DATA: &'static [u8] = b"The quick brown fox jumps over the lazy dog";
+
+/// Writes `DATA` through a port with the `noise`, returns what came out
+fn written(noise: Noise, seed: u32) -> Vec<u8> {
+    let mut port = NoisyPort::new(MemWriter::new(), seed);
+    port.set_write_noise(noise);
+    port.write(DATA).unwrap();
+
+    port.into_inner().unwrap()
+}
+
+#[test]
+fn deterministic() {
+    let noise = Noise { corrupt: 0.2, drop: 0.2, duplicate: 0.2, ..Default::default() };
+
+    assert_eq!(written(noise.clone(), 42), written(noise.clone(), 42));
+    assert!(written(noise.clone(), 42) != written(noise, 43));
+}
+
+#[test]
+fn read_noise() {
+    let mut port = NoisyPort::new(MemReader::new(DATA.to_vec()), 1);
+    port.set_read_noise(Noise { duplicate: 1., ..Default::default() });
+
+    let mut doubled = Vec::new();
+    for &byte in DATA.iter() {
+        doubled.push_all(&[byte, byte]);
+    }
+
+    // The duplicates don't fit in a single read
+    let mut buf = Vec::from_elem(DATA.len(), 0u8);
+    assert_eq!(port.read(buf.as_mut_slice()), Ok(DATA.len()));
+    assert_eq!(buf.as_slice(), doubled.slice_to(DATA.len()));
+    assert_eq!(port.read_to_end().unwrap().as_slice(), doubled.slice_from(DATA.len()));
+}
+
+#[test]
+fn slow_link() {
+    let (mut device, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let link = Link { latency: Duration::milliseconds(50), ..Link::from_baud_rate(10000) };
+    let mut port = SlowPort::new(port, link, 0);
+
+    device.write(b"+").unwrap();
+    port.set_timeout(Some(Duration::milliseconds(10)));
+    match port.read_byte() {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+    port.set_timeout(None);
+    assert_eq!(port.read_byte(), Ok(b'+'));
+
+    // 50 ms of latency, then 1 ms per byte
+    let start = time::precise_time_ns();
+    device.write(DATA).unwrap();
+    assert_eq!(port.read_exact(DATA.len()).unwrap().as_slice(), DATA);
+    assert!(time::precise_time_ns() - start >= 80_000_000);
+}
+
+#[test]
+fn virtual_clock() {
+    let (mut device, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let clock = VirtualClock::new();
+    let link = Link { latency: Duration::minutes(1), ..Link::from_baud_rate(10000) };
+    let mut port = SlowPort::new(port, link, 0);
+    port.set_clock(clock.clone());
+    let start = time::precise_time_ns();
+
+    device.write(b"+").unwrap();
+    port.set_timeout(Some(Duration::seconds(10)));
+    match port.read_byte() {
+        Err(ref e) if e.kind == TimedOut => {},
+        result => panic!("Expected a time out, got {}", result),
+    }
+    assert_eq!(clock.now(), Duration::seconds(10));
+
+    // A minute is well past the 32 bits of nanoseconds
+    port.set_timeout(None);
+    assert_eq!(port.read_byte(), Ok(b'+'));
+    assert_eq!(clock.now(), Duration::minutes(1));
+
+    // None of this took real time
+    assert!(time::precise_time_ns() - start < 1_000_000_000);
+}
+
+#[test]
+fn write_noise() {
+    assert_eq!(written(Default::default(), 0).as_slice(), DATA);
+    assert_eq!(written(Noise { drop: 1., ..Default::default() }, 0), vec![]);
+
+    let corrupted = written(Noise { corrupt: 1., ..Default::default() }, 0);
+    assert_eq!(corrupted.len(), DATA.len());
+    for (&a, &b) in corrupted.iter().zip(DATA.iter()) {
+        assert_eq!((a ^ b).count_ones(), 1);
+    }
+}
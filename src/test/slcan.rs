@@ -0,0 +1,62 @@
+use slcan::{Adapter, Frame, Rate500K};
+use SerialPort;
+
+#[test]
+fn adapter() {
+    let (mut device, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    spawn(proc() {
+        assert_eq!(device.read_exact(3).unwrap().as_slice(), b"S6\r");
+        device.write(b"\r").unwrap();
+        assert_eq!(device.read_exact(2).unwrap().as_slice(), b"O\r");
+        device.write(b"\r").unwrap();
+
+        // A frame arrives before the acknowledgment of the transmitted one
+        assert_eq!(device.read_exact(10).unwrap().as_slice(), b"t1232ABCD\r");
+        device.write(b"t0101FF\rz\r").unwrap();
+
+        // Already open
+        assert_eq!(device.read_exact(2).unwrap().as_slice(), b"O\r");
+        device.write(&[0x07]).unwrap();
+    });
+
+    let mut adapter = Adapter::new(port);
+
+    match adapter.set_bitrate(Rate500K).and_then(|_| adapter.open()) {
+        Err(e) => panic!("Couldn't open the channel ({})", e),
+        Ok(_) => {},
+    }
+
+    match adapter.send(&Frame::new(0x123, &[0xAB, 0xCD])) {
+        Err(e) => panic!("Couldn't send a frame ({})", e),
+        Ok(_) => {},
+    }
+
+    assert!(adapter.open().is_err());
+    assert_eq!(adapter.receive().unwrap(), Frame::new(0x010, &[0xFF]));
+
+    assert!(adapter.send(&Frame::new(0x800, &[])).is_err());
+}
+
+#[test]
+fn frames() {
+    assert_eq!(Frame::new_extended(0x1ABCDE, &[0x01]).encode().unwrap().as_slice(),
+               b"T001ABCDE101");
+    let remote = Frame { remote: true, ..Frame::new(0x7FF, &[0, 0, 0]) };
+    assert_eq!(remote.encode().unwrap().as_slice(), b"r7FF3");
+    assert_eq!(Frame::new(0x100, &[0, ..9]).encode(), None);
+
+    assert_eq!(Frame::parse(b"t7FF3010203"), Some(Frame::new(0x7FF, &[1, 2, 3])));
+    assert_eq!(Frame::parse(b"T1FFFFFFF0"), Some(Frame::new_extended(0x1FFFFFFF, &[])));
+    assert_eq!(Frame::parse(b"r7FF3"), Some(remote));
+
+    // Trailing timestamp
+    assert_eq!(Frame::parse(b"t00111F3A01"), Some(Frame::new(0x001, &[0x1F])));
+
+    assert_eq!(Frame::parse(b"t8001FF"), None);
+    assert_eq!(Frame::parse(b"t10019"), None);
+    assert_eq!(Frame::parse(b"t1002FF"), None);
+}
@@ -0,0 +1,18 @@
+use std::io::FileNotFound;
+
+use stable;
+use testing;
+
+#[test]
+fn unknown_devices() {
+    let pty = match testing::pty() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pty) => pty,
+    };
+
+    // Ptys aren't adapters
+    assert_eq!(stable::stable_name(&pty.path).unwrap(), None);
+
+    assert_eq!(stable::find("usb-No_Such_Adapter-if00-port0").map_err(|e| e.kind),
+               Err(FileNotFound));
+}
@@ -0,0 +1,38 @@
+use std::io::ChanWriter;
+
+use tee::Tee;
+use SerialPort;
+
+#[test]
+fn fan_out() {
+    let (mut master, slave) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let mut tee = Tee::new().chunk_size(4);
+    let (first, second) = (tee.subscribe(), tee.subscribe());
+    let (sender, log) = channel();
+    tee.add_writer(box ChanWriter::new(sender));
+
+    // A consumer that goes away doesn't stop the others
+    drop(tee.subscribe());
+
+    let stopped = tee.spawn(slave);
+    master.write_str("Hello, world!").unwrap();
+
+    for consumer in [first, second, log].iter() {
+        let mut received = Vec::new();
+        while received.len() < 13 {
+            let chunk = consumer.recv();
+            assert!(chunk.len() <= 4);
+            received.push_all(chunk.as_slice());
+        }
+
+        assert_eq!(received.as_slice(), b"Hello, world!");
+    }
+
+    // The reads stop once the master hangs up
+    drop(master);
+    stopped.recv();
+}
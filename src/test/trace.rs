@@ -0,0 +1,31 @@
+use std::io::MemWriter;
+use std::str;
+
+use trace::{DumpTracer, TracedPort};
+use SerialPort;
+
+#[test]
+fn dump() {
+    let (port, mut device) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let mut port = TracedPort::new(port, DumpTracer::new(MemWriter::new()));
+
+    port.write(b"AT\r").unwrap();
+    assert_eq!(device.read_exact(3).unwrap().as_slice(), b"AT\r");
+
+    device.write(b"0123456789ABCDEF\r\n").unwrap();
+    assert_eq!(port.read_exact(18).unwrap().as_slice(), b"0123456789ABCDEF\r\n");
+
+    let (_, tracer) = port.into_inner();
+    let dump = tracer.into_inner().unwrap();
+    let lines: Vec<&str> = str::from_utf8(dump.as_slice()).unwrap().lines().collect();
+
+    assert!(lines[0].contains(" TX 41 54 0D "));
+    assert!(lines[0].ends_with("  |AT.|"));
+    assert!(lines[1].contains(" RX 30 31 32 33 34 35 36 37 38 39 41 42 43 44 45 46  "));
+    assert!(lines[1].ends_with("  |0123456789ABCDEF|"));
+    assert!(lines[2].ends_with("  |..|"));
+}
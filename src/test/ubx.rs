@@ -0,0 +1,67 @@
+use framing::BadChecksum;
+use ubx::{AckMessage, Message, NavPvt, NavPvtMessage, Packet, PacketDecoder};
+
+use super::total;
+
+const CFG_RATE: &'static [u8] = &[
+    0xB5, 0x62, 0x06, 0x08, 0x06, 0x00, 0xE8, 0x03, 0x01, 0x00, 0x01, 0x00, 0x01, 0x39,
+];
+
+#[quickcheck]
+fn arbitrary_input(data: Vec<u8>, split: uint) -> bool {
+    total(PacketDecoder::new(), PacketDecoder::new(), data.as_slice(), split,
+          |decoder, data| decoder.feed(data))
+}
+
+#[test]
+fn decode() {
+    let ack = [0xB5, 0x62, 0x05, 0x01, 0x02, 0x00, 0x06, 0x08, 0x16, 0x3F];
+    let mut corrupted = CFG_RATE.to_vec();
+    corrupted.as_mut_slice()[7] ^= 0x01;
+
+    let mut stream = b"$GPGGA,,*56\r\n".to_vec();
+    stream.push_all(corrupted.as_slice());
+    stream.push_all(&ack);
+    stream.push_all(CFG_RATE);
+
+    let mut decoder = PacketDecoder::new();
+    let mut packets = Vec::new();
+    for chunk in stream.as_slice().chunks(3) {
+        packets.push_all(decoder.feed(chunk).as_slice());
+    }
+
+    assert_eq!(packets, vec![
+        Err(BadChecksum),
+        Ok(Packet::new(0x05, 0x01, vec![0x06, 0x08])),
+        Ok(Packet::cfg_rate(1000, 1, 1)),
+    ]);
+    assert_eq!(Message::parse(packets[1].as_ref().unwrap()), AckMessage(0x06, 0x08));
+}
+
+#[test]
+fn encode() {
+    assert_eq!(Packet::cfg_rate(1000, 1, 1).encode().as_slice(), CFG_RATE);
+}
+
+#[test]
+fn nav_pvt() {
+    let mut payload = Vec::from_elem(92, 0u8);
+    {
+        let payload = payload.as_mut_slice();
+        payload[4] = 0xE8;
+        payload[5] = 0x07;
+        payload[20] = 3;
+        payload[23] = 12;
+        // Latitude: -1 (1e-7 degrees)
+        for i in range(28u, 32) {
+            payload[i] = 0xFF;
+        }
+    }
+
+    match Message::parse(&Packet::new(0x01, 0x07, payload)) {
+        NavPvtMessage(NavPvt { year, fix_type, satellites, latitude, .. }) => {
+            assert_eq!((year, fix_type, satellites, latitude), (2024, 3, 12, -1));
+        },
+        got => panic!("Expected a NAV-PVT message, got {}", got),
+    }
+}
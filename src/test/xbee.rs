@@ -0,0 +1,120 @@
+use framing::{BadChecksum, Decoder, Encoder, Truncated};
+use xbee::{ApiCodec, AtCommandFrame, AtResponseFrame, BROADCAST, Frame, ModemStatusFrame, Radio};
+use xbee::{ReceivePacket, ReceivePacketFrame, TransmitRequestFrame, TransmitStatus};
+use xbee::TransmitStatusFrame;
+use SerialPort;
+
+use super::total;
+
+#[quickcheck]
+fn arbitrary_input(data: Vec<u8>, split: uint) -> bool {
+    total(ApiCodec::new(), ApiCodec::new(), data.as_slice(), split, |codec, data| codec.feed(data))
+}
+
+#[test]
+fn codec() {
+    let mut encoded = Vec::new();
+    ApiCodec::new().encode(&[0x08, 0x01, b'N', b'I'], &mut encoded).unwrap();
+    assert_eq!(encoded, vec![0x7E, 0x00, 0x04, 0x08, 0x01, 0x4E, 0x49, 0x5F]);
+
+    let mut escaped = Vec::new();
+    ApiCodec::escaped().encode(&[0x8A, 0x11], &mut escaped).unwrap();
+    assert_eq!(escaped, vec![0x7E, 0x00, 0x02, 0x8A, 0x7D, 0x31, 0x64]);
+
+    // A frame cut short, a corrupted frame, then a valid one split across feeds
+    let mut codec = ApiCodec::escaped();
+    let mut frames = codec.feed(&[0x7E, 0x00, 0x04, 0x08, 0x7E, 0x00, 0x02, 0x8A, 0x12, 0x64]);
+    frames.push_all(codec.feed(&[0x7E, 0x00, 0x02, 0x8A, 0x7D]).as_slice());
+    frames.push_all(codec.feed(&[0x31, 0x64]).as_slice());
+
+    assert_eq!(frames, vec![Err(Truncated), Err(BadChecksum), Ok(vec![0x8A, 0x11])]);
+}
+
+#[test]
+fn frames() {
+    let packet = ReceivePacketFrame(ReceivePacket {
+        source: 0x0013A20040522BAA,
+        network_address: 0x7D84,
+        options: 0x01,
+        data: b"Hi".to_vec(),
+    });
+    let bytes = [
+        0x90, 0x00, 0x13, 0xA2, 0x00, 0x40, 0x52, 0x2B, 0xAA, 0x7D, 0x84, 0x01, b'H', b'i',
+    ];
+
+    assert_eq!(packet.encode().as_slice(), bytes.as_slice());
+    assert_eq!(Frame::parse(&bytes), Some(packet));
+    assert_eq!(Frame::parse(bytes.slice_to(11)), None);
+}
+
+#[test]
+fn radio() {
+    let (mut device, port) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    spawn(proc() {
+        assert_eq!(read_frame(&mut device), AtCommandFrame(1, [b'N', b'I'], vec![]));
+        write_frame(&mut device, ModemStatusFrame(0x02));
+        write_frame(&mut device, AtResponseFrame(1, [b'N', b'I'], 0, b"ROUTER".to_vec()));
+
+        assert_eq!(read_frame(&mut device), AtCommandFrame(2, [b'I', b'D'], vec![0x12, 0x34]));
+        write_frame(&mut device, AtResponseFrame(2, [b'I', b'D'], 1, vec![]));
+
+        match read_frame(&mut device) {
+            TransmitRequestFrame(request) => {
+                assert_eq!((request.frame_id, request.destination), (3, BROADCAST));
+                assert_eq!(request.data.as_slice(), b"Hello");
+            },
+            frame => panic!("radio: Expected a transmit request, got {}", frame),
+        }
+        write_frame(&mut device, TransmitStatusFrame(TransmitStatus {
+            frame_id: 3,
+            network_address: 0xFFFD,
+            retries: 0,
+            delivery_status: 0,
+            discovery_status: 0,
+        }));
+    });
+
+    let mut radio = Radio::new(port, ApiCodec::new());
+
+    match radio.at_command("NI", &[]) {
+        Err(e) => panic!("Couldn't read the node identifier ({})", e),
+        Ok(value) => assert_eq!(value.as_slice(), b"ROUTER"),
+    }
+
+    assert!(radio.at_command("ID", &[0x12, 0x34]).is_err());
+
+    match radio.transmit(BROADCAST, b"Hello") {
+        Err(e) => panic!("Couldn't transmit ({})", e),
+        Ok(status) => assert!(status.is_delivered()),
+    }
+
+    // Received while waiting for the first response
+    assert_eq!(radio.receive().unwrap(), ModemStatusFrame(0x02));
+    assert!(radio.at_command("NIX", &[]).is_err());
+}
+
+/// Reads a frame sent to the radio
+fn read_frame(device: &mut SerialPort) -> Frame {
+    let mut encoded = device.read_exact(3).unwrap();
+    let len = (encoded[1] as uint) << 8 | encoded[2] as uint;
+    encoded.push_all(device.read_exact(len + 1).unwrap().as_slice());
+
+    let frames = ApiCodec::new().feed(encoded.as_slice());
+    assert_eq!(frames.len(), 1);
+
+    match frames[0] {
+        Err(ref e) => panic!("radio: Malformed frame ({})", e),
+        Ok(ref data) => Frame::parse(data.as_slice()).unwrap(),
+    }
+}
+
+/// Sends a frame from the radio
+fn write_frame(device: &mut SerialPort, frame: Frame) {
+    let mut encoded = Vec::new();
+    ApiCodec::new().encode(frame.encode().as_slice(), &mut encoded).unwrap();
+    device.write(encoded.as_slice()).unwrap();
+}
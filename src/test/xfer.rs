@@ -0,0 +1,145 @@
+use std::io::{MemReader, MemWriter};
+use std::time::Duration;
+
+use xfer::BatchFile;
+use xfer::kermit::Kermit;
+use xfer::xmodem::{Block128, Block1K, BlockCheck, BlockSize, CrcCheck, SumCheck, Xmodem};
+use xfer::ymodem::Ymodem;
+use xfer::zmodem::Zmodem;
+use SerialPort;
+
+fn batch() -> Vec<BatchFile> {
+    vec![
+        BatchFile {
+            name: "firmware.bin".to_string(),
+            data: range(0, 3000u).map(|i| (i * 7) as u8).collect(),
+        },
+        BatchFile {
+            name: "empty.txt".to_string(),
+            data: vec![],
+        },
+    ]
+}
+
+fn xmodem(check: BlockCheck, block_size: BlockSize, len: uint) {
+    let (mut sender, mut receiver) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let data: Vec<u8> = range(0, len).map(|i| (i * 7) as u8).collect();
+    let xmodem = Xmodem::new().check(check).block_size(block_size).timeout(Duration::seconds(1));
+
+    let (to_send, xmodem_) = (data.clone(), xmodem.clone());
+    spawn(proc() {
+        match xmodem_.send(&mut sender, &mut MemReader::new(to_send)) {
+            Err(e) => panic!("sender: Couldn't send ({})", e),
+            Ok(sent) => assert_eq!(sent, len),
+        }
+    });
+
+    let mut out = MemWriter::new();
+    match xmodem.receive(&mut receiver, &mut out) {
+        Err(e) => panic!("receiver: Couldn't receive ({})", e),
+        Ok(received) => assert_eq!(received, out.get_ref().len()),
+    }
+
+    let received = out.get_ref();
+    assert_eq!(received.len() % 128, 0);
+    assert_eq!(received.slice_to(len), data.as_slice());
+    assert!(received.slice_from(len).iter().all(|&byte| byte == 0x1A));
+}
+
+fn kermit(sender_kermit: Kermit, receiver_kermit: Kermit) {
+    let (mut sender, mut receiver) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    spawn(proc() {
+        match sender_kermit.send(&mut sender, batch().as_slice()) {
+            Err(e) => panic!("sender: Couldn't send ({})", e),
+            Ok(_) => {},
+        }
+    });
+
+    match receiver_kermit.receive(&mut receiver) {
+        Err(e) => panic!("receiver: Couldn't receive ({})", e),
+        Ok(files) => assert_eq!(files, batch()),
+    }
+}
+
+#[test]
+fn kermit_7bit() {
+    let kermit_ = Kermit::new().timeout(Duration::seconds(1));
+
+    kermit(kermit_.clone().quote_8bit(true), kermit_)
+}
+
+#[test]
+fn kermit_8bit() {
+    let kermit_ = Kermit::new().timeout(Duration::seconds(1));
+
+    kermit(kermit_.clone(), kermit_)
+}
+
+#[test]
+fn xmodem_1k() {
+    xmodem(CrcCheck, Block1K, 2100)
+}
+
+#[test]
+fn xmodem_crc() {
+    xmodem(CrcCheck, Block128, 300)
+}
+
+#[test]
+fn xmodem_sum() {
+    xmodem(SumCheck, Block128, 256)
+}
+
+#[test]
+fn ymodem() {
+    let (mut sender, mut receiver) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let ymodem = Ymodem::new().timeout(Duration::seconds(1));
+
+    let ymodem_ = ymodem.clone();
+    spawn(proc() {
+        match ymodem_.send(&mut sender, batch().as_slice()) {
+            Err(e) => panic!("sender: Couldn't send ({})", e),
+            Ok(_) => {},
+        }
+    });
+
+    match ymodem.receive(&mut receiver) {
+        Err(e) => panic!("receiver: Couldn't receive ({})", e),
+        Ok(files) => assert_eq!(files, batch()),
+    }
+}
+
+#[test]
+fn zmodem() {
+    let (mut sender, mut receiver) = match SerialPort::pty_pair() {
+        Err(e) => panic!("Couldn't open a pty pair ({})", e),
+        Ok(pair) => pair,
+    };
+
+    let zmodem = Zmodem::new().timeout(Duration::seconds(1));
+
+    let zmodem_ = zmodem.clone();
+    spawn(proc() {
+        match zmodem_.send(&mut sender, batch().as_slice()) {
+            Err(e) => panic!("sender: Couldn't send ({})", e),
+            Ok(_) => {},
+        }
+    });
+
+    match zmodem.receive(&mut receiver) {
+        Err(e) => panic!("receiver: Couldn't receive ({})", e),
+        Ok(files) => assert_eq!(files, batch()),
+    }
+}
@@ -0,0 +1,120 @@
+//! Virtual devices for the tests of the crates that use serial ports, enabled by the `testing`
+//! feature
+//!
+//! `pty` needs nothing but the C library; `socat` gives two symmetric device paths, for code that
+//! opens both ends itself, and needs the `socat` program.
+//!
+//! ```ignore
+//! let pty = try!(testing::pty());
+//! let mut device = try!(SerialPort::open(&pty.path, ReadWrite));
+//! try!(pty.master.write(b"AT\r"));
+//! ```
+
+use std::c_str::CString;
+use std::io::{BufferedReader, Command, EndOfFile, IoError, IoResult, OtherIoError};
+use std::io::process::{Process, PipeStream};
+
+use SerialPort;
+use pty;
+
+/// A pseudo-terminal whose slave side is left for the code under test to open
+pub struct Pty {
+    /// The master side, which plays the part of the remote device
+    pub master: SerialPort,
+    /// Path to the slave side, which only exists while `master` is open
+    pub path: Path,
+}
+
+/// Opens a pseudo-terminal with `openpty`, see `Pty`
+pub fn pty() -> IoResult<Pty> {
+    let (master, slave) = try!(SerialPort::pty_pair());
+
+    let name = unsafe { pty::ttyname(slave.as_raw_fd()) };
+    if name.is_null() {
+        return Err(IoError::last_error())
+    }
+    let name = unsafe { CString::new(name, false) };
+
+    Ok(Pty {
+        master: master,
+        path: Path::new(name.as_bytes_no_nul()),
+    })
+}
+
+/// Two linked pseudo-terminals created by a `socat` process, which is killed when this is
+/// dropped
+pub struct Socat {
+    paths: (Path, Path),
+    process: Process,
+    // The notices of socat go there until it exits
+    _stderr: BufferedReader<PipeStream>,
+}
+
+impl Socat {
+    /// Returns the paths of the two ends, anything written to one end can be read from the other
+    pub fn paths(&self) -> (&Path, &Path) {
+        (&self.paths.0, &self.paths.1)
+    }
+}
+
+impl Drop for Socat {
+    fn drop(&mut self) {
+        let _ = self.process.signal_kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Spawns `socat` to link two raw pseudo-terminals, see `Socat`
+///
+/// Returns once the link is up. Fails with `FileNotFound` if `socat` isn't installed.
+pub fn socat() -> IoResult<Socat> {
+    let mut process = try!(Command::new("socat")
+        .args(&["-d", "-d", "pty,raw,echo=0", "pty,raw,echo=0"])
+        .spawn());
+    let mut stderr = BufferedReader::new(process.stderr.take().unwrap());
+
+    // With `-d -d`, socat reports the devices, then the start of the transfer loop
+    let mut paths = Vec::new();
+    loop {
+        let line = match stderr.read_line() {
+            Err(ref e) if e.kind == EndOfFile => {
+                let _ = process.wait();
+
+                return Err(IoError {
+                    kind: OtherIoError,
+                    desc: "socat exited before linking the devices",
+                    detail: None,
+                })
+            },
+            Err(e) => {
+                let _ = process.signal_kill();
+                return Err(e)
+            },
+            Ok(line) => line,
+        };
+
+        match line.as_slice().find_str("PTY is ") {
+            Some(start) => paths.push(Path::new(line.as_slice().slice_from(start + 7).trim())),
+            None if line.as_slice().contains("starting data transfer loop") => break,
+            None => {},
+        }
+    }
+
+    if paths.len() != 2 {
+        let _ = process.signal_kill();
+
+        return Err(IoError {
+            kind: OtherIoError,
+            desc: "Unexpected output from socat",
+            detail: Some(format!("got {} devices", paths.len())),
+        })
+    }
+    let second = paths.pop().unwrap();
+    let first = paths.pop().unwrap();
+
+    Ok(Socat {
+        paths: (first, second),
+        process: process,
+        _stderr: stderr,
+    })
+}
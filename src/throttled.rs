@@ -0,0 +1,124 @@
+use std::cmp;
+use std::io::IoResult;
+use std::io::timer;
+use std::time::Duration;
+use time;
+
+use {SerialIo, Settings};
+
+/// A serial transport whose writes are limited to a sustained byte rate
+///
+/// The limit is a token bucket: bursts of up to `burst` bytes go out at once, then the writes
+/// slow down to `rate` bytes per second. This keeps devices with a small receive buffer and no
+/// flow control from dropping data. Reads are passed through untouched.
+pub struct ThrottledWriter<S> {
+    inner: S,
+    /// Bytes per second
+    rate: uint,
+    burst: uint,
+    tokens: f64,
+    /// Last refill of the bucket, in `precise_time_ns` units
+    last: u64,
+}
+
+impl<S> ThrottledWriter<S> {
+    /// Limits the writes to `inner` to `rate` bytes per second, with bursts of 10 ms worth of
+    /// data
+    pub fn new(inner: S, rate: uint) -> ThrottledWriter<S> {
+        ThrottledWriter::with_burst(inner, rate, rate / 100)
+    }
+
+    /// Limits the writes to `inner` to `rate` bytes per second, with bursts of up to `burst`
+    /// bytes
+    pub fn with_burst(inner: S, rate: uint, burst: uint) -> ThrottledWriter<S> {
+        let rate = cmp::max(rate, 1);
+        let burst = cmp::max(burst, 1);
+
+        ThrottledWriter {
+            inner: inner,
+            rate: rate,
+            burst: burst,
+            tokens: burst as f64,
+            last: time::precise_time_ns(),
+        }
+    }
+
+    /// Returns a reference to the underlying transport
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying transport
+    ///
+    /// Writing directly to the transport bypasses the limit.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying transport
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Adds the tokens earned since the last refill
+    fn refill(&mut self) {
+        let now = time::precise_time_ns();
+        let earned = (now - self.last) as f64 * self.rate as f64 / 1e9;
+
+        self.tokens = (self.tokens + earned).min(self.burst as f64);
+        self.last = now;
+    }
+}
+
+impl<S: Reader> Reader for ThrottledWriter<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        self.inner.read(buf)
+    }
+}
+
+impl<S: SerialIo> SerialIo for ThrottledWriter<S> {
+    fn settings(&self) -> IoResult<Settings> {
+        self.inner.settings()
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.inner.configure(settings)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_timeout(timeout)
+    }
+}
+
+impl<S: Writer> Writer for ThrottledWriter<S> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let mut buf = buf;
+
+        while !buf.is_empty() {
+            self.refill();
+
+            let wanted = cmp::min(buf.len(), self.burst);
+            if self.tokens < wanted as f64 {
+                let missing = wanted as f64 - self.tokens;
+                let nanos = (missing * 1e9 / self.rate as f64).ceil() as i64;
+
+                timer::sleep(Duration::nanoseconds(nanos));
+                continue
+            }
+
+            try!(self.inner.write(buf.slice_to(wanted)));
+            self.tokens -= wanted as f64;
+            buf = buf.slice_from(wanted);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,209 @@
+//! Tracing of the traffic of a port
+//!
+//! `TracedPort` wraps a transport and hands every chunk of data that goes through it to a
+//! `Tracer`, so the traffic of a misbehaving device can be inspected without touching the code
+//! that talks to it.
+
+use std::io::IoResult;
+use std::time::Duration;
+
+use {SerialIo, Settings, Timestamp};
+
+/// Bytes per line of the hex dumps
+const BYTES_PER_LINE: uint = 16;
+
+const HEX_DIGITS: &'static [u8] = b"0123456789ABCDEF";
+
+/// Direction of the traced data
+#[deriving(Clone, PartialEq, Show)]
+pub enum Direction {
+    /// Read from the transport
+    Received,
+    /// Written to the transport
+    Sent,
+}
+
+/// Receives the traffic of a `TracedPort`
+pub trait Tracer {
+    /// Called with every chunk of data read from or written to the port
+    ///
+    /// Tracing is best effort: a tracer can't fail the I/O it observes, so it has to deal with
+    /// its own errors.
+    fn trace(&mut self, direction: Direction, timestamp: &Timestamp, data: &[u8]);
+}
+
+/// Writes the traffic as a hex + ASCII dump, one line per 16 bytes
+///
+/// ```text
+/// 1418220000.123456 TX 41 54 0D                                         |AT.|
+/// ```
+///
+/// The time is the wall clock time, in seconds since the Unix epoch.
+pub struct DumpTracer<W> {
+    inner: W,
+}
+
+impl<W: Writer> DumpTracer<W> {
+    /// Dumps the traffic to `inner`
+    pub fn new(inner: W) -> DumpTracer<W> {
+        DumpTracer {
+            inner: inner,
+        }
+    }
+
+    /// Returns a reference to the writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Unwraps the writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Writer> Tracer for DumpTracer<W> {
+    fn trace(&mut self, direction: Direction, timestamp: &Timestamp, data: &[u8]) {
+        for line in dump(direction, timestamp, data).iter() {
+            let _ = self.inner.write_line(line.as_slice());
+        }
+    }
+}
+
+/// Logs the traffic through the `log` macros, at the debug level
+///
+/// The lines are the same as the ones of `DumpTracer`, prefixed with the `name` of the port.
+pub struct LogTracer {
+    name: String,
+}
+
+impl LogTracer {
+    pub fn new(name: &str) -> LogTracer {
+        LogTracer {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl Tracer for LogTracer {
+    fn trace(&mut self, direction: Direction, timestamp: &Timestamp, data: &[u8]) {
+        for line in dump(direction, timestamp, data).iter() {
+            debug!("{}: {}", self.name, line);
+        }
+    }
+}
+
+/// A transport whose traffic is passed to a `Tracer`
+///
+/// Only successful reads and writes are traced, errors (and time outs) are returned as usual.
+pub struct TracedPort<S, T> {
+    inner: S,
+    tracer: T,
+}
+
+impl<S, T: Tracer> TracedPort<S, T> {
+    /// Traces the traffic of `inner` with `tracer`
+    pub fn new(inner: S, tracer: T) -> TracedPort<S, T> {
+        TracedPort {
+            inner: inner,
+            tracer: tracer,
+        }
+    }
+
+    /// Returns a reference to the underlying transport
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying transport, its traffic isn't traced
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Returns a reference to the tracer
+    pub fn tracer(&self) -> &T {
+        &self.tracer
+    }
+
+    /// Returns a mutable reference to the tracer
+    pub fn tracer_mut(&mut self) -> &mut T {
+        &mut self.tracer
+    }
+
+    /// Unwraps the underlying transport and the tracer
+    pub fn into_inner(self) -> (S, T) {
+        (self.inner, self.tracer)
+    }
+}
+
+impl<S: Reader, T: Tracer> Reader for TracedPort<S, T> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let n = try!(self.inner.read(buf));
+
+        if n > 0 {
+            self.tracer.trace(Received, &Timestamp::now(), buf.slice_to(n));
+        }
+
+        Ok(n)
+    }
+}
+
+impl<S: SerialIo, T: Tracer> SerialIo for TracedPort<S, T> {
+    fn settings(&self) -> IoResult<Settings> {
+        self.inner.settings()
+    }
+
+    fn configure(&mut self, settings: &Settings) -> IoResult<()> {
+        self.inner.configure(settings)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_timeout(timeout)
+    }
+}
+
+impl<S: Writer, T: Tracer> Writer for TracedPort<S, T> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let timestamp = Timestamp::now();
+        try!(self.inner.write(buf));
+
+        if !buf.is_empty() {
+            self.tracer.trace(Sent, &timestamp, buf);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+/// Formats `data` as hex + ASCII dump lines
+fn dump(direction: Direction, timestamp: &Timestamp, data: &[u8]) -> Vec<String> {
+    let direction = match direction {
+        Received => "RX",
+        Sent => "TX",
+    };
+    let prefix = format!("{}.{:06} {}", timestamp.time.sec, timestamp.time.nsec / 1000, direction);
+
+    data.chunks(BYTES_PER_LINE).map(|chunk| {
+        let mut hex = String::with_capacity(3 * BYTES_PER_LINE);
+        for &byte in chunk.iter() {
+            hex.push(' ');
+            hex.push(HEX_DIGITS[(byte >> 4) as uint] as char);
+            hex.push(HEX_DIGITS[(byte & 0x0F) as uint] as char);
+        }
+        hex.grow(3 * (BYTES_PER_LINE - chunk.len()), ' ');
+
+        let ascii: String = chunk.iter().map(|&byte| {
+            if byte >= b' ' && byte <= b'~' { byte as char } else { '.' }
+        }).collect();
+
+        format!("{}{}  |{}|", prefix, hex, ascii)
+    }).collect()
+}
@@ -0,0 +1,361 @@
+//! u-blox UBX binary protocol
+//!
+//! Packets are made of the `0xB5 0x62` sync characters, a class and an id, a little endian
+//! payload length, the payload, and a Fletcher checksum over everything but the sync characters.
+
+use std::collections::RingBuf;
+use std::io::{InvalidInput, IoError, IoResult};
+
+use checksum::{Checksum, Fletcher8};
+use framing::{BadChecksum, FrameError, Oversized};
+
+const SYNC: [u8, ..2] = [0xB5, 0x62];
+
+/// Largest payload accepted by the decoder
+pub const MAX_PAYLOAD_LEN: uint = 8192;
+
+pub const CLASS_NAV: u8 = 0x01;
+pub const CLASS_ACK: u8 = 0x05;
+pub const CLASS_CFG: u8 = 0x06;
+
+pub const ID_ACK_NAK: u8 = 0x00;
+pub const ID_ACK_ACK: u8 = 0x01;
+pub const ID_CFG_PRT: u8 = 0x00;
+pub const ID_CFG_MSG: u8 = 0x01;
+pub const ID_CFG_RATE: u8 = 0x08;
+pub const ID_NAV_PVT: u8 = 0x07;
+
+/// A UBX packet
+#[deriving(Clone, PartialEq, Show)]
+pub struct Packet {
+    pub class: u8,
+    pub id: u8,
+    pub payload: Vec<u8>,
+}
+
+impl Packet {
+    /// Creates a packet
+    pub fn new(class: u8, id: u8, payload: Vec<u8>) -> Packet {
+        Packet { class: class, id: id, payload: payload }
+    }
+
+    /// Requests the current value of a message (or configuration) with an empty payload
+    pub fn poll(class: u8, id: u8) -> Packet {
+        Packet::new(class, id, Vec::new())
+    }
+
+    /// CFG-MSG, sets the rate at which the message `class`/`id` is sent on the current port
+    ///
+    /// The rate is relative to the navigation rate, 0 disables the message
+    pub fn cfg_msg(class: u8, id: u8, rate: u8) -> Packet {
+        Packet::new(CLASS_CFG, ID_CFG_MSG, vec![class, id, rate])
+    }
+
+    /// CFG-PRT, configures the UART `port_id` to 8N1 at `baud_rate` with the given protocol masks
+    pub fn cfg_prt_uart(port_id: u8, baud_rate: u32, in_protocols: u16, out_protocols: u16)
+                        -> Packet {
+        // 8 data bits, no parity, 1 stop bit
+        const MODE_8N1: u32 = 0x0000_08C0;
+
+        let mut payload = vec![port_id, 0, 0, 0];
+        push_le(&mut payload, MODE_8N1 as u64, 4);
+        push_le(&mut payload, baud_rate as u64, 4);
+        push_le(&mut payload, in_protocols as u64, 2);
+        push_le(&mut payload, out_protocols as u64, 2);
+        payload.push_all(&[0, 0, 0, 0]);
+
+        Packet::new(CLASS_CFG, ID_CFG_PRT, payload)
+    }
+
+    /// CFG-RATE, sets the measurement period (in milliseconds), the number of measurements per
+    /// navigation solution, and the time reference (0: UTC, 1: GPS time)
+    pub fn cfg_rate(measurement_ms: u16, navigation_rate: u16, time_ref: u16) -> Packet {
+        let mut payload = Vec::new();
+        push_le(&mut payload, measurement_ms as u64, 2);
+        push_le(&mut payload, navigation_rate as u64, 2);
+        push_le(&mut payload, time_ref as u64, 2);
+
+        Packet::new(CLASS_CFG, ID_CFG_RATE, payload)
+    }
+
+    /// Serializes the packet, including the sync characters and the checksum
+    pub fn encode(&self) -> Vec<u8> {
+        let mut packet = SYNC.to_vec();
+        packet.push(self.class);
+        packet.push(self.id);
+        push_le(&mut packet, self.payload.len() as u64, 2);
+        packet.push_all(self.payload.as_slice());
+
+        let mut checksum = Fletcher8::new();
+        checksum.update(packet.slice_from(2));
+        packet.push_all(checksum.bytes().as_slice());
+
+        packet
+    }
+}
+
+/// Navigation position velocity time solution (NAV-PVT)
+#[deriving(Clone, PartialEq, Show)]
+pub struct NavPvt {
+    /// GPS time of week of the navigation epoch, in milliseconds
+    pub itow: u32,
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Validity flags of the date and time
+    pub valid: u8,
+    /// Fraction of second in nanoseconds, may be negative
+    pub nano: i32,
+    /// 0: no fix, 2: 2D fix, 3: 3D fix, ...
+    pub fix_type: u8,
+    pub flags: u8,
+    pub satellites: u8,
+    /// Longitude, in 1e-7 degrees
+    pub longitude: i32,
+    /// Latitude, in 1e-7 degrees
+    pub latitude: i32,
+    /// Height above the ellipsoid, in millimeters
+    pub height: i32,
+    /// Height above the mean sea level, in millimeters
+    pub height_msl: i32,
+    /// Horizontal accuracy estimate, in millimeters
+    pub horizontal_accuracy: u32,
+    /// Vertical accuracy estimate, in millimeters
+    pub vertical_accuracy: u32,
+    /// North, east and down velocity, in millimeters per second
+    pub velocity: (i32, i32, i32),
+    /// Ground speed, in millimeters per second
+    pub ground_speed: i32,
+    /// Heading of motion, in 1e-5 degrees
+    pub heading: i32,
+    /// Position dilution of precision, scaled by 100
+    pub pdop: u16,
+}
+
+impl NavPvt {
+    /// Parses the payload of a NAV-PVT packet, returns `None` if it's too short
+    pub fn parse(payload: &[u8]) -> Option<NavPvt> {
+        if payload.len() < 92 {
+            return None
+        }
+
+        let u = |offset: uint, len: uint| le(payload.slice(offset, offset + len));
+        let i = |offset: uint| le(payload.slice(offset, offset + 4)) as u32 as i32;
+
+        Some(NavPvt {
+            itow: u(0, 4) as u32,
+            year: u(4, 2) as u16,
+            month: payload[6],
+            day: payload[7],
+            hour: payload[8],
+            minute: payload[9],
+            second: payload[10],
+            valid: payload[11],
+            nano: i(16),
+            fix_type: payload[20],
+            flags: payload[21],
+            satellites: payload[23],
+            longitude: i(24),
+            latitude: i(28),
+            height: i(32),
+            height_msl: i(36),
+            horizontal_accuracy: u(40, 4) as u32,
+            vertical_accuracy: u(44, 4) as u32,
+            velocity: (i(48), i(52), i(56)),
+            ground_speed: i(60),
+            heading: i(64),
+            pdop: u(76, 2) as u16,
+        })
+    }
+}
+
+/// The messages understood by this module
+#[deriving(Clone, PartialEq, Show)]
+pub enum Message {
+    /// The configuration message with the given class and id was accepted
+    AckMessage(u8, u8),
+    /// The configuration message with the given class and id was rejected
+    NakMessage(u8, u8),
+    NavPvtMessage(NavPvt),
+    /// Any other packet
+    OtherMessage(Packet),
+}
+
+impl Message {
+    /// Interprets the content of `packet`
+    pub fn parse(packet: &Packet) -> Message {
+        let payload = packet.payload.as_slice();
+
+        match (packet.class, packet.id) {
+            (CLASS_ACK, ID_ACK_ACK) if payload.len() == 2 => AckMessage(payload[0], payload[1]),
+            (CLASS_ACK, ID_ACK_NAK) if payload.len() == 2 => NakMessage(payload[0], payload[1]),
+            (CLASS_NAV, ID_NAV_PVT) => match NavPvt::parse(payload) {
+                None => OtherMessage(packet.clone()),
+                Some(pvt) => NavPvtMessage(pvt),
+            },
+            _ => OtherMessage(packet.clone()),
+        }
+    }
+}
+
+/// Incremental packet decoder
+///
+/// Bytes that precede the sync characters are skipped, like the NMEA output that receivers
+/// interleave with UBX packets. After a bad checksum or an oversized length the decoder
+/// resynchronizes on the next sync characters.
+pub struct PacketDecoder {
+    buf: Vec<u8>,
+}
+
+impl PacketDecoder {
+    /// Creates a decoder with an empty state
+    pub fn new() -> PacketDecoder {
+        PacketDecoder { buf: Vec::new() }
+    }
+
+    /// Feeds `data` to the decoder, returns the packets it completed
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Result<Packet, FrameError>> {
+        let mut packets = Vec::new();
+        self.buf.push_all(data);
+
+        loop {
+            // Skip to the next sync sequence, keeping a trailing first sync char
+            let sync = SYNC.as_slice();
+            let start = self.buf.as_slice().windows(2).position(|window| window == sync);
+            let skip = match start {
+                Some(start) => start,
+                None if self.buf.last() == Some(&SYNC[0]) => self.buf.len() - 1,
+                None => self.buf.len(),
+            };
+            self.buf = self.buf.slice_from(skip).to_vec();
+
+            if self.buf.len() < 6 {
+                break
+            }
+
+            let len = le(self.buf.slice(4, 6)) as uint;
+            if len > MAX_PAYLOAD_LEN {
+                packets.push(Err(Oversized(len)));
+                self.buf = self.buf.slice_from(2).to_vec();
+                continue
+            }
+
+            if self.buf.len() < 8 + len {
+                break
+            }
+
+            let mut checksum = Fletcher8::new();
+            checksum.update(self.buf.slice(2, 6 + len));
+
+            if checksum.bytes().as_slice() == self.buf.slice(6 + len, 8 + len) {
+                let payload = self.buf.slice(6, 6 + len).to_vec();
+                packets.push(Ok(Packet::new(self.buf[2], self.buf[3], payload)));
+                self.buf = self.buf.slice_from(8 + len).to_vec();
+            } else {
+                packets.push(Err(BadChecksum));
+                self.buf = self.buf.slice_from(2).to_vec();
+            }
+        }
+
+        packets
+    }
+
+    /// Discards any partially received packet
+    pub fn reset(&mut self) {
+        self.buf.clear();
+    }
+}
+
+/// A UBX speaking receiver connected to a port
+pub struct Device<S> {
+    port: S,
+    decoder: PacketDecoder,
+    pending: RingBuf<Result<Packet, FrameError>>,
+}
+
+impl<S: Reader + Writer> Device<S> {
+    /// Talks UBX through `port`
+    pub fn new(port: S) -> Device<S> {
+        Device {
+            port: port,
+            decoder: PacketDecoder::new(),
+            pending: RingBuf::new(),
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.port
+    }
+
+    /// Unwraps the port
+    pub fn into_inner(self) -> S {
+        self.port
+    }
+
+    /// Receives the next packet
+    ///
+    /// Corrupted packets are reported as `InvalidInput` errors, port errors (like time outs) are
+    /// passed through
+    pub fn receive(&mut self) -> IoResult<Packet> {
+        loop {
+            match self.pending.pop_front() {
+                None => {},
+                Some(Err(e)) => return Err(IoError {
+                    kind: InvalidInput,
+                    desc: "Corrupted UBX packet",
+                    detail: Some(e.to_string()),
+                }),
+                Some(Ok(packet)) => return Ok(packet),
+            }
+
+            let mut chunk = [0u8, ..256];
+            let n = try!(self.port.read(&mut chunk));
+            self.pending.extend(self.decoder.feed(chunk.slice_to(n)).into_iter());
+        }
+    }
+
+    /// Sends a packet
+    pub fn send(&mut self, packet: &Packet) -> IoResult<()> {
+        self.port.write(packet.encode().as_slice())
+    }
+
+    /// Sends a configuration packet, and waits for it to be acknowledged
+    ///
+    /// A rejection is reported as an `InvalidInput` error. Use a timeout on the port to bound
+    /// the wait, packets received in the meantime are discarded.
+    pub fn send_config(&mut self, packet: &Packet) -> IoResult<()> {
+        try!(self.send(packet));
+
+        loop {
+            match Message::parse(&try!(self.receive())) {
+                AckMessage(class, id) if (class, id) == (packet.class, packet.id) => {
+                    return Ok(())
+                },
+                NakMessage(class, id) if (class, id) == (packet.class, packet.id) => {
+                    return Err(IoError {
+                        kind: InvalidInput,
+                        desc: "Configuration rejected by the receiver",
+                        detail: None,
+                    })
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+/// Reads a little endian unsigned integer
+fn le(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as u64)
+}
+
+/// Appends the `len` least significant bytes of `value` in little endian order
+fn push_le(buf: &mut Vec<u8>, value: u64, len: uint) {
+    for i in range(0, len) {
+        buf.push((value >> (8 * i)) as u8);
+    }
+}
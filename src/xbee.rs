@@ -0,0 +1,453 @@
+//! API mode of the Digi XBee radios
+//!
+//! In API mode (`ATAP1`), every exchange with the radio is a frame: a `0x7E` start delimiter,
+//! the big endian length of the frame data, the frame data (an API identifier followed by its
+//! fields) and a checksum. With `ATAP2` the bytes that follow the start delimiter are also
+//! escaped, so that a start delimiter always starts a frame.
+
+use std::collections::RingBuf;
+use std::io::{InvalidInput, IoError, IoResult, OtherIoError};
+
+use framing::{BadChecksum, Decoder, Encoder, FrameError, FrameResult, Framed, Oversized};
+use framing::Truncated;
+
+const START: u8 = 0x7E;
+const ESCAPE: u8 = 0x7D;
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+/// Largest frame data accepted by the decoder, well above the payloads of the radios
+const MAX_FRAME_LEN: uint = 1024;
+
+// API identifiers
+const AT_COMMAND: u8 = 0x08;
+const TRANSMIT_REQUEST: u8 = 0x10;
+const AT_RESPONSE: u8 = 0x88;
+const MODEM_STATUS: u8 = 0x8A;
+const TRANSMIT_STATUS: u8 = 0x8B;
+const RECEIVE_PACKET: u8 = 0x90;
+
+/// 64-bit address that reaches all the radios of the network
+pub const BROADCAST: u64 = 0xFFFF;
+/// 16-bit network address to use when it's unknown
+pub const UNKNOWN_NETWORK_ADDRESS: u16 = 0xFFFE;
+
+/// Frame codec of the API mode, see `framing::Framed`
+///
+/// The codec deals with the frame data, the start delimiter, length and checksum are added by
+/// the encoder and checked by the decoder.
+pub struct ApiCodec {
+    escaped: bool,
+    /// Whether a start delimiter was received
+    started: bool,
+    /// Length and frame data received so far, unescaped
+    buf: Vec<u8>,
+    /// Whether the previous byte was `ESCAPE`
+    escape_next: bool,
+}
+
+impl ApiCodec {
+    /// Creates a codec for the `ATAP1` mode
+    pub fn new() -> ApiCodec {
+        ApiCodec {
+            escaped: false,
+            started: false,
+            buf: Vec::new(),
+            escape_next: false,
+        }
+    }
+
+    /// Creates a codec for the `ATAP2` mode, with escaped bytes
+    pub fn escaped() -> ApiCodec {
+        ApiCodec { escaped: true, ..ApiCodec::new() }
+    }
+}
+
+impl Decoder for ApiCodec {
+    fn feed(&mut self, data: &[u8]) -> Vec<FrameResult> {
+        let mut frames = Vec::new();
+
+        for &byte in data.iter() {
+            if byte == START && (self.escaped || !self.started) {
+                // Only an escaped stream can tell that a frame was cut short
+                if self.started {
+                    frames.push(Err(Truncated));
+                }
+
+                self.reset();
+                self.started = true;
+                continue
+            } else if !self.started {
+                continue
+            }
+
+            let byte = if !self.escaped {
+                byte
+            } else if self.escape_next {
+                self.escape_next = false;
+                byte ^ 0x20
+            } else if byte == ESCAPE {
+                self.escape_next = true;
+                continue
+            } else {
+                byte
+            };
+
+            self.buf.push(byte);
+
+            if self.buf.len() < 2 {
+                continue
+            }
+
+            let len = (self.buf[0] as uint) << 8 | self.buf[1] as uint;
+            if len > MAX_FRAME_LEN {
+                frames.push(Err(Oversized(len)));
+                self.reset();
+            } else if self.buf.len() == len + 3 {
+                let sum = self.buf.slice_from(2).iter().fold(0u8, |sum, &byte| sum + byte);
+
+                frames.push(if sum == 0xFF {
+                    Ok(self.buf.slice(2, len + 2).to_vec())
+                } else {
+                    Err(BadChecksum)
+                });
+                self.reset();
+            }
+        }
+
+        frames
+    }
+
+    fn reset(&mut self) {
+        self.started = false;
+        self.buf.clear();
+        self.escape_next = false;
+    }
+}
+
+impl Encoder for ApiCodec {
+    fn encode(&mut self, frame: &[u8], out: &mut Vec<u8>) -> Result<(), FrameError> {
+        if frame.len() > 0xFFFF {
+            return Err(Oversized(frame.len()))
+        }
+
+        let mut body = vec![(frame.len() >> 8) as u8, frame.len() as u8];
+        body.push_all(frame);
+        body.push(0xFF - frame.iter().fold(0u8, |sum, &byte| sum + byte));
+
+        out.push(START);
+        for &byte in body.iter() {
+            match byte {
+                START | ESCAPE | XON | XOFF if self.escaped => {
+                    out.push_all(&[ESCAPE, byte ^ 0x20]);
+                },
+                _ => out.push(byte),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Request to transmit data to another radio
+#[deriving(Clone, PartialEq, Show)]
+pub struct TransmitRequest {
+    /// Matches the request with its `TransmitStatus`, 0 disables the status
+    pub frame_id: u8,
+    /// 64-bit address of the destination, or `BROADCAST`
+    pub destination: u64,
+    /// 16-bit network address of the destination, or `UNKNOWN_NETWORK_ADDRESS`
+    pub network_address: u16,
+    /// Maximum number of hops of a broadcast, 0 for the maximum of the network
+    pub radius: u8,
+    pub options: u8,
+    pub data: Vec<u8>,
+}
+
+impl TransmitRequest {
+    /// Creates a request to send `data` to `destination`, with the default options
+    pub fn new(destination: u64, data: &[u8]) -> TransmitRequest {
+        TransmitRequest {
+            frame_id: 0,
+            destination: destination,
+            network_address: UNKNOWN_NETWORK_ADDRESS,
+            radius: 0,
+            options: 0,
+            data: data.to_vec(),
+        }
+    }
+}
+
+/// Outcome of a `TransmitRequest`
+#[deriving(Clone, PartialEq, Show)]
+pub struct TransmitStatus {
+    pub frame_id: u8,
+    /// 16-bit network address the data was delivered to
+    pub network_address: u16,
+    pub retries: u8,
+    /// 0 on success
+    pub delivery_status: u8,
+    pub discovery_status: u8,
+}
+
+impl TransmitStatus {
+    /// Whether the data was delivered
+    pub fn is_delivered(&self) -> bool {
+        self.delivery_status == 0
+    }
+}
+
+/// Data received from another radio
+#[deriving(Clone, PartialEq, Show)]
+pub struct ReceivePacket {
+    /// 64-bit address of the source
+    pub source: u64,
+    /// 16-bit network address of the source
+    pub network_address: u16,
+    pub options: u8,
+    pub data: Vec<u8>,
+}
+
+/// An API frame
+#[deriving(Clone, PartialEq, Show)]
+pub enum Frame {
+    /// Frame id, command (like `NI`) and parameter, an empty parameter queries the setting
+    AtCommandFrame(u8, [u8, ..2], Vec<u8>),
+    /// Frame id, command, status (0 on success) and value
+    AtResponseFrame(u8, [u8, ..2], u8, Vec<u8>),
+    /// Status of the radio, like 2 when it joins a network
+    ModemStatusFrame(u8),
+    TransmitRequestFrame(TransmitRequest),
+    TransmitStatusFrame(TransmitStatus),
+    ReceivePacketFrame(ReceivePacket),
+    /// API identifier and fields of the other frames
+    UnknownFrame(u8, Vec<u8>),
+}
+
+impl Frame {
+    /// Encodes the frame data
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        match *self {
+            AtCommandFrame(frame_id, command, ref parameter) => {
+                bytes.push_all(&[AT_COMMAND, frame_id, command[0], command[1]]);
+                bytes.push_all(parameter.as_slice());
+            },
+            AtResponseFrame(frame_id, command, status, ref value) => {
+                bytes.push_all(&[AT_RESPONSE, frame_id, command[0], command[1], status]);
+                bytes.push_all(value.as_slice());
+            },
+            ModemStatusFrame(status) => bytes.push_all(&[MODEM_STATUS, status]),
+            TransmitRequestFrame(ref request) => {
+                bytes.push_all(&[TRANSMIT_REQUEST, request.frame_id]);
+                push_be(&mut bytes, request.destination, 8);
+                push_be(&mut bytes, request.network_address as u64, 2);
+                bytes.push_all(&[request.radius, request.options]);
+                bytes.push_all(request.data.as_slice());
+            },
+            TransmitStatusFrame(ref status) => {
+                bytes.push_all(&[TRANSMIT_STATUS, status.frame_id]);
+                push_be(&mut bytes, status.network_address as u64, 2);
+                bytes.push_all(&[status.retries, status.delivery_status, status.discovery_status]);
+            },
+            ReceivePacketFrame(ref packet) => {
+                bytes.push(RECEIVE_PACKET);
+                push_be(&mut bytes, packet.source, 8);
+                push_be(&mut bytes, packet.network_address as u64, 2);
+                bytes.push(packet.options);
+                bytes.push_all(packet.data.as_slice());
+            },
+            UnknownFrame(api_id, ref fields) => {
+                bytes.push(api_id);
+                bytes.push_all(fields.as_slice());
+            },
+        }
+
+        bytes
+    }
+
+    /// Parses frame data, `None` if it's too short for its API identifier
+    pub fn parse(bytes: &[u8]) -> Option<Frame> {
+        if bytes.is_empty() {
+            return None
+        }
+
+        let (api_id, fields) = (bytes[0], bytes.slice_from(1));
+
+        let min_len = match api_id {
+            AT_COMMAND => 3,
+            AT_RESPONSE => 4,
+            MODEM_STATUS => 1,
+            TRANSMIT_REQUEST => 13,
+            TRANSMIT_STATUS => 6,
+            RECEIVE_PACKET => 11,
+            _ => 0,
+        };
+
+        if fields.len() < min_len {
+            return None
+        }
+
+        Some(match api_id {
+            AT_COMMAND => {
+                AtCommandFrame(fields[0], [fields[1], fields[2]], fields.slice_from(3).to_vec())
+            },
+            AT_RESPONSE => {
+                let command = [fields[1], fields[2]];
+
+                AtResponseFrame(fields[0], command, fields[3], fields.slice_from(4).to_vec())
+            },
+            MODEM_STATUS => ModemStatusFrame(fields[0]),
+            TRANSMIT_REQUEST => TransmitRequestFrame(TransmitRequest {
+                frame_id: fields[0],
+                destination: be(fields.slice(1, 9)),
+                network_address: be(fields.slice(9, 11)) as u16,
+                radius: fields[11],
+                options: fields[12],
+                data: fields.slice_from(13).to_vec(),
+            }),
+            TRANSMIT_STATUS => TransmitStatusFrame(TransmitStatus {
+                frame_id: fields[0],
+                network_address: be(fields.slice(1, 3)) as u16,
+                retries: fields[3],
+                delivery_status: fields[4],
+                discovery_status: fields[5],
+            }),
+            RECEIVE_PACKET => ReceivePacketFrame(ReceivePacket {
+                source: be(fields.slice(0, 8)),
+                network_address: be(fields.slice(8, 10)) as u16,
+                options: fields[10],
+                data: fields.slice_from(11).to_vec(),
+            }),
+            _ => UnknownFrame(api_id, fields.to_vec()),
+        })
+    }
+}
+
+/// An XBee radio in API mode, connected to a port
+pub struct Radio<S> {
+    inner: Framed<S, ApiCodec>,
+    /// Frames received while waiting for a response
+    pending: RingBuf<Frame>,
+    frame_id: u8,
+}
+
+impl<S: Reader + Writer> Radio<S> {
+    /// Talks to the radio through `port`, `codec` must match the `ATAP` setting of the radio
+    pub fn new(port: S, codec: ApiCodec) -> Radio<S> {
+        Radio {
+            inner: Framed::new(port, codec),
+            pending: RingBuf::new(),
+            frame_id: 0,
+        }
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut S {
+        self.inner.get_mut()
+    }
+
+    /// Unwraps the port
+    pub fn into_inner(self) -> S {
+        self.inner.into_inner()
+    }
+
+    /// Executes the AT `command` (like `NI` or `ID`) on the radio, returns its value
+    ///
+    /// An empty `parameter` queries the setting. A command refused by the radio is reported as
+    /// an `OtherIoError` that carries its status.
+    pub fn at_command(&mut self, command: &str, parameter: &[u8]) -> IoResult<Vec<u8>> {
+        let command = command.as_bytes();
+        if command.len() != 2 {
+            return Err(IoError {
+                kind: InvalidInput,
+                desc: "AT commands are two characters long",
+                detail: None,
+            })
+        }
+
+        let frame_id = self.next_frame_id();
+        let command = [command[0], command[1]];
+        try!(self.send(&AtCommandFrame(frame_id, command, parameter.to_vec())));
+
+        loop {
+            match try!(self.read_frame()) {
+                AtResponseFrame(id, _, status, ref value) if id == frame_id => {
+                    return if status == 0 {
+                        Ok(value.clone())
+                    } else {
+                        Err(IoError {
+                            kind: OtherIoError,
+                            desc: "AT command failed",
+                            detail: Some(format!("status {}", status)),
+                        })
+                    }
+                },
+                frame => self.pending.push_back(frame),
+            }
+        }
+    }
+
+    /// Receives the next frame
+    ///
+    /// Malformed frames are reported as `InvalidInput` errors, and frames too short for their
+    /// API identifier are skipped. Use a timeout on the port to bound the wait.
+    pub fn receive(&mut self) -> IoResult<Frame> {
+        match self.pending.pop_front() {
+            None => self.read_frame(),
+            Some(frame) => Ok(frame),
+        }
+    }
+
+    /// Sends a frame
+    pub fn send(&mut self, frame: &Frame) -> IoResult<()> {
+        self.inner.write_frame(frame.encode().as_slice())
+    }
+
+    /// Sends `data` to the radio at the 64-bit `destination`, and waits for the outcome
+    pub fn transmit(&mut self, destination: u64, data: &[u8]) -> IoResult<TransmitStatus> {
+        let mut request = TransmitRequest::new(destination, data);
+        request.frame_id = self.next_frame_id();
+        let frame_id = request.frame_id;
+
+        try!(self.send(&TransmitRequestFrame(request)));
+
+        loop {
+            match try!(self.read_frame()) {
+                TransmitStatusFrame(ref status) if status.frame_id == frame_id => {
+                    return Ok(status.clone())
+                },
+                frame => self.pending.push_back(frame),
+            }
+        }
+    }
+
+    /// Returns the next frame id, skipping 0 which disables the responses
+    fn next_frame_id(&mut self) -> u8 {
+        self.frame_id = if self.frame_id == 0xFF { 1 } else { self.frame_id + 1 };
+        self.frame_id
+    }
+
+    /// Reads the next frame from the port
+    fn read_frame(&mut self) -> IoResult<Frame> {
+        loop {
+            match Frame::parse(try!(self.inner.read_frame()).as_slice()) {
+                None => {},
+                Some(frame) => return Ok(frame),
+            }
+        }
+    }
+}
+
+/// Decodes a big endian integer
+fn be(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |value, &byte| value << 8 | byte as u64)
+}
+
+/// Appends the `len` least significant bytes of `value`, big endian
+fn push_be(bytes: &mut Vec<u8>, value: u64, len: uint) {
+    for i in range(0, len).rev() {
+        bytes.push((value >> (8 * i)) as u8);
+    }
+}
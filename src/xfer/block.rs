@@ -0,0 +1,237 @@
+//! Numbered blocks, shared by XMODEM and YMODEM
+
+use std::cmp;
+use std::io::{IoError, IoResult, OtherIoError, TimedOut};
+use std::time::Duration;
+
+use checksum::{Checksum, Crc16};
+use SerialIo;
+use super::{CAN, cancel, cancelled, fill, purge, read_byte, too_many_retries};
+
+pub const SOH: u8 = 0x01;
+pub const STX: u8 = 0x02;
+pub const EOT: u8 = 0x04;
+pub const ACK: u8 = 0x06;
+pub const NAK: u8 = 0x15;
+pub const SUB: u8 = 0x1A;
+/// Sent by the receiver instead of NAK to request CRC checked blocks
+pub const CRC_REQUEST: u8 = b'C';
+
+/// Retry policy of a block transfer
+pub struct Link {
+    pub retries: uint,
+    pub timeout: Duration,
+}
+
+impl Link {
+    /// Waits for the receiver to request a transfer, returns whether it asked for CRCs
+    pub fn wait_start<S: SerialIo>(&self, port: &mut S) -> IoResult<bool> {
+        for _ in range(0, self.retries) {
+            match read_byte(port, self.timeout) {
+                Err(ref e) if e.kind == TimedOut => {},
+                Err(e) => return Err(e),
+                Ok(CRC_REQUEST) => return Ok(true),
+                Ok(NAK) => return Ok(false),
+                Ok(CAN) => return Err(cancelled()),
+                Ok(_) => {},
+            }
+        }
+
+        Err(too_many_retries())
+    }
+
+    /// Sends `packet` until the receiver acknowledges it
+    pub fn transmit<S: SerialIo>(&self, port: &mut S, packet: &[u8]) -> IoResult<()> {
+        let mut attempts = 1;
+        try!(port.write(packet));
+
+        loop {
+            let retry = match read_byte(port, self.timeout) {
+                Ok(ACK) => return Ok(()),
+                Ok(CAN) => return Err(cancelled()),
+                Ok(NAK) => true,
+                // Stray requests sent by the receiver before it got the first block
+                Ok(_) => false,
+                Err(ref e) if e.kind == TimedOut => true,
+                Err(e) => return Err(e),
+            };
+
+            if !retry {
+                continue
+            }
+
+            if attempts >= self.retries {
+                let _ = cancel(port);
+                return Err(too_many_retries())
+            }
+
+            attempts += 1;
+            try!(port.write(packet));
+        }
+    }
+
+    /// Sends `data` in blocks numbered from 1, followed by EOT, returns the number of bytes sent
+    ///
+    /// The last block is padded with SUB (0x1A).
+    pub fn send_data<S: SerialIo, R: Reader>(&self, port: &mut S, data: &mut R, crc: bool,
+                                            block_len: uint) -> IoResult<uint> {
+        let mut block = Vec::from_elem(block_len, SUB);
+        let mut number = 1u8;
+        let mut sent = 0;
+
+        loop {
+            let n = try!(fill(data, block.as_mut_slice()));
+            if n == 0 {
+                break
+            }
+
+            for byte in block.slice_from_mut(n).iter_mut() {
+                *byte = SUB;
+            }
+
+            // A short last block is sent as a 128 byte block, to save padding
+            let len = if n <= 128 { 128 } else { block_len };
+            let packet = encode_block(number, block.slice_to(len), crc);
+            try!(self.transmit(port, packet.as_slice()));
+
+            sent += n;
+            number += 1;
+
+            if n < block_len {
+                break
+            }
+        }
+
+        try!(self.transmit(port, &[EOT]));
+
+        Ok(sent)
+    }
+
+    /// Receives blocks numbered from 1 until EOT, and writes their data into `out`
+    ///
+    /// The transfer is started by sending `request`. If the `size` of the data is known, the
+    /// padding of the last block is dropped. If `confirm_eot` is set, the first EOT is NAKed,
+    /// to make sure that it isn't line noise. Returns the number of bytes written.
+    pub fn receive_data<S: SerialIo, W: Writer>(&self, port: &mut S, out: &mut W, crc: bool,
+                                               request: u8, size: Option<uint>,
+                                               confirm_eot: bool) -> IoResult<uint> {
+        let mut expected = 1u8;
+        let mut received = 0;
+        let mut errors = 0;
+        let mut eot = false;
+
+        try!(port.write(&[request]));
+
+        loop {
+            let header = match read_byte(port, self.timeout) {
+                Err(ref e) if e.kind == TimedOut => None,
+                Err(e) => return Err(e),
+                Ok(header) => Some(header),
+            };
+
+            let block = match header {
+                Some(EOT) if confirm_eot && !eot => {
+                    eot = true;
+                    try!(port.write(&[NAK]));
+                    continue
+                },
+                Some(EOT) => {
+                    try!(port.write(&[ACK]));
+                    return Ok(received)
+                },
+                Some(CAN) => return Err(cancelled()),
+                Some(SOH) => try!(read_block(port, 128, crc)),
+                Some(STX) => try!(read_block(port, 1024, crc)),
+                _ => None,
+            };
+
+            match block {
+                Some((number, ref data)) if number == expected => {
+                    let len = match size {
+                        None => data.len(),
+                        Some(size) => cmp::min(data.len(), size - received),
+                    };
+
+                    try!(out.write(data.slice_to(len)));
+                    try!(port.write(&[ACK]));
+
+                    received += len;
+                    expected += 1;
+                    errors = 0;
+                    eot = false;
+                    continue
+                },
+                // Our last ACK got lost, and the sender repeated the previous block
+                Some((number, _)) if number == expected - 1 => {
+                    try!(port.write(&[ACK]));
+                    continue
+                },
+                Some((number, _)) => {
+                    let _ = cancel(port);
+                    return Err(IoError {
+                        kind: OtherIoError,
+                        desc: "Received a block out of sequence",
+                        detail: Some(format!("expected {}, got {}", expected, number)),
+                    })
+                },
+                None => {},
+            }
+
+            errors += 1;
+            if errors >= self.retries {
+                let _ = cancel(port);
+                return Err(too_many_retries())
+            }
+
+            if header.is_some() {
+                try!(purge(port, Duration::milliseconds(100)));
+            }
+
+            let started = expected > 1;
+            try!(port.write(&[if started { NAK } else { request }]));
+        }
+    }
+}
+
+/// Builds the packet of block `number`, `data` must be either 128 or 1024 bytes long
+pub fn encode_block(number: u8, data: &[u8], crc: bool) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(data.len() + 5);
+
+    packet.push(if data.len() == 128 { SOH } else { STX });
+    packet.push(number);
+    packet.push(!number);
+    packet.push_all(data);
+    packet.push_all(check(data, crc).as_slice());
+
+    packet
+}
+
+/// Reads the rest of a block of `len` bytes, returns `None` if it's truncated or corrupted
+pub fn read_block<S: SerialIo>(port: &mut S, len: uint, crc: bool)
+                               -> IoResult<Option<(u8, Vec<u8>)>> {
+    let rest = match port.read_exact(2 + len + if crc { 2 } else { 1 }) {
+        Err(ref e) if e.kind == TimedOut => return Ok(None),
+        Err(e) => return Err(e),
+        Ok(rest) => rest,
+    };
+
+    let (number, complement) = (rest[0], rest[1]);
+    let data = rest.slice(2, 2 + len);
+
+    if number == !complement && check(data, crc).as_slice() == rest.slice_from(2 + len) {
+        Ok(Some((number, data.to_vec())))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Check bytes of `data`
+fn check(data: &[u8], crc: bool) -> Vec<u8> {
+    if crc {
+        let mut crc = Crc16::xmodem();
+        crc.update(data);
+        crc.bytes()
+    } else {
+        vec![data.iter().fold(0u8, |sum, &byte| sum + byte)]
+    }
+}
@@ -0,0 +1,495 @@
+//! Kermit, batch file transfers with printable packets
+//!
+//! This is a minimal implementation: single character block checks, no repeat count
+//! compression, no attribute packets and no padding. Control characters are prefixed, and
+//! 8th bit prefixing is used when requested by either side and accepted by the other, which
+//! makes transfers work over 7-bit links.
+
+use std::cmp;
+use std::io::{IoError, IoResult, OtherIoError};
+use std::time::Duration;
+
+use SerialIo;
+use super::{BatchFile, recoverable, too_many_retries};
+
+/// Start of packet
+const MARK: u8 = 0x01;
+const CR: u8 = b'\r';
+/// Longest packet, counted from the sequence number to the block check
+const MAX_LEN: uint = 94;
+
+// Packet types
+const ACK: u8 = b'Y';
+const BREAK: u8 = b'B';
+const DATA: u8 = b'D';
+const EOF: u8 = b'Z';
+const ERROR: u8 = b'E';
+const FILE_HEADER: u8 = b'F';
+const NAK: u8 = b'N';
+const SEND_INIT: u8 = b'S';
+
+/// Kermit transfer settings
+#[deriving(Clone, Show)]
+pub struct Kermit {
+    quote_8bit: bool,
+    retries: uint,
+    timeout: Duration,
+}
+
+impl Kermit {
+    /// No 8th bit prefixing unless requested by the other side, 10 retries and a 10 seconds
+    /// timeout
+    pub fn new() -> Kermit {
+        Kermit {
+            quote_8bit: false,
+            retries: 10,
+            timeout: Duration::seconds(10),
+        }
+    }
+
+    /// Requests 8th bit prefixing, needed when the link uses 7 data bits
+    pub fn quote_8bit(mut self, quote_8bit: bool) -> Kermit {
+        self.quote_8bit = quote_8bit;
+        self
+    }
+
+    /// Sets how many times a packet is retried before the transfer is cancelled
+    pub fn retries(mut self, retries: uint) -> Kermit {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets how long to wait for each packet
+    pub fn timeout(mut self, timeout: Duration) -> Kermit {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends the `files` as a single batch
+    pub fn send<S: SerialIo>(&self, port: &mut S, files: &[BatchFile]) -> IoResult<()> {
+        let ours = self.params();
+
+        let init = Packet { seq: 0, kind: SEND_INIT, data: ours.encode() };
+        let reply = try!(self.exchange(port, &init, CR));
+        let theirs = Params::parse(reply.data.as_slice());
+        let quoting = ours.quoting(&theirs);
+
+        // Room for the sequence number, the type and the block check
+        let room = if theirs.maxl < 10 { 7 } else { theirs.maxl - 3 };
+
+        let mut seq = 1;
+        for file in files.iter() {
+            let mut packets = vec![(FILE_HEADER, quoting.encode(file.name.as_bytes(), room).0)];
+
+            let mut data = file.data.as_slice();
+            while !data.is_empty() {
+                let (encoded, consumed) = quoting.encode(data, room);
+                packets.push((DATA, encoded));
+                data = data.slice_from(consumed);
+            }
+
+            packets.push((EOF, vec![]));
+
+            for (kind, data) in packets.into_iter() {
+                let packet = Packet { seq: seq, kind: kind, data: data };
+                try!(self.exchange(port, &packet, theirs.eol));
+                seq = (seq + 1) % 64;
+            }
+        }
+
+        let packet = Packet { seq: seq, kind: BREAK, data: vec![] };
+        self.exchange(port, &packet, theirs.eol).map(|_| ())
+    }
+
+    /// Receives a batch of files
+    pub fn receive<S: SerialIo>(&self, port: &mut S) -> IoResult<Vec<BatchFile>> {
+        let ours = self.params();
+        let mut theirs = Params::default();
+        let mut quoting = ours.quoting(&theirs);
+
+        let mut files = Vec::new();
+        let mut file: Option<BatchFile> = None;
+        let mut expected = 0u8;
+        let mut last_ack: Option<Vec<u8>> = None;
+        let mut errors = 0;
+
+        loop {
+            let packet = match try!(recoverable(read_packet(port, self.timeout))) {
+                Some(ref packet) if packet.seq == expected => packet.clone(),
+                // Our last ACK got lost, and the sender repeated the previous packet
+                Some(ref packet) if packet.seq == (expected + 63) % 64 && last_ack.is_some() => {
+                    try!(port.write(last_ack.as_ref().unwrap().as_slice()));
+                    continue
+                },
+                _ => {
+                    errors += 1;
+                    if errors >= self.retries {
+                        let _ = self.abort(port, expected, "Too many retries");
+                        return Err(too_many_retries())
+                    }
+
+                    let nak = Packet { seq: expected, kind: NAK, data: vec![] };
+                    try!(port.write(nak.encode(theirs.eol).as_slice()));
+                    continue
+                },
+            };
+
+            let reply = match packet.kind {
+                SEND_INIT => {
+                    theirs = Params::parse(packet.data.as_slice());
+                    quoting = ours.quoting(&theirs);
+                    ours.encode()
+                },
+                FILE_HEADER => {
+                    let name = quoting.decode(packet.data.as_slice());
+                    let name = String::from_utf8_lossy(name.as_slice()).into_string();
+
+                    file = Some(BatchFile { name: name, data: vec![] });
+                    vec![]
+                },
+                DATA => match file {
+                    None => {
+                        let _ = self.abort(port, expected, "Data outside of a file");
+                        return Err(protocol_error("Data outside of a file"))
+                    },
+                    Some(ref mut file) => {
+                        file.data.push_all(quoting.decode(packet.data.as_slice()).as_slice());
+                        vec![]
+                    },
+                },
+                EOF => {
+                    // "D" asks to discard the file
+                    let discard = packet.data.as_slice() == b"D".as_slice();
+
+                    match file.take() {
+                        Some(file) => if !discard { files.push(file) },
+                        None => {},
+                    }
+                    vec![]
+                },
+                BREAK => {
+                    let ack = Packet { seq: expected, kind: ACK, data: vec![] };
+                    try!(port.write(ack.encode(theirs.eol).as_slice()));
+                    return Ok(files)
+                },
+                ERROR => return Err(remote_error(packet.data.as_slice())),
+                _ => {
+                    let _ = self.abort(port, expected, "Unsupported packet type");
+                    return Err(protocol_error("Unsupported packet type"))
+                },
+            };
+
+            let ack = Packet { seq: expected, kind: ACK, data: reply }.encode(theirs.eol);
+            try!(port.write(ack.as_slice()));
+
+            last_ack = Some(ack);
+            expected = (expected + 1) % 64;
+            errors = 0;
+        }
+    }
+
+    /// Sends `packet` until the receiver acknowledges it, returns the acknowledgement
+    fn exchange<S: SerialIo>(&self, port: &mut S, packet: &Packet, eol: u8) -> IoResult<Packet> {
+        let encoded = packet.encode(eol);
+
+        for _ in range(0, self.retries) {
+            try!(port.write(encoded.as_slice()));
+
+            match try!(recoverable(read_packet(port, self.timeout))) {
+                Some(ref reply) if reply.kind == ACK && reply.seq == packet.seq => {
+                    return Ok(reply.clone())
+                },
+                // A NAK for the next packet implies the ACK of this one
+                Some(ref reply) if reply.kind == NAK && reply.seq == (packet.seq + 1) % 64 => {
+                    return Ok(Packet { seq: packet.seq, kind: ACK, data: vec![] })
+                },
+                Some(ref reply) if reply.kind == ERROR => {
+                    return Err(remote_error(reply.data.as_slice()))
+                },
+                _ => {},
+            }
+        }
+
+        let _ = self.abort(port, packet.seq, "Too many retries");
+        Err(too_many_retries())
+    }
+
+    /// Tells the other side why the transfer is aborted
+    fn abort<S: SerialIo>(&self, port: &mut S, seq: u8, message: &str) -> IoResult<()> {
+        let packet = Packet { seq: seq, kind: ERROR, data: message.as_bytes().to_vec() };
+
+        port.write(packet.encode(CR).as_slice())
+    }
+
+    fn params(&self) -> Params {
+        Params {
+            maxl: MAX_LEN,
+            time: cmp::min(self.timeout.num_seconds(), 94) as uint,
+            eol: CR,
+            qctl: b'#',
+            qbin: if self.quote_8bit { b'&' } else { b'Y' },
+        }
+    }
+}
+
+#[deriving(Clone)]
+struct Packet {
+    seq: u8,
+    kind: u8,
+    data: Vec<u8>,
+}
+
+impl Packet {
+    /// Serializes the packet, `data` must already be prefixed
+    fn encode(&self, eol: u8) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(self.data.len() + 6);
+
+        packet.push(MARK);
+        packet.push(tochar(self.data.len() as u8 + 3));
+        packet.push(tochar(self.seq));
+        packet.push(self.kind);
+        packet.push_all(self.data.as_slice());
+
+        let check = block_check(packet.slice_from(1));
+        packet.push(check);
+        packet.push(eol);
+
+        packet
+    }
+}
+
+/// Parameters exchanged in the Send-Init packet and its acknowledgement
+struct Params {
+    /// Longest packet that can be received
+    maxl: uint,
+    /// Timeout to use when waiting for a packet, in seconds
+    time: uint,
+    /// End of line to send after a packet
+    eol: u8,
+    /// Prefix of the control characters
+    qctl: u8,
+    /// Prefix of the bytes with the 8th bit set, 'Y' to accept, 'N' or space to refuse
+    qbin: u8,
+}
+
+impl Params {
+    /// Defaults of the protocol, for the fields that aren't sent
+    fn default() -> Params {
+        Params {
+            maxl: 80,
+            time: 5,
+            eol: CR,
+            qctl: b'#',
+            qbin: b' ',
+        }
+    }
+
+    fn parse(data: &[u8]) -> Params {
+        let mut params = Params::default();
+
+        for (i, &field) in data.iter().enumerate() {
+            match i {
+                0 => params.maxl = unchar(field) as uint,
+                1 => params.time = unchar(field) as uint,
+                4 => params.eol = unchar(field),
+                5 => params.qctl = field,
+                6 => params.qbin = field,
+                _ => {},
+            }
+        }
+
+        params
+    }
+
+    /// MAXL, TIME, NPAD, PADC, EOL, QCTL, QBIN, CHKT and REPT
+    fn encode(&self) -> Vec<u8> {
+        vec![
+            tochar(self.maxl as u8),
+            tochar(self.time as u8),
+            tochar(0),
+            ctl(0),
+            tochar(self.eol),
+            self.qctl,
+            self.qbin,
+            b'1',
+            b' ',
+        ]
+    }
+
+    /// Agrees on the prefixing, given the parameters of the other side
+    ///
+    /// Each side prefixes the control characters with its own QCTL.
+    fn quoting(&self, theirs: &Params) -> Quoting {
+        let is_prefix = |qbin: u8| (qbin > 32 && qbin < 63) || (qbin > 95 && qbin < 127);
+
+        let qbin = match (self.qbin, theirs.qbin) {
+            (ours, b'Y') if is_prefix(ours) => Some(ours),
+            (b'Y', theirs) if is_prefix(theirs) => Some(theirs),
+            (ours, theirs) if ours == theirs && is_prefix(ours) => Some(ours),
+            _ => None,
+        };
+
+        Quoting {
+            ours: self.qctl,
+            theirs: theirs.qctl,
+            qbin: qbin,
+        }
+    }
+}
+
+/// Prefixes in use after the Send-Init exchange
+struct Quoting {
+    /// QCTL used to encode
+    ours: u8,
+    /// QCTL used to decode
+    theirs: u8,
+    qbin: Option<u8>,
+}
+
+impl Quoting {
+    /// Prefixes as much of `data` as fits in `room` characters
+    ///
+    /// Returns the prefixed data, and the number of bytes of `data` it holds.
+    fn encode(&self, data: &[u8], room: uint) -> (Vec<u8>, uint) {
+        let mut encoded = Vec::with_capacity(room);
+        let mut consumed = 0;
+
+        for &byte in data.iter() {
+            let mut unit = Vec::with_capacity(3);
+            let mut byte = byte;
+
+            match self.qbin {
+                Some(qbin) if byte & 0x80 != 0 => {
+                    unit.push(qbin);
+                    byte &= 0x7F;
+                },
+                _ => {},
+            }
+
+            let low = byte & 0x7F;
+            if low < 32 || low == 127 {
+                unit.push(self.ours);
+                unit.push(ctl(byte));
+            } else if low == self.ours || Some(low) == self.qbin {
+                unit.push(self.ours);
+                unit.push(byte);
+            } else {
+                unit.push(byte);
+            }
+
+            if encoded.len() + unit.len() > room {
+                break
+            }
+
+            encoded.push_all(unit.as_slice());
+            consumed += 1;
+        }
+
+        (encoded, consumed)
+    }
+
+    /// Removes the prefixes from `data`, a dangling prefix is dropped
+    fn decode(&self, data: &[u8]) -> Vec<u8> {
+        let mut decoded = Vec::with_capacity(data.len());
+        let mut bytes = data.iter().map(|&byte| byte);
+
+        loop {
+            let mut byte = match bytes.next() {
+                None => return decoded,
+                Some(byte) => byte,
+            };
+
+            let mut high = 0;
+            if Some(byte) == self.qbin {
+                high = 0x80;
+                byte = match bytes.next() {
+                    None => return decoded,
+                    Some(byte) => byte,
+                };
+            }
+
+            if byte == self.theirs {
+                byte = match bytes.next() {
+                    None => return decoded,
+                    Some(byte) => byte,
+                };
+
+                // Only '?'...'_' stand for control characters, the rest are quoted prefixes
+                let low = byte & 0x7F;
+                if low > 62 && low < 96 {
+                    byte = ctl(byte);
+                }
+            }
+
+            decoded.push(byte | high);
+        }
+    }
+}
+
+/// Reads the next packet, skipping anything that precedes it
+///
+/// Returns `None` if the packet is garbled or fails its block check.
+fn read_packet<S: SerialIo>(port: &mut S, timeout: Duration) -> IoResult<Option<Packet>> {
+    port.set_timeout(Some(timeout));
+
+    while try!(port.read_byte()) != MARK {}
+
+    let len = try!(port.read_byte());
+    if len < tochar(3) || len > tochar(MAX_LEN as u8) {
+        return Ok(None)
+    }
+
+    let rest = try!(port.read_exact(unchar(len) as uint));
+    if rest.contains(&MARK) {
+        return Ok(None)
+    }
+
+    let mut checked = vec![len];
+    checked.push_all(rest.slice_to(rest.len() - 1));
+    if block_check(checked.as_slice()) != rest[rest.len() - 1] {
+        return Ok(None)
+    }
+
+    Ok(Some(Packet {
+        seq: unchar(rest[0]) % 64,
+        kind: rest[1],
+        data: rest.slice(2, rest.len() - 1).to_vec(),
+    }))
+}
+
+/// Single character block check (type 1) of the packet, from LEN to the end of the data
+fn block_check(packet: &[u8]) -> u8 {
+    let sum = packet.iter().fold(0u, |sum, &byte| sum + byte as uint);
+
+    tochar(((sum + ((sum & 0xC0) >> 6)) & 0x3F) as u8)
+}
+
+/// Makes a small number printable
+fn tochar(x: u8) -> u8 {
+    x + 32
+}
+
+fn unchar(c: u8) -> u8 {
+    c - 32
+}
+
+/// Toggles between a control character and its printable counterpart
+fn ctl(c: u8) -> u8 {
+    c ^ 64
+}
+
+fn protocol_error(desc: &'static str) -> IoError {
+    IoError {
+        kind: OtherIoError,
+        desc: desc,
+        detail: None,
+    }
+}
+
+fn remote_error(message: &[u8]) -> IoError {
+    IoError {
+        kind: OtherIoError,
+        desc: "Transfer aborted by the remote end",
+        detail: Some(String::from_utf8_lossy(message).into_string()),
+    }
+}
@@ -0,0 +1,131 @@
+//! File transfer protocols
+//!
+//! The transfers take over the timeout of the port, and leave it set to an unspecified value.
+
+use std::io::{EndOfFile, InvalidInput, IoError, IoResult, OtherIoError, TimedOut};
+use std::time::Duration;
+
+use SerialIo;
+
+pub mod kermit;
+pub mod xmodem;
+pub mod ymodem;
+pub mod zmodem;
+
+mod block;
+
+/// Cancels the transfer
+const CAN: u8 = 0x18;
+
+/// A file of a batch transfer
+#[deriving(Clone, PartialEq, Show)]
+pub struct BatchFile {
+    /// Name of the file, without directories
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl BatchFile {
+    /// Serializes the name and size of the file, as sent before the file data
+    ///
+    /// The format is `<name> NUL <decimal size> NUL`, shared by YMODEM and ZMODEM.
+    fn info(&self) -> IoResult<Vec<u8>> {
+        if self.name.is_empty() || self.name.as_bytes().contains(&0) {
+            return Err(IoError {
+                kind: InvalidInput,
+                desc: "Invalid file name",
+                detail: Some(self.name.clone()),
+            })
+        }
+
+        let mut info = self.name.as_bytes().to_vec();
+        info.push(0);
+        info.push_all(self.data.len().to_string().as_bytes());
+        info.push(0);
+
+        Ok(info)
+    }
+}
+
+/// Parses the name and size of a file, an empty name ends a batch
+///
+/// Returns `None` if the name isn't NUL terminated. The size is optional.
+fn parse_info(info: &[u8]) -> Option<(String, Option<uint>)> {
+    let end = match info.iter().position(|&byte| byte == 0) {
+        None => return None,
+        Some(end) => end,
+    };
+
+    let name = String::from_utf8_lossy(info.slice_to(end)).into_string();
+    let size = info.slice_from(end + 1).iter().take_while(|&&byte| byte >= b'0' && byte <= b'9');
+    let size: Vec<u8> = size.map(|&byte| byte).collect();
+    let size = String::from_utf8(size).ok().and_then(|size| from_str::<uint>(size.as_slice()));
+
+    Some((name, size))
+}
+
+/// Reads a byte, waiting up to `timeout` for it
+fn read_byte<S: SerialIo>(port: &mut S, timeout: Duration) -> IoResult<u8> {
+    port.set_timeout(Some(timeout));
+    port.read_byte()
+}
+
+/// Discards input until the line stays quiet for `timeout`
+fn purge<S: SerialIo>(port: &mut S, timeout: Duration) -> IoResult<()> {
+    port.set_timeout(Some(timeout));
+
+    let mut buf = [0u8, ..256];
+    loop {
+        match port.read(&mut buf) {
+            Err(ref e) if e.kind == TimedOut => return Ok(()),
+            Err(e) => return Err(e),
+            Ok(_) => {},
+        }
+    }
+}
+
+/// Treats a time out as a garbled packet, so it's retried
+fn recoverable<T>(result: IoResult<Option<T>>) -> IoResult<Option<T>> {
+    match result {
+        Err(ref e) if e.kind == TimedOut => Ok(None),
+        result => result,
+    }
+}
+
+/// Asks the remote end to abort the transfer
+///
+/// XMODEM and YMODEM need two CANs, ZMODEM needs five, eight are sent.
+fn cancel<S: SerialIo>(port: &mut S) -> IoResult<()> {
+    port.write(&[CAN, ..8])
+}
+
+/// Reads from `data` until `buf` is full or the end of file is reached, returns the bytes read
+fn fill<R: Reader>(data: &mut R, buf: &mut [u8]) -> IoResult<uint> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match data.read(buf.slice_from_mut(filled)) {
+            Err(ref e) if e.kind == EndOfFile => break,
+            Err(e) => return Err(e),
+            Ok(n) => filled += n,
+        }
+    }
+
+    Ok(filled)
+}
+
+fn cancelled() -> IoError {
+    IoError {
+        kind: OtherIoError,
+        desc: "Transfer cancelled by the remote end",
+        detail: None,
+    }
+}
+
+fn too_many_retries() -> IoError {
+    IoError {
+        kind: OtherIoError,
+        desc: "Transfer aborted after too many retries",
+        detail: None,
+    }
+}
@@ -0,0 +1,100 @@
+//! XMODEM, with 128 byte or 1K blocks, checked with an arithmetic sum or a CRC
+//!
+//! The receiver can't tell the padding of the last block (SUB, 0x1A) apart from the data, so
+//! the received data is always a multiple of 128 bytes.
+
+use std::io::IoResult;
+use std::time::Duration;
+
+use SerialIo;
+use super::block::{CRC_REQUEST, Link, NAK};
+
+/// How the receiver checks the blocks, the sender follows the choice of the receiver
+#[deriving(Clone, PartialEq, Show)]
+pub enum BlockCheck {
+    /// 16-bit CRC (XMODEM-CRC)
+    CrcCheck,
+    /// 8-bit arithmetic sum of the data, as in the original XMODEM
+    SumCheck,
+}
+
+/// Size of the blocks sent
+#[deriving(Clone, PartialEq, Show)]
+pub enum BlockSize {
+    Block128,
+    /// 1024 byte blocks (XMODEM-1K), only used if the receiver asks for CRC checked blocks
+    Block1K,
+}
+
+/// XMODEM transfer settings
+#[deriving(Clone, Show)]
+pub struct Xmodem {
+    check: BlockCheck,
+    block_size: BlockSize,
+    retries: uint,
+    timeout: Duration,
+}
+
+impl Xmodem {
+    /// CRC checked 128 byte blocks, 10 retries and a 10 seconds timeout
+    pub fn new() -> Xmodem {
+        Xmodem {
+            check: CrcCheck,
+            block_size: Block128,
+            retries: 10,
+            timeout: Duration::seconds(10),
+        }
+    }
+
+    /// Sets the check requested when receiving
+    pub fn check(mut self, check: BlockCheck) -> Xmodem {
+        self.check = check;
+        self
+    }
+
+    /// Sets the size of the blocks used when sending
+    pub fn block_size(mut self, block_size: BlockSize) -> Xmodem {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Sets how many times a block is retried before the transfer is cancelled
+    pub fn retries(mut self, retries: uint) -> Xmodem {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets how long to wait for each response or block
+    pub fn timeout(mut self, timeout: Duration) -> Xmodem {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends the contents of `data` through `port`, returns the number of bytes sent
+    ///
+    /// The last block is padded with SUB (0x1A).
+    pub fn send<S: SerialIo, R: Reader>(&self, port: &mut S, data: &mut R) -> IoResult<uint> {
+        let link = self.link();
+
+        let crc = try!(link.wait_start(port));
+        let block_len = if crc && self.block_size == Block1K { 1024 } else { 128 };
+
+        link.send_data(port, data, crc, block_len)
+    }
+
+    /// Receives data through `port` and writes it into `out`, returns the number of bytes
+    /// received
+    pub fn receive<S: SerialIo, W: Writer>(&self, port: &mut S, out: &mut W) -> IoResult<uint> {
+        let crc = self.check == CrcCheck;
+        let request = if crc { CRC_REQUEST } else { NAK };
+
+        self.link().receive_data(port, out, crc, request, None, false)
+    }
+
+    fn link(&self) -> Link {
+        Link {
+            retries: self.retries,
+            timeout: self.timeout,
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! YMODEM, batch transfers of named files over CRC checked 1K blocks
+//!
+//! Each file is announced by block 0, which carries its name and size, so the receiver drops
+//! the padding of the last block. A block 0 with an empty name ends the batch.
+
+use std::io::{BufReader, InvalidInput, IoError, IoResult, MemWriter, TimedOut};
+use std::time::Duration;
+
+use SerialIo;
+use super::block::{ACK, CRC_REQUEST, Link, SOH, STX, encode_block, read_block};
+use super::{BatchFile, CAN, cancel, cancelled, parse_info, purge, read_byte, too_many_retries};
+
+/// YMODEM transfer settings
+#[deriving(Clone, Show)]
+pub struct Ymodem {
+    retries: uint,
+    timeout: Duration,
+}
+
+impl Ymodem {
+    /// 10 retries and a 10 seconds timeout
+    pub fn new() -> Ymodem {
+        Ymodem {
+            retries: 10,
+            timeout: Duration::seconds(10),
+        }
+    }
+
+    /// Sets how many times a block is retried before the transfer is cancelled
+    pub fn retries(mut self, retries: uint) -> Ymodem {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets how long to wait for each response or block
+    pub fn timeout(mut self, timeout: Duration) -> Ymodem {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends the `files` as a single batch
+    pub fn send<S: SerialIo>(&self, port: &mut S, files: &[BatchFile]) -> IoResult<()> {
+        let link = self.link();
+
+        for file in files.iter() {
+            let header = try!(encode_header(try!(file.info()).as_slice()));
+
+            let crc = try!(link.wait_start(port));
+            try!(link.transmit(port, encode_block(0, header.as_slice(), crc).as_slice()));
+
+            let crc = try!(link.wait_start(port));
+            let block_len = if crc { 1024 } else { 128 };
+            let mut data = BufReader::new(file.data.as_slice());
+            try!(link.send_data(port, &mut data, crc, block_len));
+        }
+
+        let crc = try!(link.wait_start(port));
+        link.transmit(port, encode_block(0, [0u8, ..128].as_slice(), crc).as_slice())
+    }
+
+    /// Receives a batch of files
+    pub fn receive<S: SerialIo>(&self, port: &mut S) -> IoResult<Vec<BatchFile>> {
+        let link = self.link();
+        let mut files = Vec::new();
+
+        loop {
+            let header = try!(self.receive_header(port));
+
+            let (name, size) = match parse_info(header.as_slice()) {
+                None => {
+                    let _ = cancel(port);
+                    return Err(IoError {
+                        kind: InvalidInput,
+                        desc: "Malformed YMODEM header",
+                        detail: None,
+                    })
+                },
+                Some(info) => info,
+            };
+
+            try!(port.write(&[ACK]));
+
+            if name.is_empty() {
+                return Ok(files)
+            }
+
+            let mut data = MemWriter::new();
+            try!(link.receive_data(port, &mut data, true, CRC_REQUEST, size, true));
+
+            files.push(BatchFile { name: name, data: data.unwrap() });
+        }
+    }
+
+    /// Requests block 0 until it arrives
+    fn receive_header<S: SerialIo>(&self, port: &mut S) -> IoResult<Vec<u8>> {
+        for _ in range(0, self.retries) {
+            try!(port.write(&[CRC_REQUEST]));
+
+            let block = match read_byte(port, self.timeout) {
+                Err(ref e) if e.kind == TimedOut => None,
+                Err(e) => return Err(e),
+                Ok(CAN) => return Err(cancelled()),
+                Ok(SOH) => try!(read_block(port, 128, true)),
+                Ok(STX) => try!(read_block(port, 1024, true)),
+                Ok(_) => {
+                    try!(purge(port, Duration::milliseconds(100)));
+                    None
+                },
+            };
+
+            match block {
+                Some((0, header)) => return Ok(header),
+                _ => {},
+            }
+        }
+
+        let _ = cancel(port);
+        Err(too_many_retries())
+    }
+
+    fn link(&self) -> Link {
+        Link {
+            retries: self.retries,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// Pads the file `info` with NULs to a block
+fn encode_header(info: &[u8]) -> IoResult<Vec<u8>> {
+    let len = match info.len() {
+        0...128 => 128,
+        129...1024 => 1024,
+        _ => return Err(IoError {
+            kind: InvalidInput,
+            desc: "File name too long",
+            detail: None,
+        }),
+    };
+
+    let mut header = info.to_vec();
+    header.grow(len - info.len(), 0);
+
+    Ok(header)
+}
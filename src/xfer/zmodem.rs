@@ -0,0 +1,604 @@
+//! ZMODEM, streaming batch transfers that resume from the last good position after errors
+//!
+//! The subset needed to exchange files with `sz`/`rz` is implemented: no compression, no
+//! encryption and no remote commands. Binary headers and data subpackets use a 32-bit CRC when
+//! the receiver supports it.
+
+use std::cmp;
+use std::io::{IoResult, TimedOut};
+use std::time::Duration;
+
+use checksum::{Checksum, Crc16, Crc32};
+use hex;
+use SerialIo;
+use super::{BatchFile, cancel, cancelled, parse_info, read_byte, recoverable, too_many_retries};
+
+const ZPAD: u8 = b'*';
+/// ZMODEM data link escape, the same byte as CAN
+const ZDLE: u8 = 0x18;
+const ZBIN: u8 = b'A';
+const ZHEX: u8 = b'B';
+const ZBIN32: u8 = b'C';
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+// Frame types
+const ZRQINIT: u8 = 0;
+const ZRINIT: u8 = 1;
+const ZSINIT: u8 = 2;
+const ZACK: u8 = 3;
+const ZFILE: u8 = 4;
+const ZSKIP: u8 = 5;
+const ZNAK: u8 = 6;
+const ZFIN: u8 = 8;
+const ZRPOS: u8 = 9;
+const ZDATA: u8 = 10;
+const ZEOF: u8 = 11;
+
+// Subpacket terminators
+const ZCRCE: u8 = b'h';
+const ZCRCG: u8 = b'i';
+const ZCRCQ: u8 = b'j';
+const ZCRCW: u8 = b'k';
+const ZRUB0: u8 = b'l';
+const ZRUB1: u8 = b'm';
+
+// ZRINIT capabilities
+const CANFDX: u8 = 0x01;
+const CANOVIO: u8 = 0x02;
+const CANFC32: u8 = 0x20;
+
+/// Binary transfer, the conversion option of ZFILE
+const ZCBIN: u8 = 1;
+
+/// Size of the data subpackets sent
+const SUBPACKET_LEN: uint = 1024;
+/// Largest data subpacket accepted
+const MAX_SUBPACKET_LEN: uint = 8192;
+
+const HEX_DIGITS: &'static [u8] = b"0123456789abcdef";
+
+/// ZMODEM transfer settings
+#[deriving(Clone, Show)]
+pub struct Zmodem {
+    retries: uint,
+    timeout: Duration,
+}
+
+impl Zmodem {
+    /// 10 retries and a 10 seconds timeout
+    pub fn new() -> Zmodem {
+        Zmodem {
+            retries: 10,
+            timeout: Duration::seconds(10),
+        }
+    }
+
+    /// Sets how many consecutive errors are tolerated before the transfer is cancelled
+    pub fn retries(mut self, retries: uint) -> Zmodem {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets how long to wait for each header or subpacket
+    pub fn timeout(mut self, timeout: Duration) -> Zmodem {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends the `files` as a single batch
+    ///
+    /// Files skipped by the receiver (`ZSKIP`) aren't reported as errors.
+    pub fn send<S: SerialIo>(&self, port: &mut S, files: &[BatchFile]) -> IoResult<()> {
+        let mut flags = None;
+        for _ in range(0, self.retries) {
+            try!(port.write(hex_header(&Header::new(ZRQINIT, 0)).as_slice()));
+
+            match try!(recoverable(read_header(port, self.timeout, false))) {
+                Some((ref header, _)) if header.kind == ZRINIT => {
+                    flags = Some(header.data[3]);
+                    break
+                },
+                _ => {},
+            }
+        }
+
+        let crc32 = match flags {
+            None => {
+                let _ = cancel(port);
+                return Err(too_many_retries())
+            },
+            Some(flags) => flags & CANFC32 != 0,
+        };
+
+        for file in files.iter() {
+            let info = try!(file.info());
+            let mut zfile = Header::new(ZFILE, 0);
+            zfile.data[3] = ZCBIN;
+
+            let mut start = None;
+            let mut resend = true;
+            for _ in range(0, self.retries) {
+                if resend {
+                    try!(port.write(binary_header(&zfile, crc32).as_slice()));
+                    try!(port.write(subpacket(info.as_slice(), ZCRCW, crc32).as_slice()));
+                }
+
+                resend = true;
+                match try!(recoverable(read_header(port, self.timeout, false))) {
+                    Some((ref header, _)) if header.kind == ZRPOS => {
+                        start = Some(cmp::min(header.position(), file.data.len()));
+                        break
+                    },
+                    Some((ref header, _)) if header.kind == ZSKIP => break,
+                    // Sent before the ZFILE arrived
+                    Some((ref header, _)) if header.kind == ZRINIT => resend = false,
+                    _ => {},
+                }
+            }
+
+            match start {
+                None => {},
+                Some(start) => try!(self.send_file(port, file.data.as_slice(), start, crc32)),
+            }
+        }
+
+        for _ in range(0, self.retries) {
+            try!(port.write(hex_header(&Header::new(ZFIN, 0)).as_slice()));
+
+            match try!(recoverable(read_header(port, self.timeout, false))) {
+                Some((ref header, _)) if header.kind == ZFIN => return port.write(b"OO"),
+                _ => {},
+            }
+        }
+
+        Err(too_many_retries())
+    }
+
+    /// Receives a batch of files
+    pub fn receive<S: SerialIo>(&self, port: &mut S) -> IoResult<Vec<BatchFile>> {
+        let mut zrinit = Header::new(ZRINIT, 0);
+        zrinit.data[3] = CANFDX | CANOVIO | CANFC32;
+        let zrinit = hex_header(&zrinit);
+
+        let mut files = Vec::new();
+        let mut errors = 0;
+
+        try!(port.write(zrinit.as_slice()));
+
+        loop {
+            match try!(recoverable(read_header(port, self.timeout, false))) {
+                Some((ref header, _)) if header.kind == ZRQINIT => {
+                    try!(port.write(zrinit.as_slice()));
+                    continue
+                },
+                Some((ref header, crc32)) if header.kind == ZSINIT => {
+                    match try!(recoverable(read_subpacket(port, crc32))) {
+                        None => {},
+                        Some(_) => {
+                            try!(port.write(hex_header(&Header::new(ZACK, 0)).as_slice()));
+                            continue
+                        },
+                    }
+                },
+                Some((ref header, crc32)) if header.kind == ZFILE => {
+                    let info = try!(recoverable(read_subpacket(port, crc32)));
+
+                    match info.and_then(|(info, _)| parse_info(info.as_slice())) {
+                        None => try!(port.write(hex_header(&Header::new(ZNAK, 0)).as_slice())),
+                        Some((name, size)) => {
+                            let data = try!(self.receive_file(port, size));
+                            files.push(BatchFile { name: name, data: data });
+
+                            errors = 0;
+                            try!(port.write(zrinit.as_slice()));
+                            continue
+                        },
+                    }
+                },
+                Some((ref header, _)) if header.kind == ZFIN => {
+                    try!(port.write(hex_header(&Header::new(ZFIN, 0)).as_slice()));
+
+                    // The sender ends the session with "OO", which can be lost harmlessly
+                    port.set_timeout(Some(Duration::milliseconds(100)));
+                    let _ = port.read_exact(2);
+
+                    return Ok(files)
+                },
+                _ => {},
+            }
+
+            errors += 1;
+            if errors >= self.retries {
+                let _ = cancel(port);
+                return Err(too_many_retries())
+            }
+
+            try!(port.write(zrinit.as_slice()));
+        }
+    }
+
+    /// Streams `data` from `pos`, and repositions whenever the receiver asks to
+    fn send_file<S: SerialIo>(&self, port: &mut S, data: &[u8], mut pos: uint, crc32: bool)
+                              -> IoResult<()> {
+        let mut errors = 0;
+
+        loop {
+            let repositioned = if pos < data.len() {
+                try!(self.stream(port, data, pos, crc32))
+            } else {
+                None
+            };
+
+            match repositioned {
+                Some(new_pos) => pos = new_pos,
+                None => {
+                    let zeof = binary_header(&Header::new(ZEOF, data.len()), crc32);
+                    try!(port.write(zeof.as_slice()));
+
+                    match try!(recoverable(read_header(port, self.timeout, false))) {
+                        Some((ref header, _)) if header.kind == ZRINIT => return Ok(()),
+                        Some((ref header, _)) if header.kind == ZSKIP => return Ok(()),
+                        Some((ref header, _)) if header.kind == ZRPOS => {
+                            pos = cmp::min(header.position(), data.len());
+                        },
+                        _ => pos = data.len(),
+                    }
+                },
+            }
+
+            errors += 1;
+            if errors >= self.retries {
+                let _ = cancel(port);
+                return Err(too_many_retries())
+            }
+        }
+    }
+
+    /// Sends `data` from `pos` as a single ZDATA frame
+    ///
+    /// Returns the position requested by the receiver if it interrupted the frame with a ZRPOS.
+    fn stream<S: SerialIo>(&self, port: &mut S, data: &[u8], mut pos: uint, crc32: bool)
+                           -> IoResult<Option<uint>> {
+        try!(port.write(binary_header(&Header::new(ZDATA, pos), crc32).as_slice()));
+
+        while pos < data.len() {
+            let end = cmp::min(pos + SUBPACKET_LEN, data.len());
+            let terminator = if end == data.len() { ZCRCE } else { ZCRCG };
+            try!(port.write(subpacket(data.slice(pos, end), terminator, crc32).as_slice()));
+            pos = end;
+
+            // Look for a header from the receiver, without stalling the stream
+            let pad = match read_byte(port, Duration::zero()) {
+                Err(ref e) if e.kind == TimedOut => continue,
+                Err(e) => return Err(e),
+                Ok(ZPAD) => true,
+                Ok(ZDLE) => false,
+                Ok(_) => continue,
+            };
+
+            match try!(recoverable(read_header(port, self.timeout, pad))) {
+                Some((ref header, _)) if header.kind == ZRPOS => {
+                    return Ok(Some(cmp::min(header.position(), data.len())))
+                },
+                _ => {},
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Receives the data of a file, starting from position 0
+    fn receive_file<S: SerialIo>(&self, port: &mut S, size: Option<uint>) -> IoResult<Vec<u8>> {
+        let mut data = Vec::with_capacity(size.unwrap_or(0));
+        let mut errors = 0;
+
+        try!(port.write(hex_header(&Header::new(ZRPOS, 0)).as_slice()));
+
+        loop {
+            match try!(recoverable(read_header(port, self.timeout, false))) {
+                Some((ref header, crc32)) if header.kind == ZDATA => {
+                    if header.position() == data.len() {
+                        if try!(self.receive_frame(port, &mut data, crc32)) {
+                            errors = 0;
+                            continue
+                        }
+                    }
+                },
+                Some((ref header, _)) if header.kind == ZEOF => {
+                    if header.position() == data.len() {
+                        return Ok(data)
+                    }
+                },
+                // The sender missed our ZRPOS, and repeated the file information
+                Some((ref header, crc32)) if header.kind == ZFILE => {
+                    try!(recoverable(read_subpacket(port, crc32)));
+                },
+                _ => {},
+            }
+
+            errors += 1;
+            if errors >= self.retries {
+                let _ = cancel(port);
+                return Err(too_many_retries())
+            }
+
+            try!(port.write(hex_header(&Header::new(ZRPOS, data.len())).as_slice()));
+        }
+    }
+
+    /// Appends the subpackets of a ZDATA frame to `data`
+    ///
+    /// Returns `false` if the frame was interrupted by an error, and needs to be resent from
+    /// the current position.
+    fn receive_frame<S: SerialIo>(&self, port: &mut S, data: &mut Vec<u8>, crc32: bool)
+                                  -> IoResult<bool> {
+        port.set_timeout(Some(self.timeout));
+
+        loop {
+            let (chunk, terminator) = match try!(recoverable(read_subpacket(port, crc32))) {
+                None => return Ok(false),
+                Some(subpacket) => subpacket,
+            };
+
+            data.push_all(chunk.as_slice());
+
+            match terminator {
+                ZCRCG => {},
+                ZCRCQ => try!(port.write(hex_header(&Header::new(ZACK, data.len())).as_slice())),
+                ZCRCW => {
+                    try!(port.write(hex_header(&Header::new(ZACK, data.len())).as_slice()));
+                    return Ok(true)
+                },
+                _ => return Ok(true),
+            }
+        }
+    }
+}
+
+/// A frame header: the frame type and 4 bytes of either flags or a position
+struct Header {
+    kind: u8,
+    data: [u8, ..4],
+}
+
+impl Header {
+    /// Header of type `kind`, carrying the `position` (0 for the headers that carry flags)
+    fn new(kind: u8, position: uint) -> Header {
+        let position = position as u32;
+
+        Header {
+            kind: kind,
+            data: [position as u8, (position >> 8) as u8, (position >> 16) as u8,
+                   (position >> 24) as u8],
+        }
+    }
+
+    fn position(&self) -> uint {
+        self.data.iter().rev().fold(0u, |position, &byte| position << 8 | byte as uint)
+    }
+
+    fn bytes(&self) -> [u8, ..5] {
+        [self.kind, self.data[0], self.data[1], self.data[2], self.data[3]]
+    }
+}
+
+/// Unescaped input
+enum Escaped {
+    Literal(u8),
+    /// End of a data subpacket
+    Terminator(u8),
+    /// An invalid escape sequence
+    Garbled,
+}
+
+/// Encodes `header` in hexadecimal, as used by the receiver and by the session headers
+fn hex_header(header: &Header) -> Vec<u8> {
+    let mut bytes = header.bytes().to_vec();
+    bytes.push_all(check(&[header.bytes().as_slice()], false).as_slice());
+
+    let mut frame = vec![ZPAD, ZPAD, ZDLE, ZHEX];
+    for &byte in bytes.iter() {
+        frame.push(HEX_DIGITS[(byte >> 4) as uint]);
+        frame.push(HEX_DIGITS[(byte & 0x0F) as uint]);
+    }
+    frame.push_all(b"\r\x8a");
+
+    if header.kind != ZACK && header.kind != ZFIN {
+        frame.push(XON);
+    }
+
+    frame
+}
+
+/// Encodes `header` in binary, checked with a 16 or 32 bit CRC
+fn binary_header(header: &Header, crc32: bool) -> Vec<u8> {
+    let mut frame = vec![ZPAD, ZDLE, if crc32 { ZBIN32 } else { ZBIN }];
+
+    escape(header.bytes().as_slice(), &mut frame);
+    escape(check(&[header.bytes().as_slice()], crc32).as_slice(), &mut frame);
+
+    frame
+}
+
+/// Encodes a data subpacket, ended by `terminator`
+fn subpacket(data: &[u8], terminator: u8, crc32: bool) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(data.len() + 16);
+
+    escape(data, &mut packet);
+    packet.push(ZDLE);
+    packet.push(terminator);
+    let terminator = [terminator];
+    escape(check(&[data, terminator.as_slice()], crc32).as_slice(), &mut packet);
+
+    if terminator[0] == ZCRCW {
+        packet.push(XON);
+    }
+
+    packet
+}
+
+/// Escapes ZDLE and the flow control characters
+fn escape(data: &[u8], out: &mut Vec<u8>) {
+    for &byte in data.iter() {
+        match byte {
+            ZDLE | 0x10 | 0x90 | XON | 0x91 | XOFF | 0x93 => {
+                out.push(ZDLE);
+                out.push(byte ^ 0x40);
+            },
+            _ => out.push(byte),
+        }
+    }
+}
+
+/// CRC of the concatenated `parts`, in transmission order
+fn check(parts: &[&[u8]], crc32: bool) -> Vec<u8> {
+    if crc32 {
+        let mut crc = Crc32::ieee();
+        for part in parts.iter() {
+            crc.update(*part);
+        }
+        crc.bytes()
+    } else {
+        let mut crc = Crc16::xmodem();
+        for part in parts.iter() {
+            crc.update(*part);
+        }
+        crc.bytes()
+    }
+}
+
+/// Reads the next header, skipping anything that precedes it
+///
+/// `pad` tells whether a ZPAD has already been read. Returns `None` if the header is garbled.
+fn read_header<S: SerialIo>(port: &mut S, timeout: Duration, pad: bool)
+                            -> IoResult<Option<(Header, bool)>> {
+    port.set_timeout(Some(timeout));
+
+    let mut previous_pad = pad;
+    let mut cans = 0u;
+    loop {
+        let byte = try!(port.read_byte());
+
+        match byte {
+            ZDLE if previous_pad => break,
+            ZDLE => {
+                cans += 1;
+                if cans >= 5 {
+                    return Err(cancelled())
+                }
+            },
+            _ => cans = 0,
+        }
+
+        previous_pad = byte == ZPAD;
+    }
+
+    let format = try!(port.read_byte());
+    let len = match format {
+        ZHEX | ZBIN => 7,
+        ZBIN32 => 9,
+        _ => return Ok(None),
+    };
+
+    let mut bytes = Vec::with_capacity(len);
+    if format == ZHEX {
+        let digits = try!(port.read_exact(2 * len));
+        for pair in digits.as_slice().chunks(2) {
+            match (hex::value(pair[0]), hex::value(pair[1])) {
+                (Some(high), Some(low)) => bytes.push(high << 4 | low),
+                _ => return Ok(None),
+            }
+        }
+
+        // CR LF, possibly with the high bit set
+        if try!(port.read_byte()) & 0x7F == b'\r' {
+            try!(port.read_byte());
+        }
+    } else {
+        while bytes.len() < len {
+            match try!(read_escaped(port)) {
+                Literal(byte) => bytes.push(byte),
+                _ => return Ok(None),
+            }
+        }
+    }
+
+    let crc32 = format == ZBIN32;
+    if check(&[bytes.slice_to(5)], crc32).as_slice() != bytes.slice_from(5) {
+        return Ok(None)
+    }
+
+    let header = Header {
+        kind: bytes[0],
+        data: [bytes[1], bytes[2], bytes[3], bytes[4]],
+    };
+
+    Ok(Some((header, crc32)))
+}
+
+/// Reads a data subpacket, returns `None` if it's garbled or corrupted
+fn read_subpacket<S: SerialIo>(port: &mut S, crc32: bool) -> IoResult<Option<(Vec<u8>, u8)>> {
+    let mut data = Vec::new();
+
+    loop {
+        match try!(read_escaped(port)) {
+            Garbled => return Ok(None),
+            Literal(byte) => {
+                if data.len() == MAX_SUBPACKET_LEN {
+                    return Ok(None)
+                }
+
+                data.push(byte);
+            },
+            Terminator(terminator) => {
+                let mut crc = Vec::new();
+                while crc.len() < if crc32 { 4 } else { 2 } {
+                    match try!(read_escaped(port)) {
+                        Literal(byte) => crc.push(byte),
+                        _ => return Ok(None),
+                    }
+                }
+
+                let expected = check(&[data.as_slice(), [terminator].as_slice()], crc32);
+                return Ok(if crc == expected { Some((data, terminator)) } else { None })
+            },
+        }
+    }
+}
+
+/// Reads and unescapes a byte, the flow control characters are skipped
+fn read_escaped<S: SerialIo>(port: &mut S) -> IoResult<Escaped> {
+    let mut escaped = false;
+    let mut cans = 0u;
+
+    loop {
+        let byte = try!(port.read_byte());
+
+        match byte {
+            XON | XOFF | 0x91 | 0x93 => continue,
+            ZDLE => {
+                cans += 1;
+                if cans >= 5 {
+                    return Err(cancelled())
+                }
+
+                escaped = true;
+                continue
+            },
+            _ => {},
+        }
+
+        if !escaped {
+            return Ok(Literal(byte))
+        }
+
+        return Ok(match byte {
+            ZCRCE | ZCRCG | ZCRCQ | ZCRCW => Terminator(byte),
+            ZRUB0 => Literal(0x7F),
+            ZRUB1 => Literal(0xFF),
+            _ if byte & 0x60 == 0x40 => Literal(byte ^ 0x40),
+            _ => Garbled,
+        })
+    }
+}